@@ -23,6 +23,7 @@ import androidx.lifecycle.MutableLiveData;
 
 import java.util.UUID;
 
+import lora.FragmentAssembler;
 import lora.Protocol;
 
 public class BleManager {
@@ -43,6 +44,10 @@ public class BleManager {
     private final MutableLiveData<Protocol.Message> messageReceived = new MutableLiveData<>();
     private final MutableLiveData<String> showToast = new MutableLiveData<>();
     private final MutableLiveData<Boolean> connected = new MutableLiveData<>();
+    private final FragmentAssembler fragmentAssembler = new FragmentAssembler();
+    // Remaining writes of a multi-write message (or messages), written one per onCharacteristicWrite
+    private final java.util.ArrayDeque<byte[]> pendingWrites = new java.util.ArrayDeque<>();
+    private int mtu = 23; // Default ATT MTU until negotiated
     private BluetoothAdapter bluetoothAdapter;
     private BluetoothLeScanner bluetoothLeScanner;
     private BluetoothGatt bluetoothGatt;
@@ -274,6 +279,9 @@ public class BleManager {
                     connectionStatus.postValue("❌ Disconnected");
 
                     // Clean up GATT connection and characteristics
+                    fragmentAssembler.reset();
+                    pendingWrites.clear();
+                    BleManager.this.mtu = 23;
                     txCharacteristic = null;
                     rxCharacteristic = null;
 
@@ -304,6 +312,7 @@ public class BleManager {
                 Log.d(TAG, "MTU changed: mtu=" + mtu + ", status=" + status);
                 if (status == BluetoothGatt.GATT_SUCCESS) {
                     Log.d(TAG, "MTU negotiated successfully: " + mtu + " bytes");
+                    BleManager.this.mtu = mtu;
                     connectionStatus.postValue("🔧 Discovering services...");
                 } else {
                     Log.e(TAG, "MTU negotiation failed with status: " + status);
@@ -376,15 +385,40 @@ public class BleManager {
                     byte[] data = characteristic.getValue();
                     Log.d(TAG, "Received notification: " + data.length + " bytes");
                     try {
-                        Protocol.Message msg = Protocol.Message.deserialize(data);
-                        Log.d(TAG, "Deserialized message: " + msg);
-                        messageReceived.postValue(msg);
+                        // Messages larger than the MTU arrive as fragments
+                        data = fragmentAssembler.accept(data);
+                        if (data == null) {
+                            return;
+                        }
+                        // Buffered messages may arrive coalesced into one batch
+                        for (byte[] message : FragmentAssembler.unbatch(data)) {
+                            Protocol.Message msg = Protocol.Message.deserialize(message);
+                            Log.d(TAG, "Deserialized message: " + msg);
+                            // postValue() would keep only the last message of a batch
+                            mainHandler.post(() -> messageReceived.setValue(msg));
+                        }
                     } catch (Exception e) {
                         Log.e(TAG, "Failed to deserialize message: " + e.getMessage());
                     }
                 }
             }
 
+            @Override
+            public void onCharacteristicWrite(BluetoothGatt gatt, BluetoothGattCharacteristic characteristic,
+                                              int status) {
+                if (!characteristic.getUuid().equals(RX_CHAR_UUID) || pendingWrites.isEmpty()) {
+                    return;
+                }
+                if (status != BluetoothGatt.GATT_SUCCESS) {
+                    // The ESP32 discards the partial message once its composition times out
+                    Log.e(TAG, "Fragment write failed with status: " + status);
+                    pendingWrites.clear();
+                    return;
+                }
+                characteristic.setValue(pendingWrites.poll());
+                gatt.writeCharacteristic(characteristic);
+            }
+
             @Override
             public void onDescriptorWrite(BluetoothGatt gatt, android.bluetooth.BluetoothGattDescriptor descriptor,
                                           int status) {
@@ -406,29 +440,44 @@ public class BleManager {
         });
     }
 
-    @SuppressLint("MissingPermission")
     public boolean sendMessage(Protocol.Message message) {
         if (message == null) {
             Log.e(TAG, "Cannot send null message");
             return false;
         }
+        return sendMessages(java.util.Collections.singletonList(message));
+    }
 
+    /**
+     * Send messages back to back, e.g. the fragments of a long text, one write after the other
+     */
+    @SuppressLint("MissingPermission")
+    public boolean sendMessages(java.util.List<? extends Protocol.Message> messages) {
         if (bluetoothGatt == null || rxCharacteristic == null) {
             Log.e(TAG, "Cannot send message: BLE not connected");
             showToast.postValue("Error: Not connected to device");
             return false;
         }
 
-        byte[] data = message.serialize();
-        if (data == null || data.length == 0) {
-            Log.e(TAG, "Cannot send empty message");
-            showToast.postValue("Error: Empty message");
+        // Messages larger than one write are sent as fragments the ESP32 composes again
+        java.util.List<byte[]> writes = new java.util.ArrayList<>();
+        for (Protocol.Message message : messages) {
+            byte[] data = message.serialize();
+            if (data == null || data.length == 0) {
+                Log.e(TAG, "Cannot send empty message");
+                showToast.postValue("Error: Empty message");
+                return false;
+            }
+            Log.d(TAG, "Sending message: " + data.length + " bytes");
+            writes.addAll(FragmentAssembler.split(data, mtu - 3));
+        }
+        if (writes.isEmpty()) {
             return false;
         }
+        pendingWrites.clear();
+        pendingWrites.addAll(writes.subList(1, writes.size()));
 
-        Log.d(TAG, "Sending message: " + data.length + " bytes");
-
-        rxCharacteristic.setValue(data);
+        rxCharacteristic.setValue(writes.get(0));
         boolean success = bluetoothGatt.writeCharacteristic(rxCharacteristic);
         Log.d(TAG, "Write characteristic result: " + success);
         return success;
@@ -241,17 +241,20 @@ public class MainActivity extends AppCompatActivity {
         if (text == null)
             text = "";
         int charCount = text.length();
-        int packedBytes = Protocol.calculatePackedSize(text);
-        int totalMessageSize = 12 + packedBytes; // 12 byte header + packed text
+        // Texts over one frame (50 codes, shift codes of a mixed-case charset included) go as fragments
+        int frames = Protocol.isTextSupported(text)
+                ? new Protocol.TextMessage((byte) 0, text).fragments(Protocol.MAX_SERIALIZED_LENGTH).size()
+                : 1;
 
-        String countText = charCount + "/" + Protocol.MAX_TEXT_LENGTH + " chars (" + totalMessageSize + " bytes)";
+        String countText = charCount + "/" + Protocol.MAX_FRAGMENTED_TEXT_LENGTH + " chars (" + frames
+                + (frames == 1 ? " frame)" : " frames)");
         binding.charCountTextView.setText(countText);
 
         // Change color if approaching limit
-        if (charCount >= Protocol.MAX_TEXT_LENGTH) {
+        if (charCount >= Protocol.MAX_FRAGMENTED_TEXT_LENGTH) {
             binding.charCountTextView.setTextColor(
                     androidx.core.content.ContextCompat.getColor(this, R.color.char_count_exceeded));
-        } else if (charCount >= Protocol.MAX_TEXT_LENGTH * CHAR_COUNT_WARNING_THRESHOLD) {
+        } else if (charCount >= Protocol.MAX_FRAGMENTED_TEXT_LENGTH * CHAR_COUNT_WARNING_THRESHOLD) {
             binding.charCountTextView.setTextColor(
                     androidx.core.content.ContextCompat.getColor(this, R.color.char_count_warning));
         } else {
@@ -44,7 +44,8 @@ public class MessageAdapter extends RecyclerView.Adapter<MessageAdapter.MessageV
         ChatMessage message = messages.get(position);
 
         holder.messageText.setText(message.text);
-        holder.messageTime.setText(timeFormat.format(new Date(message.timestamp)));
+        String time = timeFormat.format(new Date(message.timestamp));
+        holder.messageTime.setText(message.linkQuality != null ? time + " · " + message.linkQuality : time);
 
         // Make message clickable if it has GPS coordinates
         if (message.hasGps) {
@@ -80,6 +81,18 @@ public class MessageAdapter extends RecyclerView.Adapter<MessageAdapter.MessageV
                     holder.ackStatusIcon.setText("✓"); // Checkmark for delivered
                     holder.ackStatusIcon.setTextColor(androidx.core.content.ContextCompat.getColor(context, R.color.ack_delivered));
                     break;
+                case RELAYED:
+                    holder.ackStatusIcon.setText("↪"); // Arrow for taken on by a relay
+                    holder.ackStatusIcon.setTextColor(androidx.core.content.ContextCompat.getColor(context, R.color.ack_delivered));
+                    break;
+                case SENT:
+                    holder.ackStatusIcon.setText("→"); // Arrow for sent, never confirmed
+                    holder.ackStatusIcon.setTextColor(androidx.core.content.ContextCompat.getColor(context, R.color.ack_pending));
+                    break;
+                case FAILED:
+                    holder.ackStatusIcon.setText("✗"); // Cross for not delivered
+                    holder.ackStatusIcon.setTextColor(androidx.core.content.ContextCompat.getColor(context, R.color.ack_failed));
+                    break;
                 default:
                     holder.ackStatusIcon.setVisibility(View.GONE);
                     break;
@@ -130,6 +143,27 @@ public class MessageAdapter extends RecyclerView.Adapter<MessageAdapter.MessageV
         }
     }
 
+    /**
+     * Show the signal a received message arrived with (bridge built with LINK_QUALITY_REPORTS)
+     */
+    public void updateLinkQuality(byte seq, int rssi, float snrDb) {
+        // Ensure we're on main thread
+        if (android.os.Looper.myLooper() != android.os.Looper.getMainLooper()) {
+            mainHandler.post(() -> updateLinkQuality(seq, rssi, snrDb));
+            return;
+        }
+
+        // The newest received message with that seq: seqs wrap, and the report follows its text
+        for (int i = messages.size() - 1; i >= 0; i--) {
+            ChatMessage msg = messages.get(i);
+            if (!msg.isSent && msg.seq == seq) {
+                msg.linkQuality = String.format(Locale.US, "%d dBm %.1f dB", rssi, snrDb);
+                notifyItemChanged(i);
+                break;
+            }
+        }
+    }
+
     public void clear() {
         messages.clear();
         notifyDataSetChanged();
@@ -159,7 +193,10 @@ public class MessageAdapter extends RecyclerView.Adapter<MessageAdapter.MessageV
     public enum AckStatus {
         NONE, // Not applicable (received messages)
         PENDING, // Sent, waiting for ACK
-        DELIVERED // ACK received
+        DELIVERED, // ACK received
+        RELAYED, // Hop ACK from the first relay (bridge built with ACK_SEMANTICS_HOP)
+        SENT, // Sent without awaiting an ACK (bridge built with FIRE_AND_FORGET)
+        FAILED // Bridge gave up: no ACK after its last retransmission
     }
 
     public interface ScrollCallback {
@@ -175,6 +212,7 @@ public class MessageAdapter extends RecyclerView.Adapter<MessageAdapter.MessageV
         public final double latitude;
         public final double longitude;
         public AckStatus ackStatus;
+        public String linkQuality; // RSSI and SNR of a received message, null if not reported
 
         public ChatMessage(String text, boolean isSent, byte seq) {
             this(text, isSent, seq, false, 0.0, 0.0);
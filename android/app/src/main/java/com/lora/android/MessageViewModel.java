@@ -11,6 +11,7 @@ import androidx.lifecycle.ViewModel;
 import java.util.Locale;
 
 import lora.Protocol;
+import lora.TextReassembler;
 
 public class MessageViewModel extends ViewModel {
 
@@ -36,6 +37,13 @@ public class MessageViewModel extends ViewModel {
     private byte seqCounter = 0;
     private boolean observersRegistered = false;
     private byte pendingAckSeq = -1;
+    // Received texts that arrive as several fragments
+    private final TextReassembler textReassembler = new TextReassembler();
+    // Seq of the last text frame received and of the message it completed, for its link quality report
+    private byte lastTextFrameSeq = -1;
+    private byte lastTextMessageSeq = -1;
+    // Seq (0-255) of the last SOS shown, so its repeats aren't listed again; -1 before the first
+    private int lastSosSeq = -1;
     // Observers for BLE manager
     private final Observer<Protocol.Message> messageReceivedObserver = this::handleReceivedMessage;
 
@@ -92,11 +100,18 @@ public class MessageViewModel extends ViewModel {
     }
 
     public void sendMessage(String text) {
-        Log.d(TAG, "Send message - text: " + text);
+        sendMessage(text, Protocol.Priority.NORMAL);
+    }
+
+    /**
+     * Send a text with a delivery priority (see Protocol.Priority)
+     */
+    public void sendMessage(String text, Protocol.Priority priority) {
+        Log.d(TAG, "Send message - text: " + text + ", priority: " + priority);
 
-        // Enforce maximum text length
-        if (text.length() > Protocol.MAX_TEXT_LENGTH) {
-            text = text.substring(0, Protocol.MAX_TEXT_LENGTH);
+        // Enforce maximum text length; longer than one frame is sent as fragments
+        if (text.length() > Protocol.MAX_FRAGMENTED_TEXT_LENGTH) {
+            text = text.substring(0, Protocol.MAX_FRAGMENTED_TEXT_LENGTH);
         }
 
         // Validate characters
@@ -116,7 +131,7 @@ public class MessageViewModel extends ViewModel {
             final String finalText = text;
             handler.postDelayed(() -> {
                 if (canSendMessage()) {
-                    sendMessageInternal(finalText);
+                    sendMessageInternal(finalText, priority);
                 } else {
                     showToast.postValue("Failed to connect - please try again");
                 }
@@ -130,43 +145,62 @@ public class MessageViewModel extends ViewModel {
             return;
         }
 
-        sendMessageInternal(text);
+        sendMessageInternal(text, priority);
     }
 
-    private void sendMessageInternal(String text) {
+    private void sendMessageInternal(String text, Protocol.Priority priority) {
         // Update GPS
         updateGps();
         Location location = gpsManager.getLastKnownLocation();
 
         try {
             // Send unified text message with optional GPS
-            final byte textSeq = seqCounter++;
-            pendingAckSeq = textSeq;
+            final byte firstSeq = seqCounter;
             Protocol.TextMessage textMsg;
 
             if (location != null) {
                 final int lat = (int) (location.getLatitude() * 1_000_000);
                 final int lon = (int) (location.getLongitude() * 1_000_000);
-                textMsg = new Protocol.TextMessage(textSeq, text, lat, lon);
+                // Altitude and speed only when the fix has them, clamped to their wire ranges
+                int gpsFields = 0;
+                int altitude = 0;
+                int speed = 0;
+                if (location.hasAltitude()) {
+                    gpsFields |= Protocol.TextMessage.GPS_FIELD_ALTITUDE;
+                    altitude = (int) Math.max(Short.MIN_VALUE, Math.min(Short.MAX_VALUE, Math.round(location.getAltitude())));
+                }
+                if (location.hasSpeed()) {
+                    gpsFields |= Protocol.TextMessage.GPS_FIELD_SPEED;
+                    speed = (int) Math.min(0xFFFF, Math.round(location.getSpeed() * 100.0));
+                }
+                textMsg = new Protocol.TextMessage(firstSeq, text, lat, lon, priority, gpsFields, altitude, speed);
+            } else {
+                textMsg = new Protocol.TextMessage(firstSeq, text, priority);
+            }
+
+            // One seq per fragment; the text counts as delivered with its last fragment
+            final java.util.List<Protocol.TextMessage> fragments = textMsg.fragments(Protocol.MAX_SERIALIZED_LENGTH);
+            seqCounter += fragments.size();
+            final byte textSeq = fragments.get(fragments.size() - 1).seq;
+            pendingAckSeq = textSeq;
+            if (location != null) {
                 messageAdapter.addMessage(text, true, textSeq, true,
                         location.getLatitude(), location.getLongitude());
             } else {
-                textMsg = new Protocol.TextMessage(textSeq, text);
                 messageAdapter.addMessage(text, true, textSeq);
             }
 
             // Disable send button until ACK or timeout
             canSendNewMessage.postValue(false);
 
-            boolean success = bleManager.sendMessage(textMsg);
+            boolean success = bleManager.sendMessages(fragments);
             if (!success) {
                 Log.e(TAG, "Failed to send message - will retry");
                 showToast.postValue("Send failed - retrying...");
                 // Retry after brief delay using Handler
-                final Protocol.TextMessage retryMsg = textMsg;
                 handler.postDelayed(() -> {
                     if (bleManager.isConnected()) {
-                        bleManager.sendMessage(retryMsg);
+                        bleManager.sendMessages(fragments);
                     }
                 }, 1000);
             }
@@ -206,8 +240,15 @@ public class MessageViewModel extends ViewModel {
     }
 
     private void handleReceivedMessage(Protocol.Message message) {
-        if (message instanceof Protocol.TextMessage textMsg) {
+        if (message instanceof Protocol.TextMessage fragment) {
+            Protocol.TextMessage textMsg = textReassembler.accept(fragment);
+            if (textMsg == null) {
+                Log.d(TAG, "Text fragment received: " + fragment);
+                return;
+            }
             Log.d(TAG, "Text message received: " + textMsg.text);
+            lastTextFrameSeq = fragment.seq;
+            lastTextMessageSeq = textMsg.seq;
             // Display text without GPS coordinates, but store GPS data for Maps click
             if (textMsg.hasGps) {
                 double lat = textMsg.lat / 1_000_000.0;
@@ -226,6 +267,87 @@ public class MessageViewModel extends ViewModel {
                 canSendNewMessage.postValue(true);
                 pendingAckSeq = -1;
             }
+        } else if (message instanceof Protocol.DeliveryReportMessage report) {
+            // The bridge tracks delivery itself and only forwards raw ACKs with FORWARD_ACKS
+            Log.d(TAG, "Delivery report: " + report);
+            if (report.outcome == Protocol.DeliveryReportMessage.DELIVERED) {
+                messageAdapter.updateAckStatus(report.seq, MessageAdapter.AckStatus.DELIVERED);
+                showToast.postValue("✓ Message delivered (seq " + (report.seq & 0xFF) + ")");
+            } else if (report.outcome == Protocol.DeliveryReportMessage.RELAYED) {
+                // Hop ACK semantics: a relay took the message on, the destination never confirms it
+                messageAdapter.updateAckStatus(report.seq, MessageAdapter.AckStatus.RELAYED);
+                showToast.postValue("↪ Message relayed (seq " + (report.seq & 0xFF) + ")");
+            } else if (report.outcome == Protocol.DeliveryReportMessage.SENT) {
+                // Fire-and-forget bridge: nothing will confirm the message
+                messageAdapter.updateAckStatus(report.seq, MessageAdapter.AckStatus.SENT);
+            } else {
+                messageAdapter.updateAckStatus(report.seq, MessageAdapter.AckStatus.FAILED);
+                showToast.postValue("✗ Message not delivered (seq " + (report.seq & 0xFF) + ")");
+            }
+
+            if (pendingAckSeq == report.seq) {
+                canSendNewMessage.postValue(true);
+                pendingAckSeq = -1;
+            }
+        } else if (message instanceof Protocol.LinkQualityMessage quality) {
+            // Follows its text; a fragmented text gets the quality of the fragment that completed it
+            Log.d(TAG, "Link quality: " + quality);
+            if (quality.seq == lastTextFrameSeq) {
+                messageAdapter.updateLinkQuality(lastTextMessageSeq, quality.rssi, quality.getSnrDb());
+            }
+        } else if (message instanceof Protocol.BatteryMessage battery) {
+            Log.d(TAG, "Battery level received: " + battery);
+            String info = String.format(java.util.Locale.US, "BATTERY %d%% (%.2f V)", battery.percent,
+                    battery.millivolts / 1000.0);
+            messageAdapter.addMessage(info, false, (byte) 0);
+        } else if (message instanceof Protocol.SosMessage sos) {
+            // The bridge forwards every repeat of an SOS; show the first one and toast each
+            Log.w(TAG, "SOS received: " + sos);
+            if ((sos.seq & 0xFF) != lastSosSeq) {
+                lastSosSeq = sos.seq & 0xFF;
+                double lat = sos.lat / 1_000_000.0;
+                double lon = sos.lon / 1_000_000.0;
+                String info = String.format(java.util.Locale.US, "SOS %.6f, %.6f", lat, lon);
+                messageAdapter.addMessage(info, false, sos.seq, true, lat, lon);
+            }
+            showToast.postValue("⚠ SOS received (seq " + (sos.seq & 0xFF) + ")");
+        } else if (message instanceof Protocol.IdentifyResponseMessage nodeMsg) {
+            Log.d(TAG, "Identify response received: " + nodeMsg);
+            String info = String.format(java.util.Locale.US, "NODE %d: %.2f MHz SF%d BW%.2f kHz CR4/%d %d dBm",
+                    nodeMsg.nodeId, nodeMsg.frequency / 1e6, nodeMsg.spreadingFactor,
+                    nodeMsg.getBandwidthHz() / 1e3, nodeMsg.codingRate, nodeMsg.txPower);
+            messageAdapter.addMessage(info, false, (byte) 0);
+        } else if (message instanceof Protocol.ConfigResponseMessage configMsg) {
+            // Remote diagnostics: the peer's full configuration, to check both ends of a link agree
+            Log.d(TAG, "Config response received: " + configMsg);
+            String info = String.format(java.util.Locale.US,
+                    "CONFIG %d: %.2f MHz SF%d BW%.2f kHz CR4/%d %d dBm sync 0x%02X preamble %d fw %d.%d.%d profile %d charset %d",
+                    configMsg.nodeId, configMsg.frequency / 1e6, configMsg.spreadingFactor,
+                    configMsg.getBandwidthHz() / 1e3, configMsg.codingRate, configMsg.txPower, configMsg.syncWord,
+                    configMsg.preambleLength, configMsg.firmwareMajor, configMsg.firmwareMinor,
+                    configMsg.firmwarePatch, configMsg.protocolProfile, configMsg.charset);
+            messageAdapter.addMessage(info, false, (byte) 0);
+        } else if (message instanceof Protocol.RawMessage rawMsg) {
+            // Promiscuous mode: foreign or corrupted frame, show it as hex
+            Log.d(TAG, "Raw frame received: " + rawMsg);
+            String label = "RAW " + rawMsg.frameLength + "B" + (rawMsg.isTruncated() ? " (truncated)" : "") + ": ";
+            messageAdapter.addMessage(label + rawMsg.toHex(), false, (byte) 0);
+        } else if (message instanceof Protocol.DecodeFailureMessage failure) {
+            // Debug firmware: a frame the bridge couldn't decode, with the reason
+            Log.w(TAG, "Bridge decode failure: " + failure);
+            String label = "DECODE FAILED (" + failure.reasonName() + ") " + failure.frameLength + "B: ";
+            messageAdapter.addMessage(label + failure.toHex(), false, (byte) 0);
+        } else if (message instanceof Protocol.StatusMessage statusMsg) {
+            // Half-duplex radio: our message waits while the bridge receives a frame,
+            // or until the band's duty cycle allows more airtime
+            Log.d(TAG, "Bridge status: " + statusMsg);
+            if (statusMsg.code == Protocol.StatusMessage.RADIO_BUSY) {
+                showToast.postValue("Radio busy receiving - message queued (seq " + (statusMsg.seq & 0xFF) + ")");
+            } else if (statusMsg.code == Protocol.StatusMessage.DUTY_CYCLE_LIMITED) {
+                showToast.postValue("Duty-cycle limit reached - message delayed (seq " + (statusMsg.seq & 0xFF) + ")");
+            } else {
+                showToast.postValue("Message sent (seq " + (statusMsg.seq & 0xFF) + ")");
+            }
         }
     }
 
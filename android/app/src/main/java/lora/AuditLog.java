@@ -0,0 +1,122 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+import java.util.ArrayList;
+import java.util.Collections;
+import java.util.List;
+
+/**
+ * One page of the ESP32 audit log, read via the control characteristic (0x567A).
+ * Request: write [0x04] for the oldest entries, or [0x04][first entry u32].
+ * Response: [0x84][total u32][first index u32][count u8][8-byte entries x count].
+ * The log is RAM-only on the ESP32 and starts empty after every reboot.
+ * See protocol.md for details.
+ */
+public class AuditLog {
+
+    public static final byte OPCODE = 0x04;
+    public static final byte RESPONSE = (byte) 0x84;
+    private static final int HEADER_LENGTH = 10;
+    private static final int ENTRY_LENGTH = 8;
+
+    public static final int DIRECTION_RX = 0;
+    public static final int DIRECTION_TX = 1;
+
+    /** Outcome codes, see protocol.md */
+    public static final String[] OUTCOMES = {
+            "SENT", "RETRANSMITTED", "ACKED", "ACK_TIMEOUT", "TX_FAILED", "RECEIVED", "ACK_WITHHELD", "DROPPED"};
+
+    public final long total; // Entries recorded since boot
+    public final long firstIndex; // Number of the first entry in this page
+    public final List<Entry> entries;
+
+    private AuditLog(long total, long firstIndex, List<Entry> entries) {
+        this.total = total;
+        this.firstIndex = firstIndex;
+        this.entries = Collections.unmodifiableList(entries);
+    }
+
+    public static class Entry {
+        public final long timestampMs; // ESP32 millis() when it happened
+        public final int direction; // DIRECTION_RX or DIRECTION_TX
+        public final int type; // Message type byte
+        public final int seq;
+        public final int outcome; // Index into OUTCOMES
+
+        Entry(long timestampMs, int direction, int type, int seq, int outcome) {
+            this.timestampMs = timestampMs;
+            this.direction = direction;
+            this.type = type;
+            this.seq = seq;
+            this.outcome = outcome;
+        }
+
+        public String outcomeName() {
+            return outcome < OUTCOMES.length ? OUTCOMES[outcome] : "UNKNOWN(" + outcome + ")";
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return timestampMs + " " + (direction == DIRECTION_TX ? "TX" : "RX")
+                    + " type=0x" + Integer.toHexString(type) + " seq=" + seq + " " + outcomeName();
+        }
+    }
+
+    /**
+     * Command that reads the oldest entries still held
+     */
+    public static byte[] request() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that reads entries starting at entry number firstEntry
+     */
+    public static byte[] request(long firstEntry) {
+        return Protocol.allocate(5).put(OPCODE).putInt((int) firstEntry).array();
+    }
+
+    /**
+     * Parse an audit log page as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a complete page
+     */
+    public static AuditLog parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < HEADER_LENGTH) {
+            throw new IllegalArgumentException("Data too short for audit log page");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not an audit log response: " + response[0]);
+        }
+        ByteBuffer buf = Protocol.wrap(response, 1, response.length - 1);
+        long total = buf.getInt() & 0xFFFFFFFFL;
+        long firstIndex = buf.getInt() & 0xFFFFFFFFL;
+        int count = buf.get() & 0xFF;
+        if (response.length < HEADER_LENGTH + count * ENTRY_LENGTH) {
+            throw new IllegalArgumentException("Data too short for " + count + " audit entries");
+        }
+        List<Entry> entries = new ArrayList<>(count);
+        for (int i = 0; i < count; i++) {
+            long timestamp = buf.getInt() & 0xFFFFFFFFL;
+            entries.add(new Entry(timestamp, buf.get() & 0xFF, buf.get() & 0xFF, buf.get() & 0xFF, buf.get() & 0xFF));
+        }
+        return new AuditLog(total, firstIndex, entries);
+    }
+
+    /**
+     * Entry number to request next, or -1 once the newest entry has been read
+     */
+    public long nextIndex() {
+        long next = firstIndex + entries.size();
+        return next < total ? next : -1;
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "AuditLog{total=" + total + ", first=" + firstIndex + ", entries=" + entries.size() + "}";
+    }
+}
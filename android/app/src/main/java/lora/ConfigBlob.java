@@ -0,0 +1,97 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+import java.util.Arrays;
+
+/**
+ * Complete bridge configuration via the control characteristic (0x567A), for
+ * cloning one unit's settings onto others.
+ * Request: write [0x07] to export, or [0x07][14-byte blob] to import.
+ * Response: [0x87][14-byte blob, format version 1] with the configuration in effect.
+ * See protocol.md for the byte layout.
+ */
+public class ConfigBlob {
+
+    public static final byte OPCODE = 0x07;
+    public static final byte RESPONSE = (byte) 0x87;
+    public static final int FORMAT_VERSION = 1;
+    public static final int LENGTH = 14;
+
+    public static final int FLAG_PROMISCUOUS = 0x01;
+    public static final int FLAG_CHANNEL_AGILITY = 0x02;
+    public static final int FLAG_LISTEN_WINDOWS = 0x04;
+    public static final int FLAG_TRACK_STATUS = 0x08;
+
+    public final int nodeId; // Exported only, never applied on import
+    public final long frequency; // Hz
+    public final int spreadingFactor;
+    public final int bandwidthIndex;
+    public final int codingRate; // Denominator of 4/x
+    public final int txPower; // dBm
+    public final int syncWord;
+    public final boolean rxBoost;
+    public final int lnaGain; // 0 = AGC, 1-6 = G1..G6
+    public final int buildFlags; // FLAG_* of the firmware build
+    private final byte[] blob;
+
+    private ConfigBlob(byte[] blob) {
+        this.blob = blob;
+        ByteBuffer buf = Protocol.wrap(blob, 1, LENGTH - 1);
+        nodeId = buf.get() & 0xFF;
+        frequency = buf.getInt() & 0xFFFFFFFFL;
+        spreadingFactor = buf.get() & 0xFF;
+        bandwidthIndex = buf.get() & 0xFF;
+        codingRate = buf.get() & 0xFF;
+        txPower = buf.get();
+        syncWord = buf.get() & 0xFF;
+        rxBoost = buf.get() != 0;
+        lnaGain = buf.get() & 0xFF;
+        buildFlags = buf.get() & 0xFF;
+    }
+
+    /**
+     * Command that exports the current configuration
+     */
+    public static byte[] query() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that imports this configuration verbatim into another unit
+     */
+    public byte[] request() {
+        byte[] command = new byte[1 + LENGTH];
+        command[0] = OPCODE;
+        System.arraycopy(blob, 0, command, 1, LENGTH);
+        return command;
+    }
+
+    /**
+     * Parse a config response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a supported config blob
+     */
+    public static ConfigBlob parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < 1 + LENGTH) {
+            throw new IllegalArgumentException("Data too short for config");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a config response: " + response[0]);
+        }
+        if ((response[1] & 0xFF) != FORMAT_VERSION) {
+            throw new IllegalArgumentException("Unsupported config format: " + (response[1] & 0xFF));
+        }
+        return new ConfigBlob(Arrays.copyOfRange(response, 1, 1 + LENGTH));
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "ConfigBlob{node=" + nodeId + ", freq=" + frequency + ", sf=" + spreadingFactor + ", bwIndex="
+                + bandwidthIndex + ", cr=4/" + codingRate + ", txPower=" + txPower + ", syncWord=0x"
+                + Integer.toHexString(syncWord) + ", rxBoost=" + rxBoost + ", lnaGain=" + lnaGain + ", flags=0x"
+                + Integer.toHexString(buildFlags) + "}";
+    }
+}
@@ -0,0 +1,40 @@
+package lora;
+
+import java.nio.charset.StandardCharsets;
+
+/**
+ * Optional write lock of the control characteristic (0x567A).
+ * When the firmware is built with a passcode, control commands are answered with
+ * [0xFE][opcode] until the passcode is written to the unlock characteristic (0x567B).
+ * Reading the unlock characteristic returns 1 while commands are accepted, 0 when locked.
+ * This only stops casual misconfiguration; it is not security. See protocol.md.
+ */
+public class ControlLock {
+
+    public static final String UNLOCK_CHARACTERISTIC_UUID = "0000567b-0000-1000-8000-00805f9b34fb";
+    public static final byte LOCKED = (byte) 0xFE;
+
+    private ControlLock() {
+    }
+
+    /**
+     * Value to write to the unlock characteristic
+     */
+    public static byte[] unlockRequest(String passcode) {
+        return passcode.getBytes(StandardCharsets.UTF_8);
+    }
+
+    /**
+     * Whether a control response rejected the command because control is locked
+     */
+    public static boolean isLockedResponse(byte[] response) {
+        return response.length >= 2 && response[0] == LOCKED;
+    }
+
+    /**
+     * Whether a value read from the unlock characteristic means commands are accepted
+     */
+    public static boolean isUnlocked(byte[] state) {
+        return state.length >= 1 && state[0] != 0;
+    }
+}
@@ -0,0 +1,94 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+
+/**
+ * Diagnostics blob returned by the ESP32 control characteristic (0x567A).
+ * Request: write [0x01]. Response: [0x81][35-byte blob, format version 2].
+ * See protocol.md for the byte layout.
+ */
+public class Diagnostics {
+
+    public static final byte OPCODE = 0x01;
+    public static final byte RESPONSE = (byte) 0x81;
+    public static final int FORMAT_VERSION = 2;
+    public static final int LENGTH = 35;
+    public static final int BATTERY_UNKNOWN = 0xFF;
+
+    public final int formatVersion;
+    public final String firmwareVersion;
+    public final int nodeId;
+    public final long frequency; // Hz
+    public final int spreadingFactor;
+    public final int bandwidthIndex;
+    public final int codingRate; // Denominator of 4/x
+    public final int txPower; // dBm
+    public final int lastRssi; // dBm
+    public final float lastSnr; // dB
+    public final int loraRxFrames;
+    public final int loraTxFrames;
+    public final int loraTxFailures;
+    public final int ackTimeouts;
+    public final int batteryPercent; // BATTERY_UNKNOWN if not measured
+    public final int peerCount;
+    public final int dedupWindow;
+    public final long uptimeSeconds;
+    public final int lastError;
+    public final int bufferedMessages;
+    public final int bufferDrops; // Messages dropped by the full BLE buffer, wraps
+
+    private Diagnostics(ByteBuffer buf) {
+        formatVersion = buf.get() & 0xFF;
+        firmwareVersion = (buf.get() & 0xFF) + "." + (buf.get() & 0xFF) + "." + (buf.get() & 0xFF);
+        nodeId = buf.get() & 0xFF;
+        frequency = buf.getInt() & 0xFFFFFFFFL;
+        spreadingFactor = buf.get() & 0xFF;
+        bandwidthIndex = buf.get() & 0xFF;
+        codingRate = buf.get() & 0xFF;
+        txPower = buf.get();
+        lastRssi = buf.getShort();
+        lastSnr = buf.get() / 4.0f; // 0.25 dB steps
+        loraRxFrames = buf.getShort() & 0xFFFF;
+        loraTxFrames = buf.getShort() & 0xFFFF;
+        loraTxFailures = buf.getShort() & 0xFFFF;
+        ackTimeouts = buf.getShort() & 0xFFFF;
+        batteryPercent = buf.get() & 0xFF;
+        peerCount = buf.get() & 0xFF;
+        dedupWindow = buf.get() & 0xFF;
+        uptimeSeconds = buf.getInt() & 0xFFFFFFFFL;
+        lastError = buf.get() & 0xFF;
+        bufferedMessages = buf.get() & 0xFF;
+        bufferDrops = buf.getShort() & 0xFFFF;
+    }
+
+    /**
+     * Parse a diagnostics response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a supported diagnostics blob
+     */
+    public static Diagnostics parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < 1 + LENGTH) {
+            throw new IllegalArgumentException("Data too short for diagnostics");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a diagnostics response: " + response[0]);
+        }
+        if ((response[1] & 0xFF) != FORMAT_VERSION) {
+            throw new IllegalArgumentException("Unsupported diagnostics format: " + (response[1] & 0xFF));
+        }
+        return new Diagnostics(Protocol.wrap(response, 1, LENGTH));
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "Diagnostics{fw=" + firmwareVersion + ", node=" + nodeId + ", freq=" + frequency + ", sf="
+                + spreadingFactor + ", bwIndex=" + bandwidthIndex + ", cr=4/" + codingRate + ", txPower="
+                + txPower + ", rssi=" + lastRssi + ", snr=" + lastSnr + ", rx=" + loraRxFrames + ", tx="
+                + loraTxFrames + ", txFail=" + loraTxFailures + ", ackTimeouts=" + ackTimeouts + ", battery="
+                + batteryPercent + ", peers=" + peerCount + ", dedup=" + dedupWindow + ", uptime="
+                + uptimeSeconds + "s, lastError=" + lastError + ", buffered=" + bufferedMessages + ", drops=" + bufferDrops + "}";
+    }
+}
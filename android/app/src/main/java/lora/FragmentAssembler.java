@@ -0,0 +1,141 @@
+package lora;
+
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * Reassembles messages that the ESP32 split into BLE fragments because the
+ * negotiated MTU was too small for a single notification, and splits outgoing
+ * messages the same way when they don't fit one write.
+ * Start fragment:    [0xF1][total length][first bytes...]
+ * Continue fragment: [0xF2][next bytes...]
+ * Anything else is a complete message and is passed through unchanged.
+ * A complete message may be a batch of several (see unbatch()).
+ */
+public class FragmentAssembler {
+
+    public static final byte FRAGMENT_START = (byte) 0xF1;
+    public static final byte FRAGMENT_CONTINUE = (byte) 0xF2;
+    public static final byte BATCH = (byte) 0xF3;
+
+    private byte[] pending;
+    private int received;
+
+    /**
+     * Feed one BLE payload.
+     *
+     * @return the complete message bytes, or null if more fragments are needed
+     * @throws IllegalArgumentException on a malformed or out-of-order fragment
+     */
+    public byte[] accept(byte[] payload) throws IllegalArgumentException {
+        if (payload.length == 0) {
+            throw new IllegalArgumentException("Empty payload");
+        }
+
+        if (payload[0] == FRAGMENT_START) {
+            if (payload.length < 2) {
+                throw new IllegalArgumentException("Start fragment too short");
+            }
+            // A new start always discards an unfinished message
+            pending = new byte[payload[1] & 0xFF];
+            received = 0;
+            return append(payload, 2);
+        }
+
+        if (payload[0] == FRAGMENT_CONTINUE) {
+            if (pending == null) {
+                throw new IllegalArgumentException("Continue fragment without start");
+            }
+            return append(payload, 1);
+        }
+
+        reset();
+        return payload;
+    }
+
+    /**
+     * Split a message into writes of at most maxPayload bytes (MTU - 3).
+     * A message that already fits is returned as the only element, unchanged.
+     *
+     * @throws IllegalArgumentException if the message is longer than 255 bytes or maxPayload is below 3
+     */
+    public static List<byte[]> split(byte[] message, int maxPayload) throws IllegalArgumentException {
+        List<byte[]> fragments = new ArrayList<>();
+        if (message.length <= maxPayload) {
+            fragments.add(message);
+            return fragments;
+        }
+        if (message.length > 0xFF || maxPayload < 3) {
+            throw new IllegalArgumentException("Cannot fragment " + message.length + " bytes into " + maxPayload);
+        }
+
+        int offset = 0;
+        while (offset < message.length) {
+            int header = offset == 0 ? 2 : 1;
+            int chunk = Math.min(maxPayload - header, message.length - offset);
+            byte[] fragment = new byte[header + chunk];
+            fragment[0] = offset == 0 ? FRAGMENT_START : FRAGMENT_CONTINUE;
+            if (offset == 0) {
+                fragment[1] = (byte) message.length;
+            }
+            System.arraycopy(message, offset, fragment, header, chunk);
+            fragments.add(fragment);
+            offset += chunk;
+        }
+        return fragments;
+    }
+
+    /**
+     * Split a batch notification [0xF3][length][message]... into its messages.
+     * The ESP32 coalesces buffered messages this way when the MTU allows.
+     * Anything else is a single message and is returned as the only element.
+     *
+     * @throws IllegalArgumentException if a length runs past the end of the batch
+     */
+    public static List<byte[]> unbatch(byte[] payload) throws IllegalArgumentException {
+        List<byte[]> messages = new ArrayList<>();
+        if (payload.length == 0 || payload[0] != BATCH) {
+            messages.add(payload);
+            return messages;
+        }
+
+        int offset = 1;
+        while (offset < payload.length) {
+            int length = payload[offset] & 0xFF;
+            if (length == 0 || offset + 1 + length > payload.length) {
+                throw new IllegalArgumentException("Malformed batch at offset " + offset);
+            }
+            byte[] message = new byte[length];
+            System.arraycopy(payload, offset + 1, message, 0, length);
+            messages.add(message);
+            offset += 1 + length;
+        }
+        return messages;
+    }
+
+    /**
+     * Drop any partially received message (e.g. on disconnect)
+     */
+    public void reset() {
+        pending = null;
+        received = 0;
+    }
+
+    private byte[] append(byte[] payload, int offset) {
+        int chunk = payload.length - offset;
+        if (received + chunk > pending.length) {
+            reset();
+            throw new IllegalArgumentException("Fragment exceeds announced length");
+        }
+        System.arraycopy(payload, offset, pending, received, chunk);
+        received += chunk;
+
+        if (received < pending.length) {
+            return null;
+        }
+
+        byte[] complete = pending;
+        reset();
+        return complete;
+    }
+}
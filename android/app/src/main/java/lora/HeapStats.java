@@ -0,0 +1,65 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+
+/**
+ * Heap use of the bridge firmware, read from the heap characteristic (0x567D).
+ * Format version 1, 17 bytes. See protocol.md for the byte layout.
+ */
+public class HeapStats {
+
+    public static final String HEAP_CHARACTERISTIC_UUID = "0000567d-0000-1000-8000-00805f9b34fb";
+    public static final int FORMAT_VERSION = 1;
+    public static final int LENGTH = 17;
+
+    public final long totalBytes;
+    public final long freeBytes;
+    public final long minFreeBytes; // Lowest free heap since boot
+    public final long largestFreeBlock; // Largest allocation that would succeed
+
+    private HeapStats(ByteBuffer buf) {
+        buf.get(); // Format version
+        totalBytes = buf.getInt() & 0xFFFFFFFFL;
+        freeBytes = buf.getInt() & 0xFFFFFFFFL;
+        minFreeBytes = buf.getInt() & 0xFFFFFFFFL;
+        largestFreeBlock = buf.getInt() & 0xFFFFFFFFL;
+    }
+
+    /**
+     * Parse a value read from the heap characteristic
+     *
+     * @throws IllegalArgumentException if the value is not supported heap stats
+     */
+    public static HeapStats parse(byte[] value) throws IllegalArgumentException {
+        if (value.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for heap stats");
+        }
+        if ((value[0] & 0xFF) != FORMAT_VERSION) {
+            throw new IllegalArgumentException("Unsupported heap stats format: " + (value[0] & 0xFF));
+        }
+        return new HeapStats(Protocol.wrap(value, 0, LENGTH));
+    }
+
+    /**
+     * Bytes in use right now
+     */
+    public long usedBytes() {
+        return totalBytes - freeBytes;
+    }
+
+    /**
+     * Most bytes ever in use at once since the bridge started
+     */
+    public long peakUsedBytes() {
+        return totalBytes - minFreeBytes;
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "HeapStats{used=" + usedBytes() + "/" + totalBytes + ", peak=" + peakUsedBytes() + ", free="
+                + freeBytes + ", largestBlock=" + largestFreeBlock + "}";
+    }
+}
@@ -0,0 +1,51 @@
+package lora;
+
+/**
+ * Message injection via the control characteristic (0x567A), TESTING ONLY.
+ * Only firmware built with MESSAGE_INJECTION=1 answers; others reject the opcode.
+ * The bridge notifies the message to the app as if it had been received over
+ * LoRa, without touching the radio.
+ * Request: [0x09][serialized message]. Response: [0x89][1 = stored, 0 = rejected].
+ * See protocol.md for details.
+ */
+public class Inject {
+
+    public static final byte OPCODE = 0x09;
+    public static final byte RESPONSE = (byte) 0x89;
+    public static final int MAX_MESSAGE_LENGTH = 64;
+
+    private Inject() {
+    }
+
+    /**
+     * Command that injects a message into the bridge's LoRa to BLE path
+     *
+     * @throws IllegalArgumentException if the serialized message is longer than the bridge accepts
+     */
+    public static byte[] request(Protocol.Message message) throws IllegalArgumentException {
+        byte[] serialized = message.serialize();
+        if (serialized.length > MAX_MESSAGE_LENGTH) {
+            throw new IllegalArgumentException("Message too long to inject: " + serialized.length + " bytes");
+        }
+        byte[] command = new byte[1 + serialized.length];
+        command[0] = OPCODE;
+        System.arraycopy(serialized, 0, command, 1, serialized.length);
+        return command;
+    }
+
+    /**
+     * Parse an injection response as notified on the control characteristic
+     *
+     * @return True if the bridge queued or buffered the message for the app
+     * @throws IllegalArgumentException if the response is not an injection response
+     */
+    public static boolean parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < 2) {
+            throw new IllegalArgumentException("Data too short for injection response");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not an injection response: " + response[0]);
+        }
+        return response[1] != 0;
+    }
+}
@@ -0,0 +1,65 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+
+/**
+ * Reception context of the most recently received LoRa frame, read from the
+ * last-RX characteristic (0x567C). Format version 1, 19 bytes. See protocol.md
+ * for the byte layout.
+ */
+public class LastRxMeta {
+
+    public static final String LAST_RX_CHARACTERISTIC_UUID = "0000567c-0000-1000-8000-00805f9b34fb";
+    public static final int FORMAT_VERSION = 1;
+    public static final int LENGTH = 19;
+
+    public final int rxCount; // 0 = nothing received yet
+    public final int rssi; // dBm
+    public final float snr; // dB
+    public final int frequencyErrorHz;
+    public final int frameLength; // Bytes reported by the radio
+    public final long receivedAtMs; // ESP32 uptime
+    public final long ageMs; // At the time of the read
+
+    private LastRxMeta(ByteBuffer buf) {
+        buf.get(); // Format version
+        rxCount = buf.getShort() & 0xFFFF;
+        rssi = buf.getShort();
+        snr = buf.get() / 4.0f; // 0.25 dB steps
+        frequencyErrorHz = buf.getInt();
+        frameLength = buf.get() & 0xFF;
+        receivedAtMs = buf.getInt() & 0xFFFFFFFFL;
+        ageMs = buf.getInt() & 0xFFFFFFFFL;
+    }
+
+    /**
+     * Parse a value read from the last-RX characteristic
+     *
+     * @throws IllegalArgumentException if the value is not supported last-RX metadata
+     */
+    public static LastRxMeta parse(byte[] value) throws IllegalArgumentException {
+        if (value.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for last-RX metadata");
+        }
+        if ((value[0] & 0xFF) != FORMAT_VERSION) {
+            throw new IllegalArgumentException("Unsupported last-RX format: " + (value[0] & 0xFF));
+        }
+        return new LastRxMeta(Protocol.wrap(value, 0, LENGTH));
+    }
+
+    /**
+     * Whether a frame has been received since the bridge started
+     */
+    public boolean hasReception() {
+        return rxCount != 0;
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "LastRxMeta{rx=" + rxCount + ", rssi=" + rssi + ", snr=" + snr + ", freqError=" + frequencyErrorHz
+                + "Hz, len=" + frameLength + ", receivedAt=" + receivedAtMs + "ms, age=" + ageMs + "ms}";
+    }
+}
@@ -0,0 +1,66 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+
+/**
+ * Link health counters of the bridge since boot and its data spreading factor,
+ * read or notified on the stats characteristic (0x567E). Format version 2, 12 bytes. See protocol.md for the
+ * byte layout.
+ *
+ * <p>The counters are never reset and wrap at 65535: compare two readings with
+ * {@link #since(int, int)} to get the activity in between.
+ */
+public class LinkStats {
+
+    public static final String STATS_CHARACTERISTIC_UUID = "0000567e-0000-1000-8000-00805f9b34fb";
+    public static final int FORMAT_VERSION = 2;
+    public static final int LENGTH = 12;
+
+    public final int txFrames;
+    public final int rxFrames;
+    public final int crcFailures; // Frames dropped for a CRC mismatch
+    public final int acksReceived;
+    public final int bufferDrops; // Messages dropped by the full BLE buffer
+    public final int spreadingFactor; // In effect: follows control opcode 0x0A and adaptive data rate
+
+    private LinkStats(ByteBuffer buf) {
+        buf.get(); // Format version
+        txFrames = buf.getShort() & 0xFFFF;
+        rxFrames = buf.getShort() & 0xFFFF;
+        crcFailures = buf.getShort() & 0xFFFF;
+        acksReceived = buf.getShort() & 0xFFFF;
+        bufferDrops = buf.getShort() & 0xFFFF;
+        spreadingFactor = buf.get() & 0xFF;
+    }
+
+    /**
+     * Parse a value read or notified on the stats characteristic
+     *
+     * @throws IllegalArgumentException if the value is not supported link stats
+     */
+    public static LinkStats parse(byte[] value) throws IllegalArgumentException {
+        if (value.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for link stats");
+        }
+        if ((value[0] & 0xFF) != FORMAT_VERSION) {
+            throw new IllegalArgumentException("Unsupported link stats format: " + (value[0] & 0xFF));
+        }
+        return new LinkStats(Protocol.wrap(value, 0, LENGTH));
+    }
+
+    /**
+     * Increase of a counter from an earlier reading to this one, across a wrap
+     */
+    public static int since(int earlier, int now) {
+        return (now - earlier) & 0xFFFF;
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "LinkStats{tx=" + txFrames + ", rx=" + rxFrames + ", crcFailures=" + crcFailures + ", acks="
+                + acksReceived + ", bufferDrops=" + bufferDrops + ", sf=" + spreadingFactor + "}";
+    }
+}
@@ -0,0 +1,65 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+/**
+ * Packet loss injection via the control characteristic (0x567A), TESTING ONLY.
+ * Only firmware built with LOSS_INJECTION=1 answers; others reject the opcode.
+ * Request: write [0x06] to query, or [0x06][rx loss %][tx loss %] to change.
+ * Response: [0x86][rx loss %][tx loss %].
+ * See protocol.md for details.
+ */
+public class LossInjection {
+
+    public static final byte OPCODE = 0x06;
+    public static final byte RESPONSE = (byte) 0x86;
+    private static final int LENGTH = 3;
+
+    public final int rxLossPercent;
+    public final int txLossPercent;
+
+    private LossInjection(int rxLossPercent, int txLossPercent) {
+        this.rxLossPercent = rxLossPercent;
+        this.txLossPercent = txLossPercent;
+    }
+
+    /**
+     * Command that reads the current loss percentages
+     */
+    public static byte[] query() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that sets the percentage of received and transmitted frames to discard (0-100)
+     *
+     * @throws IllegalArgumentException if a percentage is out of range
+     */
+    public static byte[] request(int rxLossPercent, int txLossPercent) throws IllegalArgumentException {
+        if (rxLossPercent < 0 || rxLossPercent > 100 || txLossPercent < 0 || txLossPercent > 100) {
+            throw new IllegalArgumentException("Loss percentage out of range: " + rxLossPercent + "/" + txLossPercent);
+        }
+        return new byte[]{OPCODE, (byte) rxLossPercent, (byte) txLossPercent};
+    }
+
+    /**
+     * Parse a loss injection response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a loss injection response
+     */
+    public static LossInjection parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for loss injection");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a loss injection response: " + response[0]);
+        }
+        return new LossInjection(response[1] & 0xFF, response[2] & 0xFF);
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "LossInjection{rx=" + rxLossPercent + "%, tx=" + txLossPercent + "%}";
+    }
+}
@@ -0,0 +1,62 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+/**
+ * Node id of a bridge via the control characteristic (0x567A).
+ * Request: write [0x08] to query, or [0x08][node id] to set it (saved on the bridge).
+ * Response: [0x88][node id].
+ * See protocol.md for details.
+ */
+public class NodeId {
+
+    public static final byte OPCODE = 0x08;
+    public static final byte RESPONSE = (byte) 0x88;
+    private static final int LENGTH = 2;
+
+    public final int nodeId;
+
+    private NodeId(int nodeId) {
+        this.nodeId = nodeId;
+    }
+
+    /**
+     * Command that reads the node id in effect
+     */
+    public static byte[] query() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that sets the node id (1-254, see Protocol.isAssignableNodeId)
+     *
+     * @throws IllegalArgumentException if the id is reserved, broadcast or out of range
+     */
+    public static byte[] request(int nodeId) throws IllegalArgumentException {
+        if (!Protocol.isAssignableNodeId(nodeId)) {
+            throw new IllegalArgumentException("Node id not assignable: " + nodeId);
+        }
+        return new byte[]{OPCODE, (byte) nodeId};
+    }
+
+    /**
+     * Parse a node id response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a node id response
+     */
+    public static NodeId parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for node id");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a node id response: " + response[0]);
+        }
+        return new NodeId(response[1] & 0xFF);
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "NodeId{" + nodeId + "}";
+    }
+}
@@ -4,6 +4,9 @@ import androidx.annotation.NonNull;
 
 import java.nio.ByteBuffer;
 import java.nio.ByteOrder;
+import java.util.ArrayList;
+import java.util.Collections;
+import java.util.List;
 
 /**
  * LoRa Message Protocol for Android
@@ -21,18 +24,154 @@ public class Protocol {
     public static final int MAX_TEXT_LENGTH = 50;
 
     /**
-     * Character set for 6-bit encoding (64 characters)
-     * UPPERCASE ONLY: Space + A-Z + 0-9 + punctuation
+     * Maximum length in characters of a text sent as several fragments, see
+     * {@link TextMessage#fragments}. 200 uppercase characters take 4 frames.
      */
-    private static final String CHARSET = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>_";
+    public static final int MAX_FRAGMENTED_TEXT_LENGTH = 200;
+
+    /**
+     * Maximum size in bytes of a serialized message, CRC included (MAX_SERIALIZED_LEN in the firmware)
+     */
+    public static final int MAX_SERIALIZED_LENGTH = 64;
+
+    /**
+     * Maximum number of frame bytes carried by a RawMessage.
+     * Longer LoRa frames are truncated by the firmware; frameLength keeps the original size.
+     */
+    public static final int MAX_RAW_LENGTH = 61;
+
+    /**
+     * Maximum number of frame bytes carried by a DecodeFailureMessage.
+     */
+    public static final int MAX_DECODE_FAILURE_LENGTH = 60;
+
+    /**
+     * Bytes of the CRC-8 closing every serialized message (see {@link #crc8})
+     */
+    public static final int CRC_LENGTH = 1;
+
+    /**
+     * CRC-8 over the first length bytes: polynomial 0x07, initial value 0x00, no
+     * reflection, no final XOR (CRC-8/SMBUS, check value 0xF4 for "123456789").
+     * The radio's own payload CRC is off, so this is what catches corrupted frames.
+     */
+    public static int crc8(byte[] data, int length) {
+        int crc = 0;
+        for (int i = 0; i < length; i++) {
+            crc ^= data[i] & 0xFF;
+            for (int bit = 0; bit < 8; bit++) {
+                crc = (crc & 0x80) != 0 ? ((crc << 1) ^ 0x07) & 0xFF : (crc << 1) & 0xFF;
+            }
+        }
+        return crc;
+    }
+
+    /**
+     * Canonical byte order of every multi-byte field (LoRa frames, BLE messages and
+     * control responses). Fixed by the protocol, not negotiated - changing it breaks
+     * every peer. Wrap frames with {@link #wrap} / {@link #allocate} instead of
+     * ordering ByteBuffers by hand.
+     */
+    public static final ByteOrder BYTE_ORDER = ByteOrder.LITTLE_ENDIAN;
+
+    /**
+     * ByteBuffer over part of a frame, in the protocol byte order
+     */
+    public static ByteBuffer wrap(byte[] data, int offset, int length) {
+        return ByteBuffer.wrap(data, offset, length).order(BYTE_ORDER);
+    }
+
+    /**
+     * New ByteBuffer for building a frame, in the protocol byte order
+     */
+    public static ByteBuffer allocate(int length) {
+        return ByteBuffer.allocate(length).order(BYTE_ORDER);
+    }
+
+    /**
+     * Node id that addresses every node in range
+     */
+    public static final int BROADCAST_NODE_ID = 0xFF;
+
+    /**
+     * Reserved node id, never assigned to a bridge
+     */
+    public static final int RESERVED_NODE_ID = 0x00;
+
+    /**
+     * Whether a node id can be given to a bridge (1-254)
+     */
+    public static boolean isAssignableNodeId(int id) {
+        return id > RESERVED_NODE_ID && id < BROADCAST_NODE_ID;
+    }
+
+    /**
+     * Mean Earth radius in metres (IUGG), for GPS distances
+     */
+    public static final double EARTH_RADIUS_M = 6371008.8;
+
+    /**
+     * Great-circle distance in metres between two positions in degrees * 1_000_000, as
+     * carried by TextMessage. Haversine on a sphere (within 0.5% of the ellipsoid),
+     * rounded to the metre; same result as gpsDistanceM() in the firmware.
+     */
+    public static long distanceMeters(int lat1, int lon1, int lat2, int lon2) {
+        double phi1 = Math.toRadians(lat1 / 1e6);
+        double phi2 = Math.toRadians(lat2 / 1e6);
+        double halfDeltaPhi = Math.toRadians(((long) lat2 - lat1) / 1e6) / 2;
+        double halfDeltaLambda = Math.toRadians(((long) lon2 - lon1) / 1e6) / 2;
+        double h = Math.sin(halfDeltaPhi) * Math.sin(halfDeltaPhi)
+                + Math.cos(phi1) * Math.cos(phi2) * Math.sin(halfDeltaLambda) * Math.sin(halfDeltaLambda);
+        return Math.round(EARTH_RADIUS_M * 2 * Math.asin(Math.sqrt(Math.min(1, h))));
+    }
+
+    /**
+     * Initial great-circle bearing from the first position to the second, in whole
+     * degrees clockwise from true north (0-359). 0 for the same position.
+     */
+    public static int bearingDegrees(int lat1, int lon1, int lat2, int lon2) {
+        double phi1 = Math.toRadians(lat1 / 1e6);
+        double phi2 = Math.toRadians(lat2 / 1e6);
+        double deltaLambda = Math.toRadians(((long) lon2 - lon1) / 1e6);
+        double y = Math.sin(deltaLambda) * Math.cos(phi2);
+        double x = Math.cos(phi1) * Math.sin(phi2) - Math.sin(phi1) * Math.cos(phi2) * Math.cos(deltaLambda);
+        return (int) ((Math.round(Math.toDegrees(Math.atan2(y, x))) + 360) % 360);
+    }
+
+    /**
+     * SX127x bandwidth steps in Hz, indexed by the bandwidth index in IdentifyResponseMessage
+     */
+    private static final double[] BANDWIDTH_STEPS_HZ = {
+            7.8E3, 10.4E3, 15.6E3, 20.8E3, 31.25E3, 41.7E3, 62.5E3, 125E3, 250E3, 500E3};
+
+    /**
+     * Character sets for 6-bit encoding, one per PROTOCOL_CHARSET of the firmware (see Protocol.h).
+     * Each has exactly 64 distinct uppercase characters; CHARSET_MIXED_CASE ends with the shift code.
+     */
+    public static final String CHARSET_STANDARD = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>_";
+    public static final String CHARSET_NUMERIC = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()^~|\\=+/<>_";
+    public static final String CHARSET_SYMBOL = " ABCDEFGHILMNOPRSTUVWY0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>_^~|\\`";
+    public static final String CHARSET_MIXED_CASE = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>\u000E";
+
+    /**
+     * Stands for the shift code in a character set, never a character of a text.
+     * One shift code switches the case of the letter after it; two in a row switch the
+     * case of every following letter, up to the next two. Texts start in uppercase.
+     */
+    public static final char SHIFT = '\u000E';
+
+    /**
+     * Character set in use - must match PROTOCOL_CHARSET of the bridges, or texts are garbled
+     */
+    private static final String CHARSET = CHARSET_STANDARD;
 
     /**
      * Convert a character to its 6-bit encoded value
      * Automatically converts lowercase to uppercase
      */
-    private static byte charTo6Bit(char ch) throws IllegalArgumentException {
+    private static byte charTo6Bit(char ch, String charset) throws IllegalArgumentException {
         char upperCh = Character.toUpperCase(ch);
-        int index = CHARSET.indexOf(upperCh);
+        int index = upperCh == SHIFT ? -1 : charset.indexOf(upperCh);
         if (index < 0) {
             throw new IllegalArgumentException("Character not supported: '" + ch + "'");
         }
@@ -42,29 +181,99 @@ public class Protocol {
     /**
      * Convert a 6-bit value back to a character
      */
-    private static char sixBitToChar(byte val) throws IllegalArgumentException {
-        if (val < 0 || val >= CHARSET.length()) {
+    private static char sixBitToChar(byte val, String charset) throws IllegalArgumentException {
+        if (val < 0 || val >= charset.length()) {
             throw new IllegalArgumentException("Invalid 6-bit value: " + val);
         }
-        return CHARSET.charAt(val);
+        return charset.charAt(val);
+    }
+
+    /**
+     * Whether a character is a letter whose case needs shift codes in the character set
+     */
+    private static boolean hasCase(char ch, String charset) {
+        return charset.indexOf(SHIFT) >= 0 && ch < 0x80 && Character.isLetter(ch);
+    }
+
+    /**
+     * For each position, the codes the rest of the text needs while letters default to
+     * uppercase ([i][0]) or lowercase ([i][1]). A letter in the other case costs a
+     * single shift (2 codes) or a case switch (3 codes, and the letters after it change
+     * default), whichever leaves fewer. Same plan as text_to_codes() in the firmware.
+     */
+    private static int[][] fewestCodes(String text, String charset) {
+        int[][] fewest = new int[text.length() + 1][2];
+        for (int i = text.length() - 1; i >= 0; i--) {
+            char ch = text.charAt(i);
+            int lower = Character.isLowerCase(ch) ? 1 : 0;
+            for (int mode = 0; mode < 2; mode++) {
+                if (!hasCase(ch, charset) || lower == mode) {
+                    fewest[i][mode] = 1 + fewest[i + 1][mode];
+                } else {
+                    fewest[i][mode] = Math.min(2 + fewest[i + 1][mode], 3 + fewest[i + 1][lower]);
+                }
+            }
+        }
+        return fewest;
+    }
+
+    /**
+     * 6-bit codes of a text, with shift codes placed so it needs the fewest
+     * Without a shift code in the set lowercase letters are converted to uppercase.
+     */
+    static byte[] textCodes(String text, String charset) throws IllegalArgumentException {
+        int[][] fewest = fewestCodes(text, charset);
+        byte shift = (byte) charset.indexOf(SHIFT);
+        byte[] codes = new byte[fewest[0][0]];
+        int count = 0;
+        int mode = 0; // Texts start in uppercase
+        for (int i = 0; i < text.length(); i++) {
+            char ch = text.charAt(i);
+            byte value = charTo6Bit(ch, charset);
+            int lower = Character.isLowerCase(ch) ? 1 : 0;
+            if (hasCase(ch, charset) && lower != mode) {
+                if (3 + fewest[i + 1][lower] < 2 + fewest[i + 1][mode]) {
+                    // Switch the default case for this and the following letters
+                    codes[count++] = shift;
+                    codes[count++] = shift;
+                    mode = lower;
+                } else {
+                    codes[count++] = shift; // This letter only
+                }
+            }
+            codes[count++] = value;
+        }
+        return codes;
+    }
+
+    /**
+     * Number of 6-bit codes a text packs into with the character set in use, shift
+     * codes included - what MAX_TEXT_LENGTH limits. Unsupported characters count as one.
+     */
+    public static int textCodeCount(String text) {
+        return fewestCodes(text, CHARSET)[0][0];
     }
 
     /**
      * Pack text into 6-bit encoded bytes
      * Each character is encoded as 6 bits instead of 8 bits (UTF-8)
-     * Lowercase letters are automatically converted to uppercase
+     * Lowercase letters are automatically converted to uppercase, unless the set has a shift code
      * 50 chars × 6 bits = 300 bits = 37.5 bytes → 38 bytes
      */
-    private static byte[] packText(String text) throws IllegalArgumentException {
-        int charCount = text.length();
-        int byteCount = (charCount * 6 + 7) / 8; // Round up
+    static byte[] packText(String text, String charset) throws IllegalArgumentException {
+        return packCodes(textCodes(text, charset));
+    }
+
+    /**
+     * Pack 6-bit codes into bytes, most significant bit first
+     */
+    private static byte[] packCodes(byte[] codes) {
+        int byteCount = (codes.length * 6 + 7) / 8; // Round up
         byte[] result = new byte[byteCount];
 
         int bitOffset = 0;
 
-        for (int i = 0; i < charCount; i++) {
-            byte value = charTo6Bit(text.charAt(i));
-
+        for (byte value : codes) {
             int byteIdx = bitOffset / 8;
             int bitInByte = bitOffset % 8;
 
@@ -90,13 +299,16 @@ public class Protocol {
 
     /**
      * Unpack 6-bit encoded bytes back to text
-     * Reads 6 bits at a time and converts to characters (uppercase)
+     * Reads 6 bits at a time and converts to characters (uppercase, or the case the shift codes give)
      */
-    private static String unpackText(byte[] packed, int charCount) throws IllegalArgumentException {
-        StringBuilder result = new StringBuilder(charCount);
+    static String unpackText(byte[] packed, int codeCount, String charset) throws IllegalArgumentException {
+        StringBuilder result = new StringBuilder(codeCount);
+        int shift = charset.indexOf(SHIFT);
+        boolean lowerByDefault = false; // Switched by two shift codes in a row
+        boolean shifted = false;        // One shift code before the next letter
         int bitOffset = 0;
 
-        for (int i = 0; i < charCount; i++) {
+        for (int i = 0; i < codeCount; i++) {
             int byteIdx = bitOffset / 8;
             int bitInByte = bitOffset % 8;
 
@@ -124,10 +336,31 @@ public class Protocol {
                 value = (byte) ((firstPart << bitsInSecond) | secondPart);
             }
 
-            result.append(sixBitToChar(value));
             bitOffset += 6;
+
+            if (value == shift) {
+                if (shifted) {
+                    lowerByDefault = !lowerByDefault;
+                }
+                shifted = !shifted;
+                continue;
+            }
+
+            char ch = sixBitToChar(value, charset);
+            if (hasCase(ch, charset)) {
+                if (lowerByDefault != shifted) {
+                    ch = Character.toLowerCase(ch);
+                }
+            } else if (shifted) {
+                throw new IllegalArgumentException("Shift code before a character without case");
+            }
+            shifted = false;
+            result.append(ch);
         }
 
+        if (shifted) {
+            throw new IllegalArgumentException("Shift code without a letter after it");
+        }
         return result.toString();
     }
 
@@ -135,7 +368,7 @@ public class Protocol {
      * Calculate the packed size for a given text
      */
     public static int calculatePackedSize(String text) {
-        return (text.length() * 6 + 7) / 8;
+        return (textCodeCount(text) * 6 + 7) / 8;
     }
 
     /**
@@ -143,7 +376,7 @@ public class Protocol {
      */
     public static boolean isCharacterSupported(char ch) {
         char upperCh = Character.toUpperCase(ch);
-        return CHARSET.indexOf(upperCh) >= 0;
+        return upperCh != SHIFT && CHARSET.indexOf(upperCh) >= 0;
     }
 
     /**
@@ -160,7 +393,18 @@ public class Protocol {
 
     public enum MessageType {
         TEXT((byte) 0x01),
-        ACK((byte) 0x02);
+        ACK((byte) 0x02),
+        BATTERY((byte) 0x05),
+        SOS((byte) 0x06),
+        IDENTIFY((byte) 0x08),
+        IDENTIFY_RESPONSE((byte) 0x09),
+        CONFIG_REQUEST((byte) 0x0D),
+        CONFIG_RESPONSE((byte) 0x0E),
+        RAW((byte) 0x80),
+        STATUS((byte) 0x81),
+        DECODE_FAILURE((byte) 0x82),
+        DELIVERY_REPORT((byte) 0x83),
+        LINK_QUALITY((byte) 0x84);
 
         private final byte value;
 
@@ -183,58 +427,269 @@ public class Protocol {
     }
 
     public static class TextMessage extends Message {
+        /** Flags bit 4: a GPS fields byte and its fields follow the coordinates */
+        static final int FLAG_GPS_FIELDS = 0x10;
+        /** Flags bit 5: the text is one fragment of a longer one, see FRAGMENT_HEADER_LENGTH */
+        static final int FLAG_FRAGMENT = 0x20;
+
+        /*
+         * Fragment header: fragment id, index and count, the last bytes before the
+         * CRC. Found from the end of the frame, so GPS fields a receiver doesn't know
+         * still sit between the coordinates and it.
+         */
+        static final int FRAGMENT_HEADER_LENGTH = 3;
+
+        /*
+         * GPS fields byte: which optional GPS fields follow it, in bit order. Fields
+         * added later take the next bit, so a receiver reads the fields it knows and
+         * ignores the bytes after them.
+         */
+        public static final int GPS_FIELD_ALTITUDE = 0x01; // i16, metres above mean sea level
+        public static final int GPS_FIELD_SPEED = 0x02;    // u16, cm/s over ground
+        public static final int GPS_FIELDS_KNOWN = GPS_FIELD_ALTITUDE | GPS_FIELD_SPEED;
+
         public final byte seq;
         public final String text;
         public final boolean hasGps;
         public final int lat; // latitude * 1_000_000 (only valid if hasGps=true)
         public final int lon; // longitude * 1_000_000 (only valid if hasGps=true)
+        public final Priority priority;
+        public final int gpsFields; // GPS_FIELD_* present (only valid if hasGps=true)
+        public final int altitude;  // Metres above mean sea level (only valid with GPS_FIELD_ALTITUDE)
+        public final int speed;     // cm/s over ground (only valid with GPS_FIELD_SPEED)
+        public final int fragmentId;    // Shared by every fragment of the text (only valid if isFragment())
+        public final int fragmentIndex; // Position of this fragment, 0 to fragmentCount - 1
+        public final int fragmentCount; // Fragments of the text, 0 for a whole text
 
         public TextMessage(byte seq, String text) {
+            this(seq, text, Priority.NORMAL);
+        }
+
+        public TextMessage(byte seq, String text, Priority priority) {
             super(MessageType.TEXT);
-            if (text.length() > MAX_TEXT_LENGTH) {
-                throw new IllegalArgumentException("Text too long (max " + MAX_TEXT_LENGTH + " chars)");
+            if (text.length() > MAX_FRAGMENTED_TEXT_LENGTH) {
+                throw new IllegalArgumentException("Text too long (max " + MAX_FRAGMENTED_TEXT_LENGTH + " chars)");
             }
             this.seq = seq;
             this.text = text;
             this.hasGps = false;
             this.lat = 0;
             this.lon = 0;
+            this.priority = priority;
+            this.gpsFields = 0;
+            this.altitude = 0;
+            this.speed = 0;
+            this.fragmentId = 0;
+            this.fragmentIndex = 0;
+            this.fragmentCount = 0;
         }
 
         public TextMessage(byte seq, String text, int lat, int lon) {
+            this(seq, text, lat, lon, Priority.NORMAL);
+        }
+
+        public TextMessage(byte seq, String text, int lat, int lon, Priority priority) {
+            this(seq, text, lat, lon, priority, 0, 0, 0);
+        }
+
+        /**
+         * Text with GPS coordinates and the optional GPS fields named in gpsFields
+         *
+         * @param altitude Metres above mean sea level (-32768 to 32767), sent with GPS_FIELD_ALTITUDE
+         * @param speed    cm/s over ground (0 to 65535), sent with GPS_FIELD_SPEED
+         */
+        public TextMessage(byte seq, String text, int lat, int lon, Priority priority, int gpsFields, int altitude,
+                int speed) {
             super(MessageType.TEXT);
-            if (text.length() > MAX_TEXT_LENGTH) {
-                throw new IllegalArgumentException("Text too long (max " + MAX_TEXT_LENGTH + " chars)");
+            if (text.length() > MAX_FRAGMENTED_TEXT_LENGTH) {
+                throw new IllegalArgumentException("Text too long (max " + MAX_FRAGMENTED_TEXT_LENGTH + " chars)");
+            }
+            if ((gpsFields & ~GPS_FIELDS_KNOWN) != 0) {
+                throw new IllegalArgumentException("Unknown GPS fields: 0x" + Integer.toHexString(gpsFields));
+            }
+            if (altitude < Short.MIN_VALUE || altitude > Short.MAX_VALUE || speed < 0 || speed > 0xFFFF) {
+                throw new IllegalArgumentException("Altitude or speed out of range");
             }
             this.seq = seq;
             this.text = text;
             this.hasGps = true;
             this.lat = lat;
             this.lon = lon;
+            this.priority = priority;
+            this.gpsFields = gpsFields;
+            this.altitude = (gpsFields & GPS_FIELD_ALTITUDE) != 0 ? altitude : 0;
+            this.speed = (gpsFields & GPS_FIELD_SPEED) != 0 ? speed : 0;
+            this.fragmentId = 0;
+            this.fragmentIndex = 0;
+            this.fragmentCount = 0;
+        }
+
+        /**
+         * Copy of a text with another seq and text, as a fragment when fragmentCount is above 0
+         */
+        TextMessage(TextMessage template, byte seq, String text, int fragmentId, int fragmentIndex,
+                int fragmentCount) {
+            super(MessageType.TEXT);
+            if (fragmentCount > 0xFF || fragmentId < 0 || fragmentId > 0xFF
+                    || (fragmentCount > 0 && (fragmentIndex < 0 || fragmentIndex >= fragmentCount))) {
+                throw new IllegalArgumentException("Fragment " + fragmentIndex + " of " + fragmentCount + " out of range");
+            }
+            this.seq = seq;
+            this.text = text;
+            this.hasGps = template.hasGps;
+            this.lat = template.lat;
+            this.lon = template.lon;
+            this.priority = template.priority;
+            this.gpsFields = template.gpsFields;
+            this.altitude = template.altitude;
+            this.speed = template.speed;
+            this.fragmentId = fragmentCount > 0 ? fragmentId : 0;
+            this.fragmentIndex = fragmentCount > 0 ? fragmentIndex : 0;
+            this.fragmentCount = Math.max(fragmentCount, 0);
+        }
+
+        /**
+         * Whether this is one fragment of a longer text (see {@link TextReassembler})
+         */
+        public boolean isFragment() {
+            return fragmentCount > 0;
+        }
+
+        /**
+         * Split into texts that each serialize to at most mtu bytes and MAX_TEXT_LENGTH codes.
+         * A text that already fits is returned as the only element, unchanged. Otherwise
+         * fragment i takes seq + i, so each is ACKed and retransmitted on its own, and
+         * every fragment carries the GPS fields and priority of the whole text. The seq of
+         * the first fragment is the fragment id.
+         *
+         * @throws IllegalArgumentException if mtu leaves no room for text or more than 255 fragments are needed
+         */
+        public List<TextMessage> fragments(int mtu) throws IllegalArgumentException {
+            int overhead = 5 + (hasGps ? 8 : 0) + (gpsFields != 0 ? 1 + gpsFieldsLength(gpsFields) : 0) + CRC_LENGTH;
+            int codeCount = textCodeCount(text);
+            if (codeCount <= MAX_TEXT_LENGTH && overhead + (codeCount * 6 + 7) / 8 <= mtu) {
+                return Collections.singletonList(this);
+            }
+
+            // A letter may need a shift code before it
+            int maxCodes = Math.min(MAX_TEXT_LENGTH, (mtu - overhead - FRAGMENT_HEADER_LENGTH) * 8 / 6);
+            if (maxCodes < 2) {
+                throw new IllegalArgumentException("MTU of " + mtu + " bytes leaves no room for text");
+            }
+            List<String> parts = new ArrayList<>();
+            int start = 0;
+            while (start < text.length()) {
+                // Longest part within maxCodes; codes never shrink as the part grows
+                int end = start + 1;
+                while (end < text.length() && textCodeCount(text.substring(start, end + 1)) <= maxCodes) {
+                    end++;
+                }
+                parts.add(text.substring(start, end));
+                start = end;
+            }
+            if (parts.size() > 0xFF) {
+                throw new IllegalArgumentException("Text needs " + parts.size() + " fragments (max 255)");
+            }
+
+            List<TextMessage> fragments = new ArrayList<>();
+            for (int i = 0; i < parts.size(); i++) {
+                fragments.add(new TextMessage(this, (byte) (seq + i), parts.get(i), seq & 0xFF, i, parts.size()));
+            }
+            return fragments;
+        }
+
+        /**
+         * Serialized fragments of this text, see {@link #fragments}
+         */
+        public List<byte[]> serializeFragments(int mtu) throws IllegalArgumentException {
+            List<byte[]> frames = new ArrayList<>();
+            for (TextMessage fragment : fragments(mtu)) {
+                frames.add(fragment.serialize());
+            }
+            return frames;
+        }
+
+        /**
+         * Distance in metres to the position of another text, see {@link #distanceMeters}
+         *
+         * @throws IllegalStateException if either text has no GPS
+         */
+        public long distanceTo(TextMessage other) {
+            requireGps(other);
+            return distanceMeters(lat, lon, other.lat, other.lon);
+        }
+
+        /**
+         * Bearing in degrees to the position of another text, see {@link #bearingDegrees}
+         *
+         * @throws IllegalStateException if either text has no GPS
+         */
+        public int bearingTo(TextMessage other) {
+            requireGps(other);
+            return bearingDegrees(lat, lon, other.lat, other.lon);
+        }
+
+        private void requireGps(TextMessage other) {
+            if (!hasGps || !other.hasGps) {
+                throw new IllegalStateException("Both texts need GPS coordinates");
+            }
         }
 
         @Override
-        public byte[] serialize() {
-            byte[] packedText = packText(text);
-            int totalSize = 1 + 1 + 1 + 1 + 1 + packedText.length; // type + seq + charCount + packedLen + hasGps + packed
+        protected byte[] serializeBody() {
+            byte[] codes = textCodes(text, CHARSET);
+            if (codes.length > MAX_TEXT_LENGTH) {
+                throw new IllegalArgumentException("Text too long with its shift codes (max " + MAX_TEXT_LENGTH + ")");
+            }
+            byte[] packedText = packCodes(codes);
+            int totalSize = 1 + 1 + 1 + 1 + 1 + packedText.length; // type + seq + charCount + packedLen + flags + packed
             if (hasGps) {
                 totalSize += 8; // lat + lon
             }
+            if (gpsFields != 0) {
+                totalSize += 1 + gpsFieldsLength(gpsFields); // GPS fields byte + fields
+            }
+            if (isFragment()) {
+                totalSize += FRAGMENT_HEADER_LENGTH;
+            }
             byte[] data = new byte[totalSize];
             data[0] = MessageType.TEXT.getValue();
             data[1] = seq;
-            data[2] = (byte) text.length(); // Original character count
+            data[2] = (byte) codes.length; // 6-bit code count (characters plus shift codes)
             data[3] = (byte) packedText.length; // Packed byte count
             System.arraycopy(packedText, 0, data, 4, packedText.length);
-            data[4 + packedText.length] = (byte) (hasGps ? 1 : 0);
+            data[4 + packedText.length] = (byte) ((hasGps ? 1 : 0) | priority.toFlags()
+                    | (gpsFields != 0 ? FLAG_GPS_FIELDS : 0) | (isFragment() ? FLAG_FRAGMENT : 0));
             if (hasGps) {
-                ByteBuffer buf = ByteBuffer.wrap(data, 5 + packedText.length, 8).order(ByteOrder.LITTLE_ENDIAN);
+                ByteBuffer buf = wrap(data, 5 + packedText.length, 8);
                 buf.putInt(lat);
                 buf.putInt(lon);
             }
+            if (gpsFields != 0) {
+                // Fields in bit order after the fields byte
+                ByteBuffer buf = wrap(data, 13 + packedText.length, 1 + gpsFieldsLength(gpsFields));
+                buf.put((byte) gpsFields);
+                if ((gpsFields & GPS_FIELD_ALTITUDE) != 0) {
+                    buf.putShort((short) altitude);
+                }
+                if ((gpsFields & GPS_FIELD_SPEED) != 0) {
+                    buf.putShort((short) speed);
+                }
+            }
+            if (isFragment()) {
+                // Always last, after any GPS fields
+                data[totalSize - 3] = (byte) fragmentId;
+                data[totalSize - 2] = (byte) fragmentIndex;
+                data[totalSize - 1] = (byte) fragmentCount;
+            }
             return data;
         }
 
+        /** Bytes taken by the known GPS fields named in gpsFields */
+        static int gpsFieldsLength(int gpsFields) {
+            return ((gpsFields & GPS_FIELD_ALTITUDE) != 0 ? 2 : 0) + ((gpsFields & GPS_FIELD_SPEED) != 0 ? 2 : 0);
+        }
+
         @Override
         public boolean equals(Object obj) {
             if (this == obj)
@@ -243,21 +698,29 @@ public class Protocol {
                 return false;
             TextMessage that = (TextMessage) obj;
             return seq == that.seq && text.equals(that.text) && hasGps == that.hasGps && lat == that.lat
-                    && lon == that.lon;
+                    && lon == that.lon && priority == that.priority && gpsFields == that.gpsFields
+                    && altitude == that.altitude && speed == that.speed && fragmentId == that.fragmentId
+                    && fragmentIndex == that.fragmentIndex && fragmentCount == that.fragmentCount;
         }
 
         @Override
         public int hashCode() {
-            return java.util.Objects.hash(seq, text, hasGps, lat, lon);
+            return java.util.Objects.hash(seq, text, hasGps, lat, lon, priority, gpsFields, altitude, speed, fragmentId,
+                    fragmentIndex, fragmentCount);
         }
 
         @NonNull
         @Override
         public String toString() {
+            String prio = (priority == Priority.NORMAL ? "" : ", priority=" + priority)
+                    + (isFragment() ? ", fragment=" + fragmentId + ":" + (fragmentIndex + 1) + "/" + fragmentCount : "");
             if (hasGps) {
-                return "TextMessage{seq=" + seq + ", text='" + text + "', lat=" + lat + ", lon=" + lon + "}";
+                String extra = ((gpsFields & GPS_FIELD_ALTITUDE) != 0 ? ", altitude=" + altitude : "")
+                        + ((gpsFields & GPS_FIELD_SPEED) != 0 ? ", speed=" + speed : "");
+                return "TextMessage{seq=" + seq + ", text='" + text + "', lat=" + lat + ", lon=" + lon + extra + prio
+                        + "}";
             }
-            return "TextMessage{seq=" + seq + ", text='" + text + "'}";
+            return "TextMessage{seq=" + seq + ", text='" + text + "'" + prio + "}";
         }
     }
 
@@ -270,7 +733,7 @@ public class Protocol {
         }
 
         @Override
-        public byte[] serialize() {
+        protected byte[] serializeBody() {
             byte[] data = new byte[2];
             data[0] = MessageType.ACK.getValue();
             data[1] = seq;
@@ -299,60 +762,983 @@ public class Protocol {
         }
     }
 
-    public static abstract class Message {
-        public final MessageType type;
+    /**
+     * Ask a node to blink its LED and reply with its node id and radio config
+     */
+    public static class IdentifyMessage extends Message {
+        public final int targetNode; // 0-255, BROADCAST_NODE_ID addresses every node
 
-        protected Message(MessageType type) {
-            this.type = type;
+        public IdentifyMessage(int targetNode) {
+            super(MessageType.IDENTIFY);
+            if (targetNode < 0 || targetNode > 0xFF) {
+                throw new IllegalArgumentException("Invalid node id: " + targetNode);
+            }
+            this.targetNode = targetNode;
         }
 
-        public static Message deserialize(byte[] data) throws IllegalArgumentException {
-            if (data.length < 1) {
-                throw new IllegalArgumentException("Data too short");
-            }
-            MessageType type = MessageType.fromByte(data[0]);
-            return switch (type) {
-                case TEXT -> deserializeText(data);
-                case ACK -> deserializeAck(data);
-            };
+        @Override
+        protected byte[] serializeBody() {
+            return new byte[]{MessageType.IDENTIFY.getValue(), (byte) targetNode};
         }
 
-        private static TextMessage deserializeText(byte[] data) {
-            if (data.length < 5) {
-                throw new IllegalArgumentException("Data too short for TextMessage header");
-            }
-            byte seq = data[1];
-            int charCount = data[2] & 0xFF; // Original character count
-            int packedLen = data[3] & 0xFF; // Packed byte count
-            if (data.length < 5 + packedLen) {
-                throw new IllegalArgumentException("Data too short for packed text + hasGps flag");
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            IdentifyMessage that = (IdentifyMessage) obj;
+            return targetNode == that.targetNode;
+        }
+
+        @Override
+        public int hashCode() {
+            return Integer.hashCode(targetNode);
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "IdentifyMessage{targetNode=" + targetNode + "}";
+        }
+    }
+
+    /**
+     * Node id and radio configuration, sent by a node in reply to IdentifyMessage
+     */
+    public static class IdentifyResponseMessage extends Message {
+        public final int nodeId;
+        public final long frequency;    // Hz
+        public final int spreadingFactor;
+        public final int bandwidthIndex; // SX127x BW index 0-9
+        public final int codingRate;    // Denominator of 4/x
+        public final int txPower;       // dBm
+
+        public IdentifyResponseMessage(int nodeId, long frequency, int spreadingFactor, int bandwidthIndex,
+                                       int codingRate, int txPower) {
+            super(MessageType.IDENTIFY_RESPONSE);
+            this.nodeId = nodeId;
+            this.frequency = frequency;
+            this.spreadingFactor = spreadingFactor;
+            this.bandwidthIndex = bandwidthIndex;
+            this.codingRate = codingRate;
+            this.txPower = txPower;
+        }
+
+        /**
+         * Bandwidth in Hz, or -1 if the index is not a known SX127x step
+         */
+        public double getBandwidthHz() {
+            if (bandwidthIndex < 0 || bandwidthIndex >= BANDWIDTH_STEPS_HZ.length) {
+                return -1;
             }
-            byte[] packedBytes = new byte[packedLen];
-            System.arraycopy(data, 4, packedBytes, 0, packedLen);
-            String text = unpackText(packedBytes, charCount);
-            boolean hasGps = data[4 + packedLen] != 0;
+            return BANDWIDTH_STEPS_HZ[bandwidthIndex];
+        }
 
-            if (hasGps) {
-                if (data.length < 5 + packedLen + 8) {
-                    throw new IllegalArgumentException("Data too short for GPS data");
-                }
-                ByteBuffer buf = ByteBuffer.wrap(data, 5 + packedLen, 8).order(ByteOrder.LITTLE_ENDIAN);
-                int lat = buf.getInt();
-                int lon = buf.getInt();
-                return new TextMessage(seq, text, lat, lon);
-            } else {
-                return new TextMessage(seq, text);
+        @Override
+        protected byte[] serializeBody() {
+            byte[] data = new byte[10];
+            data[0] = MessageType.IDENTIFY_RESPONSE.getValue();
+            data[1] = (byte) nodeId;
+            wrap(data, 2, 4).putInt((int) frequency);
+            data[6] = (byte) spreadingFactor;
+            data[7] = (byte) bandwidthIndex;
+            data[8] = (byte) codingRate;
+            data[9] = (byte) txPower;
+            return data;
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            IdentifyResponseMessage that = (IdentifyResponseMessage) obj;
+            return nodeId == that.nodeId && frequency == that.frequency && spreadingFactor == that.spreadingFactor
+                    && bandwidthIndex == that.bandwidthIndex && codingRate == that.codingRate
+                    && txPower == that.txPower;
+        }
+
+        @Override
+        public int hashCode() {
+            return java.util.Objects.hash(nodeId, frequency, spreadingFactor, bandwidthIndex, codingRate, txPower);
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "IdentifyResponseMessage{nodeId=" + nodeId + ", frequency=" + frequency + ", sf="
+                    + spreadingFactor + ", bwIndex=" + bandwidthIndex + ", cr=4/" + codingRate + ", txPower="
+                    + txPower + "}";
+        }
+    }
+
+    /**
+     * Ask a node for its full configuration (radio, firmware and protocol).
+     * Unlike IdentifyMessage the node doesn't blink; replies are rate-limited by the firmware.
+     */
+    public static class ConfigRequestMessage extends Message {
+        public final int targetNode; // 0-255, BROADCAST_NODE_ID addresses every node
+
+        public ConfigRequestMessage(int targetNode) {
+            super(MessageType.CONFIG_REQUEST);
+            if (targetNode < 0 || targetNode > 0xFF) {
+                throw new IllegalArgumentException("Invalid node id: " + targetNode);
             }
+            this.targetNode = targetNode;
         }
 
-        private static AckMessage deserializeAck(byte[] data) {
-            if (data.length < 2) {
-                throw new IllegalArgumentException("Data too short for AckMessage");
+        @Override
+        protected byte[] serializeBody() {
+            return new byte[]{MessageType.CONFIG_REQUEST.getValue(), (byte) targetNode};
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            ConfigRequestMessage that = (ConfigRequestMessage) obj;
+            return targetNode == that.targetNode;
+        }
+
+        @Override
+        public int hashCode() {
+            return Integer.hashCode(targetNode);
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "ConfigRequestMessage{targetNode=" + targetNode + "}";
+        }
+    }
+
+    /**
+     * Full configuration of a node, sent in reply to ConfigRequestMessage
+     */
+    public static class ConfigResponseMessage extends Message {
+        public static final int LENGTH = 19; // CRC included
+
+        public final int nodeId;
+        public final long frequency;    // Hz
+        public final int spreadingFactor;
+        public final int bandwidthIndex; // SX127x BW index 0-9
+        public final int codingRate;    // Denominator of 4/x
+        public final int txPower;       // dBm
+        public final int syncWord;
+        public final int preambleLength; // Symbols
+        public final int firmwareMajor;
+        public final int firmwareMinor;
+        public final int firmwarePatch;
+        public final int protocolProfile; // 0 all, 1 messaging, 2 tracking, 3 combined
+        public final int charset;         // 0 standard, 1 numeric, 2 symbol, 3 mixed case
+
+        public ConfigResponseMessage(int nodeId, long frequency, int spreadingFactor, int bandwidthIndex,
+                                     int codingRate, int txPower, int syncWord, int preambleLength,
+                                     int firmwareMajor, int firmwareMinor, int firmwarePatch,
+                                     int protocolProfile, int charset) {
+            super(MessageType.CONFIG_RESPONSE);
+            this.nodeId = nodeId;
+            this.frequency = frequency;
+            this.spreadingFactor = spreadingFactor;
+            this.bandwidthIndex = bandwidthIndex;
+            this.codingRate = codingRate;
+            this.txPower = txPower;
+            this.syncWord = syncWord;
+            this.preambleLength = preambleLength;
+            this.firmwareMajor = firmwareMajor;
+            this.firmwareMinor = firmwareMinor;
+            this.firmwarePatch = firmwarePatch;
+            this.protocolProfile = protocolProfile;
+            this.charset = charset;
+        }
+
+        /**
+         * Bandwidth in Hz, or -1 if the index is not a known SX127x step
+         */
+        public double getBandwidthHz() {
+            if (bandwidthIndex < 0 || bandwidthIndex >= BANDWIDTH_STEPS_HZ.length) {
+                return -1;
+            }
+            return BANDWIDTH_STEPS_HZ[bandwidthIndex];
+        }
+
+        /**
+         * Link parameters that differ from the local bridge's configuration (empty if the ends match).
+         * Node id and TX power may differ between the ends and are not compared.
+         */
+        public java.util.List<String> linkMismatches(ConfigBlob local) {
+            java.util.List<String> mismatches = new java.util.ArrayList<>();
+            if (frequency != local.frequency)
+                mismatches.add("frequency");
+            if (spreadingFactor != local.spreadingFactor)
+                mismatches.add("spreadingFactor");
+            if (bandwidthIndex != local.bandwidthIndex)
+                mismatches.add("bandwidth");
+            if (codingRate != local.codingRate)
+                mismatches.add("codingRate");
+            if (syncWord != local.syncWord)
+                mismatches.add("syncWord");
+            return mismatches;
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            byte[] data = new byte[LENGTH - CRC_LENGTH];
+            data[0] = MessageType.CONFIG_RESPONSE.getValue();
+            ByteBuffer buf = wrap(data, 1, data.length - 1);
+            buf.put((byte) nodeId);
+            buf.putInt((int) frequency);
+            buf.put((byte) spreadingFactor);
+            buf.put((byte) bandwidthIndex);
+            buf.put((byte) codingRate);
+            buf.put((byte) txPower);
+            buf.put((byte) syncWord);
+            buf.putShort((short) preambleLength);
+            buf.put((byte) firmwareMajor);
+            buf.put((byte) firmwareMinor);
+            buf.put((byte) firmwarePatch);
+            buf.put((byte) protocolProfile);
+            buf.put((byte) charset);
+            return data;
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            ConfigResponseMessage that = (ConfigResponseMessage) obj;
+            return nodeId == that.nodeId && frequency == that.frequency && spreadingFactor == that.spreadingFactor
+                    && bandwidthIndex == that.bandwidthIndex && codingRate == that.codingRate
+                    && txPower == that.txPower && syncWord == that.syncWord && preambleLength == that.preambleLength
+                    && firmwareMajor == that.firmwareMajor && firmwareMinor == that.firmwareMinor
+                    && firmwarePatch == that.firmwarePatch && protocolProfile == that.protocolProfile
+                    && charset == that.charset;
+        }
+
+        @Override
+        public int hashCode() {
+            return java.util.Objects.hash(nodeId, frequency, spreadingFactor, bandwidthIndex, codingRate, txPower,
+                    syncWord, preambleLength, firmwareMajor, firmwareMinor, firmwarePatch, protocolProfile, charset);
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "ConfigResponseMessage{nodeId=" + nodeId + ", frequency=" + frequency + ", sf="
+                    + spreadingFactor + ", bwIndex=" + bandwidthIndex + ", cr=4/" + codingRate + ", txPower="
+                    + txPower + ", syncWord=0x" + Integer.toHexString(syncWord) + ", preamble=" + preambleLength
+                    + ", firmware=" + firmwareMajor + "." + firmwareMinor + "." + firmwarePatch + ", profile="
+                    + protocolProfile + ", charset=" + charset + "}";
+        }
+    }
+
+    /**
+     * Unparseable LoRa frame forwarded by a bridge running in promiscuous mode.
+     * Only ever sent bridge -> app; the firmware ignores raw messages written to it.
+     */
+    public static class RawMessage extends Message {
+        public final int frameLength; // Original frame length on air
+        public final byte[] data;     // First min(frameLength, MAX_RAW_LENGTH) bytes of the frame
+
+        public RawMessage(int frameLength, byte[] data) {
+            super(MessageType.RAW);
+            if (frameLength < 0 || frameLength > 0xFF) {
+                throw new IllegalArgumentException("Invalid frame length: " + frameLength);
+            }
+            if (data.length != Math.min(frameLength, MAX_RAW_LENGTH)) {
+                throw new IllegalArgumentException("Raw data length does not match frame length");
+            }
+            this.frameLength = frameLength;
+            this.data = data.clone();
+        }
+
+        /**
+         * Whether the firmware cut the frame down to MAX_RAW_LENGTH bytes
+         */
+        public boolean isTruncated() {
+            return frameLength > data.length;
+        }
+
+        /**
+         * Frame bytes as space-separated uppercase hex, e.g. "DE AD BE EF"
+         */
+        public String toHex() {
+            StringBuilder sb = new StringBuilder(data.length * 3);
+            for (int i = 0; i < data.length; i++) {
+                if (i > 0) {
+                    sb.append(' ');
+                }
+                sb.append(String.format("%02X", data[i] & 0xFF));
+            }
+            return sb.toString();
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            byte[] result = new byte[2 + data.length];
+            result[0] = MessageType.RAW.getValue();
+            result[1] = (byte) frameLength;
+            System.arraycopy(data, 0, result, 2, data.length);
+            return result;
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            RawMessage that = (RawMessage) obj;
+            return frameLength == that.frameLength && java.util.Arrays.equals(data, that.data);
+        }
+
+        @Override
+        public int hashCode() {
+            return 31 * frameLength + java.util.Arrays.hashCode(data);
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "RawMessage{frameLength=" + frameLength + ", data=" + toHex() + "}";
+        }
+    }
+
+    /**
+     * Transient bridge status, e.g. a transmit deferred while the radio receives or
+     * held for the duty cycle.
+     * Only ever sent bridge -> app, and only while connected (never buffered).
+     */
+    public static class StatusMessage extends Message {
+        public static final int RADIO_BUSY = 0x01; // Transmit deferred until a reception completes
+        public static final int TX_RESUMED = 0x02; // The deferred transmit went out
+        public static final int DUTY_CYCLE_LIMITED = 0x03; // Transmit held until the duty-cycle budget allows it
+
+        public final int code;
+        public final byte seq; // Seq of the deferred or held text, 0 for other types
+
+        public StatusMessage(int code, byte seq) {
+            super(MessageType.STATUS);
+            if (code != RADIO_BUSY && code != TX_RESUMED && code != DUTY_CYCLE_LIMITED) {
+                throw new IllegalArgumentException("Unknown status code: " + code);
+            }
+            this.code = code;
+            this.seq = seq;
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            return new byte[]{MessageType.STATUS.getValue(), (byte) code, seq};
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            StatusMessage that = (StatusMessage) obj;
+            return code == that.code && seq == that.seq;
+        }
+
+        @Override
+        public int hashCode() {
+            return 31 * code + seq;
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "StatusMessage{code=" + code + ", seq=" + (seq & 0xFF) + "}";
+        }
+    }
+
+    /**
+     * LoRa frame the bridge dropped because it could not decode it, with the reason.
+     * Only sent by firmware built with DEBUG_DECODE_FAILURES, bridge -> app, while connected.
+     */
+    public static class DecodeFailureMessage extends Message {
+        public static final int MALFORMED = 0x01;    // Known type, but the fields don't decode
+        public static final int TRUNCATED = 0x02;    // Frame not read completely from the radio
+        public static final int UNKNOWN_TYPE = 0x03; // Type byte the firmware doesn't know
+        public static final int REJECTED = 0x04;     // Decodes, but not accepted on air (protocol profile, BLE-only type)
+        public static final int CRC_MISMATCH = 0x05; // CRC byte doesn't match the frame (corrupted on air)
+
+        public final int reason;
+        public final int frameLength; // Original frame length on air
+        public final byte[] data;     // First min(frameLength, MAX_DECODE_FAILURE_LENGTH) bytes, zero past a truncated read
+
+        public DecodeFailureMessage(int reason, int frameLength, byte[] data) {
+            super(MessageType.DECODE_FAILURE);
+            if (reason < MALFORMED || reason > CRC_MISMATCH) {
+                throw new IllegalArgumentException("Unknown decode failure reason: " + reason);
+            }
+            if (frameLength < 0 || frameLength > 0xFF) {
+                throw new IllegalArgumentException("Invalid frame length: " + frameLength);
+            }
+            if (data.length != Math.min(frameLength, MAX_DECODE_FAILURE_LENGTH)) {
+                throw new IllegalArgumentException("Decode failure data length does not match frame length");
+            }
+            this.reason = reason;
+            this.frameLength = frameLength;
+            this.data = data.clone();
+        }
+
+        /**
+         * Reason as a short label for logs and bug reports
+         */
+        public String reasonName() {
+            return switch (reason) {
+                case MALFORMED -> "malformed";
+                case TRUNCATED -> "truncated";
+                case UNKNOWN_TYPE -> "unknown type";
+                case REJECTED -> "rejected";
+                default -> "CRC mismatch";
+            };
+        }
+
+        /**
+         * Frame bytes as space-separated uppercase hex, e.g. "DE AD BE EF"
+         */
+        public String toHex() {
+            StringBuilder sb = new StringBuilder(data.length * 3);
+            for (int i = 0; i < data.length; i++) {
+                if (i > 0) {
+                    sb.append(' ');
+                }
+                sb.append(String.format("%02X", data[i] & 0xFF));
+            }
+            return sb.toString();
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            byte[] result = new byte[3 + data.length];
+            result[0] = MessageType.DECODE_FAILURE.getValue();
+            result[1] = (byte) reason;
+            result[2] = (byte) frameLength;
+            System.arraycopy(data, 0, result, 3, data.length);
+            return result;
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            DecodeFailureMessage that = (DecodeFailureMessage) obj;
+            return reason == that.reason && frameLength == that.frameLength
+                    && java.util.Arrays.equals(data, that.data);
+        }
+
+        @Override
+        public int hashCode() {
+            return 31 * (31 * reason + frameLength) + java.util.Arrays.hashCode(data);
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "DecodeFailureMessage{reason=" + reasonName() + ", frameLength=" + frameLength + ", data="
+                    + toHex() + "}";
+        }
+    }
+
+    /**
+     * Whether a text sent from this app reached the peer: DELIVERED once its ACK
+     * arrived, FAILED after the last retransmission went unACKed.
+     * Only ever sent bridge -> app; buffered while disconnected, like ACKs.
+     */
+    public static class DeliveryReportMessage extends Message {
+        public static final int DELIVERED = 0x01; // The peer ACKed the text
+        public static final int FAILED = 0x02;    // No ACK after the last retransmission
+        public static final int RELAYED = 0x03;   // A relay's hop ACK (ACK_SEMANTICS_HOP): taken on, not known to have arrived
+        public static final int SENT = 0x04;      // Sent once, never confirmed (bridge built with FIRE_AND_FORGET)
+
+        public final int outcome;
+        public final byte seq; // Seq of the text
+
+        public DeliveryReportMessage(int outcome, byte seq) {
+            super(MessageType.DELIVERY_REPORT);
+            if (outcome != DELIVERED && outcome != FAILED && outcome != RELAYED && outcome != SENT) {
+                throw new IllegalArgumentException("Unknown delivery outcome: " + outcome);
+            }
+            this.outcome = outcome;
+            this.seq = seq;
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            return new byte[]{MessageType.DELIVERY_REPORT.getValue(), (byte) outcome, seq};
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            DeliveryReportMessage that = (DeliveryReportMessage) obj;
+            return outcome == that.outcome && seq == that.seq;
+        }
+
+        @Override
+        public int hashCode() {
+            return 31 * outcome + seq;
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            String name = switch (outcome) {
+                case DELIVERED -> "delivered";
+                case RELAYED -> "relayed";
+                case SENT -> "sent";
+                default -> "failed";
+            };
+            return "DeliveryReportMessage{outcome=" + name + ", seq="
+                    + (seq & 0xFF) + "}";
+        }
+    }
+
+    /**
+     * RSSI and SNR at which the bridge received a text over LoRa, sent right after
+     * the text when the bridge is built with LINK_QUALITY_REPORTS.
+     * Only ever sent bridge -> app; buffered while disconnected, like ACKs.
+     */
+    public static class LinkQualityMessage extends Message {
+        public final byte seq; // Seq of the received text
+        public final int rssi; // dBm
+        public final int snrQuarterDb; // SNR in 0.25 dB steps
+
+        public LinkQualityMessage(byte seq, int rssi, int snrQuarterDb) {
+            super(MessageType.LINK_QUALITY);
+            if (rssi < Short.MIN_VALUE || rssi > Short.MAX_VALUE) {
+                throw new IllegalArgumentException("RSSI out of range: " + rssi);
+            }
+            if (snrQuarterDb < Byte.MIN_VALUE || snrQuarterDb > Byte.MAX_VALUE) {
+                throw new IllegalArgumentException("SNR out of range: " + snrQuarterDb);
+            }
+            this.seq = seq;
+            this.rssi = rssi;
+            this.snrQuarterDb = snrQuarterDb;
+        }
+
+        /**
+         * SNR in dB
+         */
+        public float getSnrDb() {
+            return snrQuarterDb / 4.0f;
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            ByteBuffer buf = allocate(5);
+            buf.put(MessageType.LINK_QUALITY.getValue());
+            buf.put(seq);
+            buf.putShort((short) rssi);
+            buf.put((byte) snrQuarterDb);
+            return buf.array();
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            LinkQualityMessage that = (LinkQualityMessage) obj;
+            return seq == that.seq && rssi == that.rssi && snrQuarterDb == that.snrQuarterDb;
+        }
+
+        @Override
+        public int hashCode() {
+            return (31 * seq + rssi) * 31 + snrQuarterDb;
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "LinkQualityMessage{seq=" + (seq & 0xFF) + ", rssi=" + rssi + ", snr=" + getSnrDb() + "}";
+        }
+    }
+
+    /**
+     * Battery level of a remote node, received over LoRa and ACKed by the bridge
+     * like a text. Percent is 0-100, the cell voltage is in millivolts.
+     */
+    public static class BatteryMessage extends Message {
+        public static final int MAX_PERCENT = 100;
+
+        public final byte seq;
+        public final int percent;
+        public final int millivolts;
+
+        public BatteryMessage(byte seq, int percent, int millivolts) {
+            super(MessageType.BATTERY);
+            if (percent < 0 || percent > MAX_PERCENT) {
+                throw new IllegalArgumentException("Battery percent out of range: " + percent);
+            }
+            if (millivolts < 0 || millivolts > 0xFFFF) {
+                throw new IllegalArgumentException("Battery voltage out of range: " + millivolts + " mV");
+            }
+            this.seq = seq;
+            this.percent = percent;
+            this.millivolts = millivolts;
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            ByteBuffer buf = allocate(5);
+            buf.put(MessageType.BATTERY.getValue());
+            buf.put(seq);
+            buf.put((byte) percent);
+            buf.putShort((short) millivolts);
+            return buf.array();
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            BatteryMessage that = (BatteryMessage) obj;
+            return seq == that.seq && percent == that.percent && millivolts == that.millivolts;
+        }
+
+        @Override
+        public int hashCode() {
+            return (31 * seq + percent) * 31 + millivolts;
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "BatteryMessage{seq=" + (seq & 0xFF) + ", percent=" + percent + ", millivolts=" + millivolts + "}";
+        }
+    }
+
+    /**
+     * Emergency position, sent by the bridge several times in a row. Each repeat is
+     * handed to the app (only the first one is ACKed), so the same seq can show up
+     * more than once. Coordinates are degrees * 1_000_000.
+     */
+    public static class SosMessage extends Message {
+        public final byte seq;
+        public final int lat;
+        public final int lon;
+
+        public SosMessage(byte seq, int lat, int lon) {
+            super(MessageType.SOS);
+            this.seq = seq;
+            this.lat = lat;
+            this.lon = lon;
+        }
+
+        @Override
+        protected byte[] serializeBody() {
+            ByteBuffer buf = allocate(10);
+            buf.put(MessageType.SOS.getValue());
+            buf.put(seq);
+            buf.putInt(lat);
+            buf.putInt(lon);
+            return buf.array();
+        }
+
+        @Override
+        public boolean equals(Object obj) {
+            if (this == obj)
+                return true;
+            if (obj == null || getClass() != obj.getClass())
+                return false;
+            SosMessage that = (SosMessage) obj;
+            return seq == that.seq && lat == that.lat && lon == that.lon;
+        }
+
+        @Override
+        public int hashCode() {
+            return (31 * seq + lat) * 31 + lon;
+        }
+
+        @NonNull
+        @Override
+        public String toString() {
+            return "SosMessage{seq=" + (seq & 0xFF) + ", lat=" + lat + ", lon=" + lon + "}";
+        }
+    }
+
+    /**
+     * Delivery priority of a text message, carried in bits 1-2 of its flags byte.
+     * NORMAL keeps the behaviour of messages without a priority; HIGH and CRITICAL
+     * jump the bridge's LoRa send queue and are retransmitted (CRITICAL more often).
+     */
+    public enum Priority {
+        NORMAL, HIGH, CRITICAL;
+
+        private static final int FLAG_SHIFT = 1;
+        private static final int FLAG_MASK = 0x06;
+
+        int toFlags() {
+            return ordinal() << FLAG_SHIFT;
+        }
+
+        static Priority fromFlags(int flags) {
+            int value = (flags & FLAG_MASK) >> FLAG_SHIFT;
+            return value >= CRITICAL.ordinal() ? CRITICAL : values()[value]; // 3 is reserved
+        }
+    }
+
+    public static abstract class Message {
+        public final MessageType type;
+
+        protected Message(MessageType type) {
+            this.type = type;
+        }
+
+        /**
+         * Decode a serialized message, CRC-8 last
+         * Throws IllegalArgumentException for a CRC mismatch as for any other malformed message.
+         */
+        public static Message deserialize(byte[] serialized) throws IllegalArgumentException {
+            if (serialized.length < 1 + CRC_LENGTH) {
+                throw new IllegalArgumentException("Data too short");
+            }
+            int bodyLength = serialized.length - CRC_LENGTH;
+            if (crc8(serialized, bodyLength) != (serialized[bodyLength] & 0xFF)) {
+                throw new IllegalArgumentException("CRC mismatch");
+            }
+            byte[] data = java.util.Arrays.copyOf(serialized, bodyLength);
+            MessageType type = MessageType.fromByte(data[0]);
+            return switch (type) {
+                case TEXT -> deserializeText(data);
+                case ACK -> deserializeAck(data);
+                case BATTERY -> deserializeBattery(data);
+                case SOS -> deserializeSos(data);
+                case IDENTIFY -> deserializeIdentify(data);
+                case IDENTIFY_RESPONSE -> deserializeIdentifyResponse(data);
+                case CONFIG_REQUEST -> deserializeConfigRequest(data);
+                case CONFIG_RESPONSE -> deserializeConfigResponse(data);
+                case RAW -> deserializeRaw(data);
+                case STATUS -> deserializeStatus(data);
+                case DECODE_FAILURE -> deserializeDecodeFailure(data);
+                case DELIVERY_REPORT -> deserializeDeliveryReport(data);
+                case LINK_QUALITY -> deserializeLinkQuality(data);
+            };
+        }
+
+        private static TextMessage deserializeText(byte[] data) {
+            if (data.length < 5) {
+                throw new IllegalArgumentException("Data too short for TextMessage header");
+            }
+            byte seq = data[1];
+            int codeCount = data[2] & 0xFF; // 6-bit codes, shift codes included
+            int packedLen = data[3] & 0xFF; // Packed byte count
+            if (codeCount > MAX_TEXT_LENGTH) {
+                throw new IllegalArgumentException("Text too long: " + codeCount + " codes");
+            }
+            if (packedLen != (codeCount * 6 + 7) / 8) {
+                throw new IllegalArgumentException("Packed length " + packedLen + " doesn't match " + codeCount + " codes");
+            }
+            if (data.length < 5 + packedLen) {
+                throw new IllegalArgumentException("Data too short for packed text + flags byte");
+            }
+            byte[] packedBytes = new byte[packedLen];
+            System.arraycopy(data, 4, packedBytes, 0, packedLen);
+            String text = unpackText(packedBytes, codeCount, CHARSET);
+            int flags = data[4 + packedLen] & 0xFF;
+            Priority priority = Priority.fromFlags(flags);
+            int fragmentLength = (flags & TextMessage.FLAG_FRAGMENT) != 0 ? TextMessage.FRAGMENT_HEADER_LENGTH : 0;
+            if (data.length < 5 + packedLen + fragmentLength) {
+                throw new IllegalArgumentException("Data too short for the fragment header");
+            }
+            TextMessage whole = deserializeWholeText(data, seq, text, packedLen, flags, priority, fragmentLength);
+            if (fragmentLength == 0) {
+                return whole;
+            }
+            int n = data.length;
+            int fragmentCount = data[n - 1] & 0xFF;
+            int fragmentIndex = data[n - 2] & 0xFF;
+            if (fragmentIndex >= fragmentCount) {
+                throw new IllegalArgumentException("Fragment " + fragmentIndex + " of " + fragmentCount);
+            }
+            return new TextMessage(whole, seq, text, data[n - 3] & 0xFF, fragmentIndex, fragmentCount);
+        }
+
+        /**
+         * The text and its GPS data, before the fragment header of fragmentLength bytes
+         */
+        private static TextMessage deserializeWholeText(byte[] data, byte seq, String text, int packedLen, int flags,
+                Priority priority, int fragmentLength) {
+            boolean hasGps = (flags & 0x01) != 0;
+            if (hasGps) {
+                if (data.length < 5 + packedLen + 8 + fragmentLength) {
+                    throw new IllegalArgumentException("Data too short for GPS data");
+                }
+                ByteBuffer buf = wrap(data, 5 + packedLen, 8);
+                int lat = buf.getInt();
+                int lon = buf.getInt();
+                if ((flags & TextMessage.FLAG_GPS_FIELDS) == 0) {
+                    return new TextMessage(seq, text, lat, lon, priority);
+                }
+
+                // Read the fields this version knows; later ones follow them and are ignored
+                int pos = 13 + packedLen;
+                if (data.length < pos + 1 + fragmentLength) {
+                    throw new IllegalArgumentException("Data too short for the GPS fields byte");
+                }
+                int gpsFields = data[pos] & TextMessage.GPS_FIELDS_KNOWN;
+                if (data.length < pos + 1 + TextMessage.gpsFieldsLength(gpsFields) + fragmentLength) {
+                    throw new IllegalArgumentException("Data too short for the GPS fields it announces");
+                }
+                ByteBuffer fields = wrap(data, pos + 1, TextMessage.gpsFieldsLength(gpsFields));
+                int altitude = (gpsFields & TextMessage.GPS_FIELD_ALTITUDE) != 0 ? fields.getShort() : 0;
+                int speed = (gpsFields & TextMessage.GPS_FIELD_SPEED) != 0 ? fields.getShort() & 0xFFFF : 0;
+                return new TextMessage(seq, text, lat, lon, priority, gpsFields, altitude, speed);
+            } else {
+                return new TextMessage(seq, text, priority);
+            }
+        }
+
+        private static AckMessage deserializeAck(byte[] data) {
+            if (data.length < 2) {
+                throw new IllegalArgumentException("Data too short for AckMessage");
             }
             byte seq = data[1];
             return new AckMessage(seq);
         }
 
-        public abstract byte[] serialize();
+        private static IdentifyMessage deserializeIdentify(byte[] data) {
+            if (data.length < 2) {
+                throw new IllegalArgumentException("Data too short for IdentifyMessage");
+            }
+            return new IdentifyMessage(data[1] & 0xFF);
+        }
+
+        private static IdentifyResponseMessage deserializeIdentifyResponse(byte[] data) {
+            if (data.length < 10) {
+                throw new IllegalArgumentException("Data too short for IdentifyResponseMessage");
+            }
+            long frequency = wrap(data, 2, 4).getInt() & 0xFFFFFFFFL;
+            return new IdentifyResponseMessage(data[1] & 0xFF, frequency, data[6] & 0xFF, data[7] & 0xFF,
+                    data[8] & 0xFF, data[9]);
+        }
+
+        private static ConfigRequestMessage deserializeConfigRequest(byte[] data) {
+            if (data.length < 2) {
+                throw new IllegalArgumentException("Data too short for ConfigRequestMessage");
+            }
+            return new ConfigRequestMessage(data[1] & 0xFF);
+        }
+
+        private static ConfigResponseMessage deserializeConfigResponse(byte[] data) {
+            if (data.length < ConfigResponseMessage.LENGTH - CRC_LENGTH) {
+                throw new IllegalArgumentException("Data too short for ConfigResponseMessage");
+            }
+            ByteBuffer buf = wrap(data, 1, ConfigResponseMessage.LENGTH - CRC_LENGTH - 1);
+            int nodeId = buf.get() & 0xFF;
+            long frequency = buf.getInt() & 0xFFFFFFFFL;
+            int spreadingFactor = buf.get() & 0xFF;
+            int bandwidthIndex = buf.get() & 0xFF;
+            int codingRate = buf.get() & 0xFF;
+            int txPower = buf.get();
+            int syncWord = buf.get() & 0xFF;
+            int preambleLength = buf.getShort() & 0xFFFF;
+            int firmwareMajor = buf.get() & 0xFF;
+            int firmwareMinor = buf.get() & 0xFF;
+            int firmwarePatch = buf.get() & 0xFF;
+            int protocolProfile = buf.get() & 0xFF;
+            int charset = buf.get() & 0xFF;
+            return new ConfigResponseMessage(nodeId, frequency, spreadingFactor, bandwidthIndex, codingRate, txPower,
+                    syncWord, preambleLength, firmwareMajor, firmwareMinor, firmwarePatch, protocolProfile, charset);
+        }
+
+        private static RawMessage deserializeRaw(byte[] data) {
+            if (data.length < 2) {
+                throw new IllegalArgumentException("Data too short for RawMessage header");
+            }
+            int frameLength = data[1] & 0xFF;
+            int copyLen = Math.min(frameLength, MAX_RAW_LENGTH);
+            if (data.length < 2 + copyLen) {
+                throw new IllegalArgumentException("Data too short for raw frame bytes");
+            }
+            byte[] frame = new byte[copyLen];
+            System.arraycopy(data, 2, frame, 0, copyLen);
+            return new RawMessage(frameLength, frame);
+        }
+
+        private static StatusMessage deserializeStatus(byte[] data) {
+            if (data.length < 3) {
+                throw new IllegalArgumentException("Data too short for StatusMessage");
+            }
+            return new StatusMessage(data[1] & 0xFF, data[2]);
+        }
+
+        private static DecodeFailureMessage deserializeDecodeFailure(byte[] data) {
+            if (data.length < 3) {
+                throw new IllegalArgumentException("Data too short for DecodeFailureMessage header");
+            }
+            int frameLength = data[2] & 0xFF;
+            int copyLen = Math.min(frameLength, MAX_DECODE_FAILURE_LENGTH);
+            if (data.length < 3 + copyLen) {
+                throw new IllegalArgumentException("Data too short for decode failure frame bytes");
+            }
+            byte[] frame = new byte[copyLen];
+            System.arraycopy(data, 3, frame, 0, copyLen);
+            return new DecodeFailureMessage(data[1] & 0xFF, frameLength, frame);
+        }
+
+        private static DeliveryReportMessage deserializeDeliveryReport(byte[] data) {
+            if (data.length < 3) {
+                throw new IllegalArgumentException("Data too short for DeliveryReportMessage");
+            }
+            return new DeliveryReportMessage(data[1] & 0xFF, data[2]);
+        }
+
+        private static BatteryMessage deserializeBattery(byte[] data) {
+            if (data.length < 5) {
+                throw new IllegalArgumentException("Data too short for BatteryMessage");
+            }
+            ByteBuffer buf = wrap(data, 3, 2);
+            return new BatteryMessage(data[1], data[2] & 0xFF, buf.getShort() & 0xFFFF);
+        }
+
+        private static SosMessage deserializeSos(byte[] data) {
+            if (data.length < 10) {
+                throw new IllegalArgumentException("Data too short for SosMessage");
+            }
+            ByteBuffer buf = wrap(data, 2, 8);
+            return new SosMessage(data[1], buf.getInt(), buf.getInt());
+        }
+
+        private static LinkQualityMessage deserializeLinkQuality(byte[] data) {
+            if (data.length < 5) {
+                throw new IllegalArgumentException("Data too short for LinkQualityMessage");
+            }
+            ByteBuffer buf = wrap(data, 2, 3);
+            return new LinkQualityMessage(data[1], buf.getShort(), buf.get());
+        }
+
+        /**
+         * Encode the message followed by its CRC-8
+         */
+        public final byte[] serialize() {
+            byte[] body = serializeBody();
+            byte[] data = java.util.Arrays.copyOf(body, body.length + CRC_LENGTH);
+            data[body.length] = (byte) crc8(body, body.length);
+            return data;
+        }
+
+        /**
+         * The message without its CRC
+         */
+        protected abstract byte[] serializeBody();
     }
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+
+/**
+ * Frequency and TX power of a bridge via the control characteristic (0x567A).
+ * Request: write [0x0B] to query, or [0x0B][frequency u32][tx power i8] to set both
+ * (until the bridge reboots). Response: [0x8B][frequency u32][tx power i8], little-endian.
+ * The peer bridge must be switched to the same frequency. See protocol.md for details.
+ */
+public class RadioSettings {
+
+    public static final byte OPCODE = 0x0B;
+    public static final byte RESPONSE = (byte) 0x8B;
+    public static final int MIN_TX_POWER = 2; // dBm, range of the radio
+    public static final int MAX_TX_POWER = 20;
+    private static final int LENGTH = 6;
+
+    public final long frequency; // Hz
+    public final int txPower; // dBm
+
+    private RadioSettings(long frequency, int txPower) {
+        this.frequency = frequency;
+        this.txPower = txPower;
+    }
+
+    /**
+     * Command that reads the frequency and TX power in effect
+     */
+    public static byte[] query() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that sets the frequency and TX power. The bridge still rejects a
+     * frequency outside its band, a power above the EIRP limit, or a change while
+     * it awaits an ACK.
+     *
+     * @throws IllegalArgumentException if the frequency or TX power is out of range
+     */
+    public static byte[] request(long frequency, int txPower) throws IllegalArgumentException {
+        if (frequency <= 0 || frequency > 0xFFFFFFFFL) {
+            throw new IllegalArgumentException("Frequency out of range: " + frequency);
+        }
+        if (txPower < MIN_TX_POWER || txPower > MAX_TX_POWER) {
+            throw new IllegalArgumentException("TX power out of range: " + txPower + " dBm");
+        }
+        ByteBuffer buf = Protocol.allocate(LENGTH);
+        buf.put(OPCODE);
+        buf.putInt((int) frequency);
+        buf.put((byte) txPower);
+        return buf.array();
+    }
+
+    /**
+     * Parse a radio settings response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a radio settings response
+     */
+    public static RadioSettings parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for radio settings");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a radio settings response: " + response[0]);
+        }
+        ByteBuffer buf = Protocol.wrap(response, 1, LENGTH - 1);
+        return new RadioSettings(buf.getInt() & 0xFFFFFFFFL, buf.get());
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "RadioSettings{freq=" + frequency + ", txPower=" + txPower + "}";
+    }
+}
@@ -0,0 +1,34 @@
+package lora;
+
+/**
+ * Remote ESP32 restart via the control characteristic (0x567A).
+ * Request: [0x05]["BOOT"] - the confirmation bytes guard against accidental writes.
+ * Response: [0x85] just before the bridge disconnects and restarts.
+ * See protocol.md for the cleanup sequence and what is lost.
+ */
+public class Reboot {
+
+    public static final byte OPCODE = 0x05;
+    public static final byte RESPONSE = (byte) 0x85;
+    private static final byte[] MAGIC = {'B', 'O', 'O', 'T'};
+
+    private Reboot() {
+    }
+
+    /**
+     * Command that restarts the bridge
+     */
+    public static byte[] request() {
+        byte[] command = new byte[1 + MAGIC.length];
+        command[0] = OPCODE;
+        System.arraycopy(MAGIC, 0, command, 1, MAGIC.length);
+        return command;
+    }
+
+    /**
+     * Whether a control response acknowledges the reboot (the link drops right after)
+     */
+    public static boolean isAcknowledged(byte[] response) {
+        return response.length >= 1 && response[0] == RESPONSE;
+    }
+}
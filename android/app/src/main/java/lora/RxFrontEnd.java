@@ -0,0 +1,74 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+/**
+ * ESP32 receiver front end settings via the control characteristic (0x567A).
+ * Request: write [0x03] to query, or [0x03][rx boost u8][lna gain u8] to change.
+ * Response: [0x83][rx boost u8][lna gain u8].
+ * See protocol.md for details.
+ */
+public class RxFrontEnd {
+
+    public static final byte OPCODE = 0x03;
+    public static final byte RESPONSE = (byte) 0x83;
+    private static final int LENGTH = 3;
+
+    /** LNA gain setting that leaves the gain to the radio's AGC */
+    public static final int GAIN_AGC = 0;
+    /** Fixed LNA gain range: 1 = G1 (maximum) .. 6 = G6 (minimum) */
+    public static final int GAIN_MAX = 1;
+    public static final int GAIN_MIN = 6;
+
+    public final boolean rxBoost;
+    public final int lnaGain;
+
+    private RxFrontEnd(boolean rxBoost, int lnaGain) {
+        this.rxBoost = rxBoost;
+        this.lnaGain = lnaGain;
+    }
+
+    /**
+     * Command that reads the current settings
+     */
+    public static byte[] query() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that sets RX boost and LNA gain (GAIN_AGC, or GAIN_MAX..GAIN_MIN)
+     *
+     * @throws IllegalArgumentException if the gain is out of range
+     */
+    public static byte[] request(boolean rxBoost, int lnaGain) throws IllegalArgumentException {
+        if (lnaGain < GAIN_AGC || lnaGain > GAIN_MIN) {
+            throw new IllegalArgumentException("LNA gain out of range: " + lnaGain);
+        }
+        return new byte[]{OPCODE, (byte) (rxBoost ? 1 : 0), (byte) lnaGain};
+    }
+
+    /**
+     * Parse a front end response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a front end response
+     */
+    public static RxFrontEnd parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for RX front end");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not an RX front end response: " + response[0]);
+        }
+        return new RxFrontEnd(response[1] != 0, response[2] & 0xFF);
+    }
+
+    public boolean isAgc() {
+        return lnaGain == GAIN_AGC;
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "RxFrontEnd{rxBoost=" + rxBoost + ", lnaGain=" + (isAgc() ? "AGC" : "G" + lnaGain) + "}";
+    }
+}
@@ -0,0 +1,97 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+import java.nio.ByteBuffer;
+
+/**
+ * Spectrum scan result returned by the ESP32 control characteristic (0x567A).
+ * Request: write [0x02] for the whole band, or [0x02][start u32][stop u32][step u32].
+ * Response: [0x82][first frequency u32][step u32][count u8][RSSI dBm i8 x count].
+ * See protocol.md for details.
+ */
+public class SpectrumScan {
+
+    public static final byte OPCODE = 0x02;
+    public static final byte RESPONSE = (byte) 0x82;
+    private static final int HEADER_LENGTH = 10;
+
+    public final long firstFrequency; // Hz
+    public final long step; // Hz
+    public final int[] rssi; // dBm per frequency
+
+    private SpectrumScan(long firstFrequency, long step, int[] rssi) {
+        this.firstFrequency = firstFrequency;
+        this.step = step;
+        this.rssi = rssi;
+    }
+
+    /**
+     * Command that scans the whole legal band at the firmware's default step
+     */
+    public static byte[] request() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that scans startHz..stopHz (inclusive, clamped to the band by the firmware)
+     */
+    public static byte[] request(long startHz, long stopHz, long stepHz) {
+        ByteBuffer buf = Protocol.allocate(13);
+        buf.put(OPCODE);
+        buf.putInt((int) startHz).putInt((int) stopHz).putInt((int) stepHz);
+        return buf.array();
+    }
+
+    /**
+     * Parse a spectrum scan response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a complete scan result
+     */
+    public static SpectrumScan parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < HEADER_LENGTH) {
+            throw new IllegalArgumentException("Data too short for spectrum scan");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a spectrum scan response: " + response[0]);
+        }
+        ByteBuffer buf = Protocol.wrap(response, 1, HEADER_LENGTH - 1);
+        long firstFrequency = buf.getInt() & 0xFFFFFFFFL;
+        long step = buf.getInt() & 0xFFFFFFFFL;
+        int count = buf.get() & 0xFF;
+        if (response.length < HEADER_LENGTH + count) {
+            throw new IllegalArgumentException("Data too short for " + count + " scan points");
+        }
+        int[] rssi = new int[count];
+        for (int i = 0; i < count; i++) {
+            rssi[i] = response[HEADER_LENGTH + i];
+        }
+        return new SpectrumScan(firstFrequency, step, rssi);
+    }
+
+    /**
+     * Frequency of scan point i in Hz
+     */
+    public long frequencyAt(int i) {
+        return firstFrequency + i * step;
+    }
+
+    /**
+     * Frequency with the lowest RSSI floor (the quietest channel)
+     */
+    public long quietestFrequency() {
+        int best = 0;
+        for (int i = 1; i < rssi.length; i++) {
+            if (rssi[i] < rssi[best]) {
+                best = i;
+            }
+        }
+        return frequencyAt(best);
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "SpectrumScan{first=" + firstFrequency + ", step=" + step + ", points=" + rssi.length + "}";
+    }
+}
@@ -0,0 +1,65 @@
+package lora;
+
+import androidx.annotation.NonNull;
+
+/**
+ * Spreading factor of a bridge's data frames via the control characteristic (0x567A).
+ * Request: write [0x0A] to query, or [0x0A][sf] to set it (until the bridge reboots).
+ * Response: [0x8A][sf]. The peer bridge must be switched to the same factor.
+ * See protocol.md for details.
+ */
+public class SpreadingFactor {
+
+    public static final byte OPCODE = 0x0A;
+    public static final byte RESPONSE = (byte) 0x8A;
+    public static final int MIN = 7;
+    public static final int MAX = 12;
+    private static final int LENGTH = 2;
+
+    public final int spreadingFactor;
+
+    private SpreadingFactor(int spreadingFactor) {
+        this.spreadingFactor = spreadingFactor;
+    }
+
+    /**
+     * Command that reads the spreading factor in effect
+     */
+    public static byte[] query() {
+        return new byte[]{OPCODE};
+    }
+
+    /**
+     * Command that sets the spreading factor (7-12). The bridge may still reject it,
+     * e.g. while awaiting an ACK or if the airtime would break the band's limits.
+     *
+     * @throws IllegalArgumentException if the factor is out of range
+     */
+    public static byte[] request(int spreadingFactor) throws IllegalArgumentException {
+        if (spreadingFactor < MIN || spreadingFactor > MAX) {
+            throw new IllegalArgumentException("Spreading factor out of range: " + spreadingFactor);
+        }
+        return new byte[]{OPCODE, (byte) spreadingFactor};
+    }
+
+    /**
+     * Parse a spreading factor response as notified on the control characteristic
+     *
+     * @throws IllegalArgumentException if the response is not a spreading factor response
+     */
+    public static SpreadingFactor parse(byte[] response) throws IllegalArgumentException {
+        if (response.length < LENGTH) {
+            throw new IllegalArgumentException("Data too short for spreading factor");
+        }
+        if (response[0] != RESPONSE) {
+            throw new IllegalArgumentException("Not a spreading factor response: " + response[0]);
+        }
+        return new SpreadingFactor(response[1] & 0xFF);
+    }
+
+    @NonNull
+    @Override
+    public String toString() {
+        return "SpreadingFactor{SF" + spreadingFactor + "}";
+    }
+}
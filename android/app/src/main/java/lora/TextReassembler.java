@@ -0,0 +1,115 @@
+package lora;
+
+import java.util.LinkedHashMap;
+
+/**
+ * Reassembles texts that the sending app split into several LoRa frames (see
+ * {@link Protocol.TextMessage#fragments}). Fragments may arrive in any order and
+ * more than once: each fragment's seq is ACKed and retransmitted separately, and
+ * a bridge without duplicate detection delivers a retransmitted copy again.
+ * At most maxPendingFragments fragments of incomplete texts are held; past that
+ * the oldest incomplete text is dropped, since its missing fragments are most
+ * likely lost for good.
+ */
+public class TextReassembler {
+
+    public static final int DEFAULT_MAX_PENDING_FRAGMENTS = 32;
+
+    private final int maxPendingFragments;
+    // Incomplete texts by fragment id, oldest first
+    private final LinkedHashMap<Integer, Protocol.TextMessage[]> pending = new LinkedHashMap<>();
+    private int pendingFragments;
+
+    public TextReassembler() {
+        this(DEFAULT_MAX_PENDING_FRAGMENTS);
+    }
+
+    /**
+     * @throws IllegalArgumentException if maxPendingFragments is below 1
+     */
+    public TextReassembler(int maxPendingFragments) throws IllegalArgumentException {
+        if (maxPendingFragments < 1) {
+            throw new IllegalArgumentException("maxPendingFragments must be at least 1");
+        }
+        this.maxPendingFragments = maxPendingFragments;
+    }
+
+    /**
+     * Feed one received text.
+     *
+     * @return the whole text once its last missing fragment arrives (with the seq and
+     * GPS data of its first fragment), a text that isn't a fragment unchanged, or null
+     * if fragments are still missing or this one is a duplicate
+     */
+    public Protocol.TextMessage accept(Protocol.TextMessage text) {
+        if (!text.isFragment()) {
+            return text;
+        }
+        if (text.fragmentCount > maxPendingFragments) {
+            return null; // Could never be completed
+        }
+
+        Protocol.TextMessage[] fragments = pending.get(text.fragmentId);
+        if (fragments != null && fragments.length != text.fragmentCount) {
+            // Id reused after the seq wrapped: the old text won't complete any more
+            drop(text.fragmentId);
+            fragments = null;
+        }
+        if (fragments == null) {
+            fragments = new Protocol.TextMessage[text.fragmentCount];
+            pending.put(text.fragmentId, fragments);
+        }
+        if (fragments[text.fragmentIndex] != null) {
+            return null; // Duplicate
+        }
+        fragments[text.fragmentIndex] = text;
+        pendingFragments++;
+
+        for (Protocol.TextMessage fragment : fragments) {
+            if (fragment == null) {
+                evictOldest();
+                return null;
+            }
+        }
+
+        drop(text.fragmentId);
+        StringBuilder whole = new StringBuilder();
+        for (Protocol.TextMessage fragment : fragments) {
+            whole.append(fragment.text);
+        }
+        return new Protocol.TextMessage(fragments[0], fragments[0].seq, whole.toString(), 0, 0, 0);
+    }
+
+    /**
+     * Number of fragments held for incomplete texts
+     */
+    public int getPendingFragmentCount() {
+        return pendingFragments;
+    }
+
+    /**
+     * Drop every incomplete text
+     */
+    public void reset() {
+        pending.clear();
+        pendingFragments = 0;
+    }
+
+    private void drop(int fragmentId) {
+        Protocol.TextMessage[] fragments = pending.remove(fragmentId);
+        if (fragments == null) {
+            return;
+        }
+        for (Protocol.TextMessage fragment : fragments) {
+            if (fragment != null) {
+                pendingFragments--;
+            }
+        }
+    }
+
+    private void evictOldest() {
+        while (pendingFragments > maxPendingFragments) {
+            drop(pending.keySet().iterator().next());
+        }
+    }
+}
@@ -0,0 +1,54 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the audit log command and page parsing
+ */
+public class AuditLogTest {
+
+    private static final byte[] SAMPLE_PAGE = {
+            (byte) 0x84,
+            70, 0, 0, 0, // 70 entries recorded since boot
+            6, 0, 0, 0, // page starts at entry 6
+            2,
+            (byte) 0xE8, 0x03, 0, 0, 1, 0x01, 9, 0, // 1000 ms, TX text seq 9, sent
+            (byte) 0xD0, 0x07, 0, 0, 1, 0x01, 9, 2}; // 2000 ms, TX text seq 9, ACKed
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x04}, AuditLog.request());
+        assertArrayEquals(new byte[]{0x04, 0x10, 0, 0, 0}, AuditLog.request(16));
+    }
+
+    @Test
+    public void testParsePage() {
+        AuditLog page = AuditLog.parse(SAMPLE_PAGE);
+
+        assertEquals(70, page.total);
+        assertEquals(6, page.firstIndex);
+        assertEquals(2, page.entries.size());
+        assertEquals(1000, page.entries.get(0).timestampMs);
+        assertEquals(AuditLog.DIRECTION_TX, page.entries.get(0).direction);
+        assertEquals(9, page.entries.get(0).seq);
+        assertEquals("SENT", page.entries.get(0).outcomeName());
+        assertEquals("ACKED", page.entries.get(1).outcomeName());
+        assertEquals(8, page.nextIndex());
+    }
+
+    @Test
+    public void testRejectsTruncatedPage() {
+        byte[] truncated = new byte[SAMPLE_PAGE.length - 1];
+        System.arraycopy(SAMPLE_PAGE, 0, truncated, 0, truncated.length);
+        try {
+            AuditLog.parse(truncated);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
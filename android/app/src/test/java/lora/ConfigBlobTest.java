@@ -0,0 +1,79 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.assertTrue;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+import java.util.Arrays;
+
+/**
+ * Unit tests for exporting and importing the ESP32 config blob
+ */
+public class ConfigBlobTest {
+
+    private static byte[] sampleResponse() {
+        ByteBuffer buf = ByteBuffer.allocate(1 + ConfigBlob.LENGTH).order(ByteOrder.LITTLE_ENDIAN);
+        buf.put(ConfigBlob.RESPONSE);
+        buf.put((byte) 1); // format version
+        buf.put((byte) 3); // node id
+        buf.putInt(433920000);
+        buf.put((byte) 11).put((byte) 4).put((byte) 5).put((byte) -3);
+        buf.put((byte) 0x12); // sync word
+        buf.put((byte) 1).put((byte) 0); // RX boost on, AGC
+        buf.put((byte) ConfigBlob.FLAG_TRACK_STATUS);
+        return buf.array();
+    }
+
+    @Test
+    public void testParseConfig() {
+        ConfigBlob config = ConfigBlob.parse(sampleResponse());
+
+        assertEquals(3, config.nodeId);
+        assertEquals(433920000L, config.frequency);
+        assertEquals(11, config.spreadingFactor);
+        assertEquals(4, config.bandwidthIndex);
+        assertEquals(5, config.codingRate);
+        assertEquals(-3, config.txPower);
+        assertEquals(0x12, config.syncWord);
+        assertTrue(config.rxBoost);
+        assertEquals(0, config.lnaGain);
+        assertEquals(ConfigBlob.FLAG_TRACK_STATUS, config.buildFlags);
+    }
+
+    @Test
+    public void testImportRequestCarriesTheBlobVerbatim() {
+        byte[] response = sampleResponse();
+        byte[] request = ConfigBlob.parse(response).request();
+
+        assertArrayEquals(new byte[]{0x07}, ConfigBlob.query());
+        assertEquals(ConfigBlob.OPCODE, request[0]);
+        assertArrayEquals(Arrays.copyOfRange(response, 1, response.length),
+                Arrays.copyOfRange(request, 1, request.length));
+    }
+
+    @Test
+    public void testRejectsTruncatedForeignOrNewerResponse() {
+        byte[] response = sampleResponse();
+
+        byte[][] bad = {
+                Arrays.copyOf(response, response.length - 1),
+                new byte[]{(byte) 0xFF, 0x07},
+                response.clone(),
+        };
+        bad[2][1] = 2; // format version 2
+
+        for (byte[] data : bad) {
+            try {
+                ConfigBlob.parse(data);
+                fail("Expected rejection");
+            } catch (IllegalArgumentException e) {
+                // Expected
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertTrue;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the control lock helpers
+ */
+public class ControlLockTest {
+
+    @Test
+    public void testUnlockRequest() {
+        assertArrayEquals(new byte[]{'4', '7', '1', '1'}, ControlLock.unlockRequest("4711"));
+    }
+
+    @Test
+    public void testLockedResponse() {
+        assertTrue(ControlLock.isLockedResponse(new byte[]{(byte) 0xFE, 0x05}));
+        assertFalse(ControlLock.isLockedResponse(new byte[]{(byte) 0xFF, 0x05}));
+        assertFalse(ControlLock.isLockedResponse(new byte[]{(byte) 0x81}));
+    }
+
+    @Test
+    public void testLockState() {
+        assertTrue(ControlLock.isUnlocked(new byte[]{1}));
+        assertFalse(ControlLock.isUnlocked(new byte[]{0}));
+        assertFalse(ControlLock.isUnlocked(new byte[0]));
+    }
+}
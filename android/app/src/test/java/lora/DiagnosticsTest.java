@@ -0,0 +1,84 @@
+package lora;
+
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+
+/**
+ * Unit tests for parsing the ESP32 diagnostics blob
+ */
+public class DiagnosticsTest {
+
+    private static byte[] sampleResponse() {
+        ByteBuffer buf = ByteBuffer.allocate(1 + Diagnostics.LENGTH).order(ByteOrder.LITTLE_ENDIAN);
+        buf.put(Diagnostics.RESPONSE);
+        buf.put((byte) 2); // format version
+        buf.put((byte) 1).put((byte) 0).put((byte) 2); // firmware 1.0.2
+        buf.put((byte) 3); // node id
+        buf.putInt(433920000);
+        buf.put((byte) 11).put((byte) 4).put((byte) 5).put((byte) 20);
+        buf.putShort((short) -97); // RSSI
+        buf.put((byte) -26); // SNR -6.5 dB
+        buf.putShort((short) 40000).putShort((short) 12).putShort((short) 1).putShort((short) 2);
+        buf.put((byte) 0xFF).put((byte) 0).put((byte) 0);
+        buf.putInt(86400);
+        buf.put((byte) 6); // AckTimeout
+        buf.put((byte) 4);
+        buf.putShort((short) 300); // buffer drops
+        return buf.array();
+    }
+
+    @Test
+    public void testParseDiagnostics() {
+        Diagnostics diag = Diagnostics.parse(sampleResponse());
+
+        assertEquals("1.0.2", diag.firmwareVersion);
+        assertEquals(3, diag.nodeId);
+        assertEquals(433920000L, diag.frequency);
+        assertEquals(11, diag.spreadingFactor);
+        assertEquals(4, diag.bandwidthIndex);
+        assertEquals(5, diag.codingRate);
+        assertEquals(20, diag.txPower);
+        assertEquals(-97, diag.lastRssi);
+        assertEquals(-6.5, diag.lastSnr, 0.001);
+        assertEquals(40000, diag.loraRxFrames);
+        assertEquals(12, diag.loraTxFrames);
+        assertEquals(1, diag.loraTxFailures);
+        assertEquals(2, diag.ackTimeouts);
+        assertEquals(Diagnostics.BATTERY_UNKNOWN, diag.batteryPercent);
+        assertEquals(86400L, diag.uptimeSeconds);
+        assertEquals(6, diag.lastError);
+        assertEquals(4, diag.bufferedMessages);
+        assertEquals(300, diag.bufferDrops);
+    }
+
+    @Test
+    public void testRejectsTruncatedOrForeignResponse() {
+        byte[] response = sampleResponse();
+
+        byte[] truncated = new byte[response.length - 1];
+        System.arraycopy(response, 0, truncated, 0, truncated.length);
+        assertRejected(truncated);
+
+        byte[] wrongOpcode = response.clone();
+        wrongOpcode[0] = (byte) 0xFF;
+        assertRejected(wrongOpcode);
+
+        byte[] futureFormat = response.clone();
+        futureFormat[1] = 3;
+        assertRejected(futureFormat);
+    }
+
+    private static void assertRejected(byte[] data) {
+        try {
+            Diagnostics.parse(data);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
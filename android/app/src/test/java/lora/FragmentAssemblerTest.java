@@ -0,0 +1,119 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.assertNull;
+import static org.junit.Assert.assertSame;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for BLE fragment reassembly
+ */
+public class FragmentAssemblerTest {
+
+    @Test
+    public void testUnfragmentedMessagePassesThrough() {
+        FragmentAssembler assembler = new FragmentAssembler();
+        byte[] ack = new Protocol.AckMessage((byte) 3).serialize();
+        assertSame(ack, assembler.accept(ack));
+    }
+
+    @Test
+    public void testReassemblesFragments() {
+        byte[] message = new Protocol.TextMessage((byte) 7, "AT CHECKPOINT 2, ALL GOOD", 37774200, -122419200)
+                .serialize();
+
+        // Split the way the ESP32 does at the default 23-byte MTU (20-byte payloads)
+        FragmentAssembler assembler = new FragmentAssembler();
+        byte[] start = new byte[20];
+        start[0] = FragmentAssembler.FRAGMENT_START;
+        start[1] = (byte) message.length;
+        System.arraycopy(message, 0, start, 2, 18);
+        assertNull(assembler.accept(start));
+
+        byte[] rest = new byte[1 + message.length - 18];
+        rest[0] = FragmentAssembler.FRAGMENT_CONTINUE;
+        System.arraycopy(message, 18, rest, 1, message.length - 18);
+        byte[] result = assembler.accept(rest);
+
+        assertArrayEquals(message, result);
+        assertEquals(new Protocol.TextMessage((byte) 7, "AT CHECKPOINT 2, ALL GOOD", 37774200, -122419200),
+                Protocol.Message.deserialize(result));
+    }
+
+    @Test
+    public void testContinueWithoutStartRejected() {
+        FragmentAssembler assembler = new FragmentAssembler();
+        try {
+            assembler.accept(new byte[]{FragmentAssembler.FRAGMENT_CONTINUE, 1, 2});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+
+    @Test
+    public void testOverlongFragmentRejected() {
+        FragmentAssembler assembler = new FragmentAssembler();
+        assertNull(assembler.accept(new byte[]{FragmentAssembler.FRAGMENT_START, 4, 1, 2}));
+        try {
+            assembler.accept(new byte[]{FragmentAssembler.FRAGMENT_CONTINUE, 3, 4, 5});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+
+    @Test
+    public void testSplitRoundTrip() {
+        byte[] text = new Protocol.TextMessage((byte) 5, "A MESSAGE LONGER THAN A WRITE", 47123456, 8123456)
+                .serialize();
+        java.util.List<byte[]> fragments = FragmentAssembler.split(text, 20);
+
+        assertEquals(2, fragments.size());
+        assertEquals(FragmentAssembler.FRAGMENT_START, fragments.get(0)[0]);
+        assertEquals(text.length, fragments.get(0)[1]);
+        assertEquals(FragmentAssembler.FRAGMENT_CONTINUE, fragments.get(1)[0]);
+
+        FragmentAssembler assembler = new FragmentAssembler();
+        assertNull(assembler.accept(fragments.get(0)));
+        assertArrayEquals(text, assembler.accept(fragments.get(1)));
+    }
+
+    @Test
+    public void testSplitLeavesSmallMessageAlone() {
+        byte[] ack = new Protocol.AckMessage((byte) 3).serialize();
+        java.util.List<byte[]> fragments = FragmentAssembler.split(ack, 20);
+        assertEquals(1, fragments.size());
+        assertSame(ack, fragments.get(0));
+    }
+
+    @Test
+    public void testUnbatch() {
+        byte[] ack = new Protocol.AckMessage((byte) 3).serialize();
+        byte[] text = new Protocol.TextMessage((byte) 4, "HI").serialize();
+        byte[] batch = new byte[1 + 1 + ack.length + 1 + text.length];
+        batch[0] = FragmentAssembler.BATCH;
+        batch[1] = (byte) ack.length;
+        System.arraycopy(ack, 0, batch, 2, ack.length);
+        batch[2 + ack.length] = (byte) text.length;
+        System.arraycopy(text, 0, batch, 3 + ack.length, text.length);
+
+        java.util.List<byte[]> messages = FragmentAssembler.unbatch(batch);
+        assertEquals(2, messages.size());
+        assertArrayEquals(ack, messages.get(0));
+        assertArrayEquals(text, messages.get(1));
+
+        // A plain message is its own batch of one
+        assertSame(ack, FragmentAssembler.unbatch(ack).get(0));
+
+        try {
+            FragmentAssembler.unbatch(new byte[]{FragmentAssembler.BATCH, 5, 1, 2});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
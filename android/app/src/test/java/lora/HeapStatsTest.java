@@ -0,0 +1,59 @@
+package lora;
+
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+
+/**
+ * Unit tests for parsing the heap characteristic
+ */
+public class HeapStatsTest {
+
+    private static byte[] sampleValue() {
+        ByteBuffer buf = ByteBuffer.allocate(HeapStats.LENGTH).order(ByteOrder.LITTLE_ENDIAN);
+        buf.put((byte) 1); // format version
+        buf.putInt(300000); // total
+        buf.putInt(120000); // free
+        buf.putInt(90000); // min free
+        buf.putInt(65536); // largest free block
+        return buf.array();
+    }
+
+    @Test
+    public void testParseHeapStats() {
+        HeapStats heap = HeapStats.parse(sampleValue());
+
+        assertEquals(300000L, heap.totalBytes);
+        assertEquals(120000L, heap.freeBytes);
+        assertEquals(90000L, heap.minFreeBytes);
+        assertEquals(65536L, heap.largestFreeBlock);
+        assertEquals(180000L, heap.usedBytes());
+        assertEquals(210000L, heap.peakUsedBytes());
+    }
+
+    @Test
+    public void testRejectsTruncatedOrFutureFormat() {
+        byte[] value = sampleValue();
+
+        byte[] truncated = new byte[value.length - 1];
+        System.arraycopy(value, 0, truncated, 0, truncated.length);
+        assertRejected(truncated);
+
+        byte[] futureFormat = value.clone();
+        futureFormat[0] = 2;
+        assertRejected(futureFormat);
+    }
+
+    private static void assertRejected(byte[] data) {
+        try {
+            HeapStats.parse(data);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
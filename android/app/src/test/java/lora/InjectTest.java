@@ -0,0 +1,39 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertTrue;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the message injection command and response
+ */
+public class InjectTest {
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x09, 0x02, 0x05, 0x31}, Inject.request(new Protocol.AckMessage((byte) 5)));
+
+        byte[] text = new Protocol.TextMessage((byte) 7, "HI").serialize();
+        byte[] command = Inject.request(new Protocol.TextMessage((byte) 7, "HI"));
+        assertTrue(command.length == 1 + text.length && command[0] == Inject.OPCODE);
+        byte[] payload = new byte[text.length];
+        System.arraycopy(command, 1, payload, 0, payload.length);
+        assertArrayEquals(text, payload);
+    }
+
+    @Test
+    public void testParse() {
+        assertTrue(Inject.parse(new byte[]{(byte) 0x89, 1}));
+        assertFalse(Inject.parse(new byte[]{(byte) 0x89, 0}));
+
+        try {
+            Inject.parse(new byte[]{(byte) 0xFF, 0x09});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
@@ -0,0 +1,73 @@
+package lora;
+
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertTrue;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+
+/**
+ * Unit tests for parsing the last-RX characteristic
+ */
+public class LastRxMetaTest {
+
+    private static byte[] sampleValue() {
+        ByteBuffer buf = ByteBuffer.allocate(LastRxMeta.LENGTH).order(ByteOrder.LITTLE_ENDIAN);
+        buf.put((byte) 1); // format version
+        buf.putShort((short) 40000); // reception count
+        buf.putShort((short) -118); // RSSI
+        buf.put((byte) -30); // SNR -7.5 dB
+        buf.putInt(-1830); // frequency error
+        buf.put((byte) 200); // frame length
+        buf.putInt((int) 3000000000L); // received at, past 2^31 ms
+        buf.putInt(1500); // age
+        return buf.array();
+    }
+
+    @Test
+    public void testParseLastRxMeta() {
+        LastRxMeta meta = LastRxMeta.parse(sampleValue());
+
+        assertTrue(meta.hasReception());
+        assertEquals(40000, meta.rxCount);
+        assertEquals(-118, meta.rssi);
+        assertEquals(-7.5, meta.snr, 0.001);
+        assertEquals(-1830, meta.frequencyErrorHz);
+        assertEquals(200, meta.frameLength);
+        assertEquals(3000000000L, meta.receivedAtMs);
+        assertEquals(1500L, meta.ageMs);
+    }
+
+    @Test
+    public void testNothingReceivedYet() {
+        byte[] value = new byte[LastRxMeta.LENGTH];
+        value[0] = 1;
+        assertFalse(LastRxMeta.parse(value).hasReception());
+    }
+
+    @Test
+    public void testRejectsTruncatedOrFutureFormat() {
+        byte[] value = sampleValue();
+
+        byte[] truncated = new byte[value.length - 1];
+        System.arraycopy(value, 0, truncated, 0, truncated.length);
+        assertRejected(truncated);
+
+        byte[] futureFormat = value.clone();
+        futureFormat[0] = 2;
+        assertRejected(futureFormat);
+    }
+
+    private static void assertRejected(byte[] data) {
+        try {
+            LastRxMeta.parse(data);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
@@ -0,0 +1,68 @@
+package lora;
+
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+
+/**
+ * Unit tests for parsing the stats characteristic
+ */
+public class LinkStatsTest {
+
+    private static byte[] sampleValue() {
+        ByteBuffer buf = ByteBuffer.allocate(LinkStats.LENGTH).order(ByteOrder.LITTLE_ENDIAN);
+        buf.put((byte) 2); // format version
+        buf.putShort((short) 120); // tx frames
+        buf.putShort((short) 65535); // rx frames, about to wrap
+        buf.putShort((short) 3); // CRC failures
+        buf.putShort((short) 97); // ACKs received
+        buf.putShort((short) 2); // buffer drops
+        buf.put((byte) 9); // spreading factor
+        return buf.array();
+    }
+
+    @Test
+    public void testParseLinkStats() {
+        LinkStats stats = LinkStats.parse(sampleValue());
+
+        assertEquals(120, stats.txFrames);
+        assertEquals(65535, stats.rxFrames);
+        assertEquals(3, stats.crcFailures);
+        assertEquals(97, stats.acksReceived);
+        assertEquals(2, stats.bufferDrops);
+        assertEquals(9, stats.spreadingFactor);
+    }
+
+    @Test
+    public void testSinceCountsAcrossTheWrap() {
+        assertEquals(5, LinkStats.since(10, 15));
+        assertEquals(6, LinkStats.since(65533, 3));
+        assertEquals(0, LinkStats.since(42, 42));
+    }
+
+    @Test
+    public void testRejectsTruncatedOrFutureFormat() {
+        byte[] value = sampleValue();
+
+        byte[] truncated = new byte[value.length - 1];
+        System.arraycopy(value, 0, truncated, 0, truncated.length);
+        assertRejected(truncated);
+
+        byte[] futureFormat = value.clone();
+        futureFormat[0] = 3;
+        assertRejected(futureFormat);
+    }
+
+    private static void assertRejected(byte[] data) {
+        try {
+            LinkStats.parse(data);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
@@ -0,0 +1,41 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the loss injection command and response
+ */
+public class LossInjectionTest {
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x06}, LossInjection.query());
+        assertArrayEquals(new byte[]{0x06, 25, 0}, LossInjection.request(25, 0));
+        assertArrayEquals(new byte[]{0x06, 100, 100}, LossInjection.request(100, 100));
+
+        try {
+            LossInjection.request(101, 0);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+
+    @Test
+    public void testParse() {
+        LossInjection loss = LossInjection.parse(new byte[]{(byte) 0x86, 30, 10});
+        assertEquals(30, loss.rxLossPercent);
+        assertEquals(10, loss.txLossPercent);
+
+        try {
+            LossInjection.parse(new byte[]{(byte) 0xFF, 0x06});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
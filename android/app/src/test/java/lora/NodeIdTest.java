@@ -0,0 +1,42 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the node id command and response
+ */
+public class NodeIdTest {
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x08}, NodeId.query());
+        assertArrayEquals(new byte[]{0x08, 1}, NodeId.request(1));
+        assertArrayEquals(new byte[]{0x08, (byte) 0xFE}, NodeId.request(254));
+
+        int[] invalid = {Protocol.RESERVED_NODE_ID, Protocol.BROADCAST_NODE_ID, -1, 256};
+        for (int id : invalid) {
+            try {
+                NodeId.request(id);
+                fail("Expected rejection of " + id);
+            } catch (IllegalArgumentException e) {
+                // Expected
+            }
+        }
+    }
+
+    @Test
+    public void testParse() {
+        assertEquals(200, NodeId.parse(new byte[]{(byte) 0x88, (byte) 200}).nodeId);
+
+        try {
+            NodeId.parse(new byte[]{(byte) 0xFF, 0x08});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
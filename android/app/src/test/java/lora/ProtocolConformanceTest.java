@@ -0,0 +1,216 @@
+package lora;
+
+import static org.junit.Assert.assertTrue;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+import java.util.Random;
+
+/**
+ * Conformance tests for the deserialize boundary.
+ * Data arriving over BLE is untrusted, so every malformed frame must be rejected
+ * with an IllegalArgumentException - never another exception type. Hand-made
+ * frames are closed with a valid CRC so they reach the field checks behind it.
+ */
+public class ProtocolConformanceTest {
+
+    /**
+     * Deserialize and require either success or a clean IllegalArgumentException
+     */
+    private static void assertSafe(byte[] data) {
+        try {
+            Protocol.Message.deserialize(data);
+        } catch (IllegalArgumentException e) {
+            // Rejected cleanly
+        } catch (RuntimeException e) {
+            fail("Unexpected " + e.getClass().getSimpleName() + " for input of " + data.length + " bytes: "
+                    + e.getMessage());
+        }
+    }
+
+    /**
+     * Deserialize and require a clean IllegalArgumentException
+     */
+    private static void assertRejected(byte[] data) {
+        try {
+            Protocol.Message msg = Protocol.Message.deserialize(data);
+            fail("Expected rejection but got " + msg);
+        } catch (IllegalArgumentException e) {
+            // Rejected cleanly
+        } catch (RuntimeException e) {
+            fail("Unexpected " + e.getClass().getSimpleName() + ": " + e.getMessage());
+        }
+    }
+
+    private static byte[] truncate(byte[] data, int len) {
+        byte[] result = new byte[len];
+        System.arraycopy(data, 0, result, 0, len);
+        return result;
+    }
+
+    /**
+     * Frame from message bytes, closed with their CRC-8
+     */
+    private static byte[] withCrc(byte... body) {
+        byte[] data = java.util.Arrays.copyOf(body, body.length + Protocol.CRC_LENGTH);
+        data[body.length] = (byte) Protocol.crc8(body, body.length);
+        return data;
+    }
+
+    /**
+     * Every shorter prefix of a serialized message, with and without a CRC of its own
+     */
+    private static void assertPrefixesRejected(byte[] data) {
+        for (int len = 0; len < data.length; len++) {
+            assertRejected(truncate(data, len));
+        }
+        for (int len = 0; len < data.length - Protocol.CRC_LENGTH; len++) {
+            assertRejected(withCrc(truncate(data, len)));
+        }
+    }
+
+    @Test
+    public void testEveryTypeByte() {
+        Random random = new Random(42);
+        for (int type = 0; type <= 0xFF; type++) {
+            assertSafe(new byte[]{(byte) type});
+            assertSafe(withCrc((byte) type));
+            assertSafe(withCrc((byte) type, (byte) 0));
+            assertSafe(withCrc((byte) type, (byte) 1, (byte) 2, (byte) 3, (byte) 4));
+
+            byte[] noise = new byte[63];
+            random.nextBytes(noise);
+            noise[0] = (byte) type;
+            assertSafe(noise);
+            assertSafe(withCrc(noise));
+        }
+    }
+
+    @Test
+    public void testEmptyInput() {
+        assertRejected(new byte[0]);
+    }
+
+    @Test
+    public void testTruncatedTextMessage() {
+        byte[] data = new Protocol.TextMessage((byte) 7, "HELLO WORLD").serialize();
+        assertPrefixesRejected(data);
+    }
+
+    @Test
+    public void testTruncatedTextMessageWithGps() {
+        byte[] data = new Protocol.TextMessage((byte) 7, "HELLO", 37774200, -122419200).serialize();
+        assertPrefixesRejected(data);
+    }
+
+    @Test
+    public void testTextLengthFieldsMustAgree() {
+        // 11 characters pack into 9 bytes
+        byte[] data = new Protocol.TextMessage((byte) 7, "HELLO WORLD").serialize();
+
+        byte[] body = truncate(data, data.length - Protocol.CRC_LENGTH);
+
+        byte[] fewerChars = body.clone();
+        fewerChars[2] = 10; // Would pack into 8 bytes
+        assertRejected(withCrc(fewerChars));
+
+        byte[] shorterPacked = body.clone();
+        shorterPacked[3] = 8;
+        assertRejected(withCrc(shorterPacked));
+
+        byte[] emptyWithBytes = body.clone();
+        emptyWithBytes[2] = 0;
+        assertRejected(withCrc(emptyWithBytes));
+    }
+
+    @Test
+    public void testTruncatedAckMessage() {
+        byte[] data = new Protocol.AckMessage((byte) 9).serialize();
+        assertPrefixesRejected(data);
+    }
+
+    @Test
+    public void testTruncatedIdentifyResponse() {
+        byte[] data = new Protocol.IdentifyResponseMessage(1, 433920000L, 11, 4, 5, 20).serialize();
+        assertPrefixesRejected(data);
+    }
+
+    @Test
+    public void testTruncatedConfigResponse() {
+        byte[] data = new Protocol.ConfigResponseMessage(1, 433920000L, 11, 4, 5, 20, 0x12, 8, 1, 0, 0, 0, 0)
+                .serialize();
+        assertPrefixesRejected(data);
+    }
+
+    @Test
+    public void testTruncatedRawMessage() {
+        byte[] data = new Protocol.RawMessage(5, new byte[]{1, 2, 3, 4, 5}).serialize();
+        assertPrefixesRejected(data);
+    }
+
+    @Test
+    public void testCharCountExceedsPackedLength() {
+        // 5 packed bytes hold at most 6 characters
+        byte[] data = {0x01, 0x00, 10, 5, 0, 0, 0, 0, 0, 0};
+        assertRejected(withCrc(data));
+    }
+
+    @Test
+    public void testPackedLengthExceedsBuffer() {
+        byte[] data = {0x01, 0x00, 3, 40, 0, 0, 0, 0};
+        assertRejected(withCrc(data));
+    }
+
+    @Test
+    public void testOversizedLengthFields() {
+        byte[] data = new byte[63];
+        data[0] = 0x01;
+        data[2] = (byte) 0xFF; // char count
+        data[3] = (byte) 0xFF; // packed length
+        assertRejected(withCrc(data));
+
+        // Packed data present for every character but more than MAX_TEXT_LENGTH of them
+        byte[] tooMany = new byte[5 + 45];
+        tooMany[0] = 0x01;
+        tooMany[2] = 60;
+        tooMany[3] = 45;
+        assertRejected(withCrc(tooMany));
+    }
+
+    @Test
+    public void testEveryTextHeaderCombination() {
+        // Exhaustively probe the two length fields against a fixed-size frame
+        for (int charCount = 0; charCount <= 0xFF; charCount++) {
+            for (int packedLen = 0; packedLen <= 0xFF; packedLen += 3) {
+                byte[] data = new byte[20];
+                data[0] = 0x01;
+                data[2] = (byte) charCount;
+                data[3] = (byte) packedLen;
+                assertSafe(withCrc(data));
+            }
+        }
+    }
+
+    @Test
+    public void testValidMessagesStillAccepted() {
+        byte[] text = new Protocol.TextMessage((byte) 1, "OK").serialize();
+        assertTrue(Protocol.Message.deserialize(text) instanceof Protocol.TextMessage);
+
+        byte[] ack = new Protocol.AckMessage((byte) 1).serialize();
+        assertTrue(Protocol.Message.deserialize(ack) instanceof Protocol.AckMessage);
+
+        byte[] raw = new Protocol.RawMessage(0, new byte[0]).serialize();
+        assertTrue(Protocol.Message.deserialize(raw) instanceof Protocol.RawMessage);
+    }
+
+    @Test
+    public void testAnySingleBitFlipIsRejected() {
+        byte[] data = new Protocol.TextMessage((byte) 7, "HELLO", 37774200, -122419200).serialize();
+        for (int bit = 0; bit < data.length * 8; bit++) {
+            byte[] flipped = data.clone();
+            flipped[bit / 8] ^= (byte) (1 << (bit % 8));
+            assertRejected(flipped);
+        }
+    }
+}
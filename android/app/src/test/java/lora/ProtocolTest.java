@@ -2,7 +2,9 @@ package lora;
 
 import static org.junit.Assert.assertEquals;
 import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertSame;
 import static org.junit.Assert.assertTrue;
+import static org.junit.Assert.fail;
 
 import org.junit.Test;
 
@@ -12,11 +14,31 @@ import org.junit.Test;
  */
 public class ProtocolTest {
 
+    /**
+     * Frame from hand-written message bytes, closed with their CRC-8
+     */
+    private static byte[] withCrc(byte... body) {
+        byte[] data = java.util.Arrays.copyOf(body, body.length + Protocol.CRC_LENGTH);
+        data[body.length] = (byte) Protocol.crc8(body, body.length);
+        return data;
+    }
+
+    @Test
+    public void testCharsetsHave64DistinctUppercaseCharacters() {
+        String[] charsets = {Protocol.CHARSET_STANDARD, Protocol.CHARSET_NUMERIC, Protocol.CHARSET_SYMBOL,
+                Protocol.CHARSET_MIXED_CASE};
+        for (String charset : charsets) {
+            assertEquals(64, charset.length());
+            assertEquals(64, charset.chars().distinct().count());
+            assertEquals(charset, charset.toUpperCase(java.util.Locale.ROOT));
+        }
+    }
+
     @Test
     public void testTextMessageSerialization_Empty() {
         Protocol.TextMessage msg = new Protocol.TextMessage((byte) 0, "");
         byte[] data = msg.serialize();
-        assertEquals(5, data.length);
+        assertEquals(6, data.length);
 
         Protocol.Message deserialized = Protocol.Message.deserialize(data);
         assertTrue(deserialized instanceof Protocol.TextMessage);
@@ -30,7 +52,7 @@ public class ProtocolTest {
     public void testTextMessageSerialization_Short() {
         Protocol.TextMessage msg = new Protocol.TextMessage((byte) 1, "HELLO");
         byte[] data = msg.serialize();
-        assertEquals(9, data.length);
+        assertEquals(10, data.length);
 
         Protocol.Message deserialized = Protocol.Message.deserialize(data);
         assertTrue(deserialized instanceof Protocol.TextMessage);
@@ -77,12 +99,136 @@ public class ProtocolTest {
         assertEquals((byte) 3, result.seq);
     }
 
+    @Test
+    public void testGpsFieldsRoundTripInEveryCombination() {
+        for (int fields = 0; fields <= Protocol.TextMessage.GPS_FIELDS_KNOWN; fields++) {
+            Protocol.TextMessage msg = new Protocol.TextMessage((byte) 4, "HI", 47376887, 8541694,
+                    Protocol.Priority.NORMAL, fields, -12, 65535);
+            byte[] data = msg.serialize();
+
+            // Without optional fields the frame is the plain GPS text
+            int expectedLength = 16;
+            if (fields != 0) {
+                expectedLength += 1 + ((fields & Protocol.TextMessage.GPS_FIELD_ALTITUDE) != 0 ? 2 : 0)
+                        + ((fields & Protocol.TextMessage.GPS_FIELD_SPEED) != 0 ? 2 : 0);
+            }
+            assertEquals(expectedLength, data.length);
+            assertEquals(fields != 0, (data[6] & 0x10) != 0);
+
+            Protocol.TextMessage result = (Protocol.TextMessage) Protocol.Message.deserialize(data);
+            assertEquals(msg, result);
+            assertEquals(fields, result.gpsFields);
+            assertEquals((fields & Protocol.TextMessage.GPS_FIELD_ALTITUDE) != 0 ? -12 : 0, result.altitude);
+            assertEquals((fields & Protocol.TextMessage.GPS_FIELD_SPEED) != 0 ? 65535 : 0, result.speed);
+
+            // A frame cut short of an announced field is rejected
+            if (fields != 0) {
+                byte[] cut = withCrc(java.util.Arrays.copyOf(data, data.length - 2));
+                try {
+                    Protocol.Message.deserialize(cut);
+                    fail("Expected a truncated GPS field to be rejected");
+                } catch (IllegalArgumentException expected) {
+                    // Rejected
+                }
+            }
+        }
+    }
+
+    @Test
+    public void testUnknownGpsFieldsAreSkipped() {
+        // Same frame as test_unknown_gps_fields_are_skipped in the firmware tests:
+        // altitude plus a field from a later version (bit 2) after it
+        byte[] data = withCrc(new byte[]{0x01, 9, 0, 0, 0x11, 1, 0, 0, 0, 2, 0, 0, 0, 0x05, 0x10, 0x00, (byte) 0xAA,
+                (byte) 0xBB, (byte) 0xCC});
+        Protocol.TextMessage result = (Protocol.TextMessage) Protocol.Message.deserialize(data);
+        assertEquals(Protocol.TextMessage.GPS_FIELD_ALTITUDE, result.gpsFields);
+        assertEquals(16, result.altitude);
+        assertEquals(2, result.lon);
+        assertEquals(17, result.serialize().length);
+    }
+
+    @Test
+    public void testFragmentHeaderIsFoundAfterUnknownGpsFields() {
+        // Same frame as test_fragment_header_is_found_after_unknown_gps_fields in the firmware tests
+        byte[] data = withCrc(new byte[]{0x01, 0x31, 0, 0, 0x31, 1, 0, 0, 0, 2, 0, 0, 0, 0x05, 0x10, 0x00,
+                (byte) 0xAA, (byte) 0xBB, 0x30, 1, 4});
+        Protocol.TextMessage result = (Protocol.TextMessage) Protocol.Message.deserialize(data);
+        assertTrue(result.isFragment());
+        assertEquals(0x30, result.fragmentId);
+        assertEquals(1, result.fragmentIndex);
+        assertEquals(4, result.fragmentCount);
+        assertEquals(16, result.altitude);
+        assertEquals(20, result.serialize().length);
+
+        // A fragment past the end of its text
+        data = withCrc(new byte[]{0x01, 0x31, 0, 0, 0x20, 0x30, 4, 4});
+        try {
+            Protocol.Message.deserialize(data);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+
+    @Test
+    public void testShortTextIsNotFragmented() {
+        Protocol.TextMessage msg = new Protocol.TextMessage((byte) 5, "HELLO", 1, 2);
+        java.util.List<Protocol.TextMessage> fragments = msg.fragments(Protocol.MAX_SERIALIZED_LENGTH);
+        assertEquals(1, fragments.size());
+        assertSame(msg, fragments.get(0));
+        assertFalse(msg.isFragment());
+    }
+
+    @Test
+    public void testLongTextIsSplitIntoFragments() {
+        String text = "AT THE RIDGE NOW, TRAIL IS BLOCKED BY SNOW SO WE TURN BACK TO THE HUT AND WAIT THERE FOR YOU WITH THE DOGS AND THE REST OF US";
+        Protocol.TextMessage msg = new Protocol.TextMessage((byte) 254, text, 37774200, -122419200,
+                Protocol.Priority.HIGH, Protocol.TextMessage.GPS_FIELD_ALTITUDE, 2100, 0);
+        java.util.List<byte[]> frames = msg.serializeFragments(Protocol.MAX_SERIALIZED_LENGTH);
+        assertEquals(3, frames.size());
+
+        StringBuilder joined = new StringBuilder();
+        for (int i = 0; i < frames.size(); i++) {
+            assertTrue(frames.get(i).length <= Protocol.MAX_SERIALIZED_LENGTH);
+            Protocol.TextMessage fragment = (Protocol.TextMessage) Protocol.Message.deserialize(frames.get(i));
+            // Own seq per fragment (wrapping), the first one's seq as the id
+            assertEquals((byte) (254 + i), fragment.seq);
+            assertEquals(254, fragment.fragmentId);
+            assertEquals(i, fragment.fragmentIndex);
+            assertEquals(3, fragment.fragmentCount);
+            assertEquals(Protocol.Priority.HIGH, fragment.priority);
+            assertEquals(2100, fragment.altitude);
+            joined.append(fragment.text);
+        }
+        assertEquals(text, joined.toString());
+    }
+
+    @Test
+    public void testFragmentsFitASmallMtu() {
+        String text = "A TEXT THAT FITS ONE FRAME BUT NOT A SMALL MTU";
+        java.util.List<byte[]> frames = new Protocol.TextMessage((byte) 1, text).serializeFragments(20);
+        assertTrue(frames.size() > 1);
+        for (byte[] frame : frames) {
+            assertTrue(frame.length <= 20);
+        }
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testMtuWithoutRoomForTextIsRejected() {
+        new Protocol.TextMessage((byte) 1, "A TEXT THAT NEEDS FRAGMENTS", 1, 2).fragments(17);
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testUnknownGpsFieldsCantBeSent() {
+        new Protocol.TextMessage((byte) 1, "", 0, 0, Protocol.Priority.NORMAL, 0x04, 0, 0);
+    }
+
     @Test
     public void testAckMessageSerialization() {
         Protocol.AckMessage ack = new Protocol.AckMessage((byte) 42);
         byte[] data = ack.serialize();
 
-        assertEquals(2, data.length);
+        assertEquals(3, data.length);
 
         Protocol.Message deserialized = Protocol.Message.deserialize(data);
         assertTrue(deserialized instanceof Protocol.AckMessage);
@@ -105,6 +251,360 @@ public class ProtocolTest {
         assertEquals(original.text, result.text);
     }
 
+    @Test
+    public void testTextMessagePriority() {
+        Protocol.TextMessage original = new Protocol.TextMessage((byte) 7, "SOS", 47123456, 8123456,
+                Protocol.Priority.CRITICAL);
+
+        byte[] data = original.serialize();
+        // Flags byte after type, seq, charCount, packedLen and 3 packed bytes: GPS bit + priority 2
+        assertEquals(0x05, data[7]);
+        assertEquals(original, Protocol.Message.deserialize(data));
+
+        Protocol.TextMessage high = new Protocol.TextMessage((byte) 8, "HI", Protocol.Priority.HIGH);
+        assertEquals(high, Protocol.Message.deserialize(high.serialize()));
+
+        // Frames without priority bits decode as NORMAL, the reserved value 3 as CRITICAL
+        byte[] plain = new Protocol.TextMessage((byte) 9, "OK").serialize();
+        assertEquals(0x00, plain[plain.length - 2]);
+        assertEquals(Protocol.Priority.NORMAL, ((Protocol.TextMessage) Protocol.Message.deserialize(plain)).priority);
+        plain[plain.length - 2] = 0x06;
+        plain = withCrc(java.util.Arrays.copyOf(plain, plain.length - 1));
+        assertEquals(Protocol.Priority.CRITICAL,
+                ((Protocol.TextMessage) Protocol.Message.deserialize(plain)).priority);
+    }
+
+    @Test
+    public void testCanonicalByteOrderIsLittleEndian() {
+        // lat 0x01020304, lon -2 (0xFFFFFFFE) follow the flags byte of an empty text
+        byte[] data = new Protocol.TextMessage((byte) 1, "", 0x01020304, -2).serialize();
+        assertEquals(14, data.length);
+        assertEquals(0x04, data[5]);
+        assertEquals(0x01, data[8]);
+        assertEquals((byte) 0xFE, data[9]);
+        assertEquals((byte) 0xFF, data[12]);
+
+        // 433920000 Hz = 0x19DD1800
+        byte[] response = new Protocol.IdentifyResponseMessage(1, 433920000L, 11, 4, 5, 20).serialize();
+        assertEquals(0x00, response[2]);
+        assertEquals(0x18, response[3]);
+        assertEquals((byte) 0xDD, response[4]);
+        assertEquals(0x19, response[5]);
+    }
+
+    @Test
+    public void testIdentifyMessageSerialization() {
+        Protocol.IdentifyMessage msg = new Protocol.IdentifyMessage(Protocol.BROADCAST_NODE_ID);
+        byte[] data = msg.serialize();
+
+        assertEquals(3, data.length);
+        assertEquals((byte) 0x08, data[0]);
+        assertEquals((byte) 0xFF, data[1]);
+        assertEquals(msg, Protocol.Message.deserialize(data));
+    }
+
+    @Test
+    public void testIdentifyResponseSerialization() {
+        Protocol.IdentifyResponseMessage msg =
+                new Protocol.IdentifyResponseMessage(3, 433920000L, 11, 4, 5, 20);
+        byte[] data = msg.serialize();
+
+        assertEquals(11, data.length);
+
+        Protocol.Message deserialized = Protocol.Message.deserialize(data);
+        assertTrue(deserialized instanceof Protocol.IdentifyResponseMessage);
+        Protocol.IdentifyResponseMessage result = (Protocol.IdentifyResponseMessage) deserialized;
+
+        assertEquals(msg, result);
+        assertEquals(433920000L, result.frequency);
+        assertEquals(31250.0, result.getBandwidthHz(), 0.001);
+    }
+
+    @Test
+    public void testConfigResponseSerialization() {
+        Protocol.ConfigResponseMessage msg =
+                new Protocol.ConfigResponseMessage(3, 433920000L, 11, 4, 5, 20, 0x12, 8, 1, 0, 2, 0, 1);
+        byte[] data = msg.serialize();
+
+        assertEquals(19, data.length);
+        assertEquals((byte) 0x0E, data[0]);
+        assertEquals(0x12, data[10]);
+        assertEquals(8, data[11]); // preamble length, little-endian
+        assertEquals(0, data[12]);
+        assertEquals(2, data[15]);
+        assertEquals(1, data[17]);
+        assertEquals(msg, Protocol.Message.deserialize(data));
+
+        byte[] request = new Protocol.ConfigRequestMessage(3).serialize();
+        assertEquals((byte) 0x0D, request[0]);
+        assertEquals(new Protocol.ConfigRequestMessage(3), Protocol.Message.deserialize(request));
+    }
+
+    @Test
+    public void testConfigResponseLinkMismatches() {
+        java.nio.ByteBuffer buf = java.nio.ByteBuffer.allocate(1 + ConfigBlob.LENGTH)
+                .order(java.nio.ByteOrder.LITTLE_ENDIAN);
+        buf.put(ConfigBlob.RESPONSE).put((byte) 1).put((byte) 1).putInt(433920000);
+        buf.put((byte) 11).put((byte) 4).put((byte) 5).put((byte) 14).put((byte) 0x12);
+        buf.put((byte) 1).put((byte) 0).put((byte) 0);
+        ConfigBlob local = ConfigBlob.parse(buf.array());
+
+        // Node id and TX power may differ between the ends
+        Protocol.ConfigResponseMessage same =
+                new Protocol.ConfigResponseMessage(2, 433920000L, 11, 4, 5, 20, 0x12, 8, 1, 0, 0, 0, 0);
+        assertTrue(same.linkMismatches(local).isEmpty());
+
+        Protocol.ConfigResponseMessage other =
+                new Protocol.ConfigResponseMessage(2, 433920000L, 9, 4, 5, 20, 0x34, 8, 1, 0, 0, 0, 0);
+        assertEquals(java.util.List.of("spreadingFactor", "syncWord"), other.linkMismatches(local));
+    }
+
+    @Test
+    public void testRawMessageDeserialization() {
+        byte[] data = withCrc((byte) 0x80, (byte) 4, (byte) 0xDE, (byte) 0xAD, (byte) 0xBE, (byte) 0xEF);
+
+        Protocol.Message deserialized = Protocol.Message.deserialize(data);
+        assertTrue(deserialized instanceof Protocol.RawMessage);
+        Protocol.RawMessage result = (Protocol.RawMessage) deserialized;
+
+        assertEquals(4, result.frameLength);
+        assertFalse(result.isTruncated());
+        assertEquals("DE AD BE EF", result.toHex());
+        assertTrue(java.util.Arrays.equals(data, result.serialize()));
+    }
+
+    @Test
+    public void testRawMessageTruncated() {
+        // 200-byte frame on air, firmware only forwards the first MAX_RAW_LENGTH bytes
+        byte[] body = new byte[2 + Protocol.MAX_RAW_LENGTH];
+        body[0] = (byte) 0x80;
+        body[1] = (byte) 200;
+        byte[] data = withCrc(body);
+        assertEquals(64, data.length);
+
+        Protocol.RawMessage result = (Protocol.RawMessage) Protocol.Message.deserialize(data);
+
+        assertEquals(200, result.frameLength);
+        assertEquals(Protocol.MAX_RAW_LENGTH, result.data.length);
+        assertTrue(result.isTruncated());
+    }
+
+    @Test
+    public void testStatusMessageRoundTrip() {
+        byte[] data = withCrc((byte) 0x81, (byte) 0x01, (byte) 7);
+
+        Protocol.Message deserialized = Protocol.Message.deserialize(data);
+        assertTrue(deserialized instanceof Protocol.StatusMessage);
+        Protocol.StatusMessage result = (Protocol.StatusMessage) deserialized;
+
+        assertEquals(Protocol.StatusMessage.RADIO_BUSY, result.code);
+        assertEquals(7, result.seq);
+        assertTrue(java.util.Arrays.equals(data, result.serialize()));
+    }
+
+    @Test
+    public void testDutyCycleLimitedStatus() {
+        byte[] data = withCrc((byte) 0x81, (byte) 0x03, (byte) 12);
+        Protocol.StatusMessage result = (Protocol.StatusMessage) Protocol.Message.deserialize(data);
+        assertEquals(Protocol.StatusMessage.DUTY_CYCLE_LIMITED, result.code);
+        assertEquals(12, result.seq);
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testStatusMessageUnknownCode() {
+        Protocol.Message.deserialize(withCrc((byte) 0x81, (byte) 0x09, (byte) 0));
+    }
+
+    @Test
+    public void testDeliveryReportMessageRoundTrip() {
+        byte[] data = withCrc((byte) 0x83, (byte) 0x02, (byte) 200);
+
+        Protocol.Message deserialized = Protocol.Message.deserialize(data);
+        assertTrue(deserialized instanceof Protocol.DeliveryReportMessage);
+        Protocol.DeliveryReportMessage result = (Protocol.DeliveryReportMessage) deserialized;
+
+        assertEquals(Protocol.DeliveryReportMessage.FAILED, result.outcome);
+        assertEquals(200, result.seq & 0xFF);
+        assertTrue(java.util.Arrays.equals(data, result.serialize()));
+    }
+
+    @Test
+    public void testDeliveryReportRelayed() {
+        Protocol.Message deserialized = Protocol.Message.deserialize(withCrc((byte) 0x83, (byte) 0x03, (byte) 7));
+        assertEquals(Protocol.DeliveryReportMessage.RELAYED, ((Protocol.DeliveryReportMessage) deserialized).outcome);
+    }
+
+    @Test
+    public void testDeliveryReportSent() {
+        Protocol.Message deserialized = Protocol.Message.deserialize(withCrc((byte) 0x83, (byte) 0x04, (byte) 7));
+        assertEquals(Protocol.DeliveryReportMessage.SENT, ((Protocol.DeliveryReportMessage) deserialized).outcome);
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testDeliveryReportMessageUnknownOutcome() {
+        Protocol.Message.deserialize(withCrc((byte) 0x83, (byte) 0x05, (byte) 0));
+    }
+
+    @Test
+    public void testLinkQualityMessageRoundTrip() {
+        // seq 12, -121 dBm, -7.5 dB
+        byte[] data = withCrc((byte) 0x84, (byte) 12, (byte) 0x87, (byte) 0xFF, (byte) -30);
+
+        Protocol.Message deserialized = Protocol.Message.deserialize(data);
+        assertTrue(deserialized instanceof Protocol.LinkQualityMessage);
+        Protocol.LinkQualityMessage result = (Protocol.LinkQualityMessage) deserialized;
+
+        assertEquals(12, result.seq);
+        assertEquals(-121, result.rssi);
+        assertEquals(-7.5f, result.getSnrDb(), 0.0f);
+        assertTrue(java.util.Arrays.equals(data, result.serialize()));
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testLinkQualityMessageTooShort() {
+        Protocol.Message.deserialize(withCrc((byte) 0x84, (byte) 12, (byte) 0x87, (byte) 0xFF));
+    }
+
+    @Test
+    public void testBatteryMessageRoundTrip() {
+        // seq 9, 100%, 4200 mV
+        byte[] full = withCrc((byte) 0x05, (byte) 9, (byte) 100, (byte) 0x68, (byte) 0x10);
+        Protocol.BatteryMessage result = (Protocol.BatteryMessage) Protocol.Message.deserialize(full);
+        assertEquals(9, result.seq);
+        assertEquals(100, result.percent);
+        assertEquals(4200, result.millivolts);
+        assertTrue(java.util.Arrays.equals(full, result.serialize()));
+
+        // Empty cell: 0%, 65535 mV still fits the unsigned field
+        Protocol.BatteryMessage empty = new Protocol.BatteryMessage((byte) 200, 0, 0xFFFF);
+        assertEquals(empty, Protocol.Message.deserialize(empty.serialize()));
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testBatteryMessageAbove100PercentRejected() {
+        Protocol.Message.deserialize(withCrc((byte) 0x05, (byte) 9, (byte) 101, (byte) 0x68, (byte) 0x10));
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testBatteryMessageTooShort() {
+        Protocol.Message.deserialize(withCrc((byte) 0x05, (byte) 9, (byte) 100, (byte) 0x68));
+    }
+
+    @Test
+    public void testSosMessageRoundTrip() {
+        // seq 7, lat -33.856784, lon 151.215297
+        Protocol.SosMessage sos = new Protocol.SosMessage((byte) 7, -33856784, 151215297);
+        byte[] data = sos.serialize();
+        assertEquals(11, data.length);
+        assertEquals(0x06, data[0]);
+        assertEquals(sos, Protocol.Message.deserialize(data));
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testSosMessageTooShort() {
+        Protocol.Message.deserialize(withCrc((byte) 0x06, (byte) 7, (byte) 0xFD, (byte) 0xFB, (byte) 0x5C,
+                (byte) 0x10, (byte) 0x09, (byte) 0x03, (byte) 0x4B));
+    }
+
+    @Test
+    public void testDecodeFailureMessageRoundTrip() {
+        // Truncated read of a 5-byte frame: only 2 bytes came from the radio
+        byte[] data = withCrc((byte) 0x82, (byte) 0x02, (byte) 5, (byte) 0x01, (byte) 0x07, (byte) 0, (byte) 0,
+                (byte) 0);
+
+        Protocol.Message deserialized = Protocol.Message.deserialize(data);
+        assertTrue(deserialized instanceof Protocol.DecodeFailureMessage);
+        Protocol.DecodeFailureMessage result = (Protocol.DecodeFailureMessage) deserialized;
+
+        assertEquals(Protocol.DecodeFailureMessage.TRUNCATED, result.reason);
+        assertEquals("truncated", result.reasonName());
+        assertEquals(5, result.frameLength);
+        assertEquals("01 07 00 00 00", result.toHex());
+        assertTrue(java.util.Arrays.equals(data, result.serialize()));
+    }
+
+    @Test
+    public void testDecodeFailureCrcMismatch() {
+        Protocol.Message deserialized = Protocol.Message.deserialize(withCrc((byte) 0x82, (byte) 0x05, (byte) 0));
+        assertEquals("CRC mismatch", ((Protocol.DecodeFailureMessage) deserialized).reasonName());
+    }
+
+    @Test(expected = IllegalArgumentException.class)
+    public void testDecodeFailureMessageUnknownReason() {
+        Protocol.Message.deserialize(withCrc((byte) 0x82, (byte) 0x06, (byte) 0));
+    }
+
+    @Test
+    public void testCrc8MatchesTheReferenceCheckValue() {
+        byte[] check = "123456789".getBytes(java.nio.charset.StandardCharsets.US_ASCII);
+        assertEquals(0xF4, Protocol.crc8(check, check.length));
+        // Same ACK as in protocol.md
+        assertEquals(0x31, new Protocol.AckMessage((byte) 5).serialize()[2] & 0xFF);
+    }
+
+    @Test
+    public void testFlippedBitInPackedTextFailsTheCrc() {
+        // Still a well-formed text with the bit flipped, so only the CRC can catch it
+        byte[] data = new Protocol.TextMessage((byte) 1, "HELLO").serialize();
+        data[5] ^= 0x04;
+        try {
+            Protocol.Message.deserialize(data);
+            fail("Expected a corrupted frame to be rejected");
+        } catch (IllegalArgumentException expected) {
+            assertEquals("CRC mismatch", expected.getMessage());
+        }
+    }
+
+    /**
+     * Pack and unpack a text with the mixed-case charset, returning its code count
+     */
+    private static int roundTripMixedCase(String text) {
+        byte[] codes = Protocol.textCodes(text, Protocol.CHARSET_MIXED_CASE);
+        byte[] packed = Protocol.packText(text, Protocol.CHARSET_MIXED_CASE);
+        assertEquals(text, Protocol.unpackText(packed, codes.length, Protocol.CHARSET_MIXED_CASE));
+        return codes.length;
+    }
+
+    @Test
+    public void testMixedCaseUsesTheFewestShiftCodes() {
+        // Same texts as test_mixed_case_uses_the_fewest_shift_codes in the firmware tests
+        assertEquals(14, roundTripMixedCase("Hello World"));
+        assertEquals(9, roundTripMixedCase("aBcDeF"));
+        assertEquals(31, roundTripMixedCase("meet at 5pm, bring the MAP!"));
+
+        // Uppercase needs no shift and packs as with the standard charset
+        assertEquals(11, roundTripMixedCase("HELLO WORLD"));
+        assertTrue(java.util.Arrays.equals(Protocol.packText("HELLO WORLD", Protocol.CHARSET_STANDARD),
+                Protocol.packText("HELLO WORLD", Protocol.CHARSET_MIXED_CASE)));
+    }
+
+    @Test
+    public void testMixedCaseRoundTripsExactly() {
+        String text = "Visit lora.example.org/Map?id=42 (eTA 5min)";
+        roundTripMixedCase(text);
+        assertEquals("HELLO", Protocol.unpackText(Protocol.packText("Hello", Protocol.CHARSET_STANDARD), 5,
+                Protocol.CHARSET_STANDARD));
+    }
+
+    @Test
+    public void testStrayShiftCodesAreRejected() {
+        // Shift, then the digit 1 (value 28): a shift only applies to letters
+        byte[] packed = {(byte) ((63 << 2) | (28 >> 4)), (byte) ((28 & 0x0F) << 4)};
+        try {
+            Protocol.unpackText(packed, 2, Protocol.CHARSET_MIXED_CASE);
+            fail("Expected a shift before a digit to be rejected");
+        } catch (IllegalArgumentException expected) {
+            // Rejected
+        }
+        try {
+            Protocol.unpackText(packed, 1, Protocol.CHARSET_MIXED_CASE);
+            fail("Expected a trailing shift to be rejected");
+        } catch (IllegalArgumentException expected) {
+            // Rejected
+        }
+        assertFalse(Protocol.isCharacterSupported(Protocol.SHIFT));
+    }
+
     @Test
     public void testIsTextSupported() {
         assertTrue(Protocol.isTextSupported("HELLO WORLD 123!"));
@@ -127,4 +627,31 @@ public class ProtocolTest {
         assertEquals(1, Protocol.calculatePackedSize("A"));
         assertEquals(4, Protocol.calculatePackedSize("HELLO"));
     }
+
+    @Test
+    public void testGpsDistanceAndBearing() {
+        // Same reference pairs as the firmware's test_gps_distance_and_bearing
+        assertEquals(95491, Protocol.distanceMeters(47376887, 8541694, 46947974, 7447447));
+        assertEquals(240, Protocol.bearingDegrees(47376887, 8541694, 46947974, 7447447));
+        assertEquals(60, Protocol.bearingDegrees(46947974, 7447447, 47376887, 8541694));
+        assertEquals(5574848, Protocol.distanceMeters(51500700, -124600, 40689200, -74044500));
+        assertEquals(288, Protocol.bearingDegrees(51500700, -124600, 40689200, -74044500));
+        assertEquals(11, Protocol.distanceMeters(47000000, 8000000, 47000100, 8000000));
+        assertEquals(270, Protocol.bearingDegrees(47000000, 8000100, 47000000, 8000000));
+        assertEquals(22239, Protocol.distanceMeters(0, 179900000, 0, -179900000));
+        assertEquals(90, Protocol.bearingDegrees(0, 179900000, 0, -179900000));
+        assertEquals(0, Protocol.distanceMeters(47000000, 8000000, 47000000, 8000000));
+        assertEquals(0, Protocol.bearingDegrees(47000000, 8000000, 47000000, 8000000));
+
+        Protocol.TextMessage here = new Protocol.TextMessage((byte) 1, "", 47376887, 8541694);
+        Protocol.TextMessage there = new Protocol.TextMessage((byte) 2, "", 46947974, 7447447);
+        assertEquals(95491, here.distanceTo(there));
+        assertEquals(240, here.bearingTo(there));
+    }
+
+    @Test(expected = IllegalStateException.class)
+    public void testDistanceToRequiresGps() {
+        Protocol.TextMessage here = new Protocol.TextMessage((byte) 1, "", 47376887, 8541694);
+        here.distanceTo(new Protocol.TextMessage((byte) 2, "NO GPS"));
+    }
 }
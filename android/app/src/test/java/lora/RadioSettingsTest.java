@@ -0,0 +1,48 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the radio settings command and response
+ */
+public class RadioSettingsTest {
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x0B}, RadioSettings.query());
+        // 433.92 MHz = 0x19DD1800, 14 dBm
+        assertArrayEquals(new byte[]{0x0B, 0x00, 0x18, (byte) 0xDD, 0x19, 14},
+                RadioSettings.request(433920000L, 14));
+
+        long[][] invalid = {{0, 14}, {0x100000000L, 14}, {433920000L, 1}, {433920000L, 21}};
+        for (long[] settings : invalid) {
+            try {
+                RadioSettings.request(settings[0], (int) settings[1]);
+                fail("Expected rejection of " + settings[0] + " Hz, " + settings[1] + " dBm");
+            } catch (IllegalArgumentException e) {
+                // Expected
+            }
+        }
+    }
+
+    @Test
+    public void testParse() {
+        RadioSettings settings = RadioSettings.parse(new byte[]{(byte) 0x8B, 0x00, 0x18, (byte) 0xDD, 0x19, 2});
+        assertEquals(433920000L, settings.frequency);
+        assertEquals(2, settings.txPower);
+
+        byte[][] invalid = {{(byte) 0xFF, 0x0B}, {(byte) 0x8B, 0x00, 0x18, (byte) 0xDD, 0x19}};
+        for (byte[] response : invalid) {
+            try {
+                RadioSettings.parse(response);
+                fail("Expected rejection");
+            } catch (IllegalArgumentException e) {
+                // Expected
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertTrue;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the reboot command
+ */
+public class RebootTest {
+
+    @Test
+    public void testRequestCarriesConfirmation() {
+        assertArrayEquals(new byte[]{0x05, 'B', 'O', 'O', 'T'}, Reboot.request());
+    }
+
+    @Test
+    public void testAcknowledgement() {
+        assertTrue(Reboot.isAcknowledged(new byte[]{(byte) 0x85}));
+        assertFalse(Reboot.isAcknowledged(new byte[]{(byte) 0xFF, 0x05}));
+        assertFalse(Reboot.isAcknowledged(new byte[0]));
+    }
+}
@@ -0,0 +1,48 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertTrue;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the RX front end command and response
+ */
+public class RxFrontEndTest {
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x03}, RxFrontEnd.query());
+        assertArrayEquals(new byte[]{0x03, 1, 0}, RxFrontEnd.request(true, RxFrontEnd.GAIN_AGC));
+        assertArrayEquals(new byte[]{0x03, 0, 6}, RxFrontEnd.request(false, RxFrontEnd.GAIN_MIN));
+
+        try {
+            RxFrontEnd.request(true, 7);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+
+    @Test
+    public void testParse() {
+        RxFrontEnd agc = RxFrontEnd.parse(new byte[]{(byte) 0x83, 1, 0});
+        assertTrue(agc.rxBoost);
+        assertTrue(agc.isAgc());
+
+        RxFrontEnd fixed = RxFrontEnd.parse(new byte[]{(byte) 0x83, 0, 3});
+        assertFalse(fixed.rxBoost);
+        assertEquals(3, fixed.lnaGain);
+        assertFalse(fixed.isAgc());
+
+        try {
+            RxFrontEnd.parse(new byte[]{(byte) 0xFF, 0x03});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
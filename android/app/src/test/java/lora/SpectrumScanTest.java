@@ -0,0 +1,61 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the spectrum scan command and response
+ */
+public class SpectrumScanTest {
+
+    private static final byte[] SAMPLE_RESPONSE = {
+            (byte) 0x82,
+            (byte) 0x90, (byte) 0xD1, (byte) 0xCF, (byte) 0x19, // 433050000 Hz
+            (byte) 0xA8, (byte) 0x61, 0x00, 0x00, // 25000 Hz
+            3,
+            (byte) -118, (byte) -124, (byte) -96};
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x02}, SpectrumScan.request());
+        assertArrayEquals(new byte[]{0x02,
+                        (byte) 0x90, (byte) 0xD1, (byte) 0xCF, (byte) 0x19,
+                        (byte) 0x10, (byte) 0xEC, (byte) 0xD5, (byte) 0x19,
+                        (byte) 0xA8, (byte) 0x61, 0x00, 0x00},
+                SpectrumScan.request(433050000L, 433450000L, 25000L));
+    }
+
+    @Test
+    public void testParse() {
+        SpectrumScan scan = SpectrumScan.parse(SAMPLE_RESPONSE);
+
+        assertEquals(433050000L, scan.firstFrequency);
+        assertEquals(25000L, scan.step);
+        assertArrayEquals(new int[]{-118, -124, -96}, scan.rssi);
+        assertEquals(433100000L, scan.frequencyAt(2));
+        assertEquals(433075000L, scan.quietestFrequency());
+    }
+
+    @Test
+    public void testRejectsTruncatedOrForeignResponse() {
+        byte[] truncated = new byte[SAMPLE_RESPONSE.length - 1];
+        System.arraycopy(SAMPLE_RESPONSE, 0, truncated, 0, truncated.length);
+        assertRejected(truncated);
+
+        byte[] wrongOpcode = SAMPLE_RESPONSE.clone();
+        wrongOpcode[0] = (byte) 0x81;
+        assertRejected(wrongOpcode);
+    }
+
+    private static void assertRejected(byte[] data) {
+        try {
+            SpectrumScan.parse(data);
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
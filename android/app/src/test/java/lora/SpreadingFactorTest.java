@@ -0,0 +1,43 @@
+package lora;
+
+import static org.junit.Assert.assertArrayEquals;
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.fail;
+
+import org.junit.Test;
+
+/**
+ * Unit tests for the spreading factor command and response
+ */
+public class SpreadingFactorTest {
+
+    @Test
+    public void testRequest() {
+        assertArrayEquals(new byte[]{0x0A}, SpreadingFactor.query());
+        for (int sf = 7; sf <= 12; sf++) {
+            assertArrayEquals(new byte[]{0x0A, (byte) sf}, SpreadingFactor.request(sf));
+        }
+
+        int[] invalid = {0, 6, 13, 255};
+        for (int sf : invalid) {
+            try {
+                SpreadingFactor.request(sf);
+                fail("Expected rejection of " + sf);
+            } catch (IllegalArgumentException e) {
+                // Expected
+            }
+        }
+    }
+
+    @Test
+    public void testParse() {
+        assertEquals(9, SpreadingFactor.parse(new byte[]{(byte) 0x8A, 9}).spreadingFactor);
+
+        try {
+            SpreadingFactor.parse(new byte[]{(byte) 0xFF, 0x0A});
+            fail("Expected rejection");
+        } catch (IllegalArgumentException e) {
+            // Expected
+        }
+    }
+}
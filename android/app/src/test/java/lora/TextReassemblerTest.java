@@ -0,0 +1,111 @@
+package lora;
+
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.assertFalse;
+import static org.junit.Assert.assertNull;
+import static org.junit.Assert.assertSame;
+
+import org.junit.Test;
+
+import java.util.List;
+
+/**
+ * Unit tests for reassembling texts sent as several fragments
+ */
+public class TextReassemblerTest {
+
+    // 200 characters, 4 fragments of 50
+    private static final String LONG_TEXT = "MEET AT THE NORTH GATE AT 7, BRING WATER, MAPS AND THE SPARE RADIO. "
+            + "IF THE ROAD IS CLOSED TAKE THE FOREST TRACK PAST THE LAKE, WE WAIT UNTIL 8 THEN GO ON TO CAMP 2. "
+            + "CALL ON CHANNEL 3 IF YOU ARE LATE";
+
+    /** Fragments as they come off the air: serialized by the sender, deserialized by the receiver */
+    private static List<Protocol.TextMessage> receivedFragments(Protocol.TextMessage text) {
+        List<Protocol.TextMessage> received = new java.util.ArrayList<>();
+        for (byte[] frame : text.serializeFragments(Protocol.MAX_SERIALIZED_LENGTH)) {
+            received.add((Protocol.TextMessage) Protocol.Message.deserialize(frame));
+        }
+        return received;
+    }
+
+    @Test
+    public void testWholeTextPassesThrough() {
+        Protocol.TextMessage text = new Protocol.TextMessage((byte) 3, "HELLO");
+        assertSame(text, new TextReassembler().accept(text));
+    }
+
+    @Test
+    public void testReassemblesFragmentsArrivingOutOfOrder() {
+        Protocol.TextMessage text = new Protocol.TextMessage((byte) 40, LONG_TEXT, 37774200, -122419200);
+        List<Protocol.TextMessage> fragments = receivedFragments(text);
+        assertEquals(4, fragments.size());
+
+        TextReassembler reassembler = new TextReassembler();
+        assertNull(reassembler.accept(fragments.get(2)));
+        assertNull(reassembler.accept(fragments.get(0)));
+        assertNull(reassembler.accept(fragments.get(3)));
+        Protocol.TextMessage whole = reassembler.accept(fragments.get(1));
+
+        assertEquals(text, whole);
+        assertFalse(whole.isFragment());
+        assertEquals(0, reassembler.getPendingFragmentCount());
+    }
+
+    @Test
+    public void testDuplicateFragmentsAreIgnored() {
+        List<Protocol.TextMessage> fragments = receivedFragments(new Protocol.TextMessage((byte) 0, LONG_TEXT));
+
+        TextReassembler reassembler = new TextReassembler();
+        assertNull(reassembler.accept(fragments.get(1)));
+        assertNull(reassembler.accept(fragments.get(1))); // Retransmitted after a lost ACK
+        assertNull(reassembler.accept(fragments.get(0)));
+        assertNull(reassembler.accept(fragments.get(3)));
+        assertEquals(3, reassembler.getPendingFragmentCount());
+        assertEquals(LONG_TEXT, reassembler.accept(fragments.get(2)).text);
+    }
+
+    @Test
+    public void testOldestIncompleteTextIsDroppedPastTheLimit() {
+        List<Protocol.TextMessage> first = receivedFragments(new Protocol.TextMessage((byte) 10, LONG_TEXT));
+        List<Protocol.TextMessage> second = receivedFragments(new Protocol.TextMessage((byte) 20, LONG_TEXT));
+
+        TextReassembler reassembler = new TextReassembler(4);
+        assertNull(reassembler.accept(first.get(0)));
+        assertNull(reassembler.accept(first.get(1)));
+        assertNull(reassembler.accept(second.get(0)));
+        assertNull(reassembler.accept(second.get(1)));
+        assertNull(reassembler.accept(second.get(2))); // Fifth fragment: the first text goes
+        assertEquals(3, reassembler.getPendingFragmentCount());
+
+        assertEquals(LONG_TEXT, reassembler.accept(second.get(3)).text);
+
+        // The first text's earlier fragments are gone
+        assertNull(reassembler.accept(first.get(2)));
+        assertNull(reassembler.accept(first.get(3)));
+        assertEquals(2, reassembler.getPendingFragmentCount());
+    }
+
+    @Test
+    public void testTextWithMoreFragmentsThanTheLimitIsIgnored() {
+        List<Protocol.TextMessage> fragments = receivedFragments(new Protocol.TextMessage((byte) 0, LONG_TEXT));
+
+        TextReassembler reassembler = new TextReassembler(3);
+        for (Protocol.TextMessage fragment : fragments) {
+            assertNull(reassembler.accept(fragment));
+        }
+        assertEquals(0, reassembler.getPendingFragmentCount());
+    }
+
+    @Test
+    public void testReusedIdStartsANewText() {
+        List<Protocol.TextMessage> stale = receivedFragments(new Protocol.TextMessage((byte) 7, LONG_TEXT));
+        String shorter = LONG_TEXT.substring(0, 80);
+        List<Protocol.TextMessage> fresh = receivedFragments(new Protocol.TextMessage((byte) 7, shorter));
+
+        TextReassembler reassembler = new TextReassembler();
+        assertNull(reassembler.accept(stale.get(0)));
+        assertNull(reassembler.accept(fresh.get(1)));
+        assertEquals(shorter, reassembler.accept(fresh.get(0)).text);
+        assertEquals(0, reassembler.getPendingFragmentCount());
+    }
+}
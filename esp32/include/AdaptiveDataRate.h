@@ -0,0 +1,139 @@
+#ifndef ADAPTIVE_DATA_RATE_H
+#define ADAPTIVE_DATA_RATE_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstdint>
+#endif
+
+/// What the link margin asks of the data spreading factor
+enum class AdrDecision : uint8_t
+{
+    Hold,   // Stay at the factor in effect
+    Faster, // One step lower
+    Slower  // One step higher
+};
+
+/**
+ * Lowest SNR (dB) a frame can still be demodulated at: -7.5 dB at SF7, 2.5 dB
+ * lower per step down to -20 dB at SF12 (SX127x and SX126x datasheets)
+ */
+inline float loraDemodulationFloorDb(int spreadingFactor)
+{
+    return -7.5f - 2.5f * (spreadingFactor - 7);
+}
+
+/**
+ * SNR of the frames heard from the peer, and the spreading factor it calls for
+ * A step down needs a full window whose worst frame still clears the lower
+ * factor's floor by fasterMarginDb, so one fade in the window holds it back. A
+ * step up needs the window to average less than slowerMarginDb above the current
+ * floor, or missedAcks ACK timeouts in a row, since lost frames leave no samples.
+ * With fasterMarginDb above slowerMarginDb a step down can't be undone by the
+ * same link: the margin it leaves is still above the one that steps back up.
+ */
+class AdrMonitor
+{
+public:
+    static const int MAX_WINDOW = 64;
+
+    AdrMonitor(int window, float fasterMarginDb, float slowerMarginDb, int missedAcks)
+        : window(window > MAX_WINDOW ? MAX_WINDOW : window), fasterMarginDb(fasterMarginDb),
+          slowerMarginDb(slowerMarginDb), missedAcks(missedAcks), next(0), count(0), missedInARow(0) {}
+
+    /**
+     * Add the SNR (dB) of a frame from the peer, replacing the oldest once the window is full
+     */
+    void addSample(float snr)
+    {
+        samples[next] = snr;
+        next = (next + 1) % window;
+        if (count < window)
+        {
+            count++;
+        }
+        missedInARow = 0; // The peer is still heard
+    }
+
+    /**
+     * Count an ACK that never came
+     */
+    void addMissedAck()
+    {
+        if (missedInARow < 0xFF)
+        {
+            missedInARow++;
+        }
+    }
+
+    /**
+     * Forget all samples and missed ACKs (after a switch or a switch attempt)
+     */
+    void reset()
+    {
+        next = 0;
+        count = 0;
+        missedInARow = 0;
+    }
+
+    /// Samples in the window
+    int sampleCount() const
+    {
+        return count;
+    }
+
+    /// Lowest SNR in the window (0 if empty)
+    float worstSnr() const
+    {
+        float worst = 0;
+        for (int i = 0; i < count; i++)
+        {
+            worst = i == 0 || samples[i] < worst ? samples[i] : worst;
+        }
+        return worst;
+    }
+
+    /// Average SNR over the window (0 if empty)
+    float averageSnr() const
+    {
+        float sum = 0;
+        for (int i = 0; i < count; i++)
+        {
+            sum += samples[i];
+        }
+        return count == 0 ? 0 : sum / count;
+    }
+
+    /**
+     * Step the link calls for at spreadingFactor, kept within minSf..maxSf
+     */
+    AdrDecision decide(int spreadingFactor, int minSf, int maxSf) const
+    {
+        if (spreadingFactor < maxSf &&
+            (missedInARow >= missedAcks ||
+             (count == window && averageSnr() - loraDemodulationFloorDb(spreadingFactor) < slowerMarginDb)))
+        {
+            return AdrDecision::Slower;
+        }
+        if (spreadingFactor > minSf && count == window &&
+            worstSnr() - loraDemodulationFloorDb(spreadingFactor - 1) >= fasterMarginDb)
+        {
+            return AdrDecision::Faster;
+        }
+        return AdrDecision::Hold;
+    }
+
+private:
+    float samples[MAX_WINDOW];
+    int window;
+    float fasterMarginDb;
+    float slowerMarginDb;
+    int missedAcks;
+    int next;  // Slot for the next sample
+    int count; // Samples in the window
+    uint8_t missedInARow;
+};
+
+#endif // ADAPTIVE_DATA_RATE_H
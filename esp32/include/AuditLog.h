@@ -0,0 +1,142 @@
+#ifndef AUDIT_LOG_H
+#define AUDIT_LOG_H
+
+#include "Protocol.h"
+#include "bridge_config.h"
+
+/// What happened to an audited message
+enum class AuditOutcome : uint8_t
+{
+    Sent = 0,          // Transmitted over LoRa
+    Retransmitted = 1, // Sent again after a missing ACK
+    Acked = 2,         // Peer ACKed our text
+    AckTimeout = 3,    // Peer never ACKed our text
+    TxFailed = 4,      // Radio refused the frame
+    Received = 5,      // Received over LoRa and stored for the app
+    AckWithheld = 6,   // Received and stored, not ACKed (ACK_POLICY)
+    Dropped = 7        // Received but not stored (buffer full)
+};
+
+/// Direction of an audited message, seen from this bridge
+enum class AuditDirection : uint8_t
+{
+    Rx = 0,
+    Tx = 1
+};
+
+/// One audit log entry (serialized as 8 bytes, see serializeEntry())
+struct AuditEntry
+{
+    uint32_t timestampMs; // millis() when it happened, wraps after ~49 days
+    AuditDirection direction;
+    MessageType type;
+    uint8_t seq; // Text/Ack sequence number, 0 for other types
+    AuditOutcome outcome;
+};
+
+/// Serialized audit entry size in bytes
+const size_t AUDIT_ENTRY_LEN = 8;
+
+/**
+ * Fixed-capacity ring of the last AUDIT_LOG_CAPACITY message events
+ * RAM only: the log is empty after every reboot. Entries are numbered from 0
+ * since boot so a reader can page through it while new events arrive; the
+ * oldest entries are overwritten once the ring is full.
+ */
+class AuditLog
+{
+public:
+    AuditLog() : next(0), total(0) {}
+
+    /**
+     * Record one event, replacing the oldest once the ring is full
+     */
+    void record(uint32_t timestampMs, AuditDirection direction, MessageType type, uint8_t seq,
+                AuditOutcome outcome)
+    {
+        AuditEntry &entry = entries[next];
+        entry.timestampMs = timestampMs;
+        entry.direction = direction;
+        entry.type = type;
+        entry.seq = seq;
+        entry.outcome = outcome;
+        next = (next + 1) % AUDIT_LOG_CAPACITY;
+        total++;
+    }
+
+    /**
+     * Number of events recorded since boot (including overwritten ones)
+     */
+    uint32_t getTotal() const
+    {
+        return total;
+    }
+
+    /**
+     * Number of the oldest entry still held
+     */
+    uint32_t getFirstIndex() const
+    {
+        return total > AUDIT_LOG_CAPACITY ? total - AUDIT_LOG_CAPACITY : 0;
+    }
+
+    /**
+     * Get an entry by its number since boot
+     * @return False if the entry was overwritten or not recorded yet
+     */
+    bool get(uint32_t index, AuditEntry &out) const
+    {
+        if (index < getFirstIndex() || index >= total)
+        {
+            return false;
+        }
+        out = entries[index % AUDIT_LOG_CAPACITY];
+        return true;
+    }
+
+    /**
+     * Serialize up to maxEntries entries starting at entry number `from`
+     * (moved up to the oldest entry held if it was already overwritten)
+     * Format: [total u32][first index u32][count u8][entries...], little-endian
+     * @return Bytes written (9 + count * AUDIT_ENTRY_LEN)
+     */
+    size_t serializePage(uint32_t from, uint8_t maxEntries, uint8_t *out) const
+    {
+        if (from < getFirstIndex())
+        {
+            from = getFirstIndex();
+        }
+
+        uint8_t count = 0;
+        AuditEntry entry;
+        while (count < maxEntries && get(from + count, entry))
+        {
+            serializeEntry(entry, out + 9 + count * AUDIT_ENTRY_LEN);
+            count++;
+        }
+
+        writeU32(out, total);
+        writeU32(out + 4, from);
+        out[8] = count;
+        return 9 + count * AUDIT_ENTRY_LEN;
+    }
+
+    /**
+     * Serialize one entry: [timestamp u32][direction u8][type u8][seq u8][outcome u8]
+     */
+    static void serializeEntry(const AuditEntry &entry, uint8_t *out)
+    {
+        writeU32(out, entry.timestampMs);
+        out[4] = static_cast<uint8_t>(entry.direction);
+        out[5] = static_cast<uint8_t>(entry.type);
+        out[6] = entry.seq;
+        out[7] = static_cast<uint8_t>(entry.outcome);
+    }
+
+private:
+    AuditEntry entries[AUDIT_LOG_CAPACITY];
+    size_t next;
+    uint32_t total;
+};
+
+#endif // AUDIT_LOG_H
@@ -4,12 +4,192 @@
 #include <Arduino.h>
 #include <NimBLEDevice.h>
 #include <freertos/queue.h>
+#include <freertos/semphr.h>
 #include "Protocol.h"
+#include "bridge_config.h"
+#include "Backoff.h"
+#include "Diagnostics.h"
+#include "lora_config.h"
 
 // Service and Characteristic UUIDs
 #define SERVICE_UUID "00001234-0000-1000-8000-00805f9b34fb"
 #define TX_CHARACTERISTIC_UUID "00005678-0000-1000-8000-00805f9b34fb"
 #define RX_CHARACTERISTIC_UUID "00005679-0000-1000-8000-00805f9b34fb"
+#define CONTROL_CHARACTERISTIC_UUID "0000567a-0000-1000-8000-00805f9b34fb"
+#define UNLOCK_CHARACTERISTIC_UUID "0000567b-0000-1000-8000-00805f9b34fb"
+#define LAST_RX_CHARACTERISTIC_UUID "0000567c-0000-1000-8000-00805f9b34fb"
+#define HEAP_CHARACTERISTIC_UUID "0000567d-0000-1000-8000-00805f9b34fb"
+#define STATS_CHARACTERISTIC_UUID "0000567e-0000-1000-8000-00805f9b34fb"
+
+// Standard Device Information Service, read-only strings fixed at build time
+#define DEVICE_INFO_SERVICE_UUID "180A"
+#define MANUFACTURER_NAME_UUID "2A29"
+#define FIRMWARE_REVISION_UUID "2A26"
+#define HARDWARE_REVISION_UUID "2A27"
+
+// Standard Battery Service, level in percent (see BATTERY_LEVEL_PERCENT)
+#define BATTERY_SERVICE_UUID "180F"
+#define BATTERY_LEVEL_UUID "2A19"
+
+#ifndef DIS_MANUFACTURER_NAME
+#define DIS_MANUFACTURER_NAME "LoRa BLE Bridge"
+#endif
+
+// Board and radio; boards with other hardware set it in build_flags
+#ifndef DIS_HARDWARE_REVISION
+#if LORA_RADIO == LORA_RADIO_SX1262
+#define DIS_HARDWARE_REVISION "ESP32 + SX1262"
+#else
+#define DIS_HARDWARE_REVISION "ESP32 + SX127x"
+#endif
+#endif
+
+#define DIS_STRINGIFY_(x) #x
+#define DIS_STRINGIFY(x) DIS_STRINGIFY_(x)
+
+// "<major>.<minor>.<patch>+<git revision>", e.g. "1.0.0+6ba2f12"
+#define DIS_FIRMWARE_REVISION                                                                            \
+    DIS_STRINGIFY(FIRMWARE_VERSION_MAJOR) "." DIS_STRINGIFY(FIRMWARE_VERSION_MINOR) "." DIS_STRINGIFY( \
+        FIRMWARE_VERSION_PATCH) "+" FIRMWARE_GIT_HASH
+
+// Control characteristic: the app writes [opcode][args...], the response is
+// notified on the same characteristic as [opcode | CONTROL_RESPONSE_FLAG][payload...]
+// (fragmented like the TX characteristic when it exceeds the MTU).
+enum class ControlOpcode : uint8_t
+{
+    Diagnostics = 0x01,  // Respond with the diagnostics blob (see Diagnostics.h)
+    SpectrumScan = 0x02, // [start u32][stop u32][step u32] (or no args = whole band) -> RSSI per frequency
+    RxFrontEnd = 0x03,   // [rx boost u8][lna gain u8] (or no args = query) -> current settings
+    AuditLog = 0x04,     // [first entry u32] (or no args = oldest) -> one page of the audit log
+    Reboot = 0x05,       // [CONTROL_REBOOT_MAGIC] -> acknowledged, then a clean restart
+    LossInjection = 0x06, // [rx loss %][tx loss %] (or no args = query) -> current settings, LOSS_INJECTION builds only
+    Config = 0x07,        // [config blob] (or no args = export) -> config blob now in effect (see ConfigBlob.h)
+    NodeId = 0x08,        // [node id u8] (or no args = query) -> node id now in effect, saved in NVS
+    Inject = 0x09,        // [serialized message] -> [stored u8], delivered to the app as received, MESSAGE_INJECTION builds only
+    SpreadingFactor = 0x0A, // [sf u8] (or no args = query) -> data spreading factor now in effect, not saved
+    RadioSettings = 0x0B    // [frequency u32][tx power i8] (or no args = query) -> settings now in effect, not saved
+};
+
+const uint8_t CONTROL_RESPONSE_FLAG = 0x80;
+const uint8_t CONTROL_ERROR = 0xFF;  // [0xFF][rejected opcode]
+const uint8_t CONTROL_LOCKED = 0xFE; // [0xFE][rejected opcode], control is locked
+// Injection carries a whole serialized message
+const size_t CONTROL_MAX_ARGS = MESSAGE_INJECTION ? MAX_SERIALIZED_LEN : 16;
+
+// Argument the Reboot command must carry, so a stray write can't restart the bridge
+const uint8_t CONTROL_REBOOT_MAGIC[4] = {'B', 'O', 'O', 'T'};
+
+// Time for the Reboot acknowledgement to leave before the BLE stack goes down
+#ifndef REBOOT_NOTIFY_DELAY_MS
+#define REBOOT_NOTIFY_DELAY_MS 200
+#endif
+
+// Optional write lock on the control characteristic. With a non-empty passcode
+// (e.g. -DBLE_CONTROL_PASSCODE=\"4711\") every control command is rejected with
+// [CONTROL_LOCKED][opcode] until the passcode is written to the unlock
+// characteristic. The unlock ends after BLE_UNLOCK_TIMEOUT_MS without an accepted
+// command, on a wrong passcode and on disconnect. The RX characteristic (messages)
+// is never locked. The passcode crosses an unencrypted link in clear text, so
+// this only stops casual misconfiguration - it is not security (use bonding).
+// Empty (default) = no lock.
+#ifndef BLE_CONTROL_PASSCODE
+#define BLE_CONTROL_PASSCODE ""
+#endif
+
+#ifndef BLE_UNLOCK_TIMEOUT_MS
+#define BLE_UNLOCK_TIMEOUT_MS 300000
+#endif
+
+// Control command queued for the main loop
+struct ControlCommand
+{
+    uint8_t opcode;
+    uint8_t argLen;
+    uint8_t args[CONTROL_MAX_ARGS];
+};
+
+// How long to wait for the central to confirm an indication before giving up.
+// A central that disappears mid-indication would otherwise stall the main loop
+// until the ATT timeout (30 s) fires.
+#ifndef BLE_INDICATION_TIMEOUT_MS
+#define BLE_INDICATION_TIMEOUT_MS 2000
+#endif
+
+// Smallest MTU that fits every message in a single notification (3 bytes ATT header).
+// The firmware asks the central for it on connect; if the central ends up with a
+// smaller MTU, larger messages are split into BLE fragments (see Protocol.h).
+#ifndef BLE_REQUESTED_MTU
+#define BLE_REQUESTED_MTU (MAX_SERIALIZED_LEN + 3)
+#endif
+
+// Writes to the RX characteristic larger than one ATT payload are split by the
+// app using the same fragment framing as notifications (0xF1/0xF2, see Protocol.h)
+// and composed here before deserializing. A composition that isn't completed
+// within this time is discarded when the next write arrives.
+#ifndef BLE_COMPOSE_TIMEOUT_MS
+#define BLE_COMPOSE_TIMEOUT_MS 2000
+#endif
+
+// Wait after a disconnect before advertising again. Android often drops and
+// re-establishes the link within a few hundred ms during pairing or app restarts.
+#ifndef BLE_READVERTISE_DEBOUNCE_MS
+#define BLE_READVERTISE_DEBOUNCE_MS 500
+#endif
+
+// Coalesce buffered messages drained on reconnect into batch notifications
+// (0xF3, see Protocol.h): as many as fit in one MTU payload, up to
+// BLE_BATCH_MAX_MESSAGES. Fewer BLE transactions help slow Android stacks; it
+// only pays off with a negotiated MTU well above the default 23. Requires an app
+// that unpacks batches.
+#ifndef BLE_COALESCE_NOTIFICATIONS
+#define BLE_COALESCE_NOTIFICATIONS 0
+#endif
+
+#ifndef BLE_BATCH_MAX_MESSAGES
+#define BLE_BATCH_MAX_MESSAGES 4
+#endif
+
+// Notification pacing under controller backpressure. When a notify fails because
+// the controller ran out of ACL buffers (BLE_HS_ENOMEM), notifications pause for
+// the current pacing interval instead of retrying into a full controller. The
+// interval doubles on every such failure up to BLE_NOTIFY_PACING_MAX_MS and halves
+// back to BLE_NOTIFY_PACING_MIN_MS with each notification that goes through; it
+// is also the spacing of the buffered-message flush on reconnect.
+#ifndef BLE_NOTIFY_PACING_MIN_MS
+#define BLE_NOTIFY_PACING_MIN_MS 20
+#endif
+
+#ifndef BLE_NOTIFY_PACING_MAX_MS
+#define BLE_NOTIFY_PACING_MAX_MS 640
+#endif
+
+// Minimum spacing between advertising restarts, caps thrashing under rapid cycling
+#ifndef BLE_ADVERTISING_RESTART_MIN_INTERVAL_MS
+#define BLE_ADVERTISING_RESTART_MIN_INTERVAL_MS 2000
+#endif
+
+// Retry of a failed advertising start (e.g. the controller busy or out of memory):
+// the first retry waits BLE_ADVERTISING_RESTART_MIN_INTERVAL_MS, each further
+// failure doubles the wait up to BLE_ADVERTISING_BACKOFF_MAX_MS. Advertising is
+// never given up; a successful start resets the wait.
+#ifndef BLE_ADVERTISING_BACKOFF_MAX_MS
+#define BLE_ADVERTISING_BACKOFF_MAX_MS 60000
+#endif
+
+// Drop a central that has been idle this long: no write, read or subscription
+// change on any characteristic. Notifications the bridge sends don't count, so a
+// backgrounded app that only listens is dropped too. The link is closed cleanly
+// and advertising resumes, freeing the connection slot for another phone.
+// 0 (default) = never drop an idle central.
+#ifndef BLE_IDLE_DISCONNECT_MS
+#define BLE_IDLE_DISCONNECT_MS 0
+#endif
+
+// Default ATT MTU before any exchange
+#define BLE_DEFAULT_MTU 23
+
+// Longest ATT attribute value, and with it the longest fragment at any MTU
+#define BLE_MAX_ATT_PAYLOAD 512
 
 class BLEManager;
 
@@ -20,6 +200,7 @@ public:
     MyServerCallbacks(BLEManager *manager) : bleManager(manager) {}
     void onConnect(NimBLEServer *pServer, NimBLEConnInfo &connInfo);
     void onDisconnect(NimBLEServer *pServer, NimBLEConnInfo &connInfo, int reason);
+    void onMTUChange(uint16_t MTU, NimBLEConnInfo &connInfo);
 
 private:
     BLEManager *bleManager;
@@ -36,6 +217,63 @@ private:
     BLEManager *bleManager;
 };
 
+// Callback for control characteristic writes
+class MyControlCallbacks : public NimBLECharacteristicCallbacks
+{
+public:
+    MyControlCallbacks(BLEManager *manager) : bleManager(manager) {}
+    void onWrite(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo);
+
+private:
+    BLEManager *bleManager;
+};
+
+// Callback for unlock characteristic writes (passcode) and reads (lock state)
+class MyUnlockCallbacks : public NimBLECharacteristicCallbacks
+{
+public:
+    MyUnlockCallbacks(BLEManager *manager) : bleManager(manager) {}
+    void onWrite(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo);
+    void onRead(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo);
+
+private:
+    BLEManager *bleManager;
+};
+
+// Callback for last-RX characteristic reads (metadata of the last received frame)
+class MyLastRxCallbacks : public NimBLECharacteristicCallbacks
+{
+public:
+    MyLastRxCallbacks(BLEManager *manager) : bleManager(manager) {}
+    void onRead(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo);
+
+private:
+    BLEManager *bleManager;
+};
+
+// Callback for heap characteristic reads (memory headroom)
+class MyHeapCallbacks : public NimBLECharacteristicCallbacks
+{
+public:
+    MyHeapCallbacks(BLEManager *manager) : bleManager(manager) {}
+    void onRead(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo);
+
+private:
+    BLEManager *bleManager;
+};
+
+// Callback for TX characteristic subscription and indication status
+class MyTxCharacteristicCallbacks : public NimBLECharacteristicCallbacks
+{
+public:
+    MyTxCharacteristicCallbacks(BLEManager *manager) : bleManager(manager) {}
+    void onSubscribe(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo, uint16_t subValue);
+    void onStatus(NimBLECharacteristic *pCharacteristic, int code);
+
+private:
+    BLEManager *bleManager;
+};
+
 class BLEManager
 {
 public:
@@ -47,7 +285,7 @@ private:
     unsigned long lastActivityTime;
 
 public:
-    BLEManager(QueueHandle_t bleToLoraQueue);
+    BLEManager(QueueHandle_t bleToLoraQueue, QueueHandle_t controlQueue);
 
     /// Initialize BLE with device name
     bool setup(const char *deviceName = DEVICE_NAME);
@@ -55,39 +293,167 @@ public:
     /// Set activity callback (called on BLE events)
     void setActivityCallback(void (*callback)()) { activityCallback = callback; }
 
-    /// Start BLE advertising
-    void startAdvertising();
+    /// Start BLE advertising; on failure it is retried with backoff from process()
+    bool startAdvertising();
 
     /// Check if a BLE client is connected
     bool isConnected() const { return deviceConnected; }
 
-    /// Send a message to the connected BLE client via notification
+    /// Incremented on every new connection, lets callers detect a reconnect
+    /// that happened between two polls
+    uint32_t getConnectionId() const { return connectionId; }
+
+    /// Send a message to the connected BLE client.
+    /// Text and SOS messages, whatever their priority, are indicated (and confirmed)
+    /// when the client subscribed to indications, everything else is notified.
     bool sendMessage(const Message &msg);
 
+    /// Send leading messages of msgs in one batch notification, as many as fit the
+    /// MTU payload (a single message is sent like sendMessage()). The batch is
+    /// indicated if it holds a text and the client subscribed to indications.
+    /// Returns the number of messages delivered, 0 on failure.
+    size_t sendBatch(const Message *msgs, size_t count);
+
+    /// Whether notifications may be sent now (false while paused after the controller ran out of buffers)
+    bool canNotify() const;
+
+    /// Current spacing between notifications, adapted to controller backpressure
+    uint32_t getNotifyPacingMs() const { return notifyPacingMs; }
+
+    /// Check if the client subscribed to indications on the TX characteristic
+    bool isIndicationEnabled() const { return indicationsEnabled; }
+
     /// Process BLE events (call in main loop)
     void process();
 
     /// Called when RX characteristic is written
     void onMessageReceived(const uint8_t *data, size_t length);
 
+    /// Called when the control characteristic is written
+    void onControlReceived(const uint8_t *data, size_t length);
+
+    /// Called when the unlock characteristic is written
+    void onUnlockReceived(const uint8_t *data, size_t length);
+
+    /// Whether control commands are accepted (no passcode set, or unlocked and not timed out)
+    bool isControlUnlocked() const;
+
+    /// Record the reception context of a LoRa frame, returned by the next read of the last-RX characteristic
+    void setLastRxMeta(const LastRxMeta &meta);
+
+    /// Serialize the last-RX metadata as read by the app (LAST_RX_META_LEN bytes)
+    size_t readLastRxMeta(uint8_t *buf);
+
+    /// Set the Battery Level characteristic (0-100), notified to a subscribed phone if it changed
+    void setBatteryLevel(uint8_t percent);
+
+    /// Set the stats characteristic, notified to a subscribed phone if any counter changed
+    void setLinkStats(const LinkStats &stats);
+
+    /// Notify a control response (fragmented if it exceeds the MTU, at most BLE_MAX_FRAGMENTED_LEN bytes)
+    bool sendControlResponse(const uint8_t *data, size_t length);
+
     /// Connection state callbacks
-    void onConnected();
+    void onConnected(uint16_t handle);
     void onDisconnected();
+    void onMtuChanged(uint16_t mtu);
+
+    /// Negotiated ATT MTU of the current connection
+    uint16_t getMtu() const { return negotiatedMtu; }
+
+    /// TX characteristic callbacks
+    void onTxSubscribe(uint16_t subValue);
+    void onTxStatus(int code);
+
+    /// Any write, read or subscription change by the central (see BLE_IDLE_DISCONNECT_MS)
+    void onGattActivity() { lastGattActivityAt = millis(); }
 
 private:
     NimBLEServer *pServer;
     NimBLECharacteristic *pTxCharacteristic;
     NimBLECharacteristic *pRxCharacteristic;
+    NimBLECharacteristic *pControlCharacteristic;
+    NimBLECharacteristic *pUnlockCharacteristic;
+    NimBLECharacteristic *pLastRxCharacteristic;
+    NimBLECharacteristic *pHeapCharacteristic;
+    NimBLECharacteristic *pStatsCharacteristic;
+    NimBLECharacteristic *pBatteryLevelCharacteristic;
     NimBLEAdvertising *pAdvertising;
 
-    bool deviceConnected;
+    volatile bool deviceConnected;
     bool oldDeviceConnected;
+    volatile uint32_t connectionId;
+    volatile uint16_t connHandle; // Of the current connection
+
+    // Central idle timeout (see BLE_IDLE_DISCONNECT_MS)
+    volatile unsigned long lastGattActivityAt;
+    bool idleDisconnectPending;
+
+    // Debounced, rate-limited advertising restart after disconnect
+    volatile unsigned long disconnectedAt;
+    unsigned long lastAdvertisingStart;
+    bool advertisingRestartPending;
+    Backoff advertisingBackoff; // Retry of failed advertising starts
+    bool indicationsEnabled;
+    uint16_t negotiatedMtu;
+    uint8_t batteryLevel; // Last value set, BATTERY_UNKNOWN before the first
+    LinkStats linkStats;  // Last value set, all zero before the first
+
+    // Control write lock (see BLE_CONTROL_PASSCODE)
+    volatile bool controlUnlocked;
+    volatile unsigned long controlUnlockedAt; // Unlock or last accepted command
+
+    // Last received LoRa frame (written from the main loop, read from the NimBLE host task)
+    LastRxMeta lastRxMeta;
+    portMUX_TYPE lastRxMetaLock;
+
+    // Multi-write composition of inbound messages (touched only from the NimBLE host task)
+    uint8_t composeBuffer[MAX_SERIALIZED_LEN];
+    size_t composeExpected; // Announced message length, 0 = no composition in progress
+    size_t composeReceived;
+    unsigned long composeStartedAt;
+
+    // Indication confirmation handshake (given from the NimBLE host task)
+    SemaphoreHandle_t indicationSemaphore;
+    volatile bool awaitingIndication;
+    volatile bool indicationConfirmed;
+
+    // Notification backpressure (see BLE_NOTIFY_PACING_MIN_MS)
+    volatile int lastTxStatus; // Last notify/indicate status reported on the TX characteristic
+    uint32_t notifyPacingMs;
+    bool notifyPaused;
+    unsigned long notifyPausedAt;
 
     QueueHandle_t bleToLoraQueue;
+    QueueHandle_t controlQueue;
     String deviceNameStr; // Store device name for debugging
 
     MyServerCallbacks *serverCallbacks;
     MyCharacteristicCallbacks *rxCallbacks;
+    MyTxCharacteristicCallbacks *txCallbacks;
+    MyControlCallbacks *controlCallbacks;
+    MyUnlockCallbacks *unlockCallbacks;
+    MyLastRxCallbacks *lastRxCallbacks;
+    MyHeapCallbacks *heapCallbacks;
+
+    /// Add a fragmented RX write to the composition buffer.
+    /// Returns true once the announced message is complete in composeBuffer.
+    bool composeFragment(const uint8_t *data, size_t length);
+
+    /// Indicate a serialized message and wait for the central's confirmation
+    bool indicateAndWait(const uint8_t *data, size_t len);
+
+    /// Send a buffer on a characteristic, splitting it into fragments if it exceeds the MTU
+    /// @return False if it doesn't fit the fragment scheme (over BLE_MAX_FRAGMENTED_LEN bytes) or a send fails.
+    bool sendFragmented(NimBLECharacteristic *characteristic, const uint8_t *data, size_t len, bool indicate);
+
+    /// Notify or indicate one ATT payload (indications only on the TX characteristic).
+    /// Refuses a payload larger than the negotiated MTU allows instead of letting the
+    /// stack truncate it.
+    bool sendPayload(NimBLECharacteristic *characteristic, const uint8_t *data, size_t len, bool indicate);
+
+    /// Adapt the pacing after a notify or indicate attempt on the TX characteristic
+    void onTxAttempt(bool sent);
 
     void (*activityCallback)(); // Callback for activity updates
 };
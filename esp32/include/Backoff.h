@@ -0,0 +1,67 @@
+#ifndef BACKOFF_H
+#define BACKOFF_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstdint>
+#endif
+
+/**
+ * Bounded exponential backoff between attempts of a retryable operation
+ * The first failure waits minMs, each further one doubles the wait up to maxMs,
+ * and a success resets it. Retries never stop for good, and never run faster
+ * than minMs.
+ */
+class Backoff
+{
+public:
+    Backoff(unsigned long minMs, unsigned long maxMs)
+        : minMs(minMs), maxMs(maxMs), waitMs(0), failedAt(0), failureCount(0) {}
+
+    /// Whether the next attempt is due (always, until something failed)
+    bool ready(unsigned long now) const
+    {
+        return failureCount == 0 || now - failedAt >= waitMs;
+    }
+
+    /// Record a failed attempt: the next one waits minMs, then twice as long each time, up to maxMs
+    void fail(unsigned long now)
+    {
+        waitMs = failureCount == 0 ? minMs : (waitMs > maxMs / 2 ? maxMs : waitMs * 2);
+        failedAt = now;
+        if (failureCount < 0xFFFF)
+        {
+            failureCount++;
+        }
+    }
+
+    /// Record a success: the next failure starts again at minMs
+    void reset()
+    {
+        waitMs = 0;
+        failureCount = 0;
+    }
+
+    /// Wait after the last failure in ms (0 after a success)
+    unsigned long delayMs() const
+    {
+        return waitMs;
+    }
+
+    /// Failures in a row since the last success
+    uint16_t failures() const
+    {
+        return failureCount;
+    }
+
+private:
+    unsigned long minMs;
+    unsigned long maxMs;
+    unsigned long waitMs;
+    unsigned long failedAt;
+    uint16_t failureCount;
+};
+
+#endif // BACKOFF_H
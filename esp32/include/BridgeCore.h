@@ -0,0 +1,1603 @@
+#ifndef BRIDGE_CORE_H
+#define BRIDGE_CORE_H
+
+#include "Protocol.h"
+#include "MessageBuffer.h"
+#include "Diagnostics.h"
+#include "AuditLog.h"
+#include "bridge_config.h"
+#include "lora_config.h"
+
+#ifdef ARDUINO
+#define BRIDGE_LOG(...) Serial.printf(__VA_ARGS__)
+#else
+// Host builds have no serial port - arguments are still evaluated so nothing goes unused
+inline void bridgeLogDiscard(const char *, ...) {}
+#define BRIDGE_LOG(...) bridgeLogDiscard(__VA_ARGS__)
+#endif
+
+/**
+ * LoRa side of the bridge: the SX127x on the ESP32, an in-memory channel in host tests
+ */
+class RadioIo
+{
+public:
+    virtual ~RadioIo() {}
+
+    /// Transmit one frame and return to receive mode. Returns true if it was sent.
+    virtual bool send(const uint8_t *buf, size_t len) = 0;
+
+    /// Whether a frame is being received right now (sending would abort it)
+    virtual bool isReceiving() = 0;
+
+    /// Listen for another node's preamble (CAD) before transmitting. Returns true if one was heard.
+    virtual bool detectChannelActivity() = 0;
+
+    /// Whether a frame of len bytes fits the duty-cycle budget right now (LORA_ENFORCE_DUTY_CYCLE)
+    virtual bool airtimeAvailable(size_t len) = 0;
+};
+
+/**
+ * Phone side of the bridge: BLE queue and disconnected buffer on the ESP32
+ */
+class AppIo
+{
+public:
+    virtual ~AppIo() {}
+
+    /// Queue a received message for the app, or buffer it. Returns true if it was stored.
+    virtual bool deliver(const Message &msg) = 0;
+
+    /// Whether a phone is connected right now
+    virtual bool isConnected() const = 0;
+
+    /// Show a transient status to the app (never buffered while disconnected)
+    virtual void status(BridgeStatus code, uint8_t seq) = 0;
+};
+
+/// What the core did with a received frame
+enum class FrameResult : uint8_t
+{
+    Handled,        // Text or Ack, fully processed by the core
+    Passthrough,    // Decoded, the caller handles this type (msg is filled in)
+    Unparseable,    // Not a bridge frame (Raw and Status count too, they are never sent on air)
+    Unauthenticated // Without a valid auth tag (LORA_AUTH_KEY): to be dropped silently
+};
+
+static_assert(ACK_SUPPRESS_WINDOW_PERCENT < 100, "a retransmission after a lost ACK must be ACKed again");
+static_assert(RX_DEDUP_ENTRIES < 128, "the dedup window must not reach a reused seq");
+static_assert(PROTOCOL_PROFILE != PROTOCOL_PROFILE_MESSAGING || (!TRACK_STATUS && !GPS_DELTA_ENCODING),
+              "PROTOCOL_PROFILE_MESSAGING has no position updates to track or delta-encode");
+static_assert(LORA_FEC_PARITY <= MAX_FEC_PARITY_LEN, "LORA_FEC_PARITY is at most MAX_FEC_PARITY_LEN");
+
+/// When a text without its ACK is retransmitted (see RETRANSMIT_STRATEGY)
+enum class RetransmitStrategy : uint8_t
+{
+    Immediate = RETRANSMIT_IMMEDIATE, // After every ACK timeout
+    Backoff = RETRANSMIT_BACKOFF,     // Waits doubling from one ACK timeout up to RETRANSMIT_BACKOFF_MAX_MS
+    Scheduled = RETRANSMIT_SCHEDULED  // At the start of the next transmit window
+};
+
+/**
+ * Listen-before-talk backoff after the attempt-th busy CAD in a row (from 1): a
+ * random number of LBT_BACKOFF_SLOT_MS slots, 1-2 after the first, doubling up to
+ * 1-16 from the fourth on
+ */
+inline unsigned long lbtBackoffMs(uint8_t attempt, uint32_t random)
+{
+    uint8_t exponent = attempt < 1 ? 1 : (attempt > 4 ? 4 : attempt);
+    return (1 + random % (1UL << exponent)) * (unsigned long)LBT_BACKOFF_SLOT_MS;
+}
+
+/**
+ * Protocol logic of the bridge, free of FreeRTOS, BLE and radio driver code:
+ * - LoRa send queue with priority ordering and GPS coalescing
+ * - ACK wait per in-flight text, priority-based retransmission timed by the
+ *   RetransmitStrategy, and ACK timeouts, reported to the app as delivered or
+ *   failed (raw ACKs only with FORWARD_ACKS)
+ * - NACKs for damaged texts, and early retransmission of NACKed ones (NACK_DAMAGED_TEXTS)
+ * - Track status for position fixes (TRACK_STATUS): bitmap replies instead of
+ *   ACKs, and selective retransmission of the missing fixes
+ * - Position updates sent as offsets from the last full one (GPS_DELTA_ENCODING),
+ *   and received ones rebuilt into full positions for the app
+ * - No reuse of a sequence number that is still awaiting its ACK
+ * - Optionally taking one message from the app at a time (STOP_AND_WAIT)
+ * - Optionally neither ACKing nor retransmitting anything (FIRE_AND_FORGET)
+ * - Deferring transmissions while a frame is being received, or while the duty-cycle
+ *   budget is spent, with a status to the app
+ * - Optionally listening before talk, with a randomized backoff while CAD hears a preamble (LBT_CAD_ATTEMPTS)
+ * - Relaying frames once (unknown types with UNKNOWN_TYPE_RELAY, all traffic in RELAY_ONLY builds),
+ *   with a hop ACK for texts whose sender asks for one (ACK_SEMANTICS)
+ * - Enforcing the text variants of PROTOCOL_PROFILE
+ * - ACKing received texts according to ACK_POLICY and ACK_MIN_RSSI_DBM, once
+ *   per ACK_SUPPRESS_WINDOW_PERCENT of the ACK timeout, and delivering them to the app
+ *   once (RX_DEDUP_ENTRIES)
+ * - Recording every sent and received message in the audit log
+ * The caller passes the time in, so two cores can talk over an in-memory
+ * channel in a host test (test/test_bridge_core).
+ */
+class BridgeCore
+{
+public:
+    BridgeCore(RadioIo &radio, AppIo &app, BridgeStats &stats, AuditLog &audit)
+        : radio(radio), app(app), stats(stats), audit(audit), nodeId(NODE_ID), hasPendingPosition(false), ackTimeoutMs(0),
+          retransmitStrategy(static_cast<RetransmitStrategy>(RETRANSMIT_STRATEGY)), txWindowOpenedAt(0),
+          txWasAllowed(true), stopAndWait(STOP_AND_WAIT), fireAndForget(FIRE_AND_FORGET),
+          sendNacks(NACK_DAMAGED_TEXTS), linkQualityReports(LINK_QUALITY_REPORTS), gpsDeltaEncoding(GPS_DELTA_ENCODING), protocolProfile(PROTOCOL_PROFILE), heldSeqLogged(false), txDeferred(false), txDeferredSince(0), airtimeHeld(false),
+          cadAttempts(LBT_CAD_ATTEMPTS), busyChecks(0), busyAt(0), busyWaitMs(0), backoffRandom(0x9E3779B9u ^ NODE_ID), relayedNext(0), ackedNext(0), receivedNext(0), sentFixNext(0),
+          trackHeard(false), trackLastSeq(0), trackBitmap(0), trackDirty(false), trackDirtySince(0)
+    {
+        sos.repeatsLeft = 0;
+        sos.awaitingAck = false;
+        gpsSent.valid = false;
+        gpsHeard.valid = false;
+        for (InFlightText &text : inFlight)
+        {
+            text.used = false;
+        }
+        for (SentFix &fix : sentFixes)
+        {
+            fix.used = false;
+        }
+        for (RelayedFrame &frame : relayed)
+        {
+            frame.used = false;
+        }
+        for (AckedText &text : acked)
+        {
+            text.used = false;
+        }
+        for (ReceivedFrame &frame : received)
+        {
+            frame.used = false;
+        }
+#ifdef LORA_PAYLOAD_KEY
+        const uint8_t key[PAYLOAD_KEY_LEN] = {LORA_PAYLOAD_KEY};
+        setPayloadKey(key);
+#else
+        setPayloadKey(nullptr);
+#endif
+#ifdef LORA_AUTH_KEY
+        const uint8_t tagKey[AUTH_KEY_LEN] = {LORA_AUTH_KEY};
+        setAuthKey(tagKey);
+#else
+        setAuthKey(nullptr);
+#endif
+        setFecParity(LORA_FEC_PARITY);
+    }
+
+    /// Set the ACK wait (derived from the modulation, see loraAckTimeoutMs())
+    void setAckTimeout(unsigned long ms) { ackTimeoutMs = ms; }
+
+    /// Set when retransmissions go out (RETRANSMIT_STRATEGY)
+    void setRetransmitStrategy(RetransmitStrategy strategy) { retransmitStrategy = strategy; }
+
+    /// Node id sent in hop ACKs for relayed texts
+    void setNodeId(uint8_t id) { nodeId = id; }
+
+    /// Hold new messages from the app until the last one is resolved (STOP_AND_WAIT)
+    void setStopAndWait(bool on) { stopAndWait = on; }
+
+    /// Send every text once without awaiting an ACK, and ACK no received text (FIRE_AND_FORGET)
+    void setFireAndForget(bool on) { fireAndForget = on; }
+
+    /// Answer damaged texts with a NACK (NACK_DAMAGED_TEXTS)
+    void setSendNacks(bool on) { sendNacks = on; }
+
+    /// Follow every text stored for the app with its RSSI and SNR (LINK_QUALITY_REPORTS)
+    void setLinkQualityReports(bool on) { linkQualityReports = on; }
+
+    /// Send position updates as offsets from the last full one when they fit (GPS_DELTA_ENCODING)
+    void setGpsDeltaEncoding(bool on) { gpsDeltaEncoding = on; }
+
+    /// Text variants taken from the app and accepted over LoRa (PROTOCOL_PROFILE). The
+    /// encoder only has the variants of PROTOCOL_PROFILE, so this can only narrow it.
+    void setProtocolProfile(int profile) { protocolProfile = profile; }
+
+    /// Busy CADs before a transmission goes out anyway, 0 to transmit without a CAD (LBT_CAD_ATTEMPTS)
+    void setCadAttempts(uint8_t attempts) { cadAttempts = attempts; }
+
+    /// Seed the random listen-before-talk backoff, so nodes that heard the same frame don't retry in step
+    void setRandomSeed(uint32_t seed) { backoffRandom = seed != 0 ? seed : 1; }
+
+    /// Encrypt sent texts and decrypt received ones under a PAYLOAD_KEY_LEN byte key
+    /// (LORA_PAYLOAD_KEY), nullptr to send and expect them in the clear
+    void setPayloadKey(const uint8_t *key)
+    {
+        encryptPayload = key != nullptr;
+        if (encryptPayload)
+        {
+            memcpy(payloadKey, key, PAYLOAD_KEY_LEN);
+        }
+    }
+
+    /// Tag sent frames and drop received ones without a valid tag, under an
+    /// AUTH_KEY_LEN byte key (LORA_AUTH_KEY); nullptr to neither tag nor check
+    void setAuthKey(const uint8_t *key)
+    {
+        authenticate = key != nullptr;
+        if (authenticate)
+        {
+            memcpy(authKey, key, AUTH_KEY_LEN);
+        }
+    }
+
+    /// FEC parity bytes appended to every frame sent (LORA_FEC_PARITY), 0 for none.
+    /// Received frames are repaired by the caller before they get here, see Message::correctFec().
+    void setFecParity(uint8_t bytes) { fecParity = bytes; }
+
+    /// Serialize a message for the air: encrypted and tagged if there are keys, FEC parity last
+    /// @param bufSize MAX_FRAME_LEN leaves room for all three.
+    int encode(const Message &msg, uint8_t *buf, size_t bufSize)
+    {
+        int len = encryptPayload
+                      ? msg.serializeEncrypted(buf, bufSize, payloadKey, static_cast<uint8_t>(nextRandom()))
+                      : msg.serialize(buf, bufSize);
+        if (len > 0 && authenticate)
+        {
+            len = Message::addAuthTag(buf, len, bufSize, authKey);
+        }
+        if (len > 0 && fecParity > 0)
+        {
+            len = Message::addFec(buf, len, bufSize, fecParity);
+        }
+        return len;
+    }
+
+    /// Decode a frame from the air, checking its auth tag and decrypting it if there are keys
+    bool decode(const uint8_t *buf, size_t len, Message &msg) const
+    {
+        uint8_t frame[MAX_FRAME_LEN];
+        if (authenticate)
+        {
+            if (len > sizeof(frame))
+            {
+                return false;
+            }
+            memcpy(frame, buf, len);
+            int plainLen = Message::stripAuthTag(frame, len, authKey);
+            if (plainLen < 0)
+            {
+                return false;
+            }
+            buf = frame;
+            len = plainLen;
+        }
+        if (encryptPayload)
+        {
+            return msg.deserializeEncrypted(buf, len, payloadKey);
+        }
+        return msg.deserialize(buf, len);
+    }
+
+    /// Whether a frame carries a valid auth tag (always true without an auth key)
+    bool isAuthentic(const uint8_t *buf, size_t len) const
+    {
+        uint8_t frame[MAX_FRAME_LEN];
+        if (!authenticate)
+        {
+            return true;
+        }
+        if (len > sizeof(frame))
+        {
+            return false;
+        }
+        memcpy(frame, buf, len);
+        return Message::stripAuthTag(frame, len, authKey) > 0;
+    }
+
+    /**
+     * Whether submit() can take another message without dropping one. In
+     * stop-and-wait mode only once nothing is queued or awaiting an ACK, so the
+     * caller leaves the app's messages unread until the last text was ACKed or
+     * timed out.
+     */
+    bool canAccept() const
+    {
+        if (stopAndWait)
+        {
+            return backlog.isEmpty() && !hasPendingPosition && !isAwaitingAck();
+        }
+        return !backlog.isFull();
+    }
+
+    /**
+     * @brief Queue a message from the app for LoRa transmission
+     *
+     * Position-only updates are coalesced: only the newest one is kept since a
+     * stale position is worthless once a newer one exists. Messages go out in
+     * send priority order (Message::sendPriority()): ACKs, SOS and Critical texts,
+     * positions, then texts; equal send priorities keep their order.
+     * Texts are flagged for hop ACKs under ACK_SEMANTICS_HOP.
+     * @return False if the message is not valid in the protocol profile (the app
+     *         gets a Failed delivery report) or the backlog is full of messages
+     *         that rank at least as high (not queued).
+     */
+    bool submit(const Message &original)
+    {
+        if (!profileAllows(original, protocolProfile))
+        {
+            BRIDGE_LOG("Text variant not allowed by the protocol profile - rejected\n");
+            app.deliver(Message::createDeliveryReport(DeliveryOutcome::Failed, original.textData.seq));
+            return false;
+        }
+
+        Message msg = original;
+        if (msg.type == MessageType::Text && ACK_SEMANTICS == ACK_SEMANTICS_HOP)
+        {
+            msg.textData.hopAck = true;
+        }
+
+        if (isPositionUpdate(msg) && msg.priority() == MessagePriority::Normal)
+        {
+            if (hasPendingPosition)
+            {
+                BRIDGE_LOG("Coalescing GPS update - dropping stale position\n");
+            }
+            pendingPosition = msg;
+            hasPendingPosition = true;
+        }
+        else if (!backlog.addBySendPriority(msg))
+        {
+            BRIDGE_LOG("Backlog full - message dropped\n");
+            return false;
+        }
+        return true;
+    }
+
+    /**
+     * @brief Hand a message to the app as if it had been received over LoRa
+     *
+     * Bypasses the radio and everything done to received frames: no ACK, no
+     * duplicate detection, no stats or audit entry. The single entry point for
+     * testing the BLE notification and app parsing path without a peer.
+     * @return True if the message was queued or buffered for the app.
+     */
+    bool inject(const Message &msg)
+    {
+        BRIDGE_LOG("Injecting message type %d for the app (not received over LoRa)\n", static_cast<int>(msg.type));
+        return app.deliver(msg);
+    }
+
+    /**
+     * @brief Queue a received frame for retransmission unchanged (relay)
+     *
+     * A frame relayed within the last RELAY_DEDUP_MS is not relayed again, so
+     * relaying nodes in range of each other don't bounce it back and forth.
+     * A text whose sender accepts hop ACKs is answered with a HopAck, sent
+     * before the relayed frame, and again for every retransmission heard (the
+     * sender missed the first one). The frame is queued without its auth tag,
+     * which is signed again when it goes back on air.
+     * @return False if the frame was relayed recently, is longer than
+     *         MAX_RAW_LENGTH without its tag, fails the auth tag or the send queue is full.
+     */
+    bool relay(const uint8_t *buf, size_t len, unsigned long now)
+    {
+        // Room for the frame and its hop ACK
+        if (len == 0 || len > MAX_FRAME_LEN || backlog.getCount() + 2 > backlog.getCapacity())
+        {
+            return false;
+        }
+        uint8_t untagged[MAX_FRAME_LEN];
+        size_t untaggedLen = len;
+        memcpy(untagged, buf, len);
+        if (authenticate)
+        {
+            int plainLen = Message::stripAuthTag(untagged, len, authKey);
+            if (plainLen < 0)
+            {
+                BRIDGE_LOG("Auth tag mismatch - not relaying\n");
+                return false;
+            }
+            untaggedLen = plainLen;
+        }
+        if (untaggedLen > MAX_RAW_LENGTH)
+        {
+            return false;
+        }
+
+        Message text;
+        bool hopAck = decode(buf, len, text) && text.type == MessageType::Text && text.textData.hopAck;
+
+        uint32_t hash = frameHash(buf, len);
+        for (const RelayedFrame &frame : relayed)
+        {
+            if (frame.used && frame.hash == hash && now - frame.at < RELAY_DEDUP_MS)
+            {
+                BRIDGE_LOG("Frame relayed %lu ms ago - not relaying again\n", now - frame.at);
+                if (hopAck)
+                {
+                    backlog.addBySendPriority(Message::createHopAck(text.textData.seq, nodeId));
+                }
+                return false;
+            }
+        }
+
+        if (hopAck)
+        {
+            BRIDGE_LOG("Hop ACK for seq %u\n", text.textData.seq);
+            backlog.addBySendPriority(Message::createHopAck(text.textData.seq, nodeId));
+        }
+
+        RelayedFrame &slot = relayed[relayedNext];
+        slot.used = true;
+        slot.hash = hash;
+        slot.at = now;
+        relayedNext = (relayedNext + 1) % RELAY_DEDUP_ENTRIES;
+
+        // Raw never comes from the app, so a queued Raw is always a relayed frame
+        backlog.addBySendPriority(Message::createRaw(untagged, untaggedLen));
+        return true;
+    }
+
+    /**
+     * @brief Answer a damaged frame with a NACK if it still reads as a text (NACK_DAMAGED_TEXTS)
+     *
+     * The seq of a damaged frame may itself be wrong. A NACK for one only hurries
+     * a retransmission along and never fails a text, so a wrong one costs at most
+     * one early retransmission of another text. (A Rejected NACK does fail the
+     * text, but it answers an intact frame outside the protocol profile.) The length fields of an
+     * encrypted text can't be checked, so with a payload key nothing is NACKed.
+     * @param len Bytes read (fewer than frameLen for a truncated frame).
+     * @param frameLen Frame length on air.
+     * @return True if a NACK was queued.
+     */
+    bool nack(const uint8_t *buf, size_t len, size_t frameLen, DecodeFailureReason reason)
+    {
+        uint8_t seq;
+        if (!sendNacks || fireAndForget || encryptPayload || !damagedTextSeq(buf, len, frameLen, seq) || backlog.isFull())
+        {
+            return false;
+        }
+        BRIDGE_LOG("NACK for seq %u\n", seq);
+        backlog.addBySendPriority(Message::createNack(seq, reason));
+        return true;
+    }
+
+    /**
+     * @brief Drive retransmissions, ACK timeouts and the send queue
+     * @param now Current time in ms.
+     * @param txAllowed False to hold transmissions and retransmissions (e.g. while switching
+     *        channels or between listen windows); ACK timeouts still run.
+     * @return True if a new message from the send queue was transmitted.
+     */
+    bool tick(unsigned long now, bool txAllowed = true)
+    {
+        if (txAllowed && !txWasAllowed)
+        {
+            txWindowOpenedAt = now;
+        }
+        txWasAllowed = txAllowed;
+
+        for (InFlightText &text : inFlight)
+        {
+            if (!text.used || (!text.nacked && now - text.sentAt < retryWaitMs(text)))
+            {
+                continue;
+            }
+
+            if (text.retriesLeft > 0)
+            {
+                if (!txAllowed || !retryScheduled(text, now) || !radioFree(now, text.seq, text.frameLen))
+                {
+                    continue;
+                }
+                text.retriesLeft--;
+                text.transmissions++;
+                BRIDGE_LOG("%s for seq %u - retransmitting (%u retries left)\n", text.nacked ? "NACK" : "No ACK",
+                           text.seq, text.retriesLeft);
+                text.nacked = false;
+                bool resent = radio.send(text.frame, text.frameLen);
+                endDeferral(resent, text.seq);
+                audit.record(now, AuditDirection::Tx, MessageType::Text, text.seq,
+                             resent ? AuditOutcome::Retransmitted : AuditOutcome::TxFailed);
+                text.sentAt = now;
+            }
+            else
+            {
+                text.used = false;
+                stats.ackTimeouts++;
+                stats.lastError = BridgeError::AckTimeout;
+                audit.record(now, AuditDirection::Tx, MessageType::Text, text.seq, AuditOutcome::AckTimeout);
+                BRIDGE_LOG("No ACK for seq %u within %lu ms\n", text.seq, ackTimeoutMs);
+                app.deliver(Message::createDeliveryReport(DeliveryOutcome::Failed, text.seq));
+            }
+        }
+
+        if (txAllowed)
+        {
+            repeatSos(now);
+            resendMissingFixes(now);
+            sendTrackStatus(now);
+        }
+
+        // Held while a High/Critical text may still need a retransmission
+        if (!txAllowed || retransmissionPending())
+        {
+            return false;
+        }
+
+        Message msg;
+        if (!peekNext(msg))
+        {
+            return false;
+        }
+
+        // An ACK for a reused seq would be ambiguous - wait until the earlier text
+        // with that seq is ACKed or timed out (texts also hold while the table is
+        // full). A battery level or SOS is ACKed too, but never awaited.
+        if ((msg.type == MessageType::Text && !isTrackFix(msg) &&
+             (findInFlight(msg.textData.seq) != nullptr || freeInFlight() == nullptr)) ||
+            ((msg.type == MessageType::Battery || msg.type == MessageType::Sos) && findInFlight(seqOf(msg)) != nullptr))
+        {
+            if (!heldSeqLogged)
+            {
+                BRIDGE_LOG("Seq %u still awaiting an ACK (or too many texts in flight) - holding\n", seqOf(msg));
+                heldSeqLogged = true;
+            }
+            return false;
+        }
+        heldSeqLogged = false;
+
+        // A position update may go on air as a delta; the rest of the bookkeeping is the text's
+        Message onAir = msg;
+        if (gpsDeltaEncoding && msg.type == MessageType::Text)
+        {
+            onAir = encodeGpsPosition(msg, gpsSent, GPS_DELTA_RESYNC_EVERY);
+        }
+
+        uint8_t buf[MAX_FRAME_LEN];
+        int len;
+        if (msg.type == MessageType::Raw)
+        {
+            // Relayed frame goes back on air byte for byte, with the auth tag and parity
+            // of our own (it was queued without its tag, and arrived repaired and without its parity)
+            len = msg.rawData.frameLen;
+            memcpy(buf, msg.rawData.data, len);
+            if (authenticate)
+            {
+                len = Message::addAuthTag(buf, len, sizeof(buf), authKey);
+            }
+            if (len > 0 && fecParity > 0)
+            {
+                len = Message::addFec(buf, len, sizeof(buf), fecParity);
+            }
+        }
+        else
+        {
+            len = encode(onAir, buf, sizeof(buf));
+        }
+        if (len <= 0)
+        {
+            dropNext();
+            BRIDGE_LOG("Failed to serialize message for LoRa TX\n");
+            return false;
+        }
+
+        if (!radioFree(now, seqOf(msg), len))
+        {
+            return false;
+        }
+        dropNext();
+
+        BRIDGE_LOG("Transmitting %d bytes via LoRa\n", len);
+        bool sent = radio.send(buf, len);
+        if (!sent)
+        {
+            BRIDGE_LOG("LoRa TX failed, retrying once...\n");
+            sent = radio.send(buf, len);
+        }
+        endDeferral(sent, seqOf(msg));
+        audit.record(now, AuditDirection::Tx, msg.type, seqOf(msg), sent ? AuditOutcome::Sent : AuditOutcome::TxFailed);
+        if (!sent)
+        {
+            BRIDGE_LOG("LoRa TX failed permanently\n");
+            return false;
+        }
+        if (gpsDeltaEncoding && msg.type == MessageType::Text)
+        {
+            rememberGpsPosition(gpsSent, onAir);
+        }
+
+        // Fixes are confirmed by the peer's track status, other texts by an ACK -
+        // unless nothing is confirmed at all
+        if (fireAndForget && msg.type == MessageType::Text)
+        {
+            app.deliver(Message::createDeliveryReport(DeliveryOutcome::Sent, msg.textData.seq));
+        }
+        else if (isTrackFix(msg))
+        {
+            rememberFix(msg.textData.seq, buf, len);
+        }
+        else if (msg.type == MessageType::Text)
+        {
+            InFlightText *text = freeInFlight();
+            text->used = true;
+            text->seq = msg.textData.seq;
+            text->sentAt = now;
+            text->retriesLeft = retriesForPriority(msg.priority());
+            text->transmissions = 1;
+            text->hopAck = msg.textData.hopAck;
+            text->nacked = false;
+            memcpy(text->frame, buf, len);
+            text->frameLen = len;
+        }
+        else if (msg.type == MessageType::Sos)
+        {
+            // Repeated unchanged, see repeatSos()
+            sos.seq = msg.sosData.seq;
+            sos.sentAt = now;
+            sos.repeatsLeft = SOS_REPEATS;
+            sos.awaitingAck = !fireAndForget;
+            memcpy(sos.frame, buf, len);
+            sos.frameLen = len;
+        }
+        return true;
+    }
+
+    /**
+     * @brief Process a complete frame received over LoRa
+     * @param rssi RSSI of the frame in dBm.
+     * @param msg Receives the decoded message (valid for Handled and Passthrough).
+     * @param snr SNR of the frame in dB (for link quality reports).
+     */
+    FrameResult onRadioFrame(const uint8_t *buf, size_t len, int rssi, unsigned long now, Message &msg,
+                             float snr = 0.0f)
+    {
+        if (!isAuthentic(buf, len))
+        {
+            BRIDGE_LOG("Auth tag mismatch - dropping frame\n");
+            return FrameResult::Unauthenticated;
+        }
+        if (!decode(buf, len, msg) || msg.type == MessageType::Raw || msg.type == MessageType::Status ||
+            msg.type == MessageType::DecodeFailure || msg.type == MessageType::DeliveryReport ||
+            msg.type == MessageType::LinkQuality)
+        {
+            return FrameResult::Unparseable;
+        }
+
+        switch (msg.type)
+        {
+        case MessageType::Text:
+            if (!profileAllows(msg, protocolProfile))
+            {
+                refuseText(msg.textData.seq, now);
+                return FrameResult::Unparseable;
+            }
+            decodeGpsPosition(msg, gpsHeard, msg); // A full position update becomes the reference of deltas
+            receiveText(msg, frameHash(buf, len), rssi, snr, now);
+            return FrameResult::Handled;
+
+        case MessageType::GpsDelta:
+        {
+            uint8_t seq = msg.gpsDeltaData.seq;
+            if (!decodeGpsPosition(msg, gpsHeard, msg))
+            {
+                // Not ACKed: the sender retransmits it, and its next full update resyncs
+                BRIDGE_LOG("GPS delta seq %u against seq %u, which never arrived - dropped\n", seq,
+                           msg.gpsDeltaData.refSeq);
+                audit.record(now, AuditDirection::Rx, MessageType::GpsDelta, seq, AuditOutcome::Dropped);
+                return FrameResult::Handled;
+            }
+            if (!profileAllows(msg, protocolProfile))
+            {
+                refuseText(seq, now);
+                return FrameResult::Unparseable;
+            }
+            receiveText(msg, frameHash(buf, len), rssi, snr, now);
+            return FrameResult::Handled;
+        }
+
+        case MessageType::Sos:
+        {
+            BRIDGE_LOG("SOS - seq: %u at %.6f, %.6f\n", msg.sosData.seq, msg.sosData.lat / 1000000.0,
+                       msg.sosData.lon / 1000000.0);
+            // Every repeat goes to the app, it shows the caller still needs help;
+            // only the first copy is ACKed
+            uint32_t hash = frameHash(buf, len);
+            bool repeat = receivedBefore(MessageType::Sos, msg.sosData.seq, hash);
+            bool stored = app.deliver(msg);
+            if (repeat)
+            {
+                audit.record(now, AuditDirection::Rx, MessageType::Sos, msg.sosData.seq,
+                             stored ? AuditOutcome::Received : AuditOutcome::Dropped);
+                return FrameResult::Handled;
+            }
+            if (stored)
+            {
+                rememberReceived(MessageType::Sos, msg.sosData.seq, hash);
+            }
+            acknowledge(MessageType::Sos, msg.sosData.seq, stored, hash, rssi, now);
+            return FrameResult::Handled;
+        }
+
+        case MessageType::Battery:
+        {
+            BRIDGE_LOG("Battery - seq: %u, %u%%, %u mV\n", msg.batteryData.seq, msg.batteryData.percent,
+                       msg.batteryData.millivolts);
+            uint32_t hash = frameHash(buf, len);
+            bool duplicate;
+            acknowledge(MessageType::Battery, msg.batteryData.seq, deliverOnce(msg, hash, duplicate), hash, rssi, now);
+            return FrameResult::Handled;
+        }
+
+        case MessageType::Ack:
+        {
+            BRIDGE_LOG("ACK - seq: %u\n", msg.ackData.seq);
+            InFlightText *text = findInFlight(msg.ackData.seq);
+            if (text != nullptr)
+            {
+                text->used = false;
+                BRIDGE_LOG("ACK received after %lu ms\n", now - text->sentAt);
+                audit.record(now, AuditDirection::Tx, MessageType::Text, msg.ackData.seq, AuditOutcome::Acked);
+                app.deliver(Message::createDeliveryReport(DeliveryOutcome::Delivered, msg.ackData.seq));
+            }
+            else if (sos.awaitingAck && sos.seq == msg.ackData.seq)
+            {
+                // Someone heard the SOS; the repeats still go out
+                sos.awaitingAck = false;
+                audit.record(now, AuditDirection::Tx, MessageType::Sos, msg.ackData.seq, AuditOutcome::Acked);
+                app.deliver(Message::createDeliveryReport(DeliveryOutcome::Delivered, msg.ackData.seq));
+            }
+            else
+            {
+                // Late (after its timeout) or not ours - only the raw ACK tells the app
+                audit.record(now, AuditDirection::Rx, MessageType::Ack, msg.ackData.seq, AuditOutcome::Received);
+            }
+#if FORWARD_ACKS
+            app.deliver(msg);
+#endif
+            return FrameResult::Handled;
+        }
+
+        case MessageType::HopAck:
+        {
+            BRIDGE_LOG("Hop ACK - seq: %u from relay %u\n", msg.hopAckData.seq, msg.hopAckData.relayNode);
+            InFlightText *text = findInFlight(msg.hopAckData.seq);
+            if (text != nullptr && text->hopAck)
+            {
+                text->used = false;
+                audit.record(now, AuditDirection::Tx, MessageType::Text, msg.hopAckData.seq, AuditOutcome::Acked);
+                app.deliver(Message::createDeliveryReport(DeliveryOutcome::Relayed, msg.hopAckData.seq));
+            }
+            else
+            {
+                // Not ours, or our text waits for the end-to-end ACK
+                audit.record(now, AuditDirection::Rx, MessageType::HopAck, msg.hopAckData.seq,
+                             AuditOutcome::Received);
+            }
+            return FrameResult::Handled;
+        }
+
+        case MessageType::Nack:
+        {
+            BRIDGE_LOG("NACK - seq: %u (reason %u)\n", msg.nackData.seq, static_cast<unsigned>(msg.nackData.reason));
+            audit.record(now, AuditDirection::Rx, MessageType::Nack, msg.nackData.seq, AuditOutcome::Received);
+            InFlightText *text = findInFlight(msg.nackData.seq);
+            if (text != nullptr && msg.nackData.reason == DecodeFailureReason::Rejected)
+            {
+                // Outside the peer's protocol profile: no retransmission can help
+                text->used = false;
+                audit.record(now, AuditDirection::Tx, MessageType::Text, text->seq, AuditOutcome::AckTimeout);
+                app.deliver(Message::createDeliveryReport(DeliveryOutcome::Failed, text->seq));
+            }
+            else if (text != nullptr && text->retriesLeft > 0)
+            {
+                // Retransmitted by the next tick(); without retries left the ACK wait runs on
+                text->nacked = true;
+            }
+            return FrameResult::Handled;
+        }
+
+        case MessageType::TrackStatus:
+            audit.record(now, AuditDirection::Rx, MessageType::TrackStatus, msg.trackStatusData.lastSeq,
+                         AuditOutcome::Received);
+            onTrackStatus(msg.trackStatusData, now);
+            return FrameResult::Handled;
+
+        case MessageType::Identify:
+        case MessageType::IdentifyResponse:
+        case MessageType::ConfigRequest:
+        case MessageType::ConfigResponse:
+        case MessageType::ChannelSwitch:
+        case MessageType::RateSwitch:
+        case MessageType::ListenSchedule:
+        case MessageType::Raw:
+        case MessageType::Status:
+        case MessageType::DecodeFailure:
+        case MessageType::DeliveryReport:
+        case MessageType::LinkQuality:
+            break;
+        }
+        return FrameResult::Passthrough;
+    }
+
+    /// Whether a transmitted text is still waiting for its ACK
+    bool isAwaitingAck() const
+    {
+        for (const InFlightText &text : inFlight)
+        {
+            if (text.used)
+            {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Whether tick() has work to do soon (queued messages or a retransmission)
+    bool hasPendingWork() const
+    {
+        return !backlog.isEmpty() || hasPendingPosition || retransmissionPending() || fixResendPending() ||
+               sos.repeatsLeft > 0;
+    }
+
+    /// Number of LoRa retransmissions allowed for a message priority
+    static uint8_t retriesForPriority(MessagePriority priority)
+    {
+        switch (priority)
+        {
+        case MessagePriority::High:
+            return PRIORITY_HIGH_RETRIES;
+        case MessagePriority::Critical:
+            return PRIORITY_CRITICAL_RETRIES;
+        case MessagePriority::Normal:
+            break;
+        }
+        return PRIORITY_NORMAL_RETRIES;
+    }
+
+    /// Sequence number of a Text, Battery, Sos, Ack, Nack or HopAck, 0 for other types
+    static uint8_t seqOf(const Message &msg)
+    {
+        switch (msg.type)
+        {
+        case MessageType::Text:
+            return msg.textData.seq;
+        case MessageType::Ack:
+            return msg.ackData.seq;
+        case MessageType::Nack:
+            return msg.nackData.seq;
+        case MessageType::Battery:
+            return msg.batteryData.seq;
+        case MessageType::Sos:
+            return msg.sosData.seq;
+        case MessageType::HopAck:
+            return msg.hopAckData.seq;
+        case MessageType::GpsDelta:
+            return msg.gpsDeltaData.seq;
+        default:
+            break;
+        }
+        return 0;
+    }
+
+    /**
+     * Whether a frame may be relayed: listen schedule beacons, the channel and
+     * rate switch handshakes, hop ACKs and NACKs only concern the nodes in range of
+     * the sender (a repeated beacon carries the wrong timing, a repeated handshake
+     * would retune nodes that never agreed to it, a hop ACK or NACK answers the
+     * previous hop)
+     */
+    static bool isRelayable(const uint8_t *buf, size_t len)
+    {
+        return len > 0 && buf[0] != static_cast<uint8_t>(MessageType::ListenSchedule) &&
+               buf[0] != static_cast<uint8_t>(MessageType::ChannelSwitch) &&
+               buf[0] != static_cast<uint8_t>(MessageType::RateSwitch) &&
+               buf[0] != static_cast<uint8_t>(MessageType::HopAck) &&
+               buf[0] != static_cast<uint8_t>(MessageType::Nack);
+    }
+
+    /**
+     * Seq of a damaged frame that still reads as a text: the type byte is Text
+     * and, as far as they were read, its length fields agree with each other and
+     * with the frame length on air
+     */
+    static bool damagedTextSeq(const uint8_t *buf, size_t len, size_t frameLen, uint8_t &seq)
+    {
+        if (len < 2 || buf[0] != static_cast<uint8_t>(MessageType::Text))
+        {
+            return false;
+        }
+        if (len >= 4)
+        {
+            uint8_t codeCount = buf[2];
+            uint8_t packedLen = buf[3];
+            if (codeCount > MAX_TEXT_LENGTH || packedLen != (codeCount * 6 + 7) / 8 ||
+                frameLen < 5 + packedLen + MESSAGE_CRC_LEN)
+            {
+                return false;
+            }
+        }
+        seq = buf[1];
+        return true;
+    }
+
+    /// Check if a message is a position-only update (GPS and no text)
+    static bool isPositionUpdate(const Message &msg)
+    {
+        return msg.type == MessageType::Text && msg.textData.hasGps && msg.textData.text[0] == '\0';
+    }
+
+    /// Whether a message is valid in a protocol profile (only texts are restricted)
+    static bool profileAllows(const Message &msg, int profile = PROTOCOL_PROFILE)
+    {
+        return msg.type != MessageType::Text ||
+               profileAllowsText(profile, msg.textData.hasGps, msg.textData.text[0] != '\0');
+    }
+
+    /// Whether a text is a fix of a track, confirmed by track status instead of an ACK (TRACK_STATUS)
+    static bool isTrackFix(const Message &msg)
+    {
+        return TRACK_STATUS && isPositionUpdate(msg) && msg.priority() == MessagePriority::Normal;
+    }
+
+private:
+    RadioIo &radio;
+    AppIo &app;
+    BridgeStats &stats;
+    AuditLog &audit;
+    uint8_t nodeId;
+
+    // Messages waiting for LoRa TX in send priority order (never coalesced)
+    MessageBuffer backlog;
+
+    // Newest position-only update waiting for LoRa TX (older ones are dropped)
+    Message pendingPosition;
+    bool hasPendingPosition;
+
+    // Texts transmitted and still awaiting their ACK, with the frame kept for retransmission
+    struct InFlightText
+    {
+        bool used;
+        uint8_t seq;
+        unsigned long sentAt; // Last (re)transmission
+        uint8_t retriesLeft;
+        uint8_t transmissions; // Including retransmissions, for the backoff
+        bool hopAck; // A relay's HopAck completes it (TEXT_FLAG_HOP_ACK)
+        bool nacked; // A NACK asked for the next retransmission right away
+        uint8_t frame[MAX_FRAME_LEN];
+        size_t frameLen;
+    };
+    InFlightText inFlight[MAX_TEXTS_IN_FLIGHT];
+
+    // Last SOS sent, repeated SOS_REPEATS times whether ACKed or not
+    struct SosRepeat
+    {
+        uint8_t seq;
+        unsigned long sentAt; // Last transmission
+        uint8_t repeatsLeft;
+        bool awaitingAck; // The first ACK is reported to the app
+        uint8_t frame[MAX_FRAME_LEN];
+        size_t frameLen;
+    };
+    SosRepeat sos;
+    unsigned long ackTimeoutMs;
+    RetransmitStrategy retransmitStrategy;
+    unsigned long txWindowOpenedAt; // Last time tick() was called with txAllowed after a hold
+    bool txWasAllowed;
+    bool stopAndWait; // One message from the app at a time, see canAccept()
+    bool fireAndForget; // No ACKs sent or awaited, no retransmissions
+    bool sendNacks; // Answer damaged texts with a NACK
+    bool linkQualityReports; // Follow received texts with a LinkQuality message
+    bool gpsDeltaEncoding;   // Position updates go on air as GpsDelta when they can
+    GpsReference gpsSent;    // Last full position update sent, reference of our deltas
+    GpsReference gpsHeard;   // Last full position update received, reference of the peer's deltas
+    int protocolProfile;     // PROTOCOL_PROFILE_* of the texts submitted and received
+    bool encryptPayload;     // Texts on air are encrypted under payloadKey
+    uint8_t payloadKey[PAYLOAD_KEY_LEN];
+    bool authenticate;       // Frames on air carry a tag under authKey
+    uint8_t authKey[AUTH_KEY_LEN];
+    uint8_t fecParity;       // Reed-Solomon parity bytes after every frame sent
+    bool heldSeqLogged; // Log a held seq once, not on every tick
+
+    // Transmission waiting for a reception in progress to complete
+    bool txDeferred;
+    unsigned long txDeferredSince;
+
+    // Transmission waiting for the duty-cycle budget
+    bool airtimeHeld;
+
+    // Listen before talk: busy CADs in a row, and the backoff after the last one
+    uint8_t cadAttempts;
+    uint8_t busyChecks;
+    unsigned long busyAt;
+    unsigned long busyWaitMs;
+    uint32_t backoffRandom; // xorshift32 state
+
+    /**
+     * Whether the radio may transmit a frame of len bytes now. While the duty-cycle
+     * budget is spent it is held as long as that takes (DutyCycleLimited to the app
+     * once). While a frame is being received the transmission is deferred (RadioBusy
+     * to the app once), for at most one ACK timeout so a stuck modem status can't
+     * block the link.
+     */
+    bool radioFree(unsigned long now, uint8_t seq, size_t len)
+    {
+        if (!radio.airtimeAvailable(len))
+        {
+            if (!airtimeHeld)
+            {
+                airtimeHeld = true;
+                BRIDGE_LOG("Duty-cycle budget spent - holding seq %u\n", seq);
+                app.status(BridgeStatus::DutyCycleLimited, seq);
+            }
+            return false;
+        }
+
+        if (!radio.isReceiving())
+        {
+            return channelClear(now, seq);
+        }
+
+        if (!txDeferred)
+        {
+            txDeferred = true;
+            txDeferredSince = now;
+            BRIDGE_LOG("Reception in progress - deferring TX of seq %u\n", seq);
+            app.status(BridgeStatus::RadioBusy, seq);
+            return false;
+        }
+
+        if (now - txDeferredSince < ackTimeoutMs)
+        {
+            return false;
+        }
+
+        BRIDGE_LOG("Radio still receiving after %lu ms - transmitting anyway\n", now - txDeferredSince);
+        return true;
+    }
+
+    // Recently relayed frames, by hash
+    struct RelayedFrame
+    {
+        bool used;
+        uint32_t hash;
+        unsigned long at;
+    };
+    RelayedFrame relayed[RELAY_DEDUP_ENTRIES];
+    size_t relayedNext;
+
+    // Recently ACKed texts, by seq and frame hash
+    struct AckedText
+    {
+        bool used;
+        uint8_t seq;
+        uint32_t hash;
+        unsigned long at; // Last ACK
+    };
+    AckedText acked[ACK_SUPPRESS_ENTRIES];
+    size_t ackedNext;
+
+    // Texts and battery levels delivered to the app, oldest overwritten first
+    struct ReceivedFrame
+    {
+        bool used;
+        MessageType type;
+        uint8_t seq;
+        uint32_t hash;
+    };
+    ReceivedFrame received[RX_DEDUP_ENTRIES > 0 ? RX_DEDUP_ENTRIES : 1];
+    size_t receivedNext;
+
+    // Fixes sent without an ACK, kept until a track status confirms or gives up on them
+    struct SentFix
+    {
+        bool used;
+        bool resend; // Reported missing - retransmit on the next tick
+        uint8_t seq;
+        uint8_t retriesLeft;
+        uint8_t frame[MAX_FRAME_LEN];
+        size_t frameLen;
+    };
+    SentFix sentFixes[TRACK_HISTORY];
+    size_t sentFixNext;
+
+    // Fixes received from the peer, for the next track status
+    bool trackHeard;
+    uint8_t trackLastSeq;
+    uint32_t trackBitmap; // Bit i: fix trackLastSeq - i received
+    bool trackDirty;      // Received fixes not reported yet
+    unsigned long trackDirtySince;
+
+    /// FNV-1a over the frame bytes
+    static uint32_t frameHash(const uint8_t *buf, size_t len)
+    {
+        uint32_t hash = 2166136261u;
+        for (size_t i = 0; i < len; i++)
+        {
+            hash = (hash ^ buf[i]) * 16777619u;
+        }
+        return hash;
+    }
+
+    /// Whether the app already got this frame (see RX_DEDUP_ENTRIES)
+    bool receivedBefore(MessageType type, uint8_t seq, uint32_t hash) const
+    {
+        for (size_t i = 0; i < RX_DEDUP_ENTRIES; i++)
+        {
+            const ReceivedFrame &frame = received[i];
+            if (frame.used && frame.type == type && frame.seq == seq && frame.hash == hash)
+            {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Remember a frame delivered to the app, replacing the oldest one
+    void rememberReceived(MessageType type, uint8_t seq, uint32_t hash)
+    {
+        if (RX_DEDUP_ENTRIES == 0)
+        {
+            return;
+        }
+        ReceivedFrame &frame = received[receivedNext];
+        frame.used = true;
+        frame.type = type;
+        frame.seq = seq;
+        frame.hash = hash;
+        receivedNext = (receivedNext + 1) % (sizeof(received) / sizeof(received[0]));
+    }
+
+    /**
+     * Deliver a received text or battery level to the app unless it already has
+     * this frame; a copy is only ACKed again
+     * @param duplicate Set if this is a copy.
+     * @return Whether the app has it (queued or buffered, now or before).
+     */
+    bool deliverOnce(const Message &msg, uint32_t hash, bool &duplicate)
+    {
+        duplicate = receivedBefore(msg.type, seqOf(msg), hash);
+        if (duplicate)
+        {
+            BRIDGE_LOG("Duplicate of seq %u - ACKed, not delivered again\n", seqOf(msg));
+            return true;
+        }
+        if (!app.deliver(msg))
+        {
+            return false;
+        }
+        rememberReceived(msg.type, seqOf(msg), hash);
+        return true;
+    }
+
+    /**
+     * Whether a text was ACKed too recently for this copy to be a retransmission
+     * (see ACK_SUPPRESS_WINDOW_PERCENT). Otherwise the ACK about to be sent is
+     * remembered and the window restarts.
+     */
+    bool ackRecentlySent(uint8_t seq, uint32_t hash, unsigned long now)
+    {
+        unsigned long window = ackTimeoutMs * ACK_SUPPRESS_WINDOW_PERCENT / 100;
+        for (AckedText &text : acked)
+        {
+            if (text.used && text.seq == seq && text.hash == hash)
+            {
+                if (now - text.at < window)
+                {
+                    return true;
+                }
+                text.at = now;
+                return false;
+            }
+        }
+
+        AckedText &slot = acked[ackedNext];
+        slot.used = true;
+        slot.seq = seq;
+        slot.hash = hash;
+        slot.at = now;
+        ackedNext = (ackedNext + 1) % ACK_SUPPRESS_ENTRIES;
+        return false;
+    }
+
+    /**
+     * Listen before talk: whether a CAD hears the channel quiet. A preamble backs
+     * the transmission off for lbtBackoffMs(); after cadAttempts busy checks in a
+     * row it goes out anyway, so a jammed channel can't block the link.
+     */
+    bool channelClear(unsigned long now, uint8_t seq)
+    {
+        if (cadAttempts == 0)
+        {
+            return true;
+        }
+        if (busyChecks > 0 && now - busyAt < busyWaitMs)
+        {
+            return false;
+        }
+        if (busyChecks >= cadAttempts)
+        {
+            BRIDGE_LOG("Channel still busy after %u CADs - transmitting seq %u anyway\n", busyChecks, seq);
+            return true;
+        }
+        if (!radio.detectChannelActivity())
+        {
+            return true;
+        }
+
+        busyChecks++;
+        busyAt = now;
+        busyWaitMs = lbtBackoffMs(busyChecks, nextRandom());
+        BRIDGE_LOG("Channel busy - backing off seq %u for %lu ms\n", seq, busyWaitMs);
+        return false;
+    }
+
+    /// xorshift32: plenty to spread backoffs, no hardware RNG needed on the host
+    uint32_t nextRandom()
+    {
+        backoffRandom ^= backoffRandom << 13;
+        backoffRandom ^= backoffRandom >> 17;
+        backoffRandom ^= backoffRandom << 5;
+        return backoffRandom;
+    }
+
+    /// Close a deferral after the transmission it was waiting for (TxResumed to the app if sent)
+    void endDeferral(bool sent, uint8_t seq)
+    {
+        busyChecks = 0;
+        if (!txDeferred && !airtimeHeld)
+        {
+            return;
+        }
+        txDeferred = false;
+        airtimeHeld = false;
+        if (sent)
+        {
+            app.status(BridgeStatus::TxResumed, seq);
+        }
+    }
+
+    InFlightText *findInFlight(uint8_t seq)
+    {
+        for (InFlightText &text : inFlight)
+        {
+            if (text.used && text.seq == seq)
+            {
+                return &text;
+            }
+        }
+        return nullptr;
+    }
+
+    InFlightText *freeInFlight()
+    {
+        for (InFlightText &text : inFlight)
+        {
+            if (!text.used)
+            {
+                return &text;
+            }
+        }
+        return nullptr;
+    }
+
+    /**
+     * Time after its last transmission that a text is retransmitted, or fails
+     * once it has no retries left. Never shorter than the ACK timeout.
+     */
+    unsigned long retryWaitMs(const InFlightText &text) const
+    {
+        if (text.retriesLeft == 0 || retransmitStrategy != RetransmitStrategy::Backoff)
+        {
+            return ackTimeoutMs;
+        }
+
+        // One ACK timeout, doubled for every retransmission already sent
+        unsigned long cap = RETRANSMIT_BACKOFF_MAX_MS > ackTimeoutMs ? RETRANSMIT_BACKOFF_MAX_MS : ackTimeoutMs;
+        unsigned long wait = ackTimeoutMs;
+        for (uint8_t i = 1; i < text.transmissions && wait < cap; i++)
+        {
+            wait *= 2;
+        }
+        return wait < cap ? wait : cap;
+    }
+
+    /// Whether a timed-out text may be retransmitted now under the strategy
+    bool retryScheduled(const InFlightText &text, unsigned long now) const
+    {
+        if (retransmitStrategy != RetransmitStrategy::Scheduled || text.nacked)
+        {
+            return true; // A NACKed text goes out in the current window
+        }
+        // Only once a transmit window has opened after the ACK timeout ran out
+        return now - txWindowOpenedAt <= now - (text.sentAt + ackTimeoutMs);
+    }
+
+    bool retransmissionPending() const
+    {
+        for (const InFlightText &text : inFlight)
+        {
+            if (text.used && text.retriesLeft > 0)
+            {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Keep a sent fix for selective retransmission, replacing one with the same seq or the oldest
+    void rememberFix(uint8_t seq, const uint8_t *frame, size_t len)
+    {
+        SentFix *slot = &sentFixes[sentFixNext];
+        for (SentFix &fix : sentFixes)
+        {
+            if (fix.used && fix.seq == seq)
+            {
+                slot = &fix;
+            }
+        }
+        if (slot == &sentFixes[sentFixNext])
+        {
+            sentFixNext = (sentFixNext + 1) % TRACK_HISTORY;
+        }
+
+        slot->used = true;
+        slot->resend = false;
+        slot->seq = seq;
+        slot->retriesLeft = TRACK_MAX_RETRIES;
+        memcpy(slot->frame, frame, len);
+        slot->frameLen = len;
+    }
+
+    /// Confirm the fixes a track status reports received, and mark the missing ones for retransmission
+    void onTrackStatus(const TrackStatusMessage &status, unsigned long now)
+    {
+        for (SentFix &fix : sentFixes)
+        {
+            uint8_t behind = status.lastSeq - fix.seq;
+            if (!fix.used || behind >= 128)
+            {
+                continue; // Free, or sent after the newest fix the peer got
+            }
+
+            if (behind < TRACK_STATUS_WINDOW && (status.bitmap >> behind) & 1)
+            {
+                fix.used = false;
+                audit.record(now, AuditDirection::Tx, MessageType::Text, fix.seq, AuditOutcome::Acked);
+            }
+            else if (behind >= TRACK_STATUS_WINDOW || fix.retriesLeft == 0)
+            {
+                fix.used = false;
+                stats.ackTimeouts++;
+                stats.lastError = BridgeError::AckTimeout;
+                audit.record(now, AuditDirection::Tx, MessageType::Text, fix.seq, AuditOutcome::AckTimeout);
+                BRIDGE_LOG("Fix seq %u missing at the peer - giving up\n", fix.seq);
+            }
+            else
+            {
+                fix.resend = true;
+            }
+        }
+    }
+
+    /// Send the last SOS again once SOS_REPEAT_INTERVAL_MS has passed, while repeats are left
+    void repeatSos(unsigned long now)
+    {
+        if (sos.repeatsLeft == 0 || now - sos.sentAt < SOS_REPEAT_INTERVAL_MS || !radioFree(now, sos.seq, sos.frameLen))
+        {
+            return;
+        }
+        sos.repeatsLeft--;
+        BRIDGE_LOG("Repeating SOS seq %u (%u repeats left)\n", sos.seq, sos.repeatsLeft);
+        bool sent = radio.send(sos.frame, sos.frameLen);
+        endDeferral(sent, sos.seq);
+        audit.record(now, AuditDirection::Tx, MessageType::Sos, sos.seq,
+                     sent ? AuditOutcome::Retransmitted : AuditOutcome::TxFailed);
+        sos.sentAt = now;
+    }
+
+    /// Retransmit the fixes the last track status reported missing
+    void resendMissingFixes(unsigned long now)
+    {
+        for (SentFix &fix : sentFixes)
+        {
+            if (!fix.used || !fix.resend || !radioFree(now, fix.seq, fix.frameLen))
+            {
+                continue;
+            }
+            fix.resend = false;
+            fix.retriesLeft--;
+            BRIDGE_LOG("Fix seq %u missing at the peer - retransmitting (%u retries left)\n", fix.seq,
+                       fix.retriesLeft);
+            bool resent = radio.send(fix.frame, fix.frameLen);
+            endDeferral(resent, fix.seq);
+            audit.record(now, AuditDirection::Tx, MessageType::Text, fix.seq,
+                         resent ? AuditOutcome::Retransmitted : AuditOutcome::TxFailed);
+        }
+    }
+
+    bool fixResendPending() const
+    {
+        for (const SentFix &fix : sentFixes)
+        {
+            if (fix.used && fix.resend)
+            {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Add a received fix to the bitmap of the next track status
+    void recordFix(uint8_t seq, unsigned long now)
+    {
+        uint8_t ahead = seq - trackLastSeq;
+        if (!trackHeard || (ahead != 0 && ahead < 128))
+        {
+            // Newest fix so far: slide the window forward
+            trackBitmap = !trackHeard || ahead >= TRACK_STATUS_WINDOW ? 0 : trackBitmap << ahead;
+            trackBitmap |= 1;
+            trackLastSeq = seq;
+            trackHeard = true;
+        }
+        else
+        {
+            uint8_t behind = trackLastSeq - seq;
+            if (behind < TRACK_STATUS_WINDOW)
+            {
+                trackBitmap |= 1UL << behind;
+            }
+        }
+
+        if (!trackDirty)
+        {
+            trackDirty = true;
+            trackDirtySince = now;
+        }
+    }
+
+    /// Report the received fixes TRACK_STATUS_INTERVAL_MS after the first unreported one
+    void sendTrackStatus(unsigned long now)
+    {
+        if (!trackDirty || now - trackDirtySince < TRACK_STATUS_INTERVAL_MS || radio.isReceiving())
+        {
+            return;
+        }
+
+        Message status = Message::createTrackStatus(trackLastSeq, trackBitmap);
+        uint8_t buf[MAX_FRAME_LEN];
+        int len = encode(status, buf, sizeof(buf));
+        if (len <= 0)
+        {
+            return;
+        }
+
+        BRIDGE_LOG("Sending track status up to seq %u: %08lx\n", trackLastSeq, (unsigned long)trackBitmap);
+        bool sent = radio.send(buf, len);
+        audit.record(now, AuditDirection::Tx, MessageType::TrackStatus, trackLastSeq,
+                     sent ? AuditOutcome::Sent : AuditOutcome::TxFailed);
+        trackDirty = !sent;
+    }
+
+    /// Whether the pending position goes out before the head of the backlog
+    bool pendingPositionIsNext() const
+    {
+        Message head;
+        return hasPendingPosition && (!backlog.peek(head) || head.sendPriority() < pendingPosition.sendPriority());
+    }
+
+    /// Next message to transmit, by send priority across the backlog and the pending position
+    bool peekNext(Message &out) const
+    {
+        if (pendingPositionIsNext())
+        {
+            out = pendingPosition;
+            return true;
+        }
+        return backlog.peek(out);
+    }
+
+    /// Remove the message returned by peekNext()
+    void dropNext()
+    {
+        if (pendingPositionIsNext())
+        {
+            hasPendingPosition = false;
+            return;
+        }
+        Message discarded;
+        backlog.get(discarded);
+    }
+
+    /// Answer a received text outside PROTOCOL_PROFILE with a NACK (reason Rejected)
+    /// instead of leaving the sender to retransmit it until its ACK wait runs out
+    void refuseText(uint8_t seq, unsigned long now)
+    {
+        BRIDGE_LOG("Text variant outside the protocol profile - NACKed\n");
+        audit.record(now, AuditDirection::Rx, MessageType::Text, seq, AuditOutcome::Dropped);
+        if (!fireAndForget && !backlog.isFull())
+        {
+            backlog.addBySendPriority(Message::createNack(seq, DecodeFailureReason::Rejected));
+        }
+    }
+
+    /// Store a received text for the app first - depending on ACK_POLICY the ACK confirms it
+    void receiveText(const Message &msg, uint32_t hash, int rssi, float snr, unsigned long now)
+    {
+        BRIDGE_LOG("Text - seq: %u, text: \"%s\"\n", msg.textData.seq, msg.textData.text);
+        if (msg.textData.hasGps)
+        {
+            BRIDGE_LOG("GPS: %.6f, %.6f\n", msg.textData.lat / 1000000.0, msg.textData.lon / 1000000.0);
+        }
+
+        bool duplicate;
+        bool stored = deliverOnce(msg, hash, duplicate);
+        if (stored && !duplicate && linkQualityReports)
+        {
+            // Right behind the text, so the app can match it to the text's seq
+            app.deliver(Message::createLinkQuality(msg.textData.seq, static_cast<int16_t>(rssi), snr));
+        }
+        if (isTrackFix(msg))
+        {
+            // Confirmed in the next track status instead of an ACK (if stored)
+            if (stored)
+            {
+                recordFix(msg.textData.seq, now);
+            }
+            audit.record(now, AuditDirection::Rx, MessageType::Text, msg.textData.seq,
+                         stored ? AuditOutcome::Received : AuditOutcome::Dropped);
+            return;
+        }
+
+        acknowledge(MessageType::Text, msg.textData.seq, stored, hash, rssi, now);
+    }
+
+    /**
+     * ACK a received text or battery level according to ACK_POLICY, ACK_MIN_RSSI_DBM
+     * and the ACK suppression window, and record it in the audit log
+     * @param stored Whether the app got it (queued or buffered).
+     */
+    void acknowledge(MessageType type, uint8_t seq, bool stored, uint32_t hash, int rssi, unsigned long now)
+    {
+        if (fireAndForget)
+        {
+            // The sender awaits no ACK
+            audit.record(now, AuditDirection::Rx, type, seq, stored ? AuditOutcome::Received : AuditOutcome::Dropped);
+            return;
+        }
+
+        bool ackAllowed = true;
+#if ACK_POLICY == ACK_POLICY_CONNECTED
+        ackAllowed = stored && app.isConnected();
+#elif ACK_POLICY == ACK_POLICY_BUFFERED
+        ackAllowed = stored;
+#endif
+
+        if (ackAllowed && rssi < ACK_MIN_RSSI_DBM)
+        {
+            // Too weak for the ACK to make it back - don't waste the airtime
+            BRIDGE_LOG("ACK suppressed for seq %u (RSSI %d dBm below %d dBm)\n", seq, rssi, ACK_MIN_RSSI_DBM);
+            audit.record(now, AuditDirection::Rx, type, seq, AuditOutcome::AckWithheld);
+            return;
+        }
+
+        if (!ackAllowed)
+        {
+            BRIDGE_LOG("ACK withheld for seq %u (%s)\n", seq, stored ? "no phone connected" : "buffer full");
+            audit.record(now, AuditDirection::Rx, type, seq, stored ? AuditOutcome::AckWithheld : AuditOutcome::Dropped);
+            return;
+        }
+        if (ackRecentlySent(seq, hash, now))
+        {
+            // Too soon to be a retransmission after a lost ACK - don't feed an ACK storm
+            BRIDGE_LOG("Duplicate of seq %u within the ACK suppression window - not ACKed again\n", seq);
+            audit.record(now, AuditDirection::Rx, type, seq, AuditOutcome::AckWithheld);
+            return;
+        }
+        audit.record(now, AuditDirection::Rx, type, seq, stored ? AuditOutcome::Received : AuditOutcome::Dropped);
+
+        Message ack = Message::createAck(seq);
+        uint8_t ackBuf[MAX_FRAME_LEN];
+        int ackLen = encode(ack, ackBuf, sizeof(ackBuf));
+        if (ackLen > 0)
+        {
+            BRIDGE_LOG("Sending ACK for seq: %u\n", seq);
+            bool ackSent = radio.send(ackBuf, ackLen);
+            BRIDGE_LOG(ackSent ? "ACK sent successfully\n" : "ACK send failed\n");
+        }
+    }
+};
+
+#endif // BRIDGE_CORE_H
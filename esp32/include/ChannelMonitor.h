@@ -0,0 +1,72 @@
+#ifndef CHANNEL_MONITOR_H
+#define CHANNEL_MONITOR_H
+
+#include <Arduino.h>
+
+/**
+ * Rolling RSSI floor of the operating channel, sampled during idle RX
+ * Interference is reported once a full window averages above the threshold,
+ * so short bursts (a nearby transmitter keying up once) never trigger a switch.
+ */
+class ChannelMonitor
+{
+public:
+    static const int MAX_WINDOW = 120;
+
+    ChannelMonitor(int window, int16_t thresholdDbm)
+        : window(window > MAX_WINDOW ? MAX_WINDOW : window), thresholdDbm(thresholdDbm), next(0), count(0), sum(0) {}
+
+    /**
+     * Add one RSSI floor sample (dBm), replacing the oldest once the window is full
+     */
+    void addSample(int16_t rssi)
+    {
+        if (count == window)
+        {
+            sum -= samples[next];
+        }
+        else
+        {
+            count++;
+        }
+        samples[next] = rssi;
+        sum += rssi;
+        next = (next + 1) % window;
+    }
+
+    /**
+     * Average floor over the samples collected so far (0 if none)
+     */
+    int16_t averageFloor() const
+    {
+        return count == 0 ? 0 : sum / count;
+    }
+
+    /**
+     * True once a full window averages above the interference threshold
+     */
+    bool interferenceDetected() const
+    {
+        return count == window && averageFloor() > thresholdDbm;
+    }
+
+    /**
+     * Forget all samples (after a switch or a rejected switch attempt)
+     */
+    void reset()
+    {
+        next = 0;
+        count = 0;
+        sum = 0;
+    }
+
+private:
+    int16_t samples[MAX_WINDOW];
+    int window;
+    int16_t thresholdDbm;
+    int next;  // Slot for the next sample
+    int count; // Samples in the window
+    long sum;
+};
+
+#endif // CHANNEL_MONITOR_H
@@ -0,0 +1,123 @@
+#ifndef CONFIG_BLOB_H
+#define CONFIG_BLOB_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
+#endif
+#include "Protocol.h"
+#include "bridge_config.h"
+#include "lora_config.h"
+
+/// Bump when the config blob layout changes; blobs of other versions are rejected
+const uint8_t CONFIG_FORMAT_VERSION = 1;
+
+/// Serialized config size in bytes (format version 1)
+const size_t CONFIG_BLOB_LEN = 14;
+
+// Build flags that change what goes on air, both ends of a link need the same
+const uint8_t CONFIG_FLAG_PROMISCUOUS = 0x01;
+const uint8_t CONFIG_FLAG_CHANNEL_AGILITY = 0x02;
+const uint8_t CONFIG_FLAG_LISTEN_WINDOWS = 0x04;
+const uint8_t CONFIG_FLAG_TRACK_STATUS = 0x08;
+
+/// Complete bridge configuration, exported from one unit and imported into others
+struct BridgeConfig
+{
+    NodeInfo node;      // Node id is per unit: exported, never applied
+    uint8_t syncWord;
+    bool rxBoost;
+    uint8_t lnaGain;    // 0 = AGC, 1-6 = G1..G6
+    uint8_t buildFlags; // CONFIG_FLAG_* of the firmware build
+};
+
+/**
+ * @brief CONFIG_FLAG_* bits of this firmware build
+ */
+inline uint8_t configBuildFlags()
+{
+    uint8_t flags = 0;
+#if PROMISCUOUS_MODE
+    flags |= CONFIG_FLAG_PROMISCUOUS;
+#endif
+#if CHANNEL_AGILITY
+    flags |= CONFIG_FLAG_CHANNEL_AGILITY;
+#endif
+#if LISTEN_WINDOWS
+    flags |= CONFIG_FLAG_LISTEN_WINDOWS;
+#endif
+#if TRACK_STATUS
+    flags |= CONFIG_FLAG_TRACK_STATUS;
+#endif
+    return flags;
+}
+
+/**
+ * @brief Serializes a configuration into the config blob (little-endian)
+ * @param config Configuration to serialize.
+ * @param buf Output buffer of at least CONFIG_BLOB_LEN bytes.
+ * @return Number of bytes written (CONFIG_BLOB_LEN).
+ */
+inline size_t serializeConfig(const BridgeConfig &config, uint8_t *buf)
+{
+    buf[0] = CONFIG_FORMAT_VERSION;
+    buf[1] = config.node.nodeId;
+    writeU32(buf + 2, config.node.frequency);
+    buf[6] = config.node.spreadingFactor;
+    buf[7] = config.node.bandwidthIndex;
+    buf[8] = config.node.codingRate;
+    buf[9] = static_cast<uint8_t>(config.node.txPower);
+    buf[10] = config.syncWord;
+    buf[11] = config.rxBoost ? 1 : 0;
+    buf[12] = config.lnaGain;
+    buf[13] = config.buildFlags;
+    return CONFIG_BLOB_LEN;
+}
+
+/**
+ * @brief Parses a config blob, checking its version, length and value ranges
+ * @param buf Blob as written by serializeConfig().
+ * @param len Blob length.
+ * @param config Filled in on success.
+ * @return True if the blob is a well-formed blob of CONFIG_FORMAT_VERSION.
+ */
+inline bool parseConfig(const uint8_t *buf, size_t len, BridgeConfig &config)
+{
+    if (len != CONFIG_BLOB_LEN || buf[0] != CONFIG_FORMAT_VERSION)
+    {
+        return false;
+    }
+
+    config.node.nodeId = buf[1];
+    config.node.frequency = readU32(buf + 2);
+    config.node.spreadingFactor = buf[6];
+    config.node.bandwidthIndex = buf[7];
+    config.node.codingRate = buf[8];
+    config.node.txPower = static_cast<int8_t>(buf[9]);
+    config.syncWord = buf[10];
+    config.rxBoost = buf[11] == 1;
+    config.lnaGain = buf[12];
+    config.buildFlags = buf[13];
+    return buf[11] <= 1 && config.lnaGain <= LORA_LNA_GAIN_MIN_SETTING;
+}
+
+/**
+ * @brief Checks that an imported config only differs from ours in what can be applied at runtime
+ *
+ * Modulation, TX power, sync word and the build flags are fixed by the firmware
+ * build (the spreading factor and TX power as changed at runtime, see control
+ * opcodes 0x0A and 0x0B): a blob that differs in any of them is rejected.
+ */
+inline bool configCompatible(const BridgeConfig &imported, const BridgeConfig &local)
+{
+    return imported.node.spreadingFactor == local.node.spreadingFactor &&
+           imported.node.bandwidthIndex == local.node.bandwidthIndex &&
+           imported.node.codingRate == local.node.codingRate && imported.node.txPower == local.node.txPower &&
+           imported.syncWord == local.syncWord && imported.buildFlags == local.buildFlags;
+}
+
+#endif // CONFIG_BLOB_H
@@ -0,0 +1,217 @@
+#ifndef DIAGNOSTICS_H
+#define DIAGNOSTICS_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
+#endif
+#include "Protocol.h"
+
+/// Bump when the diagnostics layout changes so support tools can tell versions apart
+const uint8_t DIAGNOSTICS_FORMAT_VERSION = 2;
+
+/// Serialized diagnostics size in bytes (format version 2)
+const size_t DIAGNOSTICS_LEN = 35;
+
+/// Unknown battery level / not measured on this board
+const uint8_t BATTERY_UNKNOWN = 0xFF;
+
+/// Last error recorded by the bridge (reported in diagnostics)
+enum class BridgeError : uint8_t
+{
+    None = 0x00,
+    DeserializeFailed = 0x01, // LoRa frame could not be decoded
+    TruncatedFrame = 0x02,    // LoRa frame larger than it could be read
+    TxFailed = 0x03,          // LoRa transmission failed
+    BleDeliveryFailed = 0x04, // Notification/indication to the app failed
+    QueueFull = 0x05,         // Inter-task queue overflowed
+    AckTimeout = 0x06,        // No ACK within the SF-aware timeout
+    AuthFailed = 0x07         // LoRa frame without a valid auth tag (LORA_AUTH_KEY)
+};
+
+/// Counters and last-seen values collected by the bridge at runtime
+struct BridgeStats
+{
+    uint16_t loraRxFrames;
+    uint16_t loraTxFrames;
+    uint16_t loraTxFailures;
+    uint16_t ackTimeouts;
+    uint16_t loraCrcFailures; // Frames dropped for a CRC mismatch
+    uint16_t acksReceived;    // ACK frames heard, late and foreign ones included
+    int16_t lastRssi; // dBm of the last received frame
+    float lastSnr;    // dB of the last received frame
+    BridgeError lastError;
+};
+
+/// Everything reported by the diagnostics command
+struct DiagnosticsInfo
+{
+    uint8_t firmwareMajor;
+    uint8_t firmwareMinor;
+    uint8_t firmwarePatch;
+    NodeInfo node;
+    BridgeStats stats;
+    uint8_t batteryPercent; // BATTERY_UNKNOWN if not measured
+    uint8_t peerCount;      // Entries in the peer table
+    uint8_t dedupWindow;    // Size of the duplicate-detection window (0 = disabled)
+    uint32_t uptimeSeconds;
+    uint8_t bufferedMessages;
+    uint16_t bufferDrops; // Messages dropped by the full BLE buffer (wraps)
+};
+
+/**
+ * @brief Serializes diagnostics into the compact binary format (little-endian)
+ * @param info Diagnostics to serialize.
+ * @param buf Output buffer of at least DIAGNOSTICS_LEN bytes.
+ * @return Number of bytes written (DIAGNOSTICS_LEN).
+ */
+inline size_t serializeDiagnostics(const DiagnosticsInfo &info, uint8_t *buf)
+{
+    buf[0] = DIAGNOSTICS_FORMAT_VERSION;
+    buf[1] = info.firmwareMajor;
+    buf[2] = info.firmwareMinor;
+    buf[3] = info.firmwarePatch;
+    buf[4] = info.node.nodeId;
+    writeU32(buf + 5, info.node.frequency);
+    buf[9] = info.node.spreadingFactor;
+    buf[10] = info.node.bandwidthIndex;
+    buf[11] = info.node.codingRate;
+    buf[12] = static_cast<uint8_t>(info.node.txPower);
+    writeI16(buf + 13, info.stats.lastRssi);
+    buf[15] = static_cast<uint8_t>(static_cast<int8_t>(info.stats.lastSnr * 4)); // 0.25 dB steps
+    writeU16(buf + 16, info.stats.loraRxFrames);
+    writeU16(buf + 18, info.stats.loraTxFrames);
+    writeU16(buf + 20, info.stats.loraTxFailures);
+    writeU16(buf + 22, info.stats.ackTimeouts);
+    buf[24] = info.batteryPercent;
+    buf[25] = info.peerCount;
+    buf[26] = info.dedupWindow;
+    writeU32(buf + 27, info.uptimeSeconds);
+    buf[31] = static_cast<uint8_t>(info.stats.lastError);
+    buf[32] = info.bufferedMessages;
+    writeU16(buf + 33, info.bufferDrops);
+    return DIAGNOSTICS_LEN;
+}
+
+/// Bump when the last-RX metadata layout changes
+const uint8_t LAST_RX_META_FORMAT_VERSION = 1;
+
+/// Serialized last-RX metadata size in bytes (format version 1)
+const size_t LAST_RX_META_LEN = 19;
+
+/// Reception context of the most recent LoRa frame (read on the last-RX characteristic)
+struct LastRxMeta
+{
+    uint16_t rxCount;          // LoRa frames received when it arrived, 0 = none yet
+    int16_t rssi;              // dBm
+    float snr;                 // dB
+    int32_t frequencyErrorHz;  // Estimated offset of the sender's carrier from ours
+    uint8_t frameLen;          // Length reported by the radio, before any decoding
+    uint32_t receivedAtMs;     // millis() at the end of reception
+};
+
+/**
+ * @brief Serializes the last-RX metadata into the compact binary format (little-endian)
+ * @param meta Metadata of the last received frame.
+ * @param now millis() at the time of the read, for the age of the frame.
+ * @param buf Output buffer of at least LAST_RX_META_LEN bytes.
+ * @return Number of bytes written (LAST_RX_META_LEN).
+ */
+inline size_t serializeLastRxMeta(const LastRxMeta &meta, uint32_t now, uint8_t *buf)
+{
+    buf[0] = LAST_RX_META_FORMAT_VERSION;
+    writeU16(buf + 1, meta.rxCount);
+    writeI16(buf + 3, meta.rssi);
+    buf[5] = static_cast<uint8_t>(static_cast<int8_t>(meta.snr * 4)); // 0.25 dB steps
+    writeI32(buf + 6, meta.frequencyErrorHz);
+    buf[10] = meta.frameLen;
+    writeU32(buf + 11, meta.receivedAtMs);
+    writeU32(buf + 15, meta.rxCount == 0 ? 0 : now - meta.receivedAtMs);
+    return LAST_RX_META_LEN;
+}
+
+/// Bump when the heap stats layout changes
+const uint8_t HEAP_STATS_FORMAT_VERSION = 1;
+
+/// Serialized heap stats size in bytes (format version 1)
+const size_t HEAP_STATS_LEN = 17;
+
+/// Heap of the running firmware (read on the heap characteristic)
+struct HeapStats
+{
+    uint32_t totalBytes;       // Heap size after the static allocations
+    uint32_t freeBytes;        // Free right now
+    uint32_t minFreeBytes;     // Lowest free since boot, so total - min free is the peak use
+    uint32_t largestFreeBlock; // Largest single allocation that would succeed (fragmentation)
+
+    uint32_t usedBytes() const { return totalBytes - freeBytes; }
+    uint32_t peakUsedBytes() const { return totalBytes - minFreeBytes; }
+};
+
+#ifdef ARDUINO
+/**
+ * @brief Reads the heap statistics of the ESP-IDF allocator (safe from any task)
+ */
+inline HeapStats readHeapStats()
+{
+    return {ESP.getHeapSize(), ESP.getFreeHeap(), ESP.getMinFreeHeap(), ESP.getMaxAllocHeap()};
+}
+#endif
+
+/**
+ * @brief Serializes heap statistics into the compact binary format (little-endian)
+ * @param heap Heap statistics.
+ * @param buf Output buffer of at least HEAP_STATS_LEN bytes.
+ * @return Number of bytes written (HEAP_STATS_LEN).
+ */
+inline size_t serializeHeapStats(const HeapStats &heap, uint8_t *buf)
+{
+    buf[0] = HEAP_STATS_FORMAT_VERSION;
+    writeU32(buf + 1, heap.totalBytes);
+    writeU32(buf + 5, heap.freeBytes);
+    writeU32(buf + 9, heap.minFreeBytes);
+    writeU32(buf + 13, heap.largestFreeBlock);
+    return HEAP_STATS_LEN;
+}
+
+/// Bump when the link stats layout changes
+const uint8_t LINK_STATS_FORMAT_VERSION = 2;
+
+/// Serialized link stats size in bytes (format version 2)
+const size_t LINK_STATS_LEN = 12;
+
+/// Link health counters since boot (read and notified on the stats characteristic), each wraps at 65535,
+/// and the data spreading factor in effect
+struct LinkStats
+{
+    uint16_t txFrames;
+    uint16_t rxFrames;
+    uint16_t crcFailures;
+    uint16_t acksReceived;
+    uint16_t bufferDrops; // Messages dropped by the full BLE buffer
+    uint8_t spreadingFactor; // Changes with control opcode 0x0A and adaptive data rate
+};
+
+/**
+ * @brief Serializes link stats into the compact binary format (little-endian)
+ * @param link Counters to serialize.
+ * @param buf Output buffer of at least LINK_STATS_LEN bytes.
+ * @return Number of bytes written (LINK_STATS_LEN).
+ */
+inline size_t serializeLinkStats(const LinkStats &link, uint8_t *buf)
+{
+    buf[0] = LINK_STATS_FORMAT_VERSION;
+    writeU16(buf + 1, link.txFrames);
+    writeU16(buf + 3, link.rxFrames);
+    writeU16(buf + 5, link.crcFailures);
+    writeU16(buf + 7, link.acksReceived);
+    writeU16(buf + 9, link.bufferDrops);
+    buf[11] = link.spreadingFactor;
+    return LINK_STATS_LEN;
+}
+
+#endif // DIAGNOSTICS_H
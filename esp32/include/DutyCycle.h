@@ -0,0 +1,91 @@
+#ifndef DUTY_CYCLE_H
+#define DUTY_CYCLE_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstddef>
+#include <cstdint>
+#endif
+
+/**
+ * Time-on-air spent in a rolling window, against a budget (e.g. 1% of an hour)
+ * Airtime is summed in DUTY_CYCLE_SLOTS slots of windowMs / DUTY_CYCLE_SLOTS; a
+ * transmission leaves the sum once its whole slot is older than the window, so
+ * it counts for up to one slot longer than the regulation requires, never shorter.
+ */
+class DutyCycle
+{
+public:
+    static const size_t DUTY_CYCLE_SLOTS = 60;
+
+    DutyCycle(unsigned long windowMs, unsigned long budgetMs)
+        : slotMs(windowMs / DUTY_CYCLE_SLOTS > 0 ? windowMs / DUTY_CYCLE_SLOTS : 1), budgetMs(budgetMs), current(0),
+          currentStart(0)
+    {
+        for (uint32_t &slot : slots)
+        {
+            slot = 0;
+        }
+    }
+
+    /// Whether a transmission of airtimeMs fits the budget now. One always fits an
+    /// empty window, so a frame longer than the whole budget isn't held forever.
+    bool allows(unsigned long now, unsigned long airtimeMs)
+    {
+        unsigned long used = usedMs(now);
+        return used == 0 || used + airtimeMs <= budgetMs;
+    }
+
+    /// Count a transmission of airtimeMs that started now
+    void record(unsigned long now, unsigned long airtimeMs)
+    {
+        advance(now);
+        slots[current] += airtimeMs;
+    }
+
+    /// Airtime spent in the last window (rounded up to whole slots)
+    unsigned long usedMs(unsigned long now)
+    {
+        advance(now);
+        unsigned long used = 0;
+        for (uint32_t slot : slots)
+        {
+            used += slot;
+        }
+        return used;
+    }
+
+    /// Airtime the window allows
+    unsigned long budget() const
+    {
+        return budgetMs;
+    }
+
+private:
+    unsigned long slotMs;
+    unsigned long budgetMs;
+    // One slot more than the window, so the oldest slot still overlapping it is kept
+    uint32_t slots[DUTY_CYCLE_SLOTS + 1];
+    size_t current;
+    unsigned long currentStart;
+
+    /// Move the current slot up to now, emptying the slots that fell out of the window
+    void advance(unsigned long now)
+    {
+        unsigned long elapsed = (now - currentStart) / slotMs;
+        if (elapsed == 0)
+        {
+            return;
+        }
+        for (unsigned long i = 0; i < elapsed && i <= DUTY_CYCLE_SLOTS; i++)
+        {
+            current = (current + 1) % (DUTY_CYCLE_SLOTS + 1);
+            slots[current] = 0;
+        }
+        currentStart += elapsed * slotMs;
+    }
+};
+
+#endif // DUTY_CYCLE_H
@@ -2,10 +2,20 @@
 #ifndef LED_MANAGER_H
 #define LED_MANAGER_H
 
+/// Overall system state shown by the status LED heartbeat (STATUS_LED)
+enum class SystemState : uint8_t
+{
+    Initializing, // Solid on
+    Idle,         // Slow blink: running, no app connected
+    Connected,    // Fast blink: app connected over BLE
+    RadioError    // Double blink: radio failed or recovering from RX errors
+};
+
 class LEDManager
 {
 public:
-    LEDManager(int pin) : ledPin(pin) {}
+    LEDManager(int pin)
+        : ledPin(pin), identifyUntil(0), lastToggle(0), identifying(false), ledState(false), showingStatus(false) {}
 
     /**
      * @brief Initializes the LED.
@@ -24,6 +34,10 @@ public:
      */
     void blink(int times = 1, int duration = 50, int delayBetween = 200)
     {
+        if (showingStatus)
+        {
+            return; // The status heartbeat owns the LED
+        }
         for (int i = 0; i < times; i++)
         {
             setOn();
@@ -52,8 +66,98 @@ public:
         digitalWrite(ledPin, LOW);
     }
 
+    /**
+     * @brief Starts rapid blinking so the unit can be found physically.
+     * Non-blocking: call update() from the main loop to drive the blinking.
+     * @param durationMs How long to keep blinking in milliseconds.
+     */
+    void startIdentify(unsigned long durationMs)
+    {
+        identifying = true;
+        identifyUntil = millis() + durationMs;
+        lastToggle = 0;
+    }
+
+    /**
+     * @brief Returns true while identify blinking is active.
+     */
+    bool isIdentifying() const
+    {
+        return identifying;
+    }
+
+    /**
+     * @brief Advances identify blinking (call every loop iteration).
+     */
+    void update()
+    {
+        if (!identifying)
+        {
+            return;
+        }
+
+        unsigned long now = millis();
+        if ((long)(now - identifyUntil) >= 0)
+        {
+            identifying = false;
+            ledState = false;
+            setOff();
+            return;
+        }
+
+        if (now - lastToggle >= IDENTIFY_BLINK_INTERVAL_MS)
+        {
+            lastToggle = now;
+            ledState = !ledState;
+            digitalWrite(ledPin, ledState ? HIGH : LOW);
+        }
+    }
+
+    /**
+     * @brief Drives the status heartbeat for a system state (call every STATUS_LED_TICK_MS).
+     * Event blinks are suppressed from the first call on; identify blinking takes precedence.
+     * @param state Current system state.
+     */
+    void showStatus(SystemState state)
+    {
+        showingStatus = true;
+        if (!identifying)
+        {
+            digitalWrite(ledPin, statusLevel(state, millis()) ? HIGH : LOW);
+        }
+    }
+
+    /**
+     * @brief LED level of a state's pattern at a point in time.
+     */
+    static bool statusLevel(SystemState state, unsigned long now)
+    {
+        switch (state)
+        {
+        case SystemState::Initializing:
+            return true;
+        case SystemState::Idle:
+            return now % 2000 < 100; // 100 ms flash every 2 s
+        case SystemState::Connected:
+            return now % 500 < 100; // 100 ms flash every 500 ms
+        case SystemState::RadioError:
+        {
+            unsigned long phase = now % 1500; // Two 100 ms flashes every 1.5 s
+            return phase < 100 || (phase >= 250 && phase < 350);
+        }
+        }
+        return false;
+    }
+
 private:
+    static const unsigned long IDENTIFY_BLINK_INTERVAL_MS = 100; // 5 Hz blink
+
     int ledPin;
+    unsigned long identifyUntil;
+    unsigned long lastToggle;
+    bool identifying;
+    bool ledState;
+    volatile bool showingStatus;
 };
 
 #endif // LED_MANAGER_H
\ No newline at end of file
@@ -0,0 +1,210 @@
+#ifndef LISTEN_WINDOWS_H
+#define LISTEN_WINDOWS_H
+
+#include "Protocol.h"
+#include "bridge_config.h"
+
+/// What a received listen schedule beacon did to our schedule
+enum class BeaconResult : uint8_t
+{
+    Synced,    // Windows (re)aligned to the sender's
+    Refreshed, // Sender follows us - still in sync, nothing to move
+    Ignored,   // Sender follows a higher reference - it will move to ours
+    Mismatch   // Different period or window - can't share windows with it
+};
+
+/**
+ * Schedule of the synchronized listen windows (LISTEN_WINDOWS)
+ * A window opens every LISTEN_PERIOD_MS for LISTEN_WINDOW_MS, counted in local
+ * millis() from `epoch`. Without a shared clock the epoch is moved to match the
+ * beacons of the lowest node id heard (the reference): the sender's offset into
+ * its window, taken back from the time its frame started on air, gives where
+ * its window opened on our clock. Nodes that follow a reference beacon it on,
+ * so sync reaches nodes that can't hear the reference directly.
+ */
+class ListenWindows
+{
+public:
+    explicit ListenWindows(uint8_t nodeId)
+        : nodeId(nodeId), reference(nodeId), epoch(0), searching(true), aligned(false), searchStart(0),
+          lastSyncAt(0), lastBeaconAt(0), beaconSent(false) {}
+
+    /**
+     * Start from our own schedule and search for a network to join
+     */
+    void begin(unsigned long now)
+    {
+        epoch = now;
+        reference = nodeId;
+        startSearch(now);
+    }
+
+    /**
+     * End a search that found nobody, and start a new one once sync is lost
+     */
+    void update(unsigned long now)
+    {
+        // Whole beacon cycles only, so phase() survives the millis() wrap
+        const unsigned long cycleMs = (unsigned long)LISTEN_BEACON_EVERY * LISTEN_PERIOD_MS;
+        epoch += ((now - epoch) / cycleMs) * cycleMs;
+
+        if (searching && now - searchStart >= LISTEN_SEARCH_MS)
+        {
+            searching = false;
+            aligned = true; // Nobody heard: our own windows are the network's
+            lastSyncAt = now;
+        }
+        else if (!searching && now - lastSyncAt >= LISTEN_SYNC_LOST_MS)
+        {
+            // Drifted away from the reference (or never found one) - listen for it again
+            reference = nodeId;
+            startSearch(now);
+        }
+    }
+
+    /**
+     * Take a new node id (it orders references and places our beacon slot);
+     * call begin() afterwards to rejoin under it
+     */
+    void setNodeId(uint8_t id)
+    {
+        nodeId = id;
+    }
+
+    /// Listening continuously for a network (after boot or sync loss)
+    bool isSearching() const
+    {
+        return searching;
+    }
+
+    /// Node our windows are aligned to (our own id when we are the reference)
+    uint8_t referenceId() const
+    {
+        return reference;
+    }
+
+    /// Time since our current window opened, in ms
+    uint16_t phase(unsigned long now) const
+    {
+        return (now - epoch) % LISTEN_PERIOD_MS;
+    }
+
+    /// Whether the radio must be listening now
+    bool isOpen(unsigned long now) const
+    {
+        return searching || phase(now) < LISTEN_WINDOW_MS;
+    }
+
+    /**
+     * Whether a frame of airtimeMs sent now lands inside every peer's window
+     * Held until our windows are aligned, and within LISTEN_GUARD_MS of either
+     * end of the window so a peer's clock drift can't cut the frame off.
+     */
+    bool canTransmit(unsigned long now, uint32_t airtimeMs) const
+    {
+        uint16_t at = phase(now);
+        return aligned && at >= LISTEN_GUARD_MS && at + airtimeMs + LISTEN_GUARD_MS <= LISTEN_WINDOW_MS;
+    }
+
+    /// Time until the window opens or closes (or the search ends), in ms
+    unsigned long msUntilChange(unsigned long now) const
+    {
+        if (searching)
+        {
+            return LISTEN_SEARCH_MS - (now - searchStart);
+        }
+        uint16_t at = phase(now);
+        return at < LISTEN_WINDOW_MS ? LISTEN_WINDOW_MS - at : LISTEN_PERIOD_MS - at;
+    }
+
+    /**
+     * Whether to send our beacon now: once every LISTEN_BEACON_EVERY windows, in
+     * a slot after the guard that depends on our node id so neighbours don't
+     * beacon on top of each other
+     */
+    bool beaconDue(unsigned long now, uint32_t airtimeMs)
+    {
+        if (!canTransmit(now, airtimeMs) || (beaconSent && now - lastBeaconAt < LISTEN_WINDOW_MS))
+        {
+            return false;
+        }
+
+        uint32_t window = (now - epoch) / LISTEN_PERIOD_MS;
+        uint32_t slots = (LISTEN_WINDOW_MS - 2 * LISTEN_GUARD_MS) / (airtimeMs + 1);
+        uint32_t slotStart = LISTEN_GUARD_MS + (slots > 1 ? (nodeId % slots) * (airtimeMs + 1) : 0);
+        if (window % LISTEN_BEACON_EVERY != 0 || phase(now) < slotStart)
+        {
+            return false;
+        }
+
+        lastBeaconAt = now;
+        beaconSent = true;
+        return true;
+    }
+
+    /**
+     * Our schedule as advertised in a beacon sent now
+     */
+    ListenScheduleMessage beacon(unsigned long now) const
+    {
+        ListenScheduleMessage schedule;
+        schedule.nodeId = nodeId;
+        schedule.referenceId = reference;
+        schedule.periodMs = LISTEN_PERIOD_MS;
+        schedule.windowMs = LISTEN_WINDOW_MS;
+        schedule.offsetMs = phase(now);
+        return schedule;
+    }
+
+    /**
+     * Apply a beacon heard from another node
+     * @param frameStart Local time the beacon frame started on air (reception time minus its airtime).
+     * @param now Current time in ms.
+     */
+    BeaconResult onBeacon(const ListenScheduleMessage &schedule, unsigned long frameStart, unsigned long now)
+    {
+        if (schedule.periodMs != LISTEN_PERIOD_MS || schedule.windowMs != LISTEN_WINDOW_MS)
+        {
+            return BeaconResult::Mismatch;
+        }
+
+        if (schedule.referenceId == reference && reference == nodeId)
+        {
+            lastSyncAt = now;
+            return BeaconResult::Refreshed;
+        }
+
+        if (schedule.referenceId > reference)
+        {
+            return BeaconResult::Ignored;
+        }
+
+        // Keep the epoch in the past so phase() never wraps
+        unsigned long opened = frameStart - schedule.offsetMs;
+        epoch = opened + ((now - opened) / LISTEN_PERIOD_MS) * LISTEN_PERIOD_MS;
+        reference = schedule.referenceId;
+        searching = false;
+        aligned = true;
+        lastSyncAt = now;
+        return BeaconResult::Synced;
+    }
+
+private:
+    void startSearch(unsigned long now)
+    {
+        searching = true;
+        searchStart = now;
+    }
+
+    uint8_t nodeId;
+    uint8_t reference;
+    unsigned long epoch; // Local time a window opened
+    bool searching;
+    bool aligned; // Windows known to be the network's (synced, or nobody found)
+    unsigned long searchStart;
+    unsigned long lastSyncAt; // Last beacon of our reference (or search end)
+    unsigned long lastBeaconAt;
+    bool beaconSent;
+};
+
+#endif // LISTEN_WINDOWS_H
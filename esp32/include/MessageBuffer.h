@@ -2,15 +2,20 @@
 #define MESSAGE_BUFFER_H
 
 #include "Protocol.h"
+#include "bridge_config.h"
 
 /**
- * Circular buffer for storing LoRa messages when BLE is disconnected
- * Holds up to 10 messages, drops oldest when full
+ * Circular buffer of messages over storage provided by BoundedMessageBuffer
+ * Drops oldest when full
  */
-class MessageBuffer
+class MessageRing
 {
 public:
-    MessageBuffer() : head(0), tail(0), count(0) {}
+    MessageRing(Message *slots, int capacity) : slots(slots), capacity(capacity), head(0), tail(0), count(0) {}
+
+    // Points into the owner's storage - never copied
+    MessageRing(const MessageRing &) = delete;
+    MessageRing &operator=(const MessageRing &) = delete;
 
     /**
      * Add a message to the buffer
@@ -18,19 +23,84 @@ public:
      */
     void add(const Message &msg)
     {
-        if (count < MAX_MESSAGES)
+        if (count < capacity)
         {
-            buffer[tail] = msg;
-            tail = (tail + 1) % MAX_MESSAGES;
+            slots[tail] = msg;
+            tail = (tail + 1) % capacity;
             count++;
         }
         else
         {
             // Buffer full - drop oldest message
-            buffer[tail] = msg;
-            tail = (tail + 1) % MAX_MESSAGES;
-            head = (head + 1) % MAX_MESSAGES;
+            slots[tail] = msg;
+            tail = (tail + 1) % capacity;
+            head = (head + 1) % capacity;
+        }
+    }
+
+    /**
+     * Add a message ahead of every message with a lower priority
+     * Order is kept among messages of equal priority. Normal messages are
+     * appended like add(). Drops the newest message if buffer is full.
+     */
+    void addByPriority(const Message &msg)
+    {
+        if (msg.priority() == MessagePriority::Normal)
+        {
+            add(msg);
+            return;
+        }
+
+        if (count == capacity)
+        {
+            // Buffer full - drop newest message to make room
+            tail = (tail + capacity - 1) % capacity;
+            count--;
+        }
+
+        // Insert after the last message of equal or higher priority
+        int pos = 0;
+        while (pos < count && slots[(head + pos) % capacity].priority() >= msg.priority())
+        {
+            pos++;
+        }
+        insertAt(pos, msg);
+    }
+
+    /**
+     * Add a message ahead of every message with a lower send priority
+     * (Message::sendPriority()), keeping the order among equal ones.
+     * If the buffer is full the newest message of the lowest send priority is
+     * dropped to make room; if none ranks below the new message, the new
+     * message is refused instead.
+     * Returns false if the message was refused
+     */
+    bool addBySendPriority(const Message &msg)
+    {
+        if (count == capacity)
+        {
+            int lowest = count - 1;
+            for (int i = count - 2; i >= 0; i--)
+            {
+                if (slots[(head + i) % capacity].sendPriority() < slots[(head + lowest) % capacity].sendPriority())
+                {
+                    lowest = i;
+                }
+            }
+            if (slots[(head + lowest) % capacity].sendPriority() >= msg.sendPriority())
+            {
+                return false;
+            }
+            removeAt(lowest);
+        }
+
+        int pos = 0;
+        while (pos < count && slots[(head + pos) % capacity].sendPriority() >= msg.sendPriority())
+        {
+            pos++;
         }
+        insertAt(pos, msg);
+        return true;
     }
 
     /**
@@ -44,8 +114,73 @@ public:
             return false;
         }
 
-        msg = buffer[head];
-        head = (head + 1) % MAX_MESSAGES;
+        msg = slots[head];
+        head = (head + 1) % capacity;
+        count--;
+        return true;
+    }
+
+    /**
+     * Look at the next message without removing it
+     * Returns true if a message is available, false if buffer empty
+     */
+    bool peek(Message &msg) const
+    {
+        if (count == 0)
+        {
+            return false;
+        }
+
+        msg = slots[head];
+        return true;
+    }
+
+    /**
+     * Look at the message at a position from the front without removing it
+     * Returns true if a message is available there
+     */
+    bool peekAt(int index, Message &msg) const
+    {
+        if (index < 0 || index >= count)
+        {
+            return false;
+        }
+
+        msg = slots[(head + index) % capacity];
+        return true;
+    }
+
+    /**
+     * Overwrite the message at a position from the front, keeping its place
+     * Returns true if a message was there
+     */
+    bool replaceAt(int index, const Message &msg)
+    {
+        if (index < 0 || index >= count)
+        {
+            return false;
+        }
+
+        slots[(head + index) % capacity] = msg;
+        return true;
+    }
+
+    /**
+     * Remove the message at a position from the front, keeping the order of the rest
+     * Returns true if a message was there
+     */
+    bool removeAt(int index)
+    {
+        if (index < 0 || index >= count)
+        {
+            return false;
+        }
+
+        for (int i = index; i < count - 1; i++)
+        {
+            slots[(head + i) % capacity] = slots[(head + i + 1) % capacity];
+        }
+        tail = (tail + capacity - 1) % capacity;
         count--;
         return true;
     }
@@ -58,6 +193,14 @@ public:
         return count;
     }
 
+    /**
+     * Get the number of messages the buffer holds before dropping one
+     */
+    int getCapacity() const
+    {
+        return capacity;
+    }
+
     /**
      * Check if buffer is empty
      */
@@ -66,6 +209,14 @@ public:
         return count == 0;
     }
 
+    /**
+     * Check if buffer is full (the next add() drops the oldest message)
+     */
+    bool isFull() const
+    {
+        return count == capacity;
+    }
+
     /**
      * Clear all messages from buffer
      */
@@ -77,11 +228,300 @@ public:
     }
 
 private:
-    static const int MAX_MESSAGES = 10;
-    Message buffer[MAX_MESSAGES];
+    /// Insert at a position from the front (buffer must not be full)
+    void insertAt(int pos, const Message &msg)
+    {
+        for (int i = count; i > pos; i--)
+        {
+            slots[(head + i) % capacity] = slots[(head + i - 1) % capacity];
+        }
+        slots[(head + pos) % capacity] = msg;
+        tail = (tail + 1) % capacity;
+        count++;
+    }
+
+    Message *slots;
+    int capacity;
     int head; // Next message to read
     int tail; // Next position to write
     int count; // Number of messages in buffer
 };
 
+/**
+ * Circular buffer holding up to Capacity messages
+ */
+template <int Capacity>
+class BoundedMessageBuffer : public MessageRing
+{
+public:
+    static_assert(Capacity > 0, "a message buffer needs at least one slot");
+
+    BoundedMessageBuffer() : MessageRing(storage, Capacity) {}
+
+private:
+    Message storage[Capacity];
+};
+
+/**
+ * Circular buffer for storing LoRa messages when BLE is disconnected
+ * Holds up to 10 messages, drops oldest when full
+ */
+using MessageBuffer = BoundedMessageBuffer<10>;
+
+/// Kinds of buffered LoRa -> BLE messages, each with its own bounded queue
+enum class BufferClass : uint8_t
+{
+    Text,     // Text messages (with or without GPS) and SOS
+    Position, // Position-only updates (GPS, empty text)
+    Other     // ACKs, identify responses and anything else for the app
+};
+
+const int BUFFER_CLASS_COUNT = 3;
+
+/**
+ * Messages for the app held while BLE is disconnected (or delivery failed), in
+ * one bounded queue per BufferClass so a flood of one kind (e.g. position
+ * updates) only ever evicts its own kind. Drained round-robin: one message of
+ * each non-empty class in turn, by priority within a class.
+ *
+ * ACKs, delivery reports and link quality reports only describe a seq, so they
+ * are coalesced: a new one replaces the buffered one of the same type and seq in
+ * place, and when the queue is full it evicts the oldest ACK or report rather
+ * than an identify response or other message.
+ */
+class BleMessageBuffer
+{
+public:
+    BleMessageBuffer() : queues{&texts, &positions, &others}, turn(0), dropped(0) {}
+
+    /// Queue a message falls into
+    static BufferClass classOf(const Message &msg)
+    {
+        if (msg.type == MessageType::Sos)
+        {
+            return BufferClass::Text;
+        }
+        if (msg.type != MessageType::Text)
+        {
+            return BufferClass::Other;
+        }
+        return msg.textData.hasGps && msg.textData.text[0] == '\0' ? BufferClass::Position : BufferClass::Text;
+    }
+
+    /**
+     * Add a message to its class queue, ahead of lower priorities
+     * An ACK or report replaces a buffered one of its type for the same seq.
+     * Drops a message of the same class if that queue is full; for an SOS the
+     * newest lowest-priority message that isn't an SOS. Every dropped message
+     * is counted (getDropped()).
+     */
+    void addByPriority(const Message &msg)
+    {
+        if (isConfirmation(msg))
+        {
+            int index = indexOfConfirmation(msg.type, confirmedSeq(msg));
+            if (index >= 0)
+            {
+                others.replaceAt(index, msg);
+                return;
+            }
+        }
+        if (!queueOf(msg).isFull())
+        {
+            queueOf(msg).addByPriority(msg);
+            return;
+        }
+
+        dropped++;
+        if (msg.type == MessageType::Sos)
+        {
+            texts.removeAt(indexOfNonSos());
+        }
+        else if (isConfirmation(msg))
+        {
+            // Make room at the expense of a stale confirmation, if there is one
+            others.removeAt(indexOfConfirmation());
+        }
+        queueOf(msg).addByPriority(msg);
+    }
+
+    /**
+     * Whether adding the message would drop one of its class
+     * (never for an ACK or report that replaces one for the same seq, nor for
+     * an SOS while a text can make room)
+     */
+    bool isFull(const Message &msg) const
+    {
+        if (msg.type == MessageType::Sos)
+        {
+            return indexOfNonSos() < 0;
+        }
+        if (isConfirmation(msg) && indexOfConfirmation(msg.type, confirmedSeq(msg)) >= 0)
+        {
+            return false;
+        }
+        return queues[static_cast<uint8_t>(classOf(msg))]->isFull();
+    }
+
+    /// Look at the next message in drain order without removing it
+    bool peek(Message &msg) const
+    {
+        return peekAt(0, msg);
+    }
+
+    /**
+     * Look at the message at a position in drain order without removing it
+     * Returns true if a message is available there
+     */
+    bool peekAt(int index, Message &msg) const
+    {
+        int taken[BUFFER_CLASS_COUNT] = {};
+        int cls = turn;
+        for (int i = 0; i <= index; i++)
+        {
+            int skipped = 0;
+            while (taken[cls] >= queues[cls]->getCount())
+            {
+                cls = (cls + 1) % BUFFER_CLASS_COUNT;
+                if (++skipped == BUFFER_CLASS_COUNT)
+                {
+                    return false; // Fewer than index + 1 messages
+                }
+            }
+            if (i == index)
+            {
+                return queues[cls]->peekAt(taken[cls], msg);
+            }
+            taken[cls]++;
+            cls = (cls + 1) % BUFFER_CLASS_COUNT;
+        }
+        return false;
+    }
+
+    /**
+     * Remove the next message in drain order (the one peek() returns)
+     * Returns true if message retrieved, false if buffer empty
+     */
+    bool get(Message &msg)
+    {
+        for (int skipped = 0; skipped < BUFFER_CLASS_COUNT; skipped++)
+        {
+            int cls = turn;
+            turn = (turn + 1) % BUFFER_CLASS_COUNT;
+            if (queues[cls]->get(msg))
+            {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Total number of buffered messages
+    int getCount() const
+    {
+        return texts.getCount() + positions.getCount() + others.getCount();
+    }
+
+    /// Number of buffered messages of one class
+    int getCount(BufferClass cls) const
+    {
+        return queues[static_cast<uint8_t>(cls)]->getCount();
+    }
+
+    bool isEmpty() const
+    {
+        return getCount() == 0;
+    }
+
+    /// Messages dropped by full queues since boot (wraps), kept across clear()
+    uint16_t getDropped() const
+    {
+        return dropped;
+    }
+
+    void clear()
+    {
+        for (MessageRing *queue : queues)
+        {
+            queue->clear();
+        }
+        turn = 0;
+    }
+
+private:
+    MessageRing &queueOf(const Message &msg)
+    {
+        return *queues[static_cast<uint8_t>(classOf(msg))];
+    }
+
+    /// Position of the last buffered text that isn't an SOS (the one an SOS evicts), -1 if none
+    int indexOfNonSos() const
+    {
+        Message msg;
+        for (int i = texts.getCount() - 1; i >= 0; i--)
+        {
+            if (texts.peekAt(i, msg) && msg.type != MessageType::Sos)
+            {
+                return i;
+            }
+        }
+        return -1;
+    }
+
+    /// ACKs, delivery reports and link quality reports, where only the latest per seq matters
+    static bool isConfirmation(const Message &msg)
+    {
+        return msg.type == MessageType::Ack || msg.type == MessageType::DeliveryReport ||
+               msg.type == MessageType::LinkQuality;
+    }
+
+    static uint8_t confirmedSeq(const Message &msg)
+    {
+        switch (msg.type)
+        {
+        case MessageType::Ack:
+            return msg.ackData.seq;
+        case MessageType::LinkQuality:
+            return msg.linkQualityData.seq;
+        default:
+            return msg.deliveryReportData.seq;
+        }
+    }
+
+    /// Position in the Other queue of the oldest ACK or report, -1 if none
+    int indexOfConfirmation() const
+    {
+        Message buffered;
+        for (int i = 0; others.peekAt(i, buffered); i++)
+        {
+            if (isConfirmation(buffered))
+            {
+                return i;
+            }
+        }
+        return -1;
+    }
+
+    /// Position in the Other queue of the buffered confirmation of this type for seq, -1 if none
+    int indexOfConfirmation(MessageType type, uint8_t seq) const
+    {
+        Message buffered;
+        for (int i = 0; others.peekAt(i, buffered); i++)
+        {
+            if (buffered.type == type && confirmedSeq(buffered) == seq)
+            {
+                return i;
+            }
+        }
+        return -1;
+    }
+
+    BoundedMessageBuffer<BLE_BUFFER_TEXTS> texts;
+    BoundedMessageBuffer<BLE_BUFFER_POSITIONS> positions;
+    BoundedMessageBuffer<BLE_BUFFER_OTHER> others;
+    MessageRing *queues[BUFFER_CLASS_COUNT]; // Indexed by BufferClass
+    int turn; // Class whose message is delivered next
+    uint16_t dropped; // Messages dropped by full queues
+};
+
 #endif // MESSAGE_BUFFER_H
@@ -0,0 +1,63 @@
+#ifndef PERIODIC_LISTEN_H
+#define PERIODIC_LISTEN_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstdint>
+#endif
+#include "bridge_config.h"
+
+/**
+ * Schedule of the unsynchronized listen windows (RADIO_POWER_PERIODIC_RX)
+ * The radio listens for PERIODIC_RX_WINDOW_MS, then sleeps for
+ * PERIODIC_RX_SLEEP_MS, counted in local millis() from begin(). Nothing is
+ * aligned with the peers: a frame is heard if it starts on air inside one of
+ * our windows, and the sender's retries give it more chances. Reception in
+ * progress and an ACK wait keep the radio on past a window (see
+ * sleepRadioWhenIdle()), the schedule itself never moves.
+ */
+class PeriodicListen
+{
+public:
+    PeriodicListen() : epoch(0) {}
+
+    /// Open the first window now
+    void begin(unsigned long now)
+    {
+        epoch = now;
+    }
+
+    /// Time since the current window opened, in ms
+    unsigned long phase(unsigned long now) const
+    {
+        return (now - epoch) % PERIOD_MS;
+    }
+
+    /// Whether the radio must be listening now
+    bool isOpen(unsigned long now) const
+    {
+        return phase(now) < PERIODIC_RX_WINDOW_MS;
+    }
+
+    /// Time until the window opens or closes next, in ms
+    unsigned long msUntilChange(unsigned long now) const
+    {
+        unsigned long at = phase(now);
+        return at < PERIODIC_RX_WINDOW_MS ? PERIODIC_RX_WINDOW_MS - at : PERIOD_MS - at;
+    }
+
+    /// Share of the time the window is open, in percent (the radio's RX duty cycle without traffic)
+    static uint8_t listenPercent()
+    {
+        return static_cast<uint8_t>(100UL * PERIODIC_RX_WINDOW_MS / PERIOD_MS);
+    }
+
+private:
+    static const unsigned long PERIOD_MS = PERIODIC_RX_WINDOW_MS + PERIODIC_RX_SLEEP_MS;
+
+    unsigned long epoch; // millis() when the first window opened
+};
+
+#endif // PERIODIC_LISTEN_H
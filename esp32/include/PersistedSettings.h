@@ -0,0 +1,90 @@
+#ifndef PERSISTED_SETTINGS_H
+#define PERSISTED_SETTINGS_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
+#endif
+#include "Protocol.h"
+#include "lora_config.h"
+
+/// Bump when the persisted settings layout changes; settings of other versions are ignored
+const uint8_t PERSISTED_SETTINGS_VERSION = 1;
+
+/// Serialized settings size in bytes: [version][frequency u32][tx power i8][spreading factor][crc8]
+const size_t PERSISTED_SETTINGS_LEN = 8;
+
+/**
+ * @brief Radio settings changed at runtime (control opcodes 0x0A and 0x0B), kept in NVS across reboots
+ *
+ * The device name is not part of it: it is only set at build time (DEVICE_NAME).
+ */
+struct PersistedSettings
+{
+    uint32_t frequency;
+    int8_t txPower; // Conducted, dBm
+    uint8_t spreadingFactor;
+};
+
+/**
+ * @brief Settings of the firmware build, used on first boot and whenever the saved ones are unusable
+ */
+inline PersistedSettings defaultPersistedSettings()
+{
+    PersistedSettings settings;
+    settings.frequency = LORA_FREQUENCY;
+    settings.txPower = LORA_TX_POWER_CONDUCTED_DBM;
+    settings.spreadingFactor = LORA_SPREADING_FACTOR;
+    return settings;
+}
+
+/**
+ * @brief Serializes settings for NVS (little-endian, CRC-8 over the preceding bytes)
+ * @param settings Settings to serialize.
+ * @param buf Output buffer of at least PERSISTED_SETTINGS_LEN bytes.
+ * @return Number of bytes written (PERSISTED_SETTINGS_LEN).
+ */
+inline size_t serializePersistedSettings(const PersistedSettings &settings, uint8_t *buf)
+{
+    buf[0] = PERSISTED_SETTINGS_VERSION;
+    writeU32(buf + 1, settings.frequency);
+    buf[5] = static_cast<uint8_t>(settings.txPower);
+    buf[6] = settings.spreadingFactor;
+    buf[7] = crc8(buf, PERSISTED_SETTINGS_LEN - 1);
+    return PERSISTED_SETTINGS_LEN;
+}
+
+/**
+ * @brief Parses settings read from NVS, checking their version, CRC and value ranges
+ * @param buf Settings as written by serializePersistedSettings(), or nothing (len 0) on first boot.
+ * @param len Number of bytes read.
+ * @param settings Filled in on success, left untouched otherwise.
+ * @return True if the settings can be applied, false to keep the defaults.
+ */
+inline bool parsePersistedSettings(const uint8_t *buf, size_t len, PersistedSettings &settings)
+{
+    if (len != PERSISTED_SETTINGS_LEN || buf[0] != PERSISTED_SETTINGS_VERSION ||
+        crc8(buf, PERSISTED_SETTINGS_LEN - 1) != buf[PERSISTED_SETTINGS_LEN - 1])
+    {
+        return false;
+    }
+
+    uint32_t frequency = readU32(buf + 1);
+    int8_t txPower = static_cast<int8_t>(buf[5]);
+    uint8_t spreadingFactor = buf[6];
+    if (!loraRadioSettingsValid(frequency, txPower) || !loraSpreadingFactorSelectable(spreadingFactor))
+    {
+        return false;
+    }
+
+    settings.frequency = frequency;
+    settings.txPower = txPower;
+    settings.spreadingFactor = spreadingFactor;
+    return true;
+}
+
+#endif // PERSISTED_SETTINGS_H
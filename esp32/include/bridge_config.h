@@ -0,0 +1,715 @@
+#ifndef BRIDGE_CONFIG_H
+#define BRIDGE_CONFIG_H
+
+// ============================================================================
+// Bridge Behaviour Configuration
+// ============================================================================
+// Options are 0/1 flags and can be overridden via build_flags in platformio.ini
+// (e.g. -DPROMISCUOUS_MODE=1).
+
+// Firmware version reported by the diagnostics command
+#define FIRMWARE_VERSION_MAJOR 1
+#define FIRMWARE_VERSION_MINOR 0
+#define FIRMWARE_VERSION_PATCH 0
+
+// Git revision of the build, set by scripts/git_revision.py (see platformio.ini)
+#ifndef FIRMWARE_GIT_HASH
+#define FIRMWARE_GIT_HASH "unknown"
+#endif
+
+// Promiscuous mode: LoRa frames that fail to deserialize are forwarded to the
+// app as Raw messages (type 0x80) instead of being dropped. Intended for interop
+// debugging and capturing traffic from non-bridge peers.
+// WARNING: this exposes ALL traffic on the channel to the connected app.
+// Raw frames are never ACKed and are not buffered while BLE is disconnected.
+#ifndef PROMISCUOUS_MODE
+#define PROMISCUOUS_MODE 0
+#endif
+
+// Decode failure reports (DEBUG ONLY): every LoRa frame dropped because it
+// could not be decoded is sent to the app as a DecodeFailure message (type
+// 0x82, see protocol.md) with the bytes read and the reason - malformed,
+// truncated, unknown type, or rejected (decodes, but not accepted on air) - to
+// diagnose wire-format mismatches between firmware versions. Frames forwarded
+// as Raw (PROMISCUOUS_MODE, UNKNOWN_TYPE_POLICY) are not reported twice.
+// Reports are dropped while BLE is disconnected.
+#ifndef DEBUG_DECODE_FAILURES
+#define DEBUG_DECODE_FAILURES 0
+#endif
+
+// What to do with a LoRa frame whose type byte this firmware doesn't know,
+// e.g. a message type added by newer firmware elsewhere in the fleet:
+// - UNKNOWN_TYPE_DROP (default): log and drop it.
+// - UNKNOWN_TYPE_FORWARD: forward it to the app as a Raw message (type 0x80),
+//   so a newer app can decode it from the raw bytes. Not ACKed, not buffered.
+// - UNKNOWN_TYPE_RELAY: as FORWARD, and transmit the frame again unchanged so it
+//   crosses this node towards newer ones. Each frame is relayed at most once per
+//   RELAY_DEDUP_MS (remembered by hash), so two relaying nodes can't ping-pong it.
+// Frames of a known type that fail to decode are corrupt, not unknown: they are
+// only forwarded in PROMISCUOUS_MODE and never relayed.
+#define UNKNOWN_TYPE_DROP 0
+#define UNKNOWN_TYPE_FORWARD 1
+#define UNKNOWN_TYPE_RELAY 2
+
+#ifndef UNKNOWN_TYPE_POLICY
+#define UNKNOWN_TYPE_POLICY UNKNOWN_TYPE_DROP
+#endif
+
+#ifndef RELAY_DEDUP_MS
+#define RELAY_DEDUP_MS 60000
+#endif
+
+// Number of recently relayed frames remembered for RELAY_DEDUP_MS
+#ifndef RELAY_DEDUP_ENTRIES
+#define RELAY_DEDUP_ENTRIES 8
+#endif
+
+// No-TX mode (DEVELOPMENT ONLY): every LoRa transmission is logged with its
+// bytes but never keyed up, and a missing or broken radio is not fatal. Lets app
+// developers exercise the full BLE path against real firmware without a peer,
+// RF hardware or transmitting on a regulated band. Never ship with this enabled:
+// the bridge reports success for frames that were never sent.
+#ifndef NO_TX
+#define NO_TX 0
+#endif
+
+// LoRa RX wakeup. By default the radio's DIO0 (RxDone) raises an interrupt: the
+// ISR reads the frame and wakes the main loop at once, from light sleep too, so
+// a frame is handled within milliseconds however long the loop was idle. Set
+// LORA_RX_POLLING 1 for boards whose DIO0 isn't wired to a GPIO: the loop then
+// reads the RxDone flag over SPI every LORA_RX_POLL_MS instead, which adds up to
+// that much latency and wakes the CPU on every poll (less light sleep).
+// The boot log names the mode in use. Not available in RELAY_ONLY builds.
+#ifndef LORA_RX_POLLING
+#define LORA_RX_POLLING 0
+#endif
+
+#ifndef LORA_RX_POLL_MS
+#define LORA_RX_POLL_MS 20
+#endif
+
+// Relay-only build: a dedicated range extender without BLE. Every LoRa frame
+// heard is transmitted again unchanged, at most once per RELAY_DEDUP_MS
+// (RELAY_DEDUP_ENTRIES frames remembered), except the link-local listen
+// schedule and channel switch frames. The firmware is src/relay_main.cpp;
+// main.cpp, BLEManager.cpp and the NimBLE library are left out of the build.
+// Build with the esp32dev-relay environment. Not combinable with CHANNEL_AGILITY
+// or a sleeping RADIO_POWER_MODE: the relay listens continuously on LORA_FREQUENCY.
+#ifndef RELAY_ONLY
+#define RELAY_ONLY 0
+#endif
+
+// Loss injection (TESTING ONLY): randomly discard a percentage of received
+// frames before they are processed and of transmitted frames instead of keying
+// up (the core still sees them as sent, like a frame lost on air). Lets QA
+// exercise retransmission, ACK timeouts and channel fallback on the bench. The
+// percentages start at these defaults and can be changed over BLE (control
+// opcode 0x06). Never ship with this enabled: it deliberately breaks the link.
+#ifndef LOSS_INJECTION
+#define LOSS_INJECTION 0
+#endif
+
+#ifndef LOSS_INJECTION_RX_PERCENT
+#define LOSS_INJECTION_RX_PERCENT 0
+#endif
+
+#ifndef LOSS_INJECTION_TX_PERCENT
+#define LOSS_INJECTION_TX_PERCENT 0
+#endif
+
+// Message injection (TESTING ONLY): handle control opcode 0x09, which hands a
+// serialized message written by the app straight to the LoRa-to-BLE path as if
+// it had been received over LoRa (BridgeCore::inject()). Nothing is transmitted
+// or ACKed. Lets QA exercise notifications and app parsing without a second
+// node. Never ship with this enabled: anyone connected can fake received messages.
+#ifndef MESSAGE_INJECTION
+#define MESSAGE_INJECTION 0
+#endif
+
+// Node id of this bridge, used to address Identify requests (1-254).
+// 255 (BROADCAST_NODE_ID) addresses every node and 0 (RESERVED_NODE_ID) is
+// never assigned. Give each unit in a fleet a unique id, e.g. -DNODE_ID=3, or
+// set it at runtime with the node id control command (saved in NVS, where it
+// overrides this default from then on).
+#ifndef NODE_ID
+#define NODE_ID 1
+#endif
+
+// How long the LED blinks rapidly after an Identify request (requires LED_PIN).
+#ifndef IDENTIFY_DURATION_MS
+#define IDENTIFY_DURATION_MS 5000
+#endif
+
+// Minimum spacing of ConfigResponse replies (see protocol.md). Requests arriving
+// sooner are ignored, so a stuck or looping requester can't keep the radio busy.
+// The spacing is stretched further when one reply at the configured modulation
+// would use more than its share of the band's duty cycle.
+#ifndef CONFIG_RESPONSE_MIN_INTERVAL_MS
+#define CONFIG_RESPONSE_MIN_INTERVAL_MS 10000
+#endif
+
+// Startup beacon: once the radio is up, announce this node with one unsolicited
+// ConfigResponse so nearby bridges pass the join on to their apps. One-shot per
+// boot, never repeated; it counts as a reply for CONFIG_RESPONSE_MIN_INTERVAL_MS
+// and is skipped after a crash or watchdog reset (a boot loop would otherwise
+// transmit on every boot).
+#ifndef STARTUP_BEACON
+#define STARTUP_BEACON 0
+#endif
+
+// When a received text is ACKed to the LoRa peer. The ACK is what the sender's
+// app shows as "delivered", so pick the meaning it should have:
+// - ACK_POLICY_ALWAYS (default): ACK on reception. A full buffer drops its oldest
+//   message, so an ACKed text can still be lost if the phone never returns.
+// - ACK_POLICY_BUFFERED: ACK once the text is queued for BLE or buffered. A full
+//   buffer rejects new texts (no ACK) instead of dropping ACKed ones.
+// - ACK_POLICY_CONNECTED: as BUFFERED, and only while a phone is connected. Texts
+//   received while disconnected are still buffered but not ACKed, so the sender
+//   may retransmit and the app can see the same seq twice.
+#define ACK_POLICY_ALWAYS 0
+#define ACK_POLICY_BUFFERED 1
+#define ACK_POLICY_CONNECTED 2
+
+#ifndef ACK_POLICY
+#define ACK_POLICY ACK_POLICY_ALWAYS
+#endif
+
+// Fire-and-forget: a one-way mode for telemetry and broadcasts, where nobody
+// needs to know whether a message arrived. Received texts are never ACKed and
+// sent texts are never retransmitted, whatever their priority: each one goes on
+// air exactly once and is reported to the app as sent (DeliveryReport outcome
+// Sent), never delivered or failed. Gives up delivery confirmation for the least
+// airtime per message. Set it on both ends - a normal sender keeps retransmitting
+// to a fire-and-forget receiver and reports every text as failed. Excludes
+// TRACK_STATUS and ACK_SEMANTICS_HOP, which are confirmations too.
+#ifndef FIRE_AND_FORGET
+#define FIRE_AND_FORGET 0
+#endif
+
+// NACKs: a text that arrives corrupted (CRC mismatch) or truncated, but whose
+// header still reads as a text, is answered with a NACK (type 0x04) for its seq.
+// A sender with retransmissions left for that seq retransmits right away instead
+// of waiting out the ACK timeout; a NACK never fails a text, since the seq of a
+// damaged frame may be wrong and another bridge may have received it intact.
+// Costs one short frame per damaged text. Not sent under FIRE_AND_FORGET.
+#ifndef NACK_DAMAGED_TEXTS
+#define NACK_DAMAGED_TEXTS 1
+#endif
+
+// Texts sent from this bridge's app are reported back to it by a DeliveryReport
+// message (type 0x83): "delivered" when the peer's ACK arrives, "failed" once
+// the last retransmission went unACKed. The app needs nothing else to track
+// delivery, so the received ACKs themselves are not forwarded to BLE by default;
+// that saves buffer space and BLE airtime. Set FORWARD_ACKS to 1 to also forward
+// every received ACK (as before), including late ACKs and ACKs for texts this
+// bridge didn't send, which no delivery report covers. Reports are buffered
+// while BLE is disconnected, like ACKs. Position fixes confirmed by track
+// status (TRACK_STATUS) are not reported.
+#ifndef FORWARD_ACKS
+#define FORWARD_ACKS 0
+#endif
+
+// Every text received over LoRa and stored for the app is followed by a
+// LinkQuality message (type 0x84) with the RSSI and SNR it arrived at, so the app
+// can show per-message link quality. Only the receiving bridge sends it; it is
+// never transmitted. Off by default: apps that don't know the type would log it
+// as unknown, and each report takes a slot of BLE_BUFFER_OTHER while disconnected
+// (coalesced per seq and evicted before identify responses, like ACKs).
+#ifndef LINK_QUALITY_REPORTS
+#define LINK_QUALITY_REPORTS 0
+#endif
+
+// What an ACK confirms for texts sent from this bridge when relays (RELAY_ONLY
+// nodes) sit between the two bridges. Carried in bit 3 of the text's flags, so
+// the relays and the destination know what the sender expects:
+// - ACK_SEMANTICS_END_TO_END (default): only the destination bridge's ACK, relayed
+//   back to us, counts. Relays send no hop ACK. The ACK wait grows by
+//   ACK_RELAY_HOPS relayed round trips.
+// - ACK_SEMANTICS_HOP: the first relay answers with a HopAck (type 0x0F) as it
+//   takes the text on, and that completes delivery: the app gets a "relayed"
+//   report and retransmissions stop. The relay is blind and doesn't retransmit,
+//   so a text lost after the first hop goes unnoticed. An end-to-end ACK arriving
+//   first (destination in direct range) still counts as delivered.
+#define ACK_SEMANTICS_END_TO_END 0
+#define ACK_SEMANTICS_HOP 1
+
+#ifndef ACK_SEMANTICS
+#define ACK_SEMANTICS ACK_SEMANTICS_END_TO_END
+#endif
+
+// Relays an end-to-end ACK may have to cross (ACK_SEMANTICS_END_TO_END). Each one
+// adds the airtime of a relayed worst-case text and a relayed ACK to the ACK
+// wait, so the timeout stays bounded yet long enough for the ACK to come back.
+#ifndef ACK_RELAY_HOPS
+#define ACK_RELAY_HOPS 0
+#endif
+
+// Received texts weaker than this (dBm) are still stored for the app but not
+// ACKed: on a link that marginal the ACK most likely won't make it back, so it
+// would only burn airtime and duty cycle. The sender sees an ACK timeout (and
+// retransmits High/Critical texts, so the app may see the same seq twice).
+// Links are roughly symmetric, so set it a few dB above the weakest RSSI the
+// peer can still decode at your spreading factor (about -130 dBm at SF11/BW31).
+// The default is below any real reading, so every text is ACKed.
+#ifndef ACK_MIN_RSSI_DBM
+#define ACK_MIN_RSSI_DBM -200
+#endif
+
+// Duplicate-ACK suppression: a text already ACKed is ACKed again only once
+// ACK_SUPPRESS_WINDOW_PERCENT of the ACK timeout has passed since its last ACK.
+// The sender retransmits no sooner than one full ACK timeout after a send, so a
+// real retransmission (our ACK was lost) always lands outside the window and is
+// re-ACKed; copies arriving sooner - echoed by relays, or in ACK/retransmission
+// cascades between two retransmitting nodes - are delivered but not ACKed again.
+// Keyed on seq and frame content, so a reused seq with new text is always ACKed
+// (unlike message dedup, nothing is dropped). Must stay below 100; 0 disables it.
+#ifndef ACK_SUPPRESS_WINDOW_PERCENT
+#define ACK_SUPPRESS_WINDOW_PERCENT 50
+#endif
+
+// Number of recently ACKed texts remembered for the suppression window
+#ifndef ACK_SUPPRESS_ENTRIES
+#define ACK_SUPPRESS_ENTRIES 8
+#endif
+
+// Received-message dedup: the last RX_DEDUP_ENTRIES texts and battery levels
+// delivered to the app are remembered by type, seq and frame hash. A copy of one
+// of them - the sender retransmitting after our ACK was lost - is ACKed as usual
+// but not delivered again. Entries are kept by arrival, not by seq value, so the
+// seq wrapping from 255 to 0 needs no special case; keep it well below 256 so a
+// seq is evicted long before the sender reuses it. 0 disables the filter.
+#ifndef RX_DEDUP_ENTRIES
+#define RX_DEDUP_ENTRIES 16
+#endif
+
+// Messages held for the app while BLE is disconnected, per kind. Each kind has
+// its own queue and a full queue drops (or, under the stricter ACK policies,
+// rejects) only messages of its own kind, so a flood of position updates can't
+// push texts out. The queues are drained round-robin, one message of each kind
+// in turn (by priority within a kind). Each slot costs sizeof(Message) of RAM
+// (about 300 bytes, so the 64 slots of the defaults take ~19 KB). Every message
+// a full queue drops is counted in the diagnostics (buffer drops).
+// - BLE_BUFFER_TEXTS: text messages, with or without GPS, and SOS messages. An
+//   SOS arriving at a full queue pushes out the newest non-SOS message instead.
+// - BLE_BUFFER_POSITIONS: position-only updates (GPS, empty text)
+// - BLE_BUFFER_OTHER: everything else (ACKs, identify responses, ...). An ACK or
+//   delivery report replaces a buffered one for the same seq, and a full queue
+//   drops its oldest ACK or report first.
+#ifndef BLE_BUFFER_TEXTS
+#define BLE_BUFFER_TEXTS 48
+#endif
+
+#ifndef BLE_BUFFER_POSITIONS
+#define BLE_BUFFER_POSITIONS 8
+#endif
+
+#ifndef BLE_BUFFER_OTHER
+#define BLE_BUFFER_OTHER 8
+#endif
+
+// LoRa retransmissions of a text whose ACK didn't arrive within the SF-aware
+// timeout, per message priority (Normal texts are sent once by default). While a
+// text with retries left awaits its ACK, further transmissions wait so the retry
+// keeps the channel; each retry costs one more time-on-air against the duty cycle.
+// Retrying Normal texts too (e.g. PRIORITY_NORMAL_RETRIES 3) makes every text
+// reliable, at up to 4x the airtime of a lost one - and a position update with
+// retries left holds back the newer one that would have replaced it. Higher
+// priorities may not get fewer retries than lower ones (build error).
+#ifndef PRIORITY_NORMAL_RETRIES
+#define PRIORITY_NORMAL_RETRIES 0
+#endif
+
+#ifndef PRIORITY_HIGH_RETRIES
+#define PRIORITY_HIGH_RETRIES 2
+#endif
+
+#ifndef PRIORITY_CRITICAL_RETRIES
+#define PRIORITY_CRITICAL_RETRIES 4
+#endif
+
+// SOS messages (type 0x06) are not retransmitted on a missing ACK but broadcast
+// again SOS_REPEATS times, SOS_REPEAT_INTERVAL_MS apart, whether or not an ACK
+// came back: every node in range should hear a distress call, and a later
+// repeat may reach one that moved into range. The first ACK reports the SOS as
+// delivered to the app. Receivers pass every repeat on to their app but ACK
+// only the first copy. A new SOS from the app replaces the repeats left of the
+// previous one. Each repeat costs airtime: 4 repeats of the 11-byte frame at
+// SF11/BW31.25 are ~7 s on air.
+#ifndef SOS_REPEATS
+#define SOS_REPEATS 4
+#endif
+
+#ifndef SOS_REPEAT_INTERVAL_MS
+#define SOS_REPEAT_INTERVAL_MS 20000
+#endif
+
+// When those retransmissions go out (RETRANSMIT_STRATEGY):
+// - RETRANSMIT_IMMEDIATE: as soon as each ACK timeout has passed. Quickest
+//   recovery from a single lost frame, for quiet links where losses are random.
+// - RETRANSMIT_BACKOFF (default): the first retry after one ACK timeout, then
+//   the wait doubles with every retry, up to RETRANSMIT_BACKOFF_MAX_MS. Backs off
+//   a congested channel or a peer that is out of range for a while, at the cost
+//   of later deliveries; the send queue waits as long as a retry is pending.
+// - RETRANSMIT_SCHEDULED: after the ACK timeout, the retry waits for the start of
+//   the next listen window, when every node is awake and in sync (requires
+//   RADIO_POWER_DUTY_CYCLED_RX). Up to one LISTEN_PERIOD_MS per retry.
+// A text that has used up its retries fails one ACK timeout after the last one.
+#define RETRANSMIT_IMMEDIATE 0
+#define RETRANSMIT_BACKOFF 1
+#define RETRANSMIT_SCHEDULED 2
+
+#ifndef RETRANSMIT_STRATEGY
+#define RETRANSMIT_STRATEGY RETRANSMIT_BACKOFF
+#endif
+
+#ifndef RETRANSMIT_BACKOFF_MAX_MS
+#define RETRANSMIT_BACKOFF_MAX_MS 30000
+#endif
+
+// Texts that may await their ACK at the same time (each keeps its frame for
+// retransmission, MAX_SERIALIZED_LEN bytes). Sequence numbers are a u8 that
+// wraps at 256, and the app restarts its counter at 0 after a relaunch, so a seq
+// can come round again while the earlier text with that seq is still unACKed;
+// an ACK for it would then be ambiguous. Such a text is held in the queue until
+// the earlier one is ACKed or times out. The same holds for any new text while
+// the table is full.
+#ifndef MAX_TEXTS_IN_FLIGHT
+#define MAX_TEXTS_IN_FLIGHT 4
+#endif
+
+// Stop-and-wait: the bridge takes the next message from the app only once the
+// previous one is resolved - sent, and for a text ACKed or finally timed out
+// (after its retransmissions). Further writes wait in order in the BLE-to-LoRa
+// queue (10 messages); when it is full, writes are dropped, so the app should
+// send its next text after the delivery report (see FORWARD_ACKS) of the last
+// one. Guarantees one text on air at a time, in order, with every text's
+// outcome known before the next is sent. Costs throughput: at most one text per
+// round trip (frame + ACK time on air, several seconds at SF11/BW31), or per
+// ACK timeout and retransmissions when ACKs are lost. Position updates queued
+// meanwhile still coalesce.
+#ifndef STOP_AND_WAIT
+#define STOP_AND_WAIT 0
+#endif
+
+// Track status (bulk position transfer): Normal-priority position-only updates
+// ("fixes") are no longer ACKed one by one. The receiver collects which fixes it
+// got and, TRACK_STATUS_INTERVAL_MS after the first unreported one, sends back a
+// TrackStatus bitmap of the last 32 seqs (see protocol.md). The sender keeps its
+// last TRACK_HISTORY fixes (MAX_SERIALIZED_LEN bytes each) and retransmits only
+// the ones the bitmap reports missing, up to TRACK_MAX_RETRIES times each. Both
+// ends must enable it: a receiver without it ACKs fixes as usual, a sender
+// without it sees every fix as an ACK timeout.
+#ifndef TRACK_STATUS
+#define TRACK_STATUS 0
+#endif
+
+#ifndef TRACK_STATUS_INTERVAL_MS
+#define TRACK_STATUS_INTERVAL_MS 10000
+#endif
+
+#ifndef TRACK_HISTORY
+#define TRACK_HISTORY 8
+#endif
+
+#ifndef TRACK_MAX_RETRIES
+#define TRACK_MAX_RETRIES 2
+#endif
+
+// GPS delta encoding: a Normal-priority position-only update goes on air as a
+// GpsDelta (8 bytes instead of 14, see protocol.md) holding i16 offsets from the
+// last full one this bridge sent. A full update goes out instead when there is
+// none yet, when the move doesn't fit an i16 (about 3.6 km of latitude), and
+// after every GPS_DELTA_RESYNC_EVERY deltas, so a receiver that missed the full
+// one is back in step soon. A delta names the seq of its full update; a receiver
+// that doesn't have that one drops it unACKed, so it is retransmitted or, with
+// TRACK_STATUS, reported missing. The app always gets full positions. Deltas are
+// always understood; only enable sending them once every node of the fleet runs
+// firmware that knows type 0x07 (older ones drop it as an unknown type).
+#ifndef GPS_DELTA_ENCODING
+#define GPS_DELTA_ENCODING 0
+#endif
+
+#ifndef GPS_DELTA_RESYNC_EVERY
+#define GPS_DELTA_RESYNC_EVERY 10
+#endif
+
+// LoRa is half-duplex: a transmission while a frame is being received would
+// abort that frame, so the bridge always waits for the reception to complete
+// (at most one ACK timeout). With 1 it also tells the app: a Status message
+// "radio busy" when a transmission is deferred and "TX resumed" once it went
+// out (see protocol.md). Statuses are dropped while no phone is connected.
+#ifndef RADIO_BUSY_STATUS
+#define RADIO_BUSY_STATUS 0
+#endif
+
+// Listen before talk: with LBT_CAD_ATTEMPTS above 0 every text, retransmission,
+// position fix and relayed frame is preceded by a Channel Activity Detection
+// (CAD), in which the radio listens for a LoRa preamble. If it hears one the
+// transmission backs off for a random 1-2 slots of LBT_BACKOFF_SLOT_MS, then
+// 1-4, 1-8 and at most 1-16 after further busy checks (see lbtBackoffMs()), and
+// checks again; after LBT_CAD_ATTEMPTS busy checks in a row it transmits anyway.
+// Fewer collisions when several nodes share the channel, at about two symbols of
+// extra latency per transmission on a quiet channel (66 ms at SF10/BW31.25) plus
+// the backoffs on a busy one. ACKs, NACKs and track statuses go out without a
+// CAD: the peer is waiting for them, and they answer a frame that just ended.
+#ifndef LBT_CAD_ATTEMPTS
+#define LBT_CAD_ATTEMPTS 0
+#endif
+
+#ifndef LBT_BACKOFF_SLOT_MS
+#define LBT_BACKOFF_SLOT_MS 100
+#endif
+
+// Channel agility: while idle the bridge samples the RSSI floor of its channel
+// every CHANNEL_MONITOR_INTERVAL_MS. When a full window of CHANNEL_MONITOR_WINDOW
+// samples averages above CHANNEL_INTERFERENCE_DBM it scans the band, and if a
+// channel at least CHANNEL_SWITCH_MIN_GAIN_DB quieter exists it proposes the move
+// to its peer (ChannelSwitch handshake, see protocol.md). Both ends must enable it;
+// a peer that doesn't answer keeps the link where it is.
+// Anti-flapping: after any switch attempt the monitor is held off for
+// CHANNEL_SWITCH_HOLDOFF_MS, and a bridge that hears nothing for
+// CHANNEL_SILENCE_FALLBACK_MS away from LORA_FREQUENCY returns to it.
+#ifndef CHANNEL_AGILITY
+#define CHANNEL_AGILITY 0
+#endif
+
+#ifndef CHANNEL_MONITOR_INTERVAL_MS
+#define CHANNEL_MONITOR_INTERVAL_MS 2000
+#endif
+
+#ifndef CHANNEL_MONITOR_WINDOW
+#define CHANNEL_MONITOR_WINDOW 60 // 2 minutes at the default interval
+#endif
+
+#ifndef CHANNEL_INTERFERENCE_DBM
+#define CHANNEL_INTERFERENCE_DBM -100
+#endif
+
+#ifndef CHANNEL_SWITCH_MIN_GAIN_DB
+#define CHANNEL_SWITCH_MIN_GAIN_DB 6
+#endif
+
+#ifndef CHANNEL_SWITCH_HOLDOFF_MS
+#define CHANNEL_SWITCH_HOLDOFF_MS 600000UL // 10 minutes
+#endif
+
+#ifndef CHANNEL_SILENCE_FALLBACK_MS
+#define CHANNEL_SILENCE_FALLBACK_MS 1800000UL // 30 minutes
+#endif
+
+// Adaptive data rate: the bridge keeps the SNR of the last ADR_WINDOW frames it
+// heard and compares it with the demodulation floor of each spreading factor
+// (-7.5 dB at SF7, 2.5 dB lower per step). When every frame of a full window
+// would still clear the floor of the next lower factor by ADR_FASTER_MARGIN_DB,
+// it proposes that factor to its peer (RateSwitch handshake, see protocol.md);
+// when the window averages less than ADR_SLOWER_MARGIN_DB above the floor of the
+// factor in effect, or ADR_MISSED_ACKS ACKs in a row time out, it proposes the
+// next higher one. It never goes faster than ADR_MIN_SPREADING_FACTOR or slower
+// than the configured factor (LORA_SPREADING_FACTOR, or the one set with control
+// opcode 0x0A), and its changes are not saved. Both ends must enable it; a peer
+// that doesn't answer keeps the link where it is.
+// Anti-flapping: the faster margin must exceed the slower one, so a step down
+// lands inside the band between them; after any attempt the monitor is held off
+// for ADR_HOLDOFF_MS, and a bridge that hears nothing for ADR_SILENCE_FALLBACK_MS
+// below the configured factor returns to it.
+#ifndef ADAPTIVE_DATA_RATE
+#define ADAPTIVE_DATA_RATE 0
+#endif
+
+#ifndef ADR_WINDOW
+#define ADR_WINDOW 16
+#endif
+
+#ifndef ADR_FASTER_MARGIN_DB
+#define ADR_FASTER_MARGIN_DB 8
+#endif
+
+#ifndef ADR_SLOWER_MARGIN_DB
+#define ADR_SLOWER_MARGIN_DB 3
+#endif
+
+#ifndef ADR_MISSED_ACKS
+#define ADR_MISSED_ACKS 3
+#endif
+
+#ifndef ADR_MIN_SPREADING_FACTOR
+#define ADR_MIN_SPREADING_FACTOR 7
+#endif
+
+#ifndef ADR_HOLDOFF_MS
+#define ADR_HOLDOFF_MS 300000UL // 5 minutes
+#endif
+
+#ifndef ADR_SILENCE_FALLBACK_MS
+#define ADR_SILENCE_FALLBACK_MS 900000UL // 15 minutes
+#endif
+
+// When the radio may sleep (RADIO_POWER_MODE):
+// - RADIO_POWER_ALWAYS_ON_RX (default): continuous RX, back to RX right after
+//   every TX. Hears every frame at once, but the radio draws RX current (~11 mA
+//   on the SX127x) all the time.
+// - RADIO_POWER_SLEEP_BETWEEN_OPS: the radio sleeps (~1 uA) whenever it has
+//   nothing to do and wakes only to transmit, staying in RX afterwards just
+//   until the ACK is due. Lowest power, but frames from peers are only heard in
+//   that short wait - for nodes that mostly send (e.g. trackers), not for
+//   receiving messages.
+// - RADIO_POWER_DUTY_CYCLED_RX: RX only in synchronized listen windows, asleep in
+//   between (LISTEN_WINDOWS below). Power drops with the window/period ratio,
+//   and messages in both directions wait up to LISTEN_PERIOD_MS for a window.
+// - RADIO_POWER_PERIODIC_RX: RX for PERIODIC_RX_WINDOW_MS, then asleep for
+//   PERIODIC_RX_SLEEP_MS, without any sync with the peers (see below). Sends at
+//   once and never sleeps through an ACK, but a peer's frame is only heard if it
+//   starts inside one of our windows.
+// Building with LISTEN_WINDOWS 1 alone still selects duty-cycled RX. The sleeping
+// modes don't combine with CHANNEL_AGILITY (the monitor can't sample a sleeping
+// radio) or RELAY_ONLY.
+#define RADIO_POWER_ALWAYS_ON_RX 0
+#define RADIO_POWER_SLEEP_BETWEEN_OPS 1
+#define RADIO_POWER_DUTY_CYCLED_RX 2
+#define RADIO_POWER_PERIODIC_RX 3
+
+#ifndef RADIO_POWER_MODE
+#if defined(LISTEN_WINDOWS) && LISTEN_WINDOWS
+#define RADIO_POWER_MODE RADIO_POWER_DUTY_CYCLED_RX
+#else
+#define RADIO_POWER_MODE RADIO_POWER_ALWAYS_ON_RX
+#endif
+#endif
+
+// Synchronized listen windows (low-power networks): the radio only listens for
+// LISTEN_WINDOW_MS at the start of every LISTEN_PERIOD_MS and sleeps in between,
+// and messages wait for the next window. Every node of the network must use the
+// same period and window. There is no shared clock: each node beacons its
+// schedule (ListenSchedule, see protocol.md) every LISTEN_BEACON_EVERY windows,
+// and nodes align their windows to the lowest node id they hear. A node listens
+// continuously for LISTEN_SEARCH_MS after boot, and again whenever it has heard
+// no beacon of its reference for LISTEN_SYNC_LOST_MS. LISTEN_GUARD_MS at both
+// ends of a window absorbs clock drift between beacons.
+// The window must hold a full frame plus its ACK (several seconds at SF11/BW31,
+// e.g. 500 ms is enough at SF7/BW125). Don't combine with CHANNEL_AGILITY: the
+// monitor can't sample a sleeping radio.
+#ifndef LISTEN_WINDOWS
+#define LISTEN_WINDOWS (RADIO_POWER_MODE == RADIO_POWER_DUTY_CYCLED_RX)
+#endif
+
+// Periodic listening (RADIO_POWER_PERIODIC_RX, battery nodes without a network
+// schedule): the radio listens for PERIODIC_RX_WINDOW_MS and sleeps for
+// PERIODIC_RX_SLEEP_MS in turn, drawing RX current about window / (window +
+// sleep) of the time. Nothing is negotiated: a peer's frame is heard if it starts
+// inside a window, so at the defaults one transmission gets through about one
+// time in five and the sender's retries, one ACK timeout apart, do the rest.
+// Longer sleeps save more and lose more first attempts. A frame the radio locks
+// onto before the window closes is received to its end, and an ACK wait keeps
+// the radio on past the window too.
+#ifndef PERIODIC_RX_WINDOW_MS
+#define PERIODIC_RX_WINDOW_MS 3000UL
+#endif
+
+#ifndef PERIODIC_RX_SLEEP_MS
+#define PERIODIC_RX_SLEEP_MS 12000UL
+#endif
+
+#ifndef LISTEN_PERIOD_MS
+#define LISTEN_PERIOD_MS 60000 // Max 65535 (u16 on air)
+#endif
+
+#ifndef LISTEN_WINDOW_MS
+#define LISTEN_WINDOW_MS 10000
+#endif
+
+#ifndef LISTEN_GUARD_MS
+#define LISTEN_GUARD_MS 100
+#endif
+
+#ifndef LISTEN_BEACON_EVERY
+#define LISTEN_BEACON_EVERY 5
+#endif
+
+#ifndef LISTEN_SEARCH_MS
+#define LISTEN_SEARCH_MS (2UL * LISTEN_PERIOD_MS)
+#endif
+
+#ifndef LISTEN_SYNC_LOST_MS
+#define LISTEN_SYNC_LOST_MS (3UL * LISTEN_BEACON_EVERY * LISTEN_PERIOD_MS)
+#endif
+
+// Graduated recovery from LoRa RX errors (frames read back shorter than the
+// radio reported them). A run of them points at a radio left in a bad
+// modulation or packet state, e.g. by a failed mode transition: after
+// RX_ERROR_RECONFIGURE_AFTER errors in a row the modulation and packet
+// parameters are written again and RX is re-armed; if the errors go on to
+// RX_ERROR_REINIT_AFTER the radio is reset and fully re-initialized. Any
+// complete frame clears the count. 0 disables a stage.
+#ifndef RX_ERROR_RECONFIGURE_AFTER
+#define RX_ERROR_RECONFIGURE_AFTER 3
+#endif
+
+#ifndef RX_ERROR_REINIT_AFTER
+#define RX_ERROR_REINIT_AFTER 6
+#endif
+
+// Re-initializations back off while the errors go on, or while the radio fails
+// to come back: the next one waits RX_ERROR_REINIT_BACKOFF_MIN_MS after the last,
+// each further one twice as long up to RX_ERROR_REINIT_BACKOFF_MAX_MS. A
+// complete frame resets the wait. Errors in the meantime are only counted.
+#ifndef RX_ERROR_REINIT_BACKOFF_MIN_MS
+#define RX_ERROR_REINIT_BACKOFF_MIN_MS 5000
+#endif
+
+#ifndef RX_ERROR_REINIT_BACKOFF_MAX_MS
+#define RX_ERROR_REINIT_BACKOFF_MAX_MS 300000
+#endif
+
+// Audit log: RAM-only ring of the last AUDIT_LOG_CAPACITY message events (sent,
+// retransmitted, ACKed, ACK timeout, TX failed, received, ACK withheld, dropped)
+// with millis() timestamps, read page by page over the control characteristic.
+// Each entry costs 8 bytes of RAM; the log is lost on reboot.
+#ifndef AUDIT_LOG_CAPACITY
+#define AUDIT_LOG_CAPACITY 64
+#endif
+
+// Entries per audit log page (response is 10 + 8 * entries bytes, max 255)
+#define AUDIT_PAGE_ENTRIES 16
+
+// Status LED heartbeat on LED_PIN: a continuous pattern showing the system state
+// instead of per-event blinks, for checking a unit in the field without a phone.
+// Solid = initializing, slow blink (every 2 s) = idle, fast blink (every 500 ms)
+// = app connected, double blink = radio error (setup failed, re-init pending, or
+// RX_ERROR_RECONFIGURE_AFTER RX errors in a row). Identify blinking still takes
+// over while active. Costs power: a task wakes the CPU every STATUS_LED_TICK_MS.
+#ifndef STATUS_LED
+#define STATUS_LED 0
+#endif
+
+#ifndef STATUS_LED_TICK_MS
+#define STATUS_LED_TICK_MS 50
+#endif
+
+// Heap use (used / peak / free / largest free block) is logged at boot and then
+// every HEAP_LOG_INTERVAL_MS; 0 = boot only. Always readable on the heap
+// characteristic (0x567D, see protocol.md).
+#ifndef HEAP_LOG_INTERVAL_MS
+#define HEAP_LOG_INTERVAL_MS 600000UL // 10 minutes
+#endif
+
+// Standard Battery Service (0x180F): the Battery Level characteristic is read
+// from the board every BATTERY_LEVEL_INTERVAL_MS and notified to a subscribed
+// phone when it changes. The supported boards have no battery sense yet, so the
+// level is the fixed placeholder BATTERY_LEVEL_PERCENT (0-100) until an ADC
+// reading replaces it; diagnostics still report the battery as not measured.
+#ifndef BATTERY_LEVEL_PERCENT
+#define BATTERY_LEVEL_PERCENT 100
+#endif
+
+#ifndef BATTERY_LEVEL_INTERVAL_MS
+#define BATTERY_LEVEL_INTERVAL_MS 60000UL // 1 minute
+#endif
+
+// Link health counters on the stats characteristic (0x567E, see protocol.md) are
+// refreshed every LINK_STATS_INTERVAL_MS and notified to a subscribed phone when
+// any of them changed. They count from boot and are never reset.
+#ifndef LINK_STATS_INTERVAL_MS
+#define LINK_STATS_INTERVAL_MS 2000UL // 2 seconds
+#endif
+
+#endif // BRIDGE_CONFIG_H
@@ -0,0 +1,18 @@
+# PlatformIO pre-build script: passes the git revision of the tree to the
+# firmware as FIRMWARE_GIT_HASH (e.g. "6ba2f12", "6ba2f12-dirty" with local
+# changes), shown in the Firmware Revision of the Device Information Service.
+# Builds outside a git checkout keep the "unknown" default of bridge_config.h.
+import subprocess
+
+Import("env")
+
+project_dir = env.subst("$PROJECT_DIR")
+try:
+    revision = subprocess.check_output(
+        ["git", "rev-parse", "--short", "HEAD"], cwd=project_dir, stderr=subprocess.DEVNULL
+    ).decode().strip()
+    if subprocess.call(["git", "diff", "--quiet", "HEAD"], cwd=project_dir, stderr=subprocess.DEVNULL) != 0:
+        revision += "-dirty"
+    env.Append(CPPDEFINES=[("FIRMWARE_GIT_HASH", env.StringifyMacro(revision))])
+except (OSError, subprocess.CalledProcessError):
+    pass
@@ -1,3 +1,5 @@
+#include "bridge_config.h"
+#if !RELAY_ONLY // No BLE in relay-only builds
 #include "BLEManager.h"
 
 // Server callbacks implementation
@@ -10,7 +12,17 @@ void MyServerCallbacks::onConnect(NimBLEServer *pServer, NimBLEConnInfo &connInf
     Serial.print("MTU: ");
     Serial.println(connInfo.getMTU());
 
-    bleManager->onConnected();
+    bleManager->onConnected(connInfo.getConnHandle());
+    bleManager->onMtuChanged(connInfo.getMTU());
+
+    // Ask for a larger MTU ourselves instead of relying on the central to do it.
+    // The result arrives via onMTUChange().
+    int rc = ble_gattc_exchange_mtu(connInfo.getConnHandle(), nullptr, nullptr);
+    if (rc != 0)
+    {
+        Serial.print("MTU exchange request failed: ");
+        Serial.println(rc);
+    }
 
     // Stop advertising when connected
     NimBLEDevice::getAdvertising()->stop();
@@ -24,9 +36,15 @@ void MyServerCallbacks::onDisconnect(NimBLEServer *pServer, NimBLEConnInfo &conn
     bleManager->onDisconnected();
 }
 
+void MyServerCallbacks::onMTUChange(uint16_t MTU, NimBLEConnInfo &connInfo)
+{
+    bleManager->onMtuChanged(MTU);
+}
+
 // Characteristic callbacks implementation
 void MyCharacteristicCallbacks::onWrite(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo)
 {
+    bleManager->onGattActivity();
     std::string value = pCharacteristic->getValue();
     if (value.length() > 0)
     {
@@ -47,18 +65,115 @@ void MyCharacteristicCallbacks::onWrite(NimBLECharacteristic *pCharacteristic, N
     }
 }
 
+// Control characteristic callbacks implementation
+void MyControlCallbacks::onWrite(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo)
+{
+    bleManager->onGattActivity();
+    std::string value = pCharacteristic->getValue();
+    if (value.length() > 0)
+    {
+        bleManager->onControlReceived((const uint8_t *)value.data(), value.length());
+    }
+}
+
+// Unlock characteristic callbacks implementation
+void MyUnlockCallbacks::onWrite(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo)
+{
+    bleManager->onGattActivity();
+    std::string value = pCharacteristic->getValue();
+    bleManager->onUnlockReceived((const uint8_t *)value.data(), value.length());
+}
+
+void MyUnlockCallbacks::onRead(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo)
+{
+    bleManager->onGattActivity();
+    // 1 = control commands accepted, 0 = locked
+    uint8_t state = bleManager->isControlUnlocked() ? 1 : 0;
+    pCharacteristic->setValue(&state, 1);
+}
+
+// Last-RX characteristic callbacks implementation
+void MyLastRxCallbacks::onRead(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo)
+{
+    bleManager->onGattActivity();
+    uint8_t buf[LAST_RX_META_LEN];
+    size_t len = bleManager->readLastRxMeta(buf);
+    pCharacteristic->setValue(buf, len);
+}
+
+// Heap characteristic callbacks implementation
+void MyHeapCallbacks::onRead(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo)
+{
+    bleManager->onGattActivity();
+    uint8_t buf[HEAP_STATS_LEN];
+    size_t len = serializeHeapStats(readHeapStats(), buf);
+    pCharacteristic->setValue(buf, len);
+}
+
+// TX characteristic callbacks implementation
+void MyTxCharacteristicCallbacks::onSubscribe(NimBLECharacteristic *pCharacteristic, NimBLEConnInfo &connInfo, uint16_t subValue)
+{
+    Serial.print("TX characteristic subscription changed: ");
+    Serial.println(subValue);
+    bleManager->onGattActivity();
+    bleManager->onTxSubscribe(subValue);
+}
+
+void MyTxCharacteristicCallbacks::onStatus(NimBLECharacteristic *pCharacteristic, int code)
+{
+    bleManager->onTxStatus(code);
+}
+
 // BLEManager implementation
-BLEManager::BLEManager(QueueHandle_t queue)
+BLEManager::BLEManager(QueueHandle_t queue, QueueHandle_t controlQueue)
     : pServer(nullptr),
       pTxCharacteristic(nullptr),
       pRxCharacteristic(nullptr),
+      pControlCharacteristic(nullptr),
+      pUnlockCharacteristic(nullptr),
+      pLastRxCharacteristic(nullptr),
+      pHeapCharacteristic(nullptr),
+      pStatsCharacteristic(nullptr),
+      pBatteryLevelCharacteristic(nullptr),
       pAdvertising(nullptr),
       deviceConnected(false),
       oldDeviceConnected(false),
+      connectionId(0),
+      connHandle(0),
+      lastGattActivityAt(0),
+      idleDisconnectPending(false),
+      disconnectedAt(0),
+      lastAdvertisingStart(0),
+      advertisingRestartPending(false),
+      advertisingBackoff(BLE_ADVERTISING_RESTART_MIN_INTERVAL_MS, BLE_ADVERTISING_BACKOFF_MAX_MS),
+      indicationsEnabled(false),
+      negotiatedMtu(BLE_DEFAULT_MTU),
+      batteryLevel(BATTERY_UNKNOWN),
+      linkStats{},
+      controlUnlocked(false),
+      controlUnlockedAt(0),
+      lastRxMeta{},
+      lastRxMetaLock(portMUX_INITIALIZER_UNLOCKED),
+      composeExpected(0),
+      composeReceived(0),
+      composeStartedAt(0),
+      indicationSemaphore(nullptr),
+      awaitingIndication(false),
+      indicationConfirmed(false),
+      lastTxStatus(0),
+      notifyPacingMs(BLE_NOTIFY_PACING_MIN_MS),
+      notifyPaused(false),
+      notifyPausedAt(0),
       bleToLoraQueue(queue),
+      controlQueue(controlQueue),
       deviceNameStr(""),
       serverCallbacks(nullptr),
       rxCallbacks(nullptr),
+      txCallbacks(nullptr),
+      controlCallbacks(nullptr),
+      unlockCallbacks(nullptr),
+      lastRxCallbacks(nullptr),
+      heapCallbacks(nullptr),
       activityCallback(nullptr)
 {
 }
@@ -70,14 +185,31 @@ bool BLEManager::setup(const char *deviceName)
     // Store device name for debugging
     deviceNameStr = String(deviceName);
 
+    indicationSemaphore = xSemaphoreCreateBinary();
+    if (indicationSemaphore == nullptr)
+    {
+        Serial.println("Failed to create indication semaphore");
+        return false;
+    }
+
     // Create the BLE Device
     NimBLEDevice::init(deviceName);
 
+    // Our preferred MTU is what we offer in the exchange - make sure it fits every message
+    if (NimBLEDevice::getMTU() < BLE_REQUESTED_MTU)
+    {
+        NimBLEDevice::setMTU(BLE_REQUESTED_MTU);
+    }
+
     // Create the BLE Server
     pServer = NimBLEDevice::createServer();
     serverCallbacks = new MyServerCallbacks(this);
     pServer->setCallbacks(serverCallbacks);
 
+    // Advertising restarts are debounced in process() - don't let the stack
+    // restart it immediately on every disconnect as well
+    pServer->advertiseOnDisconnect(false);
+
     // Create the BLE Service
     NimBLEService *pService = pServer->createService(SERVICE_UUID);
 
@@ -86,7 +218,10 @@ bool BLEManager::setup(const char *deviceName)
         TX_CHARACTERISTIC_UUID,
         NIMBLE_PROPERTY::READ |
             NIMBLE_PROPERTY::WRITE |
-            NIMBLE_PROPERTY::NOTIFY);
+            NIMBLE_PROPERTY::NOTIFY |
+            NIMBLE_PROPERTY::INDICATE);
+    txCallbacks = new MyTxCharacteristicCallbacks(this);
+    pTxCharacteristic->setCallbacks(txCallbacks);
 
     // Create the RX Characteristic (for receiving data from phone)
     pRxCharacteristic = pService->createCharacteristic(
@@ -98,9 +233,60 @@ bool BLEManager::setup(const char *deviceName)
     rxCallbacks = new MyCharacteristicCallbacks(this);
     pRxCharacteristic->setCallbacks(rxCallbacks);
 
+    // Create the Control Characteristic (commands from phone, responses via notify)
+    pControlCharacteristic = pService->createCharacteristic(
+        CONTROL_CHARACTERISTIC_UUID,
+        NIMBLE_PROPERTY::WRITE |
+            NIMBLE_PROPERTY::NOTIFY);
+    controlCallbacks = new MyControlCallbacks(this);
+    pControlCharacteristic->setCallbacks(controlCallbacks);
+
+    // Create the Unlock Characteristic (passcode for the control lock, read = lock state)
+    pUnlockCharacteristic = pService->createCharacteristic(
+        UNLOCK_CHARACTERISTIC_UUID,
+        NIMBLE_PROPERTY::READ |
+            NIMBLE_PROPERTY::WRITE);
+    unlockCallbacks = new MyUnlockCallbacks(this);
+    pUnlockCharacteristic->setCallbacks(unlockCallbacks);
+
+    // Create the Last-RX Characteristic (read = metadata of the last received LoRa frame)
+    pLastRxCharacteristic = pService->createCharacteristic(
+        LAST_RX_CHARACTERISTIC_UUID,
+        NIMBLE_PROPERTY::READ);
+    lastRxCallbacks = new MyLastRxCallbacks(this);
+    pLastRxCharacteristic->setCallbacks(lastRxCallbacks);
+
+    // Create the Heap Characteristic (read = current and peak heap use)
+    pHeapCharacteristic = pService->createCharacteristic(
+        HEAP_CHARACTERISTIC_UUID,
+        NIMBLE_PROPERTY::READ);
+    heapCallbacks = new MyHeapCallbacks(this);
+    pHeapCharacteristic->setCallbacks(heapCallbacks);
+
+    // Create the Stats Characteristic (read/notify = link health counters since boot)
+    pStatsCharacteristic = pService->createCharacteristic(
+        STATS_CHARACTERISTIC_UUID,
+        NIMBLE_PROPERTY::READ |
+            NIMBLE_PROPERTY::NOTIFY);
+    uint8_t statsValue[LINK_STATS_LEN];
+    pStatsCharacteristic->setValue(statsValue, serializeLinkStats(linkStats, statsValue));
+
     // Start the service
     pService->start();
 
+    // Device Information Service, so clients can show what the bridge is running
+    NimBLEService *pInfoService = pServer->createService(DEVICE_INFO_SERVICE_UUID);
+    pInfoService->createCharacteristic(MANUFACTURER_NAME_UUID, NIMBLE_PROPERTY::READ)->setValue(DIS_MANUFACTURER_NAME);
+    pInfoService->createCharacteristic(FIRMWARE_REVISION_UUID, NIMBLE_PROPERTY::READ)->setValue(DIS_FIRMWARE_REVISION);
+    pInfoService->createCharacteristic(HARDWARE_REVISION_UUID, NIMBLE_PROPERTY::READ)->setValue(DIS_HARDWARE_REVISION);
+    pInfoService->start();
+
+    // Battery Service, for generic BLE apps that don't speak the LoRa protocol
+    NimBLEService *pBatteryService = pServer->createService(BATTERY_SERVICE_UUID);
+    pBatteryLevelCharacteristic =
+        pBatteryService->createCharacteristic(BATTERY_LEVEL_UUID, NIMBLE_PROPERTY::READ | NIMBLE_PROPERTY::NOTIFY);
+    pBatteryService->start();
+
     // Get advertising instance and configure for better discoverability
     pAdvertising = NimBLEDevice::getAdvertising();
     pAdvertising->addServiceUUID(SERVICE_UUID);
@@ -126,12 +312,32 @@ bool BLEManager::setup(const char *deviceName)
     Serial.println(TX_CHARACTERISTIC_UUID);
     Serial.print("RX Characteristic UUID: ");
     Serial.println(RX_CHARACTERISTIC_UUID);
+    Serial.print("Control Characteristic UUID: ");
+    Serial.println(CONTROL_CHARACTERISTIC_UUID);
+    Serial.print("Unlock Characteristic UUID: ");
+    Serial.println(UNLOCK_CHARACTERISTIC_UUID);
+    Serial.print("Last-RX Characteristic UUID: ");
+    Serial.println(LAST_RX_CHARACTERISTIC_UUID);
+    Serial.print("Heap Characteristic UUID: ");
+    Serial.println(HEAP_CHARACTERISTIC_UUID);
+    Serial.print("Firmware revision: ");
+    Serial.println(DIS_FIRMWARE_REVISION);
+    if (strlen(BLE_CONTROL_PASSCODE) > 0)
+    {
+        Serial.println("Control characteristic locked until the passcode is written");
+    }
 
     return true;
 }
 
-void BLEManager::startAdvertising()
+bool BLEManager::startAdvertising()
 {
+    if (deviceConnected || (pAdvertising && pAdvertising->isAdvertising()))
+    {
+        return true; // Nothing to do - avoids redundant restarts on every LoRa RX
+    }
+
+    lastAdvertisingStart = millis();
     Serial.println("Starting BLE advertising...");
 
     // Additional debugging information
@@ -140,11 +346,22 @@ void BLEManager::startAdvertising()
     Serial.print("MAC Address: ");
     Serial.println(NimBLEDevice::getAddress().toString().c_str());
 
-    NimBLEDevice::startAdvertising();
+    if (!NimBLEDevice::startAdvertising())
+    {
+        advertisingBackoff.fail(millis());
+        advertisingRestartPending = true;
+        Serial.print("BLE advertising failed to start - retrying in ");
+        Serial.print(advertisingBackoff.delayMs());
+        Serial.println(" ms");
+        return false;
+    }
+    advertisingBackoff.reset();
+
     Serial.println("BLE advertising started, waiting for connection...");
     Serial.print("Device should now be discoverable as '");
     Serial.print(deviceNameStr);
     Serial.println("'");
+    return true;
 }
 bool BLEManager::sendMessage(const Message &msg)
 {
@@ -154,7 +371,7 @@ bool BLEManager::sendMessage(const Message &msg)
         return false;
     }
 
-    uint8_t buf[64];
+    uint8_t buf[MAX_SERIALIZED_LEN];
     int len = msg.serialize(buf, sizeof(buf));
 
     if (len < 0)
@@ -163,26 +380,363 @@ bool BLEManager::sendMessage(const Message &msg)
         return false;
     }
 
+    // Text and SOS messages are what the user actually reads, so confirm their delivery
+    // when the client asked for indications
+    bool indicate = (msg.type == MessageType::Text || msg.type == MessageType::Sos) && indicationsEnabled;
+
+    return sendFragmented(pTxCharacteristic, buf, len, indicate);
+}
+
+size_t BLEManager::sendBatch(const Message *msgs, size_t count)
+{
+    if (!deviceConnected || count == 0)
+    {
+        return 0;
+    }
+
+    size_t maxPayload = negotiatedMtu - 3;
+    uint8_t batch[1 + BLE_BATCH_MAX_MESSAGES * (1 + MAX_SERIALIZED_LEN)];
+    batch[0] = BLE_BATCH;
+    size_t len = 1;
+    size_t packed = 0;
+    bool indicate = false;
+    while (packed < count && packed < BLE_BATCH_MAX_MESSAGES)
+    {
+        int msgLen = msgs[packed].serialize(batch + len + 1, MAX_SERIALIZED_LEN);
+        if (msgLen < 0 || len + 1 + msgLen > maxPayload)
+        {
+            break;
+        }
+        batch[len] = msgLen;
+        len += 1 + msgLen;
+        indicate = indicate || ((msgs[packed].type == MessageType::Text || msgs[packed].type == MessageType::Sos) &&
+                                indicationsEnabled);
+        packed++;
+    }
+
+    // Nothing to gain from a batch of one (or a message that needs fragmenting)
+    if (packed < 2)
+    {
+        return sendMessage(msgs[0]) ? 1 : 0;
+    }
+
+    Serial.print("Coalescing ");
+    Serial.print(packed);
+    Serial.println(" messages into one notification");
+    return sendPayload(pTxCharacteristic, batch, len, indicate) ? packed : 0;
+}
+
+bool BLEManager::sendFragmented(NimBLECharacteristic *characteristic, const uint8_t *buf, size_t len, bool indicate)
+{
+    size_t maxPayload = negotiatedMtu - 3;
+    if (len <= maxPayload)
+    {
+        return sendPayload(characteristic, buf, len, indicate);
+    }
+    if (len > BLE_MAX_FRAGMENTED_LEN)
+    {
+        Serial.print("Message of ");
+        Serial.print(len);
+        Serial.println(" bytes too long to fragment - not sent");
+        return false;
+    }
+
+    // Central refused a large enough MTU - split into fragments
+    Serial.print("Message of ");
+    Serial.print(len);
+    Serial.print(" bytes exceeds MTU payload of ");
+    Serial.print(maxPayload);
+    Serial.println(" bytes, fragmenting");
+
+    // Sized for any MTU, not for the message: control responses outgrow MAX_SERIALIZED_LEN
+    uint8_t fragment[BLE_MAX_ATT_PAYLOAD];
+    size_t fragmentLen = min(maxPayload, sizeof(fragment));
+    size_t offset = 0;
+    while (offset < len)
+    {
+        size_t header = offset == 0 ? 2 : 1;
+        size_t chunk = min(fragmentLen - header, len - offset);
+        fragment[0] = offset == 0 ? BLE_FRAGMENT_START : BLE_FRAGMENT_CONTINUE;
+        if (offset == 0)
+        {
+            fragment[1] = len;
+        }
+        memcpy(fragment + header, buf + offset, chunk);
+
+        if (!sendPayload(characteristic, fragment, header + chunk, indicate))
+        {
+            return false;
+        }
+        offset += chunk;
+    }
+
+    return true;
+}
+
+bool BLEManager::sendPayload(NimBLECharacteristic *characteristic, const uint8_t *data, size_t len, bool indicate)
+{
+    if (len > static_cast<size_t>(negotiatedMtu - 3))
+    {
+        Serial.print("Payload of ");
+        Serial.print(len);
+        Serial.print(" bytes exceeds MTU ");
+        Serial.print(negotiatedMtu);
+        Serial.println(" - not sent");
+        return false;
+    }
+    if (!canNotify())
+    {
+        Serial.println("BLE controller out of buffers - notification held back");
+        return false;
+    }
+
+    if (indicate && characteristic == pTxCharacteristic)
+    {
+        return indicateAndWait(data, len);
+    }
+
     Serial.print("Sending ");
     Serial.print(len);
     Serial.println(" bytes via BLE notification");
 
-    pTxCharacteristic->setValue(buf, len);
-    pTxCharacteristic->notify();
+    // setValue() replaces the whole value, so the notification is exactly len bytes
+    characteristic->setValue(data, len);
+    lastTxStatus = 0;
+    bool sent = characteristic->notify();
+    if (characteristic == pTxCharacteristic)
+    {
+        onTxAttempt(sent);
+    }
+    if (!sent)
+    {
+        Serial.println("BLE notification failed");
+    }
+    return sent;
+}
+
+bool BLEManager::canNotify() const
+{
+    return !notifyPaused || millis() - notifyPausedAt >= notifyPacingMs;
+}
+
+void BLEManager::onTxAttempt(bool sent)
+{
+    // A failed notify reports its status synchronously, so lastTxStatus is this attempt's
+    if (!sent && lastTxStatus == BLE_HS_ENOMEM)
+    {
+        notifyPacingMs = min((uint32_t)BLE_NOTIFY_PACING_MAX_MS, notifyPacingMs * 2);
+        notifyPaused = true;
+        notifyPausedAt = millis();
+        Serial.print("BLE controller out of buffers - pausing notifications for ");
+        Serial.print(notifyPacingMs);
+        Serial.println(" ms");
+        return;
+    }
+
+    if (sent)
+    {
+        notifyPaused = false;
+        notifyPacingMs = max((uint32_t)BLE_NOTIFY_PACING_MIN_MS, notifyPacingMs / 2);
+    }
+}
+
+void BLEManager::setBatteryLevel(uint8_t percent)
+{
+    if (pBatteryLevelCharacteristic == nullptr || percent == batteryLevel)
+    {
+        return;
+    }
+
+    batteryLevel = percent;
+    pBatteryLevelCharacteristic->setValue(&percent, 1);
+    if (deviceConnected)
+    {
+        pBatteryLevelCharacteristic->notify(); // No-op unless the phone subscribed
+    }
+    Serial.print("Battery level: ");
+    Serial.print(percent);
+    Serial.println("%");
+}
+
+void BLEManager::setLinkStats(const LinkStats &stats)
+{
+    // Compared serialized: the struct has padding
+    uint8_t value[LINK_STATS_LEN];
+    uint8_t last[LINK_STATS_LEN];
+    serializeLinkStats(stats, value);
+    serializeLinkStats(linkStats, last);
+    if (pStatsCharacteristic == nullptr || memcmp(value, last, LINK_STATS_LEN) == 0)
+    {
+        return;
+    }
+
+    linkStats = stats;
+    pStatsCharacteristic->setValue(value, LINK_STATS_LEN);
+    if (deviceConnected)
+    {
+        pStatsCharacteristic->notify(); // No-op unless the phone subscribed
+    }
+}
+
+bool BLEManager::sendControlResponse(const uint8_t *data, size_t length)
+{
+    if (!deviceConnected)
+    {
+        Serial.println("Cannot send control response: BLE not connected");
+        return false;
+    }
+
+    return sendFragmented(pControlCharacteristic, data, length, false);
+}
+
+void BLEManager::onControlReceived(const uint8_t *data, size_t length)
+{
+    Serial.print("Control command 0x");
+    Serial.print(data[0], HEX);
+    Serial.print(", ");
+    Serial.print(length - 1);
+    Serial.println(" arg bytes");
+
+    if (length - 1 > CONTROL_MAX_ARGS)
+    {
+        Serial.println("Control command arguments too long, ignoring");
+        return;
+    }
+
+    if (!isControlUnlocked())
+    {
+        Serial.println("Control command rejected - locked");
+        uint8_t response[2] = {CONTROL_LOCKED, data[0]};
+        sendControlResponse(response, sizeof(response));
+        return;
+    }
+    controlUnlockedAt = millis(); // Activity keeps the unlock alive
+
+    // Handled in the main loop, which owns the state the commands read
+    ControlCommand cmd;
+    cmd.opcode = data[0];
+    cmd.argLen = length - 1;
+    memcpy(cmd.args, data + 1, cmd.argLen);
+    if (xQueueSend(controlQueue, &cmd, 0) != pdTRUE)
+    {
+        Serial.println("Warning: control queue full, command dropped");
+    }
+}
+
+void BLEManager::onUnlockReceived(const uint8_t *data, size_t length)
+{
+    const char *passcode = BLE_CONTROL_PASSCODE;
+    size_t passcodeLen = strlen(passcode);
+    if (passcodeLen == 0)
+    {
+        return; // No lock configured
+    }
+
+    // Compare every byte so the time taken doesn't reveal the matching prefix
+    uint8_t diff = length == passcodeLen ? 0 : 1;
+    for (size_t i = 0; i < length && i < passcodeLen; i++)
+    {
+        diff |= data[i] ^ static_cast<uint8_t>(passcode[i]);
+    }
+
+    controlUnlocked = diff == 0;
+    controlUnlockedAt = millis();
+    Serial.println(controlUnlocked ? "Control characteristic unlocked" : "Wrong passcode - control characteristic locked");
+}
+
+bool BLEManager::isControlUnlocked() const
+{
+    if (strlen(BLE_CONTROL_PASSCODE) == 0)
+    {
+        return true;
+    }
+    return controlUnlocked && millis() - controlUnlockedAt < BLE_UNLOCK_TIMEOUT_MS;
+}
+
+void BLEManager::setLastRxMeta(const LastRxMeta &meta)
+{
+    portENTER_CRITICAL(&lastRxMetaLock);
+    lastRxMeta = meta;
+    portEXIT_CRITICAL(&lastRxMetaLock);
+}
+
+size_t BLEManager::readLastRxMeta(uint8_t *buf)
+{
+    portENTER_CRITICAL(&lastRxMetaLock);
+    LastRxMeta meta = lastRxMeta;
+    portEXIT_CRITICAL(&lastRxMetaLock);
+    return serializeLastRxMeta(meta, millis(), buf);
+}
+
+bool BLEManager::indicateAndWait(const uint8_t *data, size_t len)
+{
+    Serial.print("Sending ");
+    Serial.print(len);
+    Serial.println(" bytes via BLE indication");
+
+    // Drop any stale confirmation left over from an earlier timed-out indication
+    xSemaphoreTake(indicationSemaphore, 0);
+    indicationConfirmed = false;
+    awaitingIndication = true;
+
+    pTxCharacteristic->setValue(data, len);
+    lastTxStatus = 0;
+    if (!pTxCharacteristic->indicate())
+    {
+        awaitingIndication = false;
+        onTxAttempt(false);
+        Serial.println("Failed to start BLE indication");
+        return false;
+    }
+
+    bool signalled = xSemaphoreTake(indicationSemaphore, pdMS_TO_TICKS(BLE_INDICATION_TIMEOUT_MS)) == pdTRUE;
+    awaitingIndication = false;
+
+    if (!signalled)
+    {
+        Serial.print("Warning: indication not confirmed within ");
+        Serial.print(BLE_INDICATION_TIMEOUT_MS);
+        Serial.println("ms");
+        return false;
+    }
+
+    if (!indicationConfirmed)
+    {
+        Serial.println("Warning: indication aborted (client disconnected or refused)");
+        return false;
+    }
 
-    Serial.println("Message forwarded from LoRa to BLE via notification");
+    onTxAttempt(true);
+    Serial.println("Message forwarded from LoRa to BLE via indication (confirmed)");
     return true;
 }
 
+void BLEManager::onTxSubscribe(uint16_t subValue)
+{
+    // Bit 0 = notifications, bit 1 = indications (CCCD value)
+    indicationsEnabled = (subValue & 0x02) != 0;
+}
+
+void BLEManager::onTxStatus(int code)
+{
+    lastTxStatus = code;
+    if (!awaitingIndication)
+    {
+        return; // Notification status or late confirmation - nothing is waiting
+    }
+
+    // BLE_HS_EDONE means the central confirmed the indication
+    indicationConfirmed = (code == BLE_HS_EDONE);
+    xSemaphoreGive(indicationSemaphore);
+}
+
 void BLEManager::process()
 {
-    // Handle disconnection/reconnection
+    // Handle disconnection: schedule a debounced advertising restart
     if (!deviceConnected && oldDeviceConnected)
     {
-        delay(500); // give the bluetooth stack the chance to get things ready
-        startAdvertising();
-        Serial.println("Restarted advertising after disconnect");
-        oldDeviceConnected = deviceConnected;
+        oldDeviceConnected = false;
+        advertisingRestartPending = true;
     }
 
     // Handle connection
@@ -192,6 +746,44 @@ void BLEManager::process()
         Serial.println("Connection state updated");
     }
 
+    if (advertisingRestartPending)
+    {
+        unsigned long now = millis();
+        if (deviceConnected)
+        {
+            // Reconnected within the debounce window - nothing to restart
+            advertisingRestartPending = false;
+        }
+        else if (now - disconnectedAt >= BLE_READVERTISE_DEBOUNCE_MS &&
+                 now - lastAdvertisingStart >= BLE_ADVERTISING_RESTART_MIN_INTERVAL_MS &&
+                 advertisingBackoff.ready(now))
+        {
+            // A failed start sets the flag again and backs off
+            advertisingRestartPending = false;
+            if (startAdvertising())
+            {
+                Serial.println("Restarted advertising");
+            }
+        }
+    }
+
+#if BLE_IDLE_DISCONNECT_MS > 0
+    // Drop an idle central; onDisconnected() then schedules the advertising restart
+    if (deviceConnected && !idleDisconnectPending && millis() - lastGattActivityAt >= BLE_IDLE_DISCONNECT_MS)
+    {
+        Serial.print("No GATT activity for ");
+        Serial.print(BLE_IDLE_DISCONNECT_MS);
+        Serial.println(" ms - disconnecting idle client");
+        // 0x13 = remote user terminated connection, a clean disconnect for the central
+        idleDisconnectPending = pServer->disconnect(connHandle, 0x13);
+        if (!idleDisconnectPending)
+        {
+            Serial.println("Idle disconnect failed - trying again after another idle period");
+            lastGattActivityAt = millis();
+        }
+    }
+#endif
+
     // Note: Removed BLE advertising inactivity timeout
     // Requirement: Always able to receive LoRa messages and deliver to Android
     // Therefore, advertising must never stop automatically
@@ -241,11 +833,47 @@ void BLEManager::onMessageReceived(const uint8_t *data, size_t length)
         activityCallback();
     }
 
+    if (length > 0 && (data[0] == BLE_FRAGMENT_START || data[0] == BLE_FRAGMENT_CONTINUE))
+    {
+        if (!composeFragment(data, length))
+        {
+            return; // Waiting for more fragments (or fragment rejected)
+        }
+        Serial.print("Composed message from fragments, length: ");
+        Serial.println(composeExpected);
+        data = composeBuffer;
+        length = composeExpected;
+        composeExpected = 0;
+    }
+    else if (composeExpected > 0)
+    {
+        // A complete message in between means the composition was abandoned
+        Serial.println("Discarding unfinished BLE composition");
+        composeExpected = 0;
+    }
+
     Message msg;
     if (msg.deserialize(data, length))
     {
         Serial.print("Deserialized message type: ");
         Serial.println((int)msg.type);
+        if (msg.type == MessageType::Raw || msg.type == MessageType::Status ||
+            msg.type == MessageType::DecodeFailure || msg.type == MessageType::DeliveryReport ||
+            msg.type == MessageType::LinkQuality)
+        {
+            // Raw frames, status, decode failures and delivery and link quality reports only flow bridge -> app and are never transmitted
+            Serial.println("Ignoring raw, status, decode failure or report message from BLE");
+            return;
+        }
+        if (msg.type == MessageType::ChannelSwitch || msg.type == MessageType::RateSwitch ||
+            msg.type == MessageType::ListenSchedule || msg.type == MessageType::TrackStatus ||
+            msg.type == MessageType::HopAck || msg.type == MessageType::Nack || msg.type == MessageType::GpsDelta)
+        {
+            // Channel and rate switches, listen schedules, track statuses, hop ACKs, NACKs and GPS deltas are
+            // between nodes, never injected by the app
+            Serial.println("Ignoring bridge-to-bridge message from BLE");
+            return;
+        }
         // Send to queue instead of storing internally
         if (xQueueSend(bleToLoraQueue, &msg, 0) != pdTRUE)
         {
@@ -262,8 +890,64 @@ void BLEManager::onMessageReceived(const uint8_t *data, size_t length)
     }
 }
 
-void BLEManager::onConnected()
+bool BLEManager::composeFragment(const uint8_t *data, size_t length)
+{
+    unsigned long now = millis();
+    if (composeExpected > 0 && now - composeStartedAt > BLE_COMPOSE_TIMEOUT_MS)
+    {
+        Serial.println("Discarding abandoned BLE composition (timeout)");
+        composeExpected = 0;
+    }
+
+    size_t offset;
+    if (data[0] == BLE_FRAGMENT_START)
+    {
+        // A new start always replaces an unfinished composition
+        if (length < 2 || data[1] == 0 || data[1] > sizeof(composeBuffer))
+        {
+            Serial.println("Invalid fragment start - composition rejected");
+            composeExpected = 0;
+            return false;
+        }
+        composeExpected = data[1];
+        composeReceived = 0;
+        composeStartedAt = now;
+        offset = 2;
+    }
+    else
+    {
+        if (composeExpected == 0)
+        {
+            Serial.println("Fragment continuation without start - ignored");
+            return false;
+        }
+        offset = 1;
+    }
+
+    size_t chunk = length - offset;
+    if (composeReceived + chunk > composeExpected)
+    {
+        Serial.println("Fragment exceeds announced length - composition rejected");
+        composeExpected = 0;
+        return false;
+    }
+
+    memcpy(composeBuffer + composeReceived, data + offset, chunk);
+    composeReceived += chunk;
+    return composeReceived == composeExpected;
+}
+
+void BLEManager::onConnected(uint16_t handle)
 {
+    // Start every connection from a clean slate, whatever the previous one left behind
+    indicationsEnabled = false;
+    negotiatedMtu = BLE_DEFAULT_MTU;
+    notifyPaused = false;
+    notifyPacingMs = BLE_NOTIFY_PACING_MIN_MS;
+    connHandle = handle;
+    lastGattActivityAt = millis();
+    idleDisconnectPending = false;
+    connectionId++;
     deviceConnected = true;
 
     // Update activity callback if set
@@ -273,7 +957,35 @@ void BLEManager::onConnected()
     }
 }
 
+void BLEManager::onMtuChanged(uint16_t mtu)
+{
+    negotiatedMtu = mtu;
+    Serial.print("Negotiated MTU: ");
+    Serial.print(mtu);
+    if (mtu < BLE_REQUESTED_MTU)
+    {
+        Serial.print(" (below ");
+        Serial.print(BLE_REQUESTED_MTU);
+        Serial.print(", large messages will be fragmented)");
+    }
+    Serial.println();
+}
+
 void BLEManager::onDisconnected()
 {
+    composeExpected = 0;
+    controlUnlocked = false;
     deviceConnected = false;
+    disconnectedAt = millis();
+    indicationsEnabled = false;
+    negotiatedMtu = BLE_DEFAULT_MTU;
+
+    // Release a pending indication wait immediately instead of letting it time out
+    if (awaitingIndication)
+    {
+        indicationConfirmed = false;
+        xSemaphoreGive(indicationSemaphore);
+    }
 }
+
+#endif // !RELAY_ONLY
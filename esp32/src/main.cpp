@@ -10,43 +10,106 @@
 //! - Message buffering (up to 10 messages) when BLE disconnected
 //! - Light sleep for power optimization
 //! - Interrupt-driven LoRa reception (always listening)
+#include "bridge_config.h"
+#if !RELAY_ONLY // Relay-only builds use relay_main.cpp instead
 #include <Arduino.h>
 #include "lora_config.h"
+#include "lora_airtime.h"
 #include "LoRaManager.h"
 #include "BLEManager.h"
 #include "Protocol.h"
 #include "LEDManager.h"
 #include "MessageBuffer.h"
 #include "PowerManager.h"
+#include "Diagnostics.h"
+#include "ConfigBlob.h"
+#include "PersistedSettings.h"
+#include "ChannelMonitor.h"
+#include "AdaptiveDataRate.h"
+#include "BridgeCore.h"
+#include "AuditLog.h"
+#include "ListenWindows.h"
+#include "PeriodicListen.h"
+#include "Backoff.h"
+#include "DutyCycle.h"
 #include <freertos/queue.h>
 #include <esp_task_wdt.h>
 #include <freertos/task.h>
+#if LORA_RADIO == LORA_RADIO_SX127X
 #include <LoRa.h>
+#endif
 #include "Network.h"
 #include <esp_wifi.h>
+#include <Preferences.h>
 #include "esp_pm.h"
 
 // Manager objects
+#if LORA_RADIO == LORA_RADIO_SX1262
+static_assert(LORA_RX_POLLING, "The SX1262 backend reads frames in the loop: build with LORA_RX_POLLING 1");
+LoRaManager loraManager(LORA_SCK, LORA_MISO, LORA_MOSI, LORA_SS, LORA_RST, LORA_DIO1, LORA_BUSY, LORA_FREQUENCY);
+#else
 LoRaManager loraManager(LORA_SCK, LORA_MISO, LORA_MOSI, LORA_SS, LORA_RST, LORA_DIO0, LORA_FREQUENCY);
+#endif
 PowerManager powerManager;
 #ifdef LED_PIN
 LEDManager ledManager(LED_PIN);
 #endif
 
+// Node id in effect: NODE_ID until one is set at runtime, then the one saved in NVS
+uint8_t nodeId = NODE_ID;
+Preferences preferences;
+
+static_assert(LISTEN_WINDOWS == (RADIO_POWER_MODE == RADIO_POWER_DUTY_CYCLED_RX),
+              "LISTEN_WINDOWS and RADIO_POWER_DUTY_CYCLED_RX go together - set only RADIO_POWER_MODE");
+static_assert(RADIO_POWER_MODE == RADIO_POWER_ALWAYS_ON_RX || !CHANNEL_AGILITY,
+              "CHANNEL_AGILITY can't monitor a sleeping radio");
+static_assert(ADR_FASTER_MARGIN_DB > ADR_SLOWER_MARGIN_DB,
+              "ADR_FASTER_MARGIN_DB must exceed ADR_SLOWER_MARGIN_DB or ADR flaps between two factors");
+static_assert(!ADAPTIVE_DATA_RATE || loraSpreadingFactorSelectable(ADR_MIN_SPREADING_FACTOR),
+              "ADR_MIN_SPREADING_FACTOR must be a selectable spreading factor");
+static_assert(PERIODIC_RX_WINDOW_MS > 0, "RADIO_POWER_PERIODIC_RX needs a listen window");
+static_assert(LORA_ACK_SPREADING_FACTOR == 0 || (ACK_RELAY_HOPS == 0 && ACK_SEMANTICS == ACK_SEMANTICS_END_TO_END),
+              "Fast ACKs (LORA_ACK_SPREADING_FACTOR) don't cross relays, which listen at the data SF");
+static_assert(RETRANSMIT_STRATEGY != RETRANSMIT_SCHEDULED || LISTEN_WINDOWS,
+              "RETRANSMIT_SCHEDULED waits for listen windows - needs RADIO_POWER_DUTY_CYCLED_RX");
+static_assert(PRIORITY_NORMAL_RETRIES <= PRIORITY_HIGH_RETRIES && PRIORITY_HIGH_RETRIES <= PRIORITY_CRITICAL_RETRIES,
+              "Higher priorities get at least as many retries as lower ones");
+static_assert(!FIRE_AND_FORGET || (!TRACK_STATUS && ACK_SEMANTICS == ACK_SEMANTICS_END_TO_END),
+              "FIRE_AND_FORGET confirms nothing - no TRACK_STATUS or hop ACKs");
+
+#if STATUS_LED
+#ifndef LED_PIN
+#error "STATUS_LED needs an LED on LED_PIN"
+#endif
+// Shown by the status LED task, updated from setup and the loop
+volatile SystemState systemState = SystemState::Initializing;
+#endif
+
 // Message queues using FreeRTOS
 const int BLE_TO_LORA_QUEUE_SIZE = 10;
 const int LORA_TO_BLE_QUEUE_SIZE = 15;
 
 QueueHandle_t bleToLoraQueue;
+
+// Messages from the app waiting for the core, highest send priority first: the
+// loop empties bleToLoraQueue into it every pass, so a burst of texts held back
+// by the core (backlog full, STOP_AND_WAIT) never keeps a position or SOS from
+// the app waiting behind it. When full the lowest-priority message is dropped
+// (see MessageRing::addBySendPriority()).
+BoundedMessageBuffer<BLE_TO_LORA_QUEUE_SIZE> bleOutbox;
 QueueHandle_t loraToBleQueue;
+QueueHandle_t controlQueue;
 
 // Struct for LoRa packets with metadata
 struct LoRaPacket
 {
-    uint8_t buffer[256];
-    int len;
+    uint8_t buffer[LORA_MAX_PACKET_SIZE];
+    int frameLen; // Size reported by the radio
+    int len;      // Bytes actually read into buffer
     int rssi;
     float snr;
+    long frequencyError;      // Hz, sender's carrier relative to ours
+    unsigned long receivedAt; // millis() at the end of reception
 };
 
 QueueHandle_t loRaQueue;
@@ -54,14 +117,122 @@ QueueHandle_t loRaQueue;
 // BLEManager declared after queues
 BLEManager *bleManager;
 
-// Message buffer for when BLE is disconnected (SINGLE GLOBAL INSTANCE)
-MessageBuffer messageBuffer;
+// Message buffer for when BLE is disconnected, one queue per message kind (SINGLE GLOBAL INSTANCE)
+BleMessageBuffer messageBuffer;
+
+// Runtime counters reported by the diagnostics command
+BridgeStats stats = {};
+
+// Last AUDIT_LOG_CAPACITY message events, read page by page over BLE (RAM only)
+AuditLog auditLog;
+
+// ACK wait, derived from the modulation in applyModulationTimings()
+unsigned long ackTimeoutMs = 0;
+
+// Time on air of every transmission in the rolling duty-cycle window
+DutyCycle dutyCycle((unsigned long)LORA_DUTY_CYCLE_WINDOW_S * 1000,
+                    (unsigned long)loraDutyCycleBudgetMs(loraConfiguredAirtimeLimits()));
+
+// Last ConfigResponse, for the CONFIG_RESPONSE_MIN_INTERVAL_MS spacing
+bool configResponseSent = false;
+unsigned long lastConfigResponseAt = 0;
+
+unsigned long lastHeapLogAt = 0; // See HEAP_LOG_INTERVAL_MS
+unsigned long lastBatteryLevelAt = 0; // See BATTERY_LEVEL_INTERVAL_MS
+unsigned long lastLinkStatsAt = 0;    // See LINK_STATS_INTERVAL_MS
+
+static_assert(BATTERY_LEVEL_PERCENT <= 100, "BATTERY_LEVEL_PERCENT is a percentage (0-100)");
+
+#if LOSS_INJECTION
+// Percentage of frames discarded at the RX and TX boundaries (testing only)
+uint8_t rxLossPercent = LOSS_INJECTION_RX_PERCENT;
+uint8_t txLossPercent = LOSS_INJECTION_TX_PERCENT;
+
+/**
+ * @brief Decide with the hardware RNG whether to discard a frame
+ */
+bool injectLoss(uint8_t percent)
+{
+    return percent > 0 && esp_random() % 100 < percent;
+}
+#endif
+
+#if CHANNEL_AGILITY
+// Interference detection and channel switch handshake state
+ChannelMonitor channelMonitor(CHANNEL_MONITOR_WINDOW, CHANNEL_INTERFERENCE_DBM);
+unsigned long lastChannelSample = 0;
+unsigned long channelHoldoffStart = 0;
+bool channelHoldoff = false;
+bool channelSwitchProposed = false; // Initiator: waiting for Accept
+uint32_t proposedChannelHz = 0;
+unsigned long channelSwitchStart = 0;
+bool awaitingChannelConfirm = false; // Peer: retuned, waiting for Confirm on the new channel
+uint32_t previousChannelHz = 0;
+unsigned long lastFrameHeardAt = 0;
+#endif
+
+#if ADAPTIVE_DATA_RATE
+// Link margin watch and spreading factor switch handshake state
+AdrMonitor adrMonitor(ADR_WINDOW, ADR_FASTER_MARGIN_DB, ADR_SLOWER_MARGIN_DB, ADR_MISSED_ACKS);
+int adrConfiguredSf = LORA_SPREADING_FACTOR; // Slowest factor ADR returns to (build, NVS or opcode 0x0A)
+unsigned long adrHoldoffStart = 0;
+bool adrHoldoff = false;
+bool rateSwitchProposed = false; // Initiator: waiting for Accept
+uint8_t proposedSf = 0;
+unsigned long rateSwitchStart = 0;
+bool awaitingRateConfirm = false; // Peer: switched, waiting for Confirm at the new factor
+int previousSf = 0;
+uint16_t adrSeenAckTimeouts = 0; // stats.ackTimeouts already counted as missed ACKs
+unsigned long lastAdrFrameAt = 0;
+#endif
+
+#if LISTEN_WINDOWS
+// Synchronized listen windows: the radio sleeps outside them
+ListenWindows listenWindows(NODE_ID);
+uint32_t maxFrameAirtimeMs = 0; // Longest frame, must fit in a window
+uint32_t beaconAirtimeMs = 0;
+#endif
+
+#if RADIO_POWER_MODE == RADIO_POWER_PERIODIC_RX
+// Unsynchronized listen windows: the radio sleeps between them
+PeriodicListen periodicListen;
+#endif
+
+unsigned long lastLoRaTxAt = 0; // Until the ACK is due the radio stays in RX, at the ACK SF with fast ACKs
+
+// Consecutive LoRa RX errors, for the graduated radio recovery
+uint8_t rxErrorStreak = 0;
+bool radioReinitPending = false; // Full re-init failed, retried from the loop
+Backoff radioReinitBackoff(RX_ERROR_REINIT_BACKOFF_MIN_MS, RX_ERROR_REINIT_BACKOFF_MAX_MS);
 
 // Flag for LoRa activity (set in ISR, checked in loop)
 volatile bool loraActivity = false;
 
+// Task running loop(), notified by the ISR so an idle loop wakes for a frame at once
+TaskHandle_t loopTaskHandle = nullptr;
+
+#if !LORA_RX_POLLING
+/**
+ * @brief Read the frame the radio reported and its reception context
+ */
+void IRAM_ATTR readLoRaPacket(int packetSize, LoRaPacket &packet)
+{
+    packet.frameLen = packetSize;
+    packet.len = 0;
+
+    // Read exactly the reported frame, never past the buffer
+    while (packet.len < packetSize && packet.len < (int)sizeof(packet.buffer) && LoRa.available())
+    {
+        packet.buffer[packet.len++] = LoRa.read();
+    }
+    packet.rssi = LoRa.packetRssi();
+    packet.snr = LoRa.packetSnr();
+    packet.frequencyError = LoRa.packetFrequencyError();
+    packet.receivedAt = millis();
+}
+
 /**
- * @brief LoRa receive callback - handles incoming LoRa packets event-driven (ISR)
+ * @brief LoRa receive callback - handles incoming LoRa packets event-driven (ISR on DIO0)
  */
 void IRAM_ATTR onLoRaReceive(int packetSize)
 {
@@ -69,277 +240,2263 @@ void IRAM_ATTR onLoRaReceive(int packetSize)
         return;
 
     LoRaPacket packet;
-    packet.len = LoRa.readBytes(packet.buffer, sizeof(packet.buffer));
-    packet.rssi = LoRa.packetRssi();
-    packet.snr = LoRa.packetSnr();
+    readLoRaPacket(packetSize, packet);
 
-    if (packet.len > 0)
+    if (packet.frameLen > 0)
     {
         BaseType_t xHigherPriorityTaskWoken = pdFALSE;
         xQueueSendFromISR(loRaQueue, &packet, &xHigherPriorityTaskWoken);
-        loraActivity = true; // Wake up main loop
+        loraActivity = true;
+        vTaskNotifyGiveFromISR(loopTaskHandle, &xHigherPriorityTaskWoken); // Wake up main loop
         portYIELD_FROM_ISR(xHigherPriorityTaskWoken);
     }
 }
 
+#else
 /**
- * @brief Setup routine for ESP32 LoRa-BLE Bridge
+ * @brief Check the radio for a received frame (DIO0 not wired, or an SX1262) and queue it like the ISR
  */
-void setup()
+void pollLoRaReceive()
 {
-    Serial.begin(115200);
-    delay(2000);
-
-    Serial.println("Disabling WiFi and Bluetooth Classic for power savings...");
+    if (!loraManager.isRxDone())
+    {
+        return;
+    }
 
-    // Disable WiFi completely (saves ~50-80 mA)
-    // WiFi is initialized by default in ESP32 Arduino framework
-    esp_err_t err = esp_wifi_stop();
-    if (err == ESP_OK || err == ESP_ERR_WIFI_NOT_INIT) {
-        esp_wifi_deinit();
-        Serial.println("WiFi disabled successfully");
-    } else {
-        Serial.printf("WiFi stop failed: %d (may not be initialized)\n", err);
+    // Clears RxDone and reads the frame, leaving continuous RX
+    LoRaPacket packet;
+    packet.len = loraManager.readPacket(packet.buffer, sizeof(packet.buffer), packet.frameLen);
+    if (packet.frameLen > 0)
+    {
+        packet.rssi = loraManager.getPacketRssi();
+        packet.snr = loraManager.getPacketSnr();
+        packet.frequencyError = loraManager.getPacketFrequencyError();
+        packet.receivedAt = millis();
+        if (xQueueSend(loRaQueue, &packet, 0) != pdTRUE)
+        {
+            Serial.println("Warning: LoRa RX queue full, frame dropped");
+            stats.lastError = BridgeError::QueueFull;
+        }
     }
+    loraManager.startReceiveMode();
+}
+#endif
 
-    // Disable Bluetooth Classic (we only use BLE via NimBLE)
-    // Note: NimBLE doesn't use the classic Bluetooth stack
-    btStop();
-    Serial.println("Bluetooth Classic disabled (using NimBLE for BLE only)");
+/**
+ * @brief Modulation of data frames: lora_config.h at the spreading factor in effect
+ */
+LoRaModulation dataModulation(int spreadingFactor)
+{
+    LoRaModulation mod = loraConfiguredModulation();
+    mod.spreadingFactor = spreadingFactor;
+    return mod;
+}
 
-    // Set initial CPU frequency to match power management max
-    setCpuFrequencyMhz(80);
-    Serial.print("Initial CPU Frequency set to: ");
-    Serial.print(getCpuFrequencyMhz());
-    Serial.println(" MHz");
+LoRaModulation dataModulation()
+{
+    return dataModulation(loraManager.getDataSpreadingFactor());
+}
 
-    // Configure power management with automatic light sleep
-    // This enables dynamic frequency scaling and automatic light sleep
-    esp_pm_config_t pm_config = {
-        .max_freq_mhz = 80,          // Maximum CPU frequency when active
-        .min_freq_mhz = 10,          // Minimum 10 MHz when idle (reduced from 40)
-        .light_sleep_enable = true,  // Enable automatic light sleep
-    };
-    ESP_ERROR_CHECK(esp_pm_configure(&pm_config));
+// Longest frame this build puts on air: the largest message, plus the salt byte,
+// auth tag and FEC parity its options add
+const size_t WORST_CASE_FRAME_LEN = MAX_SERIALIZED_LEN
+#ifdef LORA_PAYLOAD_KEY
+                                    + PAYLOAD_SALT_LEN
+#endif
+#ifdef LORA_AUTH_KEY
+                                    + AUTH_TAG_LEN
+#endif
+                                    + LORA_FEC_PARITY;
 
-    Serial.print("Power management configured: max=");
-    Serial.print(pm_config.max_freq_mhz);
-    Serial.print(" MHz, min=");
-    Serial.print(pm_config.min_freq_mhz);
-    Serial.println(" MHz, light sleep enabled");
+// Transmissions of a worst-case message: once plus the most retries
+const int WORST_CASE_RETRIES = FIRE_AND_FORGET ? 0 : PRIORITY_CRITICAL_RETRIES;
 
-    // Initialize watchdog for robustness (30 second timeout)
-    esp_task_wdt_config_t wdt_config = {
-        .timeout_ms = 30000, // 30 seconds
-        .trigger_panic = true,
-    };
-    esp_task_wdt_init(&wdt_config);
-    esp_task_wdt_add(xTaskGetCurrentTaskHandle());
+/**
+ * @brief Whether a worst-case message (WORST_CASE_FRAME_LEN, WORST_CASE_RETRIES)
+ * fits the band's dwell time and duty cycle at a modulation
+ */
+bool worstCaseAirtimeLegal(const LoRaModulation &mod)
+{
+    LoRaAirtimeCheck check =
+        loraCheckAirtime(mod, WORST_CASE_FRAME_LEN, 1 + WORST_CASE_RETRIES, loraConfiguredAirtimeLimits());
+    return check == LoRaAirtimeCheck::Legal || check == LoRaAirtimeCheck::FewMessages;
+}
 
-    Serial.println("===================================");
-    Serial.println("ESP32 LoRa-BLE Bridge starting...");
-    Serial.println("===================================");
+/**
+ * @brief Node id and radio configuration of this bridge
+ */
+NodeInfo localNodeInfo()
+{
+    NodeInfo info;
+    info.nodeId = nodeId;
+    info.frequency = loraManager.getFrequency();
+    info.spreadingFactor = loraManager.getDataSpreadingFactor();
+    info.bandwidthIndex = loraBandwidthIndex(LORA_BANDWIDTH);
+    info.codingRate = LORA_CODING_RATE;
+    info.txPower = loraManager.getTxPower();
+    return info;
+}
 
-    // Create message queues
-    bleToLoraQueue = xQueueCreate(BLE_TO_LORA_QUEUE_SIZE, sizeof(Message));
-    loraToBleQueue = xQueueCreate(LORA_TO_BLE_QUEUE_SIZE, sizeof(Message));
-    loRaQueue = xQueueCreate(15, sizeof(LoRaPacket));
+/**
+ * @brief Node id saved in NVS by the node id command, or NODE_ID if none (or an invalid one) is saved
+ */
+uint8_t loadNodeId()
+{
+    preferences.begin("bridge", true);
+    uint8_t id = preferences.getUChar("node_id", NODE_ID);
+    preferences.end();
+    return isAssignableNodeId(id) ? id : NODE_ID;
+}
+
+/**
+ * @brief Save a node id in NVS, to be used from the next boot on
+ */
+bool saveNodeId(uint8_t id)
+{
+    preferences.begin("bridge", false);
+    bool saved = preferences.putUChar("node_id", id) == 1;
+    preferences.end();
+    return saved;
+}
 
-    if (bleToLoraQueue == nullptr || loraToBleQueue == nullptr || loRaQueue == nullptr)
+/**
+ * @brief Radio settings saved in NVS, or the build's defaults if none (first boot) or unusable ones are saved
+ */
+PersistedSettings loadPersistedSettings()
+{
+    uint8_t buf[PERSISTED_SETTINGS_LEN];
+    preferences.begin("bridge", true);
+    size_t len = preferences.getBytes("radio", buf, sizeof(buf)); // 0 if never saved
+    preferences.end();
+
+    PersistedSettings settings = defaultPersistedSettings();
+    if (len > 0 && !parsePersistedSettings(buf, len, settings))
     {
-        Serial.println("Failed to create message queues. Halting execution.");
-        while (1)
-        {
-            delay(1000);
-        }
+        Serial.println("Saved radio settings are corrupt or out of range - using the defaults");
     }
+    return settings;
+}
 
-    // Initialize BLE with queue
-    bleManager = new BLEManager(bleToLoraQueue);
+/**
+ * @brief Data spreading factor as configured (build, NVS or opcode 0x0A), without the steps ADR took
+ */
+int configuredSpreadingFactor()
+{
+#if ADAPTIVE_DATA_RATE
+    return adrConfiguredSf;
+#else
+    return loraManager.getDataSpreadingFactor();
+#endif
+}
 
-    // Initialize BLE with retry logic
-    const int BLE_RETRY_COUNT = 3;
-    int bleRetries = BLE_RETRY_COUNT;
-    bool bleSuccess = false;
+/**
+ * @brief Save the radio settings in effect in NVS, to be applied from the next boot on
+ */
+bool savePersistedSettings()
+{
+    PersistedSettings settings;
+    settings.frequency = loraManager.getFrequency();
+    settings.txPower = static_cast<int8_t>(loraManager.getTxPower());
+    settings.spreadingFactor = static_cast<uint8_t>(configuredSpreadingFactor());
+
+    uint8_t buf[PERSISTED_SETTINGS_LEN];
+    size_t len = serializePersistedSettings(settings, buf);
+    preferences.begin("bridge", false);
+    bool saved = preferences.putBytes("radio", buf, len) == len;
+    preferences.end();
+    if (!saved)
+    {
+        Serial.println("Failed to save the radio settings - they are lost on reboot");
+    }
+    return saved;
+}
 
-    while (bleRetries > 0 && !bleSuccess)
+/**
+ * @brief Check if a channel switch handshake is in progress (LoRa TX is held meanwhile)
+ */
+bool channelSwitchInProgress()
+{
+#if CHANNEL_AGILITY
+    return channelSwitchProposed || awaitingChannelConfirm;
+#else
+    return false;
+#endif
+}
+
+/**
+ * @brief Check if a spreading factor switch handshake is in progress (LoRa TX is held meanwhile)
+ */
+bool rateSwitchInProgress()
+{
+#if ADAPTIVE_DATA_RATE
+    return rateSwitchProposed || awaitingRateConfirm;
+#else
+    return false;
+#endif
+}
+
+/**
+ * @brief Radio, firmware and protocol configuration of this bridge, as sent in a ConfigResponse
+ */
+NodeConfig localNodeConfig()
+{
+    NodeConfig config;
+    config.radio = localNodeInfo();
+    config.syncWord = LORA_SYNC_WORD;
+    config.preambleLength = LORA_PREAMBLE_LENGTH;
+    config.firmwareMajor = FIRMWARE_VERSION_MAJOR;
+    config.firmwareMinor = FIRMWARE_VERSION_MINOR;
+    config.firmwarePatch = FIRMWARE_VERSION_PATCH;
+    config.protocolProfile = PROTOCOL_PROFILE;
+    config.charset = PROTOCOL_CHARSET;
+    return config;
+}
+
+/**
+ * @brief Complete configuration of this bridge, as exported by the config command
+ */
+BridgeConfig localConfig()
+{
+    BridgeConfig config;
+    config.node = localNodeInfo();
+    config.syncWord = LORA_SYNC_WORD;
+    config.rxBoost = loraManager.getRxBoost();
+    config.lnaGain = loraManager.getLnaGain();
+    config.buildFlags = configBuildFlags();
+    return config;
+}
+
+/**
+ * @brief Apply an imported configuration (frequency and RX front end; not persisted)
+ * @return False if it can't be applied to this build or radio; nothing is changed then.
+ */
+bool applyConfig(const BridgeConfig &config)
+{
+    if (!configCompatible(config, localConfig()) || !loraBandwidthValidFor(LORA_BANDWIDTH, config.node.frequency))
     {
-        Serial.print("BLE setup attempt ");
-        Serial.print(BLE_RETRY_COUNT - bleRetries + 1);
-        Serial.print("/");
-        Serial.println(BLE_RETRY_COUNT);
+        return false;
+    }
 
-        if (bleManager->setup(DEVICE_NAME))
-        {
-            bleSuccess = true;
-            Serial.println("BLE setup successful");
-        }
-        else
-        {
-            Serial.println("BLE setup failed");
-            if (bleRetries > 1)
-            {
-                Serial.println("Retrying in 2 seconds...");
-                delay(2000);
-            }
-            bleRetries--;
-        }
+    if (config.node.frequency != loraManager.getFrequency() && !loraManager.setFrequency(config.node.frequency))
+    {
+        return false;
     }
+    return loraManager.setRxBoost(config.rxBoost) && loraManager.setLnaGain(config.lnaGain);
+}
 
-    if (!bleSuccess)
+/**
+ * @brief Transmit a frame over LoRa (logged but not sent in NO_TX builds)
+ *
+ * Its time on air at the spreading factor in effect counts against the duty cycle.
+ * @return True if the frame was sent (always true with NO_TX).
+ */
+bool transmitFrame(const uint8_t *buf, size_t len)
+{
+#if NO_TX
+    Serial.print("NO_TX: would transmit ");
+    Serial.print(len);
+    Serial.print(" bytes: ");
+    for (size_t i = 0; i < len; i++)
     {
-        Serial.println("BLE setup failed permanently. Halting execution.");
-        while (1)
-        {
-            delay(1000);
-        }
+        Serial.printf("%02X ", buf[i]);
+    }
+    Serial.println();
+    return true;
+#else
+    dutyCycle.record(millis(), (unsigned long)ceil(loraTimeOnAirMs(
+                                   dataModulation(loraManager.getSpreadingFactor()), len)));
+    bool sent = loraManager.sendPacket(buf, len);
+    stats.loraTxFrames++;
+    if (!sent)
+    {
+        stats.loraTxFailures++;
+        stats.lastError = BridgeError::TxFailed;
     }
+    return sent;
+#endif
+}
 
-    bleManager->startAdvertising();
+/**
+ * @brief Return the radio to continuous RX right after a transmission
+ *
+ * Must be called immediately after transmitFrame() - before logging, LED blinks or
+ * power lock release - so a fast peer's ACK can't arrive while we're still in
+ * standby. Logs the measured re-arm time.
+ */
+void rearmReceiveAfterTx()
+{
+    unsigned long start = micros();
+    loraManager.startReceiveMode();
+    unsigned long rearmUs = micros() - start;
 
-    // Initialize LoRa
-    Serial.println("\nInitializing LoRa radio...");
-    Serial.println(loraManager.getConfigurationString());
+    delay(LORA_RX_SETTLE_MS);
 
-    const int LORA_RETRY_COUNT = 3;
-    int loraRetries = LORA_RETRY_COUNT;
-    bool loraSuccess = false;
+    Serial.print("TX->RX re-arm: ");
+    Serial.print(rearmUs);
+    Serial.println(" us");
+}
 
-    while (loraRetries > 0 && !loraSuccess)
+/**
+ * @brief RadioIo over the SX127x: TX under the power locks, then straight back to RX
+ */
+class LoRaRadio : public RadioIo
+{
+public:
+    bool send(const uint8_t *buf, size_t len) override
     {
-        Serial.print("LoRa setup attempt ");
-        Serial.print(LORA_RETRY_COUNT - loraRetries + 1);
-        Serial.print("/");
-        Serial.println(LORA_RETRY_COUNT);
-
-        if (loraManager.setup())
+#if LOSS_INJECTION
+        if (injectLoss(txLossPercent))
         {
-            loraSuccess = true;
-            Serial.println("LoRa setup successful");
+            Serial.println("Loss injection: TX frame discarded");
+            return true; // Lost on air as far as anyone can tell
         }
-        else
-        {
-            Serial.println("LoRa setup failed");
-            if (loraRetries > 1)
-            {
-                Serial.println("Retrying in 1 second...");
-                delay(1000);
-            }
-            loraRetries--;
+#endif
+
+        // Acquire high-power locks for transmission
+        powerManager.acquireForLoRaTx();
+
+#if LORA_ACK_SPREADING_FACTOR
+        // Fast ACKs: an ACK goes out at its own SF, everything else at the data SF
+        const uint8_t frameType = len > 0 ? buf[0] : 0;
+        loraManager.setSpreadingFactor(frameType == static_cast<uint8_t>(MessageType::Ack)
+                                           ? LORA_ACK_SPREADING_FACTOR
+                                           : loraManager.getDataSpreadingFactor());
+#endif
+
+        bool sent = transmitFrame(buf, len);
+
+#if LORA_ACK_SPREADING_FACTOR
+        // A text is ACKed at the ACK SF - listen there (updateAckListening() switches back)
+        loraManager.setSpreadingFactor(frameType == static_cast<uint8_t>(MessageType::Text)
+                                           ? LORA_ACK_SPREADING_FACTOR
+                                           : loraManager.getDataSpreadingFactor());
+#endif
+
+        // Re-arm RX before anything else so the peer's next frame isn't missed
+        rearmReceiveAfterTx();
+        lastLoRaTxAt = millis();
+
+        // Release power locks after transmission
+        powerManager.releaseAfterLoRaTx();
+        return sent;
+    }
+
+    bool isReceiving() override
+    {
+#if NO_TX
+        return false; // Nothing goes on air, so nothing to defer
+#else
+        return loraManager.isReceiving();
+#endif
+    }
+
+    bool detectChannelActivity() override
+    {
+#if NO_TX
+        return false;
+#else
+        return loraManager.detectChannelActivity();
+#endif
+    }
+
+    bool airtimeAvailable(size_t len) override
+    {
+#if LORA_ENFORCE_DUTY_CYCLE
+        return dutyCycle.allows(millis(), (unsigned long)ceil(loraTimeOnAirMs(dataModulation(), len)));
+#else
+        (void)len;
+        return true;
+#endif
+    }
+};
+
+/**
+ * @brief Queue a received message for BLE delivery, or buffer it while disconnected
+ *
+ * With ACK_POLICY_ALWAYS a full queue drops the oldest message of the same kind.
+ * The stricter policies never drop a message that was already ACKed, so a full
+ * queue rejects the new one instead.
+ * @return True if the message was stored.
+ */
+bool storeForBle(const Message &msg)
+{
+    if (bleManager->isConnected())
+    {
+        if (xQueueSend(loraToBleQueue, &msg, 0) == pdTRUE)
+        {
+            return true;
+        }
+        Serial.println("Warning: LoRa to BLE queue full, buffering");
+        stats.lastError = BridgeError::QueueFull;
+    }
+
+    if (messageBuffer.isFull(msg))
+    {
+#if ACK_POLICY != ACK_POLICY_ALWAYS
+        Serial.println("Warning: message buffer full for this kind, message not stored");
+        return false;
+#else
+        Serial.print("Warning: message buffer full for this kind, dropping one (drops: ");
+        Serial.print(messageBuffer.getDropped() + 1);
+        Serial.println(")");
+#endif
+    }
+
+    messageBuffer.addByPriority(msg);
+    Serial.print("Buffered message (total: ");
+    Serial.print(messageBuffer.getCount());
+    Serial.println(")");
+    return true;
+}
+
+/**
+ * @brief AppIo over BLE: the LoRa->BLE queue, or the message buffer while disconnected
+ */
+class BleApp : public AppIo
+{
+public:
+    bool deliver(const Message &msg) override { return storeForBle(msg); }
+    bool isConnected() const override { return bleManager->isConnected(); }
+
+    void status(BridgeStatus code, uint8_t seq) override
+    {
+        // The user always learns why a message is held for the duty cycle, the
+        // brief receive deferrals only with RADIO_BUSY_STATUS.
+        // Only meaningful right now - dropped rather than buffered while disconnected
+        if ((!RADIO_BUSY_STATUS && code != BridgeStatus::DutyCycleLimited) || !bleManager->isConnected())
+        {
+            return;
+        }
+        Message msg = Message::createStatus(code, seq);
+        if (xQueueSend(loraToBleQueue, &msg, 0) != pdTRUE)
+        {
+            Serial.println("Warning: LoRa to BLE queue full, status dropped");
+        }
+    }
+};
+
+LoRaRadio loraRadio;
+BleApp bleApp;
+
+// Send queue, ACK handling and retransmission (shared with the host tests)
+BridgeCore core(loraRadio, bleApp, stats, auditLog);
+
+/**
+ * @brief Derive the ACK wait and the listen window airtimes from the data modulation
+ *
+ * At boot and again whenever the spreading factor changes.
+ */
+void applyModulationTimings()
+{
+    // An end-to-end ACK may have to come back across relays; a hop ACK comes straight from the first one.
+    // Fast ACKs never cross relays and are timed at their own SF.
+    if (LORA_ACK_SPREADING_FACTOR != 0 || ACK_SEMANTICS == ACK_SEMANTICS_HOP)
+    {
+        ackTimeoutMs = loraAckTimeoutMs(loraAckModulation(dataModulation()));
+    }
+    else
+    {
+        ackTimeoutMs = loraRelayedAckTimeoutMs(dataModulation(), WORST_CASE_FRAME_LEN, ACK_RELAY_HOPS);
+    }
+    core.setAckTimeout(ackTimeoutMs);
+    Serial.print("ACK timeout: ");
+    Serial.print(ackTimeoutMs);
+    Serial.println(" ms");
+
+#if LISTEN_WINDOWS
+    maxFrameAirtimeMs = (uint32_t)ceil(loraTimeOnAirMs(dataModulation(), WORST_CASE_FRAME_LEN));
+    beaconAirtimeMs = (uint32_t)ceil(loraTimeOnAirMs(dataModulation(), LISTEN_SCHEDULE_LEN));
+    if (2 * LISTEN_GUARD_MS + maxFrameAirtimeMs + ackTimeoutMs > LISTEN_WINDOW_MS)
+    {
+        Serial.println("WARNING: LISTEN_WINDOW_MS can't hold a full frame and its ACK at this modulation");
+    }
+#endif
+}
+
+/**
+ * @brief Clean software restart, requested over BLE
+ *
+ * Nothing is persisted, so RAM state (LoRa send queue, disconnected buffer,
+ * audit log) is lost. The acknowledgement is given time to leave, the client is
+ * disconnected and the radio put to sleep so no frame is half-received, then
+ * the log is flushed before the restart.
+ */
+void rebootBridge()
+{
+    Serial.print("Reboot requested over BLE - dropping ");
+    Serial.print(messageBuffer.getCount());
+    Serial.println(" buffered messages");
+
+    delay(REBOOT_NOTIFY_DELAY_MS);
+    bleManager->disconnect();
+    loraManager.sleep();
+
+    Serial.println("Restarting...");
+    Serial.flush();
+    ESP.restart();
+}
+
+/**
+ * @brief Handle a command written to the BLE control characteristic
+ */
+void handleControlCommand(const ControlCommand &cmd)
+{
+    switch (static_cast<ControlOpcode>(cmd.opcode))
+    {
+    case ControlOpcode::Diagnostics:
+    {
+        DiagnosticsInfo info;
+        info.firmwareMajor = FIRMWARE_VERSION_MAJOR;
+        info.firmwareMinor = FIRMWARE_VERSION_MINOR;
+        info.firmwarePatch = FIRMWARE_VERSION_PATCH;
+        info.node = localNodeInfo();
+        info.stats = stats;
+        info.batteryPercent = BATTERY_UNKNOWN; // No battery sense on this board
+        info.peerCount = 0;                    // No peer table yet
+        info.dedupWindow = RX_DEDUP_ENTRIES;
+        info.uptimeSeconds = millis() / 1000;
+        info.bufferedMessages = messageBuffer.getCount();
+        info.bufferDrops = messageBuffer.getDropped();
+
+        uint8_t response[1 + DIAGNOSTICS_LEN];
+        response[0] = cmd.opcode | CONTROL_RESPONSE_FLAG;
+        size_t len = 1 + serializeDiagnostics(info, response + 1);
+
+        Serial.println("Sending diagnostics");
+        bleManager->sendControlResponse(response, len);
+        break;
+    }
+
+    case ControlOpcode::SpectrumScan:
+    {
+        uint32_t startHz = LORA_BAND_MIN_HZ;
+        uint32_t stopHz = LORA_BAND_MAX_HZ;
+        uint32_t stepHz = LORA_SCAN_DEFAULT_STEP_HZ;
+        if (cmd.argLen >= 12)
+        {
+            startHz = readU32(cmd.args);
+            stopHz = readU32(cmd.args + 4);
+            stepHz = readU32(cmd.args + 8);
+        }
+
+        // Retuning would miss the peer's ACK
+        int16_t rssi[LORA_SCAN_MAX_POINTS];
+        size_t points = 0;
+        if ((cmd.argLen == 0 || cmd.argLen >= 12) && !core.isAwaitingAck())
+        {
+            Serial.println("Starting spectrum scan...");
+            unsigned long scanStart = millis();
+            points = loraManager.scanSpectrum(startHz, stopHz, stepHz, rssi, LORA_SCAN_MAX_POINTS);
+            Serial.print("Spectrum scan: ");
+            Serial.print(points);
+            Serial.print(" points in ");
+            Serial.print(millis() - scanStart);
+            Serial.println(" ms");
+        }
+
+        if (points == 0)
+        {
+            Serial.println("Spectrum scan rejected (bad range or awaiting ACK)");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        // [0x82][first frequency u32][step u32][count u8][RSSI dBm i8 x count]
+        uint32_t firstHz = startHz < LORA_BAND_MIN_HZ ? LORA_BAND_MIN_HZ : startHz;
+        static_assert(10 + LORA_SCAN_MAX_POINTS <= BLE_MAX_FRAGMENTED_LEN,
+                      "a full spectrum scan response must fit the BLE fragment scheme");
+        uint8_t response[10 + LORA_SCAN_MAX_POINTS];
+        response[0] = cmd.opcode | CONTROL_RESPONSE_FLAG;
+        writeU32(response + 1, firstHz);
+        writeU32(response + 5, stepHz);
+        response[9] = points;
+        for (size_t i = 0; i < points; i++)
+        {
+            response[10 + i] = static_cast<uint8_t>(static_cast<int8_t>(rssi[i] < -128 ? -128 : rssi[i]));
+        }
+        bleManager->sendControlResponse(response, 10 + points);
+        break;
+    }
+
+    case ControlOpcode::RxFrontEnd:
+    {
+        // Gain 0 = AGC, 1-6 = fixed G1 (max) .. G6 (min)
+        bool valid = cmd.argLen == 0 ||
+                     (cmd.argLen == 2 && cmd.args[0] <= 1 && cmd.args[1] <= LORA_LNA_GAIN_MIN_SETTING);
+        if (valid && cmd.argLen == 2)
+        {
+            valid = loraManager.setRxBoost(cmd.args[0] == 1) && loraManager.setLnaGain(cmd.args[1]);
+        }
+
+        if (!valid)
+        {
+            Serial.println("RX front end change rejected");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        Serial.print("RX boost ");
+        Serial.print(loraManager.getRxBoost() ? "on" : "off");
+        Serial.print(", LNA gain ");
+        Serial.println(loraManager.getLnaGain());
+
+        // [0x83][rx boost u8][lna gain u8]
+        uint8_t response[3] = {static_cast<uint8_t>(cmd.opcode | CONTROL_RESPONSE_FLAG),
+                               loraManager.getRxBoost() ? (uint8_t)1 : (uint8_t)0, loraManager.getLnaGain()};
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+
+    case ControlOpcode::AuditLog:
+    {
+        uint32_t from = 0; // Oldest entry still held
+        if (cmd.argLen == 4)
+        {
+            from = readU32(cmd.args);
+        }
+        else if (cmd.argLen != 0)
+        {
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        // [0x84][total u32][first index u32][count u8][entries...]
+        static_assert(1 + 9 + AUDIT_PAGE_ENTRIES * AUDIT_ENTRY_LEN <= BLE_MAX_FRAGMENTED_LEN,
+                      "AUDIT_PAGE_ENTRIES too large for one BLE response");
+        uint8_t response[1 + 9 + AUDIT_PAGE_ENTRIES * AUDIT_ENTRY_LEN];
+        response[0] = cmd.opcode | CONTROL_RESPONSE_FLAG;
+        size_t len = 1 + auditLog.serializePage(from, AUDIT_PAGE_ENTRIES, response + 1);
+
+        Serial.print("Sending audit log page from entry ");
+        Serial.println(from);
+        bleManager->sendControlResponse(response, len);
+        break;
+    }
+
+    case ControlOpcode::Reboot:
+    {
+        if (cmd.argLen != sizeof(CONTROL_REBOOT_MAGIC) ||
+            memcmp(cmd.args, CONTROL_REBOOT_MAGIC, sizeof(CONTROL_REBOOT_MAGIC)) != 0)
+        {
+            Serial.println("Reboot rejected (missing confirmation)");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        uint8_t response[1] = {static_cast<uint8_t>(cmd.opcode | CONTROL_RESPONSE_FLAG)};
+        bleManager->sendControlResponse(response, sizeof(response));
+        rebootBridge();
+        break;
+    }
+
+    case ControlOpcode::Config:
+    {
+        // Retuning would miss the peer's ACK or break a channel or rate switch handshake
+        BridgeConfig imported;
+        bool valid = cmd.argLen == 0;
+        if (cmd.argLen > 0 && parseConfig(cmd.args, cmd.argLen, imported) && !core.isAwaitingAck() &&
+            !channelSwitchInProgress() && !rateSwitchInProgress())
+        {
+            valid = applyConfig(imported);
+        }
+
+        if (!valid)
+        {
+            Serial.println("Config import rejected (bad blob, other build, or busy)");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        if (cmd.argLen > 0)
+        {
+            Serial.print("Config imported: ");
+            Serial.print(loraManager.getFrequency());
+            Serial.println(" Hz");
+            savePersistedSettings();
+        }
+
+        // [0x87][config blob]
+        uint8_t response[1 + CONFIG_BLOB_LEN];
+        response[0] = cmd.opcode | CONTROL_RESPONSE_FLAG;
+        size_t len = 1 + serializeConfig(localConfig(), response + 1);
+        bleManager->sendControlResponse(response, len);
+        break;
+    }
+
+    case ControlOpcode::NodeId:
+    {
+        // Saved first, so the id in effect is always the one the next boot uses
+        if (cmd.argLen == 1 && isAssignableNodeId(cmd.args[0]) && saveNodeId(cmd.args[0]))
+        {
+            nodeId = cmd.args[0];
+            core.setNodeId(nodeId);
+#if LISTEN_WINDOWS
+            // Our id orders references and places our beacon slot - rejoin under the new one
+            listenWindows.setNodeId(nodeId);
+            listenWindows.begin(millis());
+#endif
+            Serial.print("Node id set to ");
+            Serial.println(nodeId);
+        }
+        else if (cmd.argLen != 0)
+        {
+            Serial.println("Node id rejected (reserved, broadcast, malformed, or not saved)");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        // [0x88][node id]
+        uint8_t response[2] = {static_cast<uint8_t>(cmd.opcode | CONTROL_RESPONSE_FLAG), nodeId};
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+
+    case ControlOpcode::SpreadingFactor:
+    {
+        // Retuning would miss the peer's ACK or break a channel or rate switch handshake
+        if (cmd.argLen == 1 && loraSpreadingFactorSelectable(cmd.args[0]) && !core.isAwaitingAck() &&
+            !channelSwitchInProgress() && !rateSwitchInProgress() &&
+            worstCaseAirtimeLegal(dataModulation(cmd.args[0])) && loraManager.setDataSpreadingFactor(cmd.args[0]))
+        {
+            Serial.print("Spreading factor set to SF");
+            Serial.print(loraManager.getDataSpreadingFactor());
+            Serial.println(" - the peer must switch too");
+            applyModulationTimings();
+#if ADAPTIVE_DATA_RATE
+            // The new factor is the slowest ADR may go; the old window says nothing about it
+            adrConfiguredSf = loraManager.getDataSpreadingFactor();
+            adrMonitor.reset();
+#endif
+            savePersistedSettings();
+        }
+        else if (cmd.argLen != 0)
+        {
+            Serial.println("Spreading factor rejected (out of range, illegal airtime, or busy)");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        // [0x8A][spreading factor]
+        uint8_t response[2] = {static_cast<uint8_t>(cmd.opcode | CONTROL_RESPONSE_FLAG),
+                               static_cast<uint8_t>(loraManager.getDataSpreadingFactor())};
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+
+    case ControlOpcode::RadioSettings:
+    {
+        // Both or neither: a valid frequency is not applied with a rejected TX power
+        bool valid = cmd.argLen == 0;
+        if (cmd.argLen == 5 && loraRadioSettingsValid(readU32(cmd.args), static_cast<int8_t>(cmd.args[4])) &&
+            !core.isAwaitingAck() && !channelSwitchInProgress() && !rateSwitchInProgress())
+        {
+            valid = loraManager.setFrequency(readU32(cmd.args)) &&
+                    loraManager.setTxPower(static_cast<int8_t>(cmd.args[4]));
+        }
+
+        if (!valid)
+        {
+            Serial.println("Radio settings rejected (out of range, outside the band, or busy)");
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        if (cmd.argLen > 0)
+        {
+            Serial.printf("Radio settings applied: %lu Hz, %d dBm - the peer must switch too\n",
+                          (unsigned long)loraManager.getFrequency(), loraManager.getTxPower());
+            savePersistedSettings();
+        }
+
+        // [0x8B][frequency u32][tx power i8]
+        uint8_t response[6];
+        response[0] = cmd.opcode | CONTROL_RESPONSE_FLAG;
+        writeU32(response + 1, loraManager.getFrequency());
+        response[5] = static_cast<uint8_t>(static_cast<int8_t>(loraManager.getTxPower()));
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+
+#if LOSS_INJECTION
+    case ControlOpcode::LossInjection:
+    {
+        if (cmd.argLen == 2 && cmd.args[0] <= 100 && cmd.args[1] <= 100)
+        {
+            rxLossPercent = cmd.args[0];
+            txLossPercent = cmd.args[1];
+        }
+        else if (cmd.argLen != 0)
+        {
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        Serial.print("Loss injection: RX ");
+        Serial.print(rxLossPercent);
+        Serial.print("%, TX ");
+        Serial.print(txLossPercent);
+        Serial.println("%");
+
+        // [0x86][rx loss %][tx loss %]
+        uint8_t response[3] = {static_cast<uint8_t>(cmd.opcode | CONTROL_RESPONSE_FLAG), rxLossPercent,
+                               txLossPercent};
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+#endif
+
+#if MESSAGE_INJECTION
+    case ControlOpcode::Inject:
+    {
+        Message msg;
+        if (!msg.deserialize(cmd.args, cmd.argLen))
+        {
+            uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+            bleManager->sendControlResponse(response, sizeof(response));
+            break;
+        }
+
+        bool stored = core.inject(msg);
+        Serial.print("Injected message type ");
+        Serial.print((int)msg.type);
+        Serial.println(stored ? " for BLE (bypassing the radio)" : " - not stored");
+
+        // [0x89][stored]
+        uint8_t response[2] = {static_cast<uint8_t>(cmd.opcode | CONTROL_RESPONSE_FLAG), stored ? (uint8_t)1 : (uint8_t)0};
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+#endif
+
+    default:
+    {
+        Serial.print("Unknown control opcode 0x");
+        Serial.println(cmd.opcode, HEX);
+        uint8_t response[2] = {CONTROL_ERROR, cmd.opcode};
+        bleManager->sendControlResponse(response, sizeof(response));
+        break;
+    }
+    }
+}
+
+#if STATUS_LED
+/**
+ * @brief Status LED task: drives the heartbeat pattern of the current system state
+ */
+void statusLedTask(void *)
+{
+    for (;;)
+    {
+        ledManager.showStatus(systemState);
+        vTaskDelay(pdMS_TO_TICKS(STATUS_LED_TICK_MS));
+    }
+}
+
+/**
+ * @brief Derive the system state from the radio and BLE link (radio errors first)
+ */
+void updateSystemState()
+{
+    bool radioError = radioReinitPending ||
+                      (RX_ERROR_RECONFIGURE_AFTER > 0 && rxErrorStreak >= RX_ERROR_RECONFIGURE_AFTER);
+    if (radioError)
+    {
+        systemState = SystemState::RadioError;
+    }
+    else
+    {
+        systemState = bleManager->isConnected() ? SystemState::Connected : SystemState::Idle;
+    }
+}
+#endif
+
+/**
+ * @brief Log current and peak heap use, free heap and the largest free block
+ *
+ * Peak use rising over days points at a leak; a largest free block far below
+ * the free heap points at fragmentation.
+ */
+void logHeapStats()
+{
+    HeapStats heap = readHeapStats();
+    Serial.printf("Heap: %u of %u bytes used (peak %u), %u free, largest free block %u\n",
+                  (unsigned)heap.usedBytes(), (unsigned)heap.totalBytes, (unsigned)heap.peakUsedBytes(),
+                  (unsigned)heap.freeBytes, (unsigned)heap.largestFreeBlock);
+    lastHeapLogAt = millis();
+}
+
+/**
+ * @brief Charge of the node's battery in percent
+ *
+ * No battery sense on the supported boards yet - the BATTERY_LEVEL_PERCENT
+ * placeholder stands in until an ADC reading replaces it.
+ */
+uint8_t readBatteryPercent()
+{
+    return BATTERY_LEVEL_PERCENT;
+}
+
+/**
+ * @brief Refresh the Battery Service level (notified to the phone when it changed)
+ */
+void updateBatteryLevel()
+{
+    bleManager->setBatteryLevel(readBatteryPercent());
+    lastBatteryLevelAt = millis();
+}
+
+/**
+ * @brief Refresh the stats characteristic (notified to the phone when a counter changed)
+ */
+void updateLinkStats()
+{
+    LinkStats link;
+    link.txFrames = stats.loraTxFrames;
+    link.rxFrames = stats.loraRxFrames;
+    link.crcFailures = stats.loraCrcFailures;
+    link.acksReceived = stats.acksReceived;
+    link.bufferDrops = messageBuffer.getDropped();
+    link.spreadingFactor = static_cast<uint8_t>(loraManager.getDataSpreadingFactor());
+    bleManager->setLinkStats(link);
+    lastLinkStatsAt = millis();
+}
+
+/**
+ * @brief Check the worst-case message (WORST_CASE_FRAME_LEN, sent with the most
+ * retries) against the band's dwell time and duty cycle and log the result
+ * @return False if such a message can't be sent legally at this modulation.
+ */
+bool checkAirtimeLimits()
+{
+    LoRaModulation mod = dataModulation();
+    LoRaAirtimeLimits limits = loraConfiguredAirtimeLimits();
+    const int retries = WORST_CASE_RETRIES;
+    const int attempts = 1 + retries;
+    uint32_t airtimeMs = (uint32_t)ceil(loraTimeOnAirMs(mod, WORST_CASE_FRAME_LEN));
+
+    Serial.print("Worst-case frame: ");
+    Serial.print(WORST_CASE_FRAME_LEN);
+    Serial.print(" bytes, ");
+    Serial.print(airtimeMs);
+    Serial.print(" ms on air, ");
+    Serial.print((uint32_t)(loraDutyCycleBudgetMs(limits) / airtimeMs));
+    Serial.print(" per ");
+    Serial.print(limits.windowS);
+    Serial.print(" s at ");
+    Serial.print(limits.dutyCyclePercent);
+    Serial.println("% duty cycle");
+
+    LoRaAirtimeCheck check = loraCheckAirtime(mod, WORST_CASE_FRAME_LEN, attempts, limits);
+    switch (check)
+    {
+    case LoRaAirtimeCheck::Legal:
+        return true;
+    case LoRaAirtimeCheck::FewMessages:
+        Serial.print("WARNING: fewer than ");
+        Serial.print(limits.minMessages);
+        Serial.println(" worst-case messages fit the duty cycle - expect long TX holds");
+        break;
+    case LoRaAirtimeCheck::DutyCycleExceeded:
+        Serial.print("WARNING: a worst-case message and its ");
+        Serial.print(retries);
+        Serial.println(" retries exceed the duty cycle - it can't be sent legally");
+        break;
+    case LoRaAirtimeCheck::DwellExceeded:
+        Serial.print("WARNING: a worst-case frame exceeds the band's ");
+        Serial.print(limits.maxDwellMs);
+        Serial.println(" ms dwell time - it can't be sent legally");
+        break;
+    }
+
+    int legalSf = loraHighestLegalSf(mod, WORST_CASE_FRAME_LEN, attempts, limits);
+    if (legalSf > 0)
+    {
+        Serial.print("  Use SF");
+        Serial.print(legalSf);
+        Serial.println(" or lower at this bandwidth (LORA_SPREADING_FACTOR), or a wider LORA_BANDWIDTH");
+    }
+    else
+    {
+        Serial.println("  Even SF7 doesn't fit - use a wider LORA_BANDWIDTH");
+    }
+    return check == LoRaAirtimeCheck::FewMessages;
+}
+
+/**
+ * @brief Minimum spacing of ConfigResponse replies
+ *
+ * CONFIG_RESPONSE_MIN_INTERVAL_MS, or longer if a reply repeated that often would
+ * exceed the band's duty cycle at the configured modulation.
+ */
+unsigned long configResponseIntervalMs()
+{
+    uint32_t airtimeMs = (uint32_t)ceil(loraTimeOnAirMs(dataModulation(), CONFIG_RESPONSE_LEN));
+    unsigned long dutyCycleMs = (unsigned long)airtimeMs * 100 / LORA_DUTY_CYCLE_PERCENT;
+    return dutyCycleMs > CONFIG_RESPONSE_MIN_INTERVAL_MS ? dutyCycleMs : CONFIG_RESPONSE_MIN_INTERVAL_MS;
+}
+
+/**
+ * @brief Transmit this bridge's configuration as a ConfigResponse
+ *
+ * Used for replies and the startup beacon, which share the
+ * configResponseIntervalMs() spacing.
+ */
+bool sendConfigResponse()
+{
+    Message reply = Message::createConfigResponse(localNodeConfig());
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = core.encode(reply, buf, sizeof(buf));
+    if (len <= 0)
+    {
+        Serial.println("Failed to serialize config response");
+        return false;
+    }
+
+    unsigned long now = millis();
+    bool sent = loraRadio.send(buf, len);
+    configResponseSent = true;
+    lastConfigResponseAt = now;
+    auditLog.record(now, AuditDirection::Tx, MessageType::ConfigResponse, 0,
+                    sent ? AuditOutcome::Sent : AuditOutcome::TxFailed);
+    return sent;
+}
+
+/**
+ * @brief Handle a ConfigRequest: reply with the full configuration of this bridge
+ *
+ * Requests addressed to another node are ignored, and so are requests arriving
+ * within configResponseIntervalMs() of the last reply.
+ */
+void handleConfigRequest(const Message &msg)
+{
+    uint8_t target = msg.configRequestData.targetNode;
+    if (target != nodeId && target != BROADCAST_NODE_ID)
+    {
+        Serial.print("Config request for node ");
+        Serial.print(target);
+        Serial.println(" - not us, ignoring");
+        return;
+    }
+
+    if (configResponseSent && millis() - lastConfigResponseAt < configResponseIntervalMs())
+    {
+        Serial.println("Config request too soon after the last response - ignoring");
+        return;
+    }
+
+    Serial.print("Config request for this node (");
+    Serial.print(nodeId);
+    Serial.println(")");
+
+    Serial.println(sendConfigResponse() ? "Config response sent" : "Config response send failed");
+}
+
+#if STARTUP_BEACON
+/**
+ * @brief Announce this bridge once after boot with an unsolicited ConfigResponse
+ *
+ * Skipped after a crash or watchdog reset, so a node stuck in a reboot loop
+ * doesn't transmit on every boot.
+ */
+void sendStartupBeacon()
+{
+    esp_reset_reason_t reason = esp_reset_reason();
+    if (reason == ESP_RST_PANIC || reason == ESP_RST_INT_WDT || reason == ESP_RST_TASK_WDT || reason == ESP_RST_WDT)
+    {
+        Serial.println("Startup beacon skipped after a crash or watchdog reset");
+        return;
+    }
+
+    Serial.print("Startup beacon: announcing node ");
+    Serial.println(nodeId);
+    Serial.println(sendConfigResponse() ? "Startup beacon sent" : "Startup beacon send failed");
+}
+#endif
+
+/**
+ * @brief Setup routine for ESP32 LoRa-BLE Bridge
+ */
+void setup()
+{
+    Serial.begin(115200);
+    delay(2000);
+
+    // loop() runs in this task too
+    loopTaskHandle = xTaskGetCurrentTaskHandle();
+
+    Serial.println("Disabling WiFi and Bluetooth Classic for power savings...");
+
+    // Disable WiFi completely (saves ~50-80 mA)
+    // WiFi is initialized by default in ESP32 Arduino framework
+    esp_err_t err = esp_wifi_stop();
+    if (err == ESP_OK || err == ESP_ERR_WIFI_NOT_INIT) {
+        esp_wifi_deinit();
+        Serial.println("WiFi disabled successfully");
+    } else {
+        Serial.printf("WiFi stop failed: %d (may not be initialized)\n", err);
+    }
+
+    // Disable Bluetooth Classic (we only use BLE via NimBLE)
+    // Note: NimBLE doesn't use the classic Bluetooth stack
+    btStop();
+    Serial.println("Bluetooth Classic disabled (using NimBLE for BLE only)");
+
+    // Set initial CPU frequency to match power management max
+    setCpuFrequencyMhz(80);
+    Serial.print("Initial CPU Frequency set to: ");
+    Serial.print(getCpuFrequencyMhz());
+    Serial.println(" MHz");
+
+    // Configure power management with automatic light sleep
+    // This enables dynamic frequency scaling and automatic light sleep
+    esp_pm_config_t pm_config = {
+        .max_freq_mhz = 80,          // Maximum CPU frequency when active
+        .min_freq_mhz = 10,          // Minimum 10 MHz when idle (reduced from 40)
+        .light_sleep_enable = true,  // Enable automatic light sleep
+    };
+    ESP_ERROR_CHECK(esp_pm_configure(&pm_config));
+
+    Serial.print("Power management configured: max=");
+    Serial.print(pm_config.max_freq_mhz);
+    Serial.print(" MHz, min=");
+    Serial.print(pm_config.min_freq_mhz);
+    Serial.println(" MHz, light sleep enabled");
+
+    // Initialize watchdog for robustness (30 second timeout)
+    esp_task_wdt_config_t wdt_config = {
+        .timeout_ms = 30000, // 30 seconds
+        .trigger_panic = true,
+    };
+    esp_task_wdt_init(&wdt_config);
+    esp_task_wdt_add(xTaskGetCurrentTaskHandle());
+
+#if STATUS_LED
+    // Solid until initialization completes
+    ledManager.setup();
+    xTaskCreate(statusLedTask, "statusLed", 2048, nullptr, 1, nullptr);
+#endif
+
+    Serial.println("===================================");
+    Serial.println("ESP32 LoRa-BLE Bridge starting...");
+    Serial.println("===================================");
+
+    // Create message queues
+    bleToLoraQueue = xQueueCreate(BLE_TO_LORA_QUEUE_SIZE, sizeof(Message));
+    loraToBleQueue = xQueueCreate(LORA_TO_BLE_QUEUE_SIZE, sizeof(Message));
+    loRaQueue = xQueueCreate(15, sizeof(LoRaPacket));
+    controlQueue = xQueueCreate(4, sizeof(ControlCommand));
+
+    if (bleToLoraQueue == nullptr || loraToBleQueue == nullptr || loRaQueue == nullptr || controlQueue == nullptr)
+    {
+        Serial.println("Failed to create message queues. Halting execution.");
+        while (1)
+        {
+            delay(1000);
+        }
+    }
+
+    nodeId = loadNodeId();
+    core.setNodeId(nodeId);
+    Serial.print("Node id: ");
+    Serial.print(nodeId);
+    Serial.println(nodeId == NODE_ID ? "" : " (set at runtime, saved in NVS)");
+    core.setRandomSeed(esp_random());
+#if LBT_CAD_ATTEMPTS
+    Serial.print("Listen before talk: up to ");
+    Serial.print(LBT_CAD_ATTEMPTS);
+    Serial.println(" CADs per transmission");
+#endif
+
+    // Initialize BLE with queue
+    bleManager = new BLEManager(bleToLoraQueue, controlQueue);
+
+    // Initialize BLE with retry logic
+    const int BLE_RETRY_COUNT = 3;
+    int bleRetries = BLE_RETRY_COUNT;
+    bool bleSuccess = false;
+
+    while (bleRetries > 0 && !bleSuccess)
+    {
+        Serial.print("BLE setup attempt ");
+        Serial.print(BLE_RETRY_COUNT - bleRetries + 1);
+        Serial.print("/");
+        Serial.println(BLE_RETRY_COUNT);
+
+        if (bleManager->setup(DEVICE_NAME))
+        {
+            bleSuccess = true;
+            Serial.println("BLE setup successful");
+        }
+        else
+        {
+            Serial.println("BLE setup failed");
+            if (bleRetries > 1)
+            {
+                Serial.println("Retrying in 2 seconds...");
+                delay(2000);
+            }
+            bleRetries--;
+        }
+    }
+
+    if (!bleSuccess)
+    {
+        Serial.println("BLE setup failed permanently. Halting execution.");
+        while (1)
+        {
+            delay(1000);
+        }
+    }
+
+    bleManager->startAdvertising();
+
+    // Initialize LoRa
+    Serial.println("\nInitializing LoRa radio...");
+    Serial.println(loraManager.getConfigurationString());
+
+    const int LORA_RETRY_COUNT = 3;
+    int loraRetries = LORA_RETRY_COUNT;
+    bool loraSuccess = false;
+
+    while (loraRetries > 0 && !loraSuccess)
+    {
+        Serial.print("LoRa setup attempt ");
+        Serial.print(LORA_RETRY_COUNT - loraRetries + 1);
+        Serial.print("/");
+        Serial.println(LORA_RETRY_COUNT);
+
+        if (loraManager.setup())
+        {
+            loraSuccess = true;
+            Serial.println("LoRa setup successful");
+        }
+        else
+        {
+            Serial.println("LoRa setup failed");
+            if (loraRetries > 1)
+            {
+                Serial.println("Retrying in 1 second...");
+                delay(1000);
+            }
+            loraRetries--;
+        }
+    }
+
+#if NO_TX
+    if (!loraSuccess)
+    {
+        // No frames are ever sent, so the BLE side stays usable without a radio
+        Serial.println("LoRa setup failed - continuing without radio (NO_TX build)");
+        loraSuccess = true;
+    }
+    Serial.println("WARNING: NO_TX build - LoRa transmissions are logged, not sent");
+#endif
+
+#if LOSS_INJECTION
+    Serial.print("WARNING: loss injection build - discarding ");
+    Serial.print(rxLossPercent);
+    Serial.print("% of RX and ");
+    Serial.print(txLossPercent);
+    Serial.println("% of TX frames");
+#endif
+
+#if MESSAGE_INJECTION
+    Serial.println("WARNING: message injection build - control opcode 0x09 fakes received messages");
+#endif
+
+    if (!loraSuccess)
+    {
+#if STATUS_LED
+        systemState = SystemState::RadioError;
+#endif
+        Serial.println("LoRa setup failed permanently. Halting execution.");
+        while (1)
+        {
+            delay(1000);
+        }
+    }
+
+    // Settings changed over BLE before the last reboot (opcodes 0x0A and 0x0B)
+    PersistedSettings saved = loadPersistedSettings();
+    if (saved.frequency != LORA_FREQUENCY || saved.txPower != LORA_TX_POWER_CONDUCTED_DBM ||
+        saved.spreadingFactor != LORA_SPREADING_FACTOR)
+    {
+        bool applied = loraManager.setFrequency(saved.frequency) && loraManager.setTxPower(saved.txPower);
+        if (worstCaseAirtimeLegal(dataModulation(saved.spreadingFactor)))
+        {
+            applied = loraManager.setDataSpreadingFactor(saved.spreadingFactor) && applied;
+        }
+        Serial.printf("Radio settings from NVS: %lu Hz, %d dBm, SF%d%s\n", (unsigned long)loraManager.getFrequency(),
+                      loraManager.getTxPower(), loraManager.getDataSpreadingFactor(),
+                      applied ? "" : " (partly rejected, defaults kept)");
+    }
+#if ADAPTIVE_DATA_RATE
+    adrConfiguredSf = loraManager.getDataSpreadingFactor();
+    lastAdrFrameAt = millis();
+    Serial.printf("Adaptive data rate: SF%d to SF%d\n", ADR_MIN_SPREADING_FACTOR, adrConfiguredSf);
+#endif
+
+    applyModulationTimings();
+#if LORA_ACK_SPREADING_FACTOR
+    Serial.print("Fast ACKs at SF");
+    Serial.print(LORA_ACK_SPREADING_FACTOR);
+    Serial.println(" - peers must use the same LORA_ACK_SPREADING_FACTOR");
+#endif
+#if FIRE_AND_FORGET
+    Serial.println("Fire-and-forget: no ACKs sent or awaited, every text sent once");
+#elif RETRANSMIT_STRATEGY == RETRANSMIT_IMMEDIATE
+    Serial.println("Retransmissions: after every ACK timeout");
+#elif RETRANSMIT_STRATEGY == RETRANSMIT_BACKOFF
+    Serial.print("Retransmissions: backing off, up to ");
+    Serial.print(RETRANSMIT_BACKOFF_MAX_MS);
+    Serial.println(" ms apart");
+#else
+    Serial.println("Retransmissions: at the start of the next listen window");
+#endif
+
+    if (!checkAirtimeLimits() && LORA_REFUSE_ILLEGAL_AIRTIME)
+    {
+        Serial.println("Modulation can't send a worst-case message legally (LORA_REFUSE_ILLEGAL_AIRTIME). Halting execution.");
+        while (1)
+        {
+            delay(1000);
+        }
+    }
+
+#if LISTEN_WINDOWS
+    listenWindows.setNodeId(nodeId);
+    listenWindows.begin(millis());
+    Serial.print("Listen windows: ");
+    Serial.print(LISTEN_WINDOW_MS);
+    Serial.print(" ms every ");
+    Serial.print(LISTEN_PERIOD_MS);
+    Serial.println(" ms - searching for a network");
+#elif RADIO_POWER_MODE == RADIO_POWER_SLEEP_BETWEEN_OPS
+    Serial.println("Radio power: asleep between operations - frames are only heard while an ACK is due");
+#elif RADIO_POWER_MODE == RADIO_POWER_PERIODIC_RX
+    periodicListen.begin(millis());
+    Serial.printf("Radio power: listening %lu ms, then asleep %lu ms (RX %u%% of the time)\n",
+                  (unsigned long)PERIODIC_RX_WINDOW_MS, (unsigned long)PERIODIC_RX_SLEEP_MS,
+                  (unsigned)PeriodicListen::listenPercent());
+#endif
+
+#if LORA_RX_POLLING
+    // No DIO0: the loop checks the RxDone flag itself
+    loraManager.startReceiveMode();
+    Serial.print("LoRa RX: polling RxDone every ");
+    Serial.print(LORA_RX_POLL_MS);
+    Serial.println(" ms (LORA_RX_POLLING, DIO0 unused)");
+#else
+    // Set up event-driven LoRa reception (CRITICAL: Always listening)
+    LoRa.onReceive(onLoRaReceive);
+
+    // Start continuous receive mode
+    loraManager.startReceiveMode();
+
+    // Configure GPIO wake-up for LoRa interrupt (allows wake from light sleep)
+    gpio_wakeup_enable((gpio_num_t)LORA_DIO0, GPIO_INTR_HIGH_LEVEL);
+    esp_sleep_enable_gpio_wakeup();
+    Serial.print("LoRa RX: interrupt-driven on DIO0 (GPIO ");
+    Serial.print(LORA_DIO0);
+    Serial.println("), wakes the loop and light sleep");
+#endif
+
+#if STARTUP_BEACON
+    sendStartupBeacon();
+#endif
+
+    // Initialize LED
+#if defined(LED_PIN) && !STATUS_LED
+    ledManager.setup();
+#endif
+#if STATUS_LED
+    updateSystemState();
+#endif
+
+    logHeapStats();
+    updateBatteryLevel();
+    updateLinkStats();
+
+    Serial.println("\n===================================");
+    Serial.println("All systems initialized successfully");
+    Serial.println("System running - waiting for connections...");
+    Serial.println("===================================\n");
+}
+
+/**
+ * @brief Handle LoRa to BLE message forwarding and buffering
+ */
+void handleLoRaToBleForwarding()
+{
+    // Send buffered messages if BLE just connected
+    static uint32_t seenConnectionId = 0;
+    static unsigned long connectTime = 0;
+    if (bleManager->isConnected() && !messageBuffer.isEmpty())
+    {
+        if (bleManager->getConnectionId() != seenConnectionId)
+        {
+            // New connection - also catches a disconnect/reconnect between two loop passes
+            seenConnectionId = bleManager->getConnectionId();
+            connectTime = millis();
+            Serial.println("BLE connected - waiting before sending buffered messages...");
+        }
+        // Wait at least 2s after connection before sending buffered messages
+        if (millis() - connectTime < 2000)
+        {
+            return;
+        }
+        Serial.print("BLE connected - sending ");
+        Serial.print(messageBuffer.getCount());
+        Serial.println(" buffered messages");
+
+#if BLE_COALESCE_NOTIFICATIONS
+        Message batch[BLE_BATCH_MAX_MESSAGES];
+        while (!messageBuffer.isEmpty())
+        {
+            size_t batchCount = 0;
+            while (batchCount < BLE_BATCH_MAX_MESSAGES && messageBuffer.peekAt(batchCount, batch[batchCount]))
+            {
+                batchCount++;
+            }
+
+            size_t delivered = bleManager->sendBatch(batch, batchCount);
+            if (delivered == 0)
+            {
+                Serial.println("Failed to send buffered messages, keeping them for next attempt");
+                break; // Stop if send fails
+            }
+
+            Message bufferedMsg;
+            for (size_t i = 0; i < delivered; i++)
+            {
+                messageBuffer.get(bufferedMsg); // Delivered - remove from buffer
+            }
+#ifdef LED_PIN
+            ledManager.blink();
+#endif
+            delay(bleManager->getNotifyPacingMs()); // Paced to the controller's buffers
+        }
+#else
+        Message bufferedMsg;
+        while (messageBuffer.peek(bufferedMsg))
+        {
+            if (bleManager->sendMessage(bufferedMsg))
+            {
+                messageBuffer.get(bufferedMsg); // Delivered - remove from buffer
+                Serial.println("Buffered message sent successfully");
+#ifdef LED_PIN
+                ledManager.blink();
+#endif
+                delay(bleManager->getNotifyPacingMs()); // Paced to the controller's buffers
+            }
+            else
+            {
+                Serial.println("Failed to send buffered message, keeping it for next attempt");
+                break; // Stop if send fails
+            }
+        }
+#endif
+    }
+
+    // Process live queue messages (left in the queue while the controller is out of buffers)
+    Message loraMsg;
+    if ((!bleManager->isConnected() || bleManager->canNotify()) && xQueueReceive(loraToBleQueue, &loraMsg, 0) == pdTRUE)
+    {
+        if (bleManager->isConnected())
+        {
+            if (bleManager->sendMessage(loraMsg))
+            {
+                Serial.println("Message forwarded from LoRa to BLE");
+#ifdef LED_PIN
+                ledManager.blink();
+#endif
+            }
+            else if (loraMsg.type == MessageType::Raw || loraMsg.type == MessageType::Status ||
+                     loraMsg.type == MessageType::DecodeFailure)
+            {
+                Serial.println("Warning: BLE delivery failed, raw frame or status dropped");
+                stats.lastError = BridgeError::BleDeliveryFailed;
+            }
+            else
+            {
+                // Unconfirmed or failed delivery - re-queue for the next connection
+                messageBuffer.addByPriority(loraMsg);
+                Serial.println("Warning: BLE delivery failed, message re-buffered");
+                stats.lastError = BridgeError::BleDeliveryFailed;
+            }
+        }
+        else if (loraMsg.type == MessageType::Raw || loraMsg.type == MessageType::Status ||
+                 loraMsg.type == MessageType::DecodeFailure)
+        {
+            Serial.println("Raw frame or status dropped (BLE disconnected)");
+        }
+        else
+        {
+            // Buffer message for later delivery
+            messageBuffer.addByPriority(loraMsg);
+            Serial.print("Buffered message (total: ");
+            Serial.print(messageBuffer.getCount());
+            Serial.println(")");
+        }
+    }
+}
+
+#if PROMISCUOUS_MODE || UNKNOWN_TYPE_POLICY != UNKNOWN_TYPE_DROP
+/**
+ * @brief Forward an unparseable LoRa frame to BLE as a Raw message
+ *
+ * Raw frames are never ACKed and are dropped while BLE is disconnected so
+ * foreign channel traffic can't evict buffered messages.
+ */
+void forwardRawFrame(const LoRaPacket &packet)
+{
+    if (!bleManager->isConnected())
+    {
+        Serial.println("Raw frame dropped (BLE not connected)");
+        return;
+    }
+
+    Message raw = Message::createRaw(packet.buffer, packet.len);
+    if (xQueueSend(loraToBleQueue, &raw, 0) != pdTRUE)
+    {
+        Serial.println("Warning: LoRa to BLE queue full, raw frame dropped");
+        stats.lastError = BridgeError::QueueFull;
+        return;
+    }
+
+    Serial.println("Unparseable frame forwarded to BLE as raw");
+}
+#endif
+
+/**
+ * @brief Report a dropped LoRa frame to BLE with the reason (DEBUG_DECODE_FAILURES)
+ *
+ * Like raw frames, reports are never ACKed or buffered: they are dropped while
+ * BLE is disconnected.
+ */
+void reportDecodeFailure(const LoRaPacket &packet, DecodeFailureReason reason)
+{
+#if DEBUG_DECODE_FAILURES
+    if (!bleManager->isConnected())
+    {
+        return;
+    }
+
+    Message report = Message::createDecodeFailure(reason, packet.buffer, packet.len, packet.frameLen);
+    if (xQueueSend(loraToBleQueue, &report, 0) != pdTRUE)
+    {
+        Serial.println("Warning: LoRa to BLE queue full, decode failure report dropped");
+        stats.lastError = BridgeError::QueueFull;
+        return;
+    }
+
+    Serial.print("Decode failure reported to BLE (reason ");
+    Serial.print(static_cast<int>(reason));
+    Serial.println(")");
+#else
+    (void)packet;
+    (void)reason;
+#endif
+}
+
+/**
+ * @brief Apply UNKNOWN_TYPE_POLICY to a frame whose type byte this firmware doesn't know
+ */
+void handleUnknownType(const LoRaPacket &packet)
+{
+    Serial.print("Unknown message type 0x");
+    Serial.println(packet.buffer[0], HEX);
+
+#if PROMISCUOUS_MODE || UNKNOWN_TYPE_POLICY != UNKNOWN_TYPE_DROP
+    forwardRawFrame(packet);
+#else
+    reportDecodeFailure(packet, DecodeFailureReason::UnknownType);
+#endif
+
+#if UNKNOWN_TYPE_POLICY == UNKNOWN_TYPE_RELAY
+    if (core.relay(packet.buffer, packet.len, millis()))
+    {
+        Serial.println("Unknown frame queued for relay");
+    }
+#endif
+}
+
+/**
+ * @brief Handle an Identify request: blink rapidly and reply with node id and radio config
+ *
+ * Requests addressed to another node are ignored. The reply is sent even on
+ * boards without LED_PIN so the operator still learns the node's config.
+ */
+void handleIdentify(const Message &msg)
+{
+    uint8_t target = msg.identifyData.targetNode;
+    if (target != nodeId && target != BROADCAST_NODE_ID)
+    {
+        Serial.print("Identify for node ");
+        Serial.print(target);
+        Serial.println(" - not us, ignoring");
+        return;
+    }
+
+    Serial.print("Identify request for this node (");
+    Serial.print(nodeId);
+    Serial.println(")");
+
+#ifdef LED_PIN
+    ledManager.startIdentify(IDENTIFY_DURATION_MS);
+#else
+    Serial.println("No LED_PIN configured - replying without visual signal");
+#endif
+
+    Message reply = Message::createIdentifyResponse(localNodeInfo());
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = core.encode(reply, buf, sizeof(buf));
+    if (len <= 0)
+    {
+        Serial.println("Failed to serialize identify response");
+        return;
+    }
+
+    bool sent = loraRadio.send(buf, len);
+    auditLog.record(millis(), AuditDirection::Tx, MessageType::IdentifyResponse, 0,
+                    sent ? AuditOutcome::Sent : AuditOutcome::TxFailed);
+    Serial.println(sent ? "Identify response sent" : "Identify response send failed");
+}
+
+#if CHANNEL_AGILITY
+/**
+ * @brief Transmit one step of the channel switch handshake on the current channel
+ */
+void sendChannelSwitch(uint32_t frequency, ChannelSwitchStage stage)
+{
+    Message msg = Message::createChannelSwitch(frequency, stage);
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = core.encode(msg, buf, sizeof(buf));
+    if (len <= 0)
+    {
+        return;
+    }
+
+    loraRadio.send(buf, len);
+}
+
+/**
+ * @brief Start the hold-off after any switch attempt so the bridge can't flap between channels
+ */
+void startChannelHoldoff()
+{
+    channelMonitor.reset();
+    channelHoldoff = true;
+    channelHoldoffStart = millis();
+}
+
+/**
+ * @brief Sample the channel during idle RX and propose a switch on persistent interference
+ */
+void monitorChannel()
+{
+    unsigned long now = millis();
+
+    if (channelSwitchProposed)
+    {
+        if (now - channelSwitchStart >= ackTimeoutMs)
+        {
+            // Peer didn't answer (or doesn't support agility) - the link stays where it is
+            Serial.println("Channel switch not accepted by peer - staying on current channel");
+            channelSwitchProposed = false;
+        }
+        return;
+    }
+
+    if (awaitingChannelConfirm)
+    {
+        if (now - channelSwitchStart >= 2 * ackTimeoutMs)
+        {
+            // The initiator never arrived (our Accept was probably lost) - go back
+            Serial.println("No channel switch confirmation - returning to previous channel");
+            loraManager.setFrequency(previousChannelHz);
+            awaitingChannelConfirm = false;
+        }
+        return;
+    }
+
+    if (loraManager.getFrequency() != LORA_FREQUENCY && now - lastFrameHeardAt >= CHANNEL_SILENCE_FALLBACK_MS)
+    {
+        // Both ends do this, so a split link always meets again on the home channel
+        Serial.println("Nothing heard on this channel - returning to LORA_FREQUENCY");
+        loraManager.setFrequency(LORA_FREQUENCY);
+        lastFrameHeardAt = now;
+        startChannelHoldoff();
+        return;
+    }
+
+    if (channelHoldoff && now - channelHoldoffStart < CHANNEL_SWITCH_HOLDOFF_MS)
+    {
+        return;
+    }
+    channelHoldoff = false;
+
+    if (core.isAwaitingAck() || rateSwitchInProgress() || now - lastChannelSample < CHANNEL_MONITOR_INTERVAL_MS)
+    {
+        return;
+    }
+    lastChannelSample = now;
+    channelMonitor.addSample(loraManager.sampleRssiFloor());
+
+    if (!channelMonitor.interferenceDetected())
+    {
+        return;
+    }
+
+    int16_t currentFloor = channelMonitor.averageFloor();
+    Serial.print("Persistent interference detected (floor ");
+    Serial.print(currentFloor);
+    Serial.println(" dBm) - scanning for a quieter channel");
+
+    int16_t rssi[LORA_SCAN_MAX_POINTS];
+    size_t points = loraManager.scanSpectrum(LORA_BAND_MIN_HZ, LORA_BAND_MAX_HZ, LORA_SCAN_DEFAULT_STEP_HZ,
+                                             rssi, LORA_SCAN_MAX_POINTS);
+    startChannelHoldoff();
+
+    uint32_t bestHz = 0;
+    int16_t bestRssi = 0;
+    for (size_t i = 0; i < points; i++)
+    {
+        uint32_t hz = LORA_BAND_MIN_HZ + i * LORA_SCAN_DEFAULT_STEP_HZ;
+        if (loraBandwidthValidFor(LORA_BANDWIDTH, hz) && (bestHz == 0 || rssi[i] < bestRssi))
+        {
+            bestHz = hz;
+            bestRssi = rssi[i];
+        }
+    }
+
+    if (bestHz == 0 || bestRssi > currentFloor - CHANNEL_SWITCH_MIN_GAIN_DB)
+    {
+        Serial.println("No sufficiently quieter channel found - staying");
+        return;
+    }
+
+    Serial.print("Proposing switch to ");
+    Serial.print(bestHz);
+    Serial.print(" Hz (floor ");
+    Serial.print(bestRssi);
+    Serial.println(" dBm)");
+    sendChannelSwitch(bestHz, ChannelSwitchStage::Propose);
+    channelSwitchProposed = true;
+    proposedChannelHz = bestHz;
+    channelSwitchStart = now;
+}
+#endif
+
+/**
+ * @brief Handle a channel switch handshake frame from the peer
+ *
+ * Propose: answer Accept on the current channel, retune and wait for Confirm.
+ * Accept:  retune to the proposed channel and send Confirm there.
+ * Confirm: the switch is complete.
+ */
+void handleChannelSwitch(const ChannelSwitchMessage &sw)
+{
+#if CHANNEL_AGILITY
+    switch (sw.stage)
+    {
+    case ChannelSwitchStage::Propose:
+        if (!loraBandwidthValidFor(LORA_BANDWIDTH, sw.frequency))
+        {
+            Serial.println("Channel switch to invalid frequency ignored");
+            return;
+        }
+        Serial.print("Peer proposes channel ");
+        Serial.print(sw.frequency);
+        Serial.println(" Hz - accepting");
+        channelSwitchProposed = false; // The peer's proposal wins over our own
+        sendChannelSwitch(sw.frequency, ChannelSwitchStage::Accept);
+        previousChannelHz = loraManager.getFrequency();
+        loraManager.setFrequency(sw.frequency);
+        awaitingChannelConfirm = true;
+        channelSwitchStart = millis();
+        startChannelHoldoff();
+        break;
+
+    case ChannelSwitchStage::Accept:
+        if (!channelSwitchProposed || sw.frequency != proposedChannelHz)
+        {
+            return;
+        }
+        channelSwitchProposed = false;
+        loraManager.setFrequency(sw.frequency);
+        sendChannelSwitch(sw.frequency, ChannelSwitchStage::Confirm);
+        Serial.print("Switched to channel ");
+        Serial.print(sw.frequency);
+        Serial.println(" Hz");
+        break;
+
+    case ChannelSwitchStage::Confirm:
+        if (awaitingChannelConfirm && sw.frequency == loraManager.getFrequency())
+        {
+            awaitingChannelConfirm = false;
+            Serial.print("Channel switch to ");
+            Serial.print(sw.frequency);
+            Serial.println(" Hz confirmed");
+        }
+        break;
+    }
+#else
+    Serial.println("Channel switch request ignored (CHANNEL_AGILITY disabled)");
+#endif
+}
+
+#if ADAPTIVE_DATA_RATE
+/**
+ * @brief Transmit one step of the rate switch handshake at the current spreading factor
+ */
+void sendRateSwitch(uint8_t spreadingFactor, ChannelSwitchStage stage)
+{
+    Message msg = Message::createRateSwitch(spreadingFactor, stage);
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = core.encode(msg, buf, sizeof(buf));
+    if (len <= 0)
+    {
+        return;
+    }
+
+    loraRadio.send(buf, len);
+}
+
+/**
+ * @brief Retune the data spreading factor and everything timed by it
+ */
+void switchDataRate(int spreadingFactor)
+{
+    if (loraManager.setDataSpreadingFactor(spreadingFactor))
+    {
+        applyModulationTimings();
+    }
+}
+
+/**
+ * @brief Start the hold-off after any switch attempt so the bridge can't flap between factors
+ */
+void startAdrHoldoff()
+{
+    adrMonitor.reset();
+    adrHoldoff = true;
+    adrHoldoffStart = millis();
+}
+
+/**
+ * @brief Check if the peer may be asked to use a spreading factor: within ADR's range and legal airtime
+ */
+bool adrSpreadingFactorAllowed(int spreadingFactor)
+{
+    return spreadingFactor >= ADR_MIN_SPREADING_FACTOR && spreadingFactor <= adrConfiguredSf &&
+           loraSpreadingFactorSelectable(spreadingFactor) && worstCaseAirtimeLegal(dataModulation(spreadingFactor));
+}
+
+/**
+ * @brief Follow the SNR of the peer's frames and propose a faster or slower spreading factor
+ */
+void monitorDataRate()
+{
+    unsigned long now = millis();
+
+    if (rateSwitchProposed)
+    {
+        if (now - rateSwitchStart >= ackTimeoutMs)
+        {
+            // Peer didn't answer (or doesn't run ADR) - the link stays where it is
+            Serial.println("Rate switch not accepted by peer - staying at current spreading factor");
+            rateSwitchProposed = false;
+        }
+        return;
+    }
+
+    if (awaitingRateConfirm)
+    {
+        if (now - rateSwitchStart >= 2 * ackTimeoutMs)
+        {
+            // The initiator never arrived (our Accept was probably lost) - go back
+            Serial.println("No rate switch confirmation - returning to previous spreading factor");
+            switchDataRate(previousSf);
+            awaitingRateConfirm = false;
+        }
+        return;
+    }
+
+    int spreadingFactor = loraManager.getDataSpreadingFactor();
+    if (spreadingFactor != adrConfiguredSf && now - lastAdrFrameAt >= ADR_SILENCE_FALLBACK_MS)
+    {
+        // Both ends do this, so a split link always meets again at the configured factor
+        Serial.println("Nothing heard at this spreading factor - returning to the configured one");
+        switchDataRate(adrConfiguredSf);
+        lastAdrFrameAt = now;
+        startAdrHoldoff();
+        return;
+    }
+
+    // Lost frames leave no SNR sample, only ACK timeouts
+    for (; adrSeenAckTimeouts != stats.ackTimeouts; adrSeenAckTimeouts++)
+    {
+        adrMonitor.addMissedAck();
+    }
+
+    if (adrHoldoff && now - adrHoldoffStart < ADR_HOLDOFF_MS)
+    {
+        return;
+    }
+    adrHoldoff = false;
+
+    if (core.isAwaitingAck() || channelSwitchInProgress())
+    {
+        return;
+    }
+
+    AdrDecision decision = adrMonitor.decide(spreadingFactor, ADR_MIN_SPREADING_FACTOR, adrConfiguredSf);
+    if (decision == AdrDecision::Hold)
+    {
+        return;
+    }
+
+    int target = decision == AdrDecision::Faster ? spreadingFactor - 1 : spreadingFactor + 1;
+    if (!adrSpreadingFactorAllowed(target))
+    {
+        startAdrHoldoff();
+        return;
+    }
+
+    Serial.printf("Link SNR %.1f dB average, %.1f dB worst at SF%d - proposing SF%d\n", adrMonitor.averageSnr(),
+                  adrMonitor.worstSnr(), spreadingFactor, target);
+    sendRateSwitch(static_cast<uint8_t>(target), ChannelSwitchStage::Propose);
+    rateSwitchProposed = true;
+    proposedSf = static_cast<uint8_t>(target);
+    rateSwitchStart = now;
+    startAdrHoldoff();
+}
+#endif
+
+/**
+ * @brief Handle a rate switch handshake frame from the peer
+ *
+ * Propose: answer Accept at the current factor, switch and wait for Confirm.
+ * Accept:  switch to the proposed factor and send Confirm at it.
+ * Confirm: the switch is complete.
+ */
+void handleRateSwitch(const RateSwitchMessage &sw)
+{
+#if ADAPTIVE_DATA_RATE
+    switch (sw.stage)
+    {
+    case ChannelSwitchStage::Propose:
+        if (!adrSpreadingFactorAllowed(sw.spreadingFactor) || awaitingRateConfirm || channelSwitchInProgress())
+        {
+            Serial.println("Rate switch outside our range, or while busy, ignored");
+            return;
+        }
+        Serial.print("Peer proposes SF");
+        Serial.print(sw.spreadingFactor);
+        Serial.println(" - accepting");
+        rateSwitchProposed = false; // The peer's proposal wins over our own
+        sendRateSwitch(sw.spreadingFactor, ChannelSwitchStage::Accept);
+        previousSf = loraManager.getDataSpreadingFactor();
+        switchDataRate(sw.spreadingFactor);
+        awaitingRateConfirm = true;
+        rateSwitchStart = millis();
+        startAdrHoldoff();
+        break;
+
+    case ChannelSwitchStage::Accept:
+        if (!rateSwitchProposed || sw.spreadingFactor != proposedSf)
+        {
+            return;
+        }
+        rateSwitchProposed = false;
+        switchDataRate(sw.spreadingFactor);
+        sendRateSwitch(sw.spreadingFactor, ChannelSwitchStage::Confirm);
+        Serial.print("Switched to SF");
+        Serial.println(sw.spreadingFactor);
+        break;
+
+    case ChannelSwitchStage::Confirm:
+        if (awaitingRateConfirm && sw.spreadingFactor == loraManager.getDataSpreadingFactor())
+        {
+            awaitingRateConfirm = false;
+            Serial.print("Rate switch to SF");
+            Serial.print(sw.spreadingFactor);
+            Serial.println(" confirmed");
+        }
+        break;
+    }
+#else
+    Serial.println("Rate switch request ignored (ADAPTIVE_DATA_RATE disabled)");
+#endif
+}
+
+#if RADIO_POWER_MODE != RADIO_POWER_ALWAYS_ON_RX
+/**
+ * @brief Put the radio to sleep unless it still has something to receive
+ *
+ * It stays on while a frame is arriving, a received frame is waiting to be
+ * processed, or an ACK for our last transmission is due.
+ */
+void sleepRadioWhenIdle(unsigned long now)
+{
+    bool ackDue = core.isAwaitingAck() && now - lastLoRaTxAt < ackTimeoutMs;
+    if (!loraManager.isAsleep() && !ackDue && !loraManager.isReceiving() && uxQueueMessagesWaiting(loRaQueue) == 0)
+    {
+        loraManager.sleep();
+    }
+}
+#endif
+
+#if LISTEN_WINDOWS
+/**
+ * @brief Transmit our listen schedule so peers can align their windows to ours
+ */
+void sendListenBeacon(unsigned long now)
+{
+    Message msg = Message::createListenSchedule(listenWindows.beacon(now));
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = core.encode(msg, buf, sizeof(buf));
+    if (len <= 0)
+    {
+        return;
+    }
+
+    bool sent = loraRadio.send(buf, len);
+    auditLog.record(now, AuditDirection::Tx, MessageType::ListenSchedule, 0,
+                    sent ? AuditOutcome::Sent : AuditOutcome::TxFailed);
+    Serial.print("Listen schedule beacon sent (reference node ");
+    Serial.print(listenWindows.referenceId());
+    Serial.println(")");
+}
+
+/**
+ * @brief Wake the radio for each listen window and put it to sleep after
+ *
+ * The radio stays on past the window as long as sleepRadioWhenIdle() keeps it.
+ */
+void updateListenWindow()
+{
+    unsigned long now = millis();
+    bool wasSearching = listenWindows.isSearching();
+    listenWindows.update(now);
+    if (listenWindows.isSearching() != wasSearching)
+    {
+        Serial.println(wasSearching ? "Listen windows: no network heard - using our own schedule"
+                                    : "Listen windows: sync lost - searching for the network");
+    }
+
+    if (listenWindows.isOpen(now))
+    {
+        if (loraManager.isAsleep())
+        {
+            loraManager.startReceiveMode();
+        }
+        if (listenWindows.beaconDue(now, beaconAirtimeMs))
+        {
+            sendListenBeacon(now);
         }
+        return;
     }
 
-    if (!loraSuccess)
+    sleepRadioWhenIdle(now);
+}
+#endif
+
+#if RADIO_POWER_MODE == RADIO_POWER_PERIODIC_RX
+/**
+ * @brief Wake the radio for each periodic listen window and put it to sleep after
+ *
+ * As with the synchronized windows, sleepRadioWhenIdle() keeps it on past the
+ * window while a frame is arriving or an ACK is due.
+ */
+void updatePeriodicListen()
+{
+    unsigned long now = millis();
+    if (periodicListen.isOpen(now))
     {
-        Serial.println("LoRa setup failed permanently. Halting execution.");
-        while (1)
+        if (loraManager.isAsleep())
         {
-            delay(1000);
+            loraManager.startReceiveMode();
         }
+        return;
     }
 
-    // Set up event-driven LoRa reception (CRITICAL: Always listening)
-    LoRa.onReceive(onLoRaReceive);
-
-    // Start continuous receive mode
-    loraManager.startReceiveMode();
+    sleepRadioWhenIdle(now);
+}
+#endif
 
-    // Configure GPIO wake-up for LoRa interrupt (allows wake from light sleep)
-    gpio_wakeup_enable((gpio_num_t)LORA_DIO0, GPIO_INTR_HIGH_LEVEL);
-    esp_sleep_enable_gpio_wakeup();
-    Serial.println("GPIO wake-up enabled for LoRa DIO0 - can wake from light sleep");
+#if LORA_ACK_SPREADING_FACTOR
+/**
+ * @brief Listen at the ACK spreading factor while an ACK is due, at the data SF otherwise
+ *
+ * Never cuts off a frame being received or wakes a sleeping radio - the next
+ * transmission sets the SF it needs itself.
+ */
+void updateAckListening()
+{
+    bool ackDue = core.isAwaitingAck() && millis() - lastLoRaTxAt < ackTimeoutMs;
+    int sf = ackDue ? LORA_ACK_SPREADING_FACTOR : loraManager.getDataSpreadingFactor();
+    if (sf != loraManager.getSpreadingFactor() && !loraManager.isAsleep() && !loraManager.isReceiving())
+    {
+        loraManager.setSpreadingFactor(sf);
+        loraManager.startReceiveMode();
+    }
+}
+#endif
 
-    // Initialize LED
-#ifdef LED_PIN
-    ledManager.setup();
+/**
+ * @brief Apply RADIO_POWER_MODE: wake or sleep the radio between operations
+ *
+ * Transmissions wake a sleeping radio themselves (LoRaRadio::send() leaves it in RX).
+ */
+void updateRadioPower()
+{
+#if RADIO_POWER_MODE == RADIO_POWER_DUTY_CYCLED_RX
+    // Also beacons our schedule
+    updateListenWindow();
+#elif RADIO_POWER_MODE == RADIO_POWER_SLEEP_BETWEEN_OPS
+    sleepRadioWhenIdle(millis());
+#elif RADIO_POWER_MODE == RADIO_POWER_PERIODIC_RX
+    updatePeriodicListen();
 #endif
+}
 
-    Serial.println("\n===================================");
-    Serial.println("All systems initialized successfully");
-    Serial.println("System running - waiting for connections...");
-    Serial.println("===================================\n");
+/**
+ * @brief Whether a message may go on air now (only inside the listen window when enabled)
+ */
+bool listenWindowAllowsTx()
+{
+#if LISTEN_WINDOWS
+    return listenWindows.canTransmit(millis(), maxFrameAirtimeMs);
+#else
+    return true;
+#endif
 }
 
 /**
- * @brief Handle LoRa to BLE message forwarding and buffering
+ * @brief Align our listen windows to a peer's beacon
+ *
+ * The frame started on air one airtime before it was received; the offset it
+ * carries then gives when the sender's window opened on our clock.
  */
-void handleLoRaToBleForwarding()
+void handleListenSchedule(const ListenScheduleMessage &schedule, const LoRaPacket &packet)
 {
-    // Send buffered messages if BLE just connected
-    static bool justConnected = false;
-    static unsigned long connectTime = 0;
-    if (bleManager->isConnected() && !messageBuffer.isEmpty())
+#if LISTEN_WINDOWS
+    unsigned long frameStart =
+        packet.receivedAt - (unsigned long)loraTimeOnAirMs(dataModulation(), packet.len);
+    switch (listenWindows.onBeacon(schedule, frameStart, millis()))
     {
-        if (!justConnected)
-        {
-            // First time BLE is connected since last disconnect
-            justConnected = true;
-            connectTime = millis();
-            Serial.println("BLE connected - waiting before sending buffered messages...");
-        }
-        // Wait at least 2s after connection before sending buffered messages
-        if (millis() - connectTime < 2000)
-        {
-            return;
-        }
-        Serial.print("BLE connected - sending ");
-        Serial.print(messageBuffer.getCount());
-        Serial.println(" buffered messages");
-
-        Message bufferedMsg;
-        while (messageBuffer.get(bufferedMsg))
-        {
-            if (bleManager->sendMessage(bufferedMsg))
-            {
-                Serial.println("Buffered message sent successfully");
-#ifdef LED_PIN
-                ledManager.blink();
+    case BeaconResult::Synced:
+        Serial.print("Listen windows aligned to node ");
+        Serial.print(schedule.nodeId);
+        Serial.print(" (reference node ");
+        Serial.print(schedule.referenceId);
+        Serial.println(")");
+        break;
+    case BeaconResult::Refreshed:
+    case BeaconResult::Ignored:
+        break;
+    case BeaconResult::Mismatch:
+        Serial.print("Listen schedule of node ");
+        Serial.print(schedule.nodeId);
+        Serial.println(" has a different period or window - ignored");
+        break;
+    }
+#else
+    (void)schedule;
+    (void)packet;
+    Serial.println("Listen schedule ignored (LISTEN_WINDOWS disabled)");
 #endif
-                delay(20); // Small delay between messages to avoid overwhelming BLE
-            }
-            else
-            {
-                Serial.println("Failed to send buffered message");
-                break; // Stop if send fails
-            }
-        }
+}
+
+/**
+ * @brief Reset and re-initialize the radio (last stage of the RX error recovery)
+ */
+void reinitRadio()
+{
+    // Only a complete frame shows the radio is healthy again - until then the next attempt backs off
+    radioReinitBackoff.fail(millis());
+    if (loraManager.setup())
+    {
+        loraManager.startReceiveMode();
+        radioReinitPending = false;
+        Serial.println("RX recovery: radio re-initialized");
     }
-    else if (!bleManager->isConnected())
+    else
     {
-        // Reset justConnected flag when disconnected
-        justConnected = false;
+        radioReinitPending = true;
+        Serial.print("RX recovery: radio re-initialization failed - retrying in ");
+        Serial.print(radioReinitBackoff.delayMs());
+        Serial.println(" ms");
     }
+}
 
-    // Process live queue messages
-    Message loraMsg;
-    if (xQueueReceive(loraToBleQueue, &loraMsg, 0) == pdTRUE)
+/**
+ * @brief Count an RX error and recover the radio in stages while they persist
+ *
+ * RX_ERROR_RECONFIGURE_AFTER errors in a row rewrite the modulation and packet
+ * parameters and re-arm RX; RX_ERROR_REINIT_AFTER re-initialize the radio, at
+ * most as often as the re-init backoff allows.
+ */
+void onLoRaRxError()
+{
+    if (rxErrorStreak < 0xFF)
     {
-        if (bleManager->isConnected())
+        rxErrorStreak++;
+    }
+
+    if (RX_ERROR_REINIT_AFTER > 0 && rxErrorStreak >= RX_ERROR_REINIT_AFTER)
+    {
+        if (!radioReinitBackoff.ready(millis()))
         {
-            if (bleManager->sendMessage(loraMsg))
+            if (rxErrorStreak == RX_ERROR_REINIT_AFTER)
             {
-                Serial.println("Message forwarded from LoRa to BLE");
-#ifdef LED_PIN
-                ledManager.blink();
-#endif
+                Serial.println("RX recovery: re-initialization backing off");
             }
+            return;
         }
-        else
+        Serial.print("RX recovery: ");
+        Serial.print(rxErrorStreak);
+        Serial.println(" RX errors in a row - re-initializing the radio");
+        rxErrorStreak = 0;
+        reinitRadio();
+    }
+    else if (RX_ERROR_RECONFIGURE_AFTER > 0 && rxErrorStreak == RX_ERROR_RECONFIGURE_AFTER)
+    {
+        Serial.print("RX recovery: ");
+        Serial.print(rxErrorStreak);
+        Serial.println(" RX errors in a row - rewriting modulation and packet parameters");
+        if (!loraManager.reconfigure())
         {
-            // Buffer message for later delivery
-            messageBuffer.add(loraMsg);
-            Serial.print("Buffered message (total: ");
-            Serial.print(messageBuffer.getCount());
-            Serial.println(")");
+            Serial.println("RX recovery: radio not initialized - reconfiguration skipped");
         }
     }
 }
 
 /**
  * @brief Process received LoRa packet
+ * @param packet Repaired in place and stripped of its parity with LORA_FEC_PARITY.
  */
-void processLoRaPacket(const LoRaPacket &packet)
+void processLoRaPacket(LoRaPacket &packet)
 {
+#if LOSS_INJECTION
+    if (injectLoss(rxLossPercent))
+    {
+        Serial.println("Loss injection: RX frame discarded");
+        return;
+    }
+#endif
+
     bleManager->updateActivity();
 
     // If not connected, start advertising to allow Android to reconnect
@@ -355,117 +2512,167 @@ void processLoRaPacket(const LoRaPacket &packet)
     Serial.print(packet.rssi);
     Serial.print(" dBm, SNR: ");
     Serial.print(packet.snr);
-    Serial.println(" dB");
-
-    // Deserialize message
-    Message msg;
-    if (!msg.deserialize(packet.buffer, packet.len))
+    Serial.print(" dB, handled ");
+    Serial.print(millis() - packet.receivedAt);
+    Serial.println(" ms after RxDone");
+
+    stats.loraRxFrames++;
+    stats.lastRssi = packet.rssi;
+    stats.lastSnr = packet.snr;
+
+    // Full reception context for the last-RX characteristic, whatever becomes of the frame
+    LastRxMeta rxMeta;
+    rxMeta.rxCount = stats.loraRxFrames != 0 ? stats.loraRxFrames : 1; // 0 means nothing received yet
+    rxMeta.rssi = packet.rssi;
+    rxMeta.snr = packet.snr;
+    rxMeta.frequencyErrorHz = packet.frequencyError;
+    rxMeta.frameLen = static_cast<uint8_t>(packet.frameLen);
+    rxMeta.receivedAtMs = packet.receivedAt;
+    bleManager->setLastRxMeta(rxMeta);
+
+    // A partial frame would decode as garbage - reject it outright
+    if (packet.len != packet.frameLen)
     {
-        Serial.println("Failed to deserialize LoRa message");
+        Serial.print("Error: frame of ");
+        Serial.print(packet.frameLen);
+        Serial.print(" bytes truncated to ");
+        Serial.print(packet.len);
+        Serial.println(" bytes - dropping");
+        stats.lastError = BridgeError::TruncatedFrame;
+        reportDecodeFailure(packet, DecodeFailureReason::Truncated);
+        core.nack(packet.buffer, packet.len, packet.frameLen, DecodeFailureReason::Truncated);
+        onLoRaRxError();
         return;
     }
+    rxErrorStreak = 0;
+    radioReinitBackoff.reset();
+
+#if LORA_FEC_PARITY > 0
+    // Repaired with its parity before the CRC decides; a frame beyond repair is
+    // left as it is and fails the CRC check below
+    int repairedLen = Message::correctFec(packet.buffer, packet.len, LORA_FEC_PARITY);
+    packet.len = repairedLen >= 0 ? repairedLen : max(packet.len - LORA_FEC_PARITY, 0);
+    packet.frameLen = packet.len;
+#endif
 
-    Serial.print("Deserialized: type=");
-    Serial.println((int)msg.type);
+    // Corrupted on air (the radio's own CRC is off): never decoded, relayed or
+    // taken for a new type
+    if (!Message::crcMatches(packet.buffer, packet.len))
+    {
+        Serial.println("CRC mismatch - dropping corrupted frame");
+        stats.loraCrcFailures++;
+        stats.lastError = BridgeError::DeserializeFailed;
+#if PROMISCUOUS_MODE
+        forwardRawFrame(packet);
+#else
+        reportDecodeFailure(packet, DecodeFailureReason::CrcMismatch);
+#endif
+        core.nack(packet.buffer, packet.len, packet.frameLen, DecodeFailureReason::CrcMismatch);
+        return;
+    }
 
-    // Handle message types
-    switch (msg.type)
+    // Text and Ack are handled by the core; Raw, Status, DecodeFailure and the reports
+    // are bridge -> app only, so a 0x80-0x84 frame on air counts as foreign traffic too
+    Message msg;
+    FrameResult result = core.onRadioFrame(packet.buffer, packet.len, packet.rssi, millis(), msg, packet.snr);
+    if (result == FrameResult::Unauthenticated)
     {
-    case MessageType::Text:
+        // Forged, or sent under another LORA_AUTH_KEY: never delivered, relayed or reported
+        stats.lastError = BridgeError::AuthFailed;
+        return;
+    }
+    if (result == FrameResult::Unparseable)
     {
-        Serial.print("Text - seq: ");
-        Serial.print(msg.textData.seq);
-        Serial.print(", text: \"");
-        Serial.print(msg.textData.text);
-        Serial.print("\"");
-
-        if (msg.textData.hasGps)
+        if (!isKnownMessageType(packet.buffer[0]))
         {
-            Serial.print(", GPS: ");
-            Serial.print(msg.textData.lat / 1000000.0, 6);
-            Serial.print("°, ");
-            Serial.print(msg.textData.lon / 1000000.0, 6);
-            Serial.print("°");
+            handleUnknownType(packet);
+            return;
         }
-        Serial.println();
-
-        // Send ACK
-        Message ack = Message::createAck(msg.textData.seq);
-        uint8_t ackBuf[64];
-        int ackLen = ack.serialize(ackBuf, sizeof(ackBuf));
-
-        if (ackLen > 0)
-        {
-            Serial.print("Sending ACK for seq: ");
-            Serial.println(msg.textData.seq);
-
-            // Acquire high-power locks for ACK transmission
-            powerManager.acquireForLoRaTx();
+        Serial.println("Failed to deserialize LoRa message");
+        stats.lastError = BridgeError::DeserializeFailed;
+#if PROMISCUOUS_MODE
+        forwardRawFrame(packet);
+#else
+        // A frame that decodes was turned away by the core, not mangled on the way
+        reportDecodeFailure(packet, core.decode(packet.buffer, packet.len, msg) ? DecodeFailureReason::Rejected
+                                                                                : DecodeFailureReason::Malformed);
+#endif
+        return;
+    }
 
-            if (loraManager.sendPacket(ackBuf, ackLen))
-            {
-                Serial.println("ACK sent successfully");
-            }
-            else
-            {
-                Serial.println("ACK send failed");
-            }
+    Serial.print("Deserialized: type=");
+    Serial.println((int)msg.type);
 
-            // Release power locks after transmission
-            powerManager.releaseAfterLoRaTx();
-            loraManager.startReceiveMode();
-        }
+#if CHANNEL_AGILITY
+    lastFrameHeardAt = millis();
+#endif
+#if ADAPTIVE_DATA_RATE
+    adrMonitor.addSample(packet.snr);
+    lastAdrFrameAt = millis();
+#endif
 
-        // Queue or buffer message for BLE delivery
-        if (bleManager->isConnected())
-        {
-            if (xQueueSend(loraToBleQueue, &msg, 0) != pdTRUE)
-            {
-                Serial.println("Warning: LoRa to BLE queue full, buffering");
-                messageBuffer.add(msg);
-            }
-        }
-        else
+    if (result == FrameResult::Handled)
+    {
+        if (msg.type == MessageType::Ack)
         {
-            messageBuffer.add(msg);
-            Serial.print("Buffered text message (total: ");
-            Serial.print(messageBuffer.getCount());
-            Serial.println(")");
+            stats.acksReceived++;
         }
-
 #ifdef LED_PIN
         ledManager.blink();
 #endif
-        break;
+        return;
     }
 
-    case MessageType::Ack:
+    // Message types the core leaves to the bridge
+    auditLog.record(millis(), AuditDirection::Rx, msg.type, 0, AuditOutcome::Received);
+    switch (msg.type)
     {
-        Serial.print("ACK - seq: ");
-        Serial.println(msg.ackData.seq);
+    case MessageType::Identify:
+        handleIdentify(msg);
+        break;
 
-        // Queue or buffer ACK for BLE delivery
-        if (bleManager->isConnected())
-        {
-            if (xQueueSend(loraToBleQueue, &msg, 0) != pdTRUE)
-            {
-                Serial.println("Warning: LoRa to BLE queue full, buffering");
-                messageBuffer.add(msg);
-            }
-        }
-        else
-        {
-            messageBuffer.add(msg);
-            Serial.print("Buffered ACK (total: ");
-            Serial.print(messageBuffer.getCount());
-            Serial.println(")");
-        }
+    case MessageType::IdentifyResponse:
+        Serial.print("Identify response from node ");
+        Serial.println(msg.nodeInfoData.nodeId);
+        storeForBle(msg);
+        break;
 
-#ifdef LED_PIN
-        ledManager.blink();
-#endif
+    case MessageType::ConfigRequest:
+        handleConfigRequest(msg);
         break;
-    }
+
+    case MessageType::ConfigResponse:
+        Serial.print("Config response from node ");
+        Serial.println(msg.nodeConfigData.radio.nodeId);
+        storeForBle(msg);
+        break;
+
+    case MessageType::ChannelSwitch:
+        handleChannelSwitch(msg.channelSwitchData);
+        break;
+
+    case MessageType::RateSwitch:
+        handleRateSwitch(msg.rateSwitchData);
+        break;
+
+    case MessageType::ListenSchedule:
+        handleListenSchedule(msg.listenScheduleData, packet);
+        break;
+
+    case MessageType::Text:
+    case MessageType::Battery:
+    case MessageType::Sos:
+    case MessageType::GpsDelta:
+    case MessageType::Ack:
+    case MessageType::Nack:
+    case MessageType::HopAck:
+    case MessageType::TrackStatus:
+    case MessageType::Raw:
+    case MessageType::Status:
+    case MessageType::DecodeFailure:
+    case MessageType::DeliveryReport:
+    case MessageType::LinkQuality:
+        break; // Handled by the core or filtered out above
     }
 }
 
@@ -480,60 +2687,60 @@ void loop()
     // Process BLE events (non-blocking)
     bleManager->process();
 
-    // Check for messages from BLE to send via LoRa
+    if (radioReinitPending && radioReinitBackoff.ready(millis()))
+    {
+        reinitRadio();
+    }
+
+    // Wake or sleep the radio (RADIO_POWER_MODE)
+    updateRadioPower();
+
+    // Hand messages from BLE to the core, then let it retransmit, time out ACKs and
+    // send the next one (held while the channel is being switched or the listen
+    // window is closed). With STOP_AND_WAIT the outbox is left alone until the
+    // last message is resolved.
     Message bleMsg;
-    if (xQueueReceive(bleToLoraQueue, &bleMsg, 0) == pdTRUE)
+    while (xQueueReceive(bleToLoraQueue, &bleMsg, 0) == pdTRUE)
     {
         Serial.print("Received from BLE queue: type=");
         Serial.println((int)bleMsg.type);
-
-        // Serialize and send via LoRa
-        uint8_t buf[64];
-        int len = bleMsg.serialize(buf, sizeof(buf));
-
-        if (len > 0)
+        if (!bleOutbox.addBySendPriority(bleMsg))
         {
-            Serial.print("Transmitting ");
-            Serial.print(len);
-            Serial.println(" bytes via LoRa");
-
-            // Acquire high-power locks for transmission
-            powerManager.acquireForLoRaTx();
-
-            bool sendSuccess = loraManager.sendPacket(buf, len);
-
-            if (!sendSuccess)
-            {
-                Serial.println("LoRa TX failed, retrying once...");
-                delay(100);
-                sendSuccess = loraManager.sendPacket(buf, len);
-            }
-
-            // Release power locks after transmission
-            powerManager.releaseAfterLoRaTx();
+            Serial.println("Warning: send queue full of higher priority messages, message dropped");
+        }
+    }
+    while (core.canAccept() && bleOutbox.get(bleMsg))
+    {
+        if (!core.submit(bleMsg))
+        {
+            Serial.println("Message from BLE rejected by the protocol profile (reported to the app) or a full backlog");
+        }
+    }
 
-            if (sendSuccess)
-            {
-                Serial.println("LoRa TX successful");
+    if (core.tick(millis(), !channelSwitchInProgress() && !rateSwitchInProgress() && listenWindowAllowsTx()))
+    {
+        Serial.println("LoRa TX successful");
 #ifdef LED_PIN
-                ledManager.blink(2);
+        ledManager.blink(2);
 #endif
-            }
-            else
-            {
-                Serial.println("LoRa TX failed permanently");
-            }
+    }
 
-            // Return to RX mode (CRITICAL: Always listening)
-            loraManager.startReceiveMode();
-            delay(50);
-        }
-        else
-        {
-            Serial.println("Failed to serialize message for LoRa TX");
-        }
+#if LORA_ACK_SPREADING_FACTOR
+    // Back to the data SF once no ACK is due
+    updateAckListening();
+#endif
+
+    // Handle commands from the control characteristic
+    ControlCommand controlCmd;
+    if (xQueueReceive(controlQueue, &controlCmd, 0) == pdTRUE)
+    {
+        handleControlCommand(controlCmd);
     }
 
+#if LORA_RX_POLLING
+    pollLoRaReceive();
+#endif
+
     // Check for LoRa packets (event-driven via ISR callback)
     LoRaPacket packet;
     if (xQueueReceive(loRaQueue, &packet, 0) == pdTRUE)
@@ -542,15 +2749,62 @@ void loop()
         loraActivity = false;
     }
 
+#if CHANNEL_AGILITY
+    // Watch for persistent interference and drive the switch handshake
+    monitorChannel();
+#endif
+
+#if ADAPTIVE_DATA_RATE
+    // Follow the link margin and drive the rate switch handshake
+    monitorDataRate();
+#endif
+
     // Forward queued/buffered messages from LoRa to BLE
     handleLoRaToBleForwarding();
 
+#ifdef LED_PIN
+    // Drive identify blinking (non-blocking)
+    ledManager.update();
+#endif
+
+#if STATUS_LED
+    updateSystemState();
+#endif
+
+#if HEAP_LOG_INTERVAL_MS > 0
+    if (millis() - lastHeapLogAt >= HEAP_LOG_INTERVAL_MS)
+    {
+        logHeapStats();
+    }
+#endif
+
+    if (millis() - lastBatteryLevelAt >= BATTERY_LEVEL_INTERVAL_MS)
+    {
+        updateBatteryLevel();
+    }
+
+    if (millis() - lastLinkStatsAt >= LINK_STATS_INTERVAL_MS)
+    {
+        updateLinkStats();
+    }
+
     // Adaptive delay for power savings
     // With automatic light sleep enabled, longer delays allow the system to
     // enter light sleep mode for significant power savings
-    bool hasActivity = uxQueueMessagesWaiting(bleToLoraQueue) > 0 ||
+    bool pendingWork = core.hasPendingWork();
+#if LISTEN_WINDOWS
+    // Queued messages wait for the next window without keeping the CPU busy
+    pendingWork = pendingWork && listenWindows.isOpen(millis());
+#endif
+    bool hasActivity = uxQueueMessagesWaiting(bleToLoraQueue) > 0 || !bleOutbox.isEmpty() ||
+                       uxQueueMessagesWaiting(controlQueue) > 0 ||
+                       pendingWork || channelSwitchInProgress() || rateSwitchInProgress() ||
                        uxQueueMessagesWaiting(loRaQueue) > 0 ||
                        loraActivity;
+#ifdef LED_PIN
+    // Keep the loop fast while identify blinking is active
+    hasActivity = hasActivity || ledManager.isIdentifying();
+#endif
 
     if (hasActivity)
     {
@@ -561,6 +2815,22 @@ void loop()
     {
         // Idle - long delay enables automatic light sleep
         // BLE modem and LoRa GPIO interrupts will wake the system
-        vTaskDelay(pdMS_TO_TICKS(2000)); // 2 seconds (was 100ms)
+        unsigned long idleMs = 2000; // 2 seconds (was 100ms)
+#if LISTEN_WINDOWS
+        // Wake in time to open or close the next listen window
+        unsigned long untilChange = listenWindows.msUntilChange(millis());
+        idleMs = untilChange < idleMs ? untilChange + 1 : idleMs;
+#elif RADIO_POWER_MODE == RADIO_POWER_PERIODIC_RX
+        // Wake in time to open or close the next periodic window
+        unsigned long untilChange = periodicListen.msUntilChange(millis());
+        idleMs = untilChange < idleMs ? untilChange + 1 : idleMs;
+#endif
+#if LORA_RX_POLLING
+        idleMs = idleMs > LORA_RX_POLL_MS ? LORA_RX_POLL_MS : idleMs;
+#endif
+        // Cut short by the DIO0 interrupt as soon as a frame arrives
+        ulTaskNotifyTake(pdTRUE, pdMS_TO_TICKS(idleMs));
     }
 }
+
+#endif // !RELAY_ONLY
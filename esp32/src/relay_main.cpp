@@ -0,0 +1,279 @@
+//! ESP32 Firmware for a relay-only node (RELAY_ONLY builds)
+//!
+//! A dedicated range extender: every LoRa frame heard is transmitted again
+//! unchanged, at most once per RELAY_DEDUP_MS. There is no BLE at all - the BLE
+//! stack, GATT server, message buffer and control commands are compiled out
+//! (main.cpp and BLEManager.cpp are empty in this build) and the BT controller's
+//! memory is returned to the heap. Configuration is by build flags only.
+#include "bridge_config.h"
+#if RELAY_ONLY
+#include <Arduino.h>
+#include "lora_config.h"
+#include "lora_airtime.h"
+#include "LoRaManager.h"
+#include "Protocol.h"
+#include "LEDManager.h"
+#include "PowerManager.h"
+#include "Diagnostics.h"
+#include "BridgeCore.h"
+#include "AuditLog.h"
+#include "DutyCycle.h"
+#include <freertos/queue.h>
+#include <esp_task_wdt.h>
+#include <esp_bt.h>
+#include <LoRa.h>
+#include <esp_wifi.h>
+#include "esp_pm.h"
+
+static_assert(!CHANNEL_AGILITY && RADIO_POWER_MODE == RADIO_POWER_ALWAYS_ON_RX,
+              "RELAY_ONLY listens continuously on LORA_FREQUENCY");
+static_assert(!LORA_RX_POLLING, "RELAY_ONLY needs DIO0 wired for interrupt-driven RX");
+static_assert(LORA_RADIO == LORA_RADIO_SX127X, "RELAY_ONLY reads frames in the DIO0 ISR, which needs an SX127x");
+
+LoRaManager loraManager(LORA_SCK, LORA_MISO, LORA_MOSI, LORA_SS, LORA_RST, LORA_DIO0, LORA_FREQUENCY);
+PowerManager powerManager;
+#ifdef LED_PIN
+LEDManager ledManager(LED_PIN);
+#endif
+
+// Received frame, handed from the ISR to the loop
+struct LoRaPacket
+{
+    uint8_t buffer[LORA_MAX_PACKET_SIZE];
+    int frameLen; // Size reported by the radio
+    int len;      // Bytes actually read into buffer
+};
+
+QueueHandle_t loRaQueue;
+
+// Task running loop(), notified by the ISR so an idle loop wakes for a frame at once
+TaskHandle_t loopTaskHandle = nullptr;
+
+BridgeStats stats = {};
+AuditLog auditLog;
+
+// Time on air of every transmission in the rolling duty-cycle window
+DutyCycle dutyCycle((unsigned long)LORA_DUTY_CYCLE_WINDOW_S * 1000,
+                    (unsigned long)loraDutyCycleBudgetMs(loraConfiguredAirtimeLimits()));
+
+/**
+ * @brief LoRa receive callback (ISR): queue the frame for the loop
+ */
+void IRAM_ATTR onLoRaReceive(int packetSize)
+{
+    if (packetSize == 0)
+        return;
+
+    LoRaPacket packet;
+    packet.frameLen = packetSize;
+    packet.len = 0;
+    while (packet.len < packetSize && packet.len < (int)sizeof(packet.buffer) && LoRa.available())
+    {
+        packet.buffer[packet.len++] = LoRa.read();
+    }
+
+    BaseType_t xHigherPriorityTaskWoken = pdFALSE;
+    xQueueSendFromISR(loRaQueue, &packet, &xHigherPriorityTaskWoken);
+    vTaskNotifyGiveFromISR(loopTaskHandle, &xHigherPriorityTaskWoken);
+    portYIELD_FROM_ISR(xHigherPriorityTaskWoken);
+}
+
+/**
+ * @brief RadioIo over the SX127x: TX under the power locks, then straight back to RX
+ */
+class LoRaRadio : public RadioIo
+{
+public:
+    bool send(const uint8_t *buf, size_t len) override
+    {
+        powerManager.acquireForLoRaTx();
+        dutyCycle.record(millis(), (unsigned long)ceil(loraTimeOnAirMs(loraConfiguredModulation(), len)));
+        bool sent = loraManager.sendPacket(buf, len);
+        loraManager.startReceiveMode();
+        powerManager.releaseAfterLoRaTx();
+
+        stats.loraTxFrames++;
+        if (!sent)
+        {
+            stats.loraTxFailures++;
+            stats.lastError = BridgeError::TxFailed;
+        }
+        return sent;
+    }
+
+    bool isReceiving() override
+    {
+        return loraManager.isReceiving();
+    }
+
+    bool detectChannelActivity() override
+    {
+        return loraManager.detectChannelActivity();
+    }
+
+    bool airtimeAvailable(size_t len) override
+    {
+        return !LORA_ENFORCE_DUTY_CYCLE ||
+               dutyCycle.allows(millis(), (unsigned long)ceil(loraTimeOnAirMs(loraConfiguredModulation(), len)));
+    }
+};
+
+/**
+ * @brief AppIo without an app: a relay never delivers anything locally
+ */
+class NoApp : public AppIo
+{
+public:
+    bool deliver(const Message &) override { return false; }
+    bool isConnected() const override { return false; }
+    void status(BridgeStatus, uint8_t) override {}
+};
+
+LoRaRadio loraRadio;
+NoApp noApp;
+
+// Only relay() and the send queue are used
+BridgeCore core(loraRadio, noApp, stats, auditLog);
+
+void setup()
+{
+    Serial.begin(115200);
+    delay(2000);
+
+    // loop() runs in this task too
+    loopTaskHandle = xTaskGetCurrentTaskHandle();
+
+    // Neither WiFi nor Bluetooth is used: stop WiFi and hand the BT controller's RAM to the heap
+    esp_err_t err = esp_wifi_stop();
+    if (err == ESP_OK || err == ESP_ERR_WIFI_NOT_INIT)
+    {
+        esp_wifi_deinit();
+    }
+    btStop();
+    esp_bt_controller_mem_release(ESP_BT_MODE_BTDM);
+
+    setCpuFrequencyMhz(80);
+    esp_pm_config_t pm_config = {
+        .max_freq_mhz = 80,
+        .min_freq_mhz = 10,
+        .light_sleep_enable = true,
+    };
+    ESP_ERROR_CHECK(esp_pm_configure(&pm_config));
+
+    esp_task_wdt_config_t wdt_config = {
+        .timeout_ms = 30000,
+        .trigger_panic = true,
+    };
+    esp_task_wdt_init(&wdt_config);
+    esp_task_wdt_add(xTaskGetCurrentTaskHandle());
+
+    Serial.println("===================================");
+    Serial.println("ESP32 LoRa relay (no BLE) starting...");
+    Serial.println("===================================");
+
+    loRaQueue = xQueueCreate(15, sizeof(LoRaPacket));
+    if (loRaQueue == nullptr)
+    {
+        Serial.println("Failed to create LoRa queue. Halting execution.");
+        while (1)
+        {
+            delay(1000);
+        }
+    }
+
+    const int LORA_RETRY_COUNT = 3;
+    bool loraSuccess = false;
+    for (int attempt = 1; attempt <= LORA_RETRY_COUNT && !loraSuccess; attempt++)
+    {
+        loraSuccess = loraManager.setup();
+        if (!loraSuccess && attempt < LORA_RETRY_COUNT)
+        {
+            Serial.println("LoRa setup failed - retrying in 1 second...");
+            delay(1000);
+        }
+    }
+    if (!loraSuccess)
+    {
+        Serial.println("LoRa setup failed permanently. Halting execution.");
+        while (1)
+        {
+            delay(1000);
+        }
+    }
+
+    LoRa.onReceive(onLoRaReceive);
+    loraManager.startReceiveMode();
+    core.setRandomSeed(esp_random());
+
+    gpio_wakeup_enable((gpio_num_t)LORA_DIO0, GPIO_INTR_HIGH_LEVEL);
+    esp_sleep_enable_gpio_wakeup();
+
+#ifdef LED_PIN
+    ledManager.setup();
+#endif
+
+    Serial.print("Relaying on ");
+    Serial.print(loraManager.getFrequency());
+    Serial.print(" Hz, each frame at most once per ");
+    Serial.print(RELAY_DEDUP_MS);
+    Serial.print(" ms, hop ACKs as node ");
+    Serial.println(NODE_ID);
+
+    HeapStats heap = readHeapStats();
+    Serial.printf("Heap: %u of %u bytes free (BT controller memory released)\n", (unsigned)heap.freeBytes,
+                  (unsigned)heap.totalBytes);
+}
+
+void loop()
+{
+    esp_task_wdt_reset();
+
+    LoRaPacket packet;
+    while (xQueueReceive(loRaQueue, &packet, 0) == pdTRUE)
+    {
+        stats.loraRxFrames++;
+        if (packet.len != packet.frameLen)
+        {
+            Serial.println("Truncated frame - not relayed");
+            stats.lastError = BridgeError::TruncatedFrame;
+            continue;
+        }
+#if LORA_FEC_PARITY > 0
+        // Repaired before the CRC check; the core adds fresh parity when relaying
+        int repairedLen = Message::correctFec(packet.buffer, packet.len, LORA_FEC_PARITY);
+        packet.len = repairedLen >= 0 ? repairedLen : max(packet.len - LORA_FEC_PARITY, 0);
+        packet.frameLen = packet.len;
+#endif
+        if (!Message::crcMatches(packet.buffer, packet.len))
+        {
+            Serial.println("CRC mismatch - corrupted frame not relayed");
+            stats.lastError = BridgeError::DeserializeFailed;
+            continue;
+        }
+        if (!BridgeCore::isRelayable(packet.buffer, packet.len))
+        {
+            Serial.println("Link-local frame - not relayed");
+            continue;
+        }
+        if (core.relay(packet.buffer, packet.len, millis()))
+        {
+            Serial.print("Relaying ");
+            Serial.print(packet.len);
+            Serial.println(" bytes");
+        }
+    }
+
+    if (core.tick(millis()))
+    {
+#ifdef LED_PIN
+        ledManager.blink();
+#endif
+    }
+
+    // Frames arrive by interrupt, which also wakes the CPU from light sleep and
+    // cuts the wait short
+    bool hasActivity = core.hasPendingWork() || uxQueueMessagesWaiting(loRaQueue) > 0;
+    ulTaskNotifyTake(pdTRUE, pdMS_TO_TICKS(hasActivity ? 10 : 2000));
+}
+
+#endif // RELAY_ONLY
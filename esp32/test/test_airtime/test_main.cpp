@@ -0,0 +1,203 @@
+// Host tests for the LoRa time-on-air calculator against published reference values
+// (Semtech LoRa Calculator / AN1200.13), and for the mW to dBm TX power conversion.
+// Run with: pio test -e native
+#include <unity.h>
+#include "lora_airtime.h"
+
+void setUp() {}
+
+void tearDown() {}
+
+void test_symbol_time()
+{
+    LoRaModulation sf7 = {7, 125E3, 5, 8, true, false};
+    TEST_ASSERT_EQUAL_FLOAT(1.024, loraSymbolTimeMs(sf7));
+
+    LoRaModulation sf12 = {12, 125E3, 5, 8, true, false};
+    TEST_ASSERT_EQUAL_FLOAT(32.768, loraSymbolTimeMs(sf12));
+}
+
+void test_sf7_bw125_reference()
+{
+    // 10 bytes, CR 4/5, 8 symbol preamble, CRC on: 41.216 ms
+    LoRaModulation mod = {7, 125E3, 5, 8, true, false};
+    TEST_ASSERT_FLOAT_WITHIN(0.001, 41.216, loraTimeOnAirMs(mod, 10));
+    TEST_ASSERT_EQUAL_UINT32(42, loraTimeOnAirMs(10, 7, 125E3, 5, 8, true, false));
+}
+
+void test_sf10_bw125_50_bytes_reference()
+{
+    // The README's "~600 ms" example: 616.448 ms with CRC, 575.488 ms without
+    TEST_ASSERT_EQUAL_UINT32(617, loraTimeOnAirMs(50, 10, 125E3, 5, 8, true, false));
+    TEST_ASSERT_EQUAL_UINT32(576, loraTimeOnAirMs(50, 10, 125E3, 5, 8, false, false));
+}
+
+void test_sf12_low_data_rate_optimize_reference()
+{
+    // 51 bytes at SF12/BW125 (LoRaWAN max payload at DR0 incl. overhead): 2465.792 ms
+    LoRaModulation mod = {12, 125E3, 5, 8, true, false};
+    TEST_ASSERT_FLOAT_WITHIN(0.001, 2465.792, loraTimeOnAirMs(mod, 51));
+}
+
+void test_implicit_header_is_shorter()
+{
+    // 10 bytes at SF7/BW125: the header costs one block of CR symbols here
+    TEST_ASSERT_EQUAL_UINT32(37, loraTimeOnAirMs(10, 7, 125E3, 5, 8, true, true));
+}
+
+void test_coding_rate_and_preamble()
+{
+    // SF9/BW125, 20 bytes, CR 4/8, 12 symbol preamble, CRC on: 263.168 ms
+    LoRaModulation mod = {9, 125E3, 8, 12, true, false};
+    TEST_ASSERT_FLOAT_WITHIN(0.001, 263.168, loraTimeOnAirMs(mod, 20));
+}
+
+void test_tx_power_mw_to_nearest_dbm()
+{
+    TEST_ASSERT_EQUAL(14, loraMwToDbm(25));
+    TEST_ASSERT_EQUAL(10, loraMwToDbm(10));
+    TEST_ASSERT_EQUAL(17, loraMwToDbm(50));
+    TEST_ASSERT_EQUAL(20, loraMwToDbm(100));
+
+    // 10 * log10(mW) rounded: 5.6 mW is 7.48 dBm, 5.7 mW is 7.56 dBm
+    TEST_ASSERT_EQUAL(7, loraMwToDbm(5.6));
+    TEST_ASSERT_EQUAL(8, loraMwToDbm(5.7));
+    TEST_ASSERT_FLOAT_WITHIN(0.001, 25.119, loraDbmToMw(14));
+}
+
+void test_tx_power_mw_is_clamped_to_the_radio()
+{
+    TEST_ASSERT_EQUAL(LORA_TX_POWER_MIN_DBM, loraMwToDbm(0.5));
+    TEST_ASSERT_EQUAL(LORA_TX_POWER_MAX_DBM, loraMwToDbm(500));
+    TEST_ASSERT_FALSE(loraTxPowerMwAchievable(0.5));
+    TEST_ASSERT_FALSE(loraTxPowerMwAchievable(500));
+    TEST_ASSERT_TRUE(loraTxPowerMwAchievable(1.5)); // Rounds to 2 dBm
+    TEST_ASSERT_TRUE(loraTxPowerMwAchievable(100));
+}
+
+void test_eirp_limit_reduces_conducted_power()
+{
+    // 20 dBm into a 5 dBi antenna under a 16 dBm EIRP limit: 11 dBm conducted
+    TEST_ASSERT_EQUAL(11, loraEirpLimitedDbm(20, 5, 16));
+    // Already within the limit: unchanged
+    TEST_ASSERT_EQUAL(10, loraEirpLimitedDbm(10, 2, 16));
+    // Fractional gain rounds the setting down, never up past the limit
+    TEST_ASSERT_EQUAL(9, loraEirpLimitedDbm(20, 2.15, 12));
+    // Below the radio's minimum is reported as such, not clamped
+    TEST_ASSERT_EQUAL(-1, loraEirpLimitedDbm(20, 12.5, 12));
+}
+
+void test_airtime_check_against_duty_cycle()
+{
+    // EU 868 MHz: 1% of an hour is 36 s; 64-byte frames at BW31.25, 1 + 4 attempts
+    LoRaAirtimeLimits eu868 = {1, 3600, 0, 10};
+    TEST_ASSERT_FLOAT_WITHIN(0.001, 36000, loraDutyCycleBudgetMs(eu868));
+    LoRaModulation sf12 = {12, 31.25E3, 5, 8, false, false}; // 11.2 s per frame
+    TEST_ASSERT_EQUAL(LoRaAirtimeCheck::DutyCycleExceeded, loraCheckAirtime(sf12, 64, 5, eu868));
+    LoRaModulation sf11 = {11, 31.25E3, 5, 8, false, false}; // 5.9 s: all attempts fit, 10 messages don't
+    TEST_ASSERT_EQUAL(LoRaAirtimeCheck::FewMessages, loraCheckAirtime(sf11, 64, 5, eu868));
+    TEST_ASSERT_EQUAL(10, loraHighestLegalSf(sf12, 64, 5, eu868)); // 3.3 s
+
+    // 433 MHz at 10%: the default modulation is fine
+    LoRaAirtimeLimits band433 = {10, 3600, 0, 10};
+    TEST_ASSERT_EQUAL(LoRaAirtimeCheck::Legal, loraCheckAirtime(sf11, 64, 5, band433));
+}
+
+void test_airtime_check_against_dwell_time()
+{
+    // US 915 MHz: no duty cycle, but no frame may exceed 400 ms
+    LoRaAirtimeLimits us915 = {100, 3600, 400, 10};
+    LoRaModulation sf9 = {9, 125E3, 5, 8, false, false}; // 370 ms
+    TEST_ASSERT_EQUAL(LoRaAirtimeCheck::Legal, loraCheckAirtime(sf9, 64, 5, us915));
+    LoRaModulation sf10 = {10, 125E3, 5, 8, false, false}; // 698 ms
+    TEST_ASSERT_EQUAL(LoRaAirtimeCheck::DwellExceeded, loraCheckAirtime(sf10, 64, 5, us915));
+    TEST_ASSERT_EQUAL(9, loraHighestLegalSf(sf10, 64, 5, us915));
+
+    // Not even SF7 fits at the narrowest bandwidth
+    LoRaModulation narrow = {12, 7.8E3, 5, 8, false, false};
+    TEST_ASSERT_EQUAL(0, loraHighestLegalSf(narrow, 64, 5, us915));
+}
+
+void test_relayed_ack_timeout_grows_per_hop()
+{
+    LoRaModulation sf7 = {7, 125E3, 5, 8, false, false};
+    unsigned long direct = loraAckTimeoutMs(sf7);
+    TEST_ASSERT_EQUAL(direct, loraRelayedAckTimeoutMs(sf7, 64, 0));
+
+    // 64 bytes (118 ms) and an ACK (26 ms) relayed, plus the relay's margin
+    unsigned long oneHop = loraRelayedAckTimeoutMs(sf7, 64, 1);
+    TEST_ASSERT_UINT32_WITHIN(2, direct + 144 + LORA_ACK_TIMEOUT_MARGIN_MS, oneHop);
+    TEST_ASSERT_EQUAL(direct + 2 * (oneHop - direct), loraRelayedAckTimeoutMs(sf7, 64, 2));
+}
+
+void test_ack_modulation_only_changes_the_spreading_factor()
+{
+    LoRaModulation data = loraConfiguredModulation();
+    LoRaModulation ack = loraAckModulation();
+    TEST_ASSERT_EQUAL(LORA_ACK_SPREADING_FACTOR == 0 ? LORA_SPREADING_FACTOR : LORA_ACK_SPREADING_FACTOR,
+                      ack.spreadingFactor);
+    TEST_ASSERT_EQUAL(data.bandwidthHz, ack.bandwidthHz);
+    TEST_ASSERT_EQUAL(data.codingRate, ack.codingRate);
+    TEST_ASSERT_EQUAL(data.preambleLength, ack.preambleLength);
+
+    // An SF7 ACK on the default BW31.25 (104 ms) waits over 3 s less than an SF11 one (1668 ms)
+    LoRaModulation fast = data;
+    fast.spreadingFactor = 7;
+    data.spreadingFactor = 11;
+    TEST_ASSERT_UINT32_WITHIN(2, 2 * (1668 - 104), loraAckTimeoutMs(data) - loraAckTimeoutMs(fast));
+}
+
+void test_runtime_spreading_factor_range()
+{
+    TEST_ASSERT_FALSE(loraSpreadingFactorSelectable(6)); // Needs implicit header
+    TEST_ASSERT_TRUE(loraSpreadingFactorSelectable(12));
+    TEST_ASSERT_FALSE(loraSpreadingFactorSelectable(13));
+    TEST_ASSERT_TRUE(loraSpreadingFactorSelectable(LORA_SPREADING_FACTOR));
+    TEST_ASSERT_EQUAL(LORA_ACK_SPREADING_FACTOR == 0, loraSpreadingFactorSelectable(7));
+
+    // Fast ACKs keep their own SF below whatever data SF was selected
+    LoRaModulation data = loraConfiguredModulation();
+    data.spreadingFactor = 12;
+    TEST_ASSERT_EQUAL(LORA_ACK_SPREADING_FACTOR == 0 ? 12 : LORA_ACK_SPREADING_FACTOR,
+                      loraAckModulation(data).spreadingFactor);
+}
+
+void test_runtime_radio_settings_range()
+{
+    TEST_ASSERT_TRUE(loraRadioSettingsValid(LORA_FREQUENCY, LORA_TX_POWER_CONDUCTED_DBM));
+
+    // TX power within the radio's range
+    TEST_ASSERT_TRUE(loraTxPowerSelectable(LORA_TX_POWER_MIN_DBM));
+    TEST_ASSERT_FALSE(loraTxPowerSelectable(LORA_TX_POWER_MIN_DBM - 1));
+    TEST_ASSERT_FALSE(loraTxPowerSelectable(LORA_TX_POWER_MAX_DBM + 1));
+    TEST_ASSERT_FALSE(loraRadioSettingsValid(LORA_FREQUENCY, -128));
+
+    // The whole channel inside the band, not just its centre
+    const uint32_t halfChannel = static_cast<uint32_t>(LORA_BANDWIDTH / 2);
+    TEST_ASSERT_TRUE(loraRadioSettingsValid(LORA_BAND_MIN_HZ + halfChannel, LORA_TX_POWER_MIN_DBM));
+    TEST_ASSERT_TRUE(loraRadioSettingsValid(LORA_BAND_MAX_HZ - halfChannel, LORA_TX_POWER_MIN_DBM));
+    TEST_ASSERT_FALSE(loraRadioSettingsValid(LORA_BAND_MIN_HZ, LORA_TX_POWER_MIN_DBM));
+    TEST_ASSERT_FALSE(loraRadioSettingsValid(LORA_BAND_MAX_HZ, LORA_TX_POWER_MIN_DBM));
+    TEST_ASSERT_FALSE(loraRadioSettingsValid(0, LORA_TX_POWER_MIN_DBM));
+}
+
+int main(int, char **)
+{
+    UNITY_BEGIN();
+    RUN_TEST(test_symbol_time);
+    RUN_TEST(test_sf7_bw125_reference);
+    RUN_TEST(test_sf10_bw125_50_bytes_reference);
+    RUN_TEST(test_sf12_low_data_rate_optimize_reference);
+    RUN_TEST(test_implicit_header_is_shorter);
+    RUN_TEST(test_coding_rate_and_preamble);
+    RUN_TEST(test_tx_power_mw_to_nearest_dbm);
+    RUN_TEST(test_tx_power_mw_is_clamped_to_the_radio);
+    RUN_TEST(test_eirp_limit_reduces_conducted_power);
+    RUN_TEST(test_airtime_check_against_duty_cycle);
+    RUN_TEST(test_airtime_check_against_dwell_time);
+    RUN_TEST(test_relayed_ack_timeout_grows_per_hop);
+    RUN_TEST(test_ack_modulation_only_changes_the_spreading_factor);
+    RUN_TEST(test_runtime_spreading_factor_range);
+    RUN_TEST(test_runtime_radio_settings_range);
+    return UNITY_END();
+}
@@ -0,0 +1,1839 @@
+// Host tests for BridgeCore: two simulated bridges linked by an in-memory LoRa channel.
+// Run with: pio test -e native
+#define ACK_MIN_RSSI_DBM -120
+#define TRACK_STATUS 1
+
+#include <unity.h>
+#include <deque>
+#include <vector>
+#include "BridgeCore.h"
+#include "ListenWindows.h"
+#include "PeriodicListen.h"
+#include "AdaptiveDataRate.h"
+#include "Backoff.h"
+#include "DutyCycle.h"
+
+const unsigned long ACK_TIMEOUT_MS = 1000;
+const int GOOD_RSSI = -90;
+
+/**
+ * One direction of the simulated air: frames sent by a node land in the peer's inbox
+ */
+class ChannelRadio : public RadioIo
+{
+public:
+    std::deque<std::vector<uint8_t>> *peerInbox = nullptr;
+    int dropNext = 0; // Number of upcoming frames lost on air
+    int framesSent = 0;
+    bool receiving = false; // A frame is arriving right now
+    int busyCads = 0;        // Number of upcoming CADs that hear a preamble
+    int cads = 0;
+    bool airtimeLeft = true; // Duty-cycle budget
+
+    bool send(const uint8_t *buf, size_t len) override
+    {
+        framesSent++;
+        if (dropNext > 0)
+        {
+            dropNext--;
+            return true; // Sent, but nobody hears it
+        }
+        peerInbox->push_back(std::vector<uint8_t>(buf, buf + len));
+        return true;
+    }
+
+    bool isReceiving() override { return receiving; }
+
+    bool detectChannelActivity() override
+    {
+        cads++;
+        if (busyCads > 0)
+        {
+            busyCads--;
+            return true;
+        }
+        return false;
+    }
+
+    bool airtimeAvailable(size_t) override { return airtimeLeft; }
+};
+
+/**
+ * Phone side: records everything the core delivers
+ */
+class RecordingApp : public AppIo
+{
+public:
+    std::vector<Message> delivered;
+    std::vector<Message> statuses;
+    bool connected = true;
+
+    bool deliver(const Message &msg) override
+    {
+        delivered.push_back(msg);
+        return true;
+    }
+
+    bool isConnected() const override { return connected; }
+
+    void status(BridgeStatus code, uint8_t seq) override { statuses.push_back(Message::createStatus(code, seq)); }
+};
+
+struct Node
+{
+    ChannelRadio radio;
+    RecordingApp app;
+    BridgeStats stats = {};
+    AuditLog audit;
+    BridgeCore core;
+    std::deque<std::vector<uint8_t>> inbox;
+
+    Node() : core(radio, app, stats, audit) { core.setAckTimeout(ACK_TIMEOUT_MS); }
+};
+
+/// Run both nodes until the air is quiet, delivering frames in order
+static void run(Node &a, Node &b, unsigned long now)
+{
+    for (int i = 0; i < 20; i++)
+    {
+        a.core.tick(now);
+        b.core.tick(now);
+        if (a.inbox.empty() && b.inbox.empty())
+        {
+            return;
+        }
+        Node *nodes[] = {&a, &b};
+        for (Node *node : nodes)
+        {
+            while (!node->inbox.empty())
+            {
+                std::vector<uint8_t> frame = node->inbox.front();
+                node->inbox.pop_front();
+                Message msg;
+                node->core.onRadioFrame(frame.data(), frame.size(), GOOD_RSSI, now, msg);
+            }
+        }
+    }
+}
+
+/// The app writes bytes to the BLE characteristic: decoded and queued for LoRa as BLEManager does
+static bool appWrite(Node &node, const uint8_t *data, size_t len)
+{
+    Message msg;
+    return msg.deserialize(data, len) && node.core.submit(msg);
+}
+
+/// The oldest frame on air reaches the node's LoRa receive path
+static FrameResult receiveFrame(Node &node, unsigned long now)
+{
+    std::vector<uint8_t> frame = node.inbox.front();
+    node.inbox.pop_front();
+    Message msg;
+    return node.core.onRadioFrame(frame.data(), frame.size(), GOOD_RSSI, now, msg);
+}
+
+/// Bytes the app would be notified with for a delivered message
+static std::vector<uint8_t> notified(const Message &msg)
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = msg.serialize(buf, sizeof(buf));
+    return std::vector<uint8_t>(buf, buf + len);
+}
+
+static Node *alice;
+static Node *bob;
+
+void setUp()
+{
+    alice = new Node();
+    bob = new Node();
+    alice->radio.peerInbox = &bob->inbox;
+    bob->radio.peerInbox = &alice->inbox;
+}
+
+void tearDown()
+{
+    delete alice;
+    delete bob;
+}
+
+void test_text_is_delivered_and_acked()
+{
+    alice->core.submit(Message::createText(1, "HELLO"));
+    run(*alice, *bob, 0);
+
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessageType::Text, bob->app.delivered[0].type);
+    TEST_ASSERT_EQUAL_STRING("HELLO", bob->app.delivered[0].textData.text);
+
+    // Bob's ACK ends the wait; Alice's phone gets a delivery report, not the ACK itself
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessageType::DeliveryReport, alice->app.delivered[0].type);
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Delivered, alice->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(1, alice->app.delivered[0].deliveryReportData.seq);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_battery_level_is_delivered_and_acked()
+{
+    alice->core.submit(Message::createBattery(4, 0, 3300));
+    alice->core.submit(Message::createText(5, "LOW BATT"));
+    run(*alice, *bob, 0);
+
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessageType::Battery, bob->app.delivered[0].type);
+    TEST_ASSERT_EQUAL(0, bob->app.delivered[0].batteryData.percent);
+    TEST_ASSERT_EQUAL(3300, bob->app.delivered[0].batteryData.millivolts);
+
+    // Both were ACKed, but only the text is awaited and reported
+    TEST_ASSERT_EQUAL(2, bob->radio.framesSent);
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(5, alice->app.delivered[0].deliveryReportData.seq);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_encrypted_text_is_delivered_and_acked()
+{
+    const uint8_t key[PAYLOAD_KEY_LEN] = {1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16};
+    alice->core.setPayloadKey(key);
+    bob->core.setPayloadKey(key);
+    alice->core.submit(Message::createText(3, "QUIET"));
+    run(*alice, *bob, 0);
+
+    // Bob's phone gets the text in the clear, Alice's the delivery report
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL_STRING("QUIET", bob->app.delivered[0].textData.text);
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Delivered, alice->app.delivered[0].deliveryReportData.outcome);
+
+    // A bridge with another key gets nothing readable
+    const uint8_t otherKey[PAYLOAD_KEY_LEN] = {16, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1};
+    bob->core.setPayloadKey(otherKey);
+    alice->core.submit(Message::createText(4, "QUIET"));
+    run(*alice, *bob, 1);
+    TEST_ASSERT_TRUE(bob->app.delivered.size() == 1 ||
+                     strcmp("QUIET", bob->app.delivered.back().textData.text) != 0);
+}
+
+void test_frames_without_a_valid_auth_tag_are_dropped()
+{
+    const uint8_t key[AUTH_KEY_LEN] = {9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 1, 2, 3, 4, 5, 6};
+    alice->core.setAuthKey(key);
+    bob->core.setAuthKey(key);
+    alice->core.submit(Message::createText(1, "SIGNED"));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Delivered, alice->app.delivered[0].deliveryReportData.outcome);
+
+    // A forged text: no delivery, no ACK, no relay
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = Message::createText(2, "FORGED").serialize(buf, sizeof(buf));
+    Message msg;
+    TEST_ASSERT_EQUAL(FrameResult::Unauthenticated, bob->core.onRadioFrame(buf, len, GOOD_RSSI, 10, msg));
+    TEST_ASSERT_FALSE(bob->core.relay(buf, len, 10));
+
+    // A tampered one: the CRC is fixed up, the tag can't be
+    len = Message::createText(3, "SIGNED").serializeAuthenticated(buf, sizeof(buf), key);
+    buf[1] = 4;
+    buf[len - 1] = crc8(buf, len - 1);
+    TEST_ASSERT_EQUAL(FrameResult::Unauthenticated, bob->core.onRadioFrame(buf, len, GOOD_RSSI, 20, msg));
+
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+}
+
+void test_relay_keeps_the_auth_tag_of_the_longest_texts()
+{
+    const uint8_t key[AUTH_KEY_LEN] = {9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 1, 2, 3, 4, 5, 6};
+    bob->core.setAuthKey(key);
+    std::deque<std::vector<uint8_t>> air;
+    bob->radio.peerInbox = &air;
+
+    // 50 characters, GPS with altitude and speed, and a fragment header: 60 bytes, 64 with the tag
+    Message text = Message::createTextWithGps(1, "ABCDEFGHIJKLMNOPQRSTUVWXYZABCDEFGHIJKLMNOPQRSTUVWX", 47000000, 8000000);
+    text.textData.gpsFields = GPS_FIELD_ALTITUDE | GPS_FIELD_SPEED;
+    text.textData.altitude = 420;
+    text.textData.speed = 1250;
+    text.textData.isFragment = true;
+    text.textData.fragmentId = 1;
+    text.textData.fragmentIndex = 0;
+    text.textData.fragmentCount = 2;
+    uint8_t frame[MAX_FRAME_LEN];
+    int len = text.serializeAuthenticated(frame, sizeof(frame), key);
+    TEST_ASSERT_EQUAL(60 + AUTH_TAG_LEN, len);
+
+    TEST_ASSERT_TRUE(bob->core.relay(frame, len, 0));
+    bob->core.tick(0);
+    TEST_ASSERT_EQUAL(1, air.size());
+    TEST_ASSERT_EQUAL(len, air[0].size());
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(frame, air[0].data(), len);
+}
+
+void test_fec_parity_repairs_frames_on_air()
+{
+    alice->core.setFecParity(4);
+    bob->core.setFecParity(4);
+    alice->core.submit(Message::createTextWithGps(1, "NEAR", 47000000, 8000000));
+    alice->core.tick(0);
+    TEST_ASSERT_EQUAL(1, bob->inbox.size());
+
+    // Two bytes hit on air: repaired as the receive path does before the CRC check
+    std::vector<uint8_t> frame = bob->inbox.front();
+    bob->inbox.pop_front();
+    uint8_t plain[MAX_FRAME_LEN];
+    int plainLen = Message::createTextWithGps(1, "NEAR", 47000000, 8000000).serialize(plain, sizeof(plain));
+    TEST_ASSERT_EQUAL(plainLen + 4, frame.size());
+    frame[3] ^= 0x40;
+    frame[plainLen + 1] ^= 0xFF;
+    TEST_ASSERT_EQUAL(plainLen, Message::correctFec(frame.data(), frame.size(), 4));
+    Message msg;
+    TEST_ASSERT_EQUAL(FrameResult::Handled, bob->core.onRadioFrame(frame.data(), plainLen, GOOD_RSSI, 0, msg));
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL_STRING("NEAR", bob->app.delivered[0].textData.text);
+    TEST_ASSERT_EQUAL(8000000, bob->app.delivered[0].textData.lon);
+
+    // The ACK carries parity too
+    TEST_ASSERT_EQUAL(1, alice->inbox.size());
+    TEST_ASSERT_EQUAL(MESSAGE_CRC_LEN + 2 + 4, alice->inbox.front().size());
+
+    // A relayed frame arrives without its parity and goes back on air with fresh parity
+    std::deque<std::vector<uint8_t>> air;
+    bob->radio.peerInbox = &air;
+    const uint8_t future[] = {0x42, 0xAA, 0xBB};
+    bob->core.relay(future, sizeof(future), 0);
+    bob->core.tick(0);
+    TEST_ASSERT_EQUAL(1, air.size());
+    TEST_ASSERT_EQUAL(sizeof(future) + 4, air[0].size());
+    air[0][0] ^= 0x01;
+    TEST_ASSERT_EQUAL(sizeof(future), Message::correctFec(air[0].data(), air[0].size(), 4));
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(future, air[0].data(), sizeof(future));
+}
+
+void test_sos_is_repeated_and_acked_once()
+{
+    alice->core.submit(Message::createText(6, "HELP COMING?"));
+    alice->core.submit(Message::createSos(7, 47000000, 8000000));
+    run(*alice, *bob, 0);
+
+    // Ahead of the text, and reported delivered on its ACK
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessageType::Sos, bob->app.delivered[0].type);
+    TEST_ASSERT_EQUAL(47000000, bob->app.delivered[0].sosData.lat);
+    TEST_ASSERT_EQUAL(2, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(7, alice->app.delivered[0].deliveryReportData.seq);
+
+    for (unsigned long repeat = 1; repeat <= SOS_REPEATS + 2; repeat++)
+    {
+        run(*alice, *bob, repeat * SOS_REPEAT_INTERVAL_MS);
+    }
+
+    // Every repeat reaches bob's app, but only the first copy was ACKed
+    TEST_ASSERT_EQUAL(2 + SOS_REPEATS, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(2 + SOS_REPEATS, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessageType::Sos, bob->app.delivered.back().type);
+    TEST_ASSERT_EQUAL(2, bob->radio.framesSent);
+    TEST_ASSERT_EQUAL(2, alice->app.delivered.size());
+    TEST_ASSERT_FALSE(alice->core.hasPendingWork());
+}
+
+void test_both_directions()
+{
+    alice->core.submit(Message::createText(1, "PING"));
+    bob->core.submit(Message::createText(7, "PONG"));
+    run(*alice, *bob, 0);
+
+    // Each side got the other's text and the delivery report for its own
+    TEST_ASSERT_EQUAL(2, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_FALSE(bob->core.isAwaitingAck());
+}
+
+void test_lost_normal_text_times_out()
+{
+    alice->radio.dropNext = 1 + PRIORITY_NORMAL_RETRIES;
+    alice->core.submit(Message::createText(2, "LOST"));
+    run(*alice, *bob, 0);
+    // Retries back off: 1, 3, 7, ... ACK timeouts after the first send
+    unsigned long retryAt = 0;
+    for (int i = 1; i <= PRIORITY_NORMAL_RETRIES; i++)
+    {
+        retryAt = 2 * retryAt + ACK_TIMEOUT_MS;
+        run(*alice, *bob, retryAt);
+    }
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+
+    run(*alice, *bob, retryAt + ACK_TIMEOUT_MS);
+
+    // Normal priority is sent once, plus PRIORITY_NORMAL_RETRIES if set
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(1 + PRIORITY_NORMAL_RETRIES, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, alice->stats.ackTimeouts);
+    TEST_ASSERT_EQUAL(0, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Failed, alice->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(2, alice->app.delivered[0].deliveryReportData.seq);
+}
+
+void test_lost_critical_text_is_retransmitted()
+{
+    alice->radio.dropNext = 1;
+    alice->core.submit(Message::createText(3, "SOS", MessagePriority::Critical));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_EQUAL(0, bob->app.delivered.size());
+
+    run(*alice, *bob, ACK_TIMEOUT_MS);
+
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessagePriority::Critical, bob->app.delivered[0].priority());
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+}
+
+/// The oldest frame on air arrives damaged: one bit of its packed text flipped, as main.cpp sees it
+static bool receiveCorrupted(Node &node)
+{
+    std::vector<uint8_t> frame = node.inbox.front();
+    node.inbox.pop_front();
+    frame[4] ^= 0x01;
+    TEST_ASSERT_FALSE(Message::crcMatches(frame.data(), frame.size()));
+    return node.core.nack(frame.data(), frame.size(), frame.size(), DecodeFailureReason::CrcMismatch);
+}
+
+void test_corrupted_text_is_nacked_and_retransmitted_early()
+{
+    alice->core.submit(Message::createText(3, "SOS", MessagePriority::Critical));
+    alice->core.tick(0);
+    TEST_ASSERT_TRUE(receiveCorrupted(*bob));
+    bob->core.tick(10);
+    TEST_ASSERT_EQUAL(FrameResult::Handled, receiveFrame(*alice, 10));
+
+    // Retransmitted well before the ACK timeout, then ACKed
+    run(*alice, *bob, 20);
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_nack_never_fails_a_text()
+{
+    // Normal priority: no retransmission to hurry, the ACK wait runs on
+    alice->core.submit(Message::createText(4, "HI"));
+    alice->core.tick(0);
+    TEST_ASSERT_TRUE(receiveCorrupted(*bob));
+    run(*alice, *bob, 10);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(0, alice->app.delivered.size());
+}
+
+void test_only_damaged_texts_with_a_readable_header_are_nacked()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createText(9, "HELLO").serialize(buf, sizeof(buf));
+    uint8_t seq = 0;
+    TEST_ASSERT_TRUE(BridgeCore::damagedTextSeq(buf, len, len, seq));
+    TEST_ASSERT_EQUAL(9, seq);
+
+    // Truncated after the seq: nothing left to contradict it
+    TEST_ASSERT_TRUE(BridgeCore::damagedTextSeq(buf, 2, len, seq));
+
+    // Length fields that disagree, a frame too short for them, or no text at all
+    buf[3]++;
+    TEST_ASSERT_FALSE(BridgeCore::damagedTextSeq(buf, len, len, seq));
+    buf[3]--;
+    TEST_ASSERT_FALSE(BridgeCore::damagedTextSeq(buf, len, len - 2, seq));
+    len = Message::createAck(9).serialize(buf, sizeof(buf));
+    TEST_ASSERT_FALSE(BridgeCore::damagedTextSeq(buf, len, len, seq));
+
+    // Nobody waits for a NACK in fire-and-forget mode
+    len = Message::createText(9, "HELLO").serialize(buf, sizeof(buf));
+    bob->core.setFireAndForget(true);
+    TEST_ASSERT_FALSE(bob->core.nack(buf, len, len, DecodeFailureReason::CrcMismatch));
+}
+
+void test_priority_jumps_the_queue()
+{
+    alice->core.submit(Message::createText(1, "FIRST"));
+    alice->core.submit(Message::createText(2, "SECOND"));
+    alice->core.submit(Message::createText(3, "URGENT", MessagePriority::High));
+    for (unsigned long now = 0; bob->app.delivered.size() < 3 && now < 10; now++)
+    {
+        run(*alice, *bob, now);
+    }
+
+    TEST_ASSERT_EQUAL(3, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(3, bob->app.delivered[0].textData.seq);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered[1].textData.seq);
+    TEST_ASSERT_EQUAL(2, bob->app.delivered[2].textData.seq);
+}
+
+void test_position_goes_out_before_queued_texts()
+{
+    alice->core.submit(Message::createText(1, "FIRST"));
+    alice->core.submit(Message::createText(2, "SECOND"));
+    alice->core.submit(Message::createTextWithGps(3, "", 47000000, 8000000));
+    alice->core.submit(Message::createText(4, "SOS", MessagePriority::Critical));
+    for (unsigned long now = 0; bob->app.delivered.size() < 4 && now < 10; now++)
+    {
+        run(*alice, *bob, now);
+    }
+
+    TEST_ASSERT_EQUAL(4, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(4, bob->app.delivered[0].textData.seq);
+    TEST_ASSERT_EQUAL(3, bob->app.delivered[1].textData.seq);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered[2].textData.seq);
+    TEST_ASSERT_EQUAL(2, bob->app.delivered[3].textData.seq);
+}
+
+void test_position_updates_are_coalesced()
+{
+    alice->core.submit(Message::createTextWithGps(1, "", 47000000, 8000000));
+    alice->core.submit(Message::createTextWithGps(2, "", 47000100, 8000100));
+    run(*alice, *bob, 0);
+
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(2, bob->app.delivered[0].textData.seq);
+    TEST_ASSERT_EQUAL(47000100, bob->app.delivered[0].textData.lat);
+}
+
+void test_audit_log_records_exchange()
+{
+    alice->core.submit(Message::createText(4, "LOG ME"));
+    run(*alice, *bob, 0);
+
+    AuditEntry entry;
+    TEST_ASSERT_EQUAL(2, alice->audit.getTotal());
+    TEST_ASSERT_TRUE(alice->audit.get(0, entry));
+    TEST_ASSERT_EQUAL(AuditOutcome::Sent, entry.outcome);
+    TEST_ASSERT_TRUE(alice->audit.get(1, entry));
+    TEST_ASSERT_EQUAL(AuditOutcome::Acked, entry.outcome);
+    TEST_ASSERT_EQUAL(4, entry.seq);
+
+    TEST_ASSERT_EQUAL(1, bob->audit.getTotal());
+    TEST_ASSERT_TRUE(bob->audit.get(0, entry));
+    TEST_ASSERT_EQUAL(AuditDirection::Rx, entry.direction);
+    TEST_ASSERT_EQUAL(AuditOutcome::Received, entry.outcome);
+}
+
+void test_audit_log_pages_after_wrap()
+{
+    AuditLog log;
+    for (uint32_t i = 0; i < AUDIT_LOG_CAPACITY + 5; i++)
+    {
+        log.record(i, AuditDirection::Tx, MessageType::Text, i, AuditOutcome::Sent);
+    }
+    TEST_ASSERT_EQUAL(5, log.getFirstIndex());
+
+    // A reader asking for overwritten entries gets the oldest one still held
+    uint8_t page[9 + AUDIT_PAGE_ENTRIES * AUDIT_ENTRY_LEN];
+    size_t len = log.serializePage(0, AUDIT_PAGE_ENTRIES, page);
+    TEST_ASSERT_EQUAL(9 + AUDIT_PAGE_ENTRIES * AUDIT_ENTRY_LEN, len);
+    TEST_ASSERT_EQUAL(AUDIT_LOG_CAPACITY + 5, page[0]);
+    TEST_ASSERT_EQUAL(5, page[4]);
+    TEST_ASSERT_EQUAL(AUDIT_PAGE_ENTRIES, page[8]);
+    TEST_ASSERT_EQUAL(5, page[9]);      // First entry's timestamp (LSB)
+    TEST_ASSERT_EQUAL(5, page[9 + 6]);  // and seq
+
+    // Past the newest entry the page is empty
+    len = log.serializePage(log.getTotal(), AUDIT_PAGE_ENTRIES, page);
+    TEST_ASSERT_EQUAL(9, len);
+    TEST_ASSERT_EQUAL(0, page[8]);
+}
+
+void test_weak_text_is_delivered_without_ack()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createText(5, "FAINT").serialize(buf, sizeof(buf));
+    Message msg;
+    bob->core.onRadioFrame(buf, len, ACK_MIN_RSSI_DBM - 1, 0, msg);
+
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(0, bob->radio.framesSent);
+
+    AuditEntry entry;
+    TEST_ASSERT_TRUE(bob->audit.get(0, entry));
+    TEST_ASSERT_EQUAL(AuditOutcome::AckWithheld, entry.outcome);
+}
+
+void test_reused_seq_waits_for_ack_timeout()
+{
+    alice->radio.dropNext = 1;
+    alice->core.submit(Message::createText(9, "FIRST"));
+    alice->core.submit(Message::createText(9, "AGAIN"));
+    run(*alice, *bob, 0);
+
+    // The second text would make an ACK for seq 9 ambiguous - it waits
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_TRUE(alice->core.hasPendingWork());
+
+    run(*alice, *bob, ACK_TIMEOUT_MS);
+
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, alice->stats.ackTimeouts);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL_STRING("AGAIN", bob->app.delivered[0].textData.text);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_reused_seq_waits_for_ack()
+{
+    bob->radio.dropNext = 1; // Keeps the first ACK from arriving at once
+    alice->core.submit(Message::createText(9, "FIRST"));
+    alice->core.submit(Message::createText(1, "OTHER"));
+    alice->core.submit(Message::createText(9, "AGAIN"));
+    run(*alice, *bob, 0);
+
+    // Seq 1 went out, seq 9 is held behind its lost ACK
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+
+    // A late ACK for seq 9 releases the held text
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createAck(9).serialize(buf, sizeof(buf));
+    Message msg;
+    alice->core.onRadioFrame(buf, len, GOOD_RSSI, 10, msg);
+    run(*alice, *bob, 10);
+
+    TEST_ASSERT_EQUAL(3, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(3, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL_STRING("AGAIN", bob->app.delivered[2].textData.text);
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+}
+
+void test_retransmission_after_lost_ack_is_acked_again()
+{
+    bob->radio.dropNext = 1;
+    alice->core.submit(Message::createText(4, "SOS", MessagePriority::Critical));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+
+    run(*alice, *bob, ACK_TIMEOUT_MS);
+
+    TEST_ASSERT_EQUAL(2, bob->radio.framesSent);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+
+    // The retransmission is ACKed, but Bob's phone gets the text once
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+}
+
+void test_duplicates_are_found_across_the_seq_wrap()
+{
+    // 250..255 then 0..5: the window holds seqs from both sides of the wrap
+    Message msg;
+    uint8_t frames[12][MAX_SERIALIZED_LEN];
+    int lens[12];
+    for (int i = 0; i < 12; i++)
+    {
+        lens[i] = Message::createText(static_cast<uint8_t>(250 + i), "WRAP").serialize(frames[i], MAX_SERIALIZED_LEN);
+        bob->core.onRadioFrame(frames[i], lens[i], GOOD_RSSI, i * ACK_TIMEOUT_MS, msg);
+    }
+    TEST_ASSERT_EQUAL(12, bob->app.delivered.size());
+
+    // Copies of 253 and 2 are ACKed again, not delivered
+    bob->core.onRadioFrame(frames[3], lens[3], GOOD_RSSI, 12 * ACK_TIMEOUT_MS, msg);
+    bob->core.onRadioFrame(frames[8], lens[8], GOOD_RSSI, 12 * ACK_TIMEOUT_MS, msg);
+    TEST_ASSERT_EQUAL(12, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(14, bob->radio.framesSent);
+
+    // Once RX_DEDUP_ENTRIES newer frames pushed it out, the seq counts as new again
+    for (int i = 0; i < RX_DEDUP_ENTRIES; i++)
+    {
+        uint8_t buf[MAX_SERIALIZED_LEN];
+        int len = Message::createText(static_cast<uint8_t>(100 + i), "FILL").serialize(buf, sizeof(buf));
+        bob->core.onRadioFrame(buf, len, GOOD_RSSI, 13 * ACK_TIMEOUT_MS, msg);
+    }
+    size_t delivered = bob->app.delivered.size();
+    bob->core.onRadioFrame(frames[3], lens[3], GOOD_RSSI, 14 * ACK_TIMEOUT_MS, msg);
+    TEST_ASSERT_EQUAL(delivered + 1, bob->app.delivered.size());
+}
+
+void test_echoed_text_is_not_acked_again()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createText(5, "ECHO").serialize(buf, sizeof(buf));
+    Message msg;
+    bob->core.onRadioFrame(buf, len, GOOD_RSSI, 0, msg);
+    bob->core.onRadioFrame(buf, len, GOOD_RSSI, ACK_TIMEOUT_MS / 4, msg);
+
+    // Delivered and ACKed once
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+
+    // Same seq, new text: a reused seq, not a copy
+    len = Message::createText(5, "NEW").serialize(buf, sizeof(buf));
+    bob->core.onRadioFrame(buf, len, GOOD_RSSI, ACK_TIMEOUT_MS / 4, msg);
+    TEST_ASSERT_EQUAL(2, bob->radio.framesSent);
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+}
+
+void test_texts_in_flight_time_out_independently()
+{
+    alice->radio.dropNext = 2;
+    alice->core.submit(Message::createText(1, "ONE"));
+    run(*alice, *bob, 0);
+    alice->core.submit(Message::createText(2, "TWO"));
+    run(*alice, *bob, ACK_TIMEOUT_MS / 2);
+
+    run(*alice, *bob, ACK_TIMEOUT_MS);
+    TEST_ASSERT_EQUAL(1, alice->stats.ackTimeouts);
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+
+    run(*alice, *bob, ACK_TIMEOUT_MS + ACK_TIMEOUT_MS / 2);
+    TEST_ASSERT_EQUAL(2, alice->stats.ackTimeouts);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_tx_waits_for_reception_in_progress()
+{
+    alice->radio.receiving = true;
+    alice->core.submit(Message::createText(6, "WAIT"));
+    run(*alice, *bob, 0);
+    run(*alice, *bob, 10);
+
+    // Deferred, and the app is told once
+    TEST_ASSERT_EQUAL(0, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, alice->app.statuses.size());
+    TEST_ASSERT_EQUAL(BridgeStatus::RadioBusy, alice->app.statuses[0].statusData.code);
+    TEST_ASSERT_EQUAL(6, alice->app.statuses[0].statusData.seq);
+
+    alice->radio.receiving = false;
+    run(*alice, *bob, 20);
+
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(2, alice->app.statuses.size());
+    TEST_ASSERT_EQUAL(BridgeStatus::TxResumed, alice->app.statuses[1].statusData.code);
+}
+
+void test_stuck_reception_defers_for_one_ack_timeout()
+{
+    alice->radio.receiving = true;
+    alice->core.submit(Message::createText(6, "STUCK"));
+    run(*alice, *bob, 0);
+    run(*alice, *bob, ACK_TIMEOUT_MS - 1);
+    TEST_ASSERT_EQUAL(0, alice->radio.framesSent);
+
+    run(*alice, *bob, ACK_TIMEOUT_MS);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+}
+
+void test_lbt_backoff_doubles_its_window_up_to_16_slots()
+{
+    TEST_ASSERT_EQUAL(LBT_BACKOFF_SLOT_MS, lbtBackoffMs(1, 0));
+    TEST_ASSERT_EQUAL(2 * LBT_BACKOFF_SLOT_MS, lbtBackoffMs(1, 1));
+    TEST_ASSERT_EQUAL(LBT_BACKOFF_SLOT_MS, lbtBackoffMs(1, 2)); // Wraps within 1-2 slots
+    TEST_ASSERT_EQUAL(4 * LBT_BACKOFF_SLOT_MS, lbtBackoffMs(2, 3));
+    TEST_ASSERT_EQUAL(16 * LBT_BACKOFF_SLOT_MS, lbtBackoffMs(4, 15));
+    TEST_ASSERT_EQUAL(16 * LBT_BACKOFF_SLOT_MS, lbtBackoffMs(9, 31)); // Capped
+    for (uint32_t random = 0; random < 1000; random += 7)
+    {
+        unsigned long wait = lbtBackoffMs(3, random);
+        TEST_ASSERT_TRUE(wait >= LBT_BACKOFF_SLOT_MS && wait <= 8 * LBT_BACKOFF_SLOT_MS);
+    }
+}
+
+void test_busy_channel_backs_off_before_transmitting()
+{
+    alice->core.setCadAttempts(3);
+    bob->core.setCadAttempts(3);
+    alice->radio.busyCads = 1;
+    alice->core.submit(Message::createText(6, "LBT"));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_EQUAL(1, alice->radio.cads);
+    TEST_ASSERT_EQUAL(0, alice->radio.framesSent);
+
+    // The first backoff is 1-2 slots
+    run(*alice, *bob, 2 * LBT_BACKOFF_SLOT_MS);
+    TEST_ASSERT_EQUAL(2, alice->radio.cads);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+
+    // The ACK answers at once, without a CAD
+    TEST_ASSERT_EQUAL(0, bob->radio.cads);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_jammed_channel_transmits_after_the_cad_attempts()
+{
+    alice->core.setCadAttempts(2);
+    alice->radio.busyCads = 100;
+    alice->core.submit(Message::createText(6, "JAM"));
+    run(*alice, *bob, 0);
+    run(*alice, *bob, 2 * LBT_BACKOFF_SLOT_MS);
+    TEST_ASSERT_EQUAL(2, alice->radio.cads);
+    TEST_ASSERT_EQUAL(0, alice->radio.framesSent);
+
+    // Second backoff is 1-4 slots, then it goes out without another CAD
+    run(*alice, *bob, 6 * LBT_BACKOFF_SLOT_MS);
+    TEST_ASSERT_EQUAL(2, alice->radio.cads);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+}
+
+void test_no_cad_without_lbt()
+{
+    alice->core.setCadAttempts(0);
+    alice->radio.busyCads = 1;
+    alice->core.submit(Message::createText(6, "NOW"));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_EQUAL(0, alice->radio.cads);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+}
+
+void test_duty_cycle_sums_the_rolling_window()
+{
+    // 1% of an hour in one-minute slots
+    DutyCycle duty(3600000, 36000);
+    duty.record(0, 20000);
+    TEST_ASSERT_TRUE(duty.allows(1000, 16000));
+    TEST_ASSERT_FALSE(duty.allows(1000, 16001));
+    duty.record(1000, 16000);
+    TEST_ASSERT_EQUAL(36000, duty.usedMs(1000));
+
+    // Still counted a full hour later - the minute it fell in isn't over yet
+    TEST_ASSERT_EQUAL(36000, duty.usedMs(3600000));
+    TEST_ASSERT_FALSE(duty.allows(3600000, 1000));
+    TEST_ASSERT_EQUAL(0, duty.usedMs(3660000));
+    TEST_ASSERT_TRUE(duty.allows(3660000, 36000));
+}
+
+void test_duty_cycle_keeps_later_slots_and_survives_long_idle()
+{
+    DutyCycle duty(3600000, 36000);
+    duty.record(0, 10000);
+    duty.record(1800000, 10000);
+    TEST_ASSERT_EQUAL(10000, duty.usedMs(3660000)); // Only the first fell out
+    TEST_ASSERT_EQUAL(0, duty.usedMs(100000000));
+
+    // A frame longer than the whole budget still goes out once the window is empty
+    TEST_ASSERT_TRUE(duty.allows(100000000, 40000));
+    duty.record(100000000, 40000);
+    TEST_ASSERT_FALSE(duty.allows(100000001, 1));
+}
+
+void test_spent_duty_cycle_holds_the_text_and_tells_the_app()
+{
+    alice->radio.airtimeLeft = false;
+    alice->core.submit(Message::createText(6, "LATER"));
+    run(*alice, *bob, 0);
+    run(*alice, *bob, 10 * ACK_TIMEOUT_MS);
+
+    // Held as long as it takes, and the app is told once
+    TEST_ASSERT_EQUAL(0, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, alice->app.statuses.size());
+    TEST_ASSERT_EQUAL(BridgeStatus::DutyCycleLimited, alice->app.statuses[0].statusData.code);
+    TEST_ASSERT_EQUAL(6, alice->app.statuses[0].statusData.seq);
+
+    alice->radio.airtimeLeft = true;
+    run(*alice, *bob, 11 * ACK_TIMEOUT_MS);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(2, alice->app.statuses.size());
+    TEST_ASSERT_EQUAL(BridgeStatus::TxResumed, alice->app.statuses[1].statusData.code);
+}
+
+void test_spent_duty_cycle_still_sends_acks()
+{
+    bob->radio.airtimeLeft = false;
+    alice->core.submit(Message::createText(6, "ACK ME"));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+void test_unknown_frame_is_relayed_once()
+{
+    const uint8_t future[] = {0x42, 0x01, 0x02, 0x03};
+    TEST_ASSERT_FALSE(isKnownMessageType(future[0]));
+
+    TEST_ASSERT_TRUE(bob->core.relay(future, sizeof(future), 0));
+    run(*alice, *bob, 0);
+
+    // Goes back on air unchanged
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+    TEST_ASSERT_EQUAL(0, alice->app.delivered.size());
+
+    // Heard again (e.g. from another relay) - not relayed a second time
+    TEST_ASSERT_FALSE(bob->core.relay(future, sizeof(future), RELAY_DEDUP_MS - 1));
+    TEST_ASSERT_TRUE(bob->core.relay(future, sizeof(future), RELAY_DEDUP_MS));
+}
+
+void test_relayed_frame_is_sent_verbatim()
+{
+    std::deque<std::vector<uint8_t>> air;
+    bob->radio.peerInbox = &air;
+    const uint8_t future[] = {0x42, 0xAA, 0xBB};
+    bob->core.relay(future, sizeof(future), 0);
+    bob->core.tick(0);
+
+    TEST_ASSERT_EQUAL(1, air.size());
+    TEST_ASSERT_EQUAL(sizeof(future), air[0].size());
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(future, air[0].data(), sizeof(future));
+}
+
+void test_relay_hop_acks_a_text_that_asks_for_it()
+{
+    Message text = Message::createText(1, "HI", MessagePriority::High);
+    text.textData.hopAck = true; // As submitted under ACK_SEMANTICS_HOP
+    alice->core.submit(text);
+    alice->core.tick(0);
+    std::vector<uint8_t> frame = bob->inbox.front();
+    bob->inbox.pop_front();
+
+    // Bob relays: the hop ACK goes out first, then the frame unchanged
+    std::deque<std::vector<uint8_t>> air;
+    bob->radio.peerInbox = &air;
+    TEST_ASSERT_TRUE(bob->core.relay(frame.data(), frame.size(), 0));
+    bob->core.tick(0);
+    bob->core.tick(0);
+    TEST_ASSERT_EQUAL(2, air.size());
+    Message hop;
+    TEST_ASSERT_TRUE(hop.deserialize(air[0].data(), air[0].size()));
+    TEST_ASSERT_EQUAL(MessageType::HopAck, hop.type);
+    TEST_ASSERT_EQUAL(1, hop.hopAckData.seq);
+    TEST_ASSERT_EQUAL(NODE_ID, hop.hopAckData.relayNode);
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(frame.data(), air[1].data(), frame.size());
+
+    // The hop ACK completes the text: reported as relayed, never retransmitted
+    Message msg;
+    TEST_ASSERT_EQUAL(FrameResult::Handled, alice->core.onRadioFrame(air[0].data(), air[0].size(), GOOD_RSSI, 10, msg));
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Relayed, alice->app.delivered[0].deliveryReportData.outcome);
+    alice->core.tick(2 * ACK_TIMEOUT_MS);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+
+    // A retransmission heard again is answered again but not relayed twice
+    TEST_ASSERT_FALSE(bob->core.relay(frame.data(), frame.size(), 100));
+    bob->core.tick(100);
+    TEST_ASSERT_EQUAL(3, air.size());
+    TEST_ASSERT_EQUAL_HEX8(static_cast<uint8_t>(MessageType::HopAck), air[2][0]);
+}
+
+void test_end_to_end_text_waits_past_a_hop_ack()
+{
+    alice->core.submit(Message::createText(1, "HI"));
+    alice->core.tick(0);
+    std::vector<uint8_t> frame = bob->inbox.front();
+    bob->inbox.pop_front();
+
+    // No hop ACK is asked for, so the relay only repeats the frame
+    std::deque<std::vector<uint8_t>> air;
+    bob->radio.peerInbox = &air;
+    TEST_ASSERT_TRUE(bob->core.relay(frame.data(), frame.size(), 0));
+    bob->core.tick(0);
+    bob->core.tick(0);
+    TEST_ASSERT_EQUAL(1, air.size());
+
+    // A stray hop ACK doesn't complete it; the destination's ACK does
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    Message msg;
+    int len = Message::createHopAck(1, 9).serialize(buf, sizeof(buf));
+    alice->core.onRadioFrame(buf, len, GOOD_RSSI, 10, msg);
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+    len = Message::createAck(1).serialize(buf, sizeof(buf));
+    alice->core.onRadioFrame(buf, len, GOOD_RSSI, 20, msg);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Delivered, alice->app.delivered.back().deliveryReportData.outcome);
+}
+
+void test_only_end_to_end_frames_are_relayable()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    size_t len = Message::createText(1, "HI").serialize(buf, sizeof(buf));
+    TEST_ASSERT_TRUE(BridgeCore::isRelayable(buf, len));
+    len = Message::createAck(1).serialize(buf, sizeof(buf));
+    TEST_ASSERT_TRUE(BridgeCore::isRelayable(buf, len));
+
+    len = Message::createChannelSwitch(433920000, ChannelSwitchStage::Propose).serialize(buf, sizeof(buf));
+    TEST_ASSERT_FALSE(BridgeCore::isRelayable(buf, len));
+    len = Message::createHopAck(1, 2).serialize(buf, sizeof(buf));
+    TEST_ASSERT_FALSE(BridgeCore::isRelayable(buf, len));
+    len = Message::createNack(1, DecodeFailureReason::CrcMismatch).serialize(buf, sizeof(buf));
+    TEST_ASSERT_FALSE(BridgeCore::isRelayable(buf, len));
+    ListenScheduleMessage schedule = {1, 1, 60000, 10000, 0};
+    len = Message::createListenSchedule(schedule).serialize(buf, sizeof(buf));
+    TEST_ASSERT_FALSE(BridgeCore::isRelayable(buf, len));
+    TEST_ASSERT_FALSE(BridgeCore::isRelayable(buf, 0));
+}
+
+void test_protocol_profiles()
+{
+    Message note = Message::createText(1, "NOTE");
+    Message noteWithGps = Message::createTextWithGps(2, "NOTE", 47000000, 8000000);
+    Message fix = Message::createTextWithGps(3, "", 47000000, 8000000);
+    Message ack = Message::createAck(1);
+
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(note, PROTOCOL_PROFILE_ALL));
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(noteWithGps, PROTOCOL_PROFILE_ALL));
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(fix, PROTOCOL_PROFILE_ALL));
+
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(note, PROTOCOL_PROFILE_MESSAGING));
+    TEST_ASSERT_FALSE(BridgeCore::profileAllows(noteWithGps, PROTOCOL_PROFILE_MESSAGING));
+    TEST_ASSERT_FALSE(BridgeCore::profileAllows(fix, PROTOCOL_PROFILE_MESSAGING));
+
+    // Standalone variants: a note and a position travel separately
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(note, PROTOCOL_PROFILE_TRACKING));
+    TEST_ASSERT_FALSE(BridgeCore::profileAllows(noteWithGps, PROTOCOL_PROFILE_TRACKING));
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(fix, PROTOCOL_PROFILE_TRACKING));
+
+    // TextWithGps only
+    TEST_ASSERT_FALSE(BridgeCore::profileAllows(note, PROTOCOL_PROFILE_COMBINED));
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(noteWithGps, PROTOCOL_PROFILE_COMBINED));
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(fix, PROTOCOL_PROFILE_COMBINED));
+    TEST_ASSERT_TRUE(BridgeCore::profileAllows(ack, PROTOCOL_PROFILE_COMBINED));
+}
+
+void test_texts_outside_the_profile_are_reported()
+{
+    bob->core.setProtocolProfile(PROTOCOL_PROFILE_COMBINED);
+
+    // From the app: refused with a Failed report instead of vanishing
+    TEST_ASSERT_FALSE(bob->core.submit(Message::createText(4, "NO GPS")));
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Failed, bob->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(4, bob->app.delivered[0].deliveryReportData.seq);
+
+    // Over LoRa: NACKed as Rejected, which fails the text at once instead of
+    // after its retries and ACK wait
+    alice->core.submit(Message::createText(5, "NO GPS", MessagePriority::High));
+    run(*alice, *bob, 0);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Failed, alice->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(5, alice->app.delivered[0].deliveryReportData.seq);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+
+    // A text with GPS still goes through
+    alice->core.submit(Message::createTextWithGps(6, "HERE", 47000000, 8000000));
+    run(*alice, *bob, 10);
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL_STRING("HERE", bob->app.delivered[1].textData.text);
+}
+
+void test_missing_fixes_are_retransmitted_selectively()
+{
+    for (uint8_t seq = 1; seq <= 3; seq++)
+    {
+        alice->radio.dropNext = seq == 2 ? 1 : 0;
+        alice->core.submit(Message::createTextWithGps(seq, "", 47000000 + seq, 8000000));
+        run(*alice, *bob, 0);
+    }
+
+    // No ACK per fix
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(0, bob->radio.framesSent);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+
+    // Status up to seq 3 without seq 2: only seq 2 goes out again
+    run(*alice, *bob, TRACK_STATUS_INTERVAL_MS);
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+    TEST_ASSERT_EQUAL(4, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(3, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(2, bob->app.delivered[2].textData.seq);
+
+    // The next status confirms it, then the track is quiet
+    run(*alice, *bob, 2 * TRACK_STATUS_INTERVAL_MS);
+    run(*alice, *bob, 3 * TRACK_STATUS_INTERVAL_MS);
+    TEST_ASSERT_EQUAL(2, bob->radio.framesSent);
+    TEST_ASSERT_EQUAL(4, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+    TEST_ASSERT_FALSE(alice->core.hasPendingWork());
+}
+
+/// Submit a position update and return the type byte of the frame it went on air as
+static uint8_t sendFix(uint8_t seq, int32_t lat, int32_t lon, unsigned long now)
+{
+    alice->core.submit(Message::createTextWithGps(seq, "", lat, lon));
+    int sent = alice->radio.framesSent;
+    bool lost = alice->radio.dropNext > 0;
+    alice->core.tick(now);
+    TEST_ASSERT_EQUAL(sent + 1, alice->radio.framesSent);
+    uint8_t type = lost ? 0 : bob->inbox.back()[0];
+    run(*alice, *bob, now);
+    return type;
+}
+
+void test_gps_deltas_reach_the_app_as_full_positions()
+{
+    alice->core.setGpsDeltaEncoding(true);
+
+    // No reference yet: full; then small moves as deltas, rebuilt for the app
+    TEST_ASSERT_EQUAL_HEX8(0x01, sendFix(1, 47000000, 8000000, 0));
+    TEST_ASSERT_EQUAL_HEX8(0x07, sendFix(2, 47000250, 7999900, 0));
+    TEST_ASSERT_EQUAL_HEX8(0x07, sendFix(3, 46999800, 8000400, 0));
+    TEST_ASSERT_EQUAL(3, bob->app.delivered.size());
+    const Message &rebuilt = bob->app.delivered[2];
+    TEST_ASSERT_EQUAL(MessageType::Text, rebuilt.type);
+    TEST_ASSERT_EQUAL(3, rebuilt.textData.seq);
+    TEST_ASSERT_TRUE(rebuilt.textData.hasGps);
+    TEST_ASSERT_EQUAL_STRING("", rebuilt.textData.text);
+    TEST_ASSERT_EQUAL_INT32(46999800, rebuilt.textData.lat);
+    TEST_ASSERT_EQUAL_INT32(8000400, rebuilt.textData.lon);
+
+    // A jump beyond an i16 goes out full; it is lost, so the deltas against it are dropped
+    alice->radio.dropNext = 1;
+    TEST_ASSERT_EQUAL_HEX8(0, sendFix(4, 47100000, 8000400, 0));
+    uint8_t seq = 5;
+    for (; seq < 5 + GPS_DELTA_RESYNC_EVERY; seq++)
+    {
+        TEST_ASSERT_EQUAL_HEX8(0x07, sendFix(seq, 47100000 + seq, 8000400, 0));
+    }
+    TEST_ASSERT_EQUAL(3, bob->app.delivered.size());
+
+    // The resync brings the receiver back in step
+    TEST_ASSERT_EQUAL_HEX8(0x01, sendFix(seq, 47100100, 8000400, 0));
+    TEST_ASSERT_EQUAL_HEX8(0x07, sendFix(seq + 1, 47100200, 8000500, 0));
+    TEST_ASSERT_EQUAL(5, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL_INT32(47100200, bob->app.delivered[4].textData.lat);
+    TEST_ASSERT_EQUAL_INT32(8000500, bob->app.delivered[4].textData.lon);
+}
+
+void test_retransmission_waits_for_tx_allowed()
+{
+    alice->radio.dropNext = 1;
+    alice->core.submit(Message::createText(3, "SOS", MessagePriority::Critical));
+    run(*alice, *bob, 0);
+
+    // e.g. the listen window is closed: the retry waits, it isn't used up
+    alice->core.tick(ACK_TIMEOUT_MS, false);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+
+    run(*alice, *bob, ACK_TIMEOUT_MS + 500);
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+}
+
+void test_retransmit_strategies_space_the_retries()
+{
+    // Every frame lost: Critical texts are sent once and retried 4 times
+    alice->radio.dropNext = 100;
+    alice->core.setRetransmitStrategy(RetransmitStrategy::Backoff);
+    alice->core.submit(Message::createText(3, "SOS", MessagePriority::Critical));
+    alice->core.tick(0);
+
+    // Backoff: one ACK timeout, then doubling
+    const unsigned long backoffRetries[] = {1000, 3000, 7000, 15000};
+    for (int i = 0; i < 4; i++)
+    {
+        alice->core.tick(backoffRetries[i] - 1);
+        TEST_ASSERT_EQUAL(1 + i, alice->radio.framesSent);
+        alice->core.tick(backoffRetries[i]);
+        TEST_ASSERT_EQUAL(2 + i, alice->radio.framesSent);
+    }
+
+    // Out of retries: fails one ACK timeout after the last
+    alice->core.tick(15999);
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+    alice->core.tick(16000);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(1, alice->stats.ackTimeouts);
+
+    // Immediate: after every ACK timeout
+    alice->core.setRetransmitStrategy(RetransmitStrategy::Immediate);
+    alice->core.submit(Message::createText(4, "SOS", MessagePriority::Critical));
+    alice->core.tick(20000);
+    for (int i = 1; i <= 4; i++)
+    {
+        alice->core.tick(20000 + i * ACK_TIMEOUT_MS);
+        TEST_ASSERT_EQUAL(6 + i, alice->radio.framesSent);
+    }
+    alice->core.tick(25000);
+    TEST_ASSERT_EQUAL(2, alice->stats.ackTimeouts);
+}
+
+void test_scheduled_retransmission_waits_for_the_next_window()
+{
+    alice->radio.dropNext = 1;
+    alice->core.setRetransmitStrategy(RetransmitStrategy::Scheduled);
+    alice->core.submit(Message::createText(3, "SOS", MessagePriority::Critical));
+    alice->core.tick(0);
+
+    // The ACK timeout runs out while the window is still open: no retry yet
+    alice->core.tick(ACK_TIMEOUT_MS);
+    alice->core.tick(ACK_TIMEOUT_MS + 500);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+
+    // Window closed, then the next one opens: the retry goes out at its start
+    alice->core.tick(3000, false);
+    TEST_ASSERT_EQUAL(1, alice->radio.framesSent);
+    run(*alice, *bob, 5000);
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+}
+
+/// Beacon sent by `from` at txAt and heard by `to` one airtime later, through the wire format
+static BeaconResult hearBeacon(const ListenWindows &from, ListenWindows &to, unsigned long txAt,
+                               unsigned long airtimeMs)
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createListenSchedule(from.beacon(txAt)).serialize(buf, sizeof(buf));
+    TEST_ASSERT_EQUAL(LISTEN_SCHEDULE_LEN, len);
+    Message msg;
+    TEST_ASSERT_TRUE(msg.deserialize(buf, len));
+    return to.onBeacon(msg.listenScheduleData, txAt, txAt + airtimeMs);
+}
+
+void test_listen_windows_follow_lowest_node()
+{
+    ListenWindows a(1), b(2), c(3);
+    a.begin(0);
+    b.begin(12345);
+    TEST_ASSERT_TRUE(b.isOpen(12345 + LISTEN_WINDOW_MS)); // Searching: always listening
+
+    a.update(LISTEN_SEARCH_MS); // Nobody heard - a keeps its own windows
+    TEST_ASSERT_FALSE(a.isSearching());
+
+    unsigned long now = LISTEN_SEARCH_MS + 700;
+    TEST_ASSERT_EQUAL(BeaconResult::Synced, hearBeacon(a, b, now, 300));
+    TEST_ASSERT_FALSE(b.isSearching());
+    TEST_ASSERT_EQUAL_UINT8(1, b.referenceId());
+    for (unsigned long t = now + 300; t < now + 3 * LISTEN_PERIOD_MS; t += 250)
+    {
+        TEST_ASSERT_EQUAL(a.isOpen(t), b.isOpen(t));
+        TEST_ASSERT_EQUAL(a.phase(t), b.phase(t));
+    }
+
+    // Following beacons keep a where it is; a higher reference never moves it
+    TEST_ASSERT_EQUAL(BeaconResult::Refreshed, hearBeacon(b, a, now + 1000, 300));
+    c.begin(now);
+    TEST_ASSERT_EQUAL(BeaconResult::Ignored, hearBeacon(c, a, now + 1000, 300));
+    TEST_ASSERT_EQUAL(BeaconResult::Synced, hearBeacon(b, c, now + 1000, 300));
+    TEST_ASSERT_EQUAL_UINT8(1, c.referenceId());
+
+    ListenScheduleMessage other = a.beacon(now);
+    other.windowMs = LISTEN_WINDOW_MS / 2;
+    TEST_ASSERT_EQUAL(BeaconResult::Mismatch, b.onBeacon(other, now, now));
+}
+
+void test_listen_windows_hold_tx_and_resync()
+{
+    const uint32_t airtimeMs = 1000;
+    ListenWindows a(1);
+    a.begin(0);
+    TEST_ASSERT_FALSE(a.canTransmit(LISTEN_GUARD_MS, airtimeMs)); // Still searching
+    a.update(LISTEN_SEARCH_MS);
+
+    unsigned long opened = LISTEN_SEARCH_MS;
+    TEST_ASSERT_FALSE(a.canTransmit(opened, airtimeMs)); // Guard
+    TEST_ASSERT_TRUE(a.canTransmit(opened + LISTEN_GUARD_MS, airtimeMs));
+    TEST_ASSERT_TRUE(a.canTransmit(opened + LISTEN_WINDOW_MS - LISTEN_GUARD_MS - airtimeMs, airtimeMs));
+    TEST_ASSERT_FALSE(a.canTransmit(opened + LISTEN_WINDOW_MS - LISTEN_GUARD_MS - airtimeMs + 1, airtimeMs));
+    TEST_ASSERT_FALSE(a.isOpen(opened + LISTEN_WINDOW_MS));
+    TEST_ASSERT_EQUAL(LISTEN_PERIOD_MS - LISTEN_WINDOW_MS, a.msUntilChange(opened + LISTEN_WINDOW_MS));
+
+    // One beacon per beacon window
+    int beacons = 0;
+    for (unsigned long t = opened; t < opened + LISTEN_BEACON_EVERY * LISTEN_PERIOD_MS; t += 50)
+    {
+        beacons += a.beaconDue(t, airtimeMs) ? 1 : 0;
+    }
+    TEST_ASSERT_EQUAL(1, beacons);
+
+    // Nothing heard for too long: listen continuously again
+    a.update(opened + LISTEN_SYNC_LOST_MS);
+    TEST_ASSERT_TRUE(a.isSearching());
+    TEST_ASSERT_TRUE(a.isOpen(opened + LISTEN_SYNC_LOST_MS + LISTEN_WINDOW_MS));
+}
+
+void test_garbage_is_not_handled()
+{
+    const uint8_t garbage[] = {0x42, 0x13, 0x37};
+    Message msg;
+    TEST_ASSERT_EQUAL(FrameResult::Unparseable, bob->core.onRadioFrame(garbage, sizeof(garbage), GOOD_RSSI, 0, msg));
+    TEST_ASSERT_EQUAL(0, bob->radio.framesSent);
+}
+
+void test_periodic_listen_alternates_window_and_sleep()
+{
+    const unsigned long period = PERIODIC_RX_WINDOW_MS + PERIODIC_RX_SLEEP_MS;
+    PeriodicListen listen;
+    listen.begin(5000);
+
+    // Open right away, closed for the sleep, open again one period later
+    TEST_ASSERT_TRUE(listen.isOpen(5000));
+    TEST_ASSERT_EQUAL(PERIODIC_RX_WINDOW_MS, listen.msUntilChange(5000));
+    TEST_ASSERT_TRUE(listen.isOpen(5000 + PERIODIC_RX_WINDOW_MS - 1));
+    TEST_ASSERT_FALSE(listen.isOpen(5000 + PERIODIC_RX_WINDOW_MS));
+    TEST_ASSERT_EQUAL(PERIODIC_RX_SLEEP_MS, listen.msUntilChange(5000 + PERIODIC_RX_WINDOW_MS));
+    TEST_ASSERT_FALSE(listen.isOpen(5000 + period - 1));
+    TEST_ASSERT_EQUAL(1, listen.msUntilChange(5000 + period - 1));
+    TEST_ASSERT_TRUE(listen.isOpen(5000 + period));
+    TEST_ASSERT_TRUE(listen.isOpen(5000 + 100 * period + 1));
+
+    // Over many periods the window is open window / period of the time
+    unsigned long open = 0;
+    for (unsigned long now = 5000; now < 5000 + 10 * period; now += 10)
+    {
+        open += listen.isOpen(now) ? 10 : 0;
+    }
+    TEST_ASSERT_EQUAL(10 * PERIODIC_RX_WINDOW_MS, open);
+    TEST_ASSERT_EQUAL(100 * PERIODIC_RX_WINDOW_MS / period, PeriodicListen::listenPercent());
+
+    // The schedule never slips: the wake-up found by msUntilChange() is a window start
+    unsigned long now = 5000 + 3 * period + PERIODIC_RX_WINDOW_MS + 123;
+    unsigned long wake = now + listen.msUntilChange(now);
+    TEST_ASSERT_EQUAL(0, listen.phase(wake));
+    TEST_ASSERT_TRUE(listen.isOpen(wake));
+}
+
+/// Add the same SNR to the monitor n times
+static void addSnr(AdrMonitor &adr, float snr, int n)
+{
+    for (int i = 0; i < n; i++)
+    {
+        adr.addSample(snr);
+    }
+}
+
+void test_adr_steps_faster_only_on_a_full_strong_window()
+{
+    TEST_ASSERT_EQUAL_FLOAT(-7.5f, loraDemodulationFloorDb(7));
+    TEST_ASSERT_EQUAL_FLOAT(-20.0f, loraDemodulationFloorDb(12));
+
+    // SF8's floor is -10 dB: SNR -2 dB clears it by the 8 dB margin
+    AdrMonitor adr(8, 8, 3, 3);
+    addSnr(adr, -2.0f, 7);
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(9, 7, 12)); // Window not full yet
+    adr.addSample(-2.0f);
+    TEST_ASSERT_EQUAL(AdrDecision::Faster, adr.decide(9, 7, 12));
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(9, 9, 12)); // Already at the fastest allowed
+
+    // One fade in the window holds it back until it has slid out
+    adr.addSample(-5.0f);
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(9, 7, 12));
+    addSnr(adr, -2.0f, 7);
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(9, 7, 12));
+    adr.addSample(-2.0f);
+    TEST_ASSERT_EQUAL(AdrDecision::Faster, adr.decide(9, 7, 12));
+
+    adr.reset();
+    TEST_ASSERT_EQUAL(0, adr.sampleCount());
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(9, 7, 12));
+}
+
+void test_adr_steps_slower_on_a_weak_window_or_missed_acks()
+{
+    // SF9's floor is -12.5 dB: an average of -10 dB leaves less than 3 dB
+    AdrMonitor adr(8, 8, 3, 3);
+    addSnr(adr, -8.0f, 4);
+    addSnr(adr, -12.0f, 4);
+    TEST_ASSERT_EQUAL_FLOAT(-10.0f, adr.averageSnr());
+    TEST_ASSERT_EQUAL_FLOAT(-12.0f, adr.worstSnr());
+    TEST_ASSERT_EQUAL(AdrDecision::Slower, adr.decide(9, 7, 12));
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(9, 7, 9)); // Already at the configured factor
+
+    // Lost frames leave no samples: missed ACKs in a row step up on their own
+    AdrMonitor silent(8, 8, 3, 3);
+    silent.addMissedAck();
+    silent.addMissedAck();
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, silent.decide(8, 7, 12));
+    silent.addSample(5.0f); // The peer was heard after all
+    silent.addMissedAck();
+    silent.addMissedAck();
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, silent.decide(8, 7, 12));
+    silent.addMissedAck();
+    TEST_ASSERT_EQUAL(AdrDecision::Slower, silent.decide(8, 7, 12));
+}
+
+void test_adr_hysteresis_keeps_the_link_after_a_step()
+{
+    AdrMonitor adr(16, 8, 3, 3);
+
+    // Strong enough for SF8 at SF9, then the same link at SF8 neither steps back nor further
+    addSnr(adr, -2.0f, 16);
+    TEST_ASSERT_EQUAL(AdrDecision::Faster, adr.decide(9, 7, 12));
+    TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(8, 7, 12));
+
+    // Anywhere in the band between the margins holds: 3 to 8 dB over SF8's floor
+    for (float snr = -6.9f; snr < -2.0f; snr += 0.5f)
+    {
+        adr.reset();
+        addSnr(adr, snr, 16);
+        TEST_ASSERT_EQUAL(AdrDecision::Hold, adr.decide(8, 7, 12));
+    }
+
+    // A slowly fading link steps up once it drops below the band
+    adr.reset();
+    float snr = -2.0f;
+    int steps = 0;
+    while (adr.decide(8, 7, 12) == AdrDecision::Hold && steps < 200)
+    {
+        adr.addSample(snr);
+        snr -= 0.1f;
+        steps++;
+    }
+    TEST_ASSERT_EQUAL(AdrDecision::Slower, adr.decide(8, 7, 12));
+    TEST_ASSERT_TRUE(adr.averageSnr() < -7.0f);
+}
+
+void test_backoff_doubles_to_cap_and_resets()
+{
+    Backoff backoff(1000, 5000);
+    TEST_ASSERT_TRUE(backoff.ready(0));
+
+    backoff.fail(100);
+    TEST_ASSERT_EQUAL(1000, backoff.delayMs());
+    TEST_ASSERT_FALSE(backoff.ready(1099));
+    TEST_ASSERT_TRUE(backoff.ready(1100));
+
+    backoff.fail(1100);
+    TEST_ASSERT_EQUAL(2000, backoff.delayMs());
+    backoff.fail(3100);
+    TEST_ASSERT_EQUAL(4000, backoff.delayMs());
+    backoff.fail(7100);
+    TEST_ASSERT_EQUAL(5000, backoff.delayMs());
+    backoff.fail(12100);
+    TEST_ASSERT_EQUAL(5000, backoff.delayMs());
+    TEST_ASSERT_EQUAL(5, backoff.failures());
+
+    backoff.reset();
+    TEST_ASSERT_TRUE(backoff.ready(12101));
+    backoff.fail(12101);
+    TEST_ASSERT_EQUAL(1000, backoff.delayMs());
+}
+
+void test_position_flood_does_not_evict_texts()
+{
+    BleMessageBuffer buffer;
+    buffer.addByPriority(Message::createText(1, "HELLO"));
+    for (uint8_t seq = 2; seq < 2 + 3 * BLE_BUFFER_POSITIONS; seq++)
+    {
+        buffer.addByPriority(Message::createTextWithGps(seq, "", 47000000, 8000000));
+    }
+    buffer.addByPriority(Message::createAck(9));
+    buffer.addByPriority(Message::createText(10, "WORLD"));
+
+    TEST_ASSERT_EQUAL(2, buffer.getCount(BufferClass::Text));
+    TEST_ASSERT_EQUAL(BLE_BUFFER_POSITIONS, buffer.getCount(BufferClass::Position));
+    TEST_ASSERT_TRUE(buffer.isFull(Message::createTextWithGps(20, "", 0, 0)));
+    TEST_ASSERT_FALSE(buffer.isFull(Message::createText(20, "HI")));
+
+    // Round-robin: text, position, other, text, position - peekAt() predicts get()
+    const uint8_t firstKept = 2 + 2 * BLE_BUFFER_POSITIONS;
+    const uint8_t expected[] = {1, firstKept, 9, 10, firstKept + 1};
+    Message peeked;
+    Message msg;
+    for (int i = 0; i < 5; i++)
+    {
+        TEST_ASSERT_TRUE(buffer.peekAt(i, peeked));
+        TEST_ASSERT_EQUAL(expected[i], BridgeCore::seqOf(peeked));
+    }
+    TEST_ASSERT_EQUAL(BLE_BUFFER_POSITIONS > 2, buffer.peekAt(5, peeked));
+    for (uint8_t seq : expected)
+    {
+        TEST_ASSERT_TRUE(buffer.peek(peeked));
+        TEST_ASSERT_TRUE(buffer.get(msg));
+        TEST_ASSERT_EQUAL(seq, BridgeCore::seqOf(msg));
+        TEST_ASSERT_EQUAL(seq, BridgeCore::seqOf(peeked));
+    }
+
+    // Only the remaining positions are left, in order
+    for (int seq = firstKept + 2; seq < 2 + 3 * BLE_BUFFER_POSITIONS; seq++)
+    {
+        TEST_ASSERT_TRUE(buffer.get(msg));
+        TEST_ASSERT_EQUAL(seq, BridgeCore::seqOf(msg));
+    }
+    TEST_ASSERT_TRUE(buffer.isEmpty());
+    TEST_ASSERT_FALSE(buffer.get(msg));
+}
+
+void test_ring_wraps_around_in_order()
+{
+    BoundedMessageBuffer<4> ring;
+    Message msg;
+    for (uint8_t seq = 1; seq <= 3; seq++)
+    {
+        ring.add(Message::createAck(seq));
+    }
+    TEST_ASSERT_TRUE(ring.get(msg));
+    TEST_ASSERT_TRUE(ring.get(msg));
+
+    // Writes continue at the start of the slots
+    for (uint8_t seq = 4; seq <= 6; seq++)
+    {
+        ring.add(Message::createAck(seq));
+    }
+    TEST_ASSERT_TRUE(ring.isFull());
+
+    // Full: the oldest makes room
+    ring.add(Message::createAck(7));
+    TEST_ASSERT_EQUAL(4, ring.getCount());
+    for (uint8_t seq = 4; seq <= 7; seq++)
+    {
+        TEST_ASSERT_TRUE(ring.peekAt(seq - 4, msg));
+        TEST_ASSERT_EQUAL(seq, msg.ackData.seq);
+    }
+    for (uint8_t seq = 4; seq <= 7; seq++)
+    {
+        TEST_ASSERT_TRUE(ring.get(msg));
+        TEST_ASSERT_EQUAL(seq, msg.ackData.seq);
+    }
+    TEST_ASSERT_TRUE(ring.isEmpty());
+}
+
+void test_full_ble_buffer_counts_its_drops()
+{
+    BleMessageBuffer buffer;
+    for (int i = 0; i < BLE_BUFFER_TEXTS; i++)
+    {
+        buffer.addByPriority(Message::createText(static_cast<uint8_t>(i), "WAITING"));
+    }
+    TEST_ASSERT_EQUAL(0, buffer.getDropped());
+
+    // The oldest texts make room for three more
+    for (int i = BLE_BUFFER_TEXTS; i < BLE_BUFFER_TEXTS + 3; i++)
+    {
+        buffer.addByPriority(Message::createText(static_cast<uint8_t>(i), "WAITING"));
+    }
+    TEST_ASSERT_EQUAL(3, buffer.getDropped());
+    TEST_ASSERT_EQUAL(BLE_BUFFER_TEXTS, buffer.getCount());
+
+    // Other kinds have their own room; a replaced ACK is no loss
+    buffer.addByPriority(Message::createAck(1));
+    buffer.addByPriority(Message::createAck(1));
+    buffer.addByPriority(Message::createSos(2, 0, 0));
+    TEST_ASSERT_EQUAL(4, buffer.getDropped());
+
+    Message msg;
+    TEST_ASSERT_TRUE(buffer.get(msg));
+    TEST_ASSERT_EQUAL(MessageType::Sos, msg.type);
+    TEST_ASSERT_TRUE(buffer.get(msg));
+    TEST_ASSERT_EQUAL(MessageType::Ack, msg.type);
+    TEST_ASSERT_TRUE(buffer.get(msg));
+    TEST_ASSERT_EQUAL(3, msg.textData.seq);
+
+    buffer.clear();
+    TEST_ASSERT_EQUAL(4, buffer.getDropped());
+}
+
+void test_full_send_queue_evicts_the_lowest_priority()
+{
+    BoundedMessageBuffer<3> queue;
+    TEST_ASSERT_TRUE(queue.addBySendPriority(Message::createText(1, "ONE")));
+    TEST_ASSERT_TRUE(queue.addBySendPriority(Message::createText(2, "TWO", MessagePriority::High)));
+    TEST_ASSERT_TRUE(queue.addBySendPriority(Message::createText(3, "THREE")));
+
+    // Full: a position evicts the newest plain text, another plain text is refused
+    TEST_ASSERT_TRUE(queue.addBySendPriority(Message::createTextWithGps(4, "", 47000000, 8000000)));
+    TEST_ASSERT_FALSE(queue.addBySendPriority(Message::createText(5, "FIVE")));
+    TEST_ASSERT_TRUE(queue.addBySendPriority(Message::createText(6, "SOS", MessagePriority::Critical)));
+    TEST_ASSERT_TRUE(queue.addBySendPriority(Message::createAck(7)));
+
+    const uint8_t expected[] = {7, 6, 4};
+    Message msg;
+    for (uint8_t seq : expected)
+    {
+        TEST_ASSERT_TRUE(queue.get(msg));
+        TEST_ASSERT_EQUAL(seq, BridgeCore::seqOf(msg));
+    }
+    TEST_ASSERT_TRUE(queue.isEmpty());
+}
+
+void test_sos_evicts_a_buffered_text()
+{
+    BleMessageBuffer buffer;
+    for (uint8_t seq = 1; seq <= BLE_BUFFER_TEXTS; seq++)
+    {
+        buffer.addByPriority(Message::createText(seq, "WAITING"));
+    }
+    TEST_ASSERT_TRUE(buffer.isFull(Message::createText(20, "HI")));
+    TEST_ASSERT_FALSE(buffer.isFull(Message::createSos(21, 0, 0)));
+
+    buffer.addByPriority(Message::createSos(21, 47000000, 8000000));
+    TEST_ASSERT_EQUAL(BLE_BUFFER_TEXTS, buffer.getCount(BufferClass::Text));
+    Message msg;
+    TEST_ASSERT_TRUE(buffer.get(msg));
+    TEST_ASSERT_EQUAL(MessageType::Sos, msg.type);
+
+    // The newest text made room; once only SOS are left an SOS drops one of them
+    for (uint8_t seq = 1; seq < BLE_BUFFER_TEXTS; seq++)
+    {
+        TEST_ASSERT_TRUE(buffer.get(msg));
+        TEST_ASSERT_EQUAL(seq, msg.textData.seq);
+    }
+    for (uint8_t seq = 30; seq < 30 + BLE_BUFFER_TEXTS; seq++)
+    {
+        buffer.addByPriority(Message::createSos(seq, 0, 0));
+    }
+    TEST_ASSERT_TRUE(buffer.isFull(Message::createSos(40, 0, 0)));
+}
+
+void test_buffered_acks_are_coalesced()
+{
+    BleMessageBuffer buffer;
+    NodeInfo info = {2, 433920000, 11, 4, 5, 14};
+    buffer.addByPriority(Message::createIdentifyResponse(info));
+    for (uint8_t seq = 1; seq < BLE_BUFFER_OTHER; seq++)
+    {
+        buffer.addByPriority(Message::createAck(seq));
+    }
+
+    // The same ACK again replaces the buffered one, even in a full queue
+    TEST_ASSERT_FALSE(buffer.isFull(Message::createAck(1)));
+    buffer.addByPriority(Message::createAck(1));
+    TEST_ASSERT_EQUAL(BLE_BUFFER_OTHER, buffer.getCount(BufferClass::Other));
+
+    // A report for the same seq is a different confirmation
+    TEST_ASSERT_TRUE(buffer.isFull(Message::createDeliveryReport(DeliveryOutcome::Delivered, 1)));
+
+    // New seqs evict the oldest ACKs, never the identify response
+    for (uint8_t seq = 100; seq < 100 + BLE_BUFFER_OTHER - 1; seq++)
+    {
+        buffer.addByPriority(Message::createAck(seq));
+    }
+    TEST_ASSERT_EQUAL(BLE_BUFFER_OTHER, buffer.getCount(BufferClass::Other));
+    Message msg;
+    TEST_ASSERT_TRUE(buffer.get(msg));
+    TEST_ASSERT_EQUAL(MessageType::IdentifyResponse, msg.type);
+    for (uint8_t seq = 100; seq < 100 + BLE_BUFFER_OTHER - 1; seq++)
+    {
+        TEST_ASSERT_TRUE(buffer.get(msg));
+        TEST_ASSERT_EQUAL(MessageType::Ack, msg.type);
+        TEST_ASSERT_EQUAL(seq, msg.ackData.seq);
+    }
+    TEST_ASSERT_TRUE(buffer.isEmpty());
+}
+
+void test_journey_app_to_peer_app_and_back()
+{
+    uint8_t written[MAX_SERIALIZED_LEN];
+    int writtenLen = Message::createTextWithGps(9, "MEET AT THE HUT", 46949000, 7447000).serialize(written, sizeof(written));
+    std::vector<uint8_t> sent(written, written + writtenLen);
+
+    // Alice's app -> BLE -> send queue
+    TEST_ASSERT_TRUE(appWrite(*alice, written, writtenLen));
+
+    // Send queue -> LoRa: the frame on air is what the app wrote
+    alice->core.tick(0);
+    TEST_ASSERT_EQUAL(1, bob->inbox.size());
+    TEST_ASSERT_TRUE(sent == bob->inbox.front());
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+
+    // LoRa -> Bob: delivered intact to his app, ACK on air
+    TEST_ASSERT_EQUAL(FrameResult::Handled, receiveFrame(*bob, 10));
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_TRUE(sent == notified(bob->app.delivered[0]));
+    TEST_ASSERT_EQUAL(1, alice->inbox.size());
+
+    // ACK -> Alice: matched to the text in flight and reported to her app
+    TEST_ASSERT_EQUAL(FrameResult::Handled, receiveFrame(*alice, 20));
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Delivered, alice->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(9, alice->app.delivered[0].deliveryReportData.seq);
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+}
+
+void test_journey_with_lost_ack_is_retransmitted_and_acked()
+{
+    uint8_t written[MAX_SERIALIZED_LEN];
+    int writtenLen = Message::createText(12, "NEED WATER", MessagePriority::High).serialize(written, sizeof(written));
+    std::vector<uint8_t> sent(written, written + writtenLen);
+    TEST_ASSERT_TRUE(appWrite(*alice, written, writtenLen));
+
+    // Text gets through, Bob's ACK is lost
+    alice->core.tick(0);
+    bob->radio.dropNext = 1;
+    TEST_ASSERT_EQUAL(FrameResult::Handled, receiveFrame(*bob, 10));
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+    TEST_ASSERT_TRUE(alice->inbox.empty());
+
+    // No ACK within the timeout: Alice sends the same frame again
+    alice->core.tick(ACK_TIMEOUT_MS - 1);
+    TEST_ASSERT_TRUE(bob->inbox.empty());
+    alice->core.tick(ACK_TIMEOUT_MS);
+    TEST_ASSERT_EQUAL(1, bob->inbox.size());
+    TEST_ASSERT_TRUE(sent == bob->inbox.front());
+
+    // Bob ACKs the copy again without delivering it twice; this ACK arrives
+    TEST_ASSERT_EQUAL(FrameResult::Handled, receiveFrame(*bob, ACK_TIMEOUT_MS + 10));
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_TRUE(sent == notified(bob->app.delivered[0]));
+    TEST_ASSERT_EQUAL(1, alice->inbox.size());
+
+    TEST_ASSERT_EQUAL(FrameResult::Handled, receiveFrame(*alice, ACK_TIMEOUT_MS + 20));
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Delivered, alice->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(12, alice->app.delivered[0].deliveryReportData.seq);
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+}
+
+void test_stop_and_wait_holds_next_message_until_resolved()
+{
+    alice->core.setStopAndWait(true);
+    TEST_ASSERT_TRUE(alice->core.canAccept());
+    alice->core.submit(Message::createText(1, "FIRST"));
+    TEST_ASSERT_FALSE(alice->core.canAccept());
+
+    // Sent but not ACKed yet
+    alice->core.tick(0);
+    TEST_ASSERT_FALSE(alice->core.canAccept());
+
+    // ACKed: the next message may be taken
+    receiveFrame(*bob, 10);
+    receiveFrame(*alice, 20);
+    TEST_ASSERT_TRUE(alice->core.canAccept());
+
+    // A lost text holds the next one until its ACK timeout
+    alice->radio.dropNext = 1;
+    alice->core.submit(Message::createText(2, "SECOND"));
+    alice->core.tick(30);
+    TEST_ASSERT_FALSE(alice->core.canAccept());
+    alice->core.tick(30 + ACK_TIMEOUT_MS);
+    TEST_ASSERT_TRUE(alice->core.canAccept());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Failed, alice->app.delivered.back().deliveryReportData.outcome);
+
+    // Without stop-and-wait only a full send queue holds messages back
+    alice->core.setStopAndWait(false);
+    alice->core.submit(Message::createText(3, "THIRD"));
+    alice->core.tick(40 + ACK_TIMEOUT_MS);
+    TEST_ASSERT_TRUE(alice->core.isAwaitingAck());
+    TEST_ASSERT_TRUE(alice->core.canAccept());
+}
+
+void test_fire_and_forget_sends_once_and_never_acks()
+{
+    alice->core.setFireAndForget(true);
+    bob->core.setFireAndForget(true);
+    alice->core.submit(Message::createText(5, "21.5C", MessagePriority::Critical));
+    run(*alice, *bob, 0);
+
+    // Delivered without an ACK, reported to the sender's app as sent
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(0, bob->radio.framesSent);
+    TEST_ASSERT_FALSE(alice->core.isAwaitingAck());
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Sent, alice->app.delivered[0].deliveryReportData.outcome);
+    TEST_ASSERT_EQUAL(5, alice->app.delivered[0].deliveryReportData.seq);
+
+    // Even a lost Critical text is never retransmitted or reported failed
+    alice->radio.dropNext = 1;
+    alice->core.submit(Message::createText(6, "21.6C", MessagePriority::Critical));
+    run(*alice, *bob, 10);
+    run(*alice, *bob, 10 + 10 * ACK_TIMEOUT_MS);
+    TEST_ASSERT_EQUAL(2, alice->radio.framesSent);
+    TEST_ASSERT_EQUAL(1, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(0, alice->stats.ackTimeouts);
+    TEST_ASSERT_EQUAL(DeliveryOutcome::Sent, alice->app.delivered.back().deliveryReportData.outcome);
+}
+
+void test_received_text_is_followed_by_its_link_quality()
+{
+    bob->core.setLinkQualityReports(true);
+    alice->core.submit(Message::createText(12, "HOW COPY"));
+    alice->core.tick(0);
+
+    std::vector<uint8_t> frame = bob->inbox.front();
+    bob->inbox.pop_front();
+    Message msg;
+    TEST_ASSERT_EQUAL(FrameResult::Handled, bob->core.onRadioFrame(frame.data(), frame.size(), -118, 0, msg, 6.25f));
+
+    TEST_ASSERT_EQUAL(2, bob->app.delivered.size());
+    TEST_ASSERT_EQUAL(MessageType::Text, bob->app.delivered[0].type);
+    const Message &report = bob->app.delivered[1];
+    TEST_ASSERT_EQUAL(MessageType::LinkQuality, report.type);
+    TEST_ASSERT_EQUAL(12, report.linkQualityData.seq);
+    TEST_ASSERT_EQUAL(-118, report.linkQualityData.rssi);
+    TEST_ASSERT_EQUAL(25, report.linkQualityData.snrQuarterDb);
+
+    // Only the receiving bridge reports: nothing but the ACK goes back on air
+    TEST_ASSERT_EQUAL(1, bob->radio.framesSent);
+    TEST_ASSERT_TRUE(bob->core.inject(Message::createText(13, "LOCAL")));
+    TEST_ASSERT_EQUAL(3, bob->app.delivered.size());
+}
+
+void test_injected_message_reaches_app_without_radio()
+{
+    TEST_ASSERT_TRUE(alice->core.inject(Message::createText(4, "FAKE")));
+
+    TEST_ASSERT_EQUAL(1, alice->app.delivered.size());
+    TEST_ASSERT_EQUAL_STRING("FAKE", alice->app.delivered[0].textData.text);
+    // Not ACKed, counted or audited
+    TEST_ASSERT_EQUAL(0, alice->radio.framesSent);
+    TEST_ASSERT_TRUE(bob->inbox.empty());
+    TEST_ASSERT_EQUAL(0, alice->stats.loraRxFrames);
+    TEST_ASSERT_EQUAL(0, alice->audit.getTotal());
+}
+
+int main(int, char **)
+{
+    UNITY_BEGIN();
+    RUN_TEST(test_text_is_delivered_and_acked);
+    RUN_TEST(test_battery_level_is_delivered_and_acked);
+    RUN_TEST(test_encrypted_text_is_delivered_and_acked);
+    RUN_TEST(test_frames_without_a_valid_auth_tag_are_dropped);
+    RUN_TEST(test_relay_keeps_the_auth_tag_of_the_longest_texts);
+    RUN_TEST(test_fec_parity_repairs_frames_on_air);
+    RUN_TEST(test_sos_is_repeated_and_acked_once);
+    RUN_TEST(test_both_directions);
+    RUN_TEST(test_lost_normal_text_times_out);
+    RUN_TEST(test_lost_critical_text_is_retransmitted);
+    RUN_TEST(test_corrupted_text_is_nacked_and_retransmitted_early);
+    RUN_TEST(test_nack_never_fails_a_text);
+    RUN_TEST(test_only_damaged_texts_with_a_readable_header_are_nacked);
+    RUN_TEST(test_priority_jumps_the_queue);
+    RUN_TEST(test_position_goes_out_before_queued_texts);
+    RUN_TEST(test_position_updates_are_coalesced);
+    RUN_TEST(test_audit_log_records_exchange);
+    RUN_TEST(test_audit_log_pages_after_wrap);
+    RUN_TEST(test_weak_text_is_delivered_without_ack);
+    RUN_TEST(test_reused_seq_waits_for_ack_timeout);
+    RUN_TEST(test_reused_seq_waits_for_ack);
+    RUN_TEST(test_retransmission_after_lost_ack_is_acked_again);
+    RUN_TEST(test_duplicates_are_found_across_the_seq_wrap);
+    RUN_TEST(test_echoed_text_is_not_acked_again);
+    RUN_TEST(test_texts_in_flight_time_out_independently);
+    RUN_TEST(test_tx_waits_for_reception_in_progress);
+    RUN_TEST(test_stuck_reception_defers_for_one_ack_timeout);
+    RUN_TEST(test_lbt_backoff_doubles_its_window_up_to_16_slots);
+    RUN_TEST(test_busy_channel_backs_off_before_transmitting);
+    RUN_TEST(test_jammed_channel_transmits_after_the_cad_attempts);
+    RUN_TEST(test_no_cad_without_lbt);
+    RUN_TEST(test_duty_cycle_sums_the_rolling_window);
+    RUN_TEST(test_duty_cycle_keeps_later_slots_and_survives_long_idle);
+    RUN_TEST(test_spent_duty_cycle_holds_the_text_and_tells_the_app);
+    RUN_TEST(test_spent_duty_cycle_still_sends_acks);
+    RUN_TEST(test_unknown_frame_is_relayed_once);
+    RUN_TEST(test_relayed_frame_is_sent_verbatim);
+    RUN_TEST(test_relay_hop_acks_a_text_that_asks_for_it);
+    RUN_TEST(test_end_to_end_text_waits_past_a_hop_ack);
+    RUN_TEST(test_only_end_to_end_frames_are_relayable);
+    RUN_TEST(test_protocol_profiles);
+    RUN_TEST(test_texts_outside_the_profile_are_reported);
+    RUN_TEST(test_missing_fixes_are_retransmitted_selectively);
+    RUN_TEST(test_gps_deltas_reach_the_app_as_full_positions);
+    RUN_TEST(test_retransmission_waits_for_tx_allowed);
+    RUN_TEST(test_retransmit_strategies_space_the_retries);
+    RUN_TEST(test_scheduled_retransmission_waits_for_the_next_window);
+    RUN_TEST(test_listen_windows_follow_lowest_node);
+    RUN_TEST(test_listen_windows_hold_tx_and_resync);
+    RUN_TEST(test_periodic_listen_alternates_window_and_sleep);
+    RUN_TEST(test_adr_steps_faster_only_on_a_full_strong_window);
+    RUN_TEST(test_adr_steps_slower_on_a_weak_window_or_missed_acks);
+    RUN_TEST(test_adr_hysteresis_keeps_the_link_after_a_step);
+    RUN_TEST(test_garbage_is_not_handled);
+    RUN_TEST(test_backoff_doubles_to_cap_and_resets);
+    RUN_TEST(test_position_flood_does_not_evict_texts);
+    RUN_TEST(test_ring_wraps_around_in_order);
+    RUN_TEST(test_full_ble_buffer_counts_its_drops);
+    RUN_TEST(test_full_send_queue_evicts_the_lowest_priority);
+    RUN_TEST(test_sos_evicts_a_buffered_text);
+    RUN_TEST(test_buffered_acks_are_coalesced);
+    RUN_TEST(test_journey_app_to_peer_app_and_back);
+    RUN_TEST(test_journey_with_lost_ack_is_retransmitted_and_acked);
+    RUN_TEST(test_stop_and_wait_holds_next_message_until_resolved);
+    RUN_TEST(test_fire_and_forget_sends_once_and_never_acks);
+    RUN_TEST(test_received_text_is_followed_by_its_link_quality);
+    RUN_TEST(test_injected_message_reaches_app_without_radio);
+    return UNITY_END();
+}
@@ -0,0 +1,1347 @@
+// Host tests for the protocol byte order helpers and wire format.
+// Run with: pio test -e native
+#include <unity.h>
+#include <string.h>
+#include "Protocol.h"
+#include "Aes128.h"
+#include "SipHash.h"
+#include "PersistedSettings.h"
+#include "Diagnostics.h"
+
+void setUp() {}
+
+void tearDown() {}
+
+/// Recompute the trailing CRC of a frame edited or written by hand
+static void resealCrc(uint8_t *frame, size_t len)
+{
+    frame[len - 1] = crc8(frame, len - 1);
+}
+
+void test_helpers_write_little_endian()
+{
+    uint8_t buf[4];
+
+    writeU32(buf, 433920000); // 0x19DD1800
+    TEST_ASSERT_EQUAL_HEX8(0x00, buf[0]);
+    TEST_ASSERT_EQUAL_HEX8(0x18, buf[1]);
+    TEST_ASSERT_EQUAL_HEX8(0xDD, buf[2]);
+    TEST_ASSERT_EQUAL_HEX8(0x19, buf[3]);
+
+    writeI16(buf, -97);
+    TEST_ASSERT_EQUAL_HEX8(0x9F, buf[0]);
+    TEST_ASSERT_EQUAL_HEX8(0xFF, buf[1]);
+}
+
+void test_helpers_round_trip()
+{
+    uint8_t buf[4];
+
+    writeI32(buf, -122419200);
+    TEST_ASSERT_EQUAL_INT32(-122419200, readI32(buf));
+    writeU32(buf, 0xFFFFFFFFUL);
+    TEST_ASSERT_EQUAL_UINT32(0xFFFFFFFFUL, readU32(buf));
+    writeU16(buf, 40000);
+    TEST_ASSERT_EQUAL_UINT16(40000, readU16(buf));
+    writeI16(buf, -32768);
+    TEST_ASSERT_EQUAL_INT16(-32768, readI16(buf));
+}
+
+void test_gps_fields_are_little_endian_on_the_wire()
+{
+    // Same frame as ProtocolTest.testCanonicalByteOrderIsLittleEndian on Android
+    Message msg = Message::createTextWithGps(1, "", 0x01020304, -2);
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(14, msg.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL_HEX8(0x04, buf[5]);
+    TEST_ASSERT_EQUAL_HEX8(0x01, buf[8]);
+    TEST_ASSERT_EQUAL_HEX8(0xFE, buf[9]);
+    TEST_ASSERT_EQUAL_HEX8(0xFF, buf[12]);
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, 14));
+    TEST_ASSERT_EQUAL_INT32(0x01020304, decoded.textData.lat);
+    TEST_ASSERT_EQUAL_INT32(-2, decoded.textData.lon);
+}
+
+void test_gps_fields_round_trip_in_every_combination()
+{
+    for (uint8_t fields = 0; fields <= GPS_FIELDS_KNOWN; fields++)
+    {
+        Message msg = Message::createTextWithGps(4, "HI", 47376887, 8541694);
+        msg.textData.gpsFields = fields;
+        msg.textData.altitude = 408;
+        msg.textData.speed = 65535;
+
+        // Without optional fields the frame is the plain GPS text
+        size_t expectedLen = 15 + MESSAGE_CRC_LEN;
+        if (fields != 0)
+        {
+            expectedLen += 1 + ((fields & GPS_FIELD_ALTITUDE) ? 2 : 0) + ((fields & GPS_FIELD_SPEED) ? 2 : 0);
+        }
+        uint8_t buf[MAX_SERIALIZED_LEN];
+        int len = msg.serialize(buf, sizeof(buf));
+        TEST_ASSERT_EQUAL(expectedLen, len);
+        TEST_ASSERT_EQUAL(fields != 0, (buf[6] & TEXT_FLAG_GPS_FIELDS) != 0);
+
+        Message decoded;
+        TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+        TEST_ASSERT_EQUAL_INT32(47376887, decoded.textData.lat);
+        TEST_ASSERT_EQUAL_INT32(8541694, decoded.textData.lon);
+        TEST_ASSERT_EQUAL_HEX8(fields, decoded.textData.gpsFields);
+        TEST_ASSERT_EQUAL((fields & GPS_FIELD_ALTITUDE) ? 408 : 0, decoded.textData.altitude);
+        TEST_ASSERT_EQUAL((fields & GPS_FIELD_SPEED) ? 65535 : 0, decoded.textData.speed);
+
+        // A frame cut short of an announced field is rejected
+        if (fields != 0)
+        {
+            resealCrc(buf, len - 1);
+            TEST_ASSERT_FALSE(decoded.deserialize(buf, len - 1));
+        }
+    }
+
+    // Optional fields without coordinates are never sent
+    Message plain = Message::createText(5, "HI");
+    plain.textData.gpsFields = GPS_FIELD_ALTITUDE;
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(8, plain.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL_HEX8(0x00, buf[6]);
+}
+
+void test_altitude_below_sea_level_and_texts_without_it()
+{
+    // Dead Sea shore, 430 m below sea level: two's complement, little-endian
+    Message msg = Message::createTextWithGps(7, "", 31500000, 35500000);
+    msg.textData.gpsFields = GPS_FIELD_ALTITUDE;
+    msg.textData.altitude = -430;
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(17, msg.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL_HEX8(0x52, buf[14]);
+    TEST_ASSERT_EQUAL_HEX8(0xFE, buf[15]);
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, 17));
+    TEST_ASSERT_EQUAL_INT16(-430, decoded.textData.altitude);
+
+    // A sender that doesn't know altitude sends coordinates only: it reads as absent and 0
+    int len = Message::createTextWithGps(7, "", 31500000, 35500000).serialize(buf, sizeof(buf));
+    TEST_ASSERT_EQUAL(14, len);
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+    TEST_ASSERT_EQUAL_INT32(35500000, decoded.textData.lon);
+    TEST_ASSERT_EQUAL_HEX8(0, decoded.textData.gpsFields);
+    TEST_ASSERT_EQUAL_INT16(0, decoded.textData.altitude);
+}
+
+void test_unknown_gps_fields_are_skipped()
+{
+    // Altitude plus a field from a later version (bit 2) after it
+    uint8_t frame[] = {0x01, 9, 0, 0, TEXT_FLAG_GPS | TEXT_FLAG_GPS_FIELDS, 1, 0, 0, 0, 2, 0, 0, 0,
+                       GPS_FIELD_ALTITUDE | 0x04, 0x10, 0x00, 0xAA, 0xBB, 0xCC, 0};
+    resealCrc(frame, sizeof(frame));
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(frame, sizeof(frame)));
+    TEST_ASSERT_EQUAL_HEX8(GPS_FIELD_ALTITUDE, decoded.textData.gpsFields);
+    TEST_ASSERT_EQUAL(16, decoded.textData.altitude);
+    TEST_ASSERT_EQUAL_INT32(2, decoded.textData.lon);
+
+    // Passed on with only the fields this version knows
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(17, decoded.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL_HEX8(GPS_FIELD_ALTITUDE, buf[13]);
+
+    // Only unknown fields: coordinates alone
+    uint8_t later[] = {0x01, 9, 0, 0, TEXT_FLAG_GPS | TEXT_FLAG_GPS_FIELDS, 1, 0, 0, 0, 2, 0, 0, 0, 0x80, 0xAA, 0};
+    resealCrc(later, sizeof(later));
+    TEST_ASSERT_TRUE(decoded.deserialize(later, sizeof(later)));
+    TEST_ASSERT_EQUAL_HEX8(0, decoded.textData.gpsFields);
+    TEST_ASSERT_EQUAL(14, decoded.serialize(buf, sizeof(buf)));
+
+    // The fields byte itself is missing
+    resealCrc(later, 14);
+    TEST_ASSERT_FALSE(decoded.deserialize(later, 14));
+}
+
+void test_fragment_header_is_found_after_unknown_gps_fields()
+{
+    // Fragment 2 of 4 of text 0x30, after altitude and a field from a later version
+    uint8_t frame[] = {0x01, 0x31, 0, 0, TEXT_FLAG_GPS | TEXT_FLAG_GPS_FIELDS | TEXT_FLAG_FRAGMENT, 1, 0, 0, 0, 2, 0, 0, 0,
+                       GPS_FIELD_ALTITUDE | 0x04, 0x10, 0x00, 0xAA, 0xBB, 0x30, 1, 4, 0};
+    resealCrc(frame, sizeof(frame));
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(frame, sizeof(frame)));
+    TEST_ASSERT_TRUE(decoded.textData.isFragment);
+    TEST_ASSERT_EQUAL_HEX8(0x30, decoded.textData.fragmentId);
+    TEST_ASSERT_EQUAL(1, decoded.textData.fragmentIndex);
+    TEST_ASSERT_EQUAL(4, decoded.textData.fragmentCount);
+    TEST_ASSERT_EQUAL(16, decoded.textData.altitude);
+
+    // Passed on with the known fields, the header still last
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(20, decoded.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL_HEX8(0x30, buf[16]);
+    TEST_ASSERT_EQUAL_HEX8(4, buf[18]);
+
+    // A fragment past the end of its text
+    frame[sizeof(frame) - 3] = 4;
+    resealCrc(frame, sizeof(frame));
+    TEST_ASSERT_FALSE(decoded.deserialize(frame, sizeof(frame)));
+
+    // Too short for the header
+    uint8_t cut[] = {0x01, 0x31, 0, 0, TEXT_FLAG_FRAGMENT, 0x30, 0};
+    resealCrc(cut, sizeof(cut));
+    TEST_ASSERT_FALSE(decoded.deserialize(cut, sizeof(cut)));
+}
+
+void test_link_quality_keeps_sign_and_quarter_db()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(6, Message::createLinkQuality(77, -132, -19.75f).serialize(buf, sizeof(buf)));
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, 6));
+    TEST_ASSERT_EQUAL(MessageType::LinkQuality, decoded.type);
+    TEST_ASSERT_EQUAL(77, decoded.linkQualityData.seq);
+    TEST_ASSERT_EQUAL(-132, decoded.linkQualityData.rssi);
+    TEST_ASSERT_EQUAL(-79, decoded.linkQualityData.snrQuarterDb);
+}
+
+void test_nack_round_trips_its_reason()
+{
+    const DecodeFailureReason reasons[] = {DecodeFailureReason::Truncated, DecodeFailureReason::CrcMismatch};
+    for (DecodeFailureReason reason : reasons)
+    {
+        uint8_t buf[MAX_SERIALIZED_LEN];
+        TEST_ASSERT_EQUAL(4, Message::createNack(200, reason).serialize(buf, sizeof(buf)));
+        TEST_ASSERT_EQUAL_HEX8(0x04, buf[0]);
+        TEST_ASSERT_EQUAL(static_cast<uint8_t>(reason), buf[2]);
+
+        Message decoded;
+        TEST_ASSERT_TRUE(decoded.deserialize(buf, 4));
+        TEST_ASSERT_EQUAL(MessageType::Nack, decoded.type);
+        TEST_ASSERT_EQUAL(200, decoded.nackData.seq);
+        TEST_ASSERT_EQUAL(reason, decoded.nackData.reason);
+    }
+
+    uint8_t unknown[] = {0x04, 1, 0x06, 0};
+    resealCrc(unknown, sizeof(unknown));
+    Message decoded;
+    TEST_ASSERT_FALSE(decoded.deserialize(unknown, sizeof(unknown)));
+}
+
+void test_battery_round_trips_its_edge_values()
+{
+    const uint8_t percents[] = {0, BATTERY_PERCENT_MAX};
+    for (uint8_t percent : percents)
+    {
+        uint8_t buf[MAX_SERIALIZED_LEN];
+        TEST_ASSERT_EQUAL(6, Message::createBattery(31, percent, 3712).serialize(buf, sizeof(buf)));
+
+        Message decoded;
+        TEST_ASSERT_TRUE(decoded.deserialize(buf, 6));
+        TEST_ASSERT_EQUAL(MessageType::Battery, decoded.type);
+        TEST_ASSERT_EQUAL(31, decoded.batteryData.seq);
+        TEST_ASSERT_EQUAL(percent, decoded.batteryData.percent);
+        TEST_ASSERT_EQUAL(3712, decoded.batteryData.millivolts);
+    }
+
+    // Clamped when created, rejected when received
+    TEST_ASSERT_EQUAL(BATTERY_PERCENT_MAX, Message::createBattery(1, 150, 4200).batteryData.percent);
+    uint8_t overfull[] = {0x05, 1, 101, 0x68, 0x10, 0};
+    resealCrc(overfull, sizeof(overfull));
+    Message decoded;
+    TEST_ASSERT_FALSE(decoded.deserialize(overfull, sizeof(overfull)));
+
+    uint8_t cut[] = {0x05, 1, 50, 0x68, 0};
+    resealCrc(cut, sizeof(cut));
+    TEST_ASSERT_FALSE(decoded.deserialize(cut, sizeof(cut)));
+}
+
+void test_rate_switch_rejects_unusable_factors()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(4, Message::createRateSwitch(12, ChannelSwitchStage::Confirm).serialize(buf, sizeof(buf)));
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, 4));
+    TEST_ASSERT_EQUAL(MessageType::RateSwitch, decoded.type);
+    TEST_ASSERT_EQUAL(12, decoded.rateSwitchData.spreadingFactor);
+    TEST_ASSERT_EQUAL(ChannelSwitchStage::Confirm, decoded.rateSwitchData.stage);
+
+    // SF6 needs implicit header, SF13 doesn't exist, stage 3 isn't a step
+    uint8_t sf6[] = {0x10, 6, 0, 0};
+    uint8_t sf13[] = {0x10, 13, 0, 0};
+    uint8_t stage3[] = {0x10, 9, 3, 0};
+    uint8_t *frames[] = {sf6, sf13, stage3};
+    for (uint8_t *frame : frames)
+    {
+        resealCrc(frame, 4);
+        TEST_ASSERT_FALSE(decoded.deserialize(frame, 4));
+    }
+}
+
+void test_gps_delta_encodes_small_moves_only()
+{
+    GpsReference sent = {false, 0, 0, 0, 0};
+
+    // No prior fix: full
+    Message first = Message::createTextWithGps(1, "", -33856784, 151215297);
+    TEST_ASSERT_EQUAL(MessageType::Text, encodeGpsPosition(first, sent, 10).type);
+    rememberGpsPosition(sent, first);
+
+    // Small move: offsets from the fix, 8 bytes on air instead of 14
+    Message moved = encodeGpsPosition(Message::createTextWithGps(2, "", -33856984, 151215597), sent, 10);
+    TEST_ASSERT_EQUAL(MessageType::GpsDelta, moved.type);
+    TEST_ASSERT_EQUAL(2, moved.gpsDeltaData.seq);
+    TEST_ASSERT_EQUAL(1, moved.gpsDeltaData.refSeq);
+    TEST_ASSERT_EQUAL_INT16(-200, moved.gpsDeltaData.dLat);
+    TEST_ASSERT_EQUAL_INT16(300, moved.gpsDeltaData.dLon);
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(8, moved.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL(14, first.serialize(buf, sizeof(buf)));
+
+    // The largest offsets still fit, one more doesn't
+    TEST_ASSERT_EQUAL(MessageType::GpsDelta,
+                      encodeGpsPosition(Message::createTextWithGps(3, "", -33856784 - 32768, 151215297 + 32767), sent, 10).type);
+    TEST_ASSERT_EQUAL(MessageType::Text,
+                      encodeGpsPosition(Message::createTextWithGps(3, "", -33856784 + 32768, 151215297), sent, 10).type);
+    TEST_ASSERT_EQUAL(MessageType::Text,
+                      encodeGpsPosition(Message::createTextWithGps(3, "", -33856784, 151215297 - 32769), sent, 10).type);
+
+    // Only plain Normal position updates: not with text, a priority or GPS fields
+    TEST_ASSERT_EQUAL(MessageType::Text,
+                      encodeGpsPosition(Message::createTextWithGps(3, "HI", -33856784, 151215297), sent, 10).type);
+    Message urgent = Message::createTextWithGps(3, "", -33856784, 151215297);
+    urgent.textData.priority = MessagePriority::High;
+    TEST_ASSERT_EQUAL(MessageType::Text, encodeGpsPosition(urgent, sent, 10).type);
+    Message withAltitude = Message::createTextWithGps(3, "", -33856784, 151215297);
+    withAltitude.textData.gpsFields = GPS_FIELD_ALTITUDE;
+    TEST_ASSERT_EQUAL(MessageType::Text, encodeGpsPosition(withAltitude, sent, 10).type);
+}
+
+void test_gps_delta_resyncs_periodically()
+{
+    GpsReference sent = {false, 0, 0, 0, 0};
+    uint8_t seq = 1;
+    Message update = Message::createTextWithGps(seq, "", 47000000, 8000000);
+    rememberGpsPosition(sent, encodeGpsPosition(update, sent, 3));
+
+    // Three deltas against the fix, then a full update that becomes the new reference
+    for (int round = 0; round < 2; round++)
+    {
+        for (int i = 0; i < 3; i++)
+        {
+            seq++;
+            update = encodeGpsPosition(Message::createTextWithGps(seq, "", 47000000 + seq, 8000000), sent, 3);
+            TEST_ASSERT_EQUAL(MessageType::GpsDelta, update.type);
+            rememberGpsPosition(sent, update);
+        }
+        seq++;
+        update = encodeGpsPosition(Message::createTextWithGps(seq, "", 47000000 + seq, 8000000), sent, 3);
+        TEST_ASSERT_EQUAL(MessageType::Text, update.type);
+        rememberGpsPosition(sent, update);
+        TEST_ASSERT_EQUAL(seq, sent.seq);
+        TEST_ASSERT_EQUAL(0, sent.deltasSent);
+    }
+
+    // 0 never sends a delta
+    TEST_ASSERT_EQUAL(MessageType::Text,
+                      encodeGpsPosition(Message::createTextWithGps(++seq, "", 47000000, 8000000), sent, 0).type);
+}
+
+void test_gps_delta_receiver_rebuilds_positions()
+{
+    GpsReference heard = {false, 0, 0, 0, 0};
+    Message position;
+
+    // No prior fix: a delta can't be rebuilt
+    TEST_ASSERT_FALSE(decodeGpsPosition(Message::createGpsDelta(2, 1, 10, 10), heard, position));
+
+    // A full update is passed on and becomes the reference
+    Message fix = Message::createTextWithGps(1, "", 47000000, -8000000);
+    TEST_ASSERT_TRUE(decodeGpsPosition(fix, heard, position));
+    TEST_ASSERT_EQUAL(1, position.textData.seq);
+    TEST_ASSERT_TRUE(heard.valid);
+
+    TEST_ASSERT_TRUE(decodeGpsPosition(Message::createGpsDelta(2, 1, -32768, 32767), heard, position));
+    TEST_ASSERT_EQUAL(MessageType::Text, position.type);
+    TEST_ASSERT_EQUAL(2, position.textData.seq);
+    TEST_ASSERT_TRUE(position.textData.hasGps);
+    TEST_ASSERT_EQUAL_STRING("", position.textData.text);
+    TEST_ASSERT_EQUAL_INT32(47000000 - 32768, position.textData.lat);
+    TEST_ASSERT_EQUAL_INT32(-8000000 + 32767, position.textData.lon);
+
+    // Against a fix that never arrived: dropped, the reference stays
+    TEST_ASSERT_FALSE(decodeGpsPosition(Message::createGpsDelta(9, 8, 1, 1), heard, position));
+    TEST_ASSERT_EQUAL(1, heard.seq);
+
+    // A late retransmission of an older fix doesn't replace a newer reference
+    TEST_ASSERT_TRUE(decodeGpsPosition(Message::createTextWithGps(8, "", 46000000, 7000000), heard, position));
+    TEST_ASSERT_TRUE(decodeGpsPosition(Message::createTextWithGps(1, "", 47000000, -8000000), heard, position));
+    TEST_ASSERT_EQUAL(8, heard.seq);
+    TEST_ASSERT_TRUE(decodeGpsPosition(Message::createGpsDelta(9, 8, 1, 1), heard, position));
+    TEST_ASSERT_EQUAL_INT32(46000001, position.textData.lat);
+
+    // Texts with a message don't touch the reference
+    TEST_ASSERT_TRUE(decodeGpsPosition(Message::createTextWithGps(10, "HI", 0, 0), heard, position));
+    TEST_ASSERT_EQUAL(8, heard.seq);
+}
+
+void test_sos_round_trips_its_position()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    TEST_ASSERT_EQUAL(11, Message::createSos(200, -33856784, 151215297).serialize(buf, sizeof(buf)));
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, 11));
+    TEST_ASSERT_EQUAL(MessageType::Sos, decoded.type);
+    TEST_ASSERT_EQUAL(200, decoded.sosData.seq);
+    TEST_ASSERT_EQUAL(-33856784, decoded.sosData.lat);
+    TEST_ASSERT_EQUAL(151215297, decoded.sosData.lon);
+    TEST_ASSERT_EQUAL(MessagePriority::Critical, decoded.priority());
+    TEST_ASSERT_EQUAL(SEND_PRIORITY_SOS + 2, decoded.sendPriority());
+
+    // Cut before the end of the longitude
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, 10));
+}
+
+void test_aes128_matches_the_fips197_example()
+{
+    const uint8_t key[] = {0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+                           0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f};
+    const uint8_t plain[] = {0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77,
+                             0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff};
+    const uint8_t cipher[] = {0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30,
+                              0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5, 0x5a};
+    uint8_t block[Aes128::BLOCK_LEN];
+    Aes128(key).encryptBlock(plain, block);
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(cipher, block, sizeof(cipher));
+}
+
+static const uint8_t PAYLOAD_KEY[PAYLOAD_KEY_LEN] = {0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6,
+                                                     0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c};
+
+void test_encrypted_text_round_trips()
+{
+    Message text = Message::createTextWithGps(42, "MEET AT THE NORTH GATE", 47123456, 8123456);
+    uint8_t plain[MAX_SERIALIZED_LEN];
+    int plainLen = text.serialize(plain, sizeof(plain));
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = text.serializeEncrypted(buf, sizeof(buf), PAYLOAD_KEY, 0x5A);
+
+    // Type and seq stay readable, the salt follows, the rest is unreadable
+    TEST_ASSERT_EQUAL(plainLen + PAYLOAD_SALT_LEN, len);
+    TEST_ASSERT_EQUAL_HEX8(0x01, buf[0]);
+    TEST_ASSERT_EQUAL(42, buf[1]);
+    TEST_ASSERT_EQUAL_HEX8(0x5A, buf[2]);
+    TEST_ASSERT_NOT_EQUAL(0, memcmp(plain + 2, buf + 3, plainLen - 2 - MESSAGE_CRC_LEN));
+    TEST_ASSERT_TRUE(Message::crcMatches(buf, len));
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserializeEncrypted(buf, len, PAYLOAD_KEY));
+    TEST_ASSERT_EQUAL(42, decoded.textData.seq);
+    TEST_ASSERT_EQUAL_STRING("MEET AT THE NORTH GATE", decoded.textData.text);
+    TEST_ASSERT_EQUAL(47123456, decoded.textData.lat);
+    TEST_ASSERT_EQUAL(8123456, decoded.textData.lon);
+
+    // Another salt, other bytes on air
+    uint8_t other[MAX_SERIALIZED_LEN];
+    text.serializeEncrypted(other, sizeof(other), PAYLOAD_KEY, 0x5B);
+    TEST_ASSERT_NOT_EQUAL(0, memcmp(buf + 3, other + 3, len - 3 - MESSAGE_CRC_LEN));
+
+    // A GPS delta gives away a track as much as a position text: encrypted too
+    Message delta = Message::createGpsDelta(43, 42, -200, 300);
+    len = delta.serializeEncrypted(buf, sizeof(buf), PAYLOAD_KEY, 0x5A);
+    TEST_ASSERT_EQUAL(delta.serialize(plain, sizeof(plain)) + PAYLOAD_SALT_LEN, len);
+    TEST_ASSERT_EQUAL_HEX8(0x07, buf[0]);
+    TEST_ASSERT_TRUE(decoded.deserializeEncrypted(buf, len, PAYLOAD_KEY));
+    TEST_ASSERT_EQUAL(MessageType::GpsDelta, decoded.type);
+    TEST_ASSERT_EQUAL(42, decoded.gpsDeltaData.refSeq);
+    TEST_ASSERT_EQUAL_INT16(-200, decoded.gpsDeltaData.dLat);
+    TEST_ASSERT_EQUAL_INT16(300, decoded.gpsDeltaData.dLon);
+}
+
+void test_wrong_payload_key_yields_garbage()
+{
+    uint8_t wrongKey[PAYLOAD_KEY_LEN];
+    memcpy(wrongKey, PAYLOAD_KEY, sizeof(wrongKey));
+    wrongKey[0] ^= 0x01;
+
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    for (uint8_t salt = 0; salt < 16; salt++)
+    {
+        int len = Message::createText(7, "SECRET PLAN").serializeEncrypted(buf, sizeof(buf), PAYLOAD_KEY, salt);
+        Message decoded;
+        if (decoded.deserializeEncrypted(buf, len, wrongKey))
+        {
+            TEST_ASSERT_NOT_EQUAL(0, strcmp("SECRET PLAN", decoded.textData.text));
+        }
+        // Nor can a node without the key read it
+        TEST_ASSERT_FALSE(decoded.deserialize(buf, len) && strcmp("SECRET PLAN", decoded.textData.text) == 0);
+    }
+}
+
+void test_acks_are_never_encrypted()
+{
+    uint8_t plain[MAX_SERIALIZED_LEN];
+    uint8_t encrypted[MAX_SERIALIZED_LEN];
+    int plainLen = Message::createAck(99).serialize(plain, sizeof(plain));
+    TEST_ASSERT_EQUAL(plainLen, Message::createAck(99).serializeEncrypted(encrypted, sizeof(encrypted), PAYLOAD_KEY, 1));
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(plain, encrypted, plainLen);
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserializeEncrypted(plain, plainLen, PAYLOAD_KEY));
+    TEST_ASSERT_EQUAL(MessageType::Ack, decoded.type);
+    TEST_ASSERT_EQUAL(99, decoded.ackData.seq);
+}
+
+void test_siphash_matches_the_reference_vector()
+{
+    // Appendix A of the SipHash paper: key 00..0f, message 00..0e
+    uint8_t key[SIPHASH_KEY_LEN];
+    uint8_t data[15];
+    for (uint8_t i = 0; i < sizeof(key); i++)
+    {
+        key[i] = i;
+    }
+    for (uint8_t i = 0; i < sizeof(data); i++)
+    {
+        data[i] = i;
+    }
+    TEST_ASSERT_TRUE(sipHash24(key, data, sizeof(data)) == 0xa129ca6149be45e5ULL);
+}
+
+static const uint8_t AUTH_KEY[AUTH_KEY_LEN] = {0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08,
+                                               0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x00};
+
+void test_authenticated_message_round_trips()
+{
+    Message text = Message::createTextWithGps(8, "ALL CLEAR", 47123456, 8123456);
+    uint8_t plain[MAX_SERIALIZED_LEN];
+    int plainLen = text.serialize(plain, sizeof(plain));
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = text.serializeAuthenticated(buf, sizeof(buf), AUTH_KEY);
+
+    // The message as before, the tag, then the CRC-8 over both
+    TEST_ASSERT_EQUAL(plainLen + AUTH_TAG_LEN, len);
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(plain, buf, plainLen - MESSAGE_CRC_LEN);
+    TEST_ASSERT_TRUE(Message::crcMatches(buf, len));
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserializeAuthenticated(buf, len, AUTH_KEY));
+    TEST_ASSERT_EQUAL_STRING("ALL CLEAR", decoded.textData.text);
+    TEST_ASSERT_EQUAL(8123456, decoded.textData.lon);
+
+    // An ACK is tagged too
+    len = Message::createAck(8).serializeAuthenticated(buf, sizeof(buf), AUTH_KEY);
+    TEST_ASSERT_EQUAL(3 + AUTH_TAG_LEN, len);
+    TEST_ASSERT_TRUE(decoded.deserializeAuthenticated(buf, len, AUTH_KEY));
+    TEST_ASSERT_EQUAL(MessageType::Ack, decoded.type);
+}
+
+void test_tampered_or_untagged_message_fails_auth()
+{
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = Message::createText(8, "ALL CLEAR").serializeAuthenticated(buf, sizeof(buf), AUTH_KEY);
+    Message decoded;
+
+    // Changed seq with a fixed-up CRC: passes the CRC, fails the tag
+    buf[1] = 9;
+    resealCrc(buf, len);
+    TEST_ASSERT_TRUE(Message::crcMatches(buf, len));
+    TEST_ASSERT_FALSE(decoded.deserializeAuthenticated(buf, len, AUTH_KEY));
+    TEST_ASSERT_EQUAL(-1, Message::stripAuthTag(buf, len, AUTH_KEY));
+
+    // Tagged under another key
+    uint8_t otherKey[AUTH_KEY_LEN] = {0};
+    len = Message::createText(8, "ALL CLEAR").serializeAuthenticated(buf, sizeof(buf), otherKey);
+    TEST_ASSERT_FALSE(decoded.deserializeAuthenticated(buf, len, AUTH_KEY));
+
+    // No tag at all
+    len = Message::createAck(8).serialize(buf, sizeof(buf));
+    TEST_ASSERT_FALSE(decoded.deserializeAuthenticated(buf, len, AUTH_KEY));
+}
+
+void test_fec_message_round_trips()
+{
+    Message text = Message::createTextWithGps(8, "ALL CLEAR", 47123456, 8123456);
+    uint8_t plain[MAX_SERIALIZED_LEN];
+    int plainLen = text.serialize(plain, sizeof(plain));
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = text.serializeFec(buf, sizeof(buf), 4);
+
+    // The message as before, CRC-8 included, then the parity
+    TEST_ASSERT_EQUAL(plainLen + 4, len);
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(plain, buf, plainLen);
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserializeFec(buf, len, 4));
+    TEST_ASSERT_EQUAL_STRING("ALL CLEAR", decoded.textData.text);
+    TEST_ASSERT_EQUAL(plainLen, Message::correctFec(buf, len, 4));
+
+    // No room, or more parity than supported
+    TEST_ASSERT_EQUAL(-1, text.serializeFec(buf, plainLen + 3, 4));
+    TEST_ASSERT_EQUAL(-1, text.serializeFec(buf, sizeof(buf), MAX_FEC_PARITY_LEN + 1));
+}
+
+void test_fec_repairs_any_single_damaged_byte()
+{
+    uint8_t clean[MAX_FRAME_LEN];
+    int len = Message::createTextWithGps(3, "MEET AT THE BRIDGE", 47376887, 8541694).serializeFec(clean, sizeof(clean), 2);
+    uint8_t buf[MAX_FRAME_LEN];
+    Message decoded;
+
+    // Type, text, GPS, CRC-8 or parity: wherever it hits, the frame is repaired
+    for (int byte = 0; byte < len; byte++)
+    {
+        memcpy(buf, clean, len);
+        buf[byte] ^= 0xA5;
+        TEST_ASSERT_TRUE(decoded.deserializeFec(buf, len, 2));
+        TEST_ASSERT_EQUAL_STRING("MEET AT THE BRIDGE", decoded.textData.text);
+        TEST_ASSERT_EQUAL(47376887, decoded.textData.lat);
+
+        TEST_ASSERT_EQUAL(len - 2, Message::correctFec(buf, len, 2));
+        TEST_ASSERT_EQUAL_UINT8_ARRAY(clean, buf, len);
+    }
+}
+
+void test_fec_repairs_up_to_half_its_parity()
+{
+    uint8_t buf[MAX_FRAME_LEN];
+    Message ack = Message::createAck(42);
+    Message decoded;
+
+    // Four parity bytes: two damaged bytes are repaired, even in a 3-byte ACK
+    int len = ack.serializeFec(buf, sizeof(buf), 4);
+    buf[0] ^= 0xFF;
+    buf[5] ^= 0x01;
+    TEST_ASSERT_TRUE(decoded.deserializeFec(buf, len, 4));
+    TEST_ASSERT_EQUAL(MessageType::Ack, decoded.type);
+    TEST_ASSERT_EQUAL(42, decoded.ackData.seq);
+
+    // Three are too many: the frame is dropped, not delivered wrong
+    len = ack.serializeFec(buf, sizeof(buf), 4);
+    buf[0] ^= 0xFF;
+    buf[1] ^= 0x10;
+    buf[2] ^= 0x33;
+    TEST_ASSERT_FALSE(decoded.deserializeFec(buf, len, 4));
+
+    // A frame no longer than its parity can't be one
+    TEST_ASSERT_EQUAL(-1, Message::correctFec(buf, 4, 4));
+}
+
+void test_serialize_refuses_text_variants_outside_the_profile()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+
+    // Whatever PROTOCOL_PROFILE this is built with, only its variants encode
+    TEST_ASSERT_EQUAL(profileAllowsText(PROTOCOL_PROFILE, false, true),
+                      Message::createText(1, "NOTE").serialize(buf, sizeof(buf)) > 0);
+    TEST_ASSERT_EQUAL(profileAllowsText(PROTOCOL_PROFILE, true, true),
+                      Message::createTextWithGps(2, "NOTE", 47000000, 8000000).serialize(buf, sizeof(buf)) > 0);
+    TEST_ASSERT_EQUAL(profileAllowsText(PROTOCOL_PROFILE, true, false),
+                      Message::createTextWithGps(3, "", 47000000, 8000000).serialize(buf, sizeof(buf)) > 0);
+    TEST_ASSERT_TRUE(Message::createAck(1).serialize(buf, sizeof(buf)) > 0);
+}
+
+void test_crc8_matches_the_reference_check_value()
+{
+    const uint8_t check[] = {'1', '2', '3', '4', '5', '6', '7', '8', '9'};
+    TEST_ASSERT_EQUAL_HEX8(0xF4, crc8(check, sizeof(check)));
+    TEST_ASSERT_EQUAL_HEX8(0x00, crc8(check, 0));
+}
+
+void test_flipped_bit_in_packed_text_fails_the_crc()
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createTextWithGps(3, "MEET AT THE BRIDGE", 47376887, 8541694).serialize(buf, sizeof(buf));
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+
+    // Every single bit of the packed text (and with it every flipped character)
+    // is caught, though the frame would otherwise decode
+    for (int byte = 4; byte < 4 + buf[3]; byte++)
+    {
+        for (int bit = 0; bit < 8; bit++)
+        {
+            buf[byte] ^= 1 << bit;
+            TEST_ASSERT_FALSE(Message::crcMatches(buf, len));
+            TEST_ASSERT_FALSE(decoded.deserialize(buf, len));
+            buf[byte] ^= 1 << bit;
+        }
+    }
+
+    // Same for the CRC byte itself
+    buf[len - 1] ^= 0x01;
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, len));
+    buf[len - 1] ^= 0x01;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+    TEST_ASSERT_EQUAL_STRING("MEET AT THE BRIDGE", decoded.textData.text);
+}
+
+void test_persisted_settings_round_trip()
+{
+    PersistedSettings settings;
+    settings.frequency = LORA_BAND_MIN_HZ + static_cast<uint32_t>(LORA_BANDWIDTH / 2);
+    settings.txPower = LORA_TX_POWER_MIN_DBM;
+    settings.spreadingFactor = 12;
+
+    uint8_t buf[PERSISTED_SETTINGS_LEN];
+    TEST_ASSERT_EQUAL(PERSISTED_SETTINGS_LEN, serializePersistedSettings(settings, buf));
+    TEST_ASSERT_EQUAL_HEX8(PERSISTED_SETTINGS_VERSION, buf[0]);
+    TEST_ASSERT_EQUAL_HEX8(crc8(buf, PERSISTED_SETTINGS_LEN - 1), buf[PERSISTED_SETTINGS_LEN - 1]);
+
+    PersistedSettings loaded = defaultPersistedSettings();
+    TEST_ASSERT_TRUE(parsePersistedSettings(buf, sizeof(buf), loaded));
+    TEST_ASSERT_EQUAL_UINT32(settings.frequency, loaded.frequency);
+    TEST_ASSERT_EQUAL(settings.txPower, loaded.txPower);
+    TEST_ASSERT_EQUAL_UINT8(12, loaded.spreadingFactor);
+}
+
+void test_corrupt_or_missing_settings_keep_the_defaults()
+{
+    const PersistedSettings defaults = defaultPersistedSettings();
+    PersistedSettings changed = defaults;
+    changed.spreadingFactor = defaults.spreadingFactor == 12 ? 7 : 12;
+    uint8_t buf[PERSISTED_SETTINGS_LEN];
+    serializePersistedSettings(changed, buf);
+
+    // First boot: nothing saved yet
+    PersistedSettings loaded = defaults;
+    TEST_ASSERT_FALSE(parsePersistedSettings(buf, 0, loaded));
+    TEST_ASSERT_FALSE(parsePersistedSettings(buf, PERSISTED_SETTINGS_LEN - 1, loaded));
+
+    // Any flipped bit is caught by the CRC
+    for (size_t byte = 0; byte < PERSISTED_SETTINGS_LEN; byte++)
+    {
+        for (int bit = 0; bit < 8; bit++)
+        {
+            buf[byte] ^= 1 << bit;
+            TEST_ASSERT_FALSE(parsePersistedSettings(buf, sizeof(buf), loaded));
+            buf[byte] ^= 1 << bit;
+        }
+    }
+    TEST_ASSERT_EQUAL_UINT8(defaults.spreadingFactor, loaded.spreadingFactor);
+
+    // Erased flash, another layout version, or values this build would not select
+    memset(buf, 0xFF, sizeof(buf));
+    TEST_ASSERT_FALSE(parsePersistedSettings(buf, sizeof(buf), loaded));
+    serializePersistedSettings(changed, buf);
+    buf[0] = PERSISTED_SETTINGS_VERSION + 1;
+    buf[PERSISTED_SETTINGS_LEN - 1] = crc8(buf, PERSISTED_SETTINGS_LEN - 1);
+    TEST_ASSERT_FALSE(parsePersistedSettings(buf, sizeof(buf), loaded));
+    changed.spreadingFactor = 13;
+    serializePersistedSettings(changed, buf);
+    TEST_ASSERT_FALSE(parsePersistedSettings(buf, sizeof(buf), loaded));
+
+    TEST_ASSERT_EQUAL_UINT32(defaults.frequency, loaded.frequency);
+    TEST_ASSERT_EQUAL(defaults.txPower, loaded.txPower);
+    TEST_ASSERT_EQUAL_UINT8(defaults.spreadingFactor, loaded.spreadingFactor);
+}
+
+void test_link_stats_layout()
+{
+    LinkStats link;
+    link.txFrames = 0x0102;
+    link.rxFrames = 0x0304;
+    link.crcFailures = 0x0506;
+    link.acksReceived = 0x0708;
+    link.bufferDrops = 0xFFFF; // Wrapped counters stay unsigned
+    link.spreadingFactor = 9;
+
+    uint8_t buf[LINK_STATS_LEN + 1];
+    buf[LINK_STATS_LEN] = 0xAA;
+    TEST_ASSERT_EQUAL(LINK_STATS_LEN, serializeLinkStats(link, buf));
+
+    const uint8_t expected[LINK_STATS_LEN] = {LINK_STATS_FORMAT_VERSION, 0x02, 0x01, 0x04, 0x03, 0x06,
+                                              0x05, 0x08, 0x07, 0xFF, 0xFF, 9};
+    TEST_ASSERT_EQUAL_UINT8_ARRAY(expected, buf, LINK_STATS_LEN);
+    TEST_ASSERT_EQUAL_HEX8(0xAA, buf[LINK_STATS_LEN]);
+}
+
+/// Value of a fixed-size field at buf (variable fields are not compared)
+static int64_t readField(const FieldSpec &field, const uint8_t *buf)
+{
+    switch (field.encoding)
+    {
+    case FieldEncoding::U8:
+        return buf[0];
+    case FieldEncoding::I8:
+        return static_cast<int8_t>(buf[0]);
+    case FieldEncoding::U16:
+        return readU16(buf);
+    case FieldEncoding::I16:
+        return readI16(buf);
+    case FieldEncoding::U32:
+        return readU32(buf);
+    case FieldEncoding::I32:
+        return readI32(buf);
+    default:
+        return 0;
+    }
+}
+
+/// Walk a serialized message along its declared layout: each present field
+/// must hold the expected value, and the last one must end the message
+static void assertMatchesLayout(const Message &msg, const int64_t *expected, size_t expectedCount)
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int serialized = msg.serialize(buf, sizeof(buf));
+    TEST_ASSERT_TRUE(serialized > 0);
+
+    // The layouts end before the trailing CRC
+    TEST_ASSERT_TRUE(Message::crcMatches(buf, serialized));
+    size_t len = serialized - MESSAGE_CRC_LEN;
+
+    const MessageLayout *layout = messageLayout(buf[0]);
+    TEST_ASSERT_NOT_NULL(layout);
+
+    size_t variableEnd = 0;
+    size_t end = 0;
+    size_t present = 0;
+    for (uint8_t i = 0; i < layout->fieldCount; i++)
+    {
+        const FieldSpec &field = layout->fields[i];
+        size_t offset = field.afterVariable ? variableEnd + field.offset : field.offset;
+        if (field.optional && offset >= len)
+        {
+            continue;
+        }
+
+        size_t size = field.size;
+        if (size == FIELD_VARIABLE)
+        {
+            TEST_ASSERT_NOT_NULL(field.lengthField);
+            for (uint8_t j = 0; j < i; j++)
+            {
+                if (strcmp(layout->fields[j].name, field.lengthField) == 0)
+                {
+                    size = buf[layout->fields[j].offset];
+                }
+            }
+            if (size > MAX_RAW_LENGTH)
+            {
+                size = MAX_RAW_LENGTH;
+            }
+            variableEnd = offset + size;
+        }
+
+        TEST_ASSERT_TRUE_MESSAGE(offset + size <= len, field.name);
+        TEST_ASSERT_TRUE(present < expectedCount);
+        if (field.size != FIELD_VARIABLE)
+        {
+            TEST_ASSERT_TRUE_MESSAGE(expected[present] == readField(field, buf + offset), field.name);
+        }
+        present++;
+        end = offset + size;
+    }
+
+    TEST_ASSERT_EQUAL(expectedCount, present);
+    TEST_ASSERT_EQUAL(len, end);
+}
+
+void test_layouts_match_serialize()
+{
+    const int64_t text[] = {0x01, 7, 2, 2, 0, 0x02};
+    assertMatchesLayout(Message::createText(7, "HI", MessagePriority::High), text, 6);
+
+    const int64_t gps[] = {0x01, 1, 0, 0, 0, 0x01, 0x01020304, -2};
+    assertMatchesLayout(Message::createTextWithGps(1, "", 0x01020304, -2), gps, 8);
+
+    Message extended = Message::createTextWithGps(2, "", 0x01020304, -2);
+    extended.textData.gpsFields = GPS_FIELD_ALTITUDE | GPS_FIELD_SPEED;
+    extended.textData.altitude = -12;
+    extended.textData.speed = 1250;
+    const int64_t gpsExtended[] = {0x01, 2, 0, 0, 0, 0x11, 0x01020304, -2, 0x03, -12, 1250};
+    assertMatchesLayout(extended, gpsExtended, 11);
+
+    Message fragment = extended;
+    fragment.textData.isFragment = true;
+    fragment.textData.fragmentId = 2;
+    fragment.textData.fragmentIndex = 1;
+    fragment.textData.fragmentCount = 3;
+    const int64_t fragmented[] = {0x01, 2, 0, 0, 0, 0x31, 0x01020304, -2, 0x03, -12, 1250, 2, 1, 3};
+    assertMatchesLayout(fragment, fragmented, 14);
+
+    Message hopText = Message::createText(8, "HI", MessagePriority::High);
+    hopText.textData.hopAck = true;
+    const int64_t hopFlagged[] = {0x01, 8, 2, 2, 0, 0x0A};
+    assertMatchesLayout(hopText, hopFlagged, 6);
+
+    const int64_t nack[] = {0x04, 9, 0x05};
+    assertMatchesLayout(Message::createNack(9, DecodeFailureReason::CrcMismatch), nack, 3);
+
+    const int64_t ack[] = {0x02, 9};
+    assertMatchesLayout(Message::createAck(9), ack, 2);
+
+    const int64_t gpsDelta[] = {0x07, 12, 10, -300, 32767};
+    assertMatchesLayout(Message::createGpsDelta(12, 10, -300, 32767), gpsDelta, 5);
+
+    const int64_t hopAck[] = {0x0F, 9, 3};
+    assertMatchesLayout(Message::createHopAck(9, 3), hopAck, 3);
+
+    const int64_t identify[] = {0x08, 3};
+    assertMatchesLayout(Message::createIdentify(3), identify, 2);
+
+    NodeInfo info = {4, 433920000, 11, 4, 5, -3};
+    const int64_t identifyResponse[] = {0x09, 4, 433920000, 11, 4, 5, -3};
+    assertMatchesLayout(Message::createIdentifyResponse(info), identifyResponse, 7);
+
+    const int64_t configRequest[] = {0x0D, 4};
+    assertMatchesLayout(Message::createConfigRequest(4), configRequest, 2);
+
+    NodeConfig config = {info, 0x12, 8, 1, 2, 3, 1, 2};
+    const int64_t configResponse[] = {0x0E, 4, 433920000, 11, 4, 5, -3, 0x12, 8, 1, 2, 3, 1, 2};
+    assertMatchesLayout(Message::createConfigResponse(config), configResponse, 14);
+
+    const int64_t channelSwitch[] = {0x0A, 868100000, 1};
+    assertMatchesLayout(Message::createChannelSwitch(868100000, ChannelSwitchStage::Accept), channelSwitch, 3);
+
+    const int64_t rateSwitch[] = {0x10, 9, 0};
+    assertMatchesLayout(Message::createRateSwitch(9, ChannelSwitchStage::Propose), rateSwitch, 3);
+
+    ListenScheduleMessage schedule = {2, 1, 60000, 10000, 700};
+    const int64_t listenSchedule[] = {0x0B, 2, 1, 60000, 10000, 700};
+    assertMatchesLayout(Message::createListenSchedule(schedule), listenSchedule, 6);
+
+    const int64_t trackStatus[] = {0x0C, 5, 0x80000001};
+    assertMatchesLayout(Message::createTrackStatus(5, 0x80000001UL), trackStatus, 3);
+
+    const uint8_t frame[] = {0xDE, 0xAD, 0xBE};
+    const int64_t raw[] = {0x80, 3, 0};
+    assertMatchesLayout(Message::createRaw(frame, sizeof(frame)), raw, 3);
+
+    const int64_t status[] = {0x81, 1, 6};
+    assertMatchesLayout(Message::createStatus(BridgeStatus::RadioBusy, 6), status, 3);
+    const int64_t dutyCycleStatus[] = {0x81, 3, 6};
+    assertMatchesLayout(Message::createStatus(BridgeStatus::DutyCycleLimited, 6), dutyCycleStatus, 3);
+    uint8_t statusFrame[MAX_SERIALIZED_LEN];
+    int statusLen = Message::createStatus(BridgeStatus::DutyCycleLimited, 6).serialize(statusFrame, sizeof(statusFrame));
+    Message parsedStatus;
+    TEST_ASSERT_TRUE(parsedStatus.deserialize(statusFrame, statusLen));
+    TEST_ASSERT_EQUAL(BridgeStatus::DutyCycleLimited, parsedStatus.statusData.code);
+
+    // Truncated read: 2 of 5 bytes, the rest goes out as zero
+    const uint8_t partial[] = {0x01, 0x07};
+    const int64_t decodeFailure[] = {0x82, 2, 5, 0};
+    assertMatchesLayout(Message::createDecodeFailure(DecodeFailureReason::Truncated, partial, 2, 5), decodeFailure, 4);
+
+    const int64_t deliveryReport[] = {0x83, 2, 9};
+    assertMatchesLayout(Message::createDeliveryReport(DeliveryOutcome::Failed, 9), deliveryReport, 3);
+
+    const int64_t linkQuality[] = {0x84, 9, -121, -30};
+    assertMatchesLayout(Message::createLinkQuality(9, -121, -7.5f), linkQuality, 4);
+
+    const int64_t battery[] = {0x05, 9, 100, 4200};
+    assertMatchesLayout(Message::createBattery(9, 100, 4200), battery, 4);
+
+    const int64_t sos[] = {0x06, 9, -33856784, 151215297};
+    assertMatchesLayout(Message::createSos(9, -33856784, 151215297), sos, 4);
+}
+
+void test_every_known_type_has_a_layout()
+{
+    for (int type = 0; type <= 0xFF; type++)
+    {
+        TEST_ASSERT_EQUAL(isKnownMessageType(type), messageLayout(type) != nullptr);
+    }
+}
+
+void test_config_response_round_trips()
+{
+    NodeConfig config = {{7, 868100000, 9, 7, 8, -2}, 0x34, 12, 1, 4, 0, 2, 1};
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createConfigResponse(config).serialize(buf, sizeof(buf));
+    TEST_ASSERT_EQUAL(CONFIG_RESPONSE_LEN, len);
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+    TEST_ASSERT_EQUAL(MessageType::ConfigResponse, decoded.type);
+    TEST_ASSERT_EQUAL(7, decoded.nodeConfigData.radio.nodeId);
+    TEST_ASSERT_EQUAL(868100000, decoded.nodeConfigData.radio.frequency);
+    TEST_ASSERT_EQUAL(-2, decoded.nodeConfigData.radio.txPower);
+    TEST_ASSERT_EQUAL_HEX8(0x34, decoded.nodeConfigData.syncWord);
+    TEST_ASSERT_EQUAL(12, decoded.nodeConfigData.preambleLength);
+    TEST_ASSERT_EQUAL(4, decoded.nodeConfigData.firmwareMinor);
+    TEST_ASSERT_EQUAL(2, decoded.nodeConfigData.protocolProfile);
+    TEST_ASSERT_EQUAL(1, decoded.nodeConfigData.charset);
+
+    // One byte short is rejected
+    resealCrc(buf, len - 1);
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, len - 1));
+}
+
+void test_text_length_fields_must_agree()
+{
+    // 11 characters pack into 9 bytes
+    Message text = Message::createText(7, "HELLO WORLD");
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = text.serialize(buf, sizeof(buf));
+    TEST_ASSERT_EQUAL(11, buf[2]);
+    TEST_ASSERT_EQUAL(9, buf[3]);
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+
+    // A character count that would pack into 8 bytes
+    buf[2] = 10;
+    resealCrc(buf, len);
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, len));
+
+    // A packed length one byte short of the characters
+    buf[2] = 11;
+    buf[3] = 8;
+    resealCrc(buf, len);
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, len));
+
+    // No characters but packed bytes
+    buf[2] = 0;
+    buf[3] = 9;
+    resealCrc(buf, len);
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, len));
+}
+
+/// Deserialize a frame from a heap copy of exactly its length, so that a read past
+/// its end trips the sanitizer instead of landing in a larger stack buffer
+static bool deserializeExact(const uint8_t *frame, size_t len)
+{
+    uint8_t *copy = new uint8_t[len + 1]; // new[0] may not be dereferenced either way
+    memcpy(copy, frame, len);
+    Message decoded;
+    bool ok = decoded.deserialize(copy, len);
+    delete[] copy;
+    return ok;
+}
+
+/// Body bytes closed with their CRC-8, deserialized as above
+static bool deserializeWithCrc(const uint8_t *body, size_t len)
+{
+    uint8_t frame[256];
+    memcpy(frame, body, len);
+    frame[len] = crc8(body, len);
+    return deserializeExact(frame, len + 1);
+}
+
+void test_deserialize_survives_every_type_byte()
+{
+    const uint8_t empty[1] = {0};
+    TEST_ASSERT_FALSE(deserializeExact(empty, 0));
+
+    uint32_t noiseState = 42;
+    for (int type = 0; type <= 0xFF; type++)
+    {
+        uint8_t frame[MAX_SERIALIZED_LEN] = {static_cast<uint8_t>(type), 1, 2, 3, 4};
+        deserializeExact(frame, 1);
+        deserializeWithCrc(frame, 1);
+        deserializeWithCrc(frame, 2);
+        deserializeWithCrc(frame, 5);
+
+        for (size_t i = 1; i < sizeof(frame); i++)
+        {
+            noiseState = noiseState * 1103515245 + 12345;
+            frame[i] = noiseState >> 16;
+        }
+        deserializeExact(frame, sizeof(frame));
+        // Only a known type can decode, however its body looks
+        if (deserializeWithCrc(frame, sizeof(frame) - MESSAGE_CRC_LEN))
+        {
+            TEST_ASSERT_TRUE(isKnownMessageType(type));
+        }
+    }
+}
+
+void test_every_truncation_of_a_valid_message_is_rejected()
+{
+    Message extended = Message::createTextWithGps(2, "HERE", 0x01020304, -2);
+    extended.textData.gpsFields = GPS_FIELD_ALTITUDE | GPS_FIELD_SPEED;
+    extended.textData.altitude = -12;
+    extended.textData.speed = 1250;
+    Message fragment = extended;
+    fragment.textData.isFragment = true;
+    fragment.textData.fragmentId = 2;
+    fragment.textData.fragmentIndex = 1;
+    fragment.textData.fragmentCount = 3;
+    NodeInfo info = {4, 433920000, 11, 4, 5, -3};
+    NodeConfig config = {info, 0x12, 8, 1, 2, 3, 1, 2};
+    ListenScheduleMessage schedule = {2, 1, 60000, 10000, 700};
+
+    const Message messages[] = {
+        Message::createText(7, "HELLO WORLD", MessagePriority::High),
+        Message::createTextWithGps(7, "HELLO", 37774200, -122419200),
+        Message::createTextWithGps(8, "", 37774200, -122419200),
+        extended,
+        fragment,
+        Message::createAck(9),
+        Message::createNack(9, DecodeFailureReason::CrcMismatch),
+        Message::createBattery(9, 100, 4200),
+        Message::createSos(9, -33856784, 151215297),
+        Message::createGpsDelta(12, 10, -300, 32767),
+        Message::createHopAck(9, 3),
+        Message::createIdentify(3),
+        Message::createIdentifyResponse(info),
+        Message::createConfigRequest(4),
+        Message::createConfigResponse(config),
+        Message::createChannelSwitch(868100000, ChannelSwitchStage::Accept),
+        Message::createRateSwitch(9, ChannelSwitchStage::Propose),
+        Message::createListenSchedule(schedule),
+        Message::createTrackStatus(5, 0x80000001UL),
+    };
+
+    for (const Message &msg : messages)
+    {
+        uint8_t buf[MAX_SERIALIZED_LEN];
+        int len = msg.serialize(buf, sizeof(buf));
+        TEST_ASSERT_GREATER_THAN(0, len);
+        TEST_ASSERT_TRUE(deserializeExact(buf, len));
+
+        // Every shorter prefix, as cut off and with a CRC of its own
+        for (int cut = 0; cut < len; cut++)
+        {
+            TEST_ASSERT_FALSE(deserializeExact(buf, cut));
+        }
+        for (int cut = 0; cut < len - static_cast<int>(MESSAGE_CRC_LEN); cut++)
+        {
+            TEST_ASSERT_FALSE(deserializeWithCrc(buf, cut));
+        }
+    }
+}
+
+void test_oversized_length_fields_are_rejected()
+{
+    // Both text length fields at their maximum in a full-length frame
+    uint8_t text[MAX_SERIALIZED_LEN - MESSAGE_CRC_LEN] = {0x01, 0, 0xFF, 0xFF};
+    TEST_ASSERT_FALSE(deserializeWithCrc(text, sizeof(text)));
+
+    // Packed data present for every character, but more than MAX_TEXT_LENGTH of them
+    uint8_t tooMany[5 + 45] = {0x01, 0, 60, 45};
+    TEST_ASSERT_FALSE(deserializeWithCrc(tooMany, sizeof(tooMany)));
+
+    // A packed length beyond the end of the frame
+    const uint8_t beyond[] = {0x01, 0, 3, 40, 0, 0, 0, 0};
+    TEST_ASSERT_FALSE(deserializeWithCrc(beyond, sizeof(beyond)));
+
+    // Every combination of the two length fields against a fixed-size frame
+    for (int charCount = 0; charCount <= 0xFF; charCount++)
+    {
+        for (int packedLen = 0; packedLen <= 0xFF; packedLen++)
+        {
+            uint8_t frame[20] = {0x01, 0, static_cast<uint8_t>(charCount), static_cast<uint8_t>(packedLen)};
+            deserializeWithCrc(frame, sizeof(frame));
+        }
+    }
+}
+
+void test_ack_serializes_at_buffer_boundaries()
+{
+    Message ack = Message::createAck(0xFE);
+
+    // Exactly 3 bytes with the CRC, also from a buffer of exactly that size
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    memset(buf, 0xAA, sizeof(buf));
+    TEST_ASSERT_EQUAL(3, ack.serialize(buf, sizeof(buf)));
+    TEST_ASSERT_EQUAL_HEX8(0x02, buf[0]);
+    TEST_ASSERT_EQUAL_HEX8(0xFE, buf[1]);
+    TEST_ASSERT_EQUAL_HEX8(crc8(buf, 2), buf[2]);
+    TEST_ASSERT_EQUAL_HEX8(0xAA, buf[3]);
+
+    uint8_t exact[3];
+    TEST_ASSERT_EQUAL(3, ack.serialize(exact, sizeof(exact)));
+
+    // Too small: rejected without writing
+    uint8_t small[2] = {0xAA, 0xAA};
+    TEST_ASSERT_EQUAL(-1, ack.serialize(small, sizeof(small)));
+    TEST_ASSERT_EQUAL_HEX8(0xAA, small[0]);
+    TEST_ASSERT_EQUAL_HEX8(0xAA, small[1]);
+    TEST_ASSERT_EQUAL(-1, ack.serialize(small, 0));
+
+    Message decoded;
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, 3));
+    TEST_ASSERT_EQUAL(MessageType::Ack, decoded.type);
+    TEST_ASSERT_EQUAL(0xFE, decoded.ackData.seq);
+
+    // A truncated ACK is rejected
+    resealCrc(buf, 2);
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, 2));
+    TEST_ASSERT_FALSE(decoded.deserialize(buf, 1));
+}
+
+void test_notified_length_is_the_serialized_length()
+{
+    // What BLE notifies: buf[0..len) of the serialize() result, never the whole buffer
+    const char maxText[] = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789ABCDEFGHIJKLMN";
+    const Message messages[] = {Message::createAck(7), Message::createTextWithGps(8, "", 47000000, 8000000),
+                                Message::createTextWithGps(9, maxText, -33856784, 151215297)};
+    const int expected[] = {3, 14, 52};
+    for (int i = 0; i < 3; i++)
+    {
+        uint8_t buf[MAX_SERIALIZED_LEN];
+        memset(buf, 0xAA, sizeof(buf));
+        int len = messages[i].serialize(buf, sizeof(buf));
+        TEST_ASSERT_EQUAL(expected[i], len);
+        for (size_t j = len; j < sizeof(buf); j++)
+        {
+            TEST_ASSERT_EQUAL_HEX8(0xAA, buf[j]);
+        }
+
+        Message decoded;
+        TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+        TEST_ASSERT_EQUAL(messages[i].type, decoded.type);
+
+        // The padded 64-byte buffer is not a valid message
+        TEST_ASSERT_FALSE(decoded.deserialize(buf, sizeof(buf)));
+    }
+}
+
+void test_charset_round_trips_every_value()
+{
+    for (uint8_t value = 0; value < 64; value++)
+    {
+        if (value == CHARSET_SHIFT_CODE)
+        {
+            TEST_ASSERT_EQUAL(-1, char_to_6bit(sixbit_to_char(value))); // Never typed
+            continue;
+        }
+        char ch = sixbit_to_char(value);
+        TEST_ASSERT_EQUAL(value, char_to_6bit(ch));
+        if (ch >= 'A' && ch <= 'Z')
+        {
+            TEST_ASSERT_EQUAL(value, char_to_6bit(ch - 'A' + 'a'));
+        }
+    }
+    TEST_ASSERT_EQUAL(-1, char_to_6bit('\t'));
+}
+
+/// Serialize a text and decode it again, returning its code count
+static int roundTripText(const char *text, Message &decoded)
+{
+    uint8_t buf[MAX_SERIALIZED_LEN];
+    int len = Message::createText(1, text).serialize(buf, sizeof(buf));
+    TEST_ASSERT_GREATER_THAN(0, len);
+    TEST_ASSERT_TRUE(decoded.deserialize(buf, len));
+    return buf[2];
+}
+
+void test_uppercase_texts_need_no_shift_codes()
+{
+    Message decoded;
+    TEST_ASSERT_EQUAL(11, roundTripText("HELLO WORLD", decoded));
+    TEST_ASSERT_EQUAL_STRING("HELLO WORLD", decoded.textData.text);
+    TEST_ASSERT_EQUAL(CHARSET_SHIFT_CODE < 0 ? 11 : 14, text_code_count("Hello World"));
+}
+
+#if PROTOCOL_CHARSET == CHARSET_MIXED_CASE
+void test_mixed_case_uses_the_fewest_shift_codes()
+{
+    Message decoded;
+
+    // H, a case switch before "ello", a single shift for W
+    TEST_ASSERT_EQUAL(14, roundTripText("Hello World", decoded));
+    TEST_ASSERT_EQUAL_STRING("Hello World", decoded.textData.text);
+
+    // Alternating case: one shift per lowercase letter beats switching back and forth
+    TEST_ASSERT_EQUAL(9, roundTripText("aBcDeF", decoded));
+    TEST_ASSERT_EQUAL_STRING("aBcDeF", decoded.textData.text);
+
+    // One switch to lowercase, one back for MAP; digits and punctuation keep the case in force
+    TEST_ASSERT_EQUAL(31, roundTripText("meet at 5pm, bring the MAP!", decoded));
+    TEST_ASSERT_EQUAL_STRING("meet at 5pm, bring the MAP!", decoded.textData.text);
+}
+
+void test_stray_shift_codes_are_rejected()
+{
+    char text[MAX_TEXT_LENGTH + 1];
+    uint8_t packed[2];
+
+    // Shift, then the digit 1 (value 28): a shift only applies to letters
+    packed[0] = (CHARSET_SHIFT_CODE << 2) | (28 >> 4);
+    packed[1] = (28 & 0x0F) << 4;
+    TEST_ASSERT_FALSE(unpack_text(packed, 2, 2, text, sizeof(text)));
+
+    // A lone shift at the end has no letter to apply to
+    TEST_ASSERT_FALSE(unpack_text(packed, 1, 1, text, sizeof(text)));
+}
+#endif
+
+void test_gps_distance_and_bearing()
+{
+    // Zurich HB -> Bern HB: 95.49 km, 240.4 degrees (and 59.6 back)
+    TEST_ASSERT_UINT32_WITHIN(1, 95491, gpsDistanceM(47376887, 8541694, 46947974, 7447447));
+    TEST_ASSERT_EQUAL(240, gpsBearingDeg(47376887, 8541694, 46947974, 7447447));
+    TEST_ASSERT_EQUAL(60, gpsBearingDeg(46947974, 7447447, 47376887, 8541694));
+
+    // London -> New York: 5575 km, west-north-west
+    TEST_ASSERT_UINT32_WITHIN(1, 5574848, gpsDistanceM(51500700, -124600, 40689200, -74044500));
+    TEST_ASSERT_EQUAL(288, gpsBearingDeg(51500700, -124600, 40689200, -74044500));
+
+    // Short hops: 0.0001 degree of latitude is 11 m north, of longitude at 47N 8 m east
+    TEST_ASSERT_EQUAL(11, gpsDistanceM(47000000, 8000000, 47000100, 8000000));
+    TEST_ASSERT_EQUAL(0, gpsBearingDeg(47000000, 8000000, 47000100, 8000000));
+    TEST_ASSERT_EQUAL(8, gpsDistanceM(47000000, 8000000, 47000000, 8000100));
+    TEST_ASSERT_EQUAL(270, gpsBearingDeg(47000000, 8000100, 47000000, 8000000));
+
+    // Southern hemisphere and across the antimeridian
+    TEST_ASSERT_UINT32_WITHIN(1, 650, gpsDistanceM(-33856800, 151215300, -33852300, 151210800));
+    TEST_ASSERT_EQUAL(320, gpsBearingDeg(-33856800, 151215300, -33852300, 151210800));
+    TEST_ASSERT_UINT32_WITHIN(1, 22239, gpsDistanceM(0, 179900000, 0, -179900000));
+    TEST_ASSERT_EQUAL(90, gpsBearingDeg(0, 179900000, 0, -179900000));
+
+    // Same position
+    TEST_ASSERT_EQUAL(0, gpsDistanceM(47000000, 8000000, 47000000, 8000000));
+    TEST_ASSERT_EQUAL(0, gpsBearingDeg(47000000, 8000000, 47000000, 8000000));
+
+    Message here = Message::createTextWithGps(1, "", 47376887, 8541694);
+    Message there = Message::createTextWithGps(2, "", 46947974, 7447447);
+    TEST_ASSERT_EQUAL(gpsDistanceM(47376887, 8541694, 46947974, 7447447), here.textData.distanceTo(there.textData));
+    TEST_ASSERT_EQUAL(240, here.textData.bearingTo(there.textData));
+}
+
+int main(int, char **)
+{
+    UNITY_BEGIN();
+    RUN_TEST(test_helpers_write_little_endian);
+    RUN_TEST(test_helpers_round_trip);
+    RUN_TEST(test_gps_fields_are_little_endian_on_the_wire);
+    RUN_TEST(test_gps_fields_round_trip_in_every_combination);
+    RUN_TEST(test_altitude_below_sea_level_and_texts_without_it);
+    RUN_TEST(test_unknown_gps_fields_are_skipped);
+    RUN_TEST(test_fragment_header_is_found_after_unknown_gps_fields);
+    RUN_TEST(test_layouts_match_serialize);
+    RUN_TEST(test_every_known_type_has_a_layout);
+    RUN_TEST(test_config_response_round_trips);
+    RUN_TEST(test_text_length_fields_must_agree);
+    RUN_TEST(test_deserialize_survives_every_type_byte);
+    RUN_TEST(test_every_truncation_of_a_valid_message_is_rejected);
+    RUN_TEST(test_oversized_length_fields_are_rejected);
+    RUN_TEST(test_ack_serializes_at_buffer_boundaries);
+    RUN_TEST(test_notified_length_is_the_serialized_length);
+    RUN_TEST(test_nack_round_trips_its_reason);
+    RUN_TEST(test_link_quality_keeps_sign_and_quarter_db);
+    RUN_TEST(test_battery_round_trips_its_edge_values);
+    RUN_TEST(test_sos_round_trips_its_position);
+    RUN_TEST(test_aes128_matches_the_fips197_example);
+    RUN_TEST(test_encrypted_text_round_trips);
+    RUN_TEST(test_wrong_payload_key_yields_garbage);
+    RUN_TEST(test_acks_are_never_encrypted);
+    RUN_TEST(test_siphash_matches_the_reference_vector);
+    RUN_TEST(test_authenticated_message_round_trips);
+    RUN_TEST(test_tampered_or_untagged_message_fails_auth);
+    RUN_TEST(test_fec_message_round_trips);
+    RUN_TEST(test_fec_repairs_any_single_damaged_byte);
+    RUN_TEST(test_fec_repairs_up_to_half_its_parity);
+    RUN_TEST(test_serialize_refuses_text_variants_outside_the_profile);
+    RUN_TEST(test_crc8_matches_the_reference_check_value);
+    RUN_TEST(test_flipped_bit_in_packed_text_fails_the_crc);
+    RUN_TEST(test_persisted_settings_round_trip);
+    RUN_TEST(test_corrupt_or_missing_settings_keep_the_defaults);
+    RUN_TEST(test_link_stats_layout);
+    RUN_TEST(test_rate_switch_rejects_unusable_factors);
+    RUN_TEST(test_gps_delta_encodes_small_moves_only);
+    RUN_TEST(test_gps_delta_resyncs_periodically);
+    RUN_TEST(test_gps_delta_receiver_rebuilds_positions);
+    RUN_TEST(test_charset_round_trips_every_value);
+    RUN_TEST(test_uppercase_texts_need_no_shift_codes);
+#if PROTOCOL_CHARSET == CHARSET_MIXED_CASE
+    RUN_TEST(test_mixed_case_uses_the_fewest_shift_codes);
+    RUN_TEST(test_stray_shift_codes_are_rejected);
+#endif
+    RUN_TEST(test_gps_distance_and_bearing);
+    return UNITY_END();
+}
@@ -20,6 +20,8 @@
 #include <LoRa.h>
 #include <DisplayManager.h>
 
+static_assert(LORA_RADIO == LORA_RADIO_SX127X, "The debugger reads frames in the DIO0 ISR, which needs an SX127x");
+
 // --- Pin Definitions ---
 /**
  * @brief Define the pins used for SPI communication and peripherals.
@@ -64,8 +66,9 @@ LoRaManager loraManager(LORA_SCK, LORA_MISO, LORA_MOSI, LORA_SS, LORA_RST, LORA_
 // Struct for LoRa packets with metadata
 struct LoRaPacket
 {
-    uint8_t buffer[256];
-    int len;
+    uint8_t buffer[LORA_MAX_PACKET_SIZE];
+    int frameLen; // Size reported by the radio
+    int len;      // Bytes actually read into buffer
     int rssi;
     float snr;
 };
@@ -89,6 +92,42 @@ bool ackPending = false;
 Message pendingAckMsg;
 int pendingAckSeq = 0;
 
+// Last full position update heard, to rebuild GPS deltas against
+GpsReference gpsHeard = {false, 0, 0, 0, 0};
+
+#ifdef LORA_PAYLOAD_KEY
+// Encrypts and decrypts the texts of a fleet built with a payload key
+const uint8_t payloadKey[PAYLOAD_KEY_LEN] = {LORA_PAYLOAD_KEY};
+#endif
+
+#ifdef LORA_AUTH_KEY
+// Tags sent frames and checks received ones, like the bridges of the fleet
+const uint8_t authKey[AUTH_KEY_LEN] = {LORA_AUTH_KEY};
+#endif
+
+/// Serialize a message for the air the way the bridges expect it (LORA_PAYLOAD_KEY, LORA_AUTH_KEY, LORA_FEC_PARITY)
+int encodeFrame(const Message &msg, uint8_t *buf, size_t bufSize)
+{
+#ifdef LORA_PAYLOAD_KEY
+    int len = msg.serializeEncrypted(buf, bufSize, payloadKey, (uint8_t)random(256));
+#else
+    int len = msg.serialize(buf, bufSize);
+#endif
+#ifdef LORA_AUTH_KEY
+    if (len > 0)
+    {
+        len = Message::addAuthTag(buf, len, bufSize, authKey);
+    }
+#endif
+#if LORA_FEC_PARITY > 0
+    if (len > 0)
+    {
+        len = Message::addFec(buf, len, bufSize, LORA_FEC_PARITY);
+    }
+#endif
+    return len;
+}
+
 // Button debouncing and long press detection
 unsigned long lastButtonPressTime = 0;
 const unsigned long BUTTON_DEBOUNCE = 50;       // 50ms debounce
@@ -122,11 +161,18 @@ void onLoRaReceive(int packetSize)
         return;
 
     LoRaPacket packet;
-    packet.len = LoRa.readBytes(packet.buffer, sizeof(packet.buffer));
+    packet.frameLen = packetSize;
+    packet.len = 0;
+
+    // Read exactly the reported frame, never past the buffer
+    while (packet.len < packetSize && packet.len < (int)sizeof(packet.buffer) && LoRa.available())
+    {
+        packet.buffer[packet.len++] = LoRa.read();
+    }
     packet.rssi = LoRa.packetRssi();
     packet.snr = LoRa.packetSnr();
 
-    if (packet.len > 0)
+    if (packet.frameLen > 0)
     {
         xQueueSend(loRaQueue, &packet, 0);
     }
@@ -158,8 +204,8 @@ void enterDeepSleep()
     // Send LoRa message to notify about deep sleep
     Serial.println("Sending deep sleep notification via LoRa...");
     Message deepSleepMsg = Message::createText(0, "Going to deep sleep");
-    uint8_t buf[64];
-    int len = deepSleepMsg.serialize(buf, sizeof(buf));
+    uint8_t buf[MAX_FRAME_LEN];
+    int len = encodeFrame(deepSleepMsg, buf, sizeof(buf));
 
     if (len > 0)
     {
@@ -576,9 +622,43 @@ void loop()
         Serial.print(packet.snr);
         Serial.println(" dB");
 
-        // Deserialize message
+        // Deserialize message (a partial frame would decode as garbage)
         Message msg;
-        if (msg.deserialize(packet.buffer, packet.len))
+#if LORA_FEC_PARITY > 0
+        // Repaired with its parity before the CRC check; beyond repair it shows as a CRC mismatch
+        if (packet.len == packet.frameLen)
+        {
+            int repairedLen = Message::correctFec(packet.buffer, packet.len, LORA_FEC_PARITY);
+            packet.len = repairedLen >= 0 ? repairedLen : max(packet.len - LORA_FEC_PARITY, 0);
+            packet.frameLen = packet.len;
+        }
+#endif
+        if (packet.len != packet.frameLen)
+        {
+            Serial.print("Error: frame of ");
+            Serial.print(packet.frameLen);
+            Serial.print(" bytes truncated to ");
+            Serial.print(packet.len);
+            Serial.println(" bytes - dropping");
+            addMessageToDisplay("ERROR: Truncated frame", packet.rssi, packet.snr);
+        }
+        else if (!Message::crcMatches(packet.buffer, packet.len))
+        {
+            Serial.println("Error: CRC mismatch - frame corrupted on air");
+            addMessageToDisplay("ERROR: CRC mismatch", packet.rssi, packet.snr);
+        }
+#ifdef LORA_AUTH_KEY
+        else if ((packet.len = Message::stripAuthTag(packet.buffer, packet.len, authKey)) < 0)
+        {
+            Serial.println("Error: auth tag mismatch - forged, or sent under another LORA_AUTH_KEY");
+            addMessageToDisplay("ERROR: Auth failed", packet.rssi, packet.snr);
+        }
+#endif
+#ifdef LORA_PAYLOAD_KEY
+        else if (msg.deserializeEncrypted(packet.buffer, packet.len, payloadKey))
+#else
+        else if (msg.deserialize(packet.buffer, packet.len))
+#endif
         {
             Serial.print("LoRa message deserialized: type=");
             Serial.println((int)msg.type);
@@ -588,6 +668,7 @@ void loop()
             {
             case MessageType::Text:
             {
+                decodeGpsPosition(msg, gpsHeard, msg); // A full position update is the reference of deltas
                 Serial.print("Text message - seq: ");
                 Serial.print(msg.textData.seq);
                 Serial.print(", text: \"");
@@ -607,6 +688,23 @@ void loop()
                 // Display text message on screen
                 String displayText = "TXT #";
                 displayText += String(msg.textData.seq);
+                if (msg.textData.priority == MessagePriority::High)
+                {
+                    displayText += " [HIGH]";
+                }
+                else if (msg.textData.priority == MessagePriority::Critical)
+                {
+                    displayText += " [CRIT]";
+                }
+                if (msg.textData.isFragment)
+                {
+                    // Shown as it arrives; the app reassembles the whole text
+                    displayText += " (";
+                    displayText += String(msg.textData.fragmentIndex + 1);
+                    displayText += "/";
+                    displayText += String(msg.textData.fragmentCount);
+                    displayText += ")";
+                }
                 displayText += ": ";
                 displayText += String(msg.textData.text);
 
@@ -649,6 +747,240 @@ void loop()
                 addMessageToDisplay(ackDisplay, packet.rssi, packet.snr);
                 break;
             }
+
+            case MessageType::Identify:
+            {
+                // The debugger has no node id and never answers Identify
+                String identifyDisplay = "IDENTIFY -> node ";
+                identifyDisplay += String(msg.identifyData.targetNode);
+                addMessageToDisplay(identifyDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::IdentifyResponse:
+            {
+                String nodeDisplay = "NODE ";
+                nodeDisplay += String(msg.nodeInfoData.nodeId);
+                nodeDisplay += ": SF";
+                nodeDisplay += String(msg.nodeInfoData.spreadingFactor);
+                nodeDisplay += " ";
+                nodeDisplay += String(msg.nodeInfoData.txPower);
+                nodeDisplay += "dBm";
+                addMessageToDisplay(nodeDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::Battery:
+            {
+                String batteryDisplay = "BATT #";
+                batteryDisplay += String(msg.batteryData.seq);
+                batteryDisplay += " ";
+                batteryDisplay += String(msg.batteryData.percent);
+                batteryDisplay += "% ";
+                batteryDisplay += String(msg.batteryData.millivolts);
+                batteryDisplay += "mV";
+                addMessageToDisplay(batteryDisplay, packet.rssi, packet.snr);
+
+                // ACKed like a text
+                ackPending = true;
+                pendingAckSeq = msg.batteryData.seq;
+                ackSendTime = millis() + ACK_DELAY_MS;
+                pendingAckMsg = Message::createAck(msg.batteryData.seq);
+                break;
+            }
+
+            case MessageType::Sos:
+            {
+                String sosDisplay = "SOS #";
+                sosDisplay += String(msg.sosData.seq);
+                sosDisplay += " ";
+                sosDisplay += String(msg.sosData.lat / 1000000.0, 5);
+                sosDisplay += ",";
+                sosDisplay += String(msg.sosData.lon / 1000000.0, 5);
+                addMessageToDisplay(sosDisplay, packet.rssi, packet.snr);
+
+                // ACKed like a text
+                ackPending = true;
+                pendingAckSeq = msg.sosData.seq;
+                ackSendTime = millis() + ACK_DELAY_MS;
+                pendingAckMsg = Message::createAck(msg.sosData.seq);
+                break;
+            }
+
+            case MessageType::GpsDelta:
+            {
+                uint8_t deltaSeq = msg.gpsDeltaData.seq;
+                String deltaDisplay = "POS #";
+                deltaDisplay += String(deltaSeq);
+                if (!decodeGpsPosition(msg, gpsHeard, msg))
+                {
+                    // Not ACKed, as by a bridge: the sender retransmits it or resyncs with a full one
+                    deltaDisplay += " delta, no fix #";
+                    deltaDisplay += String(msg.gpsDeltaData.refSeq);
+                    addMessageToDisplay(deltaDisplay, packet.rssi, packet.snr);
+                    break;
+                }
+                deltaDisplay += " [";
+                deltaDisplay += String(msg.textData.lat / 1000000.0, 5);
+                deltaDisplay += "°,";
+                deltaDisplay += String(msg.textData.lon / 1000000.0, 5);
+                deltaDisplay += "°] delta";
+                addMessageToDisplay(deltaDisplay, packet.rssi, packet.snr);
+
+                // ACKed like a text
+                ackPending = true;
+                pendingAckSeq = deltaSeq;
+                ackSendTime = millis() + ACK_DELAY_MS;
+                pendingAckMsg = Message::createAck(deltaSeq);
+                break;
+            }
+
+            case MessageType::Nack:
+            {
+                // A bridge heard a damaged text; the sender retransmits it early
+                String nackDisplay = "NACK #";
+                nackDisplay += String(msg.nackData.seq);
+                nackDisplay += msg.nackData.reason == DecodeFailureReason::CrcMismatch ? " (CRC)" : " (truncated)";
+                addMessageToDisplay(nackDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::HopAck:
+            {
+                // The debugger never relays, so this is a relay answering a bridge
+                String hopDisplay = "HOP ACK #";
+                hopDisplay += String(msg.hopAckData.seq);
+                hopDisplay += " by ";
+                hopDisplay += String(msg.hopAckData.relayNode);
+                addMessageToDisplay(hopDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::ConfigRequest:
+            {
+                // Like Identify, the debugger has no node id and never answers
+                String requestDisplay = "CONFIG? -> node ";
+                requestDisplay += String(msg.configRequestData.targetNode);
+                addMessageToDisplay(requestDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::ConfigResponse:
+            {
+                const NodeInfo &radio = msg.nodeConfigData.radio;
+                String configDisplay = "CONFIG ";
+                configDisplay += String(radio.nodeId);
+                configDisplay += ": SF";
+                configDisplay += String(radio.spreadingFactor);
+                configDisplay += " v";
+                configDisplay += String(msg.nodeConfigData.firmwareMajor);
+                configDisplay += ".";
+                configDisplay += String(msg.nodeConfigData.firmwareMinor);
+                configDisplay += ".";
+                configDisplay += String(msg.nodeConfigData.firmwarePatch);
+                addMessageToDisplay(configDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::ChannelSwitch:
+            {
+                // The debugger stays on its frequency; it never accepts, so bridges won't move away from it
+                String switchDisplay = "CHSW ";
+                switchDisplay += String(msg.channelSwitchData.frequency / 1000000.0, 3);
+                switchDisplay += "MHz #";
+                switchDisplay += String(static_cast<int>(msg.channelSwitchData.stage));
+                addMessageToDisplay(switchDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::RateSwitch:
+            {
+                // Nor does it follow a spreading factor switch: it keeps listening at its own
+                String rateDisplay = "RATE SF";
+                rateDisplay += String(msg.rateSwitchData.spreadingFactor);
+                rateDisplay += " #";
+                rateDisplay += String(static_cast<int>(msg.rateSwitchData.stage));
+                addMessageToDisplay(rateDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::ListenSchedule:
+            {
+                // The debugger always listens, so it hears every window's beacon without following it
+                String scheduleDisplay = "SCHED ";
+                scheduleDisplay += String(msg.listenScheduleData.nodeId);
+                scheduleDisplay += "->";
+                scheduleDisplay += String(msg.listenScheduleData.referenceId);
+                scheduleDisplay += " ";
+                scheduleDisplay += String(msg.listenScheduleData.windowMs);
+                scheduleDisplay += "/";
+                scheduleDisplay += String(msg.listenScheduleData.periodMs);
+                scheduleDisplay += "ms";
+                addMessageToDisplay(scheduleDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::TrackStatus:
+            {
+                // The debugger never sends tracks, so this is a status between two bridges
+                String trackDisplay = "TRACK #";
+                trackDisplay += String(msg.trackStatusData.lastSeq);
+                trackDisplay += " ";
+                trackDisplay += String((unsigned long)msg.trackStatusData.bitmap, HEX);
+                addMessageToDisplay(trackDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::Raw:
+            {
+                // Raw is only ever sent bridge -> app, so this is a foreign frame on air
+                String rawDisplay = "RAW ";
+                rawDisplay += String(msg.rawData.frameLen);
+                rawDisplay += " bytes";
+                addMessageToDisplay(rawDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::Status:
+            {
+                // Status is bridge -> app only as well
+                String statusDisplay = "STATUS ";
+                statusDisplay += String(static_cast<int>(msg.statusData.code));
+                addMessageToDisplay(statusDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::DecodeFailure:
+            {
+                // Decode failure reports are bridge -> app only too
+                String failureDisplay = "DECODE FAIL ";
+                failureDisplay += String(static_cast<int>(msg.decodeFailureData.reason));
+                addMessageToDisplay(failureDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::DeliveryReport:
+            {
+                // Delivery reports are bridge -> app only too
+                String reportDisplay = "DELIVERY ";
+                reportDisplay += String(static_cast<int>(msg.deliveryReportData.outcome));
+                reportDisplay += " #";
+                reportDisplay += String(msg.deliveryReportData.seq);
+                addMessageToDisplay(reportDisplay, packet.rssi, packet.snr);
+                break;
+            }
+
+            case MessageType::LinkQuality:
+            {
+                // Link quality reports are bridge -> app only too
+                String qualityDisplay = "LINK #";
+                qualityDisplay += String(msg.linkQualityData.seq);
+                qualityDisplay += " ";
+                qualityDisplay += String(msg.linkQualityData.rssi);
+                qualityDisplay += " dBm";
+                addMessageToDisplay(qualityDisplay, packet.rssi, packet.snr);
+                break;
+            }
             }
         }
         else
@@ -663,14 +995,21 @@ void loop()
     {
         ackPending = false;
 
-        uint8_t ackBuf[64];
-        int ackLen = pendingAckMsg.serialize(ackBuf, sizeof(ackBuf));
+        uint8_t ackBuf[MAX_FRAME_LEN];
+        int ackLen = encodeFrame(pendingAckMsg, ackBuf, sizeof(ackBuf));
 
         if (ackLen > 0)
         {
             Serial.print("Sending ACK for seq: ");
             Serial.println(pendingAckSeq);
+#if LORA_ACK_SPREADING_FACTOR
+            // Fast ACKs: the bridge listens for the ACK at its own SF
+            loraManager.setSpreadingFactor(LORA_ACK_SPREADING_FACTOR);
+#endif
             bool ackSent = loraManager.sendPacket(ackBuf, ackLen);
+#if LORA_ACK_SPREADING_FACTOR
+            loraManager.setSpreadingFactor(LORA_SPREADING_FACTOR);
+#endif
             if (ackSent)
             {
                 Serial.println("ACK sent successfully");
@@ -1,22 +1,49 @@
 #ifndef LORA_MANAGER_H
 #define LORA_MANAGER_H
 
+#include "lora_config.h"
+
+#if LORA_RADIO == LORA_RADIO_SX1262
+#include "Sx126xManager.h"
+#else
 #include <SPI.h>
 #include <LoRa.h>
-#include "lora_config.h"
+
+// SX127x registers the LoRa library only sets at init
+#define SX127X_REG_LNA 0x0C
+#define SX127X_REG_MODEM_CONFIG_3 0x26
+#define SX127X_LNA_BOOST_HF 0x03     // RegLna bits 1-0: 150% LNA current
+#define SX127X_LNA_GAIN_SHIFT 5      // RegLna bits 7-5: G1 (max) .. G6 (min)
+#define SX127X_AGC_AUTO_ON 0x04      // RegModemConfig3 bit 2
+#define SX127X_REG_MODEM_STAT 0x18
+#define SX127X_REG_IRQ_FLAGS 0x12
+#define SX127X_IRQ_RX_DONE 0x40      // RegIrqFlags bit 6, also what DIO0 signals in RX
+#define SX127X_MODEM_STAT_RX_ACTIVE 0x0A // RegModemStat: signal synchronized (bit 1) or header valid (bit 3)
+#define SX127X_REG_OP_MODE 0x01
+#define SX127X_MODE_LORA_CAD 0x87    // RegOpMode: LoRa mode, channel activity detection
+#define SX127X_IRQ_CAD_DONE 0x04     // RegIrqFlags bit 2
+#define SX127X_IRQ_CAD_DETECTED 0x01 // RegIrqFlags bit 0
 
 class LoRaManager
 {
 public:
     LoRaManager(int sck, int miso, int mosi, int ss, int rst, int dio0, long frequency)
-        : sckPin(sck), misoPin(miso), mosiPin(mosi), ssPin(ss), rstPin(rst), dio0Pin(dio0), frequency(frequency) {}
+        : sckPin(sck), misoPin(miso), mosiPin(mosi), ssPin(ss), rstPin(rst), dio0Pin(dio0), frequency(frequency),
+          ready(false), asleep(false), dataSpreadingFactor(LORA_SPREADING_FACTOR), spreadingFactor(LORA_SPREADING_FACTOR),
+          txPower(LORA_TX_POWER_CONDUCTED_DBM), rxBoost(LORA_RX_BOOST), lnaGain(LORA_LNA_GAIN) {}
 
     /**
      * @brief Initializes the LoRa module.
+     *
+     * Until this has succeeded every radio operation below is a no-op that
+     * reports failure, so a caller inside a setup retry loop (or one that ignores
+     * the result) can never drive a radio in an undefined state.
      * @return True if the LoRa module was initialized successfully, false otherwise.
      */
     bool setup()
     {
+        ready = false;
+        asleep = false;
         SPI.begin(sckPin, misoPin, mosiPin, ssPin);
         LoRa.setPins(ssPin, rstPin, dio0Pin);
 
@@ -26,25 +53,40 @@ public:
             return false;
         }
 
-        // Configure LoRa parameters from lora_config.h
-        LoRa.setSignalBandwidth(LORA_BANDWIDTH);
-        LoRa.setCodingRate4(LORA_CODING_RATE);
-        LoRa.setSpreadingFactor(LORA_SPREADING_FACTOR);
-        LoRa.setTxPower(LORA_TX_POWER);
-        LoRa.disableCrc();
+        writeModulation();
 
         Serial.println("LoRa initialized successfully.");
+        ready = true;
         return true;
     }
 
+    /**
+     * @brief Checks whether setup() has completed successfully.
+     */
+    bool isReady() const
+    {
+        return ready;
+    }
+
     /**
      * @brief Sends a packet with the given byte buffer.
+     *
+     * The radio is left in standby after the transmission, never asleep: call
+     * startReceiveMode() right away to keep listening (the bridge does this in
+     * rearmReceiveAfterTx()). Sleep only happens through an explicit sleep().
      * @param buffer The byte buffer to send.
      * @param length The number of bytes to send from the buffer.
      * @return True if the packet was sent successfully, false otherwise.
      */
     bool sendPacket(const byte *buffer, size_t length)
     {
+        if (!ready)
+        {
+            Serial.println("LoRa not initialized - packet not sent.");
+            return false;
+        }
+
+        asleep = false;
         LoRa.beginPacket();
         LoRa.write(buffer, length);         // Use LoRa.write for byte arrays
         int success = LoRa.endPacket(true); // true for synchronous mode
@@ -59,6 +101,28 @@ public:
         return success > 0;
     }
 
+    /**
+     * @brief Writes the modulation and packet parameters again and restarts receive mode.
+     *
+     * Recovers a radio whose registers no longer match lora_config.h (e.g. after
+     * a failed mode transition) without resetting it: the frequency, the data
+     * spreading factor, the RX front end settings and the receive callback are kept.
+     * @return True if applied, false if the radio is not initialized.
+     */
+    bool reconfigure()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+
+        LoRa.idle();
+        LoRa.setFrequency(frequency);
+        writeModulation();
+        startReceiveMode();
+        return true;
+    }
+
     /**
      * @brief Starts continuous receive mode.
      *
@@ -66,6 +130,11 @@ public:
      */
     void startReceiveMode()
     {
+        if (!ready)
+        {
+            return;
+        }
+        asleep = false;
         LoRa.receive();
     }
 
@@ -73,10 +142,15 @@ public:
      * @brief Checks for and reads a packet into a byte buffer.
      * @param buffer The buffer to store the received packet data.
      * @param maxLen The maximum number of bytes to read into the buffer.
-     * @return The number of bytes received (packet size), or 0 if no packet was received or buffer is too small.
+     * @return The number of bytes received (packet size), or 0 if no packet was received, the buffer is too small, or the radio is not initialized.
      */
     int receivePacket(byte *buffer, size_t maxLen)
     {
+        if (!ready)
+        {
+            return 0;
+        }
+
         int packetSize = LoRa.parsePacket();
         if (packetSize == 0 || packetSize > maxLen)
         {
@@ -112,6 +186,34 @@ public:
         return bytesRead; // Return the actual number of bytes read
     }
 
+    /**
+     * @brief Reads the frame isRxDone() reported, truncating it to the buffer (LORA_RX_POLLING).
+     *
+     * Unlike receivePacket() an oversized frame is not discarded, so the caller
+     * can tell a truncated frame from a short one.
+     * @param frameLen Receives the frame length on air, 0 if there was no frame.
+     * @return The number of bytes read into buffer (at most maxLen).
+     */
+    int readPacket(byte *buffer, size_t maxLen, int &frameLen)
+    {
+        frameLen = ready ? LoRa.parsePacket() : 0;
+        int bytesRead = 0;
+        while (bytesRead < frameLen && bytesRead < (int)maxLen && LoRa.available())
+        {
+            buffer[bytesRead++] = (byte)LoRa.read();
+        }
+        return bytesRead;
+    }
+
+    /**
+     * @brief Gets the frequency error of the last packet (sender's carrier relative to ours).
+     * @return Frequency error in Hz.
+     */
+    long getPacketFrequencyError()
+    {
+        return LoRa.packetFrequencyError();
+    }
+
     /**
      * @brief Gets the received signal strength indicator (RSSI) of the last packet.
      * @return The RSSI value.
@@ -148,6 +250,360 @@ public:
         return LoRa.packetSnr();
     }
 
+    /**
+     * @brief Retunes the operating frequency and returns to continuous receive mode.
+     * @param hz New operating frequency, must be valid for LORA_BANDWIDTH (see loraBandwidthValidFor()).
+     * @return True if the radio was retuned, false if the frequency is not allowed or the radio is not initialized.
+     */
+    bool setFrequency(uint32_t hz)
+    {
+        if (!ready || !loraBandwidthValidFor(LORA_BANDWIDTH, hz))
+        {
+            return false;
+        }
+
+        LoRa.idle();
+        LoRa.setFrequency(hz);
+        frequency = hz;
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Switches the spreading factor, e.g. to LORA_ACK_SPREADING_FACTOR around an ACK.
+     *
+     * Written in standby and the radio is left there: follow with sendPacket() or
+     * startReceiveMode(). Nothing is written if the factor is already in effect.
+     * @return True if in effect, false if the radio is not initialized.
+     */
+    bool setSpreadingFactor(int sf)
+    {
+        if (!ready)
+        {
+            return false;
+        }
+        if (sf == spreadingFactor)
+        {
+            return true;
+        }
+
+        LoRa.idle();
+        asleep = false;
+        LoRa.setSpreadingFactor(sf);
+        spreadingFactor = sf;
+        return true;
+    }
+
+    /**
+     * @brief Gets the spreading factor in effect.
+     */
+    int getSpreadingFactor() const
+    {
+        return spreadingFactor;
+    }
+
+    /**
+     * @brief Changes the spreading factor of data frames and returns to continuous receive mode.
+     *
+     * Replaces LORA_SPREADING_FACTOR until the next reboot; reconfigure() and
+     * setup() keep it. Frames from a peer still at the old factor are no longer
+     * heard, and a frame arriving during the register write is lost.
+     * @return True if applied, false if the factor is not selectable (see
+     *         loraSpreadingFactorSelectable()) or the radio is not initialized.
+     */
+    bool setDataSpreadingFactor(int sf)
+    {
+        if (!ready || !loraSpreadingFactorSelectable(sf))
+        {
+            return false;
+        }
+
+        dataSpreadingFactor = sf;
+        setSpreadingFactor(sf);
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Gets the spreading factor of data frames (LORA_SPREADING_FACTOR unless changed).
+     */
+    int getDataSpreadingFactor() const
+    {
+        return dataSpreadingFactor;
+    }
+
+    /**
+     * @brief Changes the TX power and returns to continuous receive mode.
+     *
+     * Replaces LORA_TX_POWER until the next reboot; reconfigure() and setup() keep it.
+     * @return True if applied, false if the power is not selectable (see
+     *         loraTxPowerSelectable()) or the radio is not initialized.
+     */
+    bool setTxPower(int dbm)
+    {
+        if (!ready || !loraTxPowerSelectable(dbm))
+        {
+            return false;
+        }
+
+        LoRa.idle();
+        LoRa.setTxPower(dbm);
+        txPower = dbm;
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Gets the conducted TX power in dBm (LORA_TX_POWER_CONDUCTED_DBM unless changed).
+     */
+    int getTxPower() const
+    {
+        return txPower;
+    }
+
+    /**
+     * @brief Gets the current operating frequency in Hz.
+     */
+    uint32_t getFrequency() const
+    {
+        return frequency;
+    }
+
+    /**
+     * @brief Switches the LNA boost (150% LNA current) on or off.
+     *
+     * Written in standby, then continuous receive mode is restored; a frame
+     * arriving during the register write is lost.
+     * @return True if applied, false if the radio is not initialized.
+     */
+    bool setRxBoost(bool enabled)
+    {
+        if (!ready)
+        {
+            return false;
+        }
+
+        rxBoost = enabled;
+        LoRa.idle();
+        writeFrontEnd();
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Sets the LNA gain: 0 for AGC, 1 (G1, maximum) to 6 (G6, minimum) for a fixed gain.
+     *
+     * Written in standby like setRxBoost().
+     * @return True if applied, false if the setting is out of range or the radio is not initialized.
+     */
+    bool setLnaGain(uint8_t gain)
+    {
+        if (!ready || gain > LORA_LNA_GAIN_MIN_SETTING)
+        {
+            return false;
+        }
+
+        lnaGain = gain;
+        LoRa.idle();
+        writeFrontEnd();
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Checks whether the LNA boost is on.
+     */
+    bool getRxBoost() const
+    {
+        return rxBoost;
+    }
+
+    /**
+     * @brief Gets the LNA gain setting (0 = AGC, 1-6 = fixed G1-G6).
+     */
+    uint8_t getLnaGain() const
+    {
+        return lnaGain;
+    }
+
+    /**
+     * @brief Puts the radio into sleep mode (lowest power, no reception).
+     *
+     * Used between operations by the radio power modes (RADIO_POWER_MODE) and
+     * before a software restart. Anything that starts receive mode or sends a
+     * packet wakes it again; setup() resets it.
+     */
+    void sleep()
+    {
+        if (!ready)
+        {
+            return;
+        }
+        LoRa.sleep();
+        asleep = true;
+    }
+
+    /**
+     * @brief Checks whether the radio was put to sleep and not woken since.
+     */
+    bool isAsleep() const
+    {
+        return asleep;
+    }
+
+    /**
+     * @brief Checks the RxDone flag without touching the receive mode.
+     *
+     * For polling when DIO0 isn't wired (LORA_RX_POLLING): only call receivePacket()
+     * or LoRa.parsePacket() once this is true, as those switch a radio without a
+     * received frame from continuous to single receive mode.
+     * @return True if a complete frame waits in the FIFO, false otherwise or if the radio is not initialized.
+     */
+    bool isRxDone()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+        return (readRegister(SX127X_REG_IRQ_FLAGS) & SX127X_IRQ_RX_DONE) != 0;
+    }
+
+    /**
+     * @brief Checks whether a frame is being received right now.
+     *
+     * True from the preamble lock until the frame is complete. "Signal detected"
+     * alone is ignored, it flickers on noise. Transmitting now would abort the frame.
+     * @return True if a reception is in progress, false otherwise or if the radio is not initialized.
+     */
+    bool isReceiving()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+        return (readRegister(SX127X_REG_MODEM_STAT) & SX127X_MODEM_STAT_RX_ACTIVE) != 0;
+    }
+
+    /**
+     * @brief Listens for a LoRa preamble at the spreading factor in effect (Channel Activity Detection).
+     *
+     * Blocks for the CAD, about two symbols (66 ms at SF10/BW31.25), then returns
+     * to continuous receive mode; a frame whose preamble started during the CAD is
+     * lost. Unlike isReceiving() this also hears a frame the modem hasn't locked
+     * onto yet, so it is the listen-before-talk check ahead of a transmission.
+     * @return True if a preamble was heard, false if the channel is quiet or the radio is not initialized.
+     */
+    bool detectChannelActivity()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+
+        LoRa.idle();
+        asleep = false;
+        writeRegister(SX127X_REG_IRQ_FLAGS, SX127X_IRQ_CAD_DONE | SX127X_IRQ_CAD_DETECTED);
+        writeRegister(SX127X_REG_OP_MODE, SX127X_MODE_LORA_CAD);
+
+        // A CAD takes about two symbols; give up after eight
+        unsigned long timeoutMs = 8UL * (1UL << spreadingFactor) * 1000 / LORA_BANDWIDTH + 1;
+        unsigned long start = millis();
+        uint8_t flags = 0;
+        while (!(flags & SX127X_IRQ_CAD_DONE) && millis() - start <= timeoutMs)
+        {
+            flags = readRegister(SX127X_REG_IRQ_FLAGS);
+        }
+
+        writeRegister(SX127X_REG_IRQ_FLAGS, SX127X_IRQ_CAD_DONE | SX127X_IRQ_CAD_DETECTED);
+        startReceiveMode();
+        return (flags & SX127X_IRQ_CAD_DETECTED) != 0;
+    }
+
+    /**
+     * @brief Samples the RSSI floor of the operating channel while in receive mode.
+     *
+     * Takes the lowest of LORA_SCAN_SAMPLES readings 1 ms apart so a frame on air
+     * during the sample doesn't read as interference.
+     * @return RSSI floor in dBm, or 0 if the radio is not initialized.
+     */
+    int16_t sampleRssiFloor()
+    {
+        if (!ready)
+        {
+            return 0;
+        }
+
+        int16_t floor = 0;
+        for (int i = 0; i < LORA_SCAN_SAMPLES; i++)
+        {
+            int16_t rssi = LoRa.rssi();
+            if (i == 0 || rssi < floor)
+            {
+                floor = rssi;
+            }
+            delay(1);
+        }
+        return floor;
+    }
+
+    /**
+     * @brief Sweeps a frequency range and measures the RSSI floor at each step.
+     *
+     * The range is clamped to LORA_BAND_MIN_HZ/LORA_BAND_MAX_HZ so the scan never
+     * leaves the legal band. The radio only listens, nothing is transmitted. The
+     * operating frequency and continuous receive mode are restored afterwards;
+     * frames arriving during the scan are lost.
+     * @param startHz First frequency to measure.
+     * @param stopHz Last frequency to measure (inclusive).
+     * @param stepHz Distance between measurements (must be > 0).
+     * @param rssiOut Receives the averaged RSSI in dBm per frequency.
+     * @param maxPoints Capacity of rssiOut.
+     * @return Number of frequencies measured, 0 if the range is empty or invalid or the radio is not initialized.
+     */
+    size_t scanSpectrum(uint32_t startHz, uint32_t stopHz, uint32_t stepHz, int16_t *rssiOut, size_t maxPoints)
+    {
+        if (!ready)
+        {
+            return 0;
+        }
+
+        if (startHz < LORA_BAND_MIN_HZ)
+        {
+            startHz = LORA_BAND_MIN_HZ;
+        }
+        if (stopHz > LORA_BAND_MAX_HZ)
+        {
+            stopHz = LORA_BAND_MAX_HZ;
+        }
+        if (stepHz == 0 || startHz > stopHz)
+        {
+            return 0;
+        }
+
+        size_t points = 0;
+        for (uint32_t freq = startHz; freq <= stopHz && points < maxPoints; freq += stepHz)
+        {
+            // Retune through standby, the SX127x only applies a new frequency on a mode change
+            LoRa.idle();
+            LoRa.setFrequency(freq);
+            LoRa.receive();
+            delay(LORA_SCAN_SETTLE_MS);
+
+            long sum = 0;
+            for (int i = 0; i < LORA_SCAN_SAMPLES; i++)
+            {
+                sum += LoRa.rssi();
+                delay(1);
+            }
+            rssiOut[points++] = sum / LORA_SCAN_SAMPLES;
+        }
+
+        LoRa.idle();
+        LoRa.setFrequency(frequency);
+        startReceiveMode();
+        return points;
+    }
+
     /**
      * @brief Returns a string with the current LoRa configuration.
      * @return Configuration string.
@@ -157,9 +613,30 @@ public:
         String config = "LoRa Configuration:\n";
         config += "  Frequency: " + String(frequency / 1000000.0, 2) + " MHz\n";
         config += "  Bandwidth: " + String(LORA_BANDWIDTH / 1000.0, 1) + " kHz\n";
-        config += "  Spreading Factor: " + String(LORA_SPREADING_FACTOR) + "\n";
+        config += "  Spreading Factor: " + String(dataSpreadingFactor) + "\n";
         config += "  Coding Rate: 4/" + String(LORA_CODING_RATE) + "\n";
+#ifdef LORA_TX_POWER_MW
+        config += "  TX Power: " + String(LORA_TX_POWER) + " dBm (" + String(LORA_TX_POWER_MW) + " mW requested";
+        config += loraTxPowerMwAchievable(LORA_TX_POWER_MW) ? ")\n" : " - WARNING: not achievable, clamped to 2-20 dBm)\n";
+#else
         config += "  TX Power: " + String(LORA_TX_POWER) + " dBm\n";
+#endif
+#ifdef ANTENNA_GAIN_DBI
+        if (LORA_TX_POWER_CONDUCTED_DBM < LORA_TX_POWER)
+        {
+            config += "  TX Power reduced to " + String(LORA_TX_POWER_CONDUCTED_DBM) + " dBm for the EIRP limit\n";
+        }
+        config += "  Antenna Gain: " + String(static_cast<double>(ANTENNA_GAIN_DBI), 1) + " dBi\n";
+        config += "  EIRP: " + String(LORA_TX_POWER_CONDUCTED_DBM + static_cast<double>(ANTENNA_GAIN_DBI), 1) + " dBm (limit " + String(LORA_EIRP_MAX_DBM) + " dBm)\n";
+#else
+        config += "  EIRP: not checked (set ANTENNA_GAIN_DBI)\n";
+#endif
+        if (txPower != LORA_TX_POWER_CONDUCTED_DBM)
+        {
+            config += "  TX Power changed at runtime to " + String(txPower) + " dBm\n";
+        }
+        config += "  RX Boost: " + String(rxBoost ? "on" : "off") + "\n";
+        config += "  LNA Gain: " + (lnaGain == 0 ? String("AGC") : "G" + String(lnaGain)) + "\n";
         return config;
     }
 
@@ -171,6 +648,88 @@ private:
     int rstPin;
     int dio0Pin;
     long frequency;
+    bool ready; // Set once setup() succeeded, guards every radio operation
+    bool asleep; // Put to sleep by sleep(), cleared by anything that wakes the radio
+    int dataSpreadingFactor; // LORA_SPREADING_FACTOR unless changed at runtime
+    int spreadingFactor; // dataSpreadingFactor unless switched for an ACK
+    int txPower; // LORA_TX_POWER_CONDUCTED_DBM unless changed at runtime
+    bool rxBoost;
+    uint8_t lnaGain;
+
+    /**
+     * @brief Writes the modulation and packet parameters from lora_config.h (radio must be in standby).
+     *
+     * The spreading factor and TX power are dataSpreadingFactor and txPower, which
+     * may have been changed at runtime.
+     */
+    void writeModulation()
+    {
+        LoRa.setSignalBandwidth(LORA_BANDWIDTH);
+        LoRa.setCodingRate4(LORA_CODING_RATE);
+        LoRa.setSpreadingFactor(dataSpreadingFactor);
+        spreadingFactor = dataSpreadingFactor;
+        LoRa.setTxPower(txPower);
+        LoRa.setPreambleLength(LORA_PREAMBLE_LENGTH);
+        LoRa.setSyncWord(LORA_SYNC_WORD);
+        LoRa.disableCrc();
+        writeFrontEnd();
+    }
+
+    /**
+     * @brief Writes rxBoost and lnaGain to RegLna / RegModemConfig3 (radio must be in standby).
+     *
+     * The LoRa library keeps these registers private and only sets them in begin(),
+     * so they are written over SPI directly. RegModemConfig3 is read-modify-written
+     * to keep LowDataRateOptimize, which setSpreadingFactor() manages.
+     */
+    void writeFrontEnd()
+    {
+        // Keep LnaBoostLf and the reserved bit, rebuild gain and LnaBoostHf
+        uint8_t lna = readRegister(SX127X_REG_LNA) & 0x1C;
+        if (rxBoost)
+        {
+            lna |= SX127X_LNA_BOOST_HF;
+        }
+
+        uint8_t config3 = readRegister(SX127X_REG_MODEM_CONFIG_3);
+        if (lnaGain == 0)
+        {
+            config3 |= SX127X_AGC_AUTO_ON;
+            lna |= 1 << SX127X_LNA_GAIN_SHIFT; // Ignored while the AGC is on
+        }
+        else
+        {
+            config3 &= ~SX127X_AGC_AUTO_ON;
+            lna |= lnaGain << SX127X_LNA_GAIN_SHIFT;
+        }
+
+        writeRegister(SX127X_REG_MODEM_CONFIG_3, config3);
+        writeRegister(SX127X_REG_LNA, lna);
+    }
+
+    uint8_t readRegister(uint8_t address)
+    {
+        return transferRegister(address & 0x7F, 0x00);
+    }
+
+    void writeRegister(uint8_t address, uint8_t value)
+    {
+        transferRegister(address | 0x80, value);
+    }
+
+    /// Same SPI framing as the LoRa library: address byte (MSB = write), then data
+    uint8_t transferRegister(uint8_t address, uint8_t value)
+    {
+        digitalWrite(ssPin, LOW);
+        SPI.beginTransaction(SPISettings(LORA_DEFAULT_SPI_FREQUENCY, MSBFIRST, SPI_MODE0));
+        SPI.transfer(address);
+        uint8_t response = SPI.transfer(value);
+        SPI.endTransaction();
+        digitalWrite(ssPin, HIGH);
+        return response;
+    }
 };
 
+#endif // LORA_RADIO
+
 #endif // LORA_MANAGER_H
\ No newline at end of file
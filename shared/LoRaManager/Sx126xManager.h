@@ -0,0 +1,535 @@
+#ifndef SX126X_MANAGER_H
+#define SX126X_MANAGER_H
+
+#include <SPI.h>
+#include <RadioLib.h>
+#include "lora_config.h"
+
+#ifndef LORA_BUSY
+#error "LORA_RADIO_SX1262 needs the BUSY pin: build with -DLORA_BUSY=<gpio>"
+#endif
+
+/**
+ * LoRaManager for an SX1262 (LORA_RADIO_SX1262), driven through RadioLib.
+ *
+ * Same interface and semantics as the SX127x LoRaManager in LoRaManager.h, so
+ * the bridge logic doesn't know which chip it talks to. Differences:
+ * - Frames are read in the loop (readPacket() after isRxDone()); DIO1 is only
+ *   used by RadioLib to wait for TxDone and CadDone.
+ * - isReceiving() is true from the valid header on, not from the preamble lock.
+ * - The RX boost is the SX1262 boosted gain mode; a fixed LNA gain is not
+ *   available, setLnaGain() only accepts 0 (AGC).
+ */
+class LoRaManager
+{
+public:
+    LoRaManager(int sck, int miso, int mosi, int ss, int rst, int dio1, int busy, long frequency)
+        : sckPin(sck), misoPin(miso), mosiPin(mosi), ssPin(ss), frequency(frequency),
+          module(ss, dio1, rst, busy, SPI), radio(&module),
+          ready(false), asleep(false), dataSpreadingFactor(LORA_SPREADING_FACTOR), spreadingFactor(LORA_SPREADING_FACTOR),
+          txPower(LORA_TX_POWER_CONDUCTED_DBM), rxBoost(LORA_RX_BOOST), lnaGain(0) {}
+
+    /**
+     * @brief Initializes the LoRa module.
+     *
+     * Until this has succeeded every radio operation below is a no-op that
+     * reports failure, as on the SX127x.
+     * @return True if the LoRa module was initialized successfully, false otherwise.
+     */
+    bool setup()
+    {
+        ready = false;
+        asleep = false;
+        SPI.begin(sckPin, misoPin, mosiPin, ssPin);
+
+        int16_t state = radio.begin(frequency / 1E6, bandwidthKhz(), dataSpreadingFactor, LORA_CODING_RATE,
+                                    LORA_SYNC_WORD, txPower, LORA_PREAMBLE_LENGTH,
+                                    LORA_TCXO_VOLTAGE);
+        if (state != RADIOLIB_ERR_NONE)
+        {
+            Serial.printf("LoRa initialization failed! (RadioLib error %d)\n", state);
+            return false;
+        }
+
+        spreadingFactor = dataSpreadingFactor;
+        radio.setCRC(0);
+#if LORA_DIO2_AS_RF_SWITCH
+        radio.setDio2AsRfSwitch(true);
+#endif
+        radio.setRxBoostedGainMode(rxBoost);
+
+        Serial.println("LoRa initialized successfully (SX1262).");
+        ready = true;
+        return true;
+    }
+
+    /**
+     * @brief Checks whether setup() has completed successfully.
+     */
+    bool isReady() const
+    {
+        return ready;
+    }
+
+    /**
+     * @brief Sends a packet with the given byte buffer.
+     *
+     * Blocks until TxDone; the radio is left in standby, call startReceiveMode()
+     * right away to keep listening.
+     * @return True if the packet was sent successfully, false otherwise.
+     */
+    bool sendPacket(const byte *buffer, size_t length)
+    {
+        if (!ready)
+        {
+            Serial.println("LoRa not initialized - packet not sent.");
+            return false;
+        }
+
+        asleep = false;
+        int16_t state = radio.transmit(buffer, length);
+        if (state == RADIOLIB_ERR_NONE)
+        {
+            Serial.println("Packet sent successfully!");
+        }
+        else
+        {
+            Serial.printf("Failed to send packet (RadioLib error %d).\n", state);
+        }
+        return state == RADIOLIB_ERR_NONE;
+    }
+
+    /**
+     * @brief Writes the modulation and packet parameters again and restarts receive mode.
+     *
+     * The frequency, the data spreading factor, the TX power and the RX boost are kept.
+     * @return True if applied, false if the radio is not initialized.
+     */
+    bool reconfigure()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+
+        radio.standby();
+        radio.setFrequency(frequency / 1E6);
+        radio.setBandwidth(bandwidthKhz());
+        radio.setCodingRate(LORA_CODING_RATE);
+        radio.setSpreadingFactor(dataSpreadingFactor);
+        spreadingFactor = dataSpreadingFactor;
+        radio.setOutputPower(txPower);
+        radio.setPreambleLength(LORA_PREAMBLE_LENGTH);
+        radio.setSyncWord(LORA_SYNC_WORD);
+        radio.setCRC(0);
+        radio.setRxBoostedGainMode(rxBoost);
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Starts continuous receive mode.
+     *
+     * Besides RxDone the header-valid IRQ is latched, for isReceiving().
+     */
+    void startReceiveMode()
+    {
+        if (!ready)
+        {
+            return;
+        }
+        asleep = false;
+        radio.startReceive(RADIOLIB_SX126X_RX_TIMEOUT_INF, RADIOLIB_SX126X_IRQ_RX_DEFAULT | RADIOLIB_SX126X_IRQ_HEADER_VALID,
+                           RADIOLIB_SX126X_IRQ_RX_DONE, 0);
+    }
+
+    /**
+     * @brief Reads a received packet into a byte buffer.
+     * @return The number of bytes received, or 0 if no packet was received, the
+     *         buffer is too small or the radio is not initialized.
+     */
+    int receivePacket(byte *buffer, size_t maxLen)
+    {
+        if (!isRxDone())
+        {
+            return 0;
+        }
+
+        size_t packetSize = radio.getPacketLength();
+        if (packetSize > maxLen)
+        {
+            Serial.printf("Received packet size (%d) exceeds buffer size (%d). Packet discarded.\n", packetSize, maxLen);
+            radio.startReceive();
+            return 0;
+        }
+        return radio.readData(buffer, packetSize) == RADIOLIB_ERR_NONE ? packetSize : 0;
+    }
+
+    /**
+     * @brief Reads the frame isRxDone() reported, truncating it to the buffer.
+     * @param frameLen Receives the frame length on air, 0 if there was no frame.
+     * @return The number of bytes read into buffer (at most maxLen).
+     */
+    int readPacket(byte *buffer, size_t maxLen, int &frameLen)
+    {
+        frameLen = 0;
+        if (!isRxDone())
+        {
+            return 0;
+        }
+
+        frameLen = radio.getPacketLength();
+        size_t len = (size_t)frameLen < maxLen ? frameLen : maxLen;
+        return radio.readData(buffer, len) == RADIOLIB_ERR_NONE ? len : 0;
+    }
+
+    /**
+     * @brief Gets the received signal strength indicator (RSSI) of the last packet.
+     */
+    int getPacketRssi()
+    {
+        return (int)radio.getRSSI();
+    }
+
+    /**
+     * @brief Gets the signal-to-noise ratio (SNR) of the last packet.
+     */
+    float getPacketSnr()
+    {
+        return radio.getSNR();
+    }
+
+    /**
+     * @brief Gets the frequency error of the last packet in Hz.
+     *
+     * RadioLib derives it from undocumented registers, so it is a rough estimate.
+     */
+    long getPacketFrequencyError()
+    {
+        return (long)radio.getFrequencyError();
+    }
+
+    int getRssi()
+    {
+        return getPacketRssi();
+    }
+
+    float getSnr()
+    {
+        return getPacketSnr();
+    }
+
+    /**
+     * @brief Retunes the operating frequency and returns to continuous receive mode.
+     * @return True if the radio was retuned, false if the frequency is not allowed or the radio is not initialized.
+     */
+    bool setFrequency(uint32_t hz)
+    {
+        if (!ready || !loraBandwidthValidFor(LORA_BANDWIDTH, hz))
+        {
+            return false;
+        }
+
+        radio.standby();
+        radio.setFrequency(hz / 1E6);
+        frequency = hz;
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Switches the spreading factor, e.g. to LORA_ACK_SPREADING_FACTOR around an ACK.
+     *
+     * Written in standby and the radio is left there: follow with sendPacket() or
+     * startReceiveMode(). RadioLib sets LowDataRateOptimize to match.
+     * @return True if in effect, false if the radio is not initialized.
+     */
+    bool setSpreadingFactor(int sf)
+    {
+        if (!ready)
+        {
+            return false;
+        }
+        if (sf == spreadingFactor)
+        {
+            return true;
+        }
+
+        radio.standby();
+        asleep = false;
+        radio.setSpreadingFactor(sf);
+        spreadingFactor = sf;
+        return true;
+    }
+
+    int getSpreadingFactor() const
+    {
+        return spreadingFactor;
+    }
+
+    /**
+     * @brief Changes the spreading factor of data frames and returns to continuous receive mode.
+     * @return True if applied, false if the factor is not selectable or the radio is not initialized.
+     */
+    bool setDataSpreadingFactor(int sf)
+    {
+        if (!ready || !loraSpreadingFactorSelectable(sf))
+        {
+            return false;
+        }
+
+        dataSpreadingFactor = sf;
+        setSpreadingFactor(sf);
+        startReceiveMode();
+        return true;
+    }
+
+    int getDataSpreadingFactor() const
+    {
+        return dataSpreadingFactor;
+    }
+
+    /**
+     * @brief Changes the TX power and returns to continuous receive mode.
+     * @return True if applied, false if the power is not selectable or the radio is not initialized.
+     */
+    bool setTxPower(int dbm)
+    {
+        if (!ready || !loraTxPowerSelectable(dbm))
+        {
+            return false;
+        }
+
+        radio.standby();
+        radio.setOutputPower(dbm);
+        txPower = dbm;
+        startReceiveMode();
+        return true;
+    }
+
+    int getTxPower() const
+    {
+        return txPower;
+    }
+
+    uint32_t getFrequency() const
+    {
+        return frequency;
+    }
+
+    /**
+     * @brief Switches the boosted gain mode (about 2 mA more for ~2 dB sensitivity) on or off.
+     * @return True if applied, false if the radio is not initialized.
+     */
+    bool setRxBoost(bool enabled)
+    {
+        if (!ready)
+        {
+            return false;
+        }
+
+        rxBoost = enabled;
+        radio.standby();
+        radio.setRxBoostedGainMode(rxBoost);
+        startReceiveMode();
+        return true;
+    }
+
+    /**
+     * @brief Sets the LNA gain. The SX1262 only has AGC, so only 0 is accepted.
+     * @return True for 0, false for a fixed gain or if the radio is not initialized.
+     */
+    bool setLnaGain(uint8_t gain)
+    {
+        return ready && gain == 0;
+    }
+
+    bool getRxBoost() const
+    {
+        return rxBoost;
+    }
+
+    uint8_t getLnaGain() const
+    {
+        return lnaGain;
+    }
+
+    /**
+     * @brief Puts the radio into sleep mode, keeping its configuration (warm start).
+     */
+    void sleep()
+    {
+        if (!ready)
+        {
+            return;
+        }
+        radio.sleep(true);
+        asleep = true;
+    }
+
+    bool isAsleep() const
+    {
+        return asleep;
+    }
+
+    /**
+     * @brief Checks the RxDone IRQ flag without touching the receive mode.
+     */
+    bool isRxDone()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+        return (radio.getIrqStatus() & RADIOLIB_SX126X_IRQ_RX_DONE) != 0;
+    }
+
+    /**
+     * @brief Checks whether a frame is being received right now.
+     *
+     * True from a valid header until the frame is complete; the SX1262 has no
+     * modem status to tell a preamble lock earlier.
+     */
+    bool isReceiving()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+        uint16_t flags = radio.getIrqStatus();
+        return (flags & RADIOLIB_SX126X_IRQ_HEADER_VALID) && !(flags & RADIOLIB_SX126X_IRQ_RX_DONE);
+    }
+
+    /**
+     * @brief Listens for a LoRa preamble at the spreading factor in effect (Channel Activity Detection).
+     *
+     * Blocks for the CAD, then returns to continuous receive mode.
+     * @return True if a preamble was heard, false if the channel is quiet or the radio is not initialized.
+     */
+    bool detectChannelActivity()
+    {
+        if (!ready)
+        {
+            return false;
+        }
+
+        asleep = false;
+        bool detected = radio.scanChannel() == RADIOLIB_LORA_DETECTED;
+        startReceiveMode();
+        return detected;
+    }
+
+    /**
+     * @brief Samples the RSSI floor of the operating channel while in receive mode.
+     * @return Lowest of LORA_SCAN_SAMPLES readings in dBm, or 0 if the radio is not initialized.
+     */
+    int16_t sampleRssiFloor()
+    {
+        if (!ready)
+        {
+            return 0;
+        }
+
+        int16_t floor = 0;
+        for (int i = 0; i < LORA_SCAN_SAMPLES; i++)
+        {
+            int16_t rssi = (int16_t)radio.getRSSI(false);
+            if (i == 0 || rssi < floor)
+            {
+                floor = rssi;
+            }
+            delay(1);
+        }
+        return floor;
+    }
+
+    /**
+     * @brief Sweeps a frequency range and measures the RSSI floor at each step.
+     *
+     * Same clamping and restore as the SX127x version.
+     * @return Number of frequencies measured, 0 if the range is empty or invalid or the radio is not initialized.
+     */
+    size_t scanSpectrum(uint32_t startHz, uint32_t stopHz, uint32_t stepHz, int16_t *rssiOut, size_t maxPoints)
+    {
+        if (!ready)
+        {
+            return 0;
+        }
+
+        if (startHz < LORA_BAND_MIN_HZ)
+        {
+            startHz = LORA_BAND_MIN_HZ;
+        }
+        if (stopHz > LORA_BAND_MAX_HZ)
+        {
+            stopHz = LORA_BAND_MAX_HZ;
+        }
+        if (stepHz == 0 || startHz > stopHz)
+        {
+            return 0;
+        }
+
+        size_t points = 0;
+        for (uint32_t freq = startHz; freq <= stopHz && points < maxPoints; freq += stepHz)
+        {
+            radio.standby();
+            radio.setFrequency(freq / 1E6);
+            radio.startReceive();
+            delay(LORA_SCAN_SETTLE_MS);
+
+            long sum = 0;
+            for (int i = 0; i < LORA_SCAN_SAMPLES; i++)
+            {
+                sum += (long)radio.getRSSI(false);
+                delay(1);
+            }
+            rssiOut[points++] = sum / LORA_SCAN_SAMPLES;
+        }
+
+        radio.standby();
+        radio.setFrequency(frequency / 1E6);
+        startReceiveMode();
+        return points;
+    }
+
+    /**
+     * @brief Returns a string with the current LoRa configuration.
+     */
+    String getConfigurationString() const
+    {
+        String config = "LoRa Configuration (SX1262):\n";
+        config += "  Frequency: " + String(frequency / 1000000.0, 2) + " MHz\n";
+        config += "  Bandwidth: " + String(LORA_BANDWIDTH / 1000.0, 1) + " kHz\n";
+        config += "  Spreading Factor: " + String(dataSpreadingFactor) + "\n";
+        config += "  Coding Rate: 4/" + String(LORA_CODING_RATE) + "\n";
+        config += "  TX Power: " + String(txPower) + " dBm\n";
+        config += "  RX Boost: " + String(rxBoost ? "on" : "off") + "\n";
+        config += "  LNA Gain: AGC\n";
+        return config;
+    }
+
+private:
+    int sckPin;
+    int misoPin;
+    int mosiPin;
+    int ssPin;
+    long frequency;
+    Module module;
+    SX1262 radio;
+    bool ready; // Set once setup() succeeded, guards every radio operation
+    bool asleep; // Put to sleep by sleep(), cleared by anything that wakes the radio
+    int dataSpreadingFactor; // LORA_SPREADING_FACTOR unless changed at runtime
+    int spreadingFactor; // dataSpreadingFactor unless switched for an ACK
+    int txPower; // LORA_TX_POWER_CONDUCTED_DBM unless changed at runtime
+    bool rxBoost;
+    uint8_t lnaGain; // Always 0 (AGC)
+
+    /// LORA_BANDWIDTH as the exact step RadioLib expects (31E3 -> 31.25 kHz)
+    static float bandwidthKhz()
+    {
+        static const float steps[] = {7.8, 10.4, 15.6, 20.8, 31.25, 41.7, 62.5, 125, 250, 500};
+        return steps[loraBandwidthIndex(LORA_BANDWIDTH)];
+    }
+};
+
+#endif // SX126X_MANAGER_H
@@ -0,0 +1,219 @@
+#ifndef LORA_AIRTIME_H
+#define LORA_AIRTIME_H
+
+#include <math.h>
+#include <stddef.h>
+#include "lora_config.h"
+
+// Time-on-air math from Semtech AN1200.13 (SX1272/3/6/7/8 LoRa Modem Designer's Guide)
+// see https://www.semtech.com/design-support/lora-calculator
+// Shared by every airtime-aware feature (ACK timeout, duty cycle, airtime
+// accounting) and checked against reference values in esp32/test/test_airtime.
+
+/**
+ * @brief Modulation parameters that determine time on air.
+ */
+struct LoRaModulation
+{
+    int spreadingFactor; // 6-12
+    double bandwidthHz;  // e.g. 31.25E3, 125E3
+    int codingRate;      // Denominator of 4/x (5-8)
+    int preambleLength;  // Symbols
+    bool crc;            // Payload CRC enabled
+    bool implicitHeader; // No PHY header (both ends must agree on length, CR and CRC)
+};
+
+/**
+ * @brief Modulation configured in lora_config.h (CRC is disabled by LoRaManager).
+ */
+inline LoRaModulation loraConfiguredModulation()
+{
+    return {LORA_SPREADING_FACTOR, LORA_BANDWIDTH, LORA_CODING_RATE, LORA_PREAMBLE_LENGTH, false, false};
+}
+
+/**
+ * @brief Modulation ACK frames go out with: the data modulation, at
+ * LORA_ACK_SPREADING_FACTOR if set.
+ */
+inline LoRaModulation loraAckModulation(const LoRaModulation &data)
+{
+    LoRaModulation mod = data;
+    if (LORA_ACK_SPREADING_FACTOR != 0)
+    {
+        mod.spreadingFactor = LORA_ACK_SPREADING_FACTOR;
+    }
+    return mod;
+}
+
+/**
+ * @brief Modulation ACK frames go out with at the configured data modulation.
+ */
+inline LoRaModulation loraAckModulation()
+{
+    return loraAckModulation(loraConfiguredModulation());
+}
+
+/**
+ * @brief Symbol duration in milliseconds.
+ */
+inline double loraSymbolTimeMs(const LoRaModulation &mod)
+{
+    return (double)(1UL << mod.spreadingFactor) / mod.bandwidthHz * 1000.0;
+}
+
+/**
+ * @brief Time on air of a frame in milliseconds.
+ *
+ * T = (preamble + 4.25) * Tsym + (8 + max(ceil((8PL - 4SF + 28 + 16CRC - 20IH) / (4(SF - 2DE))) * CR, 0)) * Tsym
+ * Low data rate optimisation (DE) is assumed on whenever the symbol time exceeds
+ * 16 ms, matching what the LoRa library configures.
+ * @param mod Modulation parameters.
+ * @param payloadLen Payload length in bytes.
+ */
+inline double loraTimeOnAirMs(const LoRaModulation &mod, size_t payloadLen)
+{
+    double symbolMs = loraSymbolTimeMs(mod);
+    int lowDataRateOptimize = symbolMs > 16.0 ? 1 : 0;
+
+    double preambleMs = (mod.preambleLength + 4.25) * symbolMs;
+
+    double numerator = 8.0 * payloadLen - 4.0 * mod.spreadingFactor + 28 + (mod.crc ? 16 : 0) -
+                       (mod.implicitHeader ? 20 : 0);
+    double denominator = 4.0 * (mod.spreadingFactor - 2 * lowDataRateOptimize);
+    double payloadSymbols = 8 + fmax(ceil(numerator / denominator) * mod.codingRate, 0.0);
+
+    return preambleMs + payloadSymbols * symbolMs;
+}
+
+/**
+ * @brief Time on air in whole milliseconds (rounded up) for arbitrary parameters.
+ * @param payloadLen Payload length in bytes.
+ * @param spreadingFactor 6-12.
+ * @param bandwidthHz e.g. 31.25E3, 125E3.
+ * @param codingRate Denominator of 4/x (5-8).
+ * @param preambleLength Programmed preamble length in symbols.
+ * @param crc Payload CRC enabled.
+ * @param implicitHeader No PHY header.
+ */
+inline uint32_t loraTimeOnAirMs(size_t payloadLen, int spreadingFactor, double bandwidthHz, int codingRate,
+                                int preambleLength, bool crc, bool implicitHeader)
+{
+    LoRaModulation mod = {spreadingFactor, bandwidthHz, codingRate, preambleLength, crc, implicitHeader};
+    return (uint32_t)ceil(loraTimeOnAirMs(mod, payloadLen));
+}
+
+/// Serialized AckMessage length (type + seq + CRC)
+const size_t LORA_ACK_PAYLOAD_LEN = 3;
+
+/**
+ * @brief Extra ACK wait on top of the airtime-based part, in milliseconds.
+ * Covers the peer's processing and turnaround (deserialize, RX->TX switch,
+ * the debugger's deliberate ACK_DELAY_MS of 500 ms) and our own RX settle.
+ */
+#ifndef LORA_ACK_TIMEOUT_MARGIN_MS
+#define LORA_ACK_TIMEOUT_MARGIN_MS 1000
+#endif
+
+/**
+ * @brief How long to wait for an ACK after our transmission ended.
+ * Twice the ACK's time on air plus LORA_ACK_TIMEOUT_MARGIN_MS: one airtime for
+ * the ACK itself and one as slack for a late preamble lock, so the wait scales
+ * with the spreading factor: ~1.05 s at SF7/BW125, ~2.3 s at SF12/BW125 and
+ * ~3.7 s at the default SF11/BW31.25.
+ */
+inline unsigned long loraAckTimeoutMs(const LoRaModulation &mod)
+{
+    return (unsigned long)ceil(2.0 * loraTimeOnAirMs(mod, LORA_ACK_PAYLOAD_LEN)) + LORA_ACK_TIMEOUT_MARGIN_MS;
+}
+
+/**
+ * @brief ACK wait for an end-to-end ACK that crosses relays.
+ * loraAckTimeoutMs() plus, per relay, the airtime of the relayed text (up to
+ * maxFrameLen bytes) and of the relayed ACK, and LORA_ACK_TIMEOUT_MARGIN_MS for
+ * the relay's own turnaround.
+ */
+inline unsigned long loraRelayedAckTimeoutMs(const LoRaModulation &mod, size_t maxFrameLen, int relayHops)
+{
+    double hopMs = loraTimeOnAirMs(mod, maxFrameLen) + loraTimeOnAirMs(mod, LORA_ACK_PAYLOAD_LEN);
+    return loraAckTimeoutMs(mod) + relayHops * ((unsigned long)ceil(hopMs) + LORA_ACK_TIMEOUT_MARGIN_MS);
+}
+
+/**
+ * @brief Regulatory airtime limits of the band.
+ */
+struct LoRaAirtimeLimits
+{
+    double dutyCyclePercent; // Transmit time per window
+    uint32_t windowS;        // Duty-cycle window
+    uint32_t maxDwellMs;     // Longest single transmission, 0 = no limit
+    uint32_t minMessages;    // Worst-case messages per window below which it is barely usable
+};
+
+/**
+ * @brief Limits configured in lora_config.h.
+ */
+inline LoRaAirtimeLimits loraConfiguredAirtimeLimits()
+{
+    return {LORA_DUTY_CYCLE_PERCENT, LORA_DUTY_CYCLE_WINDOW_S, LORA_MAX_DWELL_MS, LORA_DUTY_CYCLE_MIN_MESSAGES};
+}
+
+/// Outcome of loraCheckAirtime(), from best to worst
+enum class LoRaAirtimeCheck
+{
+    Legal,
+    FewMessages,       // Legal, but fewer than minMessages fit the duty-cycle window
+    DutyCycleExceeded, // The message and its retransmissions exceed the window's budget
+    DwellExceeded      // A single frame is longer than the dwell limit
+};
+
+/**
+ * @brief Transmit time the duty cycle allows per window in milliseconds.
+ */
+inline double loraDutyCycleBudgetMs(const LoRaAirtimeLimits &limits)
+{
+    return limits.dutyCyclePercent / 100.0 * limits.windowS * 1000.0;
+}
+
+/**
+ * @brief Whether a frame of payloadLen bytes can be sent legally at all.
+ * @param attempts Transmissions of one message in the worst case (1 + retries);
+ * they must all fit the duty-cycle budget of one window.
+ */
+inline LoRaAirtimeCheck loraCheckAirtime(const LoRaModulation &mod, size_t payloadLen, int attempts,
+                                         const LoRaAirtimeLimits &limits)
+{
+    double airtimeMs = loraTimeOnAirMs(mod, payloadLen);
+    double budgetMs = loraDutyCycleBudgetMs(limits);
+    if (limits.maxDwellMs > 0 && airtimeMs > limits.maxDwellMs)
+    {
+        return LoRaAirtimeCheck::DwellExceeded;
+    }
+    if (airtimeMs * attempts > budgetMs)
+    {
+        return LoRaAirtimeCheck::DutyCycleExceeded;
+    }
+    if (airtimeMs * limits.minMessages > budgetMs)
+    {
+        return LoRaAirtimeCheck::FewMessages;
+    }
+    return LoRaAirtimeCheck::Legal;
+}
+
+/**
+ * @brief Highest spreading factor, at the modulation's other settings, at which
+ * loraCheckAirtime() passes as Legal; 0 if none down to SF7.
+ */
+inline int loraHighestLegalSf(LoRaModulation mod, size_t payloadLen, int attempts, const LoRaAirtimeLimits &limits)
+{
+    for (int sf = mod.spreadingFactor; sf >= 7; sf--)
+    {
+        mod.spreadingFactor = sf;
+        if (loraCheckAirtime(mod, payloadLen, attempts, limits) == LoRaAirtimeCheck::Legal)
+        {
+            return sf;
+        }
+    }
+    return 0;
+}
+
+#endif // LORA_AIRTIME_H
@@ -5,6 +5,49 @@
 
 // see https://www.semtech.com/design-support/lora-calculator for LoRa settings
 
+/**
+ * @brief Radio chip the LoRaManager drives, chosen at build time.
+ * - LORA_RADIO_SX127X (default): SX1276/77/78 through the sandeepmistry LoRa
+ *   library, see LoRaManager.h.
+ * - LORA_RADIO_SX1262: SX1262 through RadioLib, see Sx126xManager.h. Build with
+ *   the esp32dev-sx1262 environment.
+ *
+ * The two chips hear each other, so a link may mix them. GPIO roles differ:
+ * - SX127x: LORA_DIO0 is RxDone/TxDone, the interrupt the bridge listens on.
+ * - SX1262: LORA_DIO1 carries every IRQ (RxDone/TxDone/CadDone) and LORA_BUSY
+ *   must be wired; the chip takes no SPI command while BUSY is high. There is no
+ *   DIO0. DIO2 usually switches the antenna (LORA_DIO2_AS_RF_SWITCH) and DIO3
+ *   powers a TCXO (LORA_TCXO_VOLTAGE).
+ * The SX1262 backend reads frames in the loop (LORA_RX_POLLING 1), it has no
+ * fixed LNA gain (only AGC, LORA_LNA_GAIN must be 0) and the RX boost maps to its
+ * boosted gain mode. The relay-only firmware and the debugger need an SX127x.
+ */
+#define LORA_RADIO_SX127X 0
+#define LORA_RADIO_SX1262 1
+
+#ifndef LORA_RADIO
+#define LORA_RADIO LORA_RADIO_SX127X
+#endif
+
+#if LORA_RADIO == LORA_RADIO_SX1262
+/**
+ * @brief TCXO supply voltage the SX1262 drives on DIO3, 0 for a plain crystal.
+ * 1.8 V suits most modules (Ebyte E22, Heltec V3); a wrong value keeps the
+ * oscillator from starting and setup() fails.
+ */
+#ifndef LORA_TCXO_VOLTAGE
+#define LORA_TCXO_VOLTAGE 1.8
+#endif
+
+/**
+ * @brief Let the SX1262 drive the antenna switch from DIO2 (1, most modules) or
+ * leave DIO2 alone (0) for a board that switches it from a GPIO of its own.
+ */
+#ifndef LORA_DIO2_AS_RF_SWITCH
+#define LORA_DIO2_AS_RF_SWITCH 1
+#endif
+#endif
+
 /**
  * @brief LoRa frequency.
  * 433.92 MHz - standard frequency for 433 MHz ISM band
@@ -12,10 +55,82 @@
 #define LORA_FREQUENCY 433920000UL // 433.92 MHz
 
 /**
- * @brief LoRa bandwidth.
+ * @brief LoRa bandwidth in Hz.
  * Narrower BW = better sensitivity, longer range
+ * Wider BW = shorter airtime, higher throughput
+ *
+ * Override with a build flag, e.g. -DLORA_BANDWIDTH=250E3. Must be one of the
+ * SX127x steps: 7.8, 10.4, 15.6, 20.8, 31.25, 41.7, 62.5, 125, 250, 500 kHz.
+ * BOTH link ends must use the same bandwidth or they cannot hear each other.
+ *
+ * Each doubling of BW halves the airtime but costs ~3 dB of sensitivity
+ * (roughly 30% less range). At SF11 a 26-byte text+GPS frame takes:
+ * - 31.25 kHz: ~3.3 s   (default, maximum range)
+ * - 125 kHz:   ~0.82 s
+ * - 250 kHz:   ~0.37 s
+ * - 500 kHz:   ~0.19 s  (short range, bulk GPS tracks)
  */
+#ifndef LORA_BANDWIDTH
 #define LORA_BANDWIDTH 31E3 // 31 kHz
+#endif
+
+/**
+ * @brief Edges of the band the channel must fit in (frequency +/- BW/2).
+ * Defaults follow the configured frequency:
+ * - 433 MHz: 433.05-434.79 MHz (ITU Region 1 ISM / ETSI EN 300 220)
+ * - 868 MHz: 863-870 MHz (EU SRD)
+ * - 915 MHz: 902-928 MHz (US ISM)
+ * Override both for other regions.
+ */
+#ifndef LORA_BAND_MIN_HZ
+#if LORA_FREQUENCY < 525000000UL
+#define LORA_BAND_MIN_HZ 433050000UL
+#define LORA_BAND_MAX_HZ 434790000UL
+#elif LORA_FREQUENCY < 900000000UL
+#define LORA_BAND_MIN_HZ 863000000UL
+#define LORA_BAND_MAX_HZ 870000000UL
+#else
+#define LORA_BAND_MIN_HZ 902000000UL
+#define LORA_BAND_MAX_HZ 928000000UL
+#endif
+#endif
+
+/**
+ * @brief Map a bandwidth in Hz to the SX127x RegModemConfig1 BW index.
+ * Accepts values within 1% of a supported step (so 31E3 maps to 31.25 kHz).
+ * @return BW index 0-9, or -1 if the bandwidth is not a supported step.
+ */
+constexpr int loraBandwidthIndex(double hz)
+{
+    constexpr double steps[] = {7.8E3, 10.4E3, 15.6E3, 20.8E3, 31.25E3, 41.7E3, 62.5E3, 125E3, 250E3, 500E3};
+    for (int i = 0; i < 10; i++)
+    {
+        double diff = hz > steps[i] ? hz - steps[i] : steps[i] - hz;
+        if (diff <= steps[i] * 0.01)
+        {
+            return i;
+        }
+    }
+    return -1;
+}
+
+/**
+ * @brief Check that a bandwidth is legal for the given centre frequency.
+ * The channel must fit inside the configured band, and the SX127x does not
+ * support 250/500 kHz in its lowest band (below ~175 MHz).
+ */
+constexpr bool loraBandwidthValidFor(double hz, double frequency)
+{
+    return loraBandwidthIndex(hz) >= 0 &&
+           frequency - hz / 2 >= LORA_BAND_MIN_HZ &&
+           frequency + hz / 2 <= LORA_BAND_MAX_HZ &&
+           !(frequency < 175E6 && hz > 125E3 * 1.01);
+}
+
+static_assert(loraBandwidthIndex(LORA_BANDWIDTH) >= 0,
+              "LORA_BANDWIDTH must be one of 7.8/10.4/15.6/20.8/31.25/41.7/62.5/125/250/500 kHz");
+static_assert(loraBandwidthValidFor(LORA_BANDWIDTH, LORA_FREQUENCY),
+              "LORA_BANDWIDTH does not fit the band for LORA_FREQUENCY (see LORA_BAND_MIN_HZ/LORA_BAND_MAX_HZ)");
 
 /**
  * @brief LoRa spreading factor.
@@ -23,6 +138,35 @@
  */
 #define LORA_SPREADING_FACTOR 11 // SF11 for long range
 
+/**
+ * @brief Spreading factor for ACK frames only ("fast ACK"), 0 = same as the data.
+ * A 2-byte ACK at a lower SF takes a fraction of the airtime (~100 ms at SF7 vs
+ * ~1.3 s at SF11 on BW31.25), which shortens the ACK wait and the time the
+ * receiver is deaf while sending it. Only worth it where the link has margin at
+ * the lower SF - a lost ACK costs a retransmission at the data SF.
+ * The SX127x listens at one SF at a time: a sender listens at this SF only while
+ * an ACK is due, and frames at the data SF sent meanwhile are missed.
+ * Both ends must use the same value, and it doesn't work across relays (they
+ * listen at the data SF).
+ */
+#ifndef LORA_ACK_SPREADING_FACTOR
+#define LORA_ACK_SPREADING_FACTOR 0
+#endif
+
+static_assert(LORA_ACK_SPREADING_FACTOR == 0 ||
+                  (LORA_ACK_SPREADING_FACTOR >= 7 && LORA_ACK_SPREADING_FACTOR < LORA_SPREADING_FACTOR),
+              "LORA_ACK_SPREADING_FACTOR must be 0 or 7 up to below LORA_SPREADING_FACTOR (SF6 needs implicit header)");
+
+/**
+ * @brief Whether a data spreading factor may be selected at runtime (control opcode 0x0A).
+ * SF7-SF12 (SF6 needs implicit header), and above LORA_ACK_SPREADING_FACTOR if
+ * fast ACKs are on.
+ */
+constexpr bool loraSpreadingFactorSelectable(int sf)
+{
+    return sf >= 7 && sf <= 12 && (LORA_ACK_SPREADING_FACTOR == 0 || sf > LORA_ACK_SPREADING_FACTOR);
+}
+
 /**
  * @brief LoRa coding rate.
  * Good error correction
@@ -30,10 +174,342 @@
 #define LORA_CODING_RATE 5 // 4/5 coding rate
 
 /**
- * @brief LoRa TX power.
- * 14 dBm (~25 mW) - check local regulations for 433 MHz ISM band
- * SX1278 supports 2-20 dBm
+ * @brief TX power range of the SX127x PA_BOOST output (used by the LoRa library).
  */
+#define LORA_TX_POWER_MIN_DBM 2
+#define LORA_TX_POWER_MAX_DBM 20
+
+/// 10^(0.5/10): half a dB as a power ratio, the rounding edge between whole dBm steps
+constexpr double LORA_HALF_DB_RATIO = 1.1220184543019633;
+
+/**
+ * @brief Power in mW of a whole dBm setting (10^(dBm/10)).
+ */
+constexpr double loraDbmToMw(int dbm)
+{
+    double mw = 1.0;
+    for (int i = 0; i < (dbm < 0 ? -dbm : dbm); i++)
+    {
+        mw = dbm < 0 ? mw / 1.2589254117941673 : mw * 1.2589254117941673; // 10^(1/10)
+    }
+    return mw;
+}
+
+/**
+ * @brief Whether a power in mW rounds to a dBm setting the radio can transmit.
+ */
+constexpr bool loraTxPowerMwAchievable(double mw)
+{
+    return mw >= loraDbmToMw(LORA_TX_POWER_MIN_DBM) / LORA_HALF_DB_RATIO &&
+           mw < loraDbmToMw(LORA_TX_POWER_MAX_DBM) * LORA_HALF_DB_RATIO;
+}
+
+/**
+ * @brief Convert a power in mW to the nearest whole dBm setting, clamped to
+ * LORA_TX_POWER_MIN_DBM..LORA_TX_POWER_MAX_DBM (e.g. 25 mW -> 14 dBm).
+ */
+constexpr int loraMwToDbm(double mw)
+{
+    int dbm = LORA_TX_POWER_MIN_DBM;
+    while (dbm < LORA_TX_POWER_MAX_DBM && mw >= loraDbmToMw(dbm) * LORA_HALF_DB_RATIO)
+    {
+        dbm++;
+    }
+    return dbm;
+}
+
+/**
+ * @brief LoRa TX power in dBm (default 20 dBm = 100 mW).
+ * Check local regulations (README: Regional Power Limits).
+ *
+ * Set it in dBm (-DLORA_TX_POWER=14) or in milliwatts (-DLORA_TX_POWER_MW=25),
+ * not both. Milliwatts are converted to the nearest whole dBm (10 * log10(mW))
+ * and clamped to the 2-20 dBm (1.6-100 mW) the radio can transmit; the boot log
+ * shows the setting used and flags a request outside that range.
+ * This is conducted power at the radio's output. Set ANTENNA_GAIN_DBI (below)
+ * to have it reduced to what the band's EIRP limit allows.
+ */
+#ifdef LORA_TX_POWER_MW
+#ifdef LORA_TX_POWER
+#error "Set LORA_TX_POWER (dBm) or LORA_TX_POWER_MW (mW), not both"
+#endif
+#define LORA_TX_POWER loraMwToDbm(LORA_TX_POWER_MW)
+#endif
+
+#ifndef LORA_TX_POWER
 #define LORA_TX_POWER 20 // dBm
+#endif
+
+/**
+ * @brief EIRP limit of the band in dBm, enforced when ANTENNA_GAIN_DBI is set.
+ * Regulators limit radiated power (EIRP = conducted power + antenna gain - cable
+ * loss), which the radio can't see. Defaults follow the configured frequency:
+ * - 433 MHz: 12 dBm (10 mW ERP, ETSI EN 300 220; ERP = EIRP - 2.15 dB)
+ * - 868 MHz: 16 dBm (25 mW ERP, EU SRD general sub-bands)
+ * - 915 MHz: 36 dBm (FCC 15.247: 1 W conducted with up to 6 dBi)
+ * Override for other regions or sub-bands (README: Regional Power Limits).
+ */
+#ifndef LORA_EIRP_MAX_DBM
+#if LORA_FREQUENCY < 525000000UL
+#define LORA_EIRP_MAX_DBM 12
+#elif LORA_FREQUENCY < 900000000UL
+#define LORA_EIRP_MAX_DBM 16
+#else
+#define LORA_EIRP_MAX_DBM 36
+#endif
+#endif
+
+/**
+ * @brief Duty-cycle limit of the band in percent of transmit time per
+ * LORA_DUTY_CYCLE_WINDOW_S. Defaults follow the configured frequency:
+ * - 433 MHz: 10% (ETSI EN 300 220, 433.05-434.79 MHz)
+ * - 868 MHz: 1% (EU SRD general sub-bands)
+ * - 915 MHz: 100% (no duty cycle in the US ISM band)
+ * Override for other regions or sub-bands (README: Duty Cycle Check).
+ */
+#ifndef LORA_DUTY_CYCLE_PERCENT
+#if LORA_FREQUENCY < 525000000UL
+#define LORA_DUTY_CYCLE_PERCENT 10
+#elif LORA_FREQUENCY < 900000000UL
+#define LORA_DUTY_CYCLE_PERCENT 1
+#else
+#define LORA_DUTY_CYCLE_PERCENT 100
+#endif
+#endif
+
+/// Window the duty cycle is measured over in seconds (ETSI: one hour)
+#ifndef LORA_DUTY_CYCLE_WINDOW_S
+#define LORA_DUTY_CYCLE_WINDOW_S 3600
+#endif
+
+/**
+ * @brief Longest single transmission the band allows in ms, 0 = no limit.
+ * Defaults to 400 ms at 915 MHz (FCC 15.247 dwell time for channels narrower
+ * than 500 kHz), no limit elsewhere.
+ */
+#ifndef LORA_MAX_DWELL_MS
+#if LORA_FREQUENCY < 900000000UL
+#define LORA_MAX_DWELL_MS 0
+#else
+#define LORA_MAX_DWELL_MS 400
+#endif
+#endif
+
+/**
+ * @brief Fewest worst-case messages per duty-cycle window before the boot log
+ * flags the modulation as barely usable.
+ */
+#ifndef LORA_DUTY_CYCLE_MIN_MESSAGES
+#define LORA_DUTY_CYCLE_MIN_MESSAGES 10
+#endif
+
+/**
+ * @brief Halt at boot instead of only warning when a worst-case message can't
+ * be sent legally (see loraCheckAirtime()). Off by default.
+ */
+#ifndef LORA_REFUSE_ILLEGAL_AIRTIME
+#define LORA_REFUSE_ILLEGAL_AIRTIME 0
+#endif
+
+/**
+ * @brief Hold transmissions at runtime once the duty-cycle budget of the rolling
+ * window is spent (see DutyCycle.h). Texts, retransmissions and relayed frames
+ * wait in the send queue and the app gets a DutyCycleLimited status; ACKs, NACKs
+ * and other replies still go out but count. No effect at 100%.
+ */
+#ifndef LORA_ENFORCE_DUTY_CYCLE
+#define LORA_ENFORCE_DUTY_CYCLE 1
+#endif
+
+/**
+ * @brief Highest whole dBm setting at or below requestedDbm that keeps
+ * EIRP (setting + antenna gain) within limitDbm.
+ * May return less than LORA_TX_POWER_MIN_DBM: the radio can't go that low.
+ */
+constexpr int loraEirpLimitedDbm(int requestedDbm, double antennaGainDbi, double limitDbm)
+{
+    double allowed = limitDbm - antennaGainDbi;
+    int floorDbm = static_cast<int>(allowed);
+    if (floorDbm > allowed)
+    {
+        floorDbm--; // Round towards -infinity for negative values
+    }
+    return requestedDbm < floorDbm ? requestedDbm : floorDbm;
+}
+
+/**
+ * @brief Antenna gain in dBi (minus cable loss in dB), e.g. -DANTENNA_GAIN_DBI=5.
+ * Not set by default: LORA_TX_POWER is used as is and EIRP is not checked.
+ * Once set (0 included), the conducted power is lowered as far as needed to keep
+ * EIRP within LORA_EIRP_MAX_DBM, and the boot log shows the resulting EIRP. A
+ * gain so high that even the radio's minimum setting exceeds the limit fails
+ * the build. Use the gain from the antenna datasheet; a pessimistic (higher)
+ * value errs on the legal side.
+ */
+#ifdef ANTENNA_GAIN_DBI
+#define LORA_TX_POWER_CONDUCTED_DBM loraEirpLimitedDbm(LORA_TX_POWER, ANTENNA_GAIN_DBI, LORA_EIRP_MAX_DBM)
+static_assert(LORA_TX_POWER_CONDUCTED_DBM >= LORA_TX_POWER_MIN_DBM,
+              "ANTENNA_GAIN_DBI exceeds LORA_EIRP_MAX_DBM even at the radio's minimum TX power");
+#else
+#define LORA_TX_POWER_CONDUCTED_DBM LORA_TX_POWER
+#endif
+
+/**
+ * @brief Whether a TX power may be selected at runtime (control opcode 0x0B).
+ * Within the radio's range and, once ANTENNA_GAIN_DBI is set, within the EIRP
+ * limit of the band like the build-time setting.
+ */
+constexpr bool loraTxPowerSelectable(int dbm)
+{
+    return dbm >= LORA_TX_POWER_MIN_DBM && dbm <= LORA_TX_POWER_MAX_DBM
+#ifdef ANTENNA_GAIN_DBI
+           && loraEirpLimitedDbm(dbm, ANTENNA_GAIN_DBI, LORA_EIRP_MAX_DBM) == dbm
+#endif
+        ;
+}
+
+/**
+ * @brief Whether a frequency and TX power may be applied together at runtime (control opcode 0x0B).
+ * The channel must fit the band at LORA_BANDWIDTH (see loraBandwidthValidFor()).
+ */
+constexpr bool loraRadioSettingsValid(uint32_t frequency, int txPowerDbm)
+{
+    return loraBandwidthValidFor(LORA_BANDWIDTH, frequency) && loraTxPowerSelectable(txPowerDbm);
+}
+
+/**
+ * @brief LoRa preamble length in symbols.
+ * 8 is the SX127x / LoRa library default; both ends must match.
+ */
+#define LORA_PREAMBLE_LENGTH 8
+
+/**
+ * @brief LoRa sync word.
+ * 0x12 is the SX127x / LoRa library default (private networks); both ends must
+ * match, so a different value keeps this fleet and others from hearing each other.
+ */
+#ifndef LORA_SYNC_WORD
+#define LORA_SYNC_WORD 0x12
+#endif
+
+/**
+ * @brief Payload encryption key: 16 comma-separated bytes, e.g.
+ * -DLORA_PAYLOAD_KEY='0x2b,0x7e,0x15,0x16,0x28,0xae,0xd2,0xa6,0xab,0xf7,0x15,0x88,0x09,0xcf,0x4f,0x3c'
+ * Undefined by default, so texts go out in the clear.
+ *
+ * With a key, everything in a text after its type and seq (the packed text, GPS
+ * data and fragment header) is encrypted with AES-128-CTR behind a random salt
+ * byte, see Message::serializeEncrypted(). ACKs and every other type stay in the
+ * clear. All nodes, relays and the debugger included, must be built with the same
+ * key: a node without it drops the texts, one with another key reads garbage.
+ * The nonce is only seq plus salt, so it repeats now and then and there is no
+ * MAC - this keeps texts from casual listeners, it doesn't authenticate them
+ * (see LORA_AUTH_KEY).
+ */
+
+/**
+ * @brief Auth key: 16 comma-separated bytes like LORA_PAYLOAD_KEY. Undefined by
+ * default, so frames carry no tag and any node can send them.
+ *
+ * With a key, every frame a bridge sends ends in a 4-byte tag (truncated
+ * SipHash-2-4 over the frame, after encryption) before its CRC-8, see
+ * Message::serializeAuthenticated(). A received frame without the right tag is
+ * dropped silently: never delivered, relayed or reported to the app. All nodes
+ * need the same key. Frames grow by 4 bytes; relays only pass on frames of up to
+ * MAX_RAW_LENGTH bytes, which the longest texts exceed with both keys set.
+ */
+
+/**
+ * @brief Forward error correction: Reed-Solomon parity bytes appended to every
+ * frame on air, 0 (default) for none. At most MAX_FEC_PARITY_LEN (16).
+ *
+ * LoRa's own coding rate (4/5) corrects bit errors within a symbol but gives up
+ * on a burst; N parity bytes repair up to N/2 damaged bytes anywhere in a frame,
+ * after the auth tag and CRC-8 (see Message::addFec()). A receiver repairs a frame
+ * before its CRC-8 is checked, and drops it as corrupted if it can't. Every frame
+ * grows by N bytes of airtime, so 4 (2 bytes repaired) suits a marginal link.
+ * All nodes, relays and the debugger included, must be built with the same value.
+ * Off, the decoder isn't compiled into the firmware.
+ */
+#ifndef LORA_FEC_PARITY
+#define LORA_FEC_PARITY 0
+#endif
+
+/**
+ * @brief Receiver front end (SX127x RegLna / RegModemConfig3), can also be changed
+ * at runtime via LoRaManager::setRxBoost()/setLnaGain().
+ *
+ * LORA_RX_BOOST 1 runs the LNA at 150% current (LnaBoostHf). Gains ~1-3 dB of
+ * sensitivity on the HF RF input for roughly 1-2 mA more while listening; on
+ * 433 MHz boards using the LF input the effect is small. 0 saves that current.
+ *
+ * LORA_LNA_GAIN 0 leaves the gain to the AGC (recommended). 1-6 fixes it, from
+ * G1 (maximum, best for weak links in a quiet environment) to G6 (-48 dB, keeps a
+ * strong nearby transmitter or a noisy site from saturating the front end).
+ * A fixed gain that is too high for the site raises the noise floor and blocks
+ * strong frames; one that is too low loses weak frames.
+ */
+#ifndef LORA_RX_BOOST
+#define LORA_RX_BOOST 1
+#endif
+
+#ifndef LORA_LNA_GAIN
+#define LORA_LNA_GAIN 0
+#endif
+
+/// Lowest fixed LNA gain setting (G6); LNA gain 0 means AGC
+#define LORA_LNA_GAIN_MIN_SETTING 6
+
+static_assert(LORA_RADIO != LORA_RADIO_SX1262 || LORA_LNA_GAIN == 0,
+              "The SX1262 has no fixed LNA gain: LORA_LNA_GAIN must be 0 (AGC)");
+
+/**
+ * @brief Largest frame the radio accepts (SX127x FIFO / RegPayloadMaxLength = 255).
+ * RX buffers are sized to this so a peer running different firmware can never
+ * overflow or silently truncate into a smaller buffer.
+ */
+#define LORA_MAX_PACKET_SIZE 255
+
+/**
+ * @brief Settle time after re-arming RX following a transmission.
+ * RX is re-armed immediately after endPacket() returns (before logging, LED
+ * blinks or power lock release), so this delay only gives the SX1278 time to
+ * stabilise in RX before the main loop continues.
+ *
+ * TX->RX switch budget (SX1276/77/78 datasheet):
+ * - TX done -> STANDBY: automatic, < 1 us
+ * - RegOpMode SPI write + FSRX/RX wake-up: ~60-250 us
+ * Firmware logs the actual re-arm time ("TX->RX re-arm: N us") after every TX.
+ *
+ * The earliest a peer's ACK can start is its processing time plus our own
+ * TX-done latency; at SF11/BW31.25 the 8-symbol preamble alone lasts ~520 ms,
+ * so RX is armed long before a preamble could be missed.
+ */
+#ifndef LORA_RX_SETTLE_MS
+#define LORA_RX_SETTLE_MS 50
+#endif
+
+/**
+ * @brief Spectrum scan settings (see LoRaManager::scanSpectrum()).
+ * Each point retunes the radio, waits LORA_SCAN_SETTLE_MS and averages
+ * LORA_SCAN_SAMPLES RSSI readings taken 1 ms apart, so a point costs roughly
+ * LORA_SCAN_SETTLE_MS + LORA_SCAN_SAMPLES ms. The default sweep of the whole
+ * 433 MHz band in 25 kHz steps (70 points) takes ~0.7 s, during which the node
+ * cannot receive on its operating frequency.
+ */
+#ifndef LORA_SCAN_SETTLE_MS
+#define LORA_SCAN_SETTLE_MS 2
+#endif
+
+#ifndef LORA_SCAN_SAMPLES
+#define LORA_SCAN_SAMPLES 8
+#endif
+
+#ifndef LORA_SCAN_DEFAULT_STEP_HZ
+#define LORA_SCAN_DEFAULT_STEP_HZ 25000UL
+#endif
+
+/// Most points in one scan (bounds the result buffer and the BLE response)
+#define LORA_SCAN_MAX_POINTS 128
 
 #endif // LORA_CONFIG_H
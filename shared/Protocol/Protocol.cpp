@@ -1,13 +1,18 @@
 #include "Protocol.h"
+#include "Aes128.h"
+#include "ReedSolomon.h"
+#include "SipHash.h"
+#include <math.h>
 
 /// Convert a character to its 6-bit encoded value
 /// Automatically converts lowercase to uppercase
 int char_to_6bit(char ch)
 {
-    char upper_ch = toupper(ch);
+    // Cast first: toupper() on a negative char (non-ASCII byte) is undefined behaviour
+    char upper_ch = toupper(static_cast<unsigned char>(ch));
     for (int i = 0; i < 64; i++)
     {
-        if (CHARSET[i] == upper_ch)
+        if (CHARSET[i] == upper_ch && i != CHARSET_SHIFT_CODE)
         {
             return i;
         }
@@ -25,16 +30,97 @@ char sixbit_to_char(uint8_t val)
     return '?'; // Invalid value
 }
 
+/// Whether a character is a letter whose case needs shift codes
+static bool has_case(char ch)
+{
+    return CHARSET_SHIFT_CODE >= 0 && isalpha(static_cast<unsigned char>(ch));
+}
+
+/// 6-bit codes of a text, with room for 2 * MAX_TEXT_LENGTH codes
+/// Without a shift code every character is one code. With one, the text is
+/// planned backwards first: fewest[i][lower] is the number of codes the rest of the
+/// text from character i needs while letters default to lowercase (or not). A
+/// letter in the other case then costs a single shift (2 codes) or a case switch
+/// (3 codes, and the letters after it change default), whichever leaves fewer.
+/// Returns the number of codes, or -1 on error
+static int text_to_codes(const char *text, uint8_t *codes)
+{
+    size_t len = strlen(text);
+    if (len > MAX_TEXT_LENGTH)
+    {
+        return -1; // Text too long
+    }
+
+    uint8_t fewest[MAX_TEXT_LENGTH + 1][2] = {};
+    for (size_t i = len; i-- > 0;)
+    {
+        int lower = islower(static_cast<unsigned char>(text[i])) ? 1 : 0;
+        for (int mode = 0; mode < 2; mode++)
+        {
+            if (!has_case(text[i]) || lower == mode)
+            {
+                fewest[i][mode] = 1 + fewest[i + 1][mode];
+            }
+            else
+            {
+                int shifted = 2 + fewest[i + 1][mode];
+                int switched = 3 + fewest[i + 1][lower];
+                fewest[i][mode] = shifted <= switched ? shifted : switched;
+            }
+        }
+    }
+
+    int count = 0;
+    int mode = 0; // Texts start in uppercase
+    for (size_t i = 0; i < len; i++)
+    {
+        int value = char_to_6bit(text[i]);
+        if (value < 0)
+        {
+            return -1; // Invalid character
+        }
+
+        int lower = islower(static_cast<unsigned char>(text[i])) ? 1 : 0;
+        if (has_case(text[i]) && lower != mode)
+        {
+            if (3 + fewest[i + 1][lower] < 2 + fewest[i + 1][mode])
+            {
+                // Switch the default case for this and the following letters
+                codes[count++] = CHARSET_SHIFT_CODE;
+                codes[count++] = CHARSET_SHIFT_CODE;
+                mode = lower;
+            }
+            else
+            {
+                codes[count++] = CHARSET_SHIFT_CODE; // This letter only
+            }
+        }
+        codes[count++] = value;
+    }
+    return count;
+}
+
+int text_code_count(const char *text)
+{
+    uint8_t codes[2 * MAX_TEXT_LENGTH];
+    return text_to_codes(text, codes);
+}
+
 /// Pack text into 6-bit encoded bytes using manual bit manipulation
 /// Each character is encoded as 6 bits instead of 8 bits (UTF-8)
-/// Lowercase letters are automatically converted to uppercase
+/// Lowercase letters are automatically converted to uppercase, unless CHARSET has a shift code
 /// 50 chars × 6 bits = 300 bits = 37.5 bytes → 38 bytes
 int pack_text(const char *text, uint8_t *output, size_t maxLen)
 {
-    size_t charCount = strlen(text);
+    uint8_t codes[2 * MAX_TEXT_LENGTH];
+    int codeCount = text_to_codes(text, codes);
+    if (codeCount < 0)
+    {
+        return -1; // Invalid character, or text too long
+    }
 
-    // Calculate required bytes: (charCount * 6 + 7) / 8 (round up)
-    size_t byteCount = (charCount * 6 + 7) / 8;
+    // Calculate required bytes: (codeCount * 6 + 7) / 8 (round up)
+    size_t byteCount = (codeCount * 6 + 7) / 8;
 
     if (byteCount > maxLen)
     {
@@ -46,13 +132,9 @@ int pack_text(const char *text, uint8_t *output, size_t maxLen)
 
     size_t bitOffset = 0;
 
-    for (size_t i = 0; i < charCount; i++)
+    for (int i = 0; i < codeCount; i++)
     {
-        int value = char_to_6bit(text[i]);
-        if (value < 0)
-        {
-            return -1; // Invalid character
-        }
+        uint8_t value = codes[i];
 
         // Calculate which byte(s) this 6-bit value spans
         size_t byteIdx = bitOffset / 8;
@@ -83,17 +165,20 @@ int pack_text(const char *text, uint8_t *output, size_t maxLen)
 }
 
 /// Unpack 6-bit encoded bytes back to text using manual bit manipulation
-/// Reads 6 bits at a time and converts to characters (uppercase)
-bool unpack_text(const uint8_t *packed, size_t packedLen, uint8_t charCount, char *output, size_t maxOutputLen)
+/// Reads 6 bits at a time and converts to characters (uppercase, or the case the shift codes give)
+bool unpack_text(const uint8_t *packed, size_t packedLen, uint8_t codeCount, char *output, size_t maxOutputLen)
 {
-    if (charCount >= maxOutputLen)
+    if (codeCount >= maxOutputLen)
     {
         return false; // Output buffer too small
     }
 
     size_t bitOffset = 0;
+    size_t charCount = 0;
+    bool lowerByDefault = false; // Switched by two shift codes in a row
+    bool shifted = false;        // One shift code before the next letter
 
-    for (uint8_t i = 0; i < charCount; i++)
+    for (uint8_t i = 0; i < codeCount; i++)
     {
         size_t byteIdx = bitOffset / 8;
         size_t bitInByte = bitOffset % 8;
@@ -129,16 +214,98 @@ bool unpack_text(const uint8_t *packed, size_t packedLen, uint8_t charCount, cha
             value = (firstPart << bitsInSecond) | secondPart;
         }
 
-        char ch = sixbit_to_char(value);
-        output[i] = ch;
-
         bitOffset += 6;
+
+        if (value == CHARSET_SHIFT_CODE)
+        {
+            if (shifted)
+            {
+                lowerByDefault = !lowerByDefault;
+            }
+            shifted = !shifted;
+            continue;
+        }
+
+        char ch = sixbit_to_char(value);
+        if (has_case(ch))
+        {
+            if (lowerByDefault != shifted)
+            {
+                ch = tolower(static_cast<unsigned char>(ch));
+            }
+        }
+        else if (shifted)
+        {
+            return false; // Shift before a character without case
+        }
+        shifted = false;
+        output[charCount++] = ch;
     }
 
+    if (shifted)
+    {
+        return false; // Shift code without a letter after it
+    }
     output[charCount] = '\0'; // Null-terminate
     return true;
 }
 
+uint8_t crc8(const uint8_t *data, size_t len)
+{
+    uint8_t crc = 0x00;
+    for (size_t i = 0; i < len; i++)
+    {
+        crc ^= data[i];
+        for (int bit = 0; bit < 8; bit++)
+        {
+            crc = (crc & 0x80) ? static_cast<uint8_t>((crc << 1) ^ 0x07) : static_cast<uint8_t>(crc << 1);
+        }
+    }
+    return crc;
+}
+
+/// Microdegrees (degrees * 1_000_000) to radians
+static double microdegreesToRadians(int64_t microdegrees)
+{
+    return microdegrees * (M_PI / 180e6);
+}
+
+uint32_t gpsDistanceM(int32_t lat1, int32_t lon1, int32_t lat2, int32_t lon2)
+{
+    double phi1 = microdegreesToRadians(lat1);
+    double phi2 = microdegreesToRadians(lat2);
+    double halfDeltaPhi = microdegreesToRadians(static_cast<int64_t>(lat2) - lat1) / 2;
+    double halfDeltaLambda = microdegreesToRadians(static_cast<int64_t>(lon2) - lon1) / 2;
+
+    // Haversine: stays accurate down to metres, where the spherical law of cosines loses precision
+    double h = sin(halfDeltaPhi) * sin(halfDeltaPhi) +
+               cos(phi1) * cos(phi2) * sin(halfDeltaLambda) * sin(halfDeltaLambda);
+    double centralAngle = 2 * asin(sqrt(h > 1 ? 1 : h));
+    return static_cast<uint32_t>(lround(EARTH_RADIUS_M * centralAngle));
+}
+
+uint16_t gpsBearingDeg(int32_t lat1, int32_t lon1, int32_t lat2, int32_t lon2)
+{
+    double phi1 = microdegreesToRadians(lat1);
+    double phi2 = microdegreesToRadians(lat2);
+    double deltaLambda = microdegreesToRadians(static_cast<int64_t>(lon2) - lon1);
+
+    double y = sin(deltaLambda) * cos(phi2);
+    double x = cos(phi1) * sin(phi2) - sin(phi1) * cos(phi2) * cos(deltaLambda);
+    long degrees = lround(atan2(y, x) * 180 / M_PI);
+    return static_cast<uint16_t>((degrees + 360) % 360);
+}
+
+uint32_t TextMessage::distanceTo(const TextMessage &other) const
+{
+    return gpsDistanceM(lat, lon, other.lat, other.lon);
+}
+
+uint16_t TextMessage::bearingTo(const TextMessage &other) const
+{
+    return gpsBearingDeg(lat, lon, other.lat, other.lon);
+}
+
 Message Message::createText(uint8_t seq, const char *text)
 {
     Message msg;
@@ -153,8 +320,17 @@ Message Message::createText(uint8_t seq, const char *text)
     memcpy(msg.textData.text, text, len);
     msg.textData.text[len] = '\0';
     msg.textData.hasGps = false;
+    msg.textData.priority = MessagePriority::Normal;
+    msg.textData.hopAck = false;
     msg.textData.lat = 0;
     msg.textData.lon = 0;
+    msg.textData.gpsFields = 0;
+    msg.textData.altitude = 0;
+    msg.textData.speed = 0;
+    msg.textData.isFragment = false;
+    msg.textData.fragmentId = 0;
+    msg.textData.fragmentIndex = 0;
+    msg.textData.fragmentCount = 0;
     return msg;
 }
 
@@ -172,11 +348,54 @@ Message Message::createTextWithGps(uint8_t seq, const char *text, int32_t lat, i
     memcpy(msg.textData.text, text, len);
     msg.textData.text[len] = '\0';
     msg.textData.hasGps = true;
+    msg.textData.priority = MessagePriority::Normal;
+    msg.textData.hopAck = false;
     msg.textData.lat = lat;
     msg.textData.lon = lon;
+    msg.textData.gpsFields = 0;
+    msg.textData.altitude = 0;
+    msg.textData.speed = 0;
+    msg.textData.isFragment = false;
+    msg.textData.fragmentId = 0;
+    msg.textData.fragmentIndex = 0;
+    msg.textData.fragmentCount = 0;
+    return msg;
+}
+
+Message Message::createText(uint8_t seq, const char *text, MessagePriority priority)
+{
+    Message msg = createText(seq, text);
+    msg.textData.priority = priority;
     return msg;
 }
 
+MessagePriority Message::priority() const
+{
+    if (type == MessageType::Sos)
+    {
+        return MessagePriority::Critical;
+    }
+    return type == MessageType::Text ? textData.priority : MessagePriority::Normal;
+}
+
+uint8_t Message::sendPriority() const
+{
+    uint8_t base = SEND_PRIORITY_TEXT;
+    if (type == MessageType::Ack || type == MessageType::HopAck || type == MessageType::Nack)
+    {
+        base = SEND_PRIORITY_ACK;
+    }
+    else if (priority() == MessagePriority::Critical)
+    {
+        base = SEND_PRIORITY_SOS;
+    }
+    else if (type == MessageType::Text && textData.hasGps && textData.text[0] == '\0')
+    {
+        base = SEND_PRIORITY_GPS;
+    }
+    return base + static_cast<uint8_t>(priority());
+}
+
 Message Message::createAck(uint8_t seq)
 {
     Message msg;
@@ -185,114 +404,1100 @@ Message Message::createAck(uint8_t seq)
     return msg;
 }
 
-/// Serializes the message into the provided buffer.
-/// Returns the number of bytes written on success, or -1 on failure.
-int Message::serialize(uint8_t *buf, size_t bufSize) const
+Message Message::createNack(uint8_t seq, DecodeFailureReason reason)
 {
-    switch (type)
-    {
-    case MessageType::Text:
-    {
-        size_t textLen = strlen(textData.text);
-        if (textLen > MAX_TEXT_LENGTH)
-        {
-            return -1; // Text too long
-        }
+    Message msg;
+    msg.type = MessageType::Nack;
+    msg.nackData.seq = seq;
+    msg.nackData.reason = reason;
+    return msg;
+}
 
-        // Pack the text using 6-bit encoding
-        uint8_t packedText[64];
-        int packedLen = pack_text(textData.text, packedText, sizeof(packedText));
-        if (packedLen < 0)
-        {
-            return -1; // Packing failed
-        }
+Message Message::createBattery(uint8_t seq, uint8_t percent, uint16_t millivolts)
+{
+    Message msg;
+    msg.type = MessageType::Battery;
+    msg.batteryData.seq = seq;
+    msg.batteryData.percent = percent > BATTERY_PERCENT_MAX ? BATTERY_PERCENT_MAX : percent;
+    msg.batteryData.millivolts = millivolts;
+    return msg;
+}
 
-        size_t totalSize = 5 + packedLen; // type + seq + charCount + packedLen + hasGps + packed text
-        if (textData.hasGps)
-        {
-            totalSize += 8; // lat + lon
-        }
+Message Message::createSos(uint8_t seq, int32_t lat, int32_t lon)
+{
+    Message msg;
+    msg.type = MessageType::Sos;
+    msg.sosData.seq = seq;
+    msg.sosData.lat = lat;
+    msg.sosData.lon = lon;
+    return msg;
+}
 
-        if (bufSize < totalSize)
-        {
-            return -1; // Buffer too small
-        }
+Message Message::createGpsDelta(uint8_t seq, uint8_t refSeq, int16_t dLat, int16_t dLon)
+{
+    Message msg;
+    msg.type = MessageType::GpsDelta;
+    msg.gpsDeltaData.seq = seq;
+    msg.gpsDeltaData.refSeq = refSeq;
+    msg.gpsDeltaData.dLat = dLat;
+    msg.gpsDeltaData.dLon = dLon;
+    return msg;
+}
 
-        buf[0] = static_cast<uint8_t>(MessageType::Text);
-        buf[1] = textData.seq;
-        buf[2] = textLen;   // Store original character count
-        buf[3] = packedLen; // Store packed byte count
-        memcpy(buf + 4, packedText, packedLen);
-        buf[4 + packedLen] = textData.hasGps ? 1 : 0;
+Message Message::createHopAck(uint8_t seq, uint8_t relayNode)
+{
+    Message msg;
+    msg.type = MessageType::HopAck;
+    msg.hopAckData.seq = seq;
+    msg.hopAckData.relayNode = relayNode;
+    return msg;
+}
 
-        if (textData.hasGps)
-        {
-            memcpy(buf + 5 + packedLen, &textData.lat, 4); // Little-endian
-            memcpy(buf + 9 + packedLen, &textData.lon, 4); // Little-endian
-        }
+Message Message::createIdentify(uint8_t targetNode)
+{
+    Message msg;
+    msg.type = MessageType::Identify;
+    msg.identifyData.targetNode = targetNode;
+    return msg;
+}
 
-        return totalSize;
+Message Message::createIdentifyResponse(const NodeInfo &info)
+{
+    Message msg;
+    msg.type = MessageType::IdentifyResponse;
+    msg.nodeInfoData = info;
+    return msg;
+}
+
+Message Message::createConfigRequest(uint8_t targetNode)
+{
+    Message msg;
+    msg.type = MessageType::ConfigRequest;
+    msg.configRequestData.targetNode = targetNode;
+    return msg;
+}
+
+Message Message::createConfigResponse(const NodeConfig &config)
+{
+    Message msg;
+    msg.type = MessageType::ConfigResponse;
+    msg.nodeConfigData = config;
+    return msg;
+}
+
+Message Message::createRaw(const uint8_t *frame, size_t len)
+{
+    Message msg;
+    msg.type = MessageType::Raw;
+    msg.rawData.frameLen = len > 255 ? 255 : len;
+    // Frames longer than MAX_RAW_LENGTH are truncated, frameLen keeps the original size
+    size_t copyLen = len > MAX_RAW_LENGTH ? MAX_RAW_LENGTH : len;
+    memcpy(msg.rawData.data, frame, copyLen);
+    return msg;
+}
+
+Message Message::createChannelSwitch(uint32_t frequency, ChannelSwitchStage stage)
+{
+    Message msg;
+    msg.type = MessageType::ChannelSwitch;
+    msg.channelSwitchData.frequency = frequency;
+    msg.channelSwitchData.stage = stage;
+    return msg;
+}
+
+Message Message::createRateSwitch(uint8_t spreadingFactor, ChannelSwitchStage stage)
+{
+    Message msg;
+    msg.type = MessageType::RateSwitch;
+    msg.rateSwitchData.spreadingFactor = spreadingFactor;
+    msg.rateSwitchData.stage = stage;
+    return msg;
+}
+
+Message Message::createListenSchedule(const ListenScheduleMessage &schedule)
+{
+    Message msg;
+    msg.type = MessageType::ListenSchedule;
+    msg.listenScheduleData = schedule;
+    return msg;
+}
+
+Message Message::createTrackStatus(uint8_t lastSeq, uint32_t bitmap)
+{
+    Message msg;
+    msg.type = MessageType::TrackStatus;
+    msg.trackStatusData.lastSeq = lastSeq;
+    msg.trackStatusData.bitmap = bitmap;
+    return msg;
+}
+
+Message Message::createStatus(BridgeStatus code, uint8_t seq)
+{
+    Message msg;
+    msg.type = MessageType::Status;
+    msg.statusData.code = code;
+    msg.statusData.seq = seq;
+    return msg;
+}
+
+/// frameLen is the size reported by the radio, len the bytes actually read (less for a truncated frame)
+Message Message::createDecodeFailure(DecodeFailureReason reason, const uint8_t *frame, size_t len, size_t frameLen)
+{
+    Message msg;
+    msg.type = MessageType::DecodeFailure;
+    msg.decodeFailureData.reason = reason;
+    msg.decodeFailureData.frameLen = frameLen > 255 ? 255 : frameLen;
+    size_t copyLen = len > MAX_DECODE_FAILURE_LENGTH ? MAX_DECODE_FAILURE_LENGTH : len;
+    if (copyLen > msg.decodeFailureData.frameLen)
+    {
+        copyLen = msg.decodeFailureData.frameLen;
     }
+    memset(msg.decodeFailureData.data, 0, sizeof(msg.decodeFailureData.data));
+    memcpy(msg.decodeFailureData.data, frame, copyLen);
+    return msg;
+}
 
-    case MessageType::Ack:
+Message Message::createDeliveryReport(DeliveryOutcome outcome, uint8_t seq)
+{
+    Message msg;
+    msg.type = MessageType::DeliveryReport;
+    msg.deliveryReportData.outcome = outcome;
+    msg.deliveryReportData.seq = seq;
+    return msg;
+}
+
+Message Message::createLinkQuality(uint8_t seq, int16_t rssi, float snr)
+{
+    Message msg;
+    msg.type = MessageType::LinkQuality;
+    msg.linkQualityData.seq = seq;
+    msg.linkQualityData.rssi = rssi;
+    msg.linkQualityData.snrQuarterDb = static_cast<int8_t>(snr * 4);
+    return msg;
+}
+
+/// Serializes the message into the provided buffer, followed by its CRC-8.
+/// Returns the number of bytes written on success, or -1 on failure.
+int Message::serialize(uint8_t *buf, size_t bufSize) const
+{
+    if (bufSize < MESSAGE_CRC_LEN)
     {
-        if (bufSize < 2)
-        {
-            return -1; // Buffer too small
-        }
-        buf[0] = static_cast<uint8_t>(MessageType::Ack);
-        buf[1] = ackData.seq;
-        return 2;
+        return -1; // Buffer too small
     }
+    int len = serializeBody(buf, bufSize - MESSAGE_CRC_LEN);
+    if (len < 0)
+    {
+        return -1;
     }
+    buf[len] = crc8(buf, len);
+    return len + MESSAGE_CRC_LEN;
+}
 
-    return -1; // Unknown message type
+bool Message::crcMatches(const uint8_t *buf, size_t len)
+{
+    return len > MESSAGE_CRC_LEN && crc8(buf, len - MESSAGE_CRC_LEN) == buf[len - MESSAGE_CRC_LEN];
 }
 
-/// Deserializes a message from the provided buffer.
+/// Deserializes a message from the provided buffer, CRC-8 last.
 /// Returns true on success, false on failure.
 bool Message::deserialize(const uint8_t *buf, size_t len)
 {
-    if (len == 0)
+    if (!crcMatches(buf, len))
     {
-        return false; // Empty buffer
+        return false; // Empty, or CRC mismatch - corrupted
     }
+    return deserializeBody(buf, len - MESSAGE_CRC_LEN);
+}
 
-    switch (buf[0])
-    {
-    case 0x01:
-    { // Text message
-        if (len < 5)
-        {
-            return false; // Buffer too small for text message header
-        }
+/// Counter block nonce of an encrypted message: type, seq and salt, zero padded
+static void payloadNonce(const uint8_t *header, uint8_t salt, uint8_t *nonce)
+{
+    memset(nonce, 0, Aes128::BLOCK_LEN);
+    nonce[0] = header[0];
+    nonce[1] = header[1];
+    nonce[2] = salt;
+}
 
-        type = MessageType::Text;
-        textData.seq = buf[1];
-        uint8_t charCount = buf[2];
-        uint8_t packedLen = buf[3];
+bool Message::isEncryptable(uint8_t typeByte)
+{
+    // A GPS delta gives away as much of the track as the text it replaces
+    return typeByte == static_cast<uint8_t>(MessageType::Text) || typeByte == static_cast<uint8_t>(MessageType::GpsDelta);
+}
 
-        if (len < 5 + packedLen)
-        {
-            return false; // Buffer too small for packed text + hasGps flag
-        }
+int Message::serializeEncrypted(uint8_t *buf, size_t bufSize, const uint8_t *key, uint8_t salt) const
+{
+    if (!isEncryptable(static_cast<uint8_t>(type)))
+    {
+        return serialize(buf, bufSize);
+    }
+    uint8_t plain[MAX_SERIALIZED_LEN];
+    int len = serializeBody(plain, sizeof(plain));
+    if (len < 2 || static_cast<size_t>(len) + PAYLOAD_SALT_LEN + MESSAGE_CRC_LEN > bufSize)
+    {
+        return -1;
+    }
+
+    // [type][seq][salt][encrypted rest of the body][CRC-8]
+    size_t payloadLen = len - 2;
+    buf[0] = plain[0];
+    buf[1] = plain[1];
+    buf[2] = salt;
+    memcpy(buf + 3, plain + 2, payloadLen);
+    uint8_t nonce[Aes128::BLOCK_LEN];
+    payloadNonce(plain, salt, nonce);
+    Aes128(key).ctr(nonce, buf + 3, payloadLen);
+
+    size_t wireLen = 3 + payloadLen;
+    buf[wireLen] = crc8(buf, wireLen);
+    return wireLen + MESSAGE_CRC_LEN;
+}
+
+bool Message::deserializeEncrypted(const uint8_t *buf, size_t len, const uint8_t *key)
+{
+    if (len < 1 || !isEncryptable(buf[0]))
+    {
+        return deserialize(buf, len);
+    }
+    if (!crcMatches(buf, len) || len < 3 + MESSAGE_CRC_LEN || len - PAYLOAD_SALT_LEN > MAX_SERIALIZED_LEN)
+    {
+        return false;
+    }
+
+    size_t payloadLen = len - 3 - MESSAGE_CRC_LEN;
+    uint8_t plain[MAX_SERIALIZED_LEN];
+    plain[0] = buf[0];
+    plain[1] = buf[1];
+    memcpy(plain + 2, buf + 3, payloadLen);
+    uint8_t nonce[Aes128::BLOCK_LEN];
+    payloadNonce(buf, buf[2], nonce);
+    Aes128(key).ctr(nonce, plain + 2, payloadLen);
+    return deserializeBody(plain, 2 + payloadLen);
+}
+
+/// Auth tag of the bytes before it
+static uint32_t authTag(const uint8_t *data, size_t len, const uint8_t *key)
+{
+    return static_cast<uint32_t>(sipHash24(key, data, len));
+}
+
+int Message::addAuthTag(uint8_t *frame, size_t len, size_t bufSize, const uint8_t *key)
+{
+    if (len < MESSAGE_CRC_LEN || len + AUTH_TAG_LEN > bufSize)
+    {
+        return -1;
+    }
+    size_t bodyLen = len - MESSAGE_CRC_LEN;
+    writeU32(frame + bodyLen, authTag(frame, bodyLen, key));
+    frame[bodyLen + AUTH_TAG_LEN] = crc8(frame, bodyLen + AUTH_TAG_LEN);
+    return len + AUTH_TAG_LEN;
+}
+
+int Message::stripAuthTag(uint8_t *frame, size_t len, const uint8_t *key)
+{
+    if (!crcMatches(frame, len) || len < AUTH_TAG_LEN + MESSAGE_CRC_LEN + 1)
+    {
+        return -1;
+    }
+    size_t bodyLen = len - MESSAGE_CRC_LEN - AUTH_TAG_LEN;
+    if (readU32(frame + bodyLen) != authTag(frame, bodyLen, key))
+    {
+        return -1; // Auth failed
+    }
+    frame[bodyLen] = crc8(frame, bodyLen);
+    return bodyLen + MESSAGE_CRC_LEN;
+}
+
+int Message::serializeAuthenticated(uint8_t *buf, size_t bufSize, const uint8_t *key) const
+{
+    int len = serialize(buf, bufSize);
+    if (len < 0)
+    {
+        return -1;
+    }
+    return addAuthTag(buf, len, bufSize, key);
+}
+
+bool Message::deserializeAuthenticated(const uint8_t *buf, size_t len, const uint8_t *key)
+{
+    uint8_t frame[MAX_FRAME_LEN];
+    if (len > sizeof(frame))
+    {
+        return false;
+    }
+    memcpy(frame, buf, len);
+    int plainLen = stripAuthTag(frame, len, key);
+    return plainLen > 0 && deserialize(frame, plainLen);
+}
+
+int Message::addFec(uint8_t *frame, size_t len, size_t bufSize, uint8_t parityLen)
+{
+    if (parityLen > MAX_FEC_PARITY_LEN || len + parityLen > bufSize || len + parityLen > 255)
+    {
+        return -1;
+    }
+    rsEncode(frame, len, frame + len, parityLen);
+    return len + parityLen;
+}
+
+int Message::correctFec(uint8_t *frame, size_t len, uint8_t parityLen)
+{
+    if (parityLen > MAX_FEC_PARITY_LEN || len <= parityLen || len > 255)
+    {
+        return -1;
+    }
+    if (!rsCorrect(frame, len, parityLen))
+    {
+        return -1; // More damaged bytes than the parity can repair
+    }
+    return len - parityLen;
+}
+
+int Message::serializeFec(uint8_t *buf, size_t bufSize, uint8_t parityLen) const
+{
+    int len = serialize(buf, bufSize);
+    if (len < 0)
+    {
+        return -1;
+    }
+    return addFec(buf, len, bufSize, parityLen);
+}
+
+bool Message::deserializeFec(const uint8_t *buf, size_t len, uint8_t parityLen)
+{
+    uint8_t frame[MAX_FRAME_LEN];
+    if (len > sizeof(frame))
+    {
+        return false;
+    }
+    memcpy(frame, buf, len);
+    int plainLen = correctFec(frame, len, parityLen);
+    return plainLen > 0 && deserialize(frame, plainLen);
+}
+
+int Message::serializeBody(uint8_t *buf, size_t bufSize) const
+{
+    switch (type)
+    {
+    case MessageType::Text:
+    {
+        if (!profileAllowsText(PROTOCOL_PROFILE, textData.hasGps, textData.text[0] != '\0'))
+        {
+            return -1; // Variant compiled out of PROTOCOL_PROFILE
+        }
+#if PROTOCOL_PROFILE == PROTOCOL_PROFILE_MESSAGING
+        const bool hasGps = false; // Everything GPS below folds away
+#else
+        const bool hasGps = textData.hasGps;
+#endif
+        int codeCount = text_code_count(textData.text);
+        if (codeCount < 0 || codeCount > MAX_TEXT_LENGTH)
+        {
+            return -1; // Text too long (with its shift codes) or invalid
+        }
+
+        // Pack the text using 6-bit encoding
+        uint8_t packedText[64];
+        int packedLen = pack_text(textData.text, packedText, sizeof(packedText));
+        if (packedLen < 0)
+        {
+            return -1; // Packing failed
+        }
+
+        size_t totalSize = 5 + packedLen; // type + seq + charCount + packedLen + flags + packed text
+        uint8_t gpsFields = hasGps ? textData.gpsFields & GPS_FIELDS_KNOWN : 0;
+        if (hasGps)
+        {
+            totalSize += 8; // lat + lon
+        }
+        if (gpsFields != 0)
+        {
+            totalSize += 1; // GPS fields byte
+            totalSize += (gpsFields & GPS_FIELD_ALTITUDE) ? 2 : 0;
+            totalSize += (gpsFields & GPS_FIELD_SPEED) ? 2 : 0;
+        }
+        if (textData.isFragment)
+        {
+            if (textData.fragmentCount == 0 || textData.fragmentIndex >= textData.fragmentCount)
+            {
+                return -1; // Fragment outside its text
+            }
+            totalSize += TEXT_FRAGMENT_HEADER_LEN;
+        }
+
+        if (bufSize < totalSize)
+        {
+            return -1; // Buffer too small
+        }
+
+        buf[0] = static_cast<uint8_t>(MessageType::Text);
+        buf[1] = textData.seq;
+        buf[2] = codeCount; // Store the 6-bit code count (characters plus shift codes)
+        buf[3] = packedLen; // Store packed byte count
+        memcpy(buf + 4, packedText, packedLen);
+        uint8_t flags = static_cast<uint8_t>(textData.priority) << TEXT_FLAG_PRIORITY_SHIFT;
+        if (hasGps)
+        {
+            flags |= TEXT_FLAG_GPS;
+        }
+        if (textData.hopAck)
+        {
+            flags |= TEXT_FLAG_HOP_ACK;
+        }
+        if (gpsFields != 0)
+        {
+            flags |= TEXT_FLAG_GPS_FIELDS;
+        }
+        if (textData.isFragment)
+        {
+            flags |= TEXT_FLAG_FRAGMENT;
+        }
+        buf[4 + packedLen] = flags;
+
+        if (hasGps)
+        {
+            writeI32(buf + 5 + packedLen, textData.lat);
+            writeI32(buf + 9 + packedLen, textData.lon);
+        }
+        if (gpsFields != 0)
+        {
+            // Fields in bit order after the fields byte
+            size_t pos = 13 + packedLen;
+            buf[pos++] = gpsFields;
+            if (gpsFields & GPS_FIELD_ALTITUDE)
+            {
+                writeI16(buf + pos, textData.altitude);
+                pos += 2;
+            }
+            if (gpsFields & GPS_FIELD_SPEED)
+            {
+                writeU16(buf + pos, textData.speed);
+            }
+        }
+        if (textData.isFragment)
+        {
+            // Always last, after any GPS fields
+            buf[totalSize - 3] = textData.fragmentId;
+            buf[totalSize - 2] = textData.fragmentIndex;
+            buf[totalSize - 1] = textData.fragmentCount;
+        }
+
+        return totalSize;
+    }
+
+    case MessageType::Ack:
+    {
+        if (bufSize < 2)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Ack);
+        buf[1] = ackData.seq;
+        return 2;
+    }
+
+    case MessageType::Nack:
+    {
+        if (bufSize < 3)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Nack);
+        buf[1] = nackData.seq;
+        buf[2] = static_cast<uint8_t>(nackData.reason);
+        return 3;
+    }
+
+    case MessageType::Battery:
+    {
+        if (bufSize < 5 || batteryData.percent > BATTERY_PERCENT_MAX)
+        {
+            return -1; // Buffer too small or level out of range
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Battery);
+        buf[1] = batteryData.seq;
+        buf[2] = batteryData.percent;
+        writeU16(buf + 3, batteryData.millivolts);
+        return 5;
+    }
+
+    case MessageType::Sos:
+    {
+        if (bufSize < 10)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Sos);
+        buf[1] = sosData.seq;
+        writeI32(buf + 2, sosData.lat);
+        writeI32(buf + 6, sosData.lon);
+        return 10;
+    }
+
+    case MessageType::GpsDelta:
+    {
+        if (bufSize < 7)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::GpsDelta);
+        buf[1] = gpsDeltaData.seq;
+        buf[2] = gpsDeltaData.refSeq;
+        writeI16(buf + 3, gpsDeltaData.dLat);
+        writeI16(buf + 5, gpsDeltaData.dLon);
+        return 7;
+    }
+
+    case MessageType::HopAck:
+    {
+        if (bufSize < 3)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::HopAck);
+        buf[1] = hopAckData.seq;
+        buf[2] = hopAckData.relayNode;
+        return 3;
+    }
+
+    case MessageType::Identify:
+    {
+        if (bufSize < 2)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Identify);
+        buf[1] = identifyData.targetNode;
+        return 2;
+    }
+
+    case MessageType::IdentifyResponse:
+    {
+        if (bufSize < 10)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::IdentifyResponse);
+        buf[1] = nodeInfoData.nodeId;
+        writeU32(buf + 2, nodeInfoData.frequency);
+        buf[6] = nodeInfoData.spreadingFactor;
+        buf[7] = nodeInfoData.bandwidthIndex;
+        buf[8] = nodeInfoData.codingRate;
+        buf[9] = static_cast<uint8_t>(nodeInfoData.txPower);
+        return 10;
+    }
+
+    case MessageType::ConfigRequest:
+    {
+        if (bufSize < 2)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::ConfigRequest);
+        buf[1] = configRequestData.targetNode;
+        return 2;
+    }
+
+    case MessageType::ConfigResponse:
+    {
+        if (bufSize < CONFIG_RESPONSE_LEN - MESSAGE_CRC_LEN)
+        {
+            return -1; // Buffer too small
+        }
+        const NodeInfo &radio = nodeConfigData.radio;
+        buf[0] = static_cast<uint8_t>(MessageType::ConfigResponse);
+        buf[1] = radio.nodeId;
+        writeU32(buf + 2, radio.frequency);
+        buf[6] = radio.spreadingFactor;
+        buf[7] = radio.bandwidthIndex;
+        buf[8] = radio.codingRate;
+        buf[9] = static_cast<uint8_t>(radio.txPower);
+        buf[10] = nodeConfigData.syncWord;
+        writeU16(buf + 11, nodeConfigData.preambleLength);
+        buf[13] = nodeConfigData.firmwareMajor;
+        buf[14] = nodeConfigData.firmwareMinor;
+        buf[15] = nodeConfigData.firmwarePatch;
+        buf[16] = nodeConfigData.protocolProfile;
+        buf[17] = nodeConfigData.charset;
+        return CONFIG_RESPONSE_LEN - MESSAGE_CRC_LEN;
+    }
+
+    case MessageType::ChannelSwitch:
+    {
+        if (bufSize < 6)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::ChannelSwitch);
+        writeU32(buf + 1, channelSwitchData.frequency);
+        buf[5] = static_cast<uint8_t>(channelSwitchData.stage);
+        return 6;
+    }
+
+    case MessageType::ListenSchedule:
+    {
+        if (bufSize < 9)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::ListenSchedule);
+        buf[1] = listenScheduleData.nodeId;
+        buf[2] = listenScheduleData.referenceId;
+        writeU16(buf + 3, listenScheduleData.periodMs);
+        writeU16(buf + 5, listenScheduleData.windowMs);
+        writeU16(buf + 7, listenScheduleData.offsetMs);
+        return 9;
+    }
+
+    case MessageType::RateSwitch:
+    {
+        if (bufSize < 3)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::RateSwitch);
+        buf[1] = rateSwitchData.spreadingFactor;
+        buf[2] = static_cast<uint8_t>(rateSwitchData.stage);
+        return 3;
+    }
+
+    case MessageType::TrackStatus:
+    {
+        if (bufSize < 6)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::TrackStatus);
+        buf[1] = trackStatusData.lastSeq;
+        writeU32(buf + 2, trackStatusData.bitmap);
+        return 6;
+    }
+
+    case MessageType::Raw:
+    {
+        size_t copyLen = rawData.frameLen > MAX_RAW_LENGTH ? MAX_RAW_LENGTH : rawData.frameLen;
+        if (bufSize < 2 + copyLen)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Raw);
+        buf[1] = rawData.frameLen;
+        memcpy(buf + 2, rawData.data, copyLen);
+        return 2 + copyLen;
+    }
+
+    case MessageType::Status:
+    {
+        if (bufSize < 3)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::Status);
+        buf[1] = static_cast<uint8_t>(statusData.code);
+        buf[2] = statusData.seq;
+        return 3;
+    }
+
+    case MessageType::DecodeFailure:
+    {
+        // A truncated frame carries fewer bytes than frameLen; the missing ones are sent as zero
+        size_t copyLen = decodeFailureData.frameLen > MAX_DECODE_FAILURE_LENGTH ? MAX_DECODE_FAILURE_LENGTH
+                                                                                : decodeFailureData.frameLen;
+        if (bufSize < 3 + copyLen)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::DecodeFailure);
+        buf[1] = static_cast<uint8_t>(decodeFailureData.reason);
+        buf[2] = decodeFailureData.frameLen;
+        memcpy(buf + 3, decodeFailureData.data, copyLen);
+        return 3 + copyLen;
+    }
+
+    case MessageType::DeliveryReport:
+    {
+        if (bufSize < 3)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::DeliveryReport);
+        buf[1] = static_cast<uint8_t>(deliveryReportData.outcome);
+        buf[2] = deliveryReportData.seq;
+        return 3;
+    }
+
+    case MessageType::LinkQuality:
+    {
+        if (bufSize < 5)
+        {
+            return -1; // Buffer too small
+        }
+        buf[0] = static_cast<uint8_t>(MessageType::LinkQuality);
+        buf[1] = linkQualityData.seq;
+        writeI16(buf + 2, linkQualityData.rssi);
+        buf[4] = static_cast<uint8_t>(linkQualityData.snrQuarterDb);
+        return 5;
+    }
+    }
+
+    return -1; // Unknown message type
+}
+
+static const FieldSpec TEXT_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x01"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number, echoed by the ACK"},
+    {"charCount", 2, 1, FieldEncoding::U8, nullptr, false, false, "6-bit codes in the text (0-50): its characters plus any shift codes"},
+    {"packedLen", 3, 1, FieldEncoding::U8, nullptr, false, false, "Bytes of packed text"},
+    {"text", 4, FIELD_VARIABLE, FieldEncoding::PackedText, "packedLen", false, false, "6-bit packed text"},
+    {"flags", 0, 1, FieldEncoding::U8, nullptr, true, false,
+     "Bit 0 GPS, bits 1-2 priority, bit 3 hop ACK, bit 4 GPS fields, bit 5 fragment, bits 6-7 zero"},
+    {"lat", 1, 4, FieldEncoding::I32, nullptr, true, true, "Latitude x 1e6, only if flags bit 0"},
+    {"lon", 5, 4, FieldEncoding::I32, nullptr, true, true, "Longitude x 1e6, only if flags bit 0"},
+    {"gpsFields", 9, 1, FieldEncoding::U8, nullptr, true, true,
+     "Bit 0 altitude, bit 1 speed, bits 2-7 later fields; only if flags bits 0 and 4"},
+    {"altitude", 10, 2, FieldEncoding::I16, nullptr, true, true, "Metres above sea level, only if gpsFields bit 0"},
+    {"speed", 12, 2, FieldEncoding::U16, nullptr, true, true,
+     "cm/s over ground, only if gpsFields bit 1 (2 bytes earlier without altitude)"},
+    {"fragmentId", 14, 1, FieldEncoding::U8, nullptr, true, true,
+     "Shared by the fragments of one text, only if flags bit 5; always the third-last byte before the CRC"},
+    {"fragmentIndex", 15, 1, FieldEncoding::U8, nullptr, true, true, "Position of the fragment from 0, only if flags bit 5"},
+    {"fragmentCount", 16, 1, FieldEncoding::U8, nullptr, true, true, "Fragments of the text (1-255), only if flags bit 5"},
+};
+
+static const FieldSpec ACK_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x02"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number of the ACKed text"},
+};
+
+static const FieldSpec NACK_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x04"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number of the damaged text"},
+    {"reason", 2, 1, FieldEncoding::U8, nullptr, false, false, "2 truncated, 5 CRC mismatch (as DecodeFailure)"},
+};
+
+static const FieldSpec BATTERY_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x05"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number, ACKed like a text"},
+    {"percent", 2, 1, FieldEncoding::U8, nullptr, false, false, "Battery level 0-100"},
+    {"millivolts", 3, 2, FieldEncoding::U16, nullptr, false, false, "Battery voltage in mV"},
+};
+
+static const FieldSpec SOS_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x06"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number, the same in every repeat"},
+    {"lat", 2, 4, FieldEncoding::I32, nullptr, false, false, "Latitude x 1e6"},
+    {"lon", 6, 4, FieldEncoding::I32, nullptr, false, false, "Longitude x 1e6"},
+};
+
+static const FieldSpec GPS_DELTA_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x07"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number"},
+    {"refSeq", 2, 1, FieldEncoding::U8, nullptr, false, false, "Seq of the full position update the offsets are from"},
+    {"dLat", 3, 2, FieldEncoding::I16, nullptr, false, false, "Latitude offset x 1e6"},
+    {"dLon", 5, 2, FieldEncoding::I16, nullptr, false, false, "Longitude offset x 1e6"},
+};
+
+static const FieldSpec HOP_ACK_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x0F"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sequence number of the relayed text"},
+    {"relayNode", 2, 1, FieldEncoding::U8, nullptr, false, false, "Node id of the relay"},
+};
+
+static const FieldSpec IDENTIFY_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x08"},
+    {"targetNode", 1, 1, FieldEncoding::U8, nullptr, false, false, "Node id, 0xFF for all nodes"},
+};
+
+static const FieldSpec IDENTIFY_RESPONSE_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x09"},
+    {"nodeId", 1, 1, FieldEncoding::U8, nullptr, false, false, "Node id of the replying bridge"},
+    {"frequency", 2, 4, FieldEncoding::U32, nullptr, false, false, "Hz"},
+    {"spreadingFactor", 6, 1, FieldEncoding::U8, nullptr, false, false, "6-12"},
+    {"bandwidthIndex", 7, 1, FieldEncoding::U8, nullptr, false, false, "SX127x index, 0 = 7.8 kHz ... 9 = 500 kHz"},
+    {"codingRate", 8, 1, FieldEncoding::U8, nullptr, false, false, "Denominator of 4/x (5-8)"},
+    {"txPower", 9, 1, FieldEncoding::I8, nullptr, false, false, "dBm"},
+};
+
+static const FieldSpec CONFIG_REQUEST_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x0D"},
+    {"targetNode", 1, 1, FieldEncoding::U8, nullptr, false, false, "Node id, 0xFF for all nodes"},
+};
+
+static const FieldSpec CONFIG_RESPONSE_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x0E"},
+    {"nodeId", 1, 1, FieldEncoding::U8, nullptr, false, false, "Node id of the replying bridge"},
+    {"frequency", 2, 4, FieldEncoding::U32, nullptr, false, false, "Hz"},
+    {"spreadingFactor", 6, 1, FieldEncoding::U8, nullptr, false, false, "6-12"},
+    {"bandwidthIndex", 7, 1, FieldEncoding::U8, nullptr, false, false, "SX127x index, 0 = 7.8 kHz ... 9 = 500 kHz"},
+    {"codingRate", 8, 1, FieldEncoding::U8, nullptr, false, false, "Denominator of 4/x (5-8)"},
+    {"txPower", 9, 1, FieldEncoding::I8, nullptr, false, false, "dBm"},
+    {"syncWord", 10, 1, FieldEncoding::U8, nullptr, false, false, "LoRa sync word"},
+    {"preambleLength", 11, 2, FieldEncoding::U16, nullptr, false, false, "Symbols"},
+    {"firmwareMajor", 13, 1, FieldEncoding::U8, nullptr, false, false, "Firmware version"},
+    {"firmwareMinor", 14, 1, FieldEncoding::U8, nullptr, false, false, "Firmware version"},
+    {"firmwarePatch", 15, 1, FieldEncoding::U8, nullptr, false, false, "Firmware version"},
+    {"protocolProfile", 16, 1, FieldEncoding::U8, nullptr, false, false, "0 all, 1 messaging, 2 tracking, 3 combined"},
+    {"charset", 17, 1, FieldEncoding::U8, nullptr, false, false, "0 standard, 1 numeric, 2 symbol, 3 mixed case"},
+};
+
+static const FieldSpec CHANNEL_SWITCH_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x0A"},
+    {"frequency", 1, 4, FieldEncoding::U32, nullptr, false, false, "Hz"},
+    {"stage", 5, 1, FieldEncoding::U8, nullptr, false, false, "0 Propose, 1 Accept, 2 Confirm"},
+};
+
+static const FieldSpec RATE_SWITCH_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x10"},
+    {"spreadingFactor", 1, 1, FieldEncoding::U8, nullptr, false, false, "7-12"},
+    {"stage", 2, 1, FieldEncoding::U8, nullptr, false, false, "0 Propose, 1 Accept, 2 Confirm"},
+};
+
+static const FieldSpec LISTEN_SCHEDULE_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x0B"},
+    {"nodeId", 1, 1, FieldEncoding::U8, nullptr, false, false, "Sender"},
+    {"referenceId", 2, 1, FieldEncoding::U8, nullptr, false, false, "Node whose clock the windows follow"},
+    {"periodMs", 3, 2, FieldEncoding::U16, nullptr, false, false, "A window opens every period, non-zero"},
+    {"windowMs", 5, 2, FieldEncoding::U16, nullptr, false, false, "Window length, at most the period"},
+    {"offsetMs", 7, 2, FieldEncoding::U16, nullptr, false, false, "Time into the sender's window, below the period"},
+};
+
+static const FieldSpec TRACK_STATUS_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x0C"},
+    {"lastSeq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Newest fix received"},
+    {"bitmap", 2, 4, FieldEncoding::U32, nullptr, false, false, "Bit i: fix lastSeq - i received"},
+};
+
+static const FieldSpec RAW_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x80"},
+    {"frameLen", 1, 1, FieldEncoding::U8, nullptr, false, false, "Original frame length on air"},
+    {"frame", 2, FIELD_VARIABLE, FieldEncoding::Bytes, "frameLen", false, false, "Frame bytes, at most 62"},
+};
+
+static const FieldSpec STATUS_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x81"},
+    {"code", 1, 1, FieldEncoding::U8, nullptr, false, false, "1 radio busy, 2 TX resumed, 3 duty-cycle limited"},
+    {"seq", 2, 1, FieldEncoding::U8, nullptr, false, false, "Seq of the deferred or held text, 0 for other types"},
+};
+
+static const FieldSpec DECODE_FAILURE_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x82"},
+    {"reason", 1, 1, FieldEncoding::U8, nullptr, false, false,
+     "1 malformed, 2 truncated, 3 unknown type, 4 rejected, 5 CRC mismatch"},
+    {"frameLen", 2, 1, FieldEncoding::U8, nullptr, false, false, "Original frame length on air"},
+    {"frame", 3, FIELD_VARIABLE, FieldEncoding::Bytes, "frameLen", false, false,
+     "Frame bytes, at most 61 (zero past the end of a truncated read)"},
+};
+
+static const FieldSpec DELIVERY_REPORT_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x83"},
+    {"outcome", 1, 1, FieldEncoding::U8, nullptr, false, false, "1 delivered, 2 failed, 3 relayed, 4 sent"},
+    {"seq", 2, 1, FieldEncoding::U8, nullptr, false, false, "Seq of the text"},
+};
+
+static const FieldSpec LINK_QUALITY_LAYOUT[] = {
+    {"type", 0, 1, FieldEncoding::U8, nullptr, false, false, "0x84"},
+    {"seq", 1, 1, FieldEncoding::U8, nullptr, false, false, "Seq of the received text"},
+    {"rssi", 2, 2, FieldEncoding::I16, nullptr, false, false, "dBm"},
+    {"snr", 4, 1, FieldEncoding::I8, nullptr, false, false, "0.25 dB steps"},
+};
+
+#define LAYOUT(type, name, fields) {type, name, fields, sizeof(fields) / sizeof(fields[0])}
+
+const MessageLayout MESSAGE_LAYOUTS[] = {
+    LAYOUT(MessageType::Text, "Text", TEXT_LAYOUT),
+    LAYOUT(MessageType::Ack, "Ack", ACK_LAYOUT),
+    LAYOUT(MessageType::Nack, "Nack", NACK_LAYOUT),
+    LAYOUT(MessageType::Battery, "Battery", BATTERY_LAYOUT),
+    LAYOUT(MessageType::Sos, "Sos", SOS_LAYOUT),
+    LAYOUT(MessageType::GpsDelta, "GpsDelta", GPS_DELTA_LAYOUT),
+    LAYOUT(MessageType::Identify, "Identify", IDENTIFY_LAYOUT),
+    LAYOUT(MessageType::IdentifyResponse, "IdentifyResponse", IDENTIFY_RESPONSE_LAYOUT),
+    LAYOUT(MessageType::ChannelSwitch, "ChannelSwitch", CHANNEL_SWITCH_LAYOUT),
+    LAYOUT(MessageType::RateSwitch, "RateSwitch", RATE_SWITCH_LAYOUT),
+    LAYOUT(MessageType::ListenSchedule, "ListenSchedule", LISTEN_SCHEDULE_LAYOUT),
+    LAYOUT(MessageType::TrackStatus, "TrackStatus", TRACK_STATUS_LAYOUT),
+    LAYOUT(MessageType::ConfigRequest, "ConfigRequest", CONFIG_REQUEST_LAYOUT),
+    LAYOUT(MessageType::ConfigResponse, "ConfigResponse", CONFIG_RESPONSE_LAYOUT),
+    LAYOUT(MessageType::HopAck, "HopAck", HOP_ACK_LAYOUT),
+    LAYOUT(MessageType::Raw, "Raw", RAW_LAYOUT),
+    LAYOUT(MessageType::Status, "Status", STATUS_LAYOUT),
+    LAYOUT(MessageType::DecodeFailure, "DecodeFailure", DECODE_FAILURE_LAYOUT),
+    LAYOUT(MessageType::DeliveryReport, "DeliveryReport", DELIVERY_REPORT_LAYOUT),
+    LAYOUT(MessageType::LinkQuality, "LinkQuality", LINK_QUALITY_LAYOUT),
+};
+
+#undef LAYOUT
+
+const size_t MESSAGE_LAYOUT_COUNT = sizeof(MESSAGE_LAYOUTS) / sizeof(MESSAGE_LAYOUTS[0]);
+
+const MessageLayout *messageLayout(uint8_t typeByte)
+{
+    for (size_t i = 0; i < MESSAGE_LAYOUT_COUNT; i++)
+    {
+        if (static_cast<uint8_t>(MESSAGE_LAYOUTS[i].type) == typeByte)
+        {
+            return &MESSAGE_LAYOUTS[i];
+        }
+    }
+    return nullptr;
+}
+
+bool isKnownMessageType(uint8_t typeByte)
+{
+    switch (static_cast<MessageType>(typeByte))
+    {
+    case MessageType::Text:
+    case MessageType::Ack:
+    case MessageType::Nack:
+    case MessageType::Battery:
+    case MessageType::Sos:
+    case MessageType::GpsDelta:
+    case MessageType::Identify:
+    case MessageType::IdentifyResponse:
+    case MessageType::ChannelSwitch:
+    case MessageType::RateSwitch:
+    case MessageType::ListenSchedule:
+    case MessageType::TrackStatus:
+    case MessageType::ConfigRequest:
+    case MessageType::ConfigResponse:
+    case MessageType::HopAck:
+    case MessageType::Raw:
+    case MessageType::Status:
+    case MessageType::DecodeFailure:
+    case MessageType::DeliveryReport:
+    case MessageType::LinkQuality:
+        return true;
+    }
+    return false;
+}
+
+/// Whether a text may go on air as a GPS delta: position only, Normal priority,
+/// nothing a delta can't carry (GPS fields, hop ACK flag)
+static bool gpsDeltaCandidate(const Message &msg)
+{
+    return msg.type == MessageType::Text && msg.textData.hasGps && msg.textData.text[0] == '\0' &&
+           msg.textData.priority == MessagePriority::Normal && !msg.textData.hopAck && msg.textData.gpsFields == 0 &&
+           !msg.textData.isFragment;
+}
+
+Message encodeGpsPosition(const Message &update, const GpsReference &reference, uint8_t resyncEvery)
+{
+    if (!gpsDeltaCandidate(update) || !reference.valid || reference.deltasSent >= resyncEvery)
+    {
+        return update;
+    }
+
+    int64_t dLat = static_cast<int64_t>(update.textData.lat) - reference.lat;
+    int64_t dLon = static_cast<int64_t>(update.textData.lon) - reference.lon;
+    if (dLat < INT16_MIN || dLat > INT16_MAX || dLon < INT16_MIN || dLon > INT16_MAX)
+    {
+        return update; // Moved too far for an i16
+    }
+    return Message::createGpsDelta(update.textData.seq, reference.seq, static_cast<int16_t>(dLat),
+                                   static_cast<int16_t>(dLon));
+}
+
+void rememberGpsPosition(GpsReference &reference, const Message &sent)
+{
+    if (sent.type == MessageType::GpsDelta)
+    {
+        reference.deltasSent++;
+    }
+    else if (gpsDeltaCandidate(sent))
+    {
+        reference.valid = true;
+        reference.seq = sent.textData.seq;
+        reference.lat = sent.textData.lat;
+        reference.lon = sent.textData.lon;
+        reference.deltasSent = 0;
+    }
+}
+
+bool decodeGpsPosition(const Message &received, GpsReference &reference, Message &position)
+{
+    if (received.type != MessageType::GpsDelta)
+    {
+        // A retransmission of an older full update must not replace a newer reference
+        uint8_t ahead = received.textData.seq - reference.seq;
+        if (gpsDeltaCandidate(received) && (!reference.valid || (ahead != 0 && ahead < 128)))
+        {
+            reference.valid = true;
+            reference.seq = received.textData.seq;
+            reference.lat = received.textData.lat;
+            reference.lon = received.textData.lon;
+        }
+        position = received;
+        return true;
+    }
+
+    const GpsDeltaMessage &delta = received.gpsDeltaData;
+    if (!reference.valid || reference.seq != delta.refSeq)
+    {
+        return false;
+    }
+    position = Message::createTextWithGps(delta.seq, "", reference.lat + delta.dLat, reference.lon + delta.dLon);
+    return true;
+}
+
+bool Message::deserializeBody(const uint8_t *buf, size_t len)
+{
+    if (len == 0)
+    {
+        return false; // Empty buffer
+    }
+
+    switch (buf[0])
+    {
+    case 0x01:
+    { // Text message
+        if (len < 5)
+        {
+            return false; // Buffer too small for text message header
+        }
+
+        type = MessageType::Text;
+        textData.seq = buf[1];
+        uint8_t codeCount = buf[2];
+        uint8_t packedLen = buf[3];
+
+        if (codeCount > MAX_TEXT_LENGTH)
+        {
+            return false; // Text too long
+        }
+
+        if (packedLen != (codeCount * 6 + 7) / 8)
+        {
+            return false; // Length fields disagree - corrupt or malformed header
+        }
+
+        if (len < 5 + packedLen)
+        {
+            return false; // Buffer too small for packed text + flags byte
+        }
 
         const uint8_t *packedBytes = buf + 4;
-        if (!unpack_text(packedBytes, packedLen, charCount, textData.text, sizeof(textData.text)))
+        if (!unpack_text(packedBytes, packedLen, codeCount, textData.text, sizeof(textData.text)))
         {
             return false;
         }
 
-        textData.hasGps = (buf[4 + packedLen] != 0);
+        uint8_t flags = buf[4 + packedLen];
+        textData.isFragment = (flags & TEXT_FLAG_FRAGMENT) != 0;
+        size_t fragmentLen = textData.isFragment ? TEXT_FRAGMENT_HEADER_LEN : 0;
+        textData.hasGps = (flags & TEXT_FLAG_GPS) != 0;
+        textData.hopAck = (flags & TEXT_FLAG_HOP_ACK) != 0;
+        uint8_t priority = (flags & TEXT_FLAG_PRIORITY_MASK) >> TEXT_FLAG_PRIORITY_SHIFT;
+        textData.priority = priority > static_cast<uint8_t>(MessagePriority::Critical)
+                                ? MessagePriority::Critical // Reserved value
+                                : static_cast<MessagePriority>(priority);
 
         if (textData.hasGps)
         {
-            if (len < 5 + packedLen + 8)
+            if (len < 5 + packedLen + 8 + fragmentLen)
             {
                 return false; // Buffer too small for GPS data
             }
-            memcpy(&textData.lat, buf + 5 + packedLen, 4); // Little-endian
-            memcpy(&textData.lon, buf + 9 + packedLen, 4); // Little-endian
+            textData.lat = readI32(buf + 5 + packedLen);
+            textData.lon = readI32(buf + 9 + packedLen);
         }
         else
         {
@@ -300,6 +1505,53 @@ bool Message::deserialize(const uint8_t *buf, size_t len)
             textData.lon = 0;
         }
 
+        textData.gpsFields = 0;
+        textData.altitude = 0;
+        textData.speed = 0;
+        if (textData.hasGps && (flags & TEXT_FLAG_GPS_FIELDS))
+        {
+            if (len < 5 + packedLen + 8 + 1 + fragmentLen)
+            {
+                return false; // Buffer too small for the GPS fields byte
+            }
+            // Read the fields this version knows; later ones follow them and are ignored
+            size_t pos = 13 + packedLen;
+            uint8_t gpsFields = buf[pos++] & GPS_FIELDS_KNOWN;
+            size_t needed = pos + ((gpsFields & GPS_FIELD_ALTITUDE) ? 2 : 0) + ((gpsFields & GPS_FIELD_SPEED) ? 2 : 0);
+            if (len < needed + fragmentLen)
+            {
+                return false; // Buffer too small for the fields it announces
+            }
+            if (gpsFields & GPS_FIELD_ALTITUDE)
+            {
+                textData.altitude = readI16(buf + pos);
+                pos += 2;
+            }
+            if (gpsFields & GPS_FIELD_SPEED)
+            {
+                textData.speed = readU16(buf + pos);
+            }
+            textData.gpsFields = gpsFields;
+        }
+
+        textData.fragmentId = 0;
+        textData.fragmentIndex = 0;
+        textData.fragmentCount = 0;
+        if (textData.isFragment)
+        {
+            if (len < 5 + packedLen + fragmentLen)
+            {
+                return false; // Buffer too small for the fragment header
+            }
+            textData.fragmentId = buf[len - 3];
+            textData.fragmentIndex = buf[len - 2];
+            textData.fragmentCount = buf[len - 1];
+            if (textData.fragmentCount == 0 || textData.fragmentIndex >= textData.fragmentCount)
+            {
+                return false; // Fragment outside its text
+            }
+        }
+
         return true;
     }
 
@@ -316,6 +1568,331 @@ bool Message::deserialize(const uint8_t *buf, size_t len)
         return true;
     }
 
+    case 0x08:
+    { // Identify request
+        if (len < 2)
+        {
+            return false; // Buffer too small for identify
+        }
+
+        type = MessageType::Identify;
+        identifyData.targetNode = buf[1];
+
+        return true;
+    }
+
+    case 0x09:
+    { // Identify response
+        if (len < 10)
+        {
+            return false; // Buffer too small for node info
+        }
+
+        type = MessageType::IdentifyResponse;
+        nodeInfoData.nodeId = buf[1];
+        nodeInfoData.frequency = readU32(buf + 2);
+        nodeInfoData.spreadingFactor = buf[6];
+        nodeInfoData.bandwidthIndex = buf[7];
+        nodeInfoData.codingRate = buf[8];
+        nodeInfoData.txPower = static_cast<int8_t>(buf[9]);
+
+        return true;
+    }
+
+    case 0x0A:
+    { // Channel switch
+        if (len < 6)
+        {
+            return false; // Buffer too small for channel switch
+        }
+        if (buf[5] > static_cast<uint8_t>(ChannelSwitchStage::Confirm))
+        {
+            return false; // Unknown handshake stage
+        }
+
+        type = MessageType::ChannelSwitch;
+        channelSwitchData.frequency = readU32(buf + 1);
+        channelSwitchData.stage = static_cast<ChannelSwitchStage>(buf[5]);
+
+        return true;
+    }
+
+    case 0x10:
+    { // Rate switch
+        if (len < 3)
+        {
+            return false; // Buffer too small for rate switch
+        }
+        if (buf[1] < 7 || buf[1] > 12 || buf[2] > static_cast<uint8_t>(ChannelSwitchStage::Confirm))
+        {
+            return false; // No LoRa spreading factor, or unknown handshake stage
+        }
+
+        type = MessageType::RateSwitch;
+        rateSwitchData.spreadingFactor = buf[1];
+        rateSwitchData.stage = static_cast<ChannelSwitchStage>(buf[2]);
+
+        return true;
+    }
+
+    case 0x0B:
+    { // Listen schedule beacon
+        if (len < 9)
+        {
+            return false; // Buffer too small for listen schedule
+        }
+        if (readU16(buf + 3) == 0 || readU16(buf + 5) > readU16(buf + 3) || readU16(buf + 7) >= readU16(buf + 3))
+        {
+            return false; // Window or offset outside the period
+        }
+
+        type = MessageType::ListenSchedule;
+        listenScheduleData.nodeId = buf[1];
+        listenScheduleData.referenceId = buf[2];
+        listenScheduleData.periodMs = readU16(buf + 3);
+        listenScheduleData.windowMs = readU16(buf + 5);
+        listenScheduleData.offsetMs = readU16(buf + 7);
+
+        return true;
+    }
+
+    case 0x0C:
+    { // Track status
+        if (len < 6)
+        {
+            return false; // Buffer too small for track status
+        }
+
+        type = MessageType::TrackStatus;
+        trackStatusData.lastSeq = buf[1];
+        trackStatusData.bitmap = readU32(buf + 2);
+
+        return true;
+    }
+
+    case 0x0D:
+    { // Config request
+        if (len < 2)
+        {
+            return false; // Buffer too small for config request
+        }
+
+        type = MessageType::ConfigRequest;
+        configRequestData.targetNode = buf[1];
+
+        return true;
+    }
+
+    case 0x0E:
+    { // Config response
+        if (len < CONFIG_RESPONSE_LEN - MESSAGE_CRC_LEN)
+        {
+            return false; // Buffer too small for node config
+        }
+
+        type = MessageType::ConfigResponse;
+        NodeInfo &radio = nodeConfigData.radio;
+        radio.nodeId = buf[1];
+        radio.frequency = readU32(buf + 2);
+        radio.spreadingFactor = buf[6];
+        radio.bandwidthIndex = buf[7];
+        radio.codingRate = buf[8];
+        radio.txPower = static_cast<int8_t>(buf[9]);
+        nodeConfigData.syncWord = buf[10];
+        nodeConfigData.preambleLength = readU16(buf + 11);
+        nodeConfigData.firmwareMajor = buf[13];
+        nodeConfigData.firmwareMinor = buf[14];
+        nodeConfigData.firmwarePatch = buf[15];
+        nodeConfigData.protocolProfile = buf[16];
+        nodeConfigData.charset = buf[17];
+
+        return true;
+    }
+
+    case 0x04:
+    { // NACK
+        if (len < 3)
+        {
+            return false; // Buffer too small for nack
+        }
+        if (buf[2] < static_cast<uint8_t>(DecodeFailureReason::Malformed) ||
+            buf[2] > static_cast<uint8_t>(DecodeFailureReason::CrcMismatch))
+        {
+            return false; // Unknown reason
+        }
+
+        type = MessageType::Nack;
+        nackData.seq = buf[1];
+        nackData.reason = static_cast<DecodeFailureReason>(buf[2]);
+
+        return true;
+    }
+
+    case 0x05:
+    { // Battery level
+        if (len < 5)
+        {
+            return false; // Buffer too small for battery level
+        }
+        if (buf[2] > BATTERY_PERCENT_MAX)
+        {
+            return false; // Level out of range
+        }
+
+        type = MessageType::Battery;
+        batteryData.seq = buf[1];
+        batteryData.percent = buf[2];
+        batteryData.millivolts = readU16(buf + 3);
+
+        return true;
+    }
+
+    case 0x06:
+    { // SOS
+        if (len < 10)
+        {
+            return false; // Buffer too small for SOS
+        }
+
+        type = MessageType::Sos;
+        sosData.seq = buf[1];
+        sosData.lat = readI32(buf + 2);
+        sosData.lon = readI32(buf + 6);
+
+        return true;
+    }
+
+    case 0x07:
+    { // GPS delta
+        if (len < 7)
+        {
+            return false; // Buffer too small for GPS delta
+        }
+
+        type = MessageType::GpsDelta;
+        gpsDeltaData.seq = buf[1];
+        gpsDeltaData.refSeq = buf[2];
+        gpsDeltaData.dLat = readI16(buf + 3);
+        gpsDeltaData.dLon = readI16(buf + 5);
+
+        return true;
+    }
+
+    case 0x0F:
+    { // Hop ACK
+        if (len < 3)
+        {
+            return false; // Buffer too small for hop ack
+        }
+
+        type = MessageType::HopAck;
+        hopAckData.seq = buf[1];
+        hopAckData.relayNode = buf[2];
+
+        return true;
+    }
+
+    case 0x80:
+    { // Raw frame
+        if (len < 2)
+        {
+            return false; // Buffer too small for raw header
+        }
+
+        uint8_t frameLen = buf[1];
+        size_t copyLen = frameLen > MAX_RAW_LENGTH ? MAX_RAW_LENGTH : frameLen;
+        if (len < 2 + copyLen)
+        {
+            return false; // Buffer too small for frame bytes
+        }
+
+        type = MessageType::Raw;
+        rawData.frameLen = frameLen;
+        memcpy(rawData.data, buf + 2, copyLen);
+
+        return true;
+    }
+
+    case 0x81:
+    { // Bridge status
+        if (len < 3)
+        {
+            return false; // Buffer too small for status
+        }
+        if (buf[1] < static_cast<uint8_t>(BridgeStatus::RadioBusy) ||
+            buf[1] > static_cast<uint8_t>(BridgeStatus::DutyCycleLimited))
+        {
+            return false; // Unknown status code
+        }
+
+        type = MessageType::Status;
+        statusData.code = static_cast<BridgeStatus>(buf[1]);
+        statusData.seq = buf[2];
+
+        return true;
+    }
+
+    case 0x82:
+    { // Decode failure report
+        if (len < 3)
+        {
+            return false; // Buffer too small for decode failure header
+        }
+        if (buf[1] < static_cast<uint8_t>(DecodeFailureReason::Malformed) ||
+            buf[1] > static_cast<uint8_t>(DecodeFailureReason::CrcMismatch))
+        {
+            return false; // Unknown reason
+        }
+
+        uint8_t frameLen = buf[2];
+        size_t copyLen = frameLen > MAX_DECODE_FAILURE_LENGTH ? MAX_DECODE_FAILURE_LENGTH : frameLen;
+        if (len < 3 + copyLen)
+        {
+            return false; // Buffer too small for frame bytes
+        }
+
+        type = MessageType::DecodeFailure;
+        decodeFailureData.reason = static_cast<DecodeFailureReason>(buf[1]);
+        decodeFailureData.frameLen = frameLen;
+        memcpy(decodeFailureData.data, buf + 3, copyLen);
+
+        return true;
+    }
+
+    case 0x83:
+    { // Delivery report
+        if (len < 3)
+        {
+            return false; // Buffer too small for delivery report
+        }
+        if (buf[1] < static_cast<uint8_t>(DeliveryOutcome::Delivered) ||
+            buf[1] > static_cast<uint8_t>(DeliveryOutcome::Sent))
+        {
+            return false; // Unknown outcome
+        }
+
+        type = MessageType::DeliveryReport;
+        deliveryReportData.outcome = static_cast<DeliveryOutcome>(buf[1]);
+        deliveryReportData.seq = buf[2];
+
+        return true;
+    }
+
+    case 0x84:
+    { // Link quality
+        if (len < 5)
+        {
+            return false; // Buffer too small for link quality
+        }
+
+        type = MessageType::LinkQuality;
+        linkQualityData.seq = buf[1];
+        linkQualityData.rssi = readI16(buf + 2);
+        linkQualityData.snrQuarterDb = static_cast<int8_t>(buf[4]);
+
+        return true;
+    }
+
     default:
         return false; // Unknown message type
     }
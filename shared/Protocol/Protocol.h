@@ -1,41 +1,539 @@
 #ifndef PROTOCOL_H
 #define PROTOCOL_H
 
+#ifdef ARDUINO
 #include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cctype>
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
+#endif
 
 /// Maximum text length in characters for optimal long-range LoRa transmission.
 /// With 6-bit packing: 50 chars = 38 bytes (was 50 bytes)
 /// With SF10, BW125, 433MHz: 50 bytes (12 header + 38 text) = ~600ms Time on Air
+/// Counts 6-bit codes, so with CHARSET_MIXED_CASE shift codes take up room too.
 const uint8_t MAX_TEXT_LENGTH = 50;
 
+/// Largest serialized message of any type, used to size TX/BLE buffers
+const size_t MAX_SERIALIZED_LEN = 64;
+
+/// Trailing CRC-8 of every serialized message, see crc8()
+const size_t MESSAGE_CRC_LEN = 1;
+
+/// AES-128 key of payload encryption, see Message::serializeEncrypted()
+const size_t PAYLOAD_KEY_LEN = 16;
+
+/// Salt byte an encrypted message carries right after its seq
+const size_t PAYLOAD_SALT_LEN = 1;
+
+/// Key of the auth tag, see Message::serializeAuthenticated()
+const size_t AUTH_KEY_LEN = 16;
+
+/// Truncated SipHash-2-4 an authenticated frame carries before its CRC-8
+const size_t AUTH_TAG_LEN = 4;
+
+/// Most Reed-Solomon parity bytes a frame can carry after its CRC-8, see Message::addFec()
+const size_t MAX_FEC_PARITY_LEN = 16;
+
+/// Largest frame on air: the largest message, encrypted, authenticated and with FEC
+const size_t MAX_FRAME_LEN = MAX_SERIALIZED_LEN + PAYLOAD_SALT_LEN + AUTH_TAG_LEN + MAX_FEC_PARITY_LEN;
+
+/// Maximum number of frame bytes carried by a Raw message (type + length header and CRC excluded)
+const uint8_t MAX_RAW_LENGTH = MAX_SERIALIZED_LEN - 2 - MESSAGE_CRC_LEN;
+
+/// Maximum number of frame bytes carried by a DecodeFailure message (type + reason + length header and CRC excluded)
+const uint8_t MAX_DECODE_FAILURE_LENGTH = MAX_SERIALIZED_LEN - 3 - MESSAGE_CRC_LEN;
+
+/// BLE fragment markers, used when a message doesn't fit one ATT payload (MTU - 3).
+/// They occupy the first byte where a message type would be, so they never clash.
+/// Start:    [0xF1][total message length][first bytes...]
+/// Continue: [0xF2][next bytes...]
+const uint8_t BLE_FRAGMENT_START = 0xF1;
+const uint8_t BLE_FRAGMENT_CONTINUE = 0xF2;
+
+/// Longest message that can be fragmented: the start fragment gives its length in one byte
+const size_t BLE_MAX_FRAGMENTED_LEN = 255;
+
+/// BLE batch marker: several complete messages in one notification, each
+/// prefixed by its length. Only used ESP32 -> app, never fragmented.
+/// Batch: [0xF3][length 1][message 1...][length 2][message 2...]...
+const uint8_t BLE_BATCH = 0xF3;
+
+/// Canonical byte order: every multi-byte field of this protocol (LoRa frames,
+/// BLE messages and control responses) is LITTLE-ENDIAN, whatever the host CPU.
+/// It is fixed, not negotiated: a peer has no way to detect another order, so
+/// changing it is a breaking protocol change. Read and write multi-byte fields
+/// only through the helpers below (never memcpy an integer into a frame), so the
+/// order lives in one place.
+inline void writeU16(uint8_t *buf, uint16_t value)
+{
+    buf[0] = value & 0xFF;
+    buf[1] = value >> 8;
+}
+
+inline void writeI16(uint8_t *buf, int16_t value)
+{
+    writeU16(buf, static_cast<uint16_t>(value));
+}
+
+inline void writeU32(uint8_t *buf, uint32_t value)
+{
+    buf[0] = value & 0xFF;
+    buf[1] = (value >> 8) & 0xFF;
+    buf[2] = (value >> 16) & 0xFF;
+    buf[3] = value >> 24;
+}
+
+inline void writeI32(uint8_t *buf, int32_t value)
+{
+    writeU32(buf, static_cast<uint32_t>(value));
+}
+
+inline uint16_t readU16(const uint8_t *buf)
+{
+    return static_cast<uint16_t>(buf[0] | (buf[1] << 8));
+}
+
+inline int16_t readI16(const uint8_t *buf)
+{
+    return static_cast<int16_t>(readU16(buf));
+}
+
+inline uint32_t readU32(const uint8_t *buf)
+{
+    return static_cast<uint32_t>(buf[0]) | (static_cast<uint32_t>(buf[1]) << 8) |
+           (static_cast<uint32_t>(buf[2]) << 16) | (static_cast<uint32_t>(buf[3]) << 24);
+}
+
+inline int32_t readI32(const uint8_t *buf)
+{
+    return static_cast<int32_t>(readU32(buf));
+}
+
+/// Node id that addresses every node (e.g. Identify all units in range)
+const uint8_t BROADCAST_NODE_ID = 0xFF;
+
+/// Reserved node id: never assigned, marks "no node"
+const uint8_t RESERVED_NODE_ID = 0x00;
+
+/// Whether a node id can be given to a bridge (1-254)
+inline bool isAssignableNodeId(uint8_t id)
+{
+    return id != RESERVED_NODE_ID && id != BROADCAST_NODE_ID;
+}
+
+// Character set for the 6-bit text encoding, selected with PROTOCOL_CHARSET
+// (e.g. -DPROTOCOL_CHARSET=CHARSET_NUMERIC). Texts travel as indices into the
+// set, so both bridges and both apps of a link must use the same one - any other
+// pairing garbles texts without an error.
+// - CHARSET_STANDARD (default): space, A-Z, 0-9 and 27 common punctuation marks
+// - CHARSET_NUMERIC: as standard with ^ ~ | \ for maths and measurements in
+//   place of the brackets and braces [ ] { }
+// - CHARSET_SYMBOL: all 32 ASCII punctuation marks; J, K, Q, X and Z are
+//   dropped to make room
+// - CHARSET_MIXED_CASE: as standard with a shift code in place of _, so texts
+//   keep their lowercase letters (see CHARSET_SHIFT_CODE)
+#define CHARSET_STANDARD 0
+#define CHARSET_NUMERIC 1
+#define CHARSET_SYMBOL 2
+#define CHARSET_MIXED_CASE 3
+
+#ifndef PROTOCOL_CHARSET
+#define PROTOCOL_CHARSET CHARSET_STANDARD
+#endif
+
 /// Character set for 6-bit encoding (64 characters)
 /// Index maps to 6-bit value: 0-63
-/// UPPERCASE ONLY: Space + A-Z (26) + 0-9 (10) + punctuation (27)
-const char CHARSET[65] = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>_";
+/// UPPERCASE ONLY: lowercase input is converted before lookup, and only
+/// CHARSET_MIXED_CASE restores it with shift codes
+#if PROTOCOL_CHARSET == CHARSET_STANDARD
+constexpr char CHARSET[] = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>_";
+#elif PROTOCOL_CHARSET == CHARSET_NUMERIC
+constexpr char CHARSET[] = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()^~|\\=+/<>_";
+#elif PROTOCOL_CHARSET == CHARSET_SYMBOL
+constexpr char CHARSET[] = " ABCDEFGHILMNOPRSTUVWY0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>_^~|\\`";
+#elif PROTOCOL_CHARSET == CHARSET_MIXED_CASE
+constexpr char CHARSET[] = " ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:;'\"@#$%&*()[]{}=+/<>\x0E"; // 0x0E: shift
+#else
+#error "Unknown PROTOCOL_CHARSET"
+#endif
+
+/// Whether every character of a set is distinct and none is lowercase (unreachable after uppercasing)
+constexpr bool charsetValid(const char *set, size_t len)
+{
+    for (size_t i = 0; i < len; i++)
+    {
+        if (set[i] >= 'a' && set[i] <= 'z')
+        {
+            return false;
+        }
+        for (size_t j = i + 1; j < len; j++)
+        {
+            if (set[i] == set[j])
+            {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+static_assert(sizeof(CHARSET) == 65, "CHARSET must have exactly 64 characters, one per 6-bit value");
+static_assert(charsetValid(CHARSET, sizeof(CHARSET) - 1), "CHARSET characters must be distinct and uppercase");
+
+/// 6-bit value of the shift code, -1 if CHARSET has none (every set but CHARSET_MIXED_CASE).
+/// One shift code switches the case of the letter after it; two in a row switch the
+/// case of every following letter, up to the next two. Texts start in uppercase.
+constexpr int CHARSET_SHIFT_CODE = PROTOCOL_CHARSET == CHARSET_MIXED_CASE ? 63 : -1;
+
+// Protocol profile: which text variants a deployment sends, selected with
+// PROTOCOL_PROFILE (e.g. -DPROTOCOL_PROFILE=PROTOCOL_PROFILE_COMBINED). The byte
+// layout of a text is the same in every profile (see protocol.md); the variants
+// outside the profile are compiled out of Message::serialize(), so both ends of
+// a link must use the same one. They still deserialize, so a bridge can refuse
+// them from a misconfigured peer instead of failing to parse them.
+// - PROTOCOL_PROFILE_ALL (default): texts with or without GPS, in any mix.
+// - PROTOCOL_PROFILE_MESSAGING: texts without GPS only; the GPS encoding is
+//   compiled out.
+// - PROTOCOL_PROFILE_TRACKING: standalone variants only - a text without GPS, or
+//   a position-only update (GPS and an empty text). A note with a position is two
+//   messages.
+// - PROTOCOL_PROFILE_COMBINED: TextWithGps only - every text carries GPS, an
+//   empty one being a position update. One frame and one ACK per note + position.
+#define PROTOCOL_PROFILE_ALL 0
+#define PROTOCOL_PROFILE_MESSAGING 1
+#define PROTOCOL_PROFILE_TRACKING 2
+#define PROTOCOL_PROFILE_COMBINED 3
+
+#ifndef PROTOCOL_PROFILE
+#define PROTOCOL_PROFILE PROTOCOL_PROFILE_ALL
+#endif
+
+#if PROTOCOL_PROFILE < PROTOCOL_PROFILE_ALL || PROTOCOL_PROFILE > PROTOCOL_PROFILE_COMBINED
+#error "Unknown PROTOCOL_PROFILE"
+#endif
+
+/// Whether a profile has a text variant: with or without GPS, with or without text
+constexpr bool profileAllowsText(int profile, bool hasGps, bool hasText)
+{
+    return profile == PROTOCOL_PROFILE_MESSAGING  ? !hasGps
+           : profile == PROTOCOL_PROFILE_TRACKING ? !hasGps || !hasText
+           : profile == PROTOCOL_PROFILE_COMBINED ? hasGps
+                                                  : true;
+}
 
 /// Message types
 enum class MessageType : uint8_t
 {
     Text = 0x01,
-    Ack = 0x02
+    Ack = 0x02,
+    Nack = 0x04,             // LoRa only: a text arrived corrupted, retransmit it now
+    Battery = 0x05,          // Battery level of the sending node, ACKed like a text
+    Sos = 0x06,              // Distress call with position, repeated by the sender (SOS_REPEATS)
+    GpsDelta = 0x07,         // LoRa only: position update as offsets from the last full one (GPS_DELTA_ENCODING)
+    Identify = 0x08,         // Ask a node to blink its LED and report its config
+    IdentifyResponse = 0x09, // Node id + radio config reply to Identify
+    ChannelSwitch = 0x0A,    // LoRa only: coordinated move to another frequency
+    ListenSchedule = 0x0B,   // LoRa only: listen window beacon for synchronized low-power RX
+    TrackStatus = 0x0C,      // LoRa only: bitmap of the position fixes received recently
+    ConfigRequest = 0x0D,    // Ask a node for its full configuration (no LED signal)
+    ConfigResponse = 0x0E,   // Radio, firmware and protocol config reply to ConfigRequest
+    HopAck = 0x0F,           // LoRa only: a relay took a text on (flags bit 3)
+    RateSwitch = 0x10,       // LoRa only: coordinated move to another spreading factor (ADAPTIVE_DATA_RATE)
+    Raw = 0x80,          // BLE only: unparseable LoRa frame forwarded in promiscuous mode
+    Status = 0x81,        // BLE only: transient bridge status for the app
+    DecodeFailure = 0x82, // BLE only: dropped LoRa frame and why (DEBUG_DECODE_FAILURES)
+    DeliveryReport = 0x83, // BLE only: whether a text sent from this bridge reached the peer
+    LinkQuality = 0x84     // BLE only: RSSI and SNR of a text received over LoRa (LINK_QUALITY_REPORTS)
+};
+
+/// Delivery priority of a text message, carried in bits 1-2 of its flags byte.
+/// Normal (0) keeps the behaviour of frames without a priority. The BLE delivery
+/// doesn't depend on it: every text is indicated when the app subscribed to indications.
+enum class MessagePriority : uint8_t
+{
+    Normal = 0,  // FIFO, no LoRa retransmission
+    High = 1,    // Jumps the LoRa send queue, retransmitted
+    Critical = 2 // As High with more retransmissions (value 3 is reserved and decodes as Critical)
 };
 
+/// Transmit order of messages waiting for LoRa, highest first: the base of the
+/// kind plus its MessagePriority (see Message::sendPriority())
+const uint8_t SEND_PRIORITY_TEXT = 0; // Texts and every other type
+const uint8_t SEND_PRIORITY_GPS = 4;  // Position updates (GPS, empty text)
+const uint8_t SEND_PRIORITY_SOS = 8;  // SOS messages and Critical texts
+const uint8_t SEND_PRIORITY_ACK = 12; // ACKs, hop ACKs and NACKs
+
+/// Flags byte of a text message
+const uint8_t TEXT_FLAG_GPS = 0x01;
+const uint8_t TEXT_FLAG_PRIORITY_SHIFT = 1;
+const uint8_t TEXT_FLAG_PRIORITY_MASK = 0x06;
+const uint8_t TEXT_FLAG_HOP_ACK = 0x08; // A relay's hop ACK completes delivery (ACK_SEMANTICS_HOP)
+const uint8_t TEXT_FLAG_GPS_FIELDS = 0x10; // A GPS fields byte and its fields follow the coordinates
+const uint8_t TEXT_FLAG_FRAGMENT = 0x20;   // The text is one fragment of a longer one, see TEXT_FRAGMENT_HEADER_LEN
+
+/// Fragment header of a text with TEXT_FLAG_FRAGMENT: fragment id, index and
+/// count, the last bytes before the CRC. Found from the end of the frame, so
+/// GPS fields a receiver doesn't know still sit between the coordinates and it.
+const size_t TEXT_FRAGMENT_HEADER_LEN = 3;
+
+/// GPS fields byte of a text: which optional GPS fields follow it, in bit order.
+/// Fields added later take the next bit, so a receiver reads the fields it knows
+/// and ignores the bytes after them.
+const uint8_t GPS_FIELD_ALTITUDE = 0x01; // i16, metres above mean sea level
+const uint8_t GPS_FIELD_SPEED = 0x02;    // u16, cm/s over ground
+const uint8_t GPS_FIELDS_KNOWN = GPS_FIELD_ALTITUDE | GPS_FIELD_SPEED;
+
 /// Text message with optional GPS coordinates
 struct TextMessage
 {
     uint8_t seq;
     char text[MAX_TEXT_LENGTH + 1]; // Fixed-size buffer for text (null-terminated)
     bool hasGps;                    // Whether GPS coordinates are included
+    MessagePriority priority;
+    bool hopAck;                    // Sender is satisfied by a relay's HopAck, see TEXT_FLAG_HOP_ACK
     int32_t lat;                    // latitude * 1_000_000 (only valid if hasGps=true)
     int32_t lon;                    // longitude * 1_000_000 (only valid if hasGps=true)
+    uint8_t gpsFields;              // GPS_FIELD_* present (only valid if hasGps=true)
+    int16_t altitude;               // Metres above mean sea level (only valid with GPS_FIELD_ALTITUDE)
+    uint16_t speed;                 // cm/s over ground (only valid with GPS_FIELD_SPEED)
+    bool isFragment;                // Part of a longer text, see TEXT_FLAG_FRAGMENT
+    uint8_t fragmentId;             // Shared by every fragment of the text (only valid if isFragment=true)
+    uint8_t fragmentIndex;          // Position of this fragment, 0 to fragmentCount - 1 (only valid if isFragment=true)
+    uint8_t fragmentCount;          // Fragments of the text, 1-255 (only valid if isFragment=true)
+
+    /// Distance in metres to the position of another text (both must have GPS), see gpsDistanceM()
+    uint32_t distanceTo(const TextMessage &other) const;
+
+    /// Bearing in degrees to the position of another text (both must have GPS), see gpsBearingDeg()
+    uint16_t bearingTo(const TextMessage &other) const;
 };
 
+/// Mean Earth radius in metres (IUGG), for GPS distances
+const double EARTH_RADIUS_M = 6371008.8;
+
+/// Great-circle distance in metres between two positions in degrees * 1_000_000, as
+/// carried by TextMessage. Haversine on a sphere: within 0.5% of the ellipsoid, and
+/// rounded to the metre, so fine for the few metres to thousands of km LoRa spans.
+uint32_t gpsDistanceM(int32_t lat1, int32_t lon1, int32_t lat2, int32_t lon2);
+
+/// Initial great-circle bearing from the first position to the second, in whole
+/// degrees clockwise from true north (0-359). 0 for the same position.
+uint16_t gpsBearingDeg(int32_t lat1, int32_t lon1, int32_t lat2, int32_t lon2);
+
 /// Acknowledgment message
 struct AckMessage
 {
     uint8_t seq;
 };
 
+/// Sent by a relay for a text whose sender accepts hop ACKs (never forwarded to BLE)
+struct HopAckMessage
+{
+    uint8_t seq;       // Seq of the relayed text
+    uint8_t relayNode; // Node id of the relay
+};
+
+/// Identify request addressed to a single node (or BROADCAST_NODE_ID)
+struct IdentifyMessage
+{
+    uint8_t targetNode;
+};
+
+/// Node id and radio configuration, sent in reply to Identify
+struct NodeInfo
+{
+    uint8_t nodeId;
+    uint32_t frequency;     // Hz
+    uint8_t spreadingFactor;
+    uint8_t bandwidthIndex; // SX127x BW index 0-9 (7.8 kHz ... 500 kHz)
+    uint8_t codingRate;     // Denominator of 4/x (5-8)
+    int8_t txPower;         // dBm
+};
+
+/// Configuration request addressed to a single node (or BROADCAST_NODE_ID)
+struct ConfigRequestMessage
+{
+    uint8_t targetNode;
+};
+
+/// Everything both ends of a link must agree on, sent in reply to ConfigRequest
+struct NodeConfig
+{
+    NodeInfo radio;          // Node id and radio parameters, as in IdentifyResponse
+    uint8_t syncWord;
+    uint16_t preambleLength; // Symbols
+    uint8_t firmwareMajor;
+    uint8_t firmwareMinor;
+    uint8_t firmwarePatch;
+    uint8_t protocolProfile; // PROTOCOL_PROFILE_* of the bridge
+    uint8_t charset;         // CHARSET_* (PROTOCOL_CHARSET)
+};
+
+/// Serialized size of a ConfigResponse (CRC included)
+const size_t CONFIG_RESPONSE_LEN = 19;
+
+/// Steps of the channel switch handshake
+enum class ChannelSwitchStage : uint8_t
+{
+    Propose = 0, // Initiator -> peer, on the current channel
+    Accept = 1,  // Peer -> initiator, on the current channel; the peer then retunes
+    Confirm = 2  // Initiator -> peer, on the new channel
+};
+
+/// Coordinated channel switch between two bridges (never forwarded to BLE)
+struct ChannelSwitchMessage
+{
+    uint32_t frequency; // Hz
+    ChannelSwitchStage stage;
+};
+
+/// Coordinated spreading factor switch between two bridges, same handshake as
+/// ChannelSwitch (never forwarded to BLE)
+struct RateSwitchMessage
+{
+    uint8_t spreadingFactor; // Data spreading factor to move to
+    ChannelSwitchStage stage;
+};
+
+/// Listen window schedule advertised by a node in synchronized listen mode (never forwarded to BLE)
+struct ListenScheduleMessage
+{
+    uint8_t nodeId;      // Sender
+    uint8_t referenceId; // Node whose clock the sender's windows are aligned to
+    uint16_t periodMs;   // A listen window opens every periodMs
+    uint16_t windowMs;   // Length of each window
+    uint16_t offsetMs;   // Time since the sender's window opened when the frame went on air
+};
+
+/// Serialized ListenSchedule length in bytes (CRC included)
+const size_t LISTEN_SCHEDULE_LEN = 10;
+
+/// Position fixes covered by one TrackStatus bitmap (lastSeq and the 31 before it)
+const uint8_t TRACK_STATUS_WINDOW = 32;
+
+/// Which recent position fixes a receiver got, sent back so the sender can retransmit only the missing ones
+struct TrackStatusMessage
+{
+    uint8_t lastSeq;  // Newest fix received
+    uint32_t bitmap;  // Bit i set: fix lastSeq - i received (bit 0 is always set)
+};
+
+/// Raw LoRa frame that failed to deserialize (firmware -> app only, never transmitted)
+struct RawMessage
+{
+    uint8_t frameLen;              // Original frame length on air
+    uint8_t data[MAX_RAW_LENGTH];  // First min(frameLen, MAX_RAW_LENGTH) bytes of the frame
+};
+
+/// Why a received LoRa frame was dropped, reported in a DecodeFailure message
+enum class DecodeFailureReason : uint8_t
+{
+    Malformed = 0x01,   // Known type, but deserialize() failed (bad length or field value)
+    Truncated = 0x02,   // Frame could not be read completely from the radio
+    UnknownType = 0x03, // Type byte this firmware doesn't know
+    Rejected = 0x04,    // Decodes, but is not accepted on air (protocol profile, BLE-only type)
+    CrcMismatch = 0x05  // Trailing CRC-8 doesn't match the frame (corrupted on air)
+};
+
+/// Sent for a text that arrived corrupted or truncated but whose seq could still
+/// be read (never forwarded to BLE)
+struct NackMessage
+{
+    uint8_t seq;                // Seq of the damaged text
+    DecodeFailureReason reason; // Truncated or CrcMismatch
+};
+
+/// Highest battery level in percent
+const uint8_t BATTERY_PERCENT_MAX = 100;
+
+/// Battery level of the sending node, ACKed and forwarded to the app like a text
+struct BatteryMessage
+{
+    uint8_t seq;
+    uint8_t percent;     // 0-100
+    uint16_t millivolts; // Battery voltage
+};
+
+/// Distress call: where the sender is, in the GPS units of a text (degrees x 1e6)
+struct SosMessage
+{
+    uint8_t seq;
+    int32_t lat;
+    int32_t lon;
+};
+
+/// Position-only update as offsets from the last full one the sender transmitted, in
+/// the GPS units of a text (degrees x 1e6). Never forwarded to BLE: the receiving
+/// bridge turns it back into a text with GPS (see decodeGpsPosition()).
+struct GpsDeltaMessage
+{
+    uint8_t seq;    // Seq of this update, ACKed like a text
+    uint8_t refSeq; // Seq of the full update the offsets are from
+    int16_t dLat;
+    int16_t dLon;
+};
+
+/// Last full position-only update of a track, the reference of GPS deltas
+struct GpsReference
+{
+    bool valid; // False until a full update was sent (sender) or received (receiver)
+    uint8_t seq;
+    int32_t lat;
+    int32_t lon;
+    uint8_t deltasSent; // Sender: deltas against it so far, for the periodic resync
+};
+
+/// Dropped LoRa frame with the reason (firmware -> app only, never transmitted)
+struct DecodeFailureMessage
+{
+    DecodeFailureReason reason;
+    uint8_t frameLen;                         // Original frame length on air
+    uint8_t data[MAX_DECODE_FAILURE_LENGTH];  // First min(frameLen, MAX_DECODE_FAILURE_LENGTH) bytes read
+};
+
+/// Status codes of a Status message
+enum class BridgeStatus : uint8_t
+{
+    RadioBusy = 0x01,       // A transmit is deferred while the radio receives a frame
+    TxResumed = 0x02,       // The deferred transmit went out
+    DutyCycleLimited = 0x03 // A transmit is held until the duty-cycle budget allows it
+};
+
+/// Transient bridge status (firmware -> app only, never buffered or transmitted)
+struct StatusMessage
+{
+    BridgeStatus code;
+    uint8_t seq; // Seq of the deferred or held text, 0 for other types
+};
+
+/// Outcomes of a DeliveryReport message
+enum class DeliveryOutcome : uint8_t
+{
+    Delivered = 0x01, // The peer ACKed the text
+    Failed = 0x02,    // No ACK after the last retransmission
+    Relayed = 0x03,   // A relay's HopAck: taken on, not known to have reached the peer
+    Sent = 0x04       // Transmitted once, never confirmed (FIRE_AND_FORGET)
+};
+
+/// Delivery outcome of a text sent from this bridge (firmware -> app only, never transmitted)
+struct DeliveryReportMessage
+{
+    DeliveryOutcome outcome;
+    uint8_t seq; // Seq of the text
+};
+
+/// Signal quality of a received text (firmware -> app only, never transmitted)
+struct LinkQualityMessage
+{
+    uint8_t seq;         // Seq of the text
+    int16_t rssi;        // dBm
+    int8_t snrQuarterDb; // SNR in 0.25 dB steps
+};
+
 /// Union of all message types
 class Message
 {
@@ -45,35 +543,216 @@ public:
     // Store all message data separately (only one will be used based on type)
     TextMessage textData;
     AckMessage ackData;
+    NackMessage nackData;
+    BatteryMessage batteryData;
+    SosMessage sosData;
+    GpsDeltaMessage gpsDeltaData;
+    HopAckMessage hopAckData;
+    IdentifyMessage identifyData;
+    NodeInfo nodeInfoData;
+    ConfigRequestMessage configRequestData;
+    NodeConfig nodeConfigData;
+    ChannelSwitchMessage channelSwitchData;
+    RateSwitchMessage rateSwitchData;
+    ListenScheduleMessage listenScheduleData;
+    TrackStatusMessage trackStatusData;
+    RawMessage rawData;
+    StatusMessage statusData;
+    DecodeFailureMessage decodeFailureData;
+    DeliveryReportMessage deliveryReportData;
+    LinkQualityMessage linkQualityData;
 
     Message() : type(MessageType::Text) {}
 
     static Message createText(uint8_t seq, const char *text);
     static Message createTextWithGps(uint8_t seq, const char *text, int32_t lat, int32_t lon);
+    static Message createText(uint8_t seq, const char *text, MessagePriority priority);
     static Message createAck(uint8_t seq);
+    static Message createNack(uint8_t seq, DecodeFailureReason reason);
+    static Message createBattery(uint8_t seq, uint8_t percent, uint16_t millivolts);
+    static Message createSos(uint8_t seq, int32_t lat, int32_t lon);
+    static Message createGpsDelta(uint8_t seq, uint8_t refSeq, int16_t dLat, int16_t dLon);
+    static Message createHopAck(uint8_t seq, uint8_t relayNode);
+    static Message createIdentify(uint8_t targetNode);
+    static Message createIdentifyResponse(const NodeInfo &info);
+    static Message createConfigRequest(uint8_t targetNode);
+    static Message createConfigResponse(const NodeConfig &config);
+    static Message createRaw(const uint8_t *frame, size_t len);
+    static Message createChannelSwitch(uint32_t frequency, ChannelSwitchStage stage);
+    static Message createRateSwitch(uint8_t spreadingFactor, ChannelSwitchStage stage);
+    static Message createListenSchedule(const ListenScheduleMessage &schedule);
+    static Message createTrackStatus(uint8_t lastSeq, uint32_t bitmap);
+    static Message createStatus(BridgeStatus code, uint8_t seq);
+    static Message createDecodeFailure(DecodeFailureReason reason, const uint8_t *frame, size_t len, size_t frameLen);
+    static Message createDeliveryReport(DeliveryOutcome outcome, uint8_t seq);
+    static Message createLinkQuality(uint8_t seq, int16_t rssi, float snr);
 
-    /// Serializes the message into the provided buffer.
+    /// Serializes the message into the provided buffer, followed by its CRC-8.
     /// Returns the number of bytes written on success, or -1 on failure.
     int serialize(uint8_t *buf, size_t bufSize) const;
 
-    /// Deserializes a message from the provided buffer.
-    /// Returns true on success, false on failure.
+    /// Deserializes a message from the provided buffer, CRC-8 last.
+    /// Returns true on success, false on failure (including a CRC mismatch, see crcMatches()).
     bool deserialize(const uint8_t *buf, size_t len);
+
+    /// Serializes like serialize(), but a Text has everything after its type and seq
+    /// encrypted with AES-128-CTR under key (PAYLOAD_KEY_LEN bytes). The counter
+    /// block starts with type, seq and salt; the salt goes on air right after the
+    /// seq. The CRC-8 covers the encrypted bytes. Other types stay in the clear.
+    int serializeEncrypted(uint8_t *buf, size_t bufSize, const uint8_t *key, uint8_t salt) const;
+
+    /// Counterpart of serializeEncrypted(). There is no MAC: under a wrong key a
+    /// Text decrypts to garbage, which fails here only if its length fields disagree.
+    bool deserializeEncrypted(const uint8_t *buf, size_t len, const uint8_t *key);
+
+    /// Whether serializeEncrypted() encrypts messages of this type
+    static bool isEncryptable(uint8_t typeByte);
+
+    /// Serializes like serialize(), with an AUTH_TAG_LEN byte tag between the last
+    /// field and the CRC-8: the first bytes (little-endian) of SipHash-2-4 under key
+    /// (AUTH_KEY_LEN bytes) over everything before it. Any type can be authenticated.
+    int serializeAuthenticated(uint8_t *buf, size_t bufSize, const uint8_t *key) const;
+
+    /// Counterpart of serializeAuthenticated(). False if the CRC-8 or the tag
+    /// doesn't match (auth failed: forged, or sent under another key) - check
+    /// with stripAuthTag() to tell them apart.
+    bool deserializeAuthenticated(const uint8_t *buf, size_t len, const uint8_t *key);
+
+    /// Add an auth tag to a serialized frame (CRC-8 last, recomputed), e.g. one from serializeEncrypted()
+    /// @return The new frame length, -1 if bufSize has no room for the tag.
+    static int addAuthTag(uint8_t *frame, size_t len, size_t bufSize, const uint8_t *key);
+
+    /// Check and remove the auth tag of a frame in place (CRC-8 last, recomputed)
+    /// @return The frame length without the tag, -1 if the CRC-8 or the tag doesn't match.
+    static int stripAuthTag(uint8_t *frame, size_t len, const uint8_t *key);
+
+    /// Serializes like serialize(), followed by parityLen Reed-Solomon parity bytes
+    /// (at most MAX_FEC_PARITY_LEN) over the whole message, CRC-8 included, so that
+    /// up to parityLen / 2 damaged bytes anywhere in the frame can be repaired.
+    int serializeFec(uint8_t *buf, size_t bufSize, uint8_t parityLen) const;
+
+    /// Counterpart of serializeFec(): repairs the frame before checking its CRC-8.
+    /// False if it has more damaged bytes than the parity can repair, or doesn't decode.
+    bool deserializeFec(const uint8_t *buf, size_t len, uint8_t parityLen);
+
+    /// Add FEC parity to a serialized frame, e.g. one from addAuthTag()
+    /// @return The new frame length, -1 if bufSize has no room for the parity.
+    static int addFec(uint8_t *frame, size_t len, size_t bufSize, uint8_t parityLen);
+
+    /// Repair a frame with its FEC parity in place and remove the parity
+    /// @return The frame length without the parity, -1 if the frame is too damaged
+    ///         (or too short) to repair. The CRC-8 is not checked.
+    static int correctFec(uint8_t *frame, size_t len, uint8_t parityLen);
+
+    /// Whether the last byte of a serialized message is the CRC-8 of the bytes before it
+    static bool crcMatches(const uint8_t *buf, size_t len);
+
+    /// Delivery priority (Critical for SOS, Normal for every other type except Text)
+    MessagePriority priority() const;
+
+    /// Transmit order when queued for LoRa: ACK > SOS > GPS > text, then by priority()
+    uint8_t sendPriority() const;
+
+private:
+    /// The message without its CRC
+    int serializeBody(uint8_t *buf, size_t bufSize) const;
+    bool deserializeBody(const uint8_t *buf, size_t len);
+};
+
+/// CRC-8 closing every serialized message: polynomial 0x07 (x^8 + x^2 + x + 1),
+/// initial value 0x00, no reflection, no final XOR (CRC-8/SMBUS, check value 0xF4
+/// for "123456789"). Catches every 1- and 2-bit error and every burst of up to 8
+/// bits in a frame of this size, which LoRa's own payload CRC would otherwise
+/// have to (LoRaManager turns it off).
+uint8_t crc8(const uint8_t *data, size_t len);
+
+/// Encoding of one field on the wire (multi-byte integers are little-endian, see writeU16())
+enum class FieldEncoding : uint8_t
+{
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    Bytes,     // Opaque bytes
+    PackedText // 6-bit packed characters, see pack_text()
+};
+
+/// Size of a field whose length is carried by an earlier field (FieldSpec::lengthField)
+const uint8_t FIELD_VARIABLE = 0;
+
+/// One field of a message's wire layout
+struct FieldSpec
+{
+    const char *name;
+    uint8_t offset;          // Bytes from the start of the message, or from the end of the variable field (afterVariable)
+    uint8_t size;            // Bytes, or FIELD_VARIABLE
+    FieldEncoding encoding;
+    const char *lengthField; // Field giving the byte count of a FIELD_VARIABLE field, nullptr otherwise
+    bool afterVariable;      // Offset counts from the end of the preceding variable field
+    bool optional;           // Only present under the condition given in meaning
+    const char *meaning;
+};
+
+/// Wire layout of one message type: its fields in order
+struct MessageLayout
+{
+    MessageType type;
+    const char *name;
+    const FieldSpec *fields;
+    uint8_t fieldCount;
 };
 
+/// Wire layouts of every message type, the reference for implementations in
+/// other languages (verified against serialize() by the native protocol tests).
+/// Every message ends with a CRC-8 byte after its last field, see crc8().
+extern const MessageLayout MESSAGE_LAYOUTS[];
+extern const size_t MESSAGE_LAYOUT_COUNT;
+
+/// Wire layout of a message type byte, nullptr for unknown types
+const MessageLayout *messageLayout(uint8_t typeByte);
+
+/// Whether a type byte belongs to a message type this firmware knows.
+/// A frame that fails to deserialize with an unknown type byte likely comes from
+/// newer firmware; with a known one it is corrupt.
+bool isKnownMessageType(uint8_t typeByte);
+
+/// Position-only update as it goes on air: a GpsDelta against reference if there is
+/// one, fewer than resyncEvery deltas were sent against it and both offsets fit an
+/// i16 (about 3.6 km of latitude); the update unchanged otherwise. Only Normal
+/// priority position-only texts without GPS fields or hop ACK flag are ever
+/// turned into deltas. Doesn't change reference, see rememberGpsPosition().
+Message encodeGpsPosition(const Message &update, const GpsReference &reference, uint8_t resyncEvery);
+
+/// Sender side: account for a position update once it was sent as encodeGpsPosition()
+/// returned it - a full one becomes the reference, a delta counts towards the resync.
+void rememberGpsPosition(GpsReference &reference, const Message &sent);
+
+/// Receiver side: a full position-only text becomes the reference (unless it is older)
+/// and comes back as is; a GpsDelta against the reference comes back as the full
+/// text it stands for. False for a delta whose reference wasn't received - there
+/// is nothing to add its offsets to until the sender's next full update.
+bool decodeGpsPosition(const Message &received, GpsReference &reference, Message &position);
+
 /// Convert a character to its 6-bit encoded value
-/// Automatically converts lowercase to uppercase
+/// Automatically converts lowercase to uppercase; -1 for the shift code's character
 int char_to_6bit(char ch);
 
 /// Convert a 6-bit value back to a character
 char sixbit_to_char(uint8_t val);
 
-/// Pack text into 6-bit encoded bytes
+/// Number of 6-bit codes a text packs into, shift codes included
+/// (at most MAX_TEXT_LENGTH characters). Returns -1 on error.
+int text_code_count(const char *text);
+
+/// Pack text (at most MAX_TEXT_LENGTH characters) into 6-bit encoded bytes
+/// With a shift code, shifts are placed so the text needs the fewest codes.
 /// Returns the number of bytes written, or -1 on error
 int pack_text(const char *text, uint8_t *output, size_t maxLen);
 
-/// Unpack 6-bit encoded bytes back to text
+/// Unpack codeCount 6-bit codes back to text, applying shift codes
 /// Returns true on success, false on error
-bool unpack_text(const uint8_t *packed, size_t packedLen, uint8_t charCount, char *output, size_t maxOutputLen);
+bool unpack_text(const uint8_t *packed, size_t packedLen, uint8_t codeCount, char *output, size_t maxOutputLen);
 
 #endif // PROTOCOL_H
@@ -0,0 +1,243 @@
+#ifndef REED_SOLOMON_H
+#define REED_SOLOMON_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstddef>
+#include <cstdint>
+#endif
+
+/// Most parity bytes rsEncode() computes; rsCorrect() repairs up to half as many byte errors
+const size_t RS_MAX_PARITY = 16;
+
+/// Product in GF(2^8) with the field polynomial x^8 + x^4 + x^3 + x^2 + 1 (0x11D).
+/// Bit by bit rather than by log tables: frames are short and the tables would cost 512 bytes of RAM.
+inline uint8_t gfMul(uint8_t a, uint8_t b)
+{
+    uint8_t product = 0;
+    while (b != 0)
+    {
+        if (b & 1)
+        {
+            product ^= a;
+        }
+        a = (a & 0x80) ? static_cast<uint8_t>((a << 1) ^ 0x1D) : static_cast<uint8_t>(a << 1);
+        b >>= 1;
+    }
+    return product;
+}
+
+/// a to the power n in GF(2^8)
+inline uint8_t gfPow(uint8_t a, unsigned n)
+{
+    uint8_t result = 1;
+    while (n != 0)
+    {
+        if (n & 1)
+        {
+            result = gfMul(result, a);
+        }
+        a = gfMul(a, a);
+        n >>= 1;
+    }
+    return result;
+}
+
+/// Multiplicative inverse of a nonzero element (a^254, since a^255 = 1)
+inline uint8_t gfInv(uint8_t a)
+{
+    return gfPow(a, 254);
+}
+
+/// Value at x of the codeword polynomial, first byte the highest power
+inline uint8_t rsEvaluate(const uint8_t *codeword, size_t len, uint8_t x)
+{
+    uint8_t value = 0;
+    for (size_t i = 0; i < len; i++)
+    {
+        value = gfMul(value, x) ^ codeword[i];
+    }
+    return value;
+}
+
+/**
+ * Systematic Reed-Solomon parity over GF(2^8) with generator roots
+ * 2^0 ... 2^(parityLen - 1), used for the FEC of frames on air (see Message::addFec())
+ * @param len Data bytes; data and parity together must not exceed 255.
+ * @param parityLen At most RS_MAX_PARITY.
+ */
+inline void rsEncode(const uint8_t *data, size_t len, uint8_t *parity, size_t parityLen)
+{
+    // Generator polynomial, lowest power first
+    uint8_t generator[RS_MAX_PARITY + 1] = {1};
+    for (size_t i = 0; i < parityLen; i++)
+    {
+        uint8_t root = gfPow(2, i);
+        for (size_t k = i + 1; k > 0; k--)
+        {
+            generator[k] = generator[k - 1] ^ gfMul(generator[k], root);
+        }
+        generator[0] = gfMul(generator[0], root);
+    }
+
+    // Remainder of data * x^parityLen divided by the generator, highest power first
+    for (size_t i = 0; i < parityLen; i++)
+    {
+        parity[i] = 0;
+    }
+    for (size_t i = 0; i < len; i++)
+    {
+        uint8_t feedback = data[i] ^ parity[0];
+        for (size_t k = 0; k + 1 < parityLen; k++)
+        {
+            parity[k] = parity[k + 1] ^ gfMul(feedback, generator[parityLen - 1 - k]);
+        }
+        parity[parityLen - 1] = gfMul(feedback, generator[0]);
+    }
+}
+
+/**
+ * Repair a codeword from rsEncode() (data followed by its parity) in place:
+ * syndromes, Berlekamp-Massey for the error locator, Chien search for the error
+ * positions and Forney for the error values.
+ * @return False if it has more than parityLen / 2 byte errors, leaving it unchanged.
+ *         More errors than that can also pass for a few others and be "repaired"
+ *         into another codeword, which the CRC-8 of the frame then rejects.
+ */
+inline bool rsCorrect(uint8_t *codeword, size_t len, size_t parityLen)
+{
+    uint8_t syndromes[RS_MAX_PARITY];
+    bool clean = true;
+    for (size_t i = 0; i < parityLen; i++)
+    {
+        syndromes[i] = rsEvaluate(codeword, len, gfPow(2, i));
+        clean = clean && syndromes[i] == 0;
+    }
+    if (clean)
+    {
+        return true;
+    }
+
+    // Error locator, lowest power first
+    uint8_t locator[RS_MAX_PARITY + 1] = {1};
+    uint8_t previous[RS_MAX_PARITY + 1] = {1};
+    size_t errors = 0;
+    size_t shift = 1;
+    uint8_t lastDiscrepancy = 1;
+    for (size_t n = 0; n < parityLen; n++)
+    {
+        uint8_t discrepancy = syndromes[n];
+        for (size_t i = 1; i <= errors; i++)
+        {
+            discrepancy ^= gfMul(locator[i], syndromes[n - i]);
+        }
+        if (discrepancy == 0)
+        {
+            shift++;
+            continue;
+        }
+        uint8_t scale = gfMul(discrepancy, gfInv(lastDiscrepancy));
+        uint8_t saved[RS_MAX_PARITY + 1];
+        for (size_t i = 0; i <= parityLen; i++)
+        {
+            saved[i] = locator[i];
+        }
+        for (size_t i = 0; i + shift <= parityLen; i++)
+        {
+            locator[i + shift] ^= gfMul(scale, previous[i]);
+        }
+        if (2 * errors <= n)
+        {
+            errors = n + 1 - errors;
+            for (size_t i = 0; i <= parityLen; i++)
+            {
+                previous[i] = saved[i];
+            }
+            lastDiscrepancy = discrepancy;
+            shift = 1;
+        }
+        else
+        {
+            shift++;
+        }
+    }
+    if (2 * errors > parityLen)
+    {
+        return false;
+    }
+
+    // Error evaluator: syndromes times locator, mod x^parityLen
+    uint8_t evaluator[RS_MAX_PARITY] = {0};
+    for (size_t i = 0; i < parityLen; i++)
+    {
+        for (size_t k = 0; k <= i && k <= errors; k++)
+        {
+            evaluator[i] ^= gfMul(syndromes[i - k], locator[k]);
+        }
+    }
+
+    // Byte i is the coefficient of x^(len - 1 - i): an error there makes
+    // 2^-(len - 1 - i) a root of the locator
+    uint8_t fixed[RS_MAX_PARITY];
+    size_t positions[RS_MAX_PARITY];
+    size_t found = 0;
+    for (size_t i = 0; i < len; i++)
+    {
+        uint8_t location = gfPow(2, len - 1 - i);
+        uint8_t inverse = gfInv(location);
+        uint8_t value = 0;
+        uint8_t derivative = 0;
+        for (size_t k = errors + 1; k > 0; k--)
+        {
+            value = gfMul(value, inverse) ^ locator[k - 1];
+        }
+        if (value != 0)
+        {
+            continue;
+        }
+        // Formal derivative of the locator: only odd powers survive
+        for (size_t k = 1; k <= errors; k += 2)
+        {
+            derivative ^= gfMul(locator[k], gfPow(inverse, k - 1));
+        }
+        if (derivative == 0 || found == errors)
+        {
+            return false;
+        }
+        uint8_t omega = 0;
+        for (size_t k = parityLen; k > 0; k--)
+        {
+            omega = gfMul(omega, inverse) ^ evaluator[k - 1];
+        }
+        positions[found] = i;
+        fixed[found] = codeword[i] ^ gfMul(location, gfMul(omega, gfInv(derivative)));
+        found++;
+    }
+    if (found != errors)
+    {
+        return false; // The locator has roots outside the codeword - too many errors
+    }
+
+    uint8_t original[RS_MAX_PARITY];
+    for (size_t k = 0; k < found; k++)
+    {
+        original[k] = codeword[positions[k]];
+        codeword[positions[k]] = fixed[k];
+    }
+    for (size_t i = 0; i < parityLen; i++)
+    {
+        if (rsEvaluate(codeword, len, gfPow(2, i)) != 0)
+        {
+            for (size_t k = 0; k < found; k++)
+            {
+                codeword[positions[k]] = original[k];
+            }
+            return false;
+        }
+    }
+    return true;
+}
+
+#endif // REED_SOLOMON_H
@@ -0,0 +1,92 @@
+#ifndef SIPHASH_H
+#define SIPHASH_H
+
+#ifdef ARDUINO
+#include <Arduino.h>
+#else
+// Host builds (native unit tests)
+#include <cstddef>
+#include <cstdint>
+#endif
+
+/// Key length of sipHash24()
+const size_t SIPHASH_KEY_LEN = 16;
+
+/// Little-endian u64 at p
+inline uint64_t sipHashReadU64(const uint8_t *p)
+{
+    uint64_t value = 0;
+    for (int i = 7; i >= 0; i--)
+    {
+        value = (value << 8) | p[i];
+    }
+    return value;
+}
+
+inline uint64_t sipHashRotl(uint64_t x, int b)
+{
+    return (x << b) | (x >> (64 - b));
+}
+
+inline void sipRound(uint64_t &v0, uint64_t &v1, uint64_t &v2, uint64_t &v3)
+{
+    v0 += v1;
+    v1 = sipHashRotl(v1, 13);
+    v1 ^= v0;
+    v0 = sipHashRotl(v0, 32);
+    v2 += v3;
+    v3 = sipHashRotl(v3, 16);
+    v3 ^= v2;
+    v0 += v3;
+    v3 = sipHashRotl(v3, 21);
+    v3 ^= v0;
+    v2 += v1;
+    v1 = sipHashRotl(v1, 17);
+    v1 ^= v2;
+    v2 = sipHashRotl(v2, 32);
+}
+
+/**
+ * SipHash-2-4 (Aumasson and Bernstein): a keyed hash that is cheap on short
+ * inputs, used for the auth tag of frames on air (see Message::serializeAuthenticated())
+ * @param key SIPHASH_KEY_LEN bytes.
+ */
+inline uint64_t sipHash24(const uint8_t *key, const uint8_t *data, size_t len)
+{
+    uint64_t k0 = sipHashReadU64(key);
+    uint64_t k1 = sipHashReadU64(key + 8);
+    uint64_t v0 = 0x736f6d6570736575ULL ^ k0;
+    uint64_t v1 = 0x646f72616e646f6dULL ^ k1;
+    uint64_t v2 = 0x6c7967656e657261ULL ^ k0;
+    uint64_t v3 = 0x7465646279746573ULL ^ k1;
+
+    size_t whole = len - len % 8;
+    for (size_t i = 0; i < whole; i += 8)
+    {
+        uint64_t m = sipHashReadU64(data + i);
+        v3 ^= m;
+        sipRound(v0, v1, v2, v3);
+        sipRound(v0, v1, v2, v3);
+        v0 ^= m;
+    }
+
+    // Last block: the remaining bytes, with the length in the top byte
+    uint64_t last = static_cast<uint64_t>(len & 0xFF) << 56;
+    for (size_t i = 0; i < len % 8; i++)
+    {
+        last |= static_cast<uint64_t>(data[whole + i]) << (8 * i);
+    }
+    v3 ^= last;
+    sipRound(v0, v1, v2, v3);
+    sipRound(v0, v1, v2, v3);
+    v0 ^= last;
+
+    v2 ^= 0xFF;
+    for (int i = 0; i < 4; i++)
+    {
+        sipRound(v0, v1, v2, v3);
+    }
+    return v0 ^ v1 ^ v2 ^ v3;
+}
+
+#endif // SIPHASH_H
@@ -0,0 +1,103 @@
+#!/usr/bin/env python3
+"""Rapid BLE connect/disconnect stress test for the ESP32 LoRa-BLE bridge.
+
+Mimics Android connection churn (pairing retries, app restarts): connects,
+optionally holds the link briefly, disconnects, and repeats. Afterwards it
+makes one normal connection and requests diagnostics over the control
+characteristic to check the bridge is still healthy.
+
+Requires: pip install bleak
+
+Usage:
+    python3 tools/ble_churn_test.py [--name ESP32S3-LoRa] [--cycles 50] [--hold 0.0]
+
+Watch the ESP32 serial log while this runs: advertising restarts should be
+spaced at least BLE_ADVERTISING_RESTART_MIN_INTERVAL_MS apart and the free
+heap (if logged) should stay flat.
+"""
+
+import argparse
+import asyncio
+import struct
+import sys
+import time
+
+from bleak import BleakClient, BleakScanner
+
+CONTROL_CHAR_UUID = "0000567a-0000-1000-8000-00805f9b34fb"
+DIAGNOSTICS_OPCODE = 0x01
+DIAGNOSTICS_RESPONSE = 0x81
+FRAGMENT_START = 0xF1
+FRAGMENT_CONTINUE = 0xF2
+
+
+async def find_device(name, timeout):
+    device = await BleakScanner.find_device_by_name(name, timeout=timeout)
+    if device is None:
+        sys.exit(f"Device '{name}' not found")
+    return device
+
+
+async def churn(args):
+    failures = 0
+    for cycle in range(1, args.cycles + 1):
+        start = time.monotonic()
+        try:
+            device = await find_device(args.name, args.scan_timeout)
+            async with BleakClient(device, timeout=args.connect_timeout):
+                if args.hold > 0:
+                    await asyncio.sleep(args.hold)
+            print(f"cycle {cycle}: ok ({time.monotonic() - start:.2f}s)")
+        except Exception as e:  # noqa: BLE001 - report and keep cycling
+            failures += 1
+            print(f"cycle {cycle}: FAILED ({e})")
+    return failures
+
+
+async def request_diagnostics(args):
+    device = await find_device(args.name, args.scan_timeout)
+    response = bytearray()
+    done = asyncio.Event()
+    expected = None
+
+    def on_notify(_, data):
+        nonlocal expected
+        if data[0] == FRAGMENT_START:
+            expected = data[1]
+            response.extend(data[2:])
+        elif data[0] == FRAGMENT_CONTINUE:
+            response.extend(data[1:])
+        else:
+            response.extend(data)
+        if expected is None or len(response) >= expected:
+            done.set()
+
+    async with BleakClient(device, timeout=args.connect_timeout) as client:
+        await client.start_notify(CONTROL_CHAR_UUID, on_notify)
+        await client.write_gatt_char(CONTROL_CHAR_UUID, bytes([DIAGNOSTICS_OPCODE]), response=True)
+        await asyncio.wait_for(done.wait(), timeout=5)
+
+    if response[0] != DIAGNOSTICS_RESPONSE:
+        sys.exit(f"Unexpected control response: {response.hex()}")
+    major, minor, patch = response[2:5]
+    uptime = struct.unpack_from("<I", response, 28)[0]
+    print(f"bridge healthy: firmware {major}.{minor}.{patch}, uptime {uptime}s")
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--name", default="ESP32S3-LoRa", help="advertised device name")
+    parser.add_argument("--cycles", type=int, default=50, help="connect/disconnect cycles")
+    parser.add_argument("--hold", type=float, default=0.0, help="seconds to stay connected per cycle")
+    parser.add_argument("--scan-timeout", type=float, default=10.0)
+    parser.add_argument("--connect-timeout", type=float, default=10.0)
+    args = parser.parse_args()
+
+    failures = asyncio.run(churn(args))
+    asyncio.run(request_diagnostics(args))
+    print(f"{args.cycles - failures}/{args.cycles} cycles succeeded")
+    sys.exit(1 if failures else 0)
+
+
+if __name__ == "__main__":
+    main()